@@ -255,6 +255,11 @@ typedef struct YSubscription {} YSubscription;
  */
 #define ERR_CUSTOM 9
 
+/**
+ * Error code: decoded payload exceeded maximum allowed nesting depth.
+ */
+#define ERR_DEPTH_LIMIT_EXCEEDED 10
+
 #define YCHANGE_ADD 1
 
 #define YCHANGE_RETAIN 0
@@ -22,10 +22,11 @@ use yrs::undo::EventKind;
 use yrs::updates::decoder::{Decode, DecoderV1};
 use yrs::updates::encoder::{Encode, Encoder, EncoderV1, EncoderV2};
 use yrs::{
-    uuid_v4, Any, Array, ArrayRef, Assoc, BranchID, DeleteSet, GetString, Map, MapRef, Observable,
-    OffsetKind, Options, Origin, Out, Quotable, ReadTxn, Snapshot, StateVector, StickyIndex, Store,
-    SubdocsEvent, SubdocsEventIter, Text, TextRef, Transact, TransactionCleanupEvent, Update, Xml,
-    XmlElementPrelim, XmlElementRef, XmlFragmentRef, XmlTextPrelim, XmlTextRef, ID,
+    uuid_v4, Any, Array, ArrayRef, Assoc, BranchID, ConflictOrder, CounterEvent, DeleteSet,
+    GcPolicy, GetString, Map, MapRef, Observable, OffsetKind, Options, Origin, Out, Quotable,
+    ReadTxn, Snapshot, StateVector, StickyIndex, Store, SubdocsEvent, SubdocsEventIter, Text,
+    TextRef, Transact, TransactionCleanupEvent, Update, Xml, XmlElementPrelim, XmlElementRef,
+    XmlFragmentRef, XmlTextPrelim, XmlTextRef, ID,
 };
 
 /// Flag used by `YInput` and `YOutput` to tag boolean values.
@@ -85,6 +86,9 @@ pub const Y_WEAK_LINK: i8 = 8;
 /// when it's referencing a root type that has not been initalized localy.
 pub const Y_UNDEFINED: i8 = 9;
 
+/// Flag used by `YEvent` and `ytype_kind` to tag content, which is a `YCounter` shared type.
+pub const Y_COUNTER: i8 = 10;
+
 /// Flag used to mark a truthy boolean numbers.
 pub const Y_TRUE: u8 = 1;
 
@@ -312,6 +316,10 @@ impl Into<Options> for YOptions {
             auto_load: if self.auto_load == 0 { false } else { true },
             should_load: if self.should_load == 0 { false } else { true },
             offset_kind: encoding,
+            conflict_order: ConflictOrder::ClientId,
+            gc_policy: GcPolicy::default(),
+            strict_types: false,
+            isolate_observers: false,
         }
     }
 }
@@ -1161,6 +1169,9 @@ pub const ERR_TYPE_MISMATCH: u8 = 8;
 /// Error code: miscallaneous error comming from serde, not covered by other error codes.
 pub const ERR_CUSTOM: u8 = 9;
 
+/// Error code: decoded payload exceeded maximum allowed nesting depth.
+pub const ERR_DEPTH_LIMIT_EXCEEDED: u8 = 10;
+
 fn err_code(e: Error) -> u8 {
     match e {
         Error::InvalidVarInt => ERR_CODE_VAR_INT,
@@ -1170,6 +1181,7 @@ fn err_code(e: Error) -> u8 {
         Error::NotEnoughMemory(_) => ERR_NOT_ENOUGH_MEMORY,
         Error::TypeMismatch(_) => ERR_TYPE_MISMATCH,
         Error::Custom(_) => ERR_CUSTOM,
+        Error::DepthLimitExceeded(_) => ERR_DEPTH_LIMIT_EXCEEDED,
     }
 }
 
@@ -3542,7 +3554,7 @@ pub unsafe extern "C" fn yobserve_deep(
     let state = CallbackState::new(state);
     let branch = ytype.as_mut().unwrap();
     let subscription = branch.observe_deep(move |txn, events| {
-        let events: Vec<_> = events.iter().map(|e| YEvent::new(txn, e)).collect();
+        let events: Vec<_> = events.iter().filter_map(|e| YEvent::new(txn, e)).collect();
         let len = events.len() as u32;
         cb(state.0, len, events.as_ptr());
     });
@@ -3753,6 +3765,7 @@ pub struct YEvent {
     /// - [Y_MAP] for pointers to `YMap` data types.
     /// - [Y_XML_ELEM] for pointers to `YXmlElement` data types.
     /// - [Y_XML_TEXT] for pointers to `YXmlText` data types.
+    /// - [Y_COUNTER] for pointers to `YCounter` data types.
     pub tag: i8,
 
     /// A nested event type, specific for a shared data type that triggered it. Type of an
@@ -3761,8 +3774,8 @@ pub struct YEvent {
 }
 
 impl YEvent {
-    fn new<'doc>(txn: &yrs::TransactionMut<'doc>, e: &Event) -> YEvent {
-        match e {
+    fn new<'doc>(txn: &yrs::TransactionMut<'doc>, e: &Event) -> Option<YEvent> {
+        let event = match e {
             Event::Text(e) => YEvent {
                 tag: Y_TEXT,
                 content: YEventContent {
@@ -3803,7 +3816,16 @@ impl YEvent {
                     weak: YWeakLinkEvent::new(e, txn),
                 },
             },
-        }
+            Event::Counter(e) => YEvent {
+                tag: Y_COUNTER,
+                content: YEventContent {
+                    counter: YCounterEvent::new(e, txn),
+                },
+            },
+            // sets are not exposed through C API
+            Event::Set(_) => return None,
+        };
+        Some(event)
     }
 }
 
@@ -3815,6 +3837,7 @@ pub union YEventContent {
     pub xml_elem: YXmlEvent,
     pub xml_text: YXmlTextEvent,
     pub weak: YWeakLinkEvent,
+    pub counter: YCounterEvent,
 }
 
 /// Event pushed into callbacks registered with `ytext_observe` function. It contains delta of all
@@ -4000,6 +4023,36 @@ impl Deref for YWeakLinkEvent {
     }
 }
 
+/// Event pushed into callbacks registered with `yobserve_deep` function, when a counter
+/// has been changed. It contains all an event changes of the underlying transaction.
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct YCounterEvent {
+    inner: *const c_void,
+    txn: *const yrs::TransactionMut<'static>,
+}
+
+impl YCounterEvent {
+    fn new<'doc>(inner: &CounterEvent, txn: &yrs::TransactionMut<'doc>) -> Self {
+        let inner = inner as *const CounterEvent as *const _;
+        let txn: &yrs::TransactionMut<'static> = unsafe { std::mem::transmute(txn) };
+        let txn = txn as *const _;
+        YCounterEvent { inner, txn }
+    }
+
+    fn txn(&self) -> &yrs::TransactionMut<'static> {
+        unsafe { self.txn.as_ref().unwrap() }
+    }
+}
+
+impl Deref for YCounterEvent {
+    type Target = CounterEvent;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { (self.inner as *const CounterEvent).as_ref().unwrap() }
+    }
+}
+
 /// Returns a pointer to a shared collection, which triggered passed event `e`.
 #[no_mangle]
 pub unsafe extern "C" fn ytext_event_target(e: *const YTextEvent) -> *mut Branch {
@@ -4063,6 +4116,42 @@ pub unsafe extern "C" fn ytext_event_path(
     Box::into_raw(out) as *mut _
 }
 
+/// Returns a pointer to a shared counter, which triggered passed event `e`.
+#[no_mangle]
+pub unsafe extern "C" fn ycounter_event_target(e: *const YCounterEvent) -> *mut Branch {
+    assert!(!e.is_null());
+    let out = (&*e).target().clone();
+    out.into_raw_branch()
+}
+
+/// Returns a total change of a counter value made within bounds of a transaction, which
+/// triggered passed event `e`.
+#[no_mangle]
+pub unsafe extern "C" fn ycounter_event_delta(e: *const YCounterEvent) -> f64 {
+    assert!(!e.is_null());
+    let e = &*e;
+    e.delta(e.txn())
+}
+
+/// Returns a path from a root type down to a current shared collection (which can be obtained using
+/// `ycounter_event_target` function). It can consist of either integer indexes (used by sequence
+/// components) of *char keys (used by map components). `len` output parameter is used to provide
+/// information about length of the path.
+///
+/// Path returned this way should be eventually released using `ypath_destroy`.
+#[no_mangle]
+pub unsafe extern "C" fn ycounter_event_path(
+    e: *const YCounterEvent,
+    len: *mut u32,
+) -> *mut YPathSegment {
+    assert!(!e.is_null());
+    let e = &*e;
+    let path: Vec<_> = e.path().into_iter().map(YPathSegment::from).collect();
+    let out = path.into_boxed_slice();
+    *len = out.len() as u32;
+    Box::into_raw(out) as *mut _
+}
+
 /// Returns a path from a root type down to a current shared collection (which can be obtained using
 /// `ymap_event_target` function). It can consist of either integer indexes (used by sequence
 /// components) of *char keys (used by map components). `len` output parameter is used to provide
@@ -4536,7 +4625,7 @@ impl YUndoEvent {
 
 /// Returns a value informing what kind of Yrs shared collection given `branch` represents.
 /// Returns either 0 when `branch` is null or one of values: `Y_ARRAY`, `Y_TEXT`, `Y_MAP`,
-/// `Y_XML_ELEM`, `Y_XML_TEXT`.
+/// `Y_XML_ELEM`, `Y_XML_TEXT`, `Y_COUNTER`.
 #[no_mangle]
 pub unsafe extern "C" fn ytype_kind(branch: *const Branch) -> i8 {
     if let Some(branch) = branch.as_ref() {
@@ -4550,6 +4639,10 @@ pub unsafe extern "C" fn ytype_kind(branch: *const Branch) -> i8 {
             TypeRef::SubDoc => Y_DOC,
             TypeRef::WeakLink(_) => Y_WEAK_LINK,
             TypeRef::XmlHook => 0,
+            TypeRef::Counter => Y_COUNTER,
+            TypeRef::GSet => 0,
+            TypeRef::TwoPhaseSet => 0,
+            TypeRef::Fixed => 0,
             TypeRef::Undefined => 0,
         }
     } else {
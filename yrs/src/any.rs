@@ -1,4 +1,4 @@
-use crate::encoding::read::{Error, Read};
+use crate::encoding::read::{bounded_len, DecodeLimits, Error, Read};
 use crate::encoding::write::Write;
 use std::cmp::PartialEq;
 use std::collections::HashMap;
@@ -35,6 +35,22 @@ impl Any {
     }
 
     pub fn decode<R: Read>(decoder: &mut R) -> Result<Self, Error> {
+        let limits = decoder.limits().copied();
+        Self::decode_bounded(decoder, limits.as_ref(), 0)
+    }
+
+    /// Decodes [Any] value, validating its nesting depth and collection lengths against given
+    /// `limits`, if they were provided.
+    pub(crate) fn decode_bounded<R: Read>(
+        decoder: &mut R,
+        limits: Option<&DecodeLimits>,
+        depth: usize,
+    ) -> Result<Self, Error> {
+        if let Some(limits) = limits {
+            if depth > limits.max_depth {
+                return Err(Error::DepthLimitExceeded(limits.max_depth));
+            }
+        }
         Ok(match decoder.read_u8()? {
             // CASE 127: undefined
             127 => Any::Undefined,
@@ -60,19 +76,22 @@ impl Any {
             // CASE 118: Map<string,Any>
             118 => {
                 let len: usize = decoder.read_var()?;
-                let mut map = HashMap::with_capacity(len);
+                let mut map = HashMap::new();
+                map.try_reserve(bounded_len(len, decoder.remaining(), limits)?)?;
                 for _ in 0..len {
-                    let key = decoder.read_string()?;
-                    map.insert(key.to_owned(), Any::decode(decoder)?);
+                    let key = decoder.read_string()?.to_owned();
+                    let value = Any::decode_bounded(decoder, limits, depth + 1)?;
+                    map.insert(key, value);
                 }
                 Any::Map(Arc::new(map))
             }
             // CASE 117: Array<Any>
             117 => {
                 let len: usize = decoder.read_var()?;
-                let mut arr = Vec::with_capacity(len);
+                let mut arr = Vec::new();
+                arr.try_reserve(bounded_len(len, decoder.remaining(), limits)?)?;
                 for _ in 0..len {
-                    arr.push(Any::decode(decoder)?);
+                    arr.push(Any::decode_bounded(decoder, limits, depth + 1)?);
                 }
                 Any::Array(Arc::from(arr))
             }
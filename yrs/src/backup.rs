@@ -0,0 +1,187 @@
+//! Incremental backups of a document state.
+//!
+//! Dumping a full document state every time it's backed up gets expensive for large documents.
+//! Instead, a full backup can be made once with [ReadTxn::encode_backup_since] called with an
+//! empty [Snapshot], followed by incremental backups containing only the blocks and deletions
+//! made since the [Snapshot] taken at the time of a previous backup. A document is restored by
+//! applying a full backup followed by incremental ones with [TransactionMut::apply_backup].
+//!
+//! Every backup remembers the state its changes have been made on top of - both inserted and
+//! deleted blocks - so that applying it over a document, which is missing any of the previous
+//! backups, fails instead of silently producing an incomplete document.
+//!
+//! # Example
+//!
+//! ```rust
+//! use yrs::{Doc, GetString, ReadTxn, Snapshot, Text, Transact};
+//!
+//! let doc = Doc::new();
+//! let text = doc.get_or_insert_text("text");
+//! text.push(&mut doc.transact_mut(), "hello");
+//!
+//! // full backup
+//! let full = doc.transact().encode_backup_since(&Snapshot::default());
+//! let snapshot = doc.transact().snapshot();
+//!
+//! // incremental backup of changes made since the full one
+//! text.push(&mut doc.transact_mut(), " world");
+//! let incremental = doc.transact().encode_backup_since(&snapshot);
+//!
+//! let restored = Doc::new();
+//! let text = restored.get_or_insert_text("text");
+//! let mut txn = restored.transact_mut();
+//! txn.apply_backup(&full).unwrap();
+//! txn.apply_backup(&incremental).unwrap();
+//! assert_eq!(text.get_string(&txn), "hello world");
+//! ```
+//!
+//! [ReadTxn::encode_backup_since]: crate::ReadTxn::encode_backup_since
+//! [TransactionMut::apply_backup]: crate::TransactionMut::apply_backup
+
+use crate::encoding::read::{Cursor, Read};
+use crate::encoding::write::Write;
+use crate::updates::decoder::{Decode, DecoderV1};
+use crate::updates::encoder::{Encode, Encoder, EncoderV1};
+use crate::{Error, ReadTxn, Snapshot, Update};
+
+/// Version of a backup binary format.
+const BACKUP_VERSION: u8 = 1;
+
+/// Decoded backup artifact produced by [ReadTxn::encode_backup_since].
+pub(crate) struct Backup {
+    /// Snapshot of a document state, which backed up changes have been made on top of.
+    pub base: Snapshot,
+    /// Changes made since the base state.
+    pub update: Update,
+}
+
+impl Backup {
+    /// Encodes changes made in a document since a given `snapshot` was taken.
+    pub fn encode<T: ReadTxn>(txn: &T, snapshot: &Snapshot) -> Vec<u8> {
+        let update = txn.encode_diff_since_snapshot_v2(snapshot);
+        let mut encoder = EncoderV1::new();
+        encoder.write_u8(BACKUP_VERSION);
+        snapshot.encode(&mut encoder);
+        encoder.write_buf(&update);
+        encoder.to_vec()
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self, Error> {
+        let mut decoder = DecoderV1::new(Cursor::new(data));
+        let version = decoder.read_u8()?;
+        if version != BACKUP_VERSION {
+            return Err(Error::Validation(format!(
+                "unsupported backup version: {}",
+                version
+            )));
+        }
+        let base = Snapshot::decode(&mut decoder)?;
+        let update = Update::decode_v2(decoder.read_buf()?)?;
+        Ok(Backup { base, update })
+    }
+
+    /// Checks if a document state described by its `snapshot` contains all blocks and deletions,
+    /// which changes stored in this backup have been made on top of.
+    pub fn check_base(&self, snapshot: &Snapshot) -> Result<(), Error> {
+        for (client, &clock) in self.base.state_map.iter() {
+            if snapshot.state_map.get(client) < clock {
+                return Err(Error::Validation(format!(
+                    "backup base state is missing: expected client {} clock to be at least {}",
+                    client, clock
+                )));
+            }
+        }
+        let missing = self.base.delete_set.subtract(&snapshot.delete_set);
+        if !missing.is_empty() {
+            return Err(Error::Validation(format!(
+                "backup base state is missing deletions: {}",
+                missing
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::types::ToJson;
+    use crate::{Doc, GetString, Map, Options, ReadTxn, Snapshot, Text, Transact};
+
+    #[test]
+    fn incremental_backups() {
+        let doc = Doc::with_options(Options {
+            client_id: 1,
+            skip_gc: true,
+            ..Options::default()
+        });
+        let text = doc.get_or_insert_text("text");
+        let map = doc.get_or_insert_map("map");
+        text.push(&mut doc.transact_mut(), "hello world");
+        map.insert(&mut doc.transact_mut(), "a", 1);
+
+        let full = doc.transact().encode_backup_since(&Snapshot::default());
+        let s1 = doc.transact().snapshot();
+        text.remove_range(&mut doc.transact_mut(), 5, 6);
+        map.insert(&mut doc.transact_mut(), "b", 2);
+        let b1 = doc.transact().encode_backup_since(&s1);
+        let s2 = doc.transact().snapshot();
+        text.push(&mut doc.transact_mut(), "!");
+        map.remove(&mut doc.transact_mut(), "a");
+        let b2 = doc.transact().encode_backup_since(&s2);
+        let s3 = doc.transact().snapshot();
+        text.push(&mut doc.transact_mut(), "?");
+        let b3 = doc.transact().encode_backup_since(&s3);
+
+        // incremental backups contain only changes made since a previous one
+        assert!(b1.len() < full.len());
+        assert!(b2.len() < full.len());
+
+        let restored = Doc::new();
+        {
+            let mut txn = restored.transact_mut();
+            txn.apply_backup(&full).unwrap();
+            // backups cannot be applied when any of the previous ones is missing
+            assert!(txn.apply_backup(&b2).is_err());
+            txn.apply_backup(&b1).unwrap();
+            txn.apply_backup(&b2).unwrap();
+            txn.apply_backup(&b3).unwrap();
+            assert!(txn.apply_backup(&[0xff]).is_err());
+        }
+        restored.get_or_insert_text("text");
+        restored.get_or_insert_map("map");
+        assert_eq!(
+            restored.to_json(&restored.transact()),
+            doc.to_json(&doc.transact())
+        );
+        assert_eq!(restored.transact().snapshot(), doc.transact().snapshot());
+    }
+
+    #[test]
+    fn missing_deletion_only_backup() {
+        let doc = Doc::with_options(Options {
+            client_id: 1,
+            skip_gc: true,
+            ..Options::default()
+        });
+        let text = doc.get_or_insert_text("text");
+        text.push(&mut doc.transact_mut(), "hello world");
+
+        let full = doc.transact().encode_backup_since(&Snapshot::default());
+        let s1 = doc.transact().snapshot();
+        text.remove_range(&mut doc.transact_mut(), 5, 6);
+        let b1 = doc.transact().encode_backup_since(&s1);
+        let s2 = doc.transact().snapshot();
+        text.push(&mut doc.transact_mut(), "!");
+        let b2 = doc.transact().encode_backup_since(&s2);
+
+        let restored = Doc::new();
+        let text = restored.get_or_insert_text("text");
+        let mut txn = restored.transact_mut();
+        txn.apply_backup(&full).unwrap();
+        // state vector of the base state doesn't change on deletion
+        assert!(txn.apply_backup(&b2).is_err());
+        txn.apply_backup(&b1).unwrap();
+        txn.apply_backup(&b2).unwrap();
+        assert_eq!(text.get_string(&txn), "hello!");
+    }
+}
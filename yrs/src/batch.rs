@@ -0,0 +1,775 @@
+//! Batching of events emitted by shared collections across multiple transactions.
+//!
+//! UI layers often don't need to react to every single transaction - i.e. when a user is typing
+//! or remote updates arrive in quick succession. [EventBatcher] buffers changes observed over
+//! a collection and its nested collections, and emits them together, merging changes made to the
+//! same collection into a single [BatchedEvent].
+
+use crate::branch::{Branch, BranchID};
+use crate::observer::Observer;
+use crate::sync::time::{Clock, Timestamp};
+use crate::types::{Attrs, Change, DeepObservable, Delta, EntryChange, Event, Path};
+use crate::{Any, OffsetKind, Out, Subscription, TransactionMut};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+/// Options used to configure [EventBatcher].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BatchOptions {
+    /// Maximum number of transactions buffered before a batch is emitted. 0 means no limit.
+    /// Default: 0.
+    pub max_transactions: usize,
+    /// Time (in milliseconds) since the last buffered change, after which a batch is emitted.
+    /// Default: 100.
+    pub debounce_millis: Timestamp,
+}
+
+impl Default for BatchOptions {
+    fn default() -> Self {
+        BatchOptions {
+            max_transactions: 0,
+            debounce_millis: 100,
+        }
+    }
+}
+
+/// Changes made to a single collection, accumulated over all transactions within a batch.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchedEvent {
+    /// Collection which has been changed.
+    pub target: Out,
+    /// Path to the changed collection, relative to the collection observed by [EventBatcher].
+    pub path: Path,
+    /// Merged changes of sequence component of a collection.
+    pub delta: BatchedDelta,
+    /// Merged changes of map component (entries of a [MapRef] or attributes of XML nodes) of
+    /// a collection. Entries changed within a batch and then restored to their original state are
+    /// not reported.
+    ///
+    /// [MapRef]: crate::MapRef
+    pub keys: HashMap<Arc<str>, EntryChange>,
+}
+
+/// Merged changes of a sequence component of a collection.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum BatchedDelta {
+    /// Collection has no sequence component, i.e. it's a [MapRef].
+    ///
+    /// [MapRef]: crate::MapRef
+    #[default]
+    None,
+    /// Changes made to [TextRef] or [XmlTextRef].
+    ///
+    /// [TextRef]: crate::TextRef
+    /// [XmlTextRef]: crate::XmlTextRef
+    Text(Vec<Delta>),
+    /// Changes made to elements of an [ArrayRef] or children of XML nodes.
+    ///
+    /// [ArrayRef]: crate::ArrayRef
+    Array(Vec<Change>),
+}
+
+type BatchFn = Box<dyn Fn(&[BatchedEvent]) + Send + Sync + 'static>;
+
+/// Buffers events emitted by a collection and its nested collections over multiple transactions,
+/// and emits them together to callbacks subscribed with [EventBatcher::observe].
+///
+/// A batch is emitted when:
+/// - a number of buffered transactions reaches [BatchOptions::max_transactions],
+/// - a transaction is committed after [BatchOptions::debounce_millis] have passed since the last
+///   buffered change - in that case the batch buffered so far is emitted first,
+/// - [EventBatcher::flush_expired] is called after the debounce window has passed, i.e. from
+///   a timer,
+/// - [EventBatcher::flush] is called.
+///
+/// Changes made to the same collection are merged: deltas of texts and arrays are composed
+/// together, while changes of the same map entries are collapsed into a single one.
+///
+/// # Example
+///
+/// ```rust
+/// use yrs::batch::{BatchOptions, BatchedDelta, EventBatcher};
+/// use yrs::types::Delta;
+/// use yrs::{Doc, Out, Text, Transact};
+/// use std::sync::{Arc, Mutex};
+///
+/// let doc = Doc::new();
+/// let text = doc.get_or_insert_text("text");
+/// let options = BatchOptions { max_transactions: 3, debounce_millis: 100 };
+/// let batcher = EventBatcher::with_clock(&text, options, Arc::new(|| 0));
+/// let batches = Arc::new(Mutex::new(Vec::new()));
+/// let _sub = {
+///     let batches = batches.clone();
+///     batcher.observe(move |events| batches.lock().unwrap().push(events.to_vec()))
+/// };
+///
+/// text.insert(&mut doc.transact_mut(), 0, "hello");
+/// text.insert(&mut doc.transact_mut(), 5, " world");
+/// assert!(batches.lock().unwrap().is_empty());
+///
+/// text.remove_range(&mut doc.transact_mut(), 0, 6);
+/// let batches = batches.lock().unwrap();
+/// assert_eq!(batches.len(), 1);
+/// assert_eq!(
+///     batches[0][0].delta,
+///     BatchedDelta::Text(vec![Delta::Inserted(Out::from("world"), None)])
+/// );
+/// ```
+pub struct EventBatcher {
+    inner: Arc<Inner>,
+    _subscription: Subscription,
+}
+
+struct Inner {
+    options: BatchOptions,
+    clock: Arc<dyn Clock>,
+    state: Mutex<Batch>,
+    observers: Observer<BatchFn>,
+}
+
+#[derive(Default)]
+struct Batch {
+    events: Vec<BatchedEvent>,
+    index: HashMap<BranchID, usize>,
+    transactions: usize,
+    last_change: Timestamp,
+}
+
+impl EventBatcher {
+    /// Creates a new batcher of events emitted by a given `source` collection and its nested
+    /// collections, using OS date time to measure debounce windows.
+    #[cfg(not(target_family = "wasm"))]
+    pub fn new<S: DeepObservable>(source: &S, options: BatchOptions) -> Self {
+        Self::with_clock(source, options, Arc::new(crate::sync::time::SystemClock))
+    }
+
+    /// Creates a new batcher of events emitted by a given `source` collection and its nested
+    /// collections, using a custom clock (returning timestamps in milliseconds) to measure
+    /// debounce windows.
+    pub fn with_clock<S: DeepObservable>(
+        source: &S,
+        options: BatchOptions,
+        clock: Arc<dyn Clock>,
+    ) -> Self {
+        let inner = Arc::new(Inner {
+            options,
+            clock,
+            state: Mutex::new(Batch::default()),
+            observers: Observer::new(),
+        });
+        let weak = Arc::downgrade(&inner);
+        let subscription = source.observe_deep(move |txn, events| {
+            if let Some(inner) = weak.upgrade() {
+                inner.push(txn, events.iter());
+            }
+        });
+        EventBatcher {
+            inner,
+            _subscription: subscription,
+        }
+    }
+
+    /// Subscribes a callback `f`, which is called with all events of a batch, whenever one is
+    /// emitted. This method returns a subscription, which will automatically unsubscribe current
+    /// callback when dropped.
+    pub fn observe<F>(&self, f: F) -> Subscription
+    where
+        F: Fn(&[BatchedEvent]) + Send + Sync + 'static,
+    {
+        self.inner.observers.subscribe(Box::new(f))
+    }
+
+    /// Returns a number of transactions buffered in a current batch.
+    pub fn pending_transactions(&self) -> usize {
+        self.inner.state.lock().unwrap().transactions
+    }
+
+    /// Emits a current batch right away. Returns `false` if there was nothing to emit.
+    pub fn flush(&self) -> bool {
+        let batch = std::mem::take(&mut *self.inner.state.lock().unwrap());
+        self.inner.emit(batch)
+    }
+
+    /// Emits a current batch if the debounce window has passed since its last change. Returns
+    /// `false` if there was nothing to emit.
+    pub fn flush_expired(&self) -> bool {
+        let batch = {
+            let mut state = self.inner.state.lock().unwrap();
+            if !self.inner.is_expired(&state) {
+                return false;
+            }
+            std::mem::take(&mut *state)
+        };
+        self.inner.emit(batch)
+    }
+}
+
+impl Inner {
+    fn is_expired(&self, batch: &Batch) -> bool {
+        let now = self.clock.now();
+        batch.transactions > 0
+            && now.saturating_sub(batch.last_change) >= self.options.debounce_millis
+    }
+
+    fn push<'a, I>(&self, txn: &TransactionMut, events: I)
+    where
+        I: Iterator<Item = &'a Event>,
+    {
+        let kind = txn.store().options.offset_kind;
+        let mut state = self.state.lock().unwrap();
+        let expired = if self.is_expired(&state) {
+            std::mem::take(&mut *state)
+        } else {
+            Batch::default()
+        };
+        for event in events {
+            state.push(txn, event, kind);
+        }
+        state.transactions += 1;
+        state.last_change = self.clock.now();
+        let full = if self.options.max_transactions != 0
+            && state.transactions >= self.options.max_transactions
+        {
+            std::mem::take(&mut *state)
+        } else {
+            Batch::default()
+        };
+        drop(state);
+        self.emit(expired);
+        self.emit(full);
+    }
+
+    fn emit(&self, batch: Batch) -> bool {
+        if batch.transactions == 0 {
+            return false;
+        }
+        let events: Vec<_> = batch.events.into_iter().filter(|e| !e.is_empty()).collect();
+        if !events.is_empty() {
+            self.observers.trigger(|f| f(&events));
+        }
+        true
+    }
+}
+
+impl Batch {
+    fn push(&mut self, txn: &TransactionMut, event: &Event, kind: OffsetKind) {
+        let (id, event) = match BatchedEvent::capture(txn, event) {
+            Some(captured) => captured,
+            None => return,
+        };
+        match self.index.get(&id) {
+            Some(&i) => {
+                let batched = &mut self.events[i];
+                batched.path = event.path;
+                batched.delta =
+                    compose_delta(std::mem::take(&mut batched.delta), event.delta, kind);
+                for (key, change) in event.keys {
+                    let merged = match batched.keys.remove(&key) {
+                        None => Some(change),
+                        Some(prev) => merge_entry(prev, change),
+                    };
+                    if let Some(merged) = merged {
+                        batched.keys.insert(key, merged);
+                    }
+                }
+            }
+            None => {
+                self.index.insert(id, self.events.len());
+                self.events.push(event);
+            }
+        }
+    }
+}
+
+fn branch_id<B: AsRef<Branch>>(target: &B) -> BranchID {
+    target.as_ref().id()
+}
+
+impl BatchedEvent {
+    /// Creates an owned copy of changes described by a given `event`, which can be used after
+    /// its transaction has been committed. Returns `None` for events of weak links.
+    #[cfg(feature = "async")]
+    pub(crate) fn from_event(txn: &TransactionMut, event: &Event) -> Option<Self> {
+        Self::capture(txn, event).map(|(_, event)| event)
+    }
+
+    fn capture(txn: &TransactionMut, event: &Event) -> Option<(BranchID, Self)> {
+        let (id, delta, keys) = match event {
+            Event::Text(e) => (
+                branch_id(e.target()),
+                BatchedDelta::Text(e.delta(txn).to_vec()),
+                HashMap::new(),
+            ),
+            Event::Array(e) => (
+                branch_id(e.target()),
+                BatchedDelta::Array(e.delta(txn).to_vec()),
+                HashMap::new(),
+            ),
+            Event::Map(e) => (
+                branch_id(e.target()),
+                BatchedDelta::None,
+                e.keys(txn).clone(),
+            ),
+            Event::XmlFragment(e) => (
+                branch_id(e.target()),
+                BatchedDelta::Array(e.delta(txn).to_vec()),
+                e.keys(txn).clone(),
+            ),
+            Event::XmlText(e) => (
+                branch_id(e.target()),
+                BatchedDelta::Text(e.delta(txn).to_vec()),
+                e.keys(txn).clone(),
+            ),
+            #[cfg(feature = "weak")]
+            Event::Weak(_) => return None,
+            Event::Counter(_) => return None,
+            Event::Set(_) => return None,
+        };
+        let event = BatchedEvent {
+            target: event.target(),
+            path: event.path(),
+            delta,
+            keys,
+        };
+        Some((id, event))
+    }
+
+    fn is_empty(&self) -> bool {
+        let no_delta = match &self.delta {
+            BatchedDelta::None => true,
+            BatchedDelta::Text(delta) => delta.is_empty(),
+            BatchedDelta::Array(delta) => delta.is_empty(),
+        };
+        no_delta && self.keys.is_empty()
+    }
+}
+
+/// Collapses two consecutive changes of the same map entry into one. Returns `None` if both
+/// changes cancel each other out.
+fn merge_entry(prev: EntryChange, next: EntryChange) -> Option<EntryChange> {
+    match (prev, next) {
+        (EntryChange::Inserted(_), EntryChange::Updated(_, new)) => {
+            Some(EntryChange::Inserted(new))
+        }
+        (EntryChange::Inserted(_), EntryChange::Removed(_)) => None,
+        (EntryChange::Updated(old, _), EntryChange::Updated(_, new))
+        | (EntryChange::Removed(old), EntryChange::Inserted(new)) => {
+            if old == new {
+                None
+            } else {
+                Some(EntryChange::Updated(old, new))
+            }
+        }
+        (EntryChange::Updated(old, _), EntryChange::Removed(_)) => Some(EntryChange::Removed(old)),
+        (_, next) => Some(next),
+    }
+}
+
+fn compose_delta(prev: BatchedDelta, next: BatchedDelta, kind: OffsetKind) -> BatchedDelta {
+    match (prev, next) {
+        (BatchedDelta::Text(a), BatchedDelta::Text(b)) => {
+            let a = a.into_iter().map(Op::from).collect();
+            let b = b.into_iter().map(Op::from).collect();
+            BatchedDelta::Text(compose(a, b, kind).into_iter().map(Delta::from).collect())
+        }
+        (BatchedDelta::Array(a), BatchedDelta::Array(b)) => {
+            let a = a.into_iter().map(Op::from).collect();
+            let b = b.into_iter().map(Op::from).collect();
+            BatchedDelta::Array(compose(a, b, kind).into_iter().map(Change::from).collect())
+        }
+        (_, next) => next,
+    }
+}
+
+/// Content inserted by a sequence operation, which can be split at arbitrary position.
+trait Chunk: Sized {
+    fn len(&self, kind: OffsetKind) -> u32;
+
+    /// Splits current chunk at a given offset, returning its tail.
+    fn split_off(&mut self, at: u32, kind: OffsetKind) -> Self;
+
+    /// Appends `other` to the end of current chunk. Returns `other` back if it's not possible.
+    fn append(&mut self, other: Self) -> Option<Self>;
+}
+
+impl Chunk for Vec<Out> {
+    fn len(&self, _kind: OffsetKind) -> u32 {
+        Vec::len(self) as u32
+    }
+
+    fn split_off(&mut self, at: u32, _kind: OffsetKind) -> Self {
+        Vec::split_off(self, at as usize)
+    }
+
+    fn append(&mut self, mut other: Self) -> Option<Self> {
+        Vec::append(self, &mut other);
+        None
+    }
+}
+
+impl Chunk for Out {
+    fn len(&self, kind: OffsetKind) -> u32 {
+        match self {
+            Out::Any(Any::String(s)) => match kind {
+                OffsetKind::Bytes => s.len() as u32,
+                OffsetKind::Utf16 => s.encode_utf16().count() as u32,
+            },
+            _ => 1,
+        }
+    }
+
+    fn split_off(&mut self, at: u32, kind: OffsetKind) -> Self {
+        match self {
+            Out::Any(Any::String(s)) => {
+                let at = match kind {
+                    OffsetKind::Bytes => at as usize,
+                    OffsetKind::Utf16 => {
+                        let mut units = 0;
+                        s.char_indices()
+                            .find(|(_, c)| {
+                                let found = units >= at;
+                                units += c.len_utf16() as u32;
+                                found
+                            })
+                            .map(|(i, _)| i)
+                            .unwrap_or(s.len())
+                    }
+                };
+                let tail = Out::Any(Any::from(&s[at..]));
+                *self = Out::Any(Any::from(&s[..at]));
+                tail
+            }
+            // embeds have length of 1, so they are never split
+            _ => Out::Any(Any::from("")),
+        }
+    }
+
+    fn append(&mut self, other: Self) -> Option<Self> {
+        match (&*self, other) {
+            (Out::Any(Any::String(a)), Out::Any(Any::String(b))) => {
+                let mut s = a.to_string();
+                s.push_str(&b);
+                *self = Out::Any(Any::from(s));
+                None
+            }
+            (_, other) => Some(other),
+        }
+    }
+}
+
+/// Common representation of [Delta] and [Change] used for composing them.
+#[derive(Debug)]
+enum Op<T> {
+    Insert(T, Option<Box<Attrs>>),
+    Delete(u32),
+    Retain(u32, Option<Box<Attrs>>),
+}
+
+impl From<Delta> for Op<Out> {
+    fn from(delta: Delta) -> Self {
+        match delta {
+            Delta::Inserted(value, attrs) => Op::Insert(value, attrs),
+            Delta::Deleted(len) => Op::Delete(len),
+            Delta::Retain(len, attrs) => Op::Retain(len, attrs),
+        }
+    }
+}
+
+impl From<Op<Out>> for Delta {
+    fn from(op: Op<Out>) -> Self {
+        match op {
+            Op::Insert(value, attrs) => Delta::Inserted(value, attrs),
+            Op::Delete(len) => Delta::Deleted(len),
+            Op::Retain(len, attrs) => Delta::Retain(len, attrs),
+        }
+    }
+}
+
+impl From<Change> for Op<Vec<Out>> {
+    fn from(change: Change) -> Self {
+        match change {
+            Change::Added(values) => Op::Insert(values, None),
+            Change::Removed(len) => Op::Delete(len),
+            Change::Retain(len) => Op::Retain(len, None),
+        }
+    }
+}
+
+impl From<Op<Vec<Out>>> for Change {
+    fn from(op: Op<Vec<Out>>) -> Self {
+        match op {
+            Op::Insert(values, _) => Change::Added(values),
+            Op::Delete(len) => Change::Removed(len),
+            Op::Retain(len, _) => Change::Retain(len),
+        }
+    }
+}
+
+/// Composes two consecutive sequence deltas `a` and `b` into a single one, which has the same
+/// effect as applying `a` followed by `b`.
+fn compose<T: Chunk>(a: Vec<Op<T>>, b: Vec<Op<T>>, kind: OffsetKind) -> Vec<Op<T>> {
+    let mut a = VecDeque::from(a);
+    let mut result = Vec::new();
+    for op in b {
+        match op {
+            Op::Insert(value, attrs) => push(&mut result, Op::Insert(value, attrs)),
+            Op::Retain(mut len, attrs) => {
+                while len > 0 {
+                    match take(&mut a, len, kind) {
+                        None => {
+                            push(&mut result, Op::Retain(len, attrs));
+                            break;
+                        }
+                        Some(Op::Delete(n)) => push(&mut result, Op::Delete(n)),
+                        Some(Op::Insert(value, prev)) => {
+                            len -= value.len(kind);
+                            let attrs = merge_attrs(prev, &attrs, false);
+                            push(&mut result, Op::Insert(value, attrs));
+                        }
+                        Some(Op::Retain(n, prev)) => {
+                            len -= n;
+                            let attrs = merge_attrs(prev, &attrs, true);
+                            push(&mut result, Op::Retain(n, attrs));
+                        }
+                    }
+                }
+            }
+            Op::Delete(mut len) => {
+                while len > 0 {
+                    match take(&mut a, len, kind) {
+                        None => {
+                            push(&mut result, Op::Delete(len));
+                            break;
+                        }
+                        Some(Op::Delete(n)) => push(&mut result, Op::Delete(n)),
+                        // content inserted and deleted within the same batch is skipped entirely
+                        Some(Op::Insert(value, _)) => len -= value.len(kind),
+                        Some(Op::Retain(n, _)) => {
+                            len -= n;
+                            push(&mut result, Op::Delete(n));
+                        }
+                    }
+                }
+            }
+        }
+    }
+    for op in a {
+        push(&mut result, op);
+    }
+    while let Some(Op::Retain(_, None)) = result.last() {
+        result.pop();
+    }
+    result
+}
+
+/// Takes the next operation from `ops`, splitting it if it spans over more than `len` units.
+/// Deletions are always returned whole, since they don't take any space in the sequence.
+fn take<T: Chunk>(ops: &mut VecDeque<Op<T>>, len: u32, kind: OffsetKind) -> Option<Op<T>> {
+    let op = ops.pop_front()?;
+    match op {
+        Op::Insert(mut value, attrs) if value.len(kind) > len => {
+            let tail = value.split_off(len, kind);
+            ops.push_front(Op::Insert(tail, attrs.clone()));
+            Some(Op::Insert(value, attrs))
+        }
+        Op::Retain(n, attrs) if n > len => {
+            ops.push_front(Op::Retain(n - len, attrs.clone()));
+            Some(Op::Retain(len, attrs))
+        }
+        op => Some(op),
+    }
+}
+
+/// Appends `op` to `ops`, merging it with the last operation if possible.
+fn push<T: Chunk>(ops: &mut Vec<Op<T>>, op: Op<T>) {
+    let op = match (ops.last_mut(), op) {
+        (Some(Op::Delete(a)), Op::Delete(b)) => {
+            *a += b;
+            return;
+        }
+        (Some(Op::Retain(a, x)), Op::Retain(b, y)) if *x == y => {
+            *a += b;
+            return;
+        }
+        (Some(Op::Insert(a, x)), Op::Insert(b, y)) if *x == y => match a.append(b) {
+            None => return,
+            Some(b) => Op::Insert(b, y),
+        },
+        (_, op) => op,
+    };
+    ops.push(op);
+}
+
+/// Applies formatting `update` on top of `base` attributes. When `keep_nulls` is set, null
+/// values (which mark removed attributes) are kept, otherwise they are removed.
+fn merge_attrs(
+    base: Option<Box<Attrs>>,
+    update: &Option<Box<Attrs>>,
+    keep_nulls: bool,
+) -> Option<Box<Attrs>> {
+    let update = match update {
+        None => return base,
+        Some(update) => update,
+    };
+    let mut attrs = base.unwrap_or_default();
+    for (key, value) in update.iter() {
+        attrs.insert(key.clone(), value.clone());
+    }
+    if !keep_nulls {
+        attrs.retain(|_, value| *value != Any::Null);
+    }
+    if attrs.is_empty() {
+        None
+    } else {
+        Some(attrs)
+    }
+}
+
+impl std::fmt::Debug for EventBatcher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = self.inner.state.lock().unwrap();
+        f.debug_struct("EventBatcher")
+            .field("options", &self.inner.options)
+            .field("pending_transactions", &state.transactions)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::batch::{BatchOptions, BatchedDelta, BatchedEvent, EventBatcher};
+    use crate::types::{Change, Delta, EntryChange, Path, PathSegment};
+    use crate::{
+        Any, Array, ArrayPrelim, Doc, Map, MapPrelim, MapRef, Out, Subscription, Text, Transact,
+    };
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    fn collect(batcher: &EventBatcher) -> (Arc<Mutex<Vec<Vec<BatchedEvent>>>>, Subscription) {
+        let batches = Arc::new(Mutex::new(Vec::new()));
+        let sub = {
+            let batches = batches.clone();
+            batcher.observe(move |events| batches.lock().unwrap().push(events.to_vec()))
+        };
+        (batches, sub)
+    }
+
+    #[test]
+    fn merge_text_deltas() {
+        let doc = Doc::with_client_id(1);
+        let text = doc.get_or_insert_text("text");
+        let options = BatchOptions {
+            max_transactions: 0,
+            debounce_millis: 100,
+        };
+        let batcher = EventBatcher::with_clock(&text, options, Arc::new(|| 0));
+        let (batches, _sub) = collect(&batcher);
+
+        text.insert(&mut doc.transact_mut(), 0, "hello world");
+        text.insert(&mut doc.transact_mut(), 5, ",");
+        text.format(
+            &mut doc.transact_mut(),
+            0,
+            5,
+            HashMap::from([("bold".into(), Any::Bool(true))]),
+        );
+        text.remove_range(&mut doc.transact_mut(), 6, 6);
+        assert_eq!(batcher.pending_transactions(), 4);
+        assert!(batches.lock().unwrap().is_empty());
+
+        assert!(batcher.flush());
+        assert!(!batcher.flush());
+        let batches = batches.lock().unwrap();
+        assert_eq!(batches.len(), 1);
+        let bold = Some(Box::new(HashMap::from([("bold".into(), Any::Bool(true))])));
+        assert_eq!(
+            batches[0][0].delta,
+            BatchedDelta::Text(vec![
+                Delta::Inserted(Out::from("hello"), bold),
+                Delta::Inserted(Out::from(","), None),
+            ])
+        );
+    }
+
+    #[test]
+    fn merge_array_and_map_changes() {
+        let doc = Doc::with_client_id(1);
+        let root = doc.get_or_insert_map("root");
+        let array = {
+            let mut txn = doc.transact_mut();
+            root.insert(&mut txn, "items", ArrayPrelim::default())
+        };
+        let batcher = EventBatcher::with_clock(&root, BatchOptions::default(), Arc::new(|| 0));
+        let (batches, _sub) = collect(&batcher);
+
+        array.insert_range(&mut doc.transact_mut(), 0, [1, 2, 3]);
+        array.remove(&mut doc.transact_mut(), 1);
+        root.insert(&mut doc.transact_mut(), "a", 1);
+        root.insert(&mut doc.transact_mut(), "a", 2);
+        root.insert(&mut doc.transact_mut(), "b", 1);
+        root.remove(&mut doc.transact_mut(), "b");
+        batcher.flush();
+
+        let batches = batches.lock().unwrap();
+        let events = &batches[0];
+        assert_eq!(events.len(), 2);
+        assert_eq!(
+            events[0].path,
+            Path::from([PathSegment::Key("items".into())])
+        );
+        assert_eq!(
+            events[0].delta,
+            BatchedDelta::Array(vec![Change::Added(vec![Out::from(1.0), Out::from(3.0)])])
+        );
+        assert_eq!(events[1].delta, BatchedDelta::None);
+        assert_eq!(
+            events[1].keys,
+            HashMap::from([("a".into(), EntryChange::Inserted(Out::from(2.0)))])
+        );
+    }
+
+    #[test]
+    fn flush_on_limits() {
+        let doc = Doc::with_client_id(1);
+        let map = doc.get_or_insert_map("map");
+        let time = Arc::new(AtomicU64::new(0));
+        let clock = {
+            let time = time.clone();
+            Arc::new(move || time.load(Ordering::SeqCst))
+        };
+        let options = BatchOptions {
+            max_transactions: 2,
+            debounce_millis: 100,
+        };
+        let batcher = EventBatcher::with_clock(&map, options, clock);
+        let (batches, _sub) = collect(&batcher);
+
+        map.insert(&mut doc.transact_mut(), "a", 1);
+        map.insert(&mut doc.transact_mut(), "b", 1);
+        assert_eq!(batches.lock().unwrap().len(), 1); // max transactions reached
+
+        map.insert(&mut doc.transact_mut(), "c", 1);
+        time.store(50, Ordering::SeqCst);
+        assert!(!batcher.flush_expired());
+        time.store(150, Ordering::SeqCst);
+        assert!(batcher.flush_expired());
+        assert_eq!(batches.lock().unwrap().len(), 2);
+
+        map.insert(&mut doc.transact_mut(), "d", 1);
+        time.store(300, Ordering::SeqCst);
+        // debounce window has passed, previous batch is emitted before buffering a new one
+        map.insert(&mut doc.transact_mut(), "e", 1);
+        assert_eq!(batches.lock().unwrap().len(), 3);
+        assert_eq!(batcher.pending_transactions(), 1);
+
+        let nested: MapRef = map.insert(&mut doc.transact_mut(), "nested", MapPrelim::default());
+        assert_eq!(batches.lock().unwrap().len(), 4); // max transactions reached
+
+        // changes cancelling each other out are not reported
+        nested.insert(&mut doc.transact_mut(), "x", 1);
+        nested.remove(&mut doc.transact_mut(), "x");
+        assert_eq!(batcher.pending_transactions(), 0);
+        assert_eq!(batches.lock().unwrap().len(), 4);
+    }
+}
@@ -0,0 +1,507 @@
+//! Attribution of document contents to their authors.
+//!
+//! Block identifiers already tell which peer ([ClientID]) has inserted a given piece of content,
+//! but not when it happened or which user was behind it. When enabled with
+//! [Doc::record_block_meta], a document records a wall-clock timestamp and an app-defined author
+//! tag for every block it creates. This metadata is not a part of the document updates - it's
+//! exchanged as a separate [BlockMetaUpdate], that can be sent alongside the regular ones - and
+//! it can be queried using [Text::blame] or [ReadTxn::block_meta].
+//!
+//! Changes made by automated processes (i.e. bots or AI assistants) can be told apart from the
+//! ones made by people, by marking their transactions with [TransactionMut::set_actor]. Actor
+//! kind is recorded in the [BlockMeta::kind] of created blocks, it's visible to the observers of
+//! a transaction and can be used to scope [UndoManager] operations (see:
+//! [undo::Options::tracked_actors]).
+//!
+//! # Example
+//!
+//! ```rust
+//! use std::sync::Arc;
+//! use yrs::updates::decoder::Decode;
+//! use yrs::{Doc, ReadTxn, StateVector, Text, Transact, Update, WriteTxn};
+//!
+//! let alice = Doc::with_client_id(1);
+//! alice.record_block_meta(Some(Arc::from("alice"))).unwrap();
+//! let text = alice.get_or_insert_text("text");
+//! text.push(&mut alice.transact_mut(), "hello");
+//!
+//! // send metadata together with an update
+//! let bob = Doc::with_client_id(2);
+//! let txn = alice.transact();
+//! let update = txn.encode_state_as_update_v1(&StateVector::default());
+//! let meta = txn.block_meta_update(&StateVector::default());
+//! let mut remote = bob.transact_mut();
+//! remote.apply_update(Update::decode_v1(&update).unwrap());
+//! remote.apply_block_meta(meta);
+//!
+//! let text = remote.get_or_insert_text("text");
+//! let blame = text.blame(&remote);
+//! assert_eq!(blame.len(), 1);
+//! let (range, client, meta) = &blame[0];
+//! assert_eq!((range.clone(), *client), (0..5, 1));
+//! assert_eq!(meta.as_ref().unwrap().author.as_deref(), Some("alice"));
+//! ```
+//!
+//! [Doc::record_block_meta]: crate::Doc::record_block_meta
+//! [Text::blame]: crate::Text::blame
+//! [ReadTxn::block_meta]: crate::ReadTxn::block_meta
+//! [TransactionMut::set_actor]: crate::TransactionMut::set_actor
+//! [UndoManager]: crate::UndoManager
+//! [undo::Options::tracked_actors]: crate::undo::Options::tracked_actors
+
+use crate::block::{ClientID, ItemContent};
+use crate::branch::Branch;
+use crate::encoding::read::Error;
+use crate::sync::{Clock, Timestamp};
+use crate::updates::decoder::{Decode, Decoder};
+use crate::updates::encoder::{Encode, Encoder};
+use crate::{OffsetKind, ReadTxn, StateVector, ID};
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Range;
+use std::sync::Arc;
+
+/// Metadata recorded for a block at the moment of its creation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockMeta {
+    /// Wall-clock time (in milliseconds) when a block has been created.
+    pub timestamp: Timestamp,
+    /// App-defined tag of a user, who has created a block.
+    pub author: Option<Arc<str>>,
+    /// Kind of an actor, who has created a block.
+    pub kind: ActorKind,
+}
+
+/// Category of an actor making changes to a document.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum ActorKind {
+    /// Changes made by a person, i.e. typed in by a user of an editor.
+    #[default]
+    Human,
+    /// Changes made by an automated process, i.e. a bot, script or an AI assistant.
+    Automation,
+}
+
+/// Actor making changes within a read-write transaction, assigned with
+/// [TransactionMut::set_actor]. Blocks created by a transaction with an actor assigned are
+/// attributed to it, when [Doc::record_block_meta] is enabled.
+///
+/// [TransactionMut::set_actor]: crate::TransactionMut::set_actor
+/// [Doc::record_block_meta]: crate::Doc::record_block_meta
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Actor {
+    /// Kind of an actor.
+    pub kind: ActorKind,
+    /// App-defined actor identifier, recorded as [BlockMeta::author].
+    pub id: Arc<str>,
+}
+
+impl Actor {
+    pub fn new<S: Into<Arc<str>>>(kind: ActorKind, id: S) -> Self {
+        Actor {
+            kind,
+            id: id.into(),
+        }
+    }
+}
+
+/// Metadata of a continuous range of block clocks `[start, end)` created by the same client.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct MetaRange {
+    start: u32,
+    end: u32,
+    meta: BlockMeta,
+}
+
+/// Block metadata exchanged between peers, alongside document updates. It can be obtained with
+/// [ReadTxn::block_meta_update] and applied with [TransactionMut::apply_block_meta].
+///
+/// [TransactionMut::apply_block_meta]: crate::TransactionMut::apply_block_meta
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BlockMetaUpdate(BTreeMap<ClientID, Vec<MetaRange>>);
+
+impl BlockMetaUpdate {
+    /// Checks if current update contains any metadata.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// Metadata range flag: author tag is present.
+const FLAG_AUTHOR: u8 = 0b01;
+/// Metadata range flag: blocks were created by [ActorKind::Automation].
+const FLAG_AUTOMATION: u8 = 0b10;
+
+impl Encode for BlockMetaUpdate {
+    fn encode<E: Encoder>(&self, encoder: &mut E) {
+        encoder.write_var(self.0.len());
+        for (client, ranges) in self.0.iter() {
+            encoder.write_var(*client);
+            encoder.write_var(ranges.len());
+            for range in ranges.iter() {
+                encoder.write_var(range.start);
+                encoder.write_var(range.end - range.start);
+                encoder.write_var(range.meta.timestamp);
+                let mut flags = 0;
+                if range.meta.author.is_some() {
+                    flags |= FLAG_AUTHOR;
+                }
+                if range.meta.kind == ActorKind::Automation {
+                    flags |= FLAG_AUTOMATION;
+                }
+                encoder.write_u8(flags);
+                if let Some(author) = &range.meta.author {
+                    encoder.write_string(author);
+                }
+            }
+        }
+    }
+}
+
+impl Decode for BlockMetaUpdate {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, Error> {
+        let clients: u32 = decoder.read_var()?;
+        let mut result = BTreeMap::new();
+        for _ in 0..clients {
+            let client: ClientID = decoder.read_var()?;
+            let len = decoder.read_var::<u32>()? as usize;
+            let mut ranges: Vec<MetaRange> = Vec::new();
+            ranges.try_reserve(decoder.bounded_len(len)?)?;
+            for _ in 0..len {
+                let start: u32 = decoder.read_var()?;
+                let len: u32 = decoder.read_var()?;
+                let end = start.checked_add(len).ok_or(Error::UnexpectedValue)?;
+                let timestamp = decoder.read_var()?;
+                let flags = decoder.read_u8()?;
+                if flags & !(FLAG_AUTHOR | FLAG_AUTOMATION) != 0 {
+                    return Err(Error::UnexpectedValue);
+                }
+                let author = if flags & FLAG_AUTHOR != 0 {
+                    Some(Arc::from(decoder.read_string()?))
+                } else {
+                    None
+                };
+                let kind = if flags & FLAG_AUTOMATION != 0 {
+                    ActorKind::Automation
+                } else {
+                    ActorKind::Human
+                };
+                if len == 0 || ranges.last().map(|r| r.end > start).unwrap_or(false) {
+                    return Err(Error::UnexpectedValue);
+                }
+                let meta = BlockMeta {
+                    timestamp,
+                    author,
+                    kind,
+                };
+                ranges.push(MetaRange { start, end, meta });
+            }
+            if !ranges.is_empty() {
+                result.insert(client, ranges);
+            }
+        }
+        Ok(BlockMetaUpdate(result))
+    }
+}
+
+/// Metadata of blocks known to a document store.
+#[derive(Default)]
+pub(crate) struct BlockMetaLog {
+    /// Used to record metadata of locally created blocks. None if recording is disabled, but
+    /// metadata has been received from remote peers.
+    recorder: Option<Recorder>,
+    /// Non-overlapping metadata ranges, sorted by their start clock.
+    ranges: HashMap<ClientID, Vec<MetaRange>>,
+}
+
+struct Recorder {
+    clock: Arc<dyn Clock>,
+    author: Option<Arc<str>>,
+}
+
+impl BlockMetaLog {
+    pub fn recording(clock: Arc<dyn Clock>, author: Option<Arc<str>>) -> Self {
+        BlockMetaLog {
+            recorder: Some(Recorder { clock, author }),
+            ranges: HashMap::default(),
+        }
+    }
+
+    pub fn set_recorder(&mut self, clock: Arc<dyn Clock>, author: Option<Arc<str>>) {
+        self.recorder = Some(Recorder { clock, author });
+    }
+
+    /// Records metadata of blocks created by a local `client` within `[start, end)` clock range.
+    /// If `actor` is given, it's recorded in place of the default author.
+    pub fn record(&mut self, client: ClientID, start: u32, end: u32, actor: Option<&Actor>) {
+        if let Some(recorder) = &self.recorder {
+            if start < end {
+                let meta = BlockMeta {
+                    timestamp: recorder.clock.now(),
+                    author: match actor {
+                        Some(actor) => Some(actor.id.clone()),
+                        None => recorder.author.clone(),
+                    },
+                    kind: actor.map(|a| a.kind).unwrap_or_default(),
+                };
+                self.insert(client, start, end, meta);
+            }
+        }
+    }
+
+    pub fn get(&self, id: &ID) -> Option<&BlockMeta> {
+        let ranges = self.ranges.get(&id.client)?;
+        let i = ranges.partition_point(|r| r.end <= id.clock);
+        let range = ranges.get(i)?;
+        if range.start <= id.clock {
+            Some(&range.meta)
+        } else {
+            None
+        }
+    }
+
+    /// Returns metadata of blocks, which are not included in a given `state_vector`.
+    pub fn diff(&self, state_vector: &StateVector) -> BlockMetaUpdate {
+        let mut result = BTreeMap::new();
+        for (client, ranges) in self.ranges.iter() {
+            let clock = state_vector.get(client);
+            let diff: Vec<_> = ranges
+                .iter()
+                .filter(|r| r.end > clock)
+                .map(|r| MetaRange {
+                    start: r.start.max(clock),
+                    end: r.end,
+                    meta: r.meta.clone(),
+                })
+                .collect();
+            if !diff.is_empty() {
+                result.insert(*client, diff);
+            }
+        }
+        BlockMetaUpdate(result)
+    }
+
+    /// Merges metadata received from a remote peer. Metadata already known for a given block
+    /// takes precedence over the incoming one.
+    pub fn apply(&mut self, update: BlockMetaUpdate) {
+        for (client, ranges) in update.0 {
+            for range in ranges {
+                self.insert(client, range.start, range.end, range.meta);
+            }
+        }
+    }
+
+    /// Inserts metadata for the parts of `[start, end)` range, which have no metadata yet.
+    fn insert(&mut self, client: ClientID, start: u32, end: u32, meta: BlockMeta) {
+        let ranges = self.ranges.entry(client).or_default();
+        let mut i = ranges.partition_point(|r| r.end <= start);
+        let mut start = start;
+        while start < end {
+            let gap_end = match ranges.get(i) {
+                Some(next) if next.start <= start => {
+                    start = next.end;
+                    i += 1;
+                    continue;
+                }
+                Some(next) => next.start.min(end),
+                None => end,
+            };
+            let prev = if i > 0 { ranges.get_mut(i - 1) } else { None };
+            match prev {
+                Some(prev) if prev.end == start && prev.meta == meta => prev.end = gap_end,
+                _ => {
+                    let meta = meta.clone();
+                    ranges.insert(
+                        i,
+                        MetaRange {
+                            start,
+                            end: gap_end,
+                            meta,
+                        },
+                    );
+                    i += 1;
+                }
+            }
+            start = gap_end;
+        }
+    }
+}
+
+/// Attributes visible contents of a given `text` branch to the clients, which have inserted them.
+pub(crate) fn blame<T: ReadTxn>(
+    text: &Branch,
+    txn: &T,
+) -> Vec<(Range<u32>, ClientID, Option<BlockMeta>)> {
+    let store = txn.store();
+    let kind = store.options.offset_kind;
+    let log = store.block_meta.as_deref();
+    let mut result: Vec<(Range<u32>, ClientID, Option<BlockMeta>)> = Vec::new();
+    let mut push = |len: u32, id: ID| {
+        let meta = log.and_then(|log| log.get(&id)).cloned();
+        match result.last_mut() {
+            Some((range, client, last)) if *client == id.client && *last == meta => {
+                range.end += len
+            }
+            _ => {
+                let start = result.last().map(|(r, _, _)| r.end).unwrap_or(0);
+                result.push((start..start + len, id.client, meta));
+            }
+        }
+    };
+    let mut current = text.start;
+    while let Some(item) = current.as_deref() {
+        if !item.is_deleted() && item.is_countable() {
+            match &item.content {
+                ItemContent::String(str) => {
+                    // blocks are measured in UTF-16 code units, which may differ from offset kind
+                    let mut clock = item.id.clock;
+                    for c in str.as_str().chars() {
+                        let len = match kind {
+                            OffsetKind::Bytes => c.len_utf8(),
+                            OffsetKind::Utf16 => c.len_utf16(),
+                        };
+                        push(len as u32, ID::new(item.id.client, clock));
+                        clock += c.len_utf16() as u32;
+                    }
+                }
+                content => {
+                    for i in 0..content.len(kind) {
+                        push(1, ID::new(item.id.client, item.id.clock + i));
+                    }
+                }
+            }
+        }
+        current = item.right;
+    }
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use crate::blame::{ActorKind, BlockMetaUpdate};
+    use crate::sync::Clock;
+    use crate::updates::decoder::Decode;
+    use crate::updates::encoder::Encode;
+    use crate::{Doc, ReadTxn, StateVector, Text, Transact, Update, ID};
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+
+    struct TestClock(AtomicU64);
+
+    impl Clock for TestClock {
+        fn now(&self) -> u64 {
+            self.0.load(Ordering::SeqCst)
+        }
+    }
+
+    #[test]
+    fn blame_text() {
+        let clock = Arc::new(TestClock(AtomicU64::new(100)));
+        let d1 = Doc::with_client_id(1);
+        d1.record_block_meta_with_clock(Some("alice".into()), clock.clone())
+            .unwrap();
+        let d2 = Doc::with_client_id(2);
+        d2.record_block_meta_with_clock(Some("bob".into()), clock.clone())
+            .unwrap();
+        let t1 = d1.get_or_insert_text("text");
+        let t2 = d2.get_or_insert_text("text");
+
+        t1.push(&mut d1.transact_mut(), "hello");
+        clock.0.store(200, Ordering::SeqCst);
+        t1.push(&mut d1.transact_mut(), "!");
+
+        let sv = d2.transact().state_vector();
+        let (update, meta) = {
+            let txn = d1.transact();
+            (
+                txn.encode_diff_v1(&sv),
+                txn.block_meta_update(&sv).encode_v1(),
+            )
+        };
+        {
+            let mut txn = d2.transact_mut();
+            txn.apply_update(Update::decode_v1(&update).unwrap());
+            txn.apply_block_meta(BlockMetaUpdate::decode_v1(&meta).unwrap());
+        }
+        clock.0.store(300, Ordering::SeqCst);
+        t2.insert(&mut d2.transact_mut(), 2, "ÿ");
+        t2.remove_range(&mut d2.transact_mut(), 0, 1);
+
+        let txn = d2.transact();
+        let blame: Vec<_> = t2
+            .blame(&txn)
+            .into_iter()
+            .map(|(range, client, meta)| {
+                let meta = meta.unwrap();
+                (range, client, meta.timestamp, meta.author.unwrap())
+            })
+            .collect();
+        assert_eq!(
+            blame,
+            vec![
+                (0..1, 1, 100, "alice".into()),
+                (1..3, 2, 300, "bob".into()),
+                (3..6, 1, 100, "alice".into()),
+                (6..7, 1, 200, "alice".into()),
+            ]
+        );
+        assert_eq!(txn.block_meta(&ID::new(1, 5)).unwrap().timestamp, 200);
+        assert!(txn.block_meta(&ID::new(1, 6)).is_none());
+
+        // metadata already known by a remote peer is not sent again
+        let sv = txn.state_vector();
+        assert!(txn.block_meta_update(&sv).is_empty());
+        assert!(!txn.block_meta_update(&StateVector::default()).is_empty());
+    }
+
+    #[test]
+    fn blame_actor_kinds() {
+        let clock = Arc::new(TestClock(AtomicU64::new(100)));
+        let d1 = Doc::with_client_id(1);
+        d1.record_block_meta_with_clock(Some("alice".into()), clock.clone())
+            .unwrap();
+        let t1 = d1.get_or_insert_text("text");
+
+        t1.push(&mut d1.transact_mut(), "hello");
+        {
+            let mut txn = d1.transact_mut();
+            txn.set_actor(ActorKind::Automation, "assistant");
+            assert_eq!(txn.actor().unwrap().id.as_ref(), "assistant");
+            t1.push(&mut txn, " world");
+        }
+        t1.push(&mut d1.transact_mut(), "!");
+
+        // actor kinds survive the exchange with remote peers
+        let d2 = Doc::with_client_id(2);
+        let t2 = d2.get_or_insert_text("text");
+        {
+            let (update, meta) = {
+                let txn = d1.transact();
+                let sv = StateVector::default();
+                (
+                    txn.encode_diff_v1(&sv),
+                    txn.block_meta_update(&sv).encode_v1(),
+                )
+            };
+            let mut txn = d2.transact_mut();
+            txn.apply_update(Update::decode_v1(&update).unwrap());
+            txn.apply_block_meta(BlockMetaUpdate::decode_v1(&meta).unwrap());
+        }
+        let txn = d2.transact();
+        let blame: Vec<_> = t2
+            .blame(&txn)
+            .into_iter()
+            .map(|(range, _, meta)| {
+                let meta = meta.unwrap();
+                (range, meta.kind, meta.author.unwrap())
+            })
+            .collect();
+        assert_eq!(
+            blame,
+            vec![
+                (0..5, ActorKind::Human, "alice".into()),
+                (5..11, ActorKind::Automation, "assistant".into()),
+                (11..12, ActorKind::Human, "alice".into()),
+            ]
+        );
+    }
+}
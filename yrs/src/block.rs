@@ -1,6 +1,6 @@
 use crate::branch::{Branch, BranchPtr};
 use crate::doc::{DocAddr, OffsetKind};
-use crate::encoding::read::Error;
+use crate::encoding::read::{bounded_len, Error};
 use crate::gc::GCCollector;
 use crate::moving::Move;
 use crate::slice::{BlockSlice, GCSlice, ItemSlice};
@@ -473,6 +473,12 @@ impl ItemPtr {
 
             item.right = Some(new_ptr);
 
+            if item.parent_sub.is_none() {
+                if let TypePtr::Branch(mut branch) = item.parent {
+                    branch.index_split(self_ptr, new_ptr);
+                }
+            }
+
             Some(new)
         }
     }
@@ -553,6 +559,7 @@ impl ItemPtr {
                     parent_ref.start
                 };
 
+                let conflict_order = &store.options.conflict_order;
                 let mut left = this.left.clone();
                 let mut conflicting_items = HashSet::new();
                 let mut items_before_origin = HashSet::new();
@@ -569,7 +576,11 @@ impl ItemPtr {
                     conflicting_items.insert(item);
                     if this.origin == item.origin {
                         // case 1
-                        if item.id.client < this.id.client {
+                        if conflict_order.precedes(
+                            item.id.client,
+                            this.id.client,
+                            this.parent_sub.is_some(),
+                        ) {
                             left = Some(item.clone());
                             conflicting_items.clear();
                         } else if this.right_origin == item.right_origin {
@@ -637,7 +648,15 @@ impl ItemPtr {
                 right.left = Some(self_ptr);
             } else if let Some(parent_sub) = &this.parent_sub {
                 // set as current parent value if right === null and this is parentSub
-                parent_ref.map.insert(parent_sub.clone(), self_ptr);
+                let prev = parent_ref.map.insert(parent_sub.clone(), self_ptr);
+                if let Some(prev) = prev {
+                    if !prev.is_deleted() {
+                        parent_ref.map_len -= 1;
+                    }
+                }
+                if !this.is_deleted() && !matches!(this.content, ItemContent::Deleted(_)) {
+                    parent_ref.map_len += 1;
+                }
                 if let Some(mut left) = this.left {
                     #[cfg(feature = "weak")]
                     {
@@ -653,11 +672,19 @@ impl ItemPtr {
                             }
                         }
                     }
+                    if !left.is_deleted() && this.origin != Some(left.last_id()) {
+                        // current value has been written concurrently
+                        txn.add_map_conflict(parent_ref, parent_sub, &left.content);
+                    }
                     // this is the current attribute value of parent. delete right
                     txn.delete(left);
                 }
             }
 
+            if this.parent_sub.is_none() {
+                parent_ref.index_inserted(this.left, self_ptr, encoding);
+            }
+
             // adjust length of parent
             if this.parent_sub.is_none() && !this.is_deleted() {
                 if this.is_countable() {
@@ -710,7 +737,10 @@ impl ItemPtr {
                 ItemContent::Doc(parent_doc, doc) => {
                     *parent_doc = Some(txn.doc().clone());
                     {
-                        let mut child_txn = doc.transact_mut();
+                        let mut child_txn = match txn.store.subdoc_origin(txn.origin(), doc) {
+                            Some(origin) => doc.transact_mut_with(origin),
+                            None => doc.transact_mut(),
+                        };
                         child_txn.store.parent = Some(self_ptr);
                     }
                     let subdocs = txn.subdocs.get_or_init();
@@ -758,6 +788,10 @@ impl ItemPtr {
                 false
             };
             if parent_deleted || (this.parent_sub.is_some() && this.right.is_some()) {
+                if let (false, Some(key)) = (parent_deleted, &this.parent_sub) {
+                    // this value has lost to the one written concurrently
+                    txn.add_map_conflict(parent_ref, key, &this.content);
+                }
                 // delete if parent is deleted or if this is not the current attribute value of parent
                 true
             } else {
@@ -792,6 +826,11 @@ impl ItemPtr {
                 self.info.set_keep();
             }
             self.right = other.right;
+            if self.parent_sub.is_none() {
+                if let TypePtr::Branch(mut branch) = self.parent {
+                    branch.index_squashed(*self, other);
+                }
+            }
             true
         } else {
             false
@@ -1044,6 +1083,11 @@ impl ItemFlags {
         self.set(ITEM_FLAG_DELETED)
     }
 
+    #[inline]
+    pub fn clear_deleted(&mut self) {
+        self.clear(ITEM_FLAG_DELETED)
+    }
+
     #[inline]
     pub fn is_deleted(&self) -> bool {
         self.check(ITEM_FLAG_DELETED)
@@ -1332,8 +1376,8 @@ impl Item {
                         ItemContent::Type(branch) => {
                             TypePtr::Branch(BranchPtr::from(branch.as_ref()))
                         }
-                        ItemContent::Deleted(_) => TypePtr::Unknown,
-                        _ => panic!("Defect: parent points to a block which is not a shared type"),
+                        // deleted parent, or a malformed update pointing to a non-type block
+                        _ => TypePtr::Unknown,
                     }
                 } else {
                     TypePtr::Unknown
@@ -1789,7 +1833,12 @@ impl ItemContent {
             BLOCK_ITEM_JSON_REF_NUMBER => {
                 let mut remaining = decoder.read_len()? as i32;
                 let mut buf = Vec::new();
-                buf.try_reserve(remaining as usize)?;
+                // JSON strings are stored outside of the remaining bytes in v2 encoding
+                buf.try_reserve(bounded_len(
+                    remaining.max(0) as usize,
+                    decoder.remaining(),
+                    None,
+                )?)?;
 
                 while remaining >= 0 {
                     buf.push(decoder.read_string()?.to_owned());
@@ -1812,7 +1861,7 @@ impl ItemContent {
             BLOCK_ITEM_ANY_REF_NUMBER => {
                 let len = decoder.read_len()? as usize;
                 let mut values = Vec::new();
-                values.try_reserve(len)?;
+                values.try_reserve(decoder.bounded_len(len)?)?;
 
                 let mut i = 0;
                 while i < len {
@@ -1908,6 +1957,7 @@ impl ItemContent {
         match self {
             ItemContent::Type(branch) => {
                 let b = Arc::get_mut(branch).unwrap();
+                b.index = None;
                 let mut curr = b.start.take();
                 while let Some(mut item) = curr {
                     curr = item.right.clone();
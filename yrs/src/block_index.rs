@@ -0,0 +1,460 @@
+use crate::block::{ItemContent, ItemPtr};
+use crate::branch::Branch;
+use crate::OffsetKind;
+use std::collections::HashMap;
+
+/// Number of countable elements, at which a branch starts to maintain its [BlockIndex].
+pub(crate) const INDEX_THRESHOLD: u32 = 64;
+
+const NIL: u32 = u32::MAX;
+
+/// An auxiliary index over a double linked list of items of a branch sequence component, used to
+/// resolve human-readable indexes into blocks in logarithmic rather than linear time.
+///
+/// Index is a treap ordered by the positions of items within a list, in which every node keeps
+/// the lengths of all countable items in its subtree. It contains all items of the list - including
+/// deleted and non-countable ones - and needs to be notified about every change in the list
+/// structure: block integration, deletion, splitting and squashing.
+///
+/// Since the items being moved and the formatting attributes affect how indexes are resolved,
+/// lookups are only possible, when a sequence contains none of them.
+#[derive(Debug)]
+pub(crate) struct BlockIndex {
+    kind: OffsetKind,
+    nodes: Vec<Node>,
+    free: Vec<u32>,
+    root: u32,
+    lookup: HashMap<ItemPtr, u32>,
+    /// Number of non-deleted formatting attributes.
+    formats: u32,
+    /// Number of move markers.
+    moves: u32,
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    item: ItemPtr,
+    priority: u32,
+    parent: u32,
+    left: u32,
+    right: u32,
+    /// Length of an item expressed in units of [BlockIndex::kind].
+    len: u32,
+    /// Length of an item expressed in block clock units.
+    block_len: u32,
+    /// Sum of `len` of all items in this subtree.
+    sum: u32,
+    /// Sum of `block_len` of all items in this subtree.
+    block_sum: u32,
+    format: bool,
+    moved: bool,
+}
+
+/// Position of an item found in a [BlockIndex].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct IndexedItem<'a> {
+    pub item: &'a ItemPtr,
+    /// Sum of lengths of all countable items preceding found item.
+    pub offset: u32,
+    /// Sum of block lengths of all countable items preceding found item.
+    pub block_offset: u32,
+}
+
+impl BlockIndex {
+    /// Builds a new index over all items of a `branch` sequence.
+    pub fn build(branch: &Branch, kind: OffsetKind) -> Self {
+        let mut index = BlockIndex {
+            kind,
+            nodes: Vec::new(),
+            free: Vec::new(),
+            root: NIL,
+            lookup: HashMap::new(),
+            formats: 0,
+            moves: 0,
+        };
+        let mut left = None;
+        let mut curr = branch.start;
+        while let Some(item) = curr {
+            index.insert_after(left, item);
+            left = curr;
+            curr = item.right;
+        }
+        index
+    }
+
+    /// Returns units in which lengths of the indexed items are measured.
+    pub fn kind(&self) -> OffsetKind {
+        self.kind
+    }
+
+    /// Checks if this index can be used to resolve indexes.
+    pub fn is_usable(&self) -> bool {
+        self.formats == 0 && self.moves == 0
+    }
+
+    /// Checks if a given `item` is a part of this index.
+    pub fn contains(&self, item: &ItemPtr) -> bool {
+        self.lookup.contains_key(item)
+    }
+
+    /// Inserts a new `item` directly after its `left` neighbor. If `left` is `None`, an item is
+    /// inserted at the beginning of the sequence. Returns false if `left` was not indexed.
+    pub fn insert_after(&mut self, left: Option<ItemPtr>, item: ItemPtr) -> bool {
+        let mut parent = match left {
+            None => NIL,
+            Some(left) => match self.lookup.get(&left) {
+                Some(&node) => node,
+                None => return false,
+            },
+        };
+        let node = self.alloc(item);
+        if parent == NIL {
+            // insert as the leftmost node
+            parent = self.root;
+            if parent == NIL {
+                self.root = node;
+                return true;
+            }
+            while self.nodes[parent as usize].left != NIL {
+                parent = self.nodes[parent as usize].left;
+            }
+            self.nodes[parent as usize].left = node;
+        } else if self.nodes[parent as usize].right == NIL {
+            self.nodes[parent as usize].right = node;
+        } else {
+            // insert as the leftmost node of the right subtree
+            parent = self.nodes[parent as usize].right;
+            while self.nodes[parent as usize].left != NIL {
+                parent = self.nodes[parent as usize].left;
+            }
+            self.nodes[parent as usize].left = node;
+        }
+        self.nodes[node as usize].parent = parent;
+        self.update_path(parent);
+        while parent != NIL && self.nodes[parent as usize].priority < self.priority(node) {
+            self.rotate_up(node);
+            parent = self.nodes[node as usize].parent;
+        }
+        true
+    }
+
+    /// Removes a given `item` from the index.
+    pub fn remove(&mut self, item: &ItemPtr) {
+        let node = match self.lookup.remove(item) {
+            Some(node) => node,
+            None => return,
+        };
+        // rotate node down until it has at most one child
+        loop {
+            let n = &self.nodes[node as usize];
+            let (left, right) = (n.left, n.right);
+            if left == NIL || right == NIL {
+                break;
+            }
+            if self.priority(left) > self.priority(right) {
+                self.rotate_up(left);
+            } else {
+                self.rotate_up(right);
+            }
+        }
+        let n = &self.nodes[node as usize];
+        let child = if n.left != NIL { n.left } else { n.right };
+        let parent = n.parent;
+        if child != NIL {
+            self.nodes[child as usize].parent = parent;
+        }
+        self.replace_child(parent, node, child);
+        self.update_path(parent);
+        let n = &mut self.nodes[node as usize];
+        n.left = NIL;
+        n.right = NIL;
+        n.parent = NIL;
+        self.dealloc(node);
+    }
+
+    /// Recomputes the length of a given `item`, i.e. after it has been deleted or split.
+    pub fn update(&mut self, item: &ItemPtr) {
+        if let Some(&node) = self.lookup.get(item) {
+            self.unregister(node);
+            self.measure(node);
+            self.update_path(node);
+        }
+    }
+
+    /// Finds an item containing a countable element at a given `offset`.
+    pub fn find(&self, offset: u32) -> Option<IndexedItem<'_>> {
+        self.find_by(offset, |n| (n.len, n.sum))
+    }
+
+    /// Finds an item containing a countable element at a given `offset` expressed in block
+    /// clock units.
+    pub fn find_block(&self, offset: u32) -> Option<IndexedItem<'_>> {
+        self.find_by(offset, |n| (n.block_len, n.block_sum))
+    }
+
+    fn find_by<F>(&self, mut offset: u32, f: F) -> Option<IndexedItem<'_>>
+    where
+        F: Fn(&Node) -> (u32, u32),
+    {
+        let mut prefix = 0;
+        let mut block_prefix = 0;
+        let mut curr = self.root;
+        while curr != NIL {
+            let n = &self.nodes[curr as usize];
+            let left_sum = if n.left == NIL {
+                0
+            } else {
+                f(&self.nodes[n.left as usize]).1
+            };
+            if offset < left_sum {
+                curr = n.left;
+                continue;
+            }
+            offset -= left_sum;
+            prefix += self.sum(n.left);
+            block_prefix += self.block_sum(n.left);
+            let len = f(n).0;
+            if offset < len {
+                return Some(IndexedItem {
+                    item: &n.item,
+                    offset: prefix,
+                    block_offset: block_prefix,
+                });
+            }
+            offset -= len;
+            prefix += n.len;
+            block_prefix += n.block_len;
+            curr = n.right;
+        }
+        None
+    }
+
+    fn alloc(&mut self, item: ItemPtr) -> u32 {
+        let node = Node {
+            item,
+            priority: fastrand::u32(..),
+            parent: NIL,
+            left: NIL,
+            right: NIL,
+            len: 0,
+            block_len: 0,
+            sum: 0,
+            block_sum: 0,
+            format: false,
+            moved: false,
+        };
+        let id = match self.free.pop() {
+            Some(id) => {
+                self.nodes[id as usize] = node;
+                id
+            }
+            None => {
+                self.nodes.push(node);
+                (self.nodes.len() - 1) as u32
+            }
+        };
+        self.lookup.insert(item, id);
+        self.measure(id);
+        self.pull(id);
+        id
+    }
+
+    fn dealloc(&mut self, node: u32) {
+        self.unregister(node);
+        self.free.push(node);
+    }
+
+    /// Computes lengths and flags of a given `node` based on the current state of its item.
+    fn measure(&mut self, node: u32) {
+        let kind = self.kind;
+        let n = &mut self.nodes[node as usize];
+        let item = &*n.item;
+        let live = !item.is_deleted();
+        if live && item.is_countable() {
+            n.len = item.content_len(kind);
+            n.block_len = item.len();
+        } else {
+            n.len = 0;
+            n.block_len = 0;
+        }
+        n.format = live && matches!(item.content, ItemContent::Format(_, _));
+        n.moved = matches!(item.content, ItemContent::Move(_));
+        if n.format {
+            self.formats += 1;
+        }
+        if n.moved {
+            self.moves += 1;
+        }
+    }
+
+    fn unregister(&mut self, node: u32) {
+        let n = &mut self.nodes[node as usize];
+        if std::mem::take(&mut n.format) {
+            self.formats -= 1;
+        }
+        if std::mem::take(&mut n.moved) {
+            self.moves -= 1;
+        }
+    }
+
+    #[inline]
+    fn priority(&self, node: u32) -> u32 {
+        self.nodes[node as usize].priority
+    }
+
+    #[inline]
+    fn sum(&self, node: u32) -> u32 {
+        if node == NIL {
+            0
+        } else {
+            self.nodes[node as usize].sum
+        }
+    }
+
+    #[inline]
+    fn block_sum(&self, node: u32) -> u32 {
+        if node == NIL {
+            0
+        } else {
+            self.nodes[node as usize].block_sum
+        }
+    }
+
+    fn pull(&mut self, node: u32) {
+        let n = &self.nodes[node as usize];
+        let sum = n.len + self.sum(n.left) + self.sum(n.right);
+        let block_sum = n.block_len + self.block_sum(n.left) + self.block_sum(n.right);
+        let n = &mut self.nodes[node as usize];
+        n.sum = sum;
+        n.block_sum = block_sum;
+    }
+
+    /// Recomputes sums of all nodes on the path from a given `node` up to the root.
+    fn update_path(&mut self, mut node: u32) {
+        while node != NIL {
+            self.pull(node);
+            node = self.nodes[node as usize].parent;
+        }
+    }
+
+    fn replace_child(&mut self, parent: u32, old: u32, new: u32) {
+        if parent == NIL {
+            self.root = new;
+        } else {
+            let p = &mut self.nodes[parent as usize];
+            if p.left == old {
+                p.left = new;
+            } else {
+                p.right = new;
+            }
+        }
+    }
+
+    /// Rotates a given `node` up, so that it takes place of its parent.
+    fn rotate_up(&mut self, node: u32) {
+        let parent = self.nodes[node as usize].parent;
+        let grandparent = self.nodes[parent as usize].parent;
+        if self.nodes[parent as usize].left == node {
+            let child = self.nodes[node as usize].right;
+            self.nodes[parent as usize].left = child;
+            if child != NIL {
+                self.nodes[child as usize].parent = parent;
+            }
+            self.nodes[node as usize].right = parent;
+        } else {
+            let child = self.nodes[node as usize].left;
+            self.nodes[parent as usize].right = child;
+            if child != NIL {
+                self.nodes[child as usize].parent = parent;
+            }
+            self.nodes[node as usize].left = parent;
+        }
+        self.nodes[parent as usize].parent = node;
+        self.nodes[node as usize].parent = grandparent;
+        self.replace_child(grandparent, parent, node);
+        self.pull(parent);
+        self.pull(node);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::block_index::BlockIndex;
+    use crate::branch::{Branch, BranchPtr};
+    use crate::{Array, Doc, GetString, Text, Transact};
+
+    fn assert_consistent(branch: BranchPtr) {
+        let index = branch.index.as_ref().unwrap();
+        assert_eq!(index.sum(index.root), branch.content_len);
+        let mut offset = 0;
+        let mut curr = branch.start;
+        while let Some(item) = curr {
+            assert!(index.contains(&item));
+            if !item.is_deleted() && item.is_countable() {
+                let found = index.find(offset).unwrap();
+                assert_eq!(*found.item, item);
+                assert_eq!(found.offset, offset);
+                offset += item.content_len(index.kind);
+            }
+            curr = item.right;
+        }
+        assert!(index.find(offset).is_none());
+        let rebuilt = BlockIndex::build(&branch, index.kind);
+        assert_eq!(rebuilt.sum(rebuilt.root), index.sum(index.root));
+        assert_eq!(rebuilt.lookup.len(), index.lookup.len());
+    }
+
+    #[test]
+    fn index_follows_array_changes() {
+        let doc = Doc::with_client_id(1);
+        let array = doc.get_or_insert_array("array");
+        let mut txn = doc.transact_mut();
+        for i in 0..200u32 {
+            array.insert(&mut txn, (i * 7) % (i + 1), i);
+        }
+        let branch: &Branch = array.as_ref();
+        let branch = BranchPtr::from(branch);
+        assert!(branch.index.is_some());
+        assert_consistent(branch);
+
+        array.remove_range(&mut txn, 10, 50);
+        array.insert_range(&mut txn, 20, [1, 2, 3]);
+        array.remove_range(&mut txn, 0, 3);
+        drop(txn);
+        assert_consistent(branch);
+
+        let expected: Vec<_> = array.iter(&doc.transact()).collect();
+        let txn = doc.transact();
+        for (i, value) in expected.iter().enumerate() {
+            assert_eq!(array.get(&txn, i as u32).as_ref(), Some(value));
+        }
+        assert_eq!(array.get(&txn, expected.len() as u32), None);
+    }
+
+    #[test]
+    fn index_follows_text_changes() {
+        let doc = Doc::with_client_id(1);
+        let txt = doc.get_or_insert_text("text");
+        let mut expected = String::new();
+        let mut txn = doc.transact_mut();
+        for i in 0..300u32 {
+            let index = (i * 13) % (expected.len() as u32 + 1);
+            let chunk = if i % 2 == 0 { "ab" } else { "c" };
+            txt.insert(&mut txn, index, chunk);
+            expected.insert_str(index as usize, chunk);
+            if i % 5 == 0 {
+                let len = expected.len() as u32;
+                txt.remove_range(&mut txn, len / 3, 2);
+                expected.replace_range((len / 3) as usize..(len / 3 + 2) as usize, "");
+            }
+        }
+        let branch: &Branch = txt.as_ref();
+        let branch = BranchPtr::from(branch);
+        assert_consistent(branch);
+        drop(txn);
+
+        // squashing blocks on commit keeps the index consistent
+        assert_consistent(branch);
+        assert_eq!(txt.get_string(&doc.transact()), expected);
+    }
+}
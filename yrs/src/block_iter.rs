@@ -1,9 +1,53 @@
 use crate::block::{Item, ItemContent, ItemPtr, Prelim};
+use crate::block_index::INDEX_THRESHOLD;
 use crate::branch::BranchPtr;
 use crate::moving::{Move, StickyIndex};
 use crate::transaction::{ReadTxn, TransactionMut};
 use crate::types::TypePtr;
 use crate::{Assoc, Out, ID};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Positions of the blocks last visited by index-based reads within a single transaction. It
+/// allows sequential reads - i.e. `array.get(txn, i)` followed by `array.get(txn, i + 1)` - to
+/// continue from the last visited block instead of traversing a sequence from its beginning.
+///
+/// Read-write transactions invalidate cached position of a branch whenever it's modified.
+#[derive(Debug, Default)]
+pub struct CursorCache {
+    positions: Mutex<HashMap<BranchPtr, (u32, ItemPtr)>>,
+}
+
+impl CursorCache {
+    /// Returns an item last visited in a given `branch` together with an index of its first
+    /// element.
+    fn get(&self, branch: &BranchPtr) -> Option<(u32, ItemPtr)> {
+        let positions = self.positions.lock().ok()?;
+        positions.get(branch).copied()
+    }
+
+    fn set(&self, branch: BranchPtr, index: u32, item: ItemPtr) {
+        if let Ok(mut positions) = self.positions.lock() {
+            positions.insert(branch, (index, item));
+        }
+    }
+
+    /// Removes a cached position of a given `branch`.
+    pub(crate) fn invalidate(&self, branch: &BranchPtr) {
+        if let Ok(mut positions) = self.positions.lock() {
+            if !positions.is_empty() {
+                positions.remove(branch);
+            }
+        }
+    }
+
+    /// Removes all cached positions.
+    pub(crate) fn clear(&self) {
+        if let Ok(mut positions) = self.positions.lock() {
+            positions.clear();
+        }
+    }
+}
 
 /// Struct used for iterating over the sequence of item's values with respect to a potential
 /// [Move] markers that may change their order.
@@ -113,9 +157,37 @@ impl BlockIter {
 
         let mut item = self.next_item;
         self.index += len;
+        let mut from_start = false;
         if self.rel != 0 {
             len += self.rel;
             self.rel = 0;
+        } else if len > 0
+            && self.index == len
+            && self.curr_move.is_none()
+            && item == self.branch.start
+        {
+            from_start = true;
+            let cached = txn
+                .cursors()
+                .and_then(|cursors| cursors.get(&self.branch))
+                .filter(|&(index, _)| index <= len);
+            match cached {
+                // continue from the last visited block if it's close enough
+                Some((index, ptr)) if len - index <= INDEX_THRESHOLD => {
+                    item = Some(ptr);
+                    len -= index;
+                }
+                _ => {
+                    // skip directly to the block containing the last element to forward over
+                    if let Some(found) = self.branch.indexed(len - 1) {
+                        item = Some(*found.item);
+                        len -= found.offset;
+                    } else if let Some((index, ptr)) = cached {
+                        item = Some(ptr);
+                        len -= index;
+                    }
+                }
+            }
         }
 
         let encoding = txn.store().options.offset_kind;
@@ -169,6 +241,11 @@ impl BlockIter {
 
         self.index -= len;
         self.next_item = item;
+        if from_start && len == 0 && !self.reached_end && self.curr_move.is_none() {
+            if let (Some(cursors), Some(item)) = (txn.cursors(), item) {
+                cursors.set(self.branch, self.index - self.rel, item);
+            }
+        }
         true
     }
 
@@ -64,14 +64,17 @@ impl ClientBlockList {
     /// found using binary search algorithm, or a index under which this block should be inserted.
     pub(crate) fn find_pivot(&self, clock: u32) -> Option<usize> {
         let mut left = 0;
-        let mut right = self.list.len() - 1;
+        let mut right = self.list.len().checked_sub(1)?;
         let mut block = &self[right];
         let (mut start, mut end) = block.clock_range();
         if start == clock {
             // a common case is to just append a block at the end, so check first if we can do that
             Some(right)
+        } else if clock > end {
+            // clock is out of range, i.e. referenced by malformed update
+            None
         } else {
-            let mut mid = ((clock / end) * right as u32) as usize;
+            let mut mid = (clock / end.max(1)) as usize * right;
             while left <= right {
                 block = &self[mid];
                 (start, end) = block.clock_range();
@@ -80,6 +83,8 @@ impl ClientBlockList {
                         return Some(mid);
                     }
                     left = mid + 1;
+                } else if mid == 0 {
+                    break;
                 } else {
                     right = mid - 1;
                 }
@@ -255,6 +260,30 @@ impl BlockStore {
         StateVector::new(map)
     }
 
+    /// Removes all blocks of a given `client` starting at a given `clock`, returning removed
+    /// blocks. Items crossing the `clock` boundary must have been split beforehand.
+    pub(crate) fn truncate(&mut self, client: &ClientID, clock: u32) -> Vec<BlockCell> {
+        let blocks = match self.clients.get_mut(client) {
+            Some(blocks) => blocks,
+            None => return Vec::new(),
+        };
+        let mut index = match blocks.find_pivot(clock) {
+            Some(index) => index,
+            None => return Vec::new(),
+        };
+        if let BlockCell::GC(gc) = &mut blocks.list[index] {
+            if gc.start < clock {
+                gc.end = clock - 1;
+                index += 1;
+            }
+        }
+        let removed = blocks.list.split_off(index);
+        if blocks.list.is_empty() {
+            self.clients.remove(client);
+        }
+        removed
+    }
+
     pub(crate) fn get_client(&self, client_id: &ClientID) -> Option<&ClientBlockList> {
         self.clients.get(client_id)
     }
@@ -0,0 +1,157 @@
+//! Diagnostics of transactions which are kept alive for too long.
+//!
+//! When `borrow-tracker` feature is enabled, every transaction created via [crate::Transact]
+//! methods of a [Doc] records the source code location it was created at. Whenever a transaction
+//! can't be acquired because of other active transactions, panic messages of
+//! [crate::Transact::transact] and [crate::Transact::transact_mut] list the locations of these
+//! transactions. The same information can be retrieved at any time using
+//! [Doc::active_transactions].
+//!
+//! Tracking requires a global lock on every transaction creation and is meant to be used only
+//! for debugging purposes.
+//!
+//! # Example
+//!
+//! ```rust
+//! use yrs::{Doc, Transact};
+//!
+//! let doc = Doc::new();
+//! let txn = doc.transact();
+//! let active = doc.active_transactions();
+//! assert_eq!(active.len(), 1);
+//! println!("{}", active[0]); // read-only transaction created at src/main.rs:5:11 on thread 'main'
+//! drop(txn);
+//! assert!(doc.active_transactions().is_empty());
+//! ```
+
+use crate::doc::DocAddr;
+use crate::Doc;
+use std::collections::BTreeMap;
+use std::fmt::Formatter;
+use std::panic::Location;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+static ACTIVE: Mutex<BTreeMap<(DocAddr, u64), ActiveTransaction>> = Mutex::new(BTreeMap::new());
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Kind of an active transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransactionKind {
+    ReadOnly,
+    ReadWrite,
+}
+
+/// Information about a transaction, which is currently active.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActiveTransaction {
+    /// Kind of a transaction.
+    pub kind: TransactionKind,
+    /// Source code location where transaction was created.
+    pub location: &'static Location<'static>,
+    /// Name of a thread which created transaction, if it had any.
+    pub thread: Option<String>,
+}
+
+impl std::fmt::Display for ActiveTransaction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.kind {
+            TransactionKind::ReadOnly => write!(f, "read-only")?,
+            TransactionKind::ReadWrite => write!(f, "read-write")?,
+        }
+        write!(f, " transaction created at {}", self.location)?;
+        if let Some(thread) = &self.thread {
+            write!(f, " on thread '{}'", thread)?;
+        }
+        Ok(())
+    }
+}
+
+/// Registration of an active transaction, which is removed once guard is dropped.
+#[derive(Debug)]
+pub(crate) struct BorrowGuard {
+    key: (DocAddr, u64),
+}
+
+impl BorrowGuard {
+    #[track_caller]
+    pub(crate) fn new(doc: &Doc, kind: TransactionKind) -> Self {
+        let key = (DocAddr::new(doc), NEXT_ID.fetch_add(1, Ordering::Relaxed));
+        let info = ActiveTransaction {
+            kind,
+            location: Location::caller(),
+            thread: std::thread::current().name().map(String::from),
+        };
+        ACTIVE.lock().unwrap().insert(key, info);
+        BorrowGuard { key }
+    }
+}
+
+impl Drop for BorrowGuard {
+    fn drop(&mut self) {
+        if let Ok(mut active) = ACTIVE.lock() {
+            active.remove(&self.key);
+        }
+    }
+}
+
+/// Returns all transactions of a given `doc`, which are currently active.
+pub(crate) fn active_transactions(doc: &Doc) -> Vec<ActiveTransaction> {
+    let addr = DocAddr::new(doc);
+    let active = ACTIVE.lock().unwrap();
+    active
+        .range((addr, 0)..=(addr, u64::MAX))
+        .map(|(_, info)| info.clone())
+        .collect()
+}
+
+/// Panics with an `error` message extended with a list of transactions active in a given `doc`.
+#[track_caller]
+pub(crate) fn acquisition_failed<E: std::fmt::Display>(doc: &Doc, error: E) -> ! {
+    let mut msg = error.to_string();
+    let active = active_transactions(doc);
+    if !active.is_empty() {
+        msg.push_str(" Active transactions:");
+        for info in active {
+            msg.push_str("\n - ");
+            msg.push_str(&info.to_string());
+        }
+    }
+    panic!("{}", msg)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::borrow_tracker::TransactionKind;
+    use crate::{Doc, Transact};
+    use std::panic::AssertUnwindSafe;
+
+    #[test]
+    fn active_transactions() {
+        let doc = Doc::new();
+        let other = Doc::new();
+        let _other_txn = other.transact_mut();
+
+        let t1 = doc.transact();
+        let t2 = doc.transact();
+        let active = doc.active_transactions();
+        assert_eq!(active.len(), 2);
+        assert_eq!(active[0].kind, TransactionKind::ReadOnly);
+        assert_eq!(active[0].location.file(), file!());
+        assert_eq!(active[0].location.line() + 1, active[1].location.line());
+
+        let e = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            let _ = doc.transact_mut();
+        }))
+        .unwrap_err();
+        let msg = e.downcast_ref::<String>().unwrap();
+        assert!(msg.contains(&format!("{}:{}", file!(), active[0].location.line())));
+
+        drop(t1);
+        drop(t2);
+        let _txn = doc.transact_mut();
+        let active = doc.active_transactions();
+        assert_eq!(active.len(), 1);
+        assert_eq!(active[0].kind, TransactionKind::ReadWrite);
+    }
+}
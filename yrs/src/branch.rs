@@ -1,14 +1,16 @@
 use crate::block::{BlockCell, Item, ItemContent, ItemPosition, ItemPtr, Prelim};
-use crate::types::array::ArrayEvent;
-use crate::types::map::MapEvent;
+use crate::block_index::{BlockIndex, IndexedItem, INDEX_THRESHOLD};
+use crate::types::array::{ArrayEvent, ArrayIter};
+use crate::types::counter::{counter_value, CounterEvent};
+use crate::types::map::{MapEvent, MapIter};
 use crate::types::text::TextEvent;
 use crate::types::xml::{XmlEvent, XmlTextEvent};
 use crate::types::{
-    Entries, Event, Events, Path, PathSegment, RootRef, SharedRef, TypePtr, TypeRef,
+    Entries, Event, Events, Path, PathFilter, PathSegment, RootRef, SharedRef, TypePtr, TypeRef,
 };
 use crate::{
-    ArrayRef, Doc, MapRef, Observer, Origin, Out, ReadTxn, Subscription, TextRef, TransactionMut,
-    WriteTxn, XmlElementRef, XmlFragmentRef, XmlTextRef, ID,
+    Any, ArrayRef, Doc, MapRef, Observer, OffsetKind, Origin, Out, ReadTxn, Subscription, TextRef,
+    TransactionMut, WriteTxn, XmlElementRef, XmlFragmentRef, XmlTextRef, ID,
 };
 use serde::{Deserialize, Serialize};
 use std::borrow::Borrow;
@@ -37,12 +39,32 @@ impl BranchPtr {
         subs: HashSet<Option<Arc<str>>>,
     ) -> Option<Event> {
         let e = self.make_event(subs)?;
-        self.observers.trigger(|fun| fun(txn, &e));
+        self.observers.trigger(|fun| txn.isolate(|| fun(txn, &e)));
         Some(e)
     }
 
-    pub(crate) fn trigger_deep(&self, txn: &TransactionMut, e: &Events) {
-        self.deep_observers.trigger(|fun| fun(txn, e));
+    pub(crate) fn trigger_deep(&self, txn: &TransactionMut, events: &[&Event]) {
+        if self.deep_observers.has_subscribers() {
+            let e = Events::new(events);
+            self.deep_observers
+                .trigger(|fun| txn.isolate(|| fun(txn, &e)));
+        }
+        self.filtered_deep_observers.trigger(|observer| {
+            let matching: Vec<&Event> = events
+                .iter()
+                .filter(|e| observer.filter.matches(txn, e))
+                .copied()
+                .collect();
+            if !matching.is_empty() {
+                let e = Events::new(&matching);
+                txn.isolate(|| (observer.callback)(txn, &e));
+            }
+        });
+    }
+
+    /// Checks if there are any deep observers (filtered or not) subscribed to a current branch.
+    pub(crate) fn has_deep_observers(&self) -> bool {
+        self.deep_observers.has_subscribers() || self.filtered_deep_observers.has_subscribers()
     }
 }
 
@@ -124,9 +146,14 @@ impl<'a> From<&'a Branch> for BranchPtr {
 impl Into<Out> for BranchPtr {
     /// Converts current branch data into a [Out]. It uses a type ref information to resolve,
     /// which value variant is a correct one for this branch. Since branch represent only complex
-    /// types [Out::Any] will never be returned from this method.
+    /// types [Out::Any] will never be returned from this method, with an exception of counters,
+    /// which are read as plain numbers, sets, which are read as arrays of their elements, and
+    /// fixed-layout structs, which are read as arrays of their fields.
     fn into(self) -> Out {
         match self.type_ref() {
+            TypeRef::Counter => Out::Any(Any::Number(counter_value(&self))),
+            TypeRef::GSet | TypeRef::TwoPhaseSet => Out::Any(crate::types::set::set_value(&self)),
+            TypeRef::Fixed => Out::Any(crate::types::fixed::fixed_value(&self)),
             TypeRef::Array => Out::YArray(ArrayRef::from(self)),
             TypeRef::Map => Out::YMap(MapRef::from(self)),
             TypeRef::Text => Out::YText(TextRef::from(self)),
@@ -200,18 +227,36 @@ pub struct Branch {
     /// For root-level types, this is a name of a branch.
     pub(crate) name: Option<Arc<str>>,
 
-    /// A length of an indexed sequence component of a current branch node. Map component elements
-    /// are computed on demand.
+    /// A length of an indexed sequence component of a current branch node. Number of entries of
+    /// a map component is stored separately in [Branch::map_len].
+    ///
+    /// This length is expressed in units used by Yjs block clocks: a number of elements for arrays
+    /// and XML nodes, and a number of UTF-16 code units for texts. See: [Branch::len].
     pub block_len: u32,
 
+    /// A length of an indexed sequence component of a current branch node expressed in units
+    /// configured by [Options::offset_kind] of a document this branch belongs to. These are the
+    /// units used by index-based methods of shared types. See: [Branch::content_len].
+    ///
+    /// [Options::offset_kind]: crate::Options::offset_kind
     pub content_len: u32,
 
+    /// A number of non-deleted entries of a map component of a current branch node. Maintained
+    /// when map entries are integrated and deleted.
+    pub(crate) map_len: u32,
+
     /// An identifier of an underlying complex data type (eg. is it an Array or a Map).
     pub(crate) type_ref: TypeRef,
 
     pub(crate) observers: Observer<ObserveFn>,
 
     pub(crate) deep_observers: Observer<DeepObserveFn>,
+
+    pub(crate) filtered_deep_observers: Observer<FilteredDeepObserver>,
+
+    /// An auxiliary index over the indexed sequence component of this branch node, maintained
+    /// once the sequence grows over [INDEX_THRESHOLD] elements.
+    pub(crate) index: Option<Box<BlockIndex>>,
 }
 
 #[cfg(feature = "sync")]
@@ -224,6 +269,18 @@ type ObserveFn = Box<dyn Fn(&TransactionMut, &Event) + 'static>;
 #[cfg(not(feature = "sync"))]
 type DeepObserveFn = Box<dyn Fn(&TransactionMut, &Events) + 'static>;
 
+/// Deep observer callback, which is only called with events matching its path filter.
+pub(crate) struct FilteredDeepObserver {
+    filter: PathFilter,
+    callback: DeepObserveFn,
+}
+
+impl FilteredDeepObserver {
+    pub(crate) fn new(filter: PathFilter, callback: DeepObserveFn) -> Self {
+        FilteredDeepObserver { filter, callback }
+    }
+}
+
 impl std::fmt::Debug for Branch {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         std::fmt::Display::fmt(self, f)
@@ -249,11 +306,14 @@ impl Branch {
             map: HashMap::default(),
             block_len: 0,
             content_len: 0,
+            map_len: 0,
             item: None,
             name: None,
             type_ref,
             observers: Observer::default(),
             deep_observers: Observer::default(),
+            filtered_deep_observers: Observer::default(),
+            index: None,
         })
     }
 
@@ -294,28 +354,123 @@ impl Branch {
         }
     }
 
+    /// Checks if accessing current branch as a given `type_ref` would require coercing its type.
+    /// Returns `None` if no coercion is necessary.
+    pub(crate) fn type_repair(&self, type_ref: &TypeRef) -> Option<TypeRepair> {
+        if *type_ref == TypeRef::Undefined || self.type_ref == *type_ref {
+            return None;
+        }
+        let conflict = match (&self.type_ref, type_ref) {
+            // type of a root defined by remote update is unknown, but we can tell it apart
+            // based on its contents
+            (TypeRef::Undefined, TypeRef::Map) => self.start.is_some(),
+            (TypeRef::Undefined, TypeRef::Array | TypeRef::Text | TypeRef::XmlFragment) => {
+                !self.map.is_empty()
+            }
+            (TypeRef::Undefined, _) => false,
+            _ => true,
+        };
+        Some(TypeRepair {
+            name: self.name.clone()?,
+            defined: self.type_ref.clone(),
+            requested: type_ref.clone(),
+            conflict,
+        })
+    }
+
     /// Returns a length of an indexed sequence component of a current branch node.
     /// Map component elements are computed on demand.
+    ///
+    /// For arrays and XML nodes this is a number of elements, while for texts it's a number of
+    /// UTF-16 code units (embeds count as a single unit), no matter what [OffsetKind] is used by
+    /// a document. This length is the same for all peers.
     pub fn len(&self) -> u32 {
         self.block_len
     }
 
+    /// Returns a length of an indexed sequence component of a current branch node, expressed in
+    /// units configured by [Options::offset_kind] of a document this branch belongs to. Indexes
+    /// accepted and returned by methods of shared types are using the same units.
+    ///
+    /// Unlike [Branch::len], for texts this length may differ between peers using different
+    /// [OffsetKind]s. Use [Branch::len_with] to get a length in specific units.
+    ///
+    /// [Options::offset_kind]: crate::Options::offset_kind
     pub fn content_len(&self) -> u32 {
         self.content_len
     }
 
+    /// Returns a length of an indexed sequence component of a current branch node, expressed in
+    /// units of a given offset `kind`. This length doesn't depend on a document configuration, so
+    /// it can be used to translate indexes between peers using different [OffsetKind]s.
+    ///
+    /// Lengths in [OffsetKind::Utf16] are equal to [Branch::len] and don't require iteration,
+    /// other kinds are computed by visiting all blocks of a branch.
+    pub fn len_with(&self, kind: OffsetKind) -> u32 {
+        match kind {
+            OffsetKind::Utf16 => self.block_len,
+            kind => self
+                .visible_items()
+                .map(|item| item.content_len(kind))
+                .sum(),
+        }
+    }
+
+    /// Returns a number of Unicode scalar values (Rust [char]s) of an indexed sequence component
+    /// of a current branch node. Elements other than text chunks are counted as a single unit.
+    pub fn char_count(&self) -> u32 {
+        self.visible_items()
+            .map(|item| match &item.content {
+                ItemContent::String(s) => s.as_str().chars().count() as u32,
+                _ => item.len(),
+            })
+            .sum()
+    }
+
+    /// Returns a number of blocks used to store non-deleted elements of an indexed sequence
+    /// component of a current branch node. A single block may contain many elements, i.e. chunks
+    /// of text or consecutive array elements inserted by the same peer.
+    pub fn block_count(&self) -> u32 {
+        self.visible_items().count() as u32
+    }
+
+    fn visible_items(&self) -> impl Iterator<Item = ItemPtr> {
+        let mut next = self.start;
+        std::iter::from_fn(move || {
+            let item = next?;
+            next = item.right;
+            Some(item)
+        })
+        .filter(|item| !item.is_deleted() && item.is_countable())
+    }
+
     /// Get iterator over (String, Block) entries of a map component of a current root type.
     /// Deleted blocks are skipped by this iterator.
-    pub(crate) fn entries<'a, T: ReadTxn + 'a>(&'a self, txn: &'a T) -> Entries<'a, &'a T, T> {
+    pub(crate) fn item_entries<'a, T: ReadTxn + 'a>(&'a self, txn: &'a T) -> Entries<'a, &'a T, T> {
         Entries::from_ref(&self.map, txn)
     }
 
     /// Get iterator over Block entries of an array component of a current root type.
-    /// Deleted blocks are skipped by this iterator.
-    pub(crate) fn iter<'a, T: ReadTxn + 'a>(&'a self, txn: &'a T) -> Iter<'a, T> {
+    /// Deleted blocks are not skipped by this iterator.
+    pub(crate) fn items<'a, T: ReadTxn + 'a>(&'a self, txn: &'a T) -> Iter<'a, T> {
         Iter::new(self.start.as_ref(), txn)
     }
 
+    /// Returns an unordered iterator over all non-deleted (key, value) entries of a map component
+    /// of a current branch. It works the same way for every shared type - including
+    /// [Out::UndefinedRef] - so it can be used by generic tooling without casting to a concrete
+    /// shared reference first.
+    pub fn entries<'a, T: ReadTxn + 'a>(&'a self, txn: &'a T) -> MapIter<'a, &'a T, T> {
+        MapIter::new(self, txn)
+    }
+
+    /// Returns an iterator over all values stored in an indexed sequence component of a current
+    /// branch. It works the same way for every shared type - including [Out::UndefinedRef] - so
+    /// it can be used by generic tooling without casting to a concrete shared reference first.
+    pub fn iter<'a, T: ReadTxn + 'a>(&'a self, txn: &'a T) -> ArrayIter<&'a T, T> {
+        ArrayIter::from_ref(self, txn)
+    }
+
     /// Returns a materialized value of non-deleted entry under a given `key` of a map component
     /// of a current root type.
     pub(crate) fn get<T: ReadTxn>(&self, _txn: &T, key: &str) -> Option<Out> {
@@ -333,6 +488,10 @@ impl Branch {
     /// If `index` was outside of the array component boundary of current branch node, `None` will
     /// be returned.
     pub(crate) fn get_at(&self, mut index: u32) -> Option<(&ItemContent, usize)> {
+        if let Some(block_index) = self.index.as_deref() {
+            let found = block_index.find_block(index)?;
+            return Some((&found.item.content, (index - found.block_offset) as usize));
+        }
         let mut ptr = self.start.as_ref();
         while let Some(item) = ptr.map(ItemPtr::deref) {
             let len = item.len();
@@ -349,6 +508,62 @@ impl Branch {
         None
     }
 
+    /// Uses a [BlockIndex] of this branch to find an item, which contains a countable element at
+    /// a given `index`. Returns `None` if index is out of bounds or is not available, in which case
+    /// the sequence needs to be traversed instead.
+    pub(crate) fn indexed(&self, index: u32) -> Option<IndexedItem<'_>> {
+        let block_index = self.index.as_deref()?;
+        if block_index.is_usable() {
+            block_index.find(index)
+        } else {
+            None
+        }
+    }
+
+    /// Updates a [BlockIndex] of this branch after an `item` has been integrated directly after
+    /// its `left` neighbor. Index is built once a sequence grows over [INDEX_THRESHOLD] elements.
+    pub(crate) fn index_inserted(
+        &mut self,
+        left: Option<ItemPtr>,
+        item: ItemPtr,
+        kind: OffsetKind,
+    ) {
+        if let Some(block_index) = self.index.as_deref_mut() {
+            if !block_index.insert_after(left, item) {
+                // index went out of sync with the sequence
+                self.index = None;
+            }
+        } else if self.block_len >= INDEX_THRESHOLD {
+            self.index = Some(Box::new(BlockIndex::build(self, kind)));
+        }
+    }
+
+    /// Updates a [BlockIndex] of this branch after a `left` item has been split in two.
+    pub(crate) fn index_split(&mut self, left: ItemPtr, right: ItemPtr) {
+        if let Some(block_index) = self.index.as_deref_mut() {
+            if block_index.contains(&left) {
+                block_index.update(&left);
+                block_index.insert_after(Some(left), right);
+            }
+        }
+    }
+
+    /// Updates a [BlockIndex] of this branch after a `right` item has been squashed into its
+    /// `left` neighbor.
+    pub(crate) fn index_squashed(&mut self, left: ItemPtr, right: ItemPtr) {
+        if let Some(block_index) = self.index.as_deref_mut() {
+            block_index.remove(&right);
+            block_index.update(&left);
+        }
+    }
+
+    /// Updates a [BlockIndex] of this branch after an `item` has been deleted.
+    pub(crate) fn index_updated(&mut self, item: ItemPtr) {
+        if let Some(block_index) = self.index.as_deref_mut() {
+            block_index.update(&item);
+        }
+    }
+
     /// Removes an entry under given `key` of a map component of a current root type, returning
     /// a materialized representation of value stored underneath if entry existed prior deletion.
     pub(crate) fn remove(&self, txn: &mut TransactionMut, key: &str) -> Option<Out> {
@@ -393,6 +608,18 @@ impl Branch {
         mut index: u32,
     ) -> (Option<ItemPtr>, Option<ItemPtr>) {
         let encoding = txn.store.options.offset_kind;
+        if let Some(start) = ptr {
+            match start.parent {
+                TypePtr::Branch(branch) if index > 0 && branch.start == ptr => {
+                    // skip directly to the block containing the last element before an index
+                    if let Some(found) = branch.indexed(index - 1) {
+                        ptr = Some(*found.item);
+                        index -= found.offset;
+                    }
+                }
+                _ => {}
+            }
+        }
         while let Some(item) = ptr {
             let content_len = item.content_len(encoding);
             if !item.is_deleted() && item.is_countable() {
@@ -621,7 +848,7 @@ impl Branch {
         let self_ptr = BranchPtr::from(self);
         let event = match self.type_ref() {
             TypeRef::Array => Event::Array(ArrayEvent::new(self_ptr)),
-            TypeRef::Map => Event::Map(MapEvent::new(self_ptr, keys)),
+            TypeRef::Map | TypeRef::Fixed => Event::Map(MapEvent::new(self_ptr, keys)),
             TypeRef::Text => Event::Text(TextEvent::new(self_ptr)),
             TypeRef::XmlElement(_) | TypeRef::XmlFragment => {
                 Event::XmlFragment(XmlEvent::new(self_ptr, keys))
@@ -629,6 +856,10 @@ impl Branch {
             TypeRef::XmlText => Event::XmlText(XmlTextEvent::new(self_ptr, keys)),
             #[cfg(feature = "weak")]
             TypeRef::WeakLink(_) => Event::Weak(crate::types::weak::WeakEvent::new(self_ptr)),
+            TypeRef::Counter => Event::Counter(CounterEvent::new(self_ptr, keys)),
+            TypeRef::GSet | TypeRef::TwoPhaseSet => {
+                Event::Set(crate::types::set::SetEvent::new(self_ptr, keys))
+            }
             _ => return None,
         };
 
@@ -701,10 +932,32 @@ impl<S: RootRef> Root<S> {
 
     /// Returns a reference to a shared root-level collection current [Root] represents, or creates
     /// it if it wasn't instantiated before.
+    ///
+    /// # Panics
+    ///
+    /// When [Options::strict_types] is enabled, this method panics if collection already exists
+    /// and its type conflicts with `S`. See [Root::try_get_or_create].
+    ///
+    /// [Options::strict_types]: crate::Options::strict_types
     pub fn get_or_create<T: WriteTxn>(&self, txn: &mut T) -> S {
+        match self.try_get_or_create(txn) {
+            Ok(value) => value,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Returns a reference to a shared root-level collection current [Root] represents, or creates
+    /// it if it wasn't instantiated before.
+    ///
+    /// When [Options::strict_types] is enabled, this method returns an error if collection
+    /// already exists and its type conflicts with `S`. Otherwise such collection is reinterpreted
+    /// as `S` and the type change is reported to [Doc::observe_type_repairs] subscribers.
+    ///
+    /// [Options::strict_types]: crate::Options::strict_types
+    pub fn try_get_or_create<T: WriteTxn>(&self, txn: &mut T) -> Result<S, TypeRepair> {
         let store = txn.store_mut();
-        let branch = store.get_or_create_type(self.name.clone(), S::type_ref());
-        S::from(branch)
+        let branch = store.try_get_or_create_type(self.name.clone(), S::type_ref())?;
+        Ok(S::from(branch))
     }
 }
 
@@ -722,6 +975,26 @@ impl<S> Into<BranchID> for Root<S> {
     }
 }
 
+/// Information about a root-level collection, which has been accessed as a type different from
+/// the one it was defined with. It's passed to [Doc::observe_type_repairs] subscribers or returned
+/// as an error by [Root::try_get_or_create] when [Options::strict_types] is enabled.
+///
+/// [Options::strict_types]: crate::Options::strict_types
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("root type `{name}` defined as {defined} has been accessed as {requested}")]
+pub struct TypeRepair {
+    /// Name of the root-level collection.
+    pub name: Arc<str>,
+    /// Type the collection has been defined with. It's [TypeRef::Undefined] for collections
+    /// created by applying remote updates, which don't carry type information.
+    pub defined: TypeRef,
+    /// Type the collection has been accessed as.
+    pub requested: TypeRef,
+    /// If `true`, the requested type conflicts with the defined one, or the contents of
+    /// an undefined collection. Otherwise the type of an undefined collection has been inferred.
+    pub conflict: bool,
+}
+
 /// A logical reference used to represent a shared collection nested within another one. Unlike
 /// [Root]-level types which cannot be deleted and exist eternally, [Nested] collections can be
 /// added (therefore don't exist prior their instantiation) and deleted (so that any [SharedRef]
@@ -0,0 +1,310 @@
+//! Derived values computed from the contents of a document.
+//!
+//! A [Computed] wraps a pure function over a document state (i.e. a word count of a [TextRef]
+//! or a sum of numbers stored in an [ArrayRef]). Its result is cached and reused until one of
+//! the transactions modifies the observed collection, so that the value is not recomputed from
+//! scratch every time it's being read. Interested parties can also subscribe to get notified
+//! whenever a committed transaction changes the computed value.
+//!
+//! [TextRef]: crate::TextRef
+//! [ArrayRef]: crate::ArrayRef
+
+use crate::observer::Observer;
+use crate::types::{DeepObservable, Event, Events, Path, PathSegment};
+use crate::{ReadTxn, Store, Subscription, TransactionMut};
+use std::sync::{Arc, Mutex};
+
+/// Read-only view over a document state passed to the functions of [Computed] values. It
+/// implements [ReadTxn], so it can be used to read the contents of any shared collection.
+pub struct ComputeTxn<'a> {
+    store: &'a Store,
+}
+
+impl<'a> ReadTxn for ComputeTxn<'a> {
+    #[inline]
+    fn store(&self) -> &Store {
+        self.store
+    }
+}
+
+type ComputeFn<T> = Box<dyn Fn(&ComputeTxn) -> T + Send + Sync + 'static>;
+
+type ComputedObserveFn<T> = Box<dyn Fn(&TransactionMut, &T) + Send + Sync + 'static>;
+
+/// A cached value derived from the contents of a shared collection.
+///
+/// The value is computed lazily on the first [Computed::get] call and cached afterwards. Cache
+/// is invalidated when a committed transaction changes the observed collection or any of its
+/// nested collections. Invalidation can be narrowed down to specific paths within observed
+/// collection with [Computed::with_paths].
+///
+/// Callbacks registered with [Computed::observe] are called after a transaction commit whenever
+/// the value has changed. While there are any callbacks subscribed, the value is recomputed
+/// eagerly as part of the commit.
+///
+/// Cache is invalidated only once a transaction is committed. Reading a computed value from
+/// within a transaction, which has already modified the observed collection, may return a value
+/// from before these changes.
+///
+/// # Example
+///
+/// ```rust
+/// use yrs::computed::Computed;
+/// use yrs::{Doc, GetString, Text, Transact};
+///
+/// let doc = Doc::new();
+/// let text = doc.get_or_insert_text("text");
+/// let words = Computed::new(&text, {
+///     let text = text.clone();
+///     move |txn| text.get_string(txn).split_whitespace().count()
+/// });
+///
+/// text.insert(&mut doc.transact_mut(), 0, "hello world");
+/// assert_eq!(*words.get(&doc.transact()), 2);
+/// assert!(words.is_cached());
+///
+/// text.push(&mut doc.transact_mut(), " again");
+/// assert!(!words.is_cached());
+/// assert_eq!(*words.get(&doc.transact()), 3);
+/// ```
+pub struct Computed<T> {
+    inner: Arc<Inner<T>>,
+    _subscription: Subscription,
+}
+
+struct Inner<T> {
+    compute: ComputeFn<T>,
+    paths: Vec<Path>,
+    cache: Mutex<Option<Arc<T>>>,
+    observers: Observer<ComputedObserveFn<T>>,
+}
+
+impl<T> Computed<T>
+where
+    T: PartialEq + Send + Sync + 'static,
+{
+    /// Creates a new computed value using function `f`, which is invalidated whenever `source`
+    /// collection or any of its nested collections has changed.
+    pub fn new<S, F>(source: &S, f: F) -> Self
+    where
+        S: DeepObservable,
+        F: Fn(&ComputeTxn) -> T + Send + Sync + 'static,
+    {
+        Self::with_paths(source, Vec::new(), f)
+    }
+
+    /// Creates a new computed value using function `f`, which is invalidated only by changes
+    /// made at or under any of the given `paths`. Paths are relative to a `source` collection.
+    /// Empty list of paths means that any change within `source` invalidates the value.
+    ///
+    /// Changes of the collections on the way to a given path are considered as well, i.e. for
+    /// a path `["user", "name"]` overriding a `"user"` entry in `source` map invalidates the
+    /// value, while changing any other entry of that map does not.
+    pub fn with_paths<S, F>(source: &S, paths: Vec<Path>, f: F) -> Self
+    where
+        S: DeepObservable,
+        F: Fn(&ComputeTxn) -> T + Send + Sync + 'static,
+    {
+        let inner = Arc::new(Inner {
+            compute: Box::new(f),
+            paths,
+            cache: Mutex::new(None),
+            observers: Observer::new(),
+        });
+        let weak = Arc::downgrade(&inner);
+        let subscription = source.observe_deep(move |txn, events| {
+            if let Some(inner) = weak.upgrade() {
+                if inner.is_affected(txn, events) {
+                    inner.invalidate(txn);
+                }
+            }
+        });
+        Computed {
+            inner,
+            _subscription: subscription,
+        }
+    }
+
+    /// Returns a current value, computing it first if it was not cached already.
+    pub fn get<Tx: ReadTxn>(&self, txn: &Tx) -> Arc<T> {
+        self.inner.get(txn.store())
+    }
+
+    /// Checks if the value is currently cached, meaning that the next [Computed::get] call won't
+    /// have to call the computing function.
+    pub fn is_cached(&self) -> bool {
+        self.inner.cache.lock().unwrap().is_some()
+    }
+
+    /// Drops a cached value, forcing the next [Computed::get] call to compute it again.
+    pub fn invalidate(&self) {
+        self.inner.cache.lock().unwrap().take();
+    }
+
+    /// Subscribes a callback `f`, which is called after a transaction commit whenever
+    /// the computed value has changed. This method returns a subscription, which will
+    /// automatically unsubscribe current callback when dropped.
+    pub fn observe<F>(&self, f: F) -> Subscription
+    where
+        F: Fn(&TransactionMut, &T) + Send + Sync + 'static,
+    {
+        self.inner.observers.subscribe(Box::new(f))
+    }
+}
+
+impl<T> Inner<T>
+where
+    T: PartialEq + 'static,
+{
+    fn get(&self, store: &Store) -> Arc<T> {
+        let mut cache = self.cache.lock().unwrap();
+        match &*cache {
+            Some(value) => value.clone(),
+            None => {
+                let value = Arc::new((self.compute)(&ComputeTxn { store }));
+                *cache = Some(value.clone());
+                value
+            }
+        }
+    }
+
+    fn invalidate(&self, txn: &TransactionMut) {
+        let prev = self.cache.lock().unwrap().take();
+        if self.observers.has_subscribers() {
+            let value = self.get(txn.store());
+            if prev.as_deref() != Some(&*value) {
+                self.observers.trigger(|f| f(txn, &value));
+            }
+        }
+    }
+
+    fn is_affected(&self, txn: &TransactionMut, events: &Events) -> bool {
+        if self.paths.is_empty() {
+            return true;
+        }
+        events.iter().any(|event| {
+            let path = event.path();
+            self.paths
+                .iter()
+                .any(|filter| Self::matches(txn, filter, &path, event))
+        })
+    }
+
+    fn matches(txn: &TransactionMut, filter: &Path, path: &Path, event: &Event) -> bool {
+        if !path.iter().zip(filter.iter()).all(|(a, b)| a == b) {
+            false
+        } else if path.len() >= filter.len() {
+            // change happened at or under the filtered path
+            true
+        } else {
+            // change happened in one of the ancestors of the filtered path
+            match (&filter[path.len()], event) {
+                (PathSegment::Key(key), Event::Map(e)) => e.keys(txn).contains_key(key),
+                _ => true,
+            }
+        }
+    }
+}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for Computed<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Computed")
+            .field("paths", &self.inner.paths)
+            .field("cached", &*self.inner.cache.lock().unwrap())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::computed::Computed;
+    use crate::types::{Path, PathSegment};
+    use crate::{Array, Doc, Map, MapPrelim, MapRef, Out, Transact};
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn recompute_only_after_change() {
+        let doc = Doc::with_client_id(1);
+        let array = doc.get_or_insert_array("array");
+        let calls = Arc::new(AtomicU32::new(0));
+        let sum = Computed::new(&array, {
+            let array = array.clone();
+            let calls = calls.clone();
+            move |txn| {
+                calls.fetch_add(1, Ordering::SeqCst);
+                array
+                    .iter(txn)
+                    .filter_map(|v| v.cast::<f64>().ok())
+                    .sum::<f64>()
+            }
+        });
+
+        array.insert_range(&mut doc.transact_mut(), 0, [1, 2, 3]);
+        assert_eq!(*sum.get(&doc.transact()), 6.0);
+        assert_eq!(*sum.get(&doc.transact()), 6.0);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        array.push_back(&mut doc.transact_mut(), 4);
+        assert!(!sum.is_cached());
+        assert_eq!(*sum.get(&doc.transact()), 10.0);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn observe_changes() {
+        let doc = Doc::with_client_id(1);
+        let map = doc.get_or_insert_map("map");
+        let len = Computed::new(&map, {
+            let map = map.clone();
+            move |txn| map.len(txn)
+        });
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let _sub = {
+            let seen = seen.clone();
+            len.observe(move |_, value| seen.lock().unwrap().push(*value))
+        };
+
+        map.insert(&mut doc.transact_mut(), "a", 1);
+        map.insert(&mut doc.transact_mut(), "a", 2); // length didn't change
+        map.insert(&mut doc.transact_mut(), "b", 1);
+        assert_eq!(*seen.lock().unwrap(), vec![1, 2]);
+        assert!(len.is_cached());
+    }
+
+    #[test]
+    fn path_filtering() {
+        let doc = Doc::with_client_id(1);
+        let root = doc.get_or_insert_map("root");
+        {
+            let mut txn = doc.transact_mut();
+            root.insert(&mut txn, "user", MapPrelim::from([("name", "Alice")]));
+            root.insert(&mut txn, "other", MapPrelim::from([("name", "Bob")]));
+        }
+        let path: Path = [PathSegment::Key("user".into())].into();
+        let name = Computed::with_paths(&root, vec![path], {
+            let root = root.clone();
+            move |txn| match root.get(txn, "user") {
+                Some(Out::YMap(user)) => user.get(txn, "name").map(|v| v.to_string(txn)),
+                _ => None,
+            }
+        });
+        assert_eq!(*name.get(&doc.transact()), Some("Alice".to_string()));
+
+        {
+            let mut txn = doc.transact_mut();
+            let other: MapRef = root.get(&txn, "other").unwrap().cast().unwrap();
+            other.insert(&mut txn, "name", "Carol");
+            root.insert(&mut txn, "count", 1);
+        }
+        assert!(name.is_cached());
+
+        {
+            let mut txn = doc.transact_mut();
+            let user: MapRef = root.get(&txn, "user").unwrap().cast().unwrap();
+            user.insert(&mut txn, "name", "Dave");
+        }
+        assert!(!name.is_cached());
+        assert_eq!(*name.get(&doc.transact()), Some("Dave".to_string()));
+    }
+}
@@ -0,0 +1,285 @@
+//! Document wrapper which can be shared and used concurrently by multiple threads.
+//!
+//! [Doc] transactions are guarded by a non-blocking borrow checker: trying to start a transaction
+//! while another thread holds a conflicting one fails with [TransactionAcqError] (or panics in
+//! case of [Transact::transact]/[Transact::transact_mut]). This is fine for a single-threaded
+//! environment, but forces multi-threaded servers to wrap every document in a mutex of their own.
+//! [ConcurrentDoc] does that internally: it blocks the calling thread until a requested
+//! transaction can be acquired.
+
+use crate::block_iter::CursorCache;
+use crate::doc::TransactionAcqError;
+use crate::transaction::{Origin, Subdocs};
+use crate::{Doc, ReadTxn, Store, Transact, Transaction, TransactionMut, WriteTxn};
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard, TryLockError};
+
+/// A [Doc] wrapper, which can be safely shared between threads. Transactions created by it block
+/// the current thread until they can be acquired: any number of read-only transactions can be
+/// active at the same time, while read-write transaction requires exclusive access.
+///
+/// Cloning a [ConcurrentDoc] creates another handle to the same document and lock. All access to
+/// a document shared this way should go through its [ConcurrentDoc] handles - transactions created
+/// directly on an underlying [Doc] are not synchronized.
+///
+/// Callbacks subscribed to a document or its collections are called while the read-write
+/// transaction is being committed. They must not try to start new transactions on the same
+/// [ConcurrentDoc], as that would deadlock. Use [TransactionMut::defer] instead.
+///
+/// # Example
+///
+/// ```rust
+/// use yrs::concurrent::ConcurrentDoc;
+/// use yrs::{Doc, Map, WriteTxn};
+///
+/// let doc = ConcurrentDoc::new(Doc::new());
+/// let map = doc.transact_mut().get_or_insert_map("counters");
+///
+/// let handles: Vec<_> = (0..4)
+///     .map(|i| {
+///         let doc = doc.clone();
+///         let map = map.clone();
+///         std::thread::spawn(move || {
+///             let mut txn = doc.transact_mut();
+///             map.insert(&mut txn, format!("thread-{}", i), i);
+///         })
+///     })
+///     .collect();
+/// for handle in handles {
+///     handle.join().unwrap();
+/// }
+///
+/// assert_eq!(map.len(&doc.transact()), 4);
+/// ```
+#[derive(Debug, Clone)]
+pub struct ConcurrentDoc {
+    doc: Doc,
+    lock: Arc<RwLock<()>>,
+}
+
+impl ConcurrentDoc {
+    /// Wraps a given document.
+    pub fn new(doc: Doc) -> Self {
+        ConcurrentDoc {
+            doc,
+            lock: Arc::new(RwLock::new(())),
+        }
+    }
+
+    /// Creates a read-only transaction, blocking current thread until all read-write transactions
+    /// have been committed.
+    pub fn transact(&self) -> ReadGuard<'_> {
+        let lock = self.lock.read().unwrap_or_else(|e| e.into_inner());
+        ReadGuard {
+            txn: self.doc.transact(),
+            _lock: lock,
+        }
+    }
+
+    /// Creates a read-write transaction, blocking current thread until all other transactions
+    /// have been committed.
+    pub fn transact_mut(&self) -> WriteGuard<'_> {
+        let lock = self.lock.write().unwrap_or_else(|e| e.into_inner());
+        WriteGuard {
+            txn: self.doc.transact_mut(),
+            _lock: lock,
+        }
+    }
+
+    /// Creates a read-write transaction with an `origin` classifier attached, blocking current
+    /// thread until all other transactions have been committed.
+    pub fn transact_mut_with<T>(&self, origin: T) -> WriteGuard<'_>
+    where
+        T: Into<Origin>,
+    {
+        let lock = self.lock.write().unwrap_or_else(|e| e.into_inner());
+        WriteGuard {
+            txn: self.doc.transact_mut_with(origin),
+            _lock: lock,
+        }
+    }
+
+    /// Creates a read-only transaction without blocking. Returns an error if there's a read-write
+    /// transaction active at the moment.
+    pub fn try_transact(&self) -> Result<ReadGuard<'_>, TransactionAcqError> {
+        let lock = match self.lock.try_read() {
+            Ok(lock) => lock,
+            Err(TryLockError::Poisoned(e)) => e.into_inner(),
+            Err(TryLockError::WouldBlock) => return Err(TransactionAcqError::SharedAcqFailed),
+        };
+        Ok(ReadGuard {
+            txn: self.doc.try_transact()?,
+            _lock: lock,
+        })
+    }
+
+    /// Creates a read-write transaction without blocking. Returns an error if there's any other
+    /// transaction active at the moment.
+    pub fn try_transact_mut(&self) -> Result<WriteGuard<'_>, TransactionAcqError> {
+        let lock = match self.lock.try_write() {
+            Ok(lock) => lock,
+            Err(TryLockError::Poisoned(e)) => e.into_inner(),
+            Err(TryLockError::WouldBlock) => return Err(TransactionAcqError::ExclusiveAcqFailed),
+        };
+        Ok(WriteGuard {
+            txn: self.doc.try_transact_mut()?,
+            _lock: lock,
+        })
+    }
+
+    /// Calls a given function with exclusive access to an underlying [Doc]. It should be used for
+    /// operations which require exclusive access to a document store, but are not performed in
+    /// a scope of transaction - i.e. subscribing callbacks with [Doc::observe_update_v1].
+    pub fn with_doc<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&Doc) -> R,
+    {
+        let _lock = self.lock.write().unwrap_or_else(|e| e.into_inner());
+        f(&self.doc)
+    }
+}
+
+impl From<Doc> for ConcurrentDoc {
+    fn from(doc: Doc) -> Self {
+        ConcurrentDoc::new(doc)
+    }
+}
+
+/// Read-only [Transaction] created by [ConcurrentDoc::transact]. It releases the lock once dropped.
+pub struct ReadGuard<'doc> {
+    // declared first, so that a transaction is dropped before the lock is released
+    txn: Transaction<'doc>,
+    _lock: RwLockReadGuard<'doc, ()>,
+}
+
+impl<'doc> Deref for ReadGuard<'doc> {
+    type Target = Transaction<'doc>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.txn
+    }
+}
+
+impl<'doc> ReadTxn for ReadGuard<'doc> {
+    #[inline]
+    fn store(&self) -> &Store {
+        self.txn.store()
+    }
+
+    #[inline]
+    fn cursors(&self) -> Option<&CursorCache> {
+        self.txn.cursors()
+    }
+}
+
+/// Read-write [TransactionMut] created by [ConcurrentDoc::transact_mut]. It's committed and
+/// releases the lock once dropped.
+pub struct WriteGuard<'doc> {
+    // declared first, so that a transaction is committed before the lock is released
+    txn: TransactionMut<'doc>,
+    _lock: RwLockWriteGuard<'doc, ()>,
+}
+
+impl<'doc> Deref for WriteGuard<'doc> {
+    type Target = TransactionMut<'doc>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.txn
+    }
+}
+
+impl<'doc> DerefMut for WriteGuard<'doc> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.txn
+    }
+}
+
+impl<'doc> ReadTxn for WriteGuard<'doc> {
+    #[inline]
+    fn store(&self) -> &Store {
+        self.txn.store()
+    }
+
+    #[inline]
+    fn cursors(&self) -> Option<&CursorCache> {
+        self.txn.cursors()
+    }
+}
+
+impl<'doc> WriteTxn for WriteGuard<'doc> {
+    #[inline]
+    fn store_mut(&mut self) -> &mut Store {
+        self.txn.store_mut()
+    }
+
+    #[inline]
+    fn subdocs_mut(&mut self) -> &mut Subdocs {
+        self.txn.subdocs_mut()
+    }
+
+    #[inline]
+    fn origin(&self) -> Option<&Origin> {
+        self.txn.origin()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::concurrent::ConcurrentDoc;
+    use crate::doc::TransactionAcqError;
+    use crate::{Array, Doc, GetString, Text, WriteTxn};
+    use std::sync::{Arc, Barrier};
+
+    #[test]
+    fn concurrent_writers() {
+        let doc = ConcurrentDoc::new(Doc::with_client_id(1));
+        let array = doc.transact_mut().get_or_insert_array("array");
+        let barrier = Arc::new(Barrier::new(8));
+        let handles: Vec<_> = (0..8)
+            .map(|i| {
+                let doc = doc.clone();
+                let array = array.clone();
+                let barrier = barrier.clone();
+                std::thread::spawn(move || {
+                    barrier.wait();
+                    for j in 0..100 {
+                        if j % 2 == 0 {
+                            let mut txn = doc.transact_mut();
+                            array.push_back(&mut txn, i * 100 + j);
+                        } else {
+                            let txn = doc.transact();
+                            assert!(array.len(&txn) > 0);
+                        }
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(array.len(&doc.transact()), 400);
+    }
+
+    #[test]
+    fn try_transact() {
+        let doc = ConcurrentDoc::new(Doc::with_client_id(1));
+        let text = doc.transact_mut().get_or_insert_text("text");
+        {
+            let r1 = doc.try_transact().unwrap();
+            let _r2 = doc.try_transact().unwrap();
+            assert!(matches!(
+                doc.try_transact_mut(),
+                Err(TransactionAcqError::ExclusiveAcqFailed)
+            ));
+            assert_eq!(text.get_string(&r1), "");
+        }
+        let mut txn = doc.try_transact_mut().unwrap();
+        text.push(&mut txn, "hello");
+        assert!(matches!(
+            doc.try_transact(),
+            Err(TransactionAcqError::SharedAcqFailed)
+        ));
+        drop(txn);
+        assert_eq!(text.get_string(&doc.transact()), "hello");
+    }
+}
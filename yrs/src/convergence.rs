@@ -0,0 +1,318 @@
+//! Utilities for writing convergence tests.
+//!
+//! [Schedule] describes operations performed concurrently by a number of simulated peers, with
+//! explicit points at which (some of) the peers exchange their updates. Once executed, all peers
+//! are fully synchronized and their documents are compared with each other. The [ops] macro
+//! provides a shorter syntax for the same schedules.
+//!
+//! # Example
+//!
+//! ```rust
+//! use yrs::convergence::Schedule;
+//! use yrs::{GetString, Text, Transact, WriteTxn};
+//!
+//! let docs = Schedule::new(3)
+//!     .op(0, |txn| txn.get_or_insert_text("text").push(txn, "hello"))
+//!     .sync()
+//!     .op(1, |txn| txn.get_or_insert_text("text").insert(txn, 0, "A"))
+//!     .op(2, |txn| txn.get_or_insert_text("text").push(txn, "B"))
+//!     .sync_peers(&[0, 1])
+//!     .op(0, |txn| txn.get_or_insert_text("text").insert(txn, 1, "C"))
+//!     .assert_converged();
+//!
+//! let text = docs[0].get_or_insert_text("text");
+//! assert_eq!(text.get_string(&docs[0].transact()), "AChelloB");
+//! ```
+//!
+//! [ops]: crate::ops
+
+use crate::block::ClientID;
+use crate::transaction::TransactionMut;
+use crate::types::{ToJson, TypeRef};
+use crate::updates::decoder::Decode;
+use crate::{Any, Doc, ReadTxn, Transact, Update};
+use std::collections::HashMap;
+use std::fmt::Formatter;
+use std::sync::Arc;
+use thiserror::Error;
+
+/// Origin of transactions used by [Schedule] to exchange updates between simulated peers.
+pub const SYNC_ORIGIN: &str = "convergence-sync";
+
+type Operation = Box<dyn FnOnce(&mut TransactionMut)>;
+
+enum Step {
+    Op(usize, Operation),
+    Sync(Vec<usize>),
+}
+
+/// A schedule of operations performed by simulated peers. Each peer is a separate [Doc] instance.
+/// Operations are executed in the order in which they were defined, but peers only see each
+/// other's changes after they have been synchronized with [Schedule::sync] or
+/// [Schedule::sync_peers].
+pub struct Schedule {
+    docs: Vec<Doc>,
+    steps: Vec<Step>,
+}
+
+impl Schedule {
+    /// Creates a new schedule for a given number of `peers`. Peers are identified by their index,
+    /// while their documents use client identifiers starting from 1.
+    pub fn new(peers: usize) -> Self {
+        let docs = (1..=peers)
+            .map(|i| Doc::with_client_id(i as ClientID))
+            .collect();
+        Self::with_docs(docs)
+    }
+
+    /// Creates a new schedule for peers using given documents.
+    pub fn with_docs(docs: Vec<Doc>) -> Self {
+        Schedule {
+            docs,
+            steps: Vec::new(),
+        }
+    }
+
+    /// Returns a number of peers participating in this schedule.
+    pub fn peers(&self) -> usize {
+        self.docs.len()
+    }
+
+    /// Schedules an operation `f` to be executed by a given `peer` within its own transaction.
+    pub fn op<F>(mut self, peer: usize, f: F) -> Self
+    where
+        F: FnOnce(&mut TransactionMut) + 'static,
+    {
+        assert!(peer < self.docs.len(), "peer {} doesn't exist", peer);
+        self.steps.push(Step::Op(peer, Box::new(f)));
+        self
+    }
+
+    /// Schedules a synchronization point, at which all peers exchange their updates.
+    pub fn sync(mut self) -> Self {
+        self.steps.push(Step::Sync((0..self.docs.len()).collect()));
+        self
+    }
+
+    /// Schedules a synchronization point, at which only given `peers` exchange their updates.
+    /// Other peers remain unaware of their changes.
+    pub fn sync_peers(mut self, peers: &[usize]) -> Self {
+        for &peer in peers {
+            assert!(peer < self.docs.len(), "peer {} doesn't exist", peer);
+        }
+        self.steps.push(Step::Sync(peers.to_vec()));
+        self
+    }
+
+    /// Executes all scheduled operations, synchronizes all peers and checks if their documents
+    /// have converged to the same state. On success, documents of all peers are returned.
+    ///
+    /// Documents are compared using their [ToJson] representation, in which text and XML
+    /// collections are represented by their string content.
+    pub fn run(self) -> Result<Vec<Doc>, Divergence> {
+        let docs = self.docs;
+        for step in self.steps {
+            match step {
+                Step::Op(peer, f) => {
+                    let mut txn = docs[peer].transact_mut();
+                    f(&mut txn);
+                }
+                Step::Sync(peers) => sync(&docs, &peers),
+            }
+        }
+        let all: Vec<_> = (0..docs.len()).collect();
+        sync(&docs, &all);
+        define_roots(&docs);
+
+        let mut states = docs.iter().map(|doc| doc.to_json(&doc.transact()));
+        if let Some(expected) = states.next() {
+            for (i, actual) in states.enumerate() {
+                if actual != expected {
+                    return Err(Divergence {
+                        peer: i + 1,
+                        expected,
+                        actual,
+                    });
+                }
+            }
+        }
+        Ok(docs)
+    }
+
+    /// Executes all scheduled operations like [Schedule::run] does.
+    ///
+    /// # Panics
+    ///
+    /// Panics if documents of synchronized peers have not converged to the same state.
+    pub fn assert_converged(self) -> Vec<Doc> {
+        match self.run() {
+            Ok(docs) => docs,
+            Err(e) => panic!("{}", e),
+        }
+    }
+}
+
+impl std::fmt::Debug for Schedule {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Schedule")
+            .field("peers", &self.docs.len())
+            .field("steps", &self.steps.len())
+            .finish()
+    }
+}
+
+/// Error returned by [Schedule::run] when peers didn't converge to the same document state.
+#[derive(Debug, Clone, PartialEq, Error)]
+#[error("peer {peer} diverged from peer 0: expected {expected} but got {actual}")]
+pub struct Divergence {
+    /// Index of a peer, which state is different from the state of the first peer.
+    pub peer: usize,
+    /// State of the first peer.
+    pub expected: Any,
+    /// State of the diverged peer.
+    pub actual: Any,
+}
+
+/// Exchanges updates between all given peers.
+fn sync(docs: &[Doc], peers: &[usize]) {
+    for &i in peers {
+        for &j in peers {
+            if i != j {
+                let update = {
+                    let sv = docs[j].transact().state_vector();
+                    docs[i].transact().encode_state_as_update_v1(&sv)
+                };
+                let mut txn = docs[j].transact_mut_with(SYNC_ORIGIN);
+                txn.apply_update(Update::decode_v1(&update).unwrap());
+            }
+        }
+    }
+}
+
+/// Root types only have their type known to the peers, which have defined them. Make sure that
+/// all peers use the same types, so that they can be compared.
+fn define_roots(docs: &[Doc]) {
+    let mut roots: HashMap<Arc<str>, TypeRef> = HashMap::new();
+    for doc in docs {
+        let txn = doc.transact();
+        for (name, branch) in txn.store().types.iter() {
+            if branch.type_ref != TypeRef::Undefined {
+                roots.insert(name.clone(), branch.type_ref.clone());
+            }
+        }
+    }
+    for doc in docs {
+        let mut txn = doc.transact_mut();
+        for (name, type_ref) in roots.iter() {
+            txn.store_mut()
+                .get_or_create_type(name.clone(), type_ref.clone());
+        }
+    }
+}
+
+/// Executes a [Schedule] of operations performed by a number of simulated peers and asserts that
+/// they have converged to the same state, returning their documents.
+///
+/// A schedule starts with a number of `peers`, followed by a comma separated list of steps:
+///
+/// - `<peer> => <closure>` executes an operation on a given peer within its own transaction.
+/// - `sync` exchanges updates between all peers.
+/// - `sync(<peer>, ...)` exchanges updates only between given peers.
+///
+/// # Example
+///
+/// ```rust
+/// use yrs::{ops, Array, WriteTxn};
+///
+/// let docs = ops! {
+///     peers: 2,
+///     0 => |txn| { txn.get_or_insert_array("array").push_back(txn, 1); },
+///     1 => |txn| { txn.get_or_insert_array("array").push_back(txn, 2); },
+///     sync(0, 1),
+///     0 => |txn| { txn.get_or_insert_array("array").remove(txn, 0); },
+/// };
+/// assert_eq!(docs.len(), 2);
+/// ```
+#[macro_export]
+macro_rules! ops {
+    (peers: $peers:expr $(, $($steps:tt)*)?) => {
+        $crate::ops!(@steps $crate::convergence::Schedule::new($peers); $($($steps)*)?)
+            .assert_converged()
+    };
+    (@steps $schedule:expr; ) => { $schedule };
+    (@steps $schedule:expr; sync ( $($peer:expr),+ ) $(, $($rest:tt)*)?) => {
+        $crate::ops!(@steps $schedule.sync_peers(&[$($peer),+]); $($($rest)*)?)
+    };
+    (@steps $schedule:expr; sync $(, $($rest:tt)*)?) => {
+        $crate::ops!(@steps $schedule.sync(); $($($rest)*)?)
+    };
+    (@steps $schedule:expr; $peer:literal => $op:expr $(, $($rest:tt)*)?) => {
+        $crate::ops!(@steps $schedule.op($peer, $op); $($($rest)*)?)
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use crate::convergence::Schedule;
+    use crate::{any, Array, Doc, GetString, Map, Text, Transact, WriteTxn};
+
+    #[test]
+    fn concurrent_map_and_text() {
+        let docs = Schedule::new(3)
+            .op(0, |txn| {
+                txn.get_or_insert_text("text").push(txn, "abc");
+            })
+            .sync()
+            .op(0, |txn| {
+                txn.get_or_insert_text("text").remove_range(txn, 0, 1)
+            })
+            .op(1, |txn| txn.get_or_insert_text("text").insert(txn, 1, "x"))
+            .op(2, |txn| {
+                txn.get_or_insert_map("map").insert(txn, "key", 1);
+            })
+            .op(1, |txn| {
+                txn.get_or_insert_map("map").insert(txn, "key", 2);
+            })
+            .sync_peers(&[0, 2])
+            .assert_converged();
+
+        for doc in docs.iter() {
+            let text = doc.get_or_insert_text("text");
+            let txn = doc.transact();
+            assert_eq!(text.get_string(&txn), "xbc");
+        }
+    }
+
+    #[test]
+    fn ops_macro() {
+        let docs = ops! {
+            peers: 2,
+            0 => |txn| { txn.get_or_insert_array("array").insert(txn, 0, 1); },
+            1 => |txn| { txn.get_or_insert_array("array").insert(txn, 0, 2); },
+            sync,
+            1 => |txn| { txn.get_or_insert_array("array").push_back(txn, 3); },
+        };
+        let array = docs[1].get_or_insert_array("array");
+        let txn = docs[1].transact();
+        assert_eq!(array.len(&txn), 3);
+        assert_eq!(array.get(&txn, 2), Some(3.into()));
+    }
+
+    #[test]
+    fn divergence() {
+        // peers sharing the same client ID produce colliding updates
+        let docs = vec![Doc::with_client_id(1), Doc::with_client_id(1)];
+        let result = Schedule::with_docs(docs)
+            .op(0, |txn| {
+                txn.get_or_insert_map("map").insert(txn, "key", "a");
+            })
+            .op(1, |txn| {
+                txn.get_or_insert_map("map").insert(txn, "key", "b");
+            })
+            .run();
+        let e = result.unwrap_err();
+        assert_eq!(e.peer, 1);
+        assert_eq!(e.expected, any!({"map": {"key": "a"}}));
+        assert_eq!(e.actual, any!({"map": {"key": "b"}}));
+    }
+}
@@ -0,0 +1,349 @@
+//! Helpers for sharing carets and selections between collaborating peers.
+//!
+//! A [Cursor] is a [StickyIndex] which can be serialized into the JSON format used by Yjs relative
+//! positions (see: `Y.relativePositionToJSON`), so that it can be exchanged with JavaScript peers as
+//! part of their [Awareness] state. Since cursors refer to specific elements rather than numeric
+//! indexes, they stay in place when local or remote updates are applied to a document.
+//!
+//! [Selection] is a pair of cursors - an anchor and a head - following the convention used by
+//! editor bindings like y-prosemirror or y-codemirror.
+//!
+//! # Example
+//!
+//! ```rust
+//! use yrs::cursor::{self, Selection};
+//! use yrs::sync::Awareness;
+//! use yrs::{Doc, Text, Transact};
+//!
+//! let doc = Doc::with_client_id(1);
+//! let txt = doc.get_or_insert_text("text");
+//! txt.insert(&mut doc.transact_mut(), 0, "hello world");
+//!
+//! let mut awareness = Awareness::new(doc.clone());
+//! let selection = Selection::new(&doc.transact(), &txt, 0, 5).unwrap();
+//! cursor::set_local_selection(&mut awareness, "cursor", Some(&selection)).unwrap();
+//!
+//! txt.insert(&mut doc.transact_mut(), 0, ">> ");
+//! let selections = cursor::selections(&awareness, "cursor");
+//! assert_eq!(selections[&1].range(&doc.transact()), Some(3..8));
+//! ```
+
+use crate::block::ClientID;
+use crate::branch::Branch;
+use crate::encoding::read::Error;
+use crate::sync::awareness;
+use crate::sync::Awareness;
+use crate::updates::decoder::{Decode, Decoder};
+use crate::updates::encoder::{Encode, Encoder};
+use crate::{Assoc, IndexScope, ReadTxn, StickyIndex, ID};
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::Arc;
+
+/// A position of a caret inside of a shared sequence, which is not affected by concurrent changes.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Cursor(StickyIndex);
+
+impl Cursor {
+    /// Creates a new cursor placed at a given `index` of a `shared` sequence.
+    /// Returns `None` if `index` is beyond the length of that sequence.
+    pub fn new<T, S>(txn: &T, shared: &S, index: u32, assoc: Assoc) -> Option<Self>
+    where
+        T: ReadTxn,
+        S: AsRef<Branch>,
+    {
+        let index = StickyIndex::at(txn, shared.as_ref().into(), index, assoc)?;
+        Some(Cursor(index))
+    }
+
+    /// Returns an index pointed by this cursor, as of a given transaction. It reflects all updates
+    /// applied so far, including remote ones.
+    ///
+    /// Returns `None` if the sequence this cursor was placed in doesn't exist in a document.
+    pub fn index<T: ReadTxn>(&self, txn: &T) -> Option<u32> {
+        Some(self.0.get_offset(txn)?.index)
+    }
+
+    /// Re-creates this cursor at its current index. If the element this cursor was attached to has
+    /// been deleted, cursor is moved onto an element that currently occupies its position.
+    ///
+    /// Returns `false` if the sequence this cursor was placed in no longer exists.
+    pub fn refresh<T: ReadTxn>(&mut self, txn: &T) -> bool {
+        if let Some(offset) = self.0.get_offset(txn) {
+            let assoc = offset.assoc;
+            if let Some(index) = StickyIndex::at(txn, offset.branch, offset.index, assoc)
+                .or_else(|| StickyIndex::from_type(txn, &offset.branch, assoc).into())
+            {
+                self.0 = index;
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Returns a JSON representation of this cursor, compatible with Yjs relative positions.
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::to_value(self).unwrap()
+    }
+
+    /// Parses a cursor from its JSON representation, compatible with Yjs relative positions.
+    pub fn from_json(json: &serde_json::Value) -> Result<Self, serde_json::Error> {
+        Self::deserialize(json)
+    }
+}
+
+impl From<StickyIndex> for Cursor {
+    fn from(index: StickyIndex) -> Self {
+        Cursor(index)
+    }
+}
+
+impl From<Cursor> for StickyIndex {
+    fn from(cursor: Cursor) -> Self {
+        cursor.0
+    }
+}
+
+impl AsRef<StickyIndex> for Cursor {
+    fn as_ref(&self) -> &StickyIndex {
+        &self.0
+    }
+}
+
+impl Encode for Cursor {
+    fn encode<E: Encoder>(&self, encoder: &mut E) {
+        self.0.encode(encoder)
+    }
+}
+
+impl Decode for Cursor {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, Error> {
+        Ok(Cursor(StickyIndex::decode(decoder)?))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct JsonId {
+    client: ClientID,
+    clock: u32,
+}
+
+/// JSON representation of a Yjs relative position.
+#[derive(Serialize, Deserialize)]
+struct RelativePosition {
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    type_id: Option<JsonId>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tname: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    item: Option<JsonId>,
+    #[serde(default)]
+    assoc: i32,
+}
+
+impl Serialize for Cursor {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut pos = RelativePosition {
+            type_id: None,
+            tname: None,
+            item: None,
+            assoc: if self.0.assoc == Assoc::After { 0 } else { -1 },
+        };
+        match self.0.scope() {
+            IndexScope::Relative(id) => {
+                pos.item = Some(JsonId {
+                    client: id.client,
+                    clock: id.clock,
+                })
+            }
+            IndexScope::Nested(id) => {
+                pos.type_id = Some(JsonId {
+                    client: id.client,
+                    clock: id.clock,
+                })
+            }
+            IndexScope::Root(name) => pos.tname = Some(name.to_string()),
+        }
+        pos.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Cursor {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let pos = RelativePosition::deserialize(deserializer)?;
+        let assoc = if pos.assoc >= 0 {
+            Assoc::After
+        } else {
+            Assoc::Before
+        };
+        let scope = if let Some(id) = pos.item {
+            IndexScope::Relative(ID::new(id.client, id.clock))
+        } else if let Some(name) = pos.tname {
+            IndexScope::Root(Arc::from(name))
+        } else if let Some(id) = pos.type_id {
+            IndexScope::Nested(ID::new(id.client, id.clock))
+        } else {
+            return Err(D::Error::custom(
+                "relative position must define either item, tname or type",
+            ));
+        };
+        Ok(Cursor(StickyIndex::new(scope, assoc)))
+    }
+}
+
+/// A selection made within a shared sequence, described by a pair of cursors. An `anchor` is
+/// a side of a selection that doesn't move when selection is extended, while a `head` is the one
+/// that does. Both cursors are equal when selection is collapsed to a caret.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Selection {
+    pub anchor: Cursor,
+    pub head: Cursor,
+}
+
+impl Selection {
+    /// Creates a new selection spanning between `anchor` and `head` indexes of a `shared` sequence.
+    /// Returns `None` if any of these indexes is beyond the length of that sequence.
+    pub fn new<T, S>(txn: &T, shared: &S, anchor: u32, head: u32) -> Option<Self>
+    where
+        T: ReadTxn,
+        S: AsRef<Branch>,
+    {
+        let anchor = Self::cursor(txn, shared, anchor)?;
+        let head = Self::cursor(txn, shared, head)?;
+        Some(Selection { anchor, head })
+    }
+
+    /// Creates a new selection collapsed to a single caret at a given `index`.
+    pub fn caret<T, S>(txn: &T, shared: &S, index: u32) -> Option<Self>
+    where
+        T: ReadTxn,
+        S: AsRef<Branch>,
+    {
+        let cursor = Self::cursor(txn, shared, index)?;
+        Some(Selection {
+            anchor: cursor.clone(),
+            head: cursor,
+        })
+    }
+
+    fn cursor<T, S>(txn: &T, shared: &S, index: u32) -> Option<Cursor>
+    where
+        T: ReadTxn,
+        S: AsRef<Branch>,
+    {
+        // position at the end of a sequence can only be associated with a preceding element
+        Cursor::new(txn, shared, index, Assoc::After)
+            .or_else(|| Cursor::new(txn, shared, index, Assoc::Before))
+    }
+
+    /// Returns a range of indexes covered by this selection as of a given transaction, regardless
+    /// of its direction.
+    pub fn range<T: ReadTxn>(&self, txn: &T) -> Option<Range<u32>> {
+        let anchor = self.anchor.index(txn)?;
+        let head = self.head.index(txn)?;
+        Some(anchor.min(head)..anchor.max(head))
+    }
+
+    /// Checks if this selection is collapsed to a single caret as of a given transaction.
+    pub fn is_collapsed<T: ReadTxn>(&self, txn: &T) -> bool {
+        self.anchor.index(txn) == self.head.index(txn)
+    }
+}
+
+/// Returns selections stored under a given `field` of awareness states of all clients (including
+/// the local one). States which don't contain a valid selection are skipped.
+pub fn selections(awareness: &Awareness, field: &str) -> HashMap<ClientID, Selection> {
+    let mut result = HashMap::new();
+    for (client_id, json) in awareness.clients() {
+        if let Ok(serde_json::Value::Object(mut state)) = serde_json::from_str(json) {
+            if let Some(value) = state.remove(field) {
+                if let Ok(selection) = Selection::deserialize(value) {
+                    result.insert(*client_id, selection);
+                }
+            }
+        }
+    }
+    result
+}
+
+/// Sets a `selection` under a given `field` of a local awareness state, preserving all other
+/// fields of that state. If `selection` is `None`, the field is set to `null`.
+pub fn set_local_selection(
+    awareness: &mut Awareness,
+    field: &str,
+    selection: Option<&Selection>,
+) -> Result<(), awareness::Error> {
+    let mut state = match awareness.local_state::<serde_json::Value>() {
+        Some(serde_json::Value::Object(state)) => state,
+        _ => serde_json::Map::new(),
+    };
+    let value = match selection {
+        Some(selection) => serde_json::to_value(selection)?,
+        None => serde_json::Value::Null,
+    };
+    state.insert(field.to_string(), value);
+    awareness.set_local_state(state)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cursor::{Cursor, Selection};
+    use crate::updates::decoder::Decode;
+    use crate::updates::encoder::Encode;
+    use crate::{Assoc, Doc, ReadTxn, Text, Transact, Update};
+    use serde_json::json;
+
+    #[test]
+    fn cursor_json() {
+        let doc = Doc::with_client_id(1);
+        let txt = doc.get_or_insert_text("text");
+        let txn = doc.transact();
+        let cursor = Cursor::new(&txn, &txt, 0, Assoc::After);
+        assert!(cursor.is_none(), "text is empty");
+        let cursor = Cursor::new(&txn, &txt, 0, Assoc::Before).unwrap();
+        assert_eq!(cursor.to_json(), json!({"tname": "text", "assoc": -1}));
+        drop(txn);
+
+        txt.insert(&mut doc.transact_mut(), 0, "abc");
+        let txn = doc.transact();
+        let cursor = Cursor::new(&txn, &txt, 1, Assoc::After).unwrap();
+        let json = cursor.to_json();
+        assert_eq!(json, json!({"item": {"client": 1, "clock": 1}, "assoc": 0}));
+        assert_eq!(Cursor::from_json(&json).unwrap(), cursor);
+        assert_eq!(Cursor::decode_v1(&cursor.encode_v1()).unwrap(), cursor);
+        assert!(Cursor::from_json(&json!({"assoc": 0})).is_err());
+    }
+
+    #[test]
+    fn cursor_remote_updates() {
+        let d1 = Doc::with_client_id(1);
+        let t1 = d1.get_or_insert_text("text");
+        t1.insert(&mut d1.transact_mut(), 0, "hello world");
+        let d2 = Doc::with_client_id(2);
+        let t2 = d2.get_or_insert_text("text");
+        let exchange = |from: &Doc, to: &Doc| {
+            let sv = to.transact().state_vector();
+            let update = from.transact().encode_state_as_update_v1(&sv);
+            to.transact_mut()
+                .apply_update(Update::decode_v1(&update).unwrap());
+        };
+        exchange(&d1, &d2);
+
+        let selection = Selection::new(&d2.transact(), &t2, 11, 6).unwrap();
+        let json = serde_json::to_value(&selection).unwrap();
+
+        t1.insert(&mut d1.transact_mut(), 0, ">> ");
+        exchange(&d1, &d2);
+        let selection: Selection = serde_json::from_value(json).unwrap();
+        assert_eq!(selection.range(&d2.transact()), Some(9..14));
+        assert_eq!(selection.head.index(&d1.transact()), Some(9));
+
+        let mut caret = Selection::caret(&d2.transact(), &t2, 4).unwrap().head;
+        t2.remove_range(&mut d2.transact_mut(), 3, 3);
+        assert!(caret.refresh(&d2.transact()));
+        t2.insert(&mut d2.transact_mut(), 3, "xyz");
+        assert_eq!(caret.index(&d2.transact()), Some(6));
+    }
+}
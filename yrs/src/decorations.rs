@@ -0,0 +1,358 @@
+//! Ephemeral decorations attached to ranges of shared sequences.
+//!
+//! [Decorations] keep transient, local-only annotations - like lint warnings, spell-check marks or
+//! search highlights - which should follow the content they were attached to, but which should never
+//! be replicated to other peers or persisted as part of a document state (unlike i.e. text
+//! formatting attributes).
+//!
+//! Each decoration is anchored using a [StickyRange]. After every committed transaction, offsets of
+//! all decorations are recomputed and subscribers are notified about decorations that have moved or
+//! have been invalidated because all of their content has been removed.
+//!
+//! # Example
+//!
+//! ```rust
+//! use yrs::decorations::Decorations;
+//! use yrs::{Any, Doc, IndexedSequence, Text, Transact};
+//!
+//! let doc = Doc::new();
+//! let txt = doc.get_or_insert_text("text");
+//! txt.insert(&mut doc.transact_mut(), 0, "hello wrold");
+//!
+//! let decorations = Decorations::new(&doc).unwrap();
+//! let id = {
+//!     let mut txn = doc.transact_mut();
+//!     let range = txt.sticky_range(&mut txn, 6..11).unwrap();
+//!     decorations.insert(&txn, range, Any::from("typo")).unwrap()
+//! };
+//!
+//! txt.insert(&mut doc.transact_mut(), 0, "oh, ");
+//! assert_eq!(decorations.get(id).unwrap().offsets, 10..15);
+//!
+//! // removing decorated content invalidates a decoration
+//! txt.remove_range(&mut doc.transact_mut(), 10, 5);
+//! assert!(decorations.get(id).is_none());
+//! ```
+
+use crate::branch::{Branch, BranchID};
+use crate::observer::Observer;
+use crate::{Any, Doc, ReadTxn, StickyRange, Subscription, TransactionMut};
+use atomic_refcell::BorrowMutError;
+use std::collections::BTreeMap;
+use std::ops::Range;
+#[cfg(not(feature = "sync"))]
+use std::rc::Rc;
+use std::sync::atomic::{AtomicU64, Ordering};
+#[cfg(feature = "sync")]
+use std::sync::Arc;
+use std::sync::Mutex;
+
+/// Unique identifier of a decoration within its [Decorations] store.
+pub type DecorationId = u64;
+
+#[cfg(feature = "sync")]
+type DecorationsFn = Box<dyn Fn(&TransactionMut, &DecorationsEvent) + Send + Sync + 'static>;
+
+#[cfg(not(feature = "sync"))]
+type DecorationsFn = Box<dyn Fn(&TransactionMut, &DecorationsEvent) + 'static>;
+
+#[cfg(feature = "sync")]
+type InnerRef = Arc<Inner>;
+
+#[cfg(not(feature = "sync"))]
+type InnerRef = Rc<Inner>;
+
+/// A single decoration stored in [Decorations].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Decoration {
+    /// Sticky range this decoration is anchored to.
+    pub range: StickyRange,
+    /// Identifier of a collection, which contains decorated range.
+    pub branch: BranchID,
+    /// Offsets of a decorated range, as of the last committed transaction.
+    pub offsets: Range<u32>,
+    /// User-defined value of this decoration.
+    pub value: Any,
+}
+
+/// Event passed to callbacks subscribed via [Decorations::observe].
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DecorationsEvent {
+    /// Decorations which offsets have changed, together with their new offsets.
+    pub moved: Vec<(DecorationId, Range<u32>)>,
+    /// Decorations which have been removed, because their content no longer exists.
+    pub invalidated: Vec<(DecorationId, Decoration)>,
+}
+
+impl DecorationsEvent {
+    pub fn is_empty(&self) -> bool {
+        self.moved.is_empty() && self.invalidated.is_empty()
+    }
+}
+
+/// A non-replicated store of decorations attached to a single [Doc]. Decorations live only as
+/// long as this store and are never included in document updates.
+pub struct Decorations {
+    inner: InnerRef,
+    _subscription: Subscription,
+}
+
+struct Inner {
+    entries: Mutex<BTreeMap<DecorationId, Decoration>>,
+    next_id: AtomicU64,
+    observer: Observer<DecorationsFn>,
+}
+
+impl Decorations {
+    /// Creates a new empty decoration store for a given `doc`. Decorations are remapped after
+    /// every transaction committed on that document.
+    pub fn new(doc: &Doc) -> Result<Self, BorrowMutError> {
+        let inner = InnerRef::new(Inner {
+            entries: Mutex::new(BTreeMap::new()),
+            next_id: AtomicU64::new(0),
+            observer: Observer::new(),
+        });
+        let weak = InnerRef::downgrade(&inner);
+        let subscription = doc.observe_after_transaction(move |txn| {
+            if let Some(inner) = weak.upgrade() {
+                inner.remap(txn);
+            }
+        })?;
+        Ok(Decorations {
+            inner,
+            _subscription: subscription,
+        })
+    }
+
+    /// Attaches a new decoration with a given `value` to a `range`. Returns `None` if the range
+    /// couldn't be resolved within a given transaction.
+    pub fn insert<T: ReadTxn>(
+        &self,
+        txn: &T,
+        range: StickyRange,
+        value: Any,
+    ) -> Option<DecorationId> {
+        let branch = range.start.get_offset(txn)?.branch.id();
+        let offsets = range.get_offsets(txn)?;
+        let id = self.inner.next_id.fetch_add(1, Ordering::Relaxed);
+        let decoration = Decoration {
+            range,
+            branch,
+            offsets,
+            value,
+        };
+        self.inner.entries.lock().unwrap().insert(id, decoration);
+        Some(id)
+    }
+
+    /// Removes a decoration with a given `id`, returning it if it existed.
+    pub fn remove(&self, id: DecorationId) -> Option<Decoration> {
+        self.inner.entries.lock().unwrap().remove(&id)
+    }
+
+    /// Returns a decoration with a given `id`.
+    pub fn get(&self, id: DecorationId) -> Option<Decoration> {
+        self.inner.entries.lock().unwrap().get(&id).cloned()
+    }
+
+    /// Returns a number of decorations in this store.
+    pub fn len(&self) -> usize {
+        self.inner.entries.lock().unwrap().len()
+    }
+
+    /// Checks if this store contains no decorations.
+    pub fn is_empty(&self) -> bool {
+        self.inner.entries.lock().unwrap().is_empty()
+    }
+
+    /// Removes all decorations from this store.
+    pub fn clear(&self) {
+        self.inner.entries.lock().unwrap().clear()
+    }
+
+    /// Returns all decorations attached to a given `shared` collection, which overlap with
+    /// a given `range` of indexes, ordered by their offsets.
+    pub fn in_range<B>(&self, shared: &B, range: Range<u32>) -> Vec<(DecorationId, Decoration)>
+    where
+        B: AsRef<Branch>,
+    {
+        let branch = shared.as_ref().id();
+        let entries = self.inner.entries.lock().unwrap();
+        let mut result: Vec<_> = entries
+            .iter()
+            .filter(|(_, d)| {
+                d.branch == branch && d.offsets.start <= range.end && d.offsets.end >= range.start
+            })
+            .map(|(id, d)| (*id, d.clone()))
+            .collect();
+        result.sort_by_key(|(id, d)| (d.offsets.start, d.offsets.end, *id));
+        result
+    }
+
+    /// Subscribes a callback `f` to be called whenever a committed transaction has moved or
+    /// invalidated any of the decorations.
+    #[cfg(feature = "sync")]
+    pub fn observe<F>(&self, f: F) -> Subscription
+    where
+        F: Fn(&TransactionMut, &DecorationsEvent) + Send + Sync + 'static,
+    {
+        self.inner.observer.subscribe(Box::new(f))
+    }
+
+    /// Subscribes a callback `f` to be called whenever a committed transaction has moved or
+    /// invalidated any of the decorations.
+    #[cfg(not(feature = "sync"))]
+    pub fn observe<F>(&self, f: F) -> Subscription
+    where
+        F: Fn(&TransactionMut, &DecorationsEvent) + 'static,
+    {
+        self.inner.observer.subscribe(Box::new(f))
+    }
+}
+
+impl std::fmt::Debug for Decorations {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_map()
+            .entries(self.inner.entries.lock().unwrap().iter())
+            .finish()
+    }
+}
+
+impl Inner {
+    fn remap(&self, txn: &TransactionMut) {
+        if txn.before_state() == txn.after_state() && txn.delete_set().is_empty() {
+            return; // nothing has changed
+        }
+        let mut event = DecorationsEvent::default();
+        {
+            let mut entries = self.entries.lock().unwrap();
+            let mut invalidated = Vec::new();
+            for (id, d) in entries.iter_mut() {
+                match d.range.get_offsets(txn) {
+                    // non-empty range has lost all of its content
+                    Some(offsets) if offsets.is_empty() && !d.offsets.is_empty() => {
+                        invalidated.push(*id)
+                    }
+                    // decorated collection has been removed
+                    Some(_)
+                        if d.branch
+                            .get_branch(txn)
+                            .filter(|b| !b.is_deleted())
+                            .is_none() =>
+                    {
+                        invalidated.push(*id)
+                    }
+                    Some(offsets) => {
+                        if offsets != d.offsets {
+                            d.offsets = offsets.clone();
+                            event.moved.push((*id, offsets));
+                        }
+                    }
+                    None => invalidated.push(*id),
+                }
+            }
+            for id in invalidated {
+                if let Some(d) = entries.remove(&id) {
+                    event.invalidated.push((id, d));
+                }
+            }
+        }
+        if !event.is_empty() {
+            self.observer.trigger(|f| f(txn, &event));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::branch::BranchID;
+    use crate::decorations::Decorations;
+    use crate::updates::decoder::Decode;
+    use crate::{
+        Any, ArrayPrelim, ArrayRef, Doc, IndexedSequence, Map, MapPrelim, ReadTxn, Text, Transact,
+        Update, WriteTxn,
+    };
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn decorations_remap() {
+        let doc = Doc::with_client_id(1);
+        let txt = doc.get_or_insert_text("text");
+        txt.insert(&mut doc.transact_mut(), 0, "abc def ghi");
+
+        let decorations = Decorations::new(&doc).unwrap();
+        let (a, b) = {
+            let mut txn = doc.transact_mut();
+            let a = txt.sticky_range(&mut txn, 0..3).unwrap();
+            let b = txt.sticky_range(&mut txn, 8..11).unwrap();
+            (
+                decorations.insert(&txn, a, Any::from("a")).unwrap(),
+                decorations.insert(&txn, b, Any::from("b")).unwrap(),
+            )
+        };
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let events_c = events.clone();
+        let _sub = decorations.observe(move |_, e| events_c.lock().unwrap().push(e.clone()));
+
+        // transaction without changes doesn't trigger remapping
+        drop(doc.transact_mut());
+        assert!(events.lock().unwrap().is_empty());
+
+        txt.insert(&mut doc.transact_mut(), 4, "xyz ");
+        {
+            let events = events.lock().unwrap();
+            assert_eq!(events.len(), 1);
+            assert_eq!(events[0].moved, vec![(b, 12..15)]);
+            assert!(events[0].invalidated.is_empty());
+        }
+
+        let found = decorations.in_range(&txt, 0..4);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].0, a);
+        assert_eq!(found[0].1.branch, BranchID::Root("text".into()));
+        assert_eq!(found[0].1.value, Any::from("a"));
+
+        txt.remove_range(&mut doc.transact_mut(), 0, 4);
+        {
+            let events = events.lock().unwrap();
+            assert_eq!(events.len(), 2);
+            assert_eq!(events[1].moved, vec![(b, 8..11)]);
+            assert_eq!(events[1].invalidated.len(), 1);
+            assert_eq!(events[1].invalidated[0].0, a);
+        }
+        assert!(decorations.get(a).is_none());
+        assert_eq!(decorations.len(), 1);
+    }
+
+    #[test]
+    fn decorations_are_not_replicated() {
+        let doc = Doc::with_client_id(1);
+        let root = doc.get_or_insert_map("map");
+        let nested = {
+            let mut txn = doc.transact_mut();
+            let array: ArrayRef = root.insert(&mut txn, "array", ArrayPrelim::from([1, 2, 3]));
+            array
+        };
+        let decorations = Decorations::new(&doc).unwrap();
+        let id = {
+            let mut txn = doc.transact_mut();
+            let range = nested.sticky_range(&mut txn, 1..2).unwrap();
+            decorations.insert(&txn, range, Any::Null).unwrap()
+        };
+
+        let remote = Doc::with_client_id(2);
+        let update = doc
+            .transact()
+            .encode_state_as_update_v1(&Default::default());
+        let mut txn = remote.transact_mut();
+        txn.apply_update(Update::decode_v1(&update).unwrap());
+        assert_eq!(txn.get_or_insert_map("map").len(&txn), 1);
+        drop(txn);
+
+        // removing a parent collection invalidates its decorations
+        root.insert(&mut doc.transact_mut(), "array", MapPrelim::default());
+        assert!(decorations.get(id).is_none());
+        assert!(decorations.is_empty());
+    }
+}
@@ -0,0 +1,168 @@
+use std::collections::VecDeque;
+
+use crate::updates::decoder::Decode;
+use crate::{StateVector, Update};
+
+/// In-memory buffer of the most recent updates committed over a document, each one tagged with
+/// state vectors of the document before and after its transaction. It's enabled with
+/// [Doc::set_delta_buffer] and used to serve diffs for peers which are only slightly behind,
+/// without encoding blocks from the entire block store.
+///
+/// [Doc::set_delta_buffer]: crate::Doc::set_delta_buffer
+#[derive(Debug)]
+pub(crate) struct DeltaBuffer {
+    capacity: usize,
+    deltas: VecDeque<BufferedDelta>,
+}
+
+#[derive(Debug)]
+struct BufferedDelta {
+    before: StateVector,
+    after: StateVector,
+    update: Vec<u8>,
+}
+
+impl DeltaBuffer {
+    pub fn new(capacity: usize) -> Self {
+        DeltaBuffer {
+            capacity,
+            deltas: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    /// Drops all buffered updates.
+    pub fn clear(&mut self) {
+        self.deltas.clear();
+    }
+
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        while self.deltas.len() > capacity {
+            self.deltas.pop_front();
+        }
+    }
+
+    /// Appends a lib0 v1 encoded `update` committed by a transaction, which moved document from
+    /// `before` to `after` state.
+    pub fn push(&mut self, before: StateVector, after: StateVector, update: Vec<u8>) {
+        if self.deltas.len() == self.capacity {
+            self.deltas.pop_front();
+        }
+        self.deltas.push_back(BufferedDelta {
+            before,
+            after,
+            update,
+        });
+    }
+
+    /// Returns an update containing all blocks, which a peer with a given state vector `sv` is
+    /// missing - as long as all of them can be found within the buffer. Otherwise returns `None`.
+    ///
+    /// Returned update carries only deletions made within buffered transactions. Since state
+    /// vectors don't describe deletions, it's up to the caller to attach all of them.
+    pub fn diff(&self, sv: &StateVector) -> Option<Update> {
+        let last = self.deltas.back()?;
+        if covers(sv, &last.after) {
+            return Some(Update::new());
+        }
+        let start = self.deltas.iter().position(|d| covers(sv, &d.before))?;
+        let mut updates = Vec::with_capacity(self.deltas.len() - start);
+        for delta in self.deltas.range(start..) {
+            updates.push(Update::decode_v1(&delta.update).ok()?);
+        }
+        Some(Update::merge_updates(updates))
+    }
+}
+
+/// Checks if state vector `a` contains all the changes described by state vector `b`.
+fn covers(a: &StateVector, b: &StateVector) -> bool {
+    b.iter().all(|(client, &clock)| a.get(client) >= clock)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::updates::decoder::Decode;
+    use crate::updates::encoder::Encode;
+    use crate::{Doc, GetString, ReadTxn, StateVector, Text, Transact, Update};
+
+    #[test]
+    fn serve_diff_from_buffer() {
+        let doc = Doc::with_client_id(1);
+        let text = doc.get_or_insert_text("text");
+        text.push(&mut doc.transact_mut(), "a");
+        doc.set_delta_buffer(2).unwrap();
+
+        let peer = Doc::with_client_id(2);
+        let peer_text = peer.get_or_insert_text("text");
+        let update = doc.transact().encode_diff_v1(&StateVector::default());
+        peer.transact_mut()
+            .apply_update(Update::decode_v1(&update).unwrap());
+
+        text.push(&mut doc.transact_mut(), "b");
+        text.remove_range(&mut doc.transact_mut(), 0, 1);
+        let sv = peer.transact().state_vector();
+        let buffered = doc.transact().store().buffered_diff(&sv);
+        assert!(buffered.is_some());
+        let update = doc.transact().encode_diff_v1(&sv);
+        assert_eq!(Some(update.clone()), buffered.map(|u| u.encode_v1()));
+        peer.transact_mut()
+            .apply_update(Update::decode_v1(&update).unwrap());
+        assert_eq!(peer_text.get_string(&peer.transact()), "b");
+
+        // peer is up to date
+        let sv = peer.transact().state_vector();
+        let update = doc.transact().encode_diff_v2(&sv);
+        let update = Update::decode_v2(&update).unwrap();
+        assert_eq!(update.state_vector(), StateVector::default());
+
+        // changes older than the buffer are served from the block store
+        text.push(&mut doc.transact_mut(), "c");
+        text.push(&mut doc.transact_mut(), "d");
+        text.push(&mut doc.transact_mut(), "e");
+        let txn = doc.transact();
+        assert!(txn.store().buffered_diff(&sv).is_none());
+        drop(txn);
+        let update = doc.transact().encode_diff_v1(&sv);
+        peer.transact_mut()
+            .apply_update(Update::decode_v1(&update).unwrap());
+        assert_eq!(peer_text.get_string(&peer.transact()), "bcde");
+    }
+
+    #[test]
+    fn buffered_diff_carries_deletions() {
+        let doc = Doc::with_client_id(1);
+        let text = doc.get_or_insert_text("text");
+        doc.set_delta_buffer(2).unwrap();
+        text.push(&mut doc.transact_mut(), "abc");
+
+        let peer = Doc::with_client_id(2);
+        let peer_text = peer.get_or_insert_text("text");
+        let update = doc.transact().encode_diff_v1(&StateVector::default());
+        peer.transact_mut()
+            .apply_update(Update::decode_v1(&update).unwrap());
+
+        // deletion doesn't change the state vector of a document
+        text.remove_range(&mut doc.transact_mut(), 0, 1);
+        let sv = peer.transact().state_vector();
+        let update = doc.transact().encode_diff_v1(&sv);
+        peer.transact_mut()
+            .apply_update(Update::decode_v1(&update).unwrap());
+        assert_eq!(peer_text.get_string(&peer.transact()), "bc");
+
+        // deletions of transactions evicted from the buffer are served as well
+        let peer = Doc::with_client_id(3);
+        let peer_text = peer.get_or_insert_text("text");
+        let update = doc.transact().encode_diff_v1(&StateVector::default());
+        peer.transact_mut()
+            .apply_update(Update::decode_v1(&update).unwrap());
+        let sv = peer.transact().state_vector();
+        text.remove_range(&mut doc.transact_mut(), 0, 1);
+        text.push(&mut doc.transact_mut(), "d");
+        text.push(&mut doc.transact_mut(), "e");
+        assert!(doc.transact().store().buffered_diff(&sv).is_some());
+        let update = doc.transact().encode_diff_v2(&sv);
+        peer.transact_mut()
+            .apply_update(Update::decode_v2(&update).unwrap());
+        assert_eq!(peer_text.get_string(&peer.transact()), "cde");
+    }
+}
@@ -0,0 +1,342 @@
+//! Notifications about destructive operations.
+//!
+//! A misbehaving client - or a bug in application code - can wipe out most of the document
+//! contents with a handful of operations, which are then faithfully replicated to every peer.
+//! Callbacks subscribed with [Doc::observe_destructive_ops] are notified about operations
+//! removing large amounts of data, so that application can alert about them:
+//!
+//! - [DestructiveOp::RootCleared] when all contents of a root type have been removed and
+//!   a number of removed elements has reached [DestructivePolicy::clear_threshold],
+//! - [DestructiveOp::RangeRemoved] when a number of elements removed from a single collection
+//!   within one transaction has reached [DestructivePolicy::range_threshold],
+//! - [DestructiveOp::GarbageCollected] when tombstones have been pruned with
+//!   [TransactionMut::gc_with], or by the garbage collection performed on transaction commit
+//!   if a number of pruned elements has reached [DestructivePolicy::gc_threshold].
+//!
+//! Removals are reported on transaction commit, after type observers have been called but before
+//! any of the update events are emitted. Only removals of elements which existed before the
+//! transaction has started are taken into account, regardless of whether they were made locally
+//! or by applying a remote update. [TransactionMut::origin] can be used to tell them apart.
+//!
+//! Notifications are purely informational: by the time they are delivered, the changes have
+//! already been applied and will be sent to other peers. Removed elements can be brought back
+//! afterwards (i.e. with [crate::undo::UndoManager]) only as long as they haven't been garbage
+//! collected.
+//!
+//! [Doc::observe_destructive_ops]: crate::Doc::observe_destructive_ops
+//!
+//! # Example
+//!
+//! ```rust
+//! use std::sync::{Arc, Mutex};
+//! use yrs::destructive::{DestructiveOp, DestructivePolicy};
+//! use yrs::{Array, Doc, Transact};
+//!
+//! let doc = Doc::new();
+//! doc.set_destructive_policy(DestructivePolicy {
+//!     range_threshold: 10,
+//!     clear_threshold: 10,
+//!     ..DestructivePolicy::default()
+//! })
+//! .unwrap();
+//! let array = doc.get_or_insert_array("array");
+//! array.insert_range(&mut doc.transact_mut(), 0, 0..20);
+//!
+//! let ops = Arc::new(Mutex::new(Vec::new()));
+//! let _sub = {
+//!     let ops = ops.clone();
+//!     doc.observe_destructive_ops(move |_, op| ops.lock().unwrap().push(op.clone()))
+//!         .unwrap()
+//! };
+//!
+//! array.remove_range(&mut doc.transact_mut(), 0, 5); // below threshold
+//! array.remove_range(&mut doc.transact_mut(), 0, 15);
+//! assert_eq!(
+//!     ops.lock().unwrap().as_slice(),
+//!     &[DestructiveOp::RootCleared { root: "array".into(), removed: 15 }]
+//! );
+//! ```
+
+use crate::branch::{Branch, BranchPtr};
+use crate::iter::TxnIterator;
+use crate::types::Path;
+use crate::TransactionMut;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Configuration of destructive operations reported to [Doc::observe_destructive_ops]
+/// subscribers, set with [Doc::set_destructive_policy].
+///
+/// [Doc::observe_destructive_ops]: crate::Doc::observe_destructive_ops
+/// [Doc::set_destructive_policy]: crate::Doc::set_destructive_policy
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DestructivePolicy {
+    /// Minimum number of elements (or map entries) removed from a single collection within one
+    /// transaction, which is reported as [DestructiveOp::RangeRemoved]. Default: 100.
+    pub range_threshold: u32,
+    /// Minimum number of elements (or map entries) removed within one transaction from a root
+    /// type left empty afterwards, which is reported as [DestructiveOp::RootCleared]. Smaller
+    /// removals are reported as [DestructiveOp::RangeRemoved] if they reach
+    /// [DestructivePolicy::range_threshold]. Default: 10.
+    pub clear_threshold: u32,
+    /// Minimum number of elements, which contents have been discarded by the garbage collection
+    /// performed on transaction commit, reported as [DestructiveOp::GarbageCollected]. Explicit
+    /// collections made with [TransactionMut::gc_with] are always reported. Default: 100.
+    pub gc_threshold: u32,
+}
+
+impl Default for DestructivePolicy {
+    fn default() -> Self {
+        DestructivePolicy {
+            range_threshold: 100,
+            clear_threshold: 10,
+            gc_threshold: 100,
+        }
+    }
+}
+
+/// Destructive operation passed to callbacks subscribed with [Doc::observe_destructive_ops].
+///
+/// [Doc::observe_destructive_ops]: crate::Doc::observe_destructive_ops
+#[derive(Debug, Clone, PartialEq)]
+pub enum DestructiveOp {
+    /// All contents of a root type have been removed and a number of removed elements has
+    /// reached [DestructivePolicy::clear_threshold].
+    RootCleared {
+        /// Name of a cleared root type.
+        root: Arc<str>,
+        /// Number of elements (or map entries) removed within a transaction.
+        removed: u32,
+    },
+    /// A number of elements (or map entries) removed from a single collection within
+    /// a transaction has reached [DestructivePolicy::range_threshold].
+    RangeRemoved {
+        /// Name of a root type, which contains the collection.
+        root: Arc<str>,
+        /// Path from the root type to the collection. Empty if collection is a root type itself.
+        path: Path,
+        /// Number of elements (or map entries) removed within a transaction.
+        removed: u32,
+    },
+    /// Tombstones have been pruned with [TransactionMut::gc_with] or by the garbage collection
+    /// performed on transaction commit. Their contents can no longer be restored, i.e. with
+    /// [crate::undo::UndoManager] or from a [crate::Snapshot].
+    GarbageCollected {
+        /// Number of elements, which contents have been discarded.
+        collected: u32,
+    },
+}
+
+/// Returns root cleared and range removed operations made within a given transaction.
+pub(crate) fn detect(txn: &TransactionMut, policy: &DestructivePolicy) -> Vec<DestructiveOp> {
+    let mut removed: Vec<(BranchPtr, u32)> = Vec::new();
+    let mut indexes: HashMap<BranchPtr, usize> = HashMap::new();
+    let mut deleted = txn.delete_set.deleted_blocks();
+    while let Some(slice) = deleted.next(txn) {
+        let item = match slice.as_item() {
+            Some(item) => item,
+            None => continue,
+        };
+        if slice.clock_start() >= txn.before_state.get(&item.id.client) {
+            // item was both inserted and removed within current transaction
+            continue;
+        }
+        let parent = match item.parent.as_branch() {
+            Some(parent) => *parent,
+            None => continue,
+        };
+        let count = match &item.parent_sub {
+            // overwritten map entries are not removed
+            Some(key) if parent.map.get(key) == Some(&item) => 1,
+            Some(_) => continue,
+            None if item.is_countable() => slice.len(),
+            None => continue,
+        };
+        let i = *indexes.entry(parent).or_insert_with(|| {
+            removed.push((parent, 0));
+            removed.len() - 1
+        });
+        removed[i].1 += count;
+    }
+
+    let mut ops = Vec::new();
+    for (branch, removed) in removed {
+        if let Some(item) = &branch.item {
+            if item.is_deleted() {
+                // removal of the collection itself is reported by its parent
+                continue;
+            }
+        }
+        let (root, path) = root_path(branch);
+        if branch.item.is_none() && removed >= policy.clear_threshold && is_empty(&branch) {
+            ops.push(DestructiveOp::RootCleared { root, removed });
+        } else if removed >= policy.range_threshold {
+            ops.push(DestructiveOp::RangeRemoved {
+                root,
+                path,
+                removed,
+            });
+        }
+    }
+    ops
+}
+
+fn is_empty(branch: &Branch) -> bool {
+    branch.block_len == 0 && branch.map.values().all(|item| item.is_deleted())
+}
+
+fn root_path(branch: BranchPtr) -> (Arc<str>, Path) {
+    let mut root = branch;
+    while let Some(item) = &root.item {
+        match item.parent.as_branch() {
+            Some(parent) => root = *parent,
+            None => break,
+        }
+    }
+    let name = root.name.clone().unwrap_or_else(|| Arc::from(""));
+    (name, Branch::path(root, branch))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::destructive::{DestructiveOp, DestructivePolicy};
+    use crate::types::PathSegment;
+    use crate::updates::decoder::Decode;
+    use crate::{
+        Array, ArrayPrelim, ArrayRef, Doc, GcPolicy, Map, MapPrelim, Options, ReadTxn, Text,
+        Transact, Update,
+    };
+    use std::collections::VecDeque;
+    use std::sync::{Arc, Mutex};
+
+    fn observe(doc: &Doc) -> (Arc<Mutex<Vec<DestructiveOp>>>, crate::Subscription) {
+        let ops = Arc::new(Mutex::new(Vec::new()));
+        let sub = {
+            let ops = ops.clone();
+            doc.observe_destructive_ops(move |_, op| ops.lock().unwrap().push(op.clone()))
+                .unwrap()
+        };
+        (ops, sub)
+    }
+
+    #[test]
+    fn remote_removals() {
+        let d1 = Doc::with_client_id(1);
+        let d2 = Doc::with_client_id(2);
+        d2.set_destructive_policy(DestructivePolicy {
+            range_threshold: 3,
+            clear_threshold: 2,
+            ..DestructivePolicy::default()
+        })
+        .unwrap();
+        let root = d1.get_or_insert_map("root");
+        let text = d1.get_or_insert_text("text");
+        {
+            let mut txn = d1.transact_mut();
+            let nested = root.insert(&mut txn, "list", ArrayPrelim::from([1, 2, 3, 4]));
+            nested.push_back(&mut txn, MapPrelim::from([("a", 1)]));
+            root.insert(&mut txn, "key", "value");
+            text.insert(&mut txn, 0, "hello");
+        }
+        let sv = d2.transact().state_vector();
+        let update = d1.transact().encode_state_as_update_v1(&sv);
+        d2.transact_mut()
+            .apply_update(Update::decode_v1(&update).unwrap());
+        let (ops, _sub) = observe(&d2);
+
+        let sv = d2.transact().state_vector();
+        {
+            let mut txn = d1.transact_mut();
+            let nested = root.get(&txn, "list").unwrap().cast::<ArrayRef>().unwrap();
+            nested.remove_range(&mut txn, 0, 3);
+            root.insert(&mut txn, "key", "changed"); // overwrite is not a removal
+            text.remove_range(&mut txn, 0, 2); // below threshold
+        }
+        let update = d1.transact().encode_state_as_update_v1(&sv);
+        d2.transact_mut()
+            .apply_update(Update::decode_v1(&update).unwrap());
+        assert_eq!(
+            ops.lock().unwrap().as_slice(),
+            &[DestructiveOp::RangeRemoved {
+                root: "root".into(),
+                path: VecDeque::from([PathSegment::Key("list".into())]),
+                removed: 3,
+            }]
+        );
+        ops.lock().unwrap().clear();
+
+        // removal of a nested collection is reported only by its parent
+        let sv = d2.transact().state_vector();
+        {
+            let mut txn = d1.transact_mut();
+            root.remove(&mut txn, "list");
+            root.remove(&mut txn, "key");
+            text.remove_range(&mut txn, 0, 3);
+        }
+        let update = d1.transact().encode_state_as_update_v1(&sv);
+        d2.transact_mut()
+            .apply_update(Update::decode_v1(&update).unwrap());
+        let ops = ops.lock().unwrap();
+        assert_eq!(ops.len(), 2);
+        assert!(ops.contains(&DestructiveOp::RootCleared {
+            root: "root".into(),
+            removed: 2
+        }));
+        assert!(ops.contains(&DestructiveOp::RootCleared {
+            root: "text".into(),
+            removed: 3
+        }));
+    }
+
+    #[test]
+    fn garbage_collected() {
+        let doc = Doc::with_options(Options {
+            skip_gc: true,
+            ..Options::default()
+        });
+        let text = doc.get_or_insert_text("text");
+        text.insert(&mut doc.transact_mut(), 0, "hello world");
+        text.remove_range(&mut doc.transact_mut(), 5, 6);
+        let (ops, _sub) = observe(&doc);
+
+        doc.transact_mut().gc_with(&GcPolicy::default());
+        doc.transact_mut().gc_with(&GcPolicy::default()); // nothing left to collect
+        assert_eq!(
+            ops.lock().unwrap().as_slice(),
+            &[DestructiveOp::GarbageCollected { collected: 6 }]
+        );
+    }
+
+    #[test]
+    fn commit_thresholds() {
+        let doc = Doc::with_client_id(1);
+        doc.set_destructive_policy(DestructivePolicy {
+            range_threshold: 100,
+            clear_threshold: 5,
+            gc_threshold: 5,
+        })
+        .unwrap();
+        let text = doc.get_or_insert_text("text");
+        let array = doc.get_or_insert_array("array");
+        text.insert(&mut doc.transact_mut(), 0, "hi");
+        array.insert_range(&mut doc.transact_mut(), 0, 0..10);
+        let (ops, _sub) = observe(&doc);
+
+        // clearing a root below the threshold is not reported
+        text.remove_range(&mut doc.transact_mut(), 0, 2);
+        assert!(ops.lock().unwrap().is_empty());
+
+        // tombstones collected on commit are reported once they reach the threshold
+        array.remove_range(&mut doc.transact_mut(), 0, 10);
+        assert_eq!(
+            ops.lock().unwrap().as_slice(),
+            &[
+                DestructiveOp::RootCleared {
+                    root: "array".into(),
+                    removed: 10
+                },
+                DestructiveOp::GarbageCollected { collected: 10 }
+            ]
+        );
+    }
+}
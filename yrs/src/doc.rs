@@ -1,23 +1,33 @@
+use crate::blame::BlockMetaLog;
 use crate::block::{ClientID, ItemContent, ItemPtr, Prelim};
-use crate::branch::BranchPtr;
+#[cfg(feature = "borrow-tracker")]
+use crate::borrow_tracker;
+use crate::branch::{BranchPtr, TypeRepair};
+use crate::delta_buffer::DeltaBuffer;
+use crate::destructive::{DestructiveOp, DestructivePolicy};
 use crate::encoding::read::Error;
-use crate::event::{SubdocsEvent, TransactionCleanupEvent, UpdateEvent};
-use crate::store::{Store, StoreRef};
+use crate::event::{RootsEvent, SubdocsEvent, TransactionCleanupEvent, UpdateEvent};
+use crate::out::infer_type_from_content;
+use crate::pending::{PendingEvictionEvent, PendingPolicy, PendingState};
+use crate::persistence::{self, DocStore};
+use crate::store::{CloseHook, Store, StoreCell, StoreRef, UpdateLimiter};
 use crate::transaction::{Origin, Transaction, TransactionMut};
-use crate::types::{RootRef, ToJson};
+use crate::types::text::YChange;
+use crate::types::{AsPrelim, Delta, Path, PathSegment, RootRef, SharedRef, ToJson};
 use crate::updates::decoder::{Decode, Decoder};
-use crate::updates::encoder::{Encode, Encoder};
+use crate::updates::encoder::{Encode, Encoder, EncoderV1};
 use crate::utils::OptionExt;
+use crate::xml_index::XmlIdIndex;
 use crate::{
-    uuid_v4, uuid_v4_from, ArrayRef, BranchID, MapRef, Out, ReadTxn, TextRef, Uuid, WriteTxn,
-    XmlFragmentRef,
+    uuid_v4, uuid_v4_from, Array, ArrayRef, BranchID, CounterRef, GSetRef, In, Map, MapRef, Out,
+    ReadTxn, Snapshot, Text, TextRef, TwoPhaseSetRef, Update, Uuid, WriteTxn, XmlFragmentRef,
 };
-use crate::{Any, Subscription};
-use atomic_refcell::{AtomicRefCell, BorrowError, BorrowMutError};
-use std::collections::HashMap;
+use crate::{Any, CallbackError, Subscription};
+use atomic_refcell::{BorrowError, BorrowMutError};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::convert::TryFrom;
 use std::fmt::Formatter;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use thiserror::Error;
 
 /// A Yrs document type. Documents are the most important units of collaborative resources management.
@@ -86,7 +96,7 @@ impl Doc {
 
     #[doc(hidden)]
     pub unsafe fn from_raw(ptr: *const Doc) -> Doc {
-        let ptr = ptr as *const AtomicRefCell<Store>;
+        let ptr = ptr as *const StoreCell;
         let cell = Arc::from_raw(ptr);
         Doc {
             store: StoreRef(cell),
@@ -112,6 +122,48 @@ impl Doc {
         }
     }
 
+    pub(crate) fn from_store(store: Store) -> Self {
+        let store = StoreRef::from(store);
+        store.close();
+        Doc { store }
+    }
+
+    /// Opens a document persisted in a given `store` under a name equal to [Options::guid]. All
+    /// updates stored so far are applied onto a newly created document, while all updates made
+    /// to it from now on will be appended to the `store`.
+    ///
+    /// Updates which couldn't be written into the `store` are kept in memory and written together
+    /// with the next update.
+    pub fn open_with<S>(store: Arc<S>, options: Options) -> Result<Self, persistence::Error>
+    where
+        S: DocStore + 'static,
+    {
+        let doc = Doc::with_options(options);
+        let name = doc.guid().clone();
+        let updates = store.load_doc(&name)?;
+        {
+            let mut txn = doc
+                .try_transact_mut_with(persistence::PERSISTENCE_ORIGIN)
+                .map_err(crate::Error::from)?;
+            for update in updates {
+                txn.apply_update(update);
+            }
+        }
+        let pending = Mutex::new(VecDeque::<Vec<u8>>::new());
+        doc.observe_update_v1_with(persistence::PERSISTENCE_ORIGIN, move |_, e| {
+            let mut pending = pending.lock().unwrap();
+            pending.push_back(e.update.clone());
+            while let Some(update) = pending.front() {
+                if store.store_update(&name, update).is_err() {
+                    break; // retry with the next update
+                }
+                pending.pop_front();
+            }
+        })
+        .map_err(crate::Error::from)?;
+        Ok(doc)
+    }
+
     pub(crate) fn subdoc(parent: ItemPtr, options: Options) -> Self {
         let mut store = Store::new(options);
         store.parent = Some(parent);
@@ -178,6 +230,52 @@ impl Doc {
         MapRef::root(name).get_or_create(&mut self.transact_mut())
     }
 
+    /// Returns a [CounterRef] data structure stored under a given `name`. Counters are numeric
+    /// values, which can be incremented concurrently by many peers without overwriting each
+    /// other's changes.
+    ///
+    /// If no structure under defined `name` existed before, it will be created and returned
+    /// instead.
+    ///
+    /// # Panics
+    ///
+    /// This method requires exclusive access to an underlying document store. If there
+    /// is another transaction in process, it will panic. It's advised to define all root shared
+    /// types during the document creation.
+    pub fn get_or_insert_counter<N: Into<Arc<str>>>(&self, name: N) -> CounterRef {
+        CounterRef::root(name).get_or_create(&mut self.transact_mut())
+    }
+
+    /// Returns a [GSetRef] data structure stored under a given `name`. Grow-only sets store unique
+    /// values, which can be inserted but never removed.
+    ///
+    /// If no structure under defined `name` existed before, it will be created and returned
+    /// instead.
+    ///
+    /// # Panics
+    ///
+    /// This method requires exclusive access to an underlying document store. If there
+    /// is another transaction in process, it will panic. It's advised to define all root shared
+    /// types during the document creation.
+    pub fn get_or_insert_gset<N: Into<Arc<str>>>(&self, name: N) -> GSetRef {
+        GSetRef::root(name).get_or_create(&mut self.transact_mut())
+    }
+
+    /// Returns a [TwoPhaseSetRef] data structure stored under a given `name`. Two-phase sets store
+    /// unique values, which can be removed - but once removed, they cannot be inserted again.
+    ///
+    /// If no structure under defined `name` existed before, it will be created and returned
+    /// instead.
+    ///
+    /// # Panics
+    ///
+    /// This method requires exclusive access to an underlying document store. If there
+    /// is another transaction in process, it will panic. It's advised to define all root shared
+    /// types during the document creation.
+    pub fn get_or_insert_two_phase_set<N: Into<Arc<str>>>(&self, name: N) -> TwoPhaseSetRef {
+        TwoPhaseSetRef::root(name).get_or_create(&mut self.transact_mut())
+    }
+
     /// Returns an [ArrayRef] data structure stored under a given `name`. Array structures are used for
     /// storing a sequences of elements in ordered manner, positioning given element accordingly
     /// to its index.
@@ -220,6 +318,292 @@ impl Doc {
         XmlFragmentRef::root(name).get_or_create(&mut self.transact_mut())
     }
 
+    /// Enables an in-memory buffer of up to `capacity` most recent updates committed over this
+    /// document. When enabled, [ReadTxn::encode_diff_v1]/[ReadTxn::encode_diff_v2] requested by
+    /// peers which are only slightly behind are served by merging buffered updates, instead of
+    /// encoding blocks from the entire block store. Peers which are further behind than the
+    /// buffer reaches are served the regular way. Capacity of 0 disables the buffer.
+    ///
+    /// Since state vectors don't tell which deletions a peer has already seen, buffered diffs
+    /// still carry a delete set of the whole document, just like the regular ones.
+    pub fn set_delta_buffer(&self, capacity: usize) -> Result<(), BorrowMutError> {
+        let mut r = self.store.try_borrow_mut()?;
+        if capacity == 0 {
+            r.delta_buffer = None;
+        } else if let Some(buffer) = r.delta_buffer.as_mut() {
+            buffer.set_capacity(capacity);
+        } else {
+            r.delta_buffer = Some(Box::new(DeltaBuffer::new(capacity)));
+        }
+        Ok(())
+    }
+
+    /// Enables an index of XML elements by the value of a given `attr` attribute (eg. `id` or
+    /// `data-uid`), which is used by [XmlFragment::get_by_id] lookups. The index is built from
+    /// the current document state and then kept up to date as transactions are committed, both
+    /// local and remote ones. Passing `None` disables the index.
+    ///
+    /// [XmlFragment::get_by_id]: crate::XmlFragment::get_by_id
+    pub fn set_xml_id_index(&self, attr: Option<&str>) -> Result<(), BorrowMutError> {
+        let mut r = self.store.try_borrow_mut()?;
+        match attr {
+            None => r.xml_id_index = None,
+            Some(attr) if r.xml_id_index.as_ref().map(|i| i.attr().as_ref()) == Some(attr) => {}
+            Some(attr) => {
+                let mut index = XmlIdIndex::new(attr.into());
+                for root in r.types.values() {
+                    index.index_tree(BranchPtr::from(root));
+                }
+                r.xml_id_index = Some(Box::new(index));
+            }
+        }
+        Ok(())
+    }
+
+    /// Enables rate-limited update emission: updates produced by transactions committed within
+    /// `interval_millis` since the last emission are buffered and merged together, and callbacks
+    /// subscribed with [Doc::observe_update_v1]/[Doc::observe_update_v2] are called once with
+    /// a merged update. This way high-frequency local edits don't flood the network with tiny
+    /// packets.
+    ///
+    /// Buffered updates are emitted on the first commit after the interval has passed. Use
+    /// [Doc::flush_updates] to emit them right away, i.e. from a timer. Interval of 0 disables
+    /// rate limiting (updates buffered so far will be emitted on the next commit).
+    #[cfg(not(target_family = "wasm"))]
+    pub fn set_update_interval(&self, interval_millis: u64) -> Result<(), BorrowMutError> {
+        self.set_update_interval_with_clock(
+            interval_millis,
+            Arc::new(crate::sync::time::SystemClock),
+        )
+    }
+
+    /// Same as [Doc::set_update_interval], but uses a custom `clock` (returning timestamps in
+    /// milliseconds) to measure intervals.
+    pub fn set_update_interval_with_clock(
+        &self,
+        interval_millis: u64,
+        clock: Arc<dyn crate::sync::Clock>,
+    ) -> Result<(), BorrowMutError> {
+        let mut r = self.store.try_borrow_mut()?;
+        let events = r.events.get_or_init();
+        match events.update_limiter.as_mut() {
+            Some(limiter) => {
+                if interval_millis == 0
+                    && limiter.pending_v1.is_empty()
+                    && limiter.pending_v2.is_empty()
+                {
+                    events.update_limiter = None;
+                } else {
+                    limiter.interval = interval_millis;
+                    limiter.clock = clock;
+                }
+            }
+            None if interval_millis != 0 => {
+                let limiter = UpdateLimiter::new(interval_millis, clock);
+                events.update_limiter = Some(Box::new(limiter));
+            }
+            None => {}
+        }
+        Ok(())
+    }
+
+    /// Configures limits on the size and age of a pending update - blocks, which couldn't be
+    /// integrated yet because the blocks they depend on are missing. When any of the limits is
+    /// exceeded, the pending update is dropped, passed to [Doc::observe_pending_evictions]
+    /// subscribers or forcibly integrated, depending on [PendingPolicy::action]. Passing `None`
+    /// removes the limits.
+    ///
+    /// [PendingPolicy::action]: crate::pending::PendingPolicy::action
+    #[cfg(not(target_family = "wasm"))]
+    pub fn set_pending_policy(&self, policy: Option<PendingPolicy>) -> Result<(), BorrowMutError> {
+        self.set_pending_policy_with_clock(policy, Arc::new(crate::sync::time::SystemClock))
+    }
+
+    /// Same as [Doc::set_pending_policy], but uses a custom `clock` (returning timestamps in
+    /// milliseconds) to measure the age of a pending update.
+    pub fn set_pending_policy_with_clock(
+        &self,
+        policy: Option<PendingPolicy>,
+        clock: Arc<dyn crate::sync::Clock>,
+    ) -> Result<(), BorrowMutError> {
+        let mut r = self.store.try_borrow_mut()?;
+        r.pending_state = policy.map(|policy| Box::new(PendingState::new(policy, clock)));
+        Ok(())
+    }
+
+    /// Starts recording the wall-clock time and an app-defined `author` tag of every block created
+    /// by current document. Recorded metadata can be exchanged with remote peers using
+    /// [ReadTxn::block_meta_update] and queried with [Text::blame]. See [crate::blame] for details.
+    ///
+    /// Calling this method again changes the author tag assigned to the blocks created from now on.
+    #[cfg(not(target_family = "wasm"))]
+    pub fn record_block_meta(&self, author: Option<Arc<str>>) -> Result<(), BorrowMutError> {
+        self.record_block_meta_with_clock(author, Arc::new(crate::sync::time::SystemClock))
+    }
+
+    /// Same as [Doc::record_block_meta], but uses a custom `clock` (returning timestamps in
+    /// milliseconds) to timestamp created blocks.
+    pub fn record_block_meta_with_clock(
+        &self,
+        author: Option<Arc<str>>,
+        clock: Arc<dyn crate::sync::Clock>,
+    ) -> Result<(), BorrowMutError> {
+        let mut r = self.store.try_borrow_mut()?;
+        match r.block_meta.as_mut() {
+            Some(log) => log.set_recorder(clock, author),
+            None => r.block_meta = Some(Box::new(BlockMetaLog::recording(clock, author))),
+        }
+        Ok(())
+    }
+
+    /// Configures which removals are reported to [Doc::observe_destructive_ops] subscribers.
+    pub fn set_destructive_policy(&self, policy: DestructivePolicy) -> Result<(), BorrowMutError> {
+        let mut r = self.store.try_borrow_mut()?;
+        r.destructive_policy = policy;
+        Ok(())
+    }
+
+    /// Sets a function used to compute an origin of transactions started over sub-documents of
+    /// current document while committing its own transactions (i.e. when integrating, loading or
+    /// destroying sub-documents as a result of [TransactionMut::apply_update]). Function receives
+    /// an origin of a parent transaction and a sub-document, and returns an origin to be used
+    /// (or `None` to start a sub-document transaction without origin).
+    ///
+    /// By default sub-document transactions inherit an origin of their parent transaction as is.
+    /// Function is called while a transaction over the sub-document is about to start, so it
+    /// should not try to start transactions over the sub-document itself.
+    #[cfg(feature = "sync")]
+    pub fn map_subdoc_origin<F>(&self, f: F) -> Result<(), BorrowMutError>
+    where
+        F: Fn(&Origin, &Doc) -> Option<Origin> + Send + Sync + 'static,
+    {
+        let mut r = self.store.try_borrow_mut()?;
+        r.subdoc_origin = Some(Box::new(f));
+        Ok(())
+    }
+
+    /// Sets a function used to compute an origin of transactions started over sub-documents of
+    /// current document while committing its own transactions (i.e. when integrating, loading or
+    /// destroying sub-documents as a result of [TransactionMut::apply_update]). Function receives
+    /// an origin of a parent transaction and a sub-document, and returns an origin to be used
+    /// (or `None` to start a sub-document transaction without origin).
+    ///
+    /// By default sub-document transactions inherit an origin of their parent transaction as is.
+    /// Function is called while a transaction over the sub-document is about to start, so it
+    /// should not try to start transactions over the sub-document itself.
+    #[cfg(not(feature = "sync"))]
+    pub fn map_subdoc_origin<F>(&self, f: F) -> Result<(), BorrowMutError>
+    where
+        F: Fn(&Origin, &Doc) -> Option<Origin> + 'static,
+    {
+        let mut r = self.store.try_borrow_mut()?;
+        r.subdoc_origin = Some(Box::new(f));
+        Ok(())
+    }
+
+    /// Decodes a lib0 v1 encoded `update` and applies it within a new read-write transaction.
+    ///
+    /// Unlike combining [Update::decode_v1] with [TransactionMut::apply_update], this method never
+    /// panics: if another transaction is active an [Error::Borrow] is returned, while malformed
+    /// payloads produce [Error::Encoding] or [Error::Capacity].
+    ///
+    /// [Error::Borrow]: crate::Error::Borrow
+    /// [Error::Encoding]: crate::Error::Encoding
+    /// [Error::Capacity]: crate::Error::Capacity
+    pub fn try_apply_update_v1(&self, update: &[u8]) -> Result<(), crate::Error> {
+        let mut txn = self.try_transact_mut()?;
+        txn.apply_update(Update::decode_v1(update)?);
+        Ok(())
+    }
+
+    /// Decodes a lib0 v2 encoded `update` and applies it within a new read-write transaction.
+    /// See [Doc::try_apply_update_v1] for details.
+    pub fn try_apply_update_v2(&self, update: &[u8]) -> Result<(), crate::Error> {
+        let mut txn = self.try_transact_mut()?;
+        txn.apply_update(Update::decode_v2(update)?);
+        Ok(())
+    }
+
+    /// Emits all updates buffered due to [Doc::set_update_interval] right away, without waiting
+    /// for the interval to pass.
+    pub fn flush_updates(&self) -> Result<(), TransactionAcqError> {
+        let mut txn = self.try_transact_mut()?;
+        if let Some(events) = txn.store_mut().events.as_mut() {
+            if let Some(limiter) = events.update_limiter.as_mut() {
+                limiter.flush_requested = true;
+            }
+        }
+        Ok(())
+    }
+
+    /// Asynchronous counterpart of [Doc::observe_update_v1]. Updates are queued on transaction
+    /// commit and passed to `f` by a returned [ObserverTask], which needs to be spawned on an async
+    /// runtime. Futures returned by `f` are awaited one at a time, in the order of commits.
+    ///
+    /// Returns a subscription, which will unsubscribe function when dropped. Task completes
+    /// once the subscription has been dropped and all queued updates have been processed.
+    ///
+    /// [ObserverTask]: crate::stream::ObserverTask
+    #[cfg(feature = "async")]
+    pub fn observe_update_v1_async<F, Fut>(
+        &self,
+        f: F,
+    ) -> Result<(Subscription, crate::stream::ObserverTask), BorrowMutError>
+    where
+        F: Fn(crate::stream::EncodedUpdate) -> Fut + Send + 'static,
+        Fut: std::future::Future<Output = ()> + Send + 'static,
+    {
+        let mut r = self.store.try_borrow_mut()?;
+        let events = r.events.get_or_init();
+        Ok(events.observe_update_v1_async(f))
+    }
+
+    /// Asynchronous counterpart of [Doc::observe_update_v2]. Works the same way as
+    /// [Doc::observe_update_v1_async], except that updates are encoded using lib0 v2 encoding.
+    #[cfg(feature = "async")]
+    pub fn observe_update_v2_async<F, Fut>(
+        &self,
+        f: F,
+    ) -> Result<(Subscription, crate::stream::ObserverTask), BorrowMutError>
+    where
+        F: Fn(crate::stream::EncodedUpdate) -> Fut + Send + 'static,
+        Fut: std::future::Future<Output = ()> + Send + 'static,
+    {
+        let mut r = self.store.try_borrow_mut()?;
+        let events = r.events.get_or_init();
+        Ok(events.observe_update_v2_async(f))
+    }
+
+    /// Asynchronous counterpart of [Doc::observe_transaction_cleanup]. See
+    /// [Doc::observe_update_v1_async] for details.
+    #[cfg(feature = "async")]
+    pub fn observe_transaction_cleanup_async<F, Fut>(
+        &self,
+        f: F,
+    ) -> Result<(Subscription, crate::stream::ObserverTask), BorrowMutError>
+    where
+        F: Fn(TransactionCleanupEvent) -> Fut + Send + 'static,
+        Fut: std::future::Future<Output = ()> + Send + 'static,
+    {
+        let mut r = self.store.try_borrow_mut()?;
+        let events = r.events.get_or_init();
+        Ok(events.observe_transaction_cleanup_async(f))
+    }
+
+    /// Returns an asynchronous stream of lib0 v1 encoded updates committed by transactions over
+    /// this document - an alternative to [Doc::observe_update_v1] callbacks, which is easier to
+    /// plug into async pipelines. Buffering and backpressure can be configured with `options`.
+    ///
+    /// Stream stops receiving updates once it's dropped. It ends once current document has been
+    /// closed or dropped.
+    #[cfg(feature = "async")]
+    pub fn update_stream(
+        &self,
+        options: crate::stream::UpdateStreamOptions,
+    ) -> Result<crate::stream::UpdateStream, BorrowMutError> {
+        crate::stream::UpdateStream::new(self, options)
+    }
+
     /// Subscribe callback function for any changes performed within transaction scope. These
     /// changes are encoded using lib0 v1 encoding and can be decoded using [Update::decode_v1] if
     /// necessary or passed to remote peers right away. This callback is triggered on function
@@ -457,6 +841,16 @@ impl Doc {
         Ok(events.after_transaction_events.subscribe(Box::new(f)))
     }
 
+    #[cfg(not(feature = "sync"))]
+    pub fn observe_after_transaction<F>(&self, f: F) -> Result<Subscription, BorrowMutError>
+    where
+        F: Fn(&mut TransactionMut) + 'static,
+    {
+        let mut r = self.store.try_borrow_mut()?;
+        let events = r.events.get_or_init();
+        Ok(events.after_transaction_events.subscribe(Box::new(f)))
+    }
+
     #[cfg(feature = "sync")]
     pub fn observe_after_transaction_with<K, F>(&self, key: K, f: F) -> Result<(), BorrowMutError>
     where
@@ -559,139 +953,496 @@ impl Doc {
         Ok(events.subdocs_events.unsubscribe(&key.into()))
     }
 
-    /// Subscribe callback function, that will be called whenever a [DocRef::destroy] has been called.
+    /// Subscribe callback function, that will be called whenever new root types have been defined
+    /// in this [Doc] - including the ones created by applying remote updates. This way generic
+    /// services, which don't know the document schema up front, can discover new root types and
+    /// subscribe to their changes.
+    ///
+    /// Returns a subscription, which will unsubscribe function when dropped.
     #[cfg(feature = "sync")]
-    pub fn observe_destroy<F>(&self, f: F) -> Result<Subscription, BorrowMutError>
+    pub fn observe_roots<F>(&self, f: F) -> Result<Subscription, BorrowMutError>
     where
-        F: Fn(&TransactionMut, &Doc) + Send + Sync + 'static,
+        F: Fn(&TransactionMut, &RootsEvent) + Send + Sync + 'static,
     {
         let mut r = self.store.try_borrow_mut()?;
         let events = r.events.get_or_init();
-        Ok(events.destroy_events.subscribe(Box::new(f)))
+        Ok(events.roots_events.subscribe(Box::new(f)))
     }
 
-    /// Subscribe callback function, that will be called whenever a [DocRef::destroy] has been called.
+    /// Subscribe callback function, that will be called whenever new root types have been defined
+    /// in this [Doc] - including the ones created by applying remote updates. This way generic
+    /// services, which don't know the document schema up front, can discover new root types and
+    /// subscribe to their changes.
+    ///
+    /// Returns a subscription, which will unsubscribe function when dropped.
     #[cfg(not(feature = "sync"))]
-    pub fn observe_destroy<F>(&self, f: F) -> Result<Subscription, BorrowMutError>
+    pub fn observe_roots<F>(&self, f: F) -> Result<Subscription, BorrowMutError>
     where
-        F: Fn(&TransactionMut, &Doc) + 'static,
+        F: Fn(&TransactionMut, &RootsEvent) + 'static,
     {
         let mut r = self.store.try_borrow_mut()?;
         let events = r.events.get_or_init();
-        Ok(events.destroy_events.subscribe(Box::new(f)))
+        Ok(events.roots_events.subscribe(Box::new(f)))
     }
 
-    /// Subscribe callback function, that will be called whenever a [DocRef::destroy] has been called.
+    /// Subscribe callback function, that will be called whenever new root types have been defined
+    /// in this [Doc]. Callback can be unsubscribed with [Doc::unobserve_roots] using the same `key`.
     #[cfg(feature = "sync")]
-    pub fn observe_destroy_with<K, F>(&self, key: K, f: F) -> Result<(), BorrowMutError>
+    pub fn observe_roots_with<K, F>(&self, key: K, f: F) -> Result<(), BorrowMutError>
     where
         K: Into<Origin>,
-        F: Fn(&TransactionMut, &Doc) + Send + Sync + 'static,
+        F: Fn(&TransactionMut, &RootsEvent) + Send + Sync + 'static,
     {
         let mut r = self.store.try_borrow_mut()?;
         let events = r.events.get_or_init();
-        events
-            .destroy_events
-            .subscribe_with(key.into(), Box::new(f));
+        events.roots_events.subscribe_with(key.into(), Box::new(f));
         Ok(())
     }
 
-    pub fn unobserve_destroy<K>(&self, key: K) -> Result<bool, BorrowMutError>
+    /// Subscribe callback function, that will be called whenever new root types have been defined
+    /// in this [Doc]. Callback can be unsubscribed with [Doc::unobserve_roots] using the same `key`.
+    #[cfg(not(feature = "sync"))]
+    pub fn observe_roots_with<K, F>(&self, key: K, f: F) -> Result<(), BorrowMutError>
     where
         K: Into<Origin>,
+        F: Fn(&TransactionMut, &RootsEvent) + 'static,
     {
         let mut r = self.store.try_borrow_mut()?;
         let events = r.events.get_or_init();
-        Ok(events.destroy_events.unsubscribe(&key.into()))
+        events.roots_events.subscribe_with(key.into(), Box::new(f));
+        Ok(())
     }
 
-    /// Subscribe callback function, that will be called whenever a [DocRef::destroy] has been called.
-    #[cfg(not(feature = "sync"))]
-    pub fn observe_destroy_with<K, F>(&self, key: K, f: F) -> Result<(), BorrowMutError>
+    pub fn unobserve_roots<K>(&self, key: K) -> Result<bool, BorrowMutError>
     where
         K: Into<Origin>,
-        F: Fn(&TransactionMut, &Doc) + 'static,
     {
         let mut r = self.store.try_borrow_mut()?;
         let events = r.events.get_or_init();
-        events
-            .destroy_events
-            .subscribe_with(key.into(), Box::new(f));
-        Ok(())
+        Ok(events.roots_events.unsubscribe(&key.into()))
     }
 
-    /// Sends a load request to a parent document. Works only if current document is a sub-document
-    /// of an another document.
-    pub fn load<T>(&self, parent_txn: &mut T)
+    /// Subscribe callback function, that will be called whenever a root type has been accessed
+    /// as a different type than the one it was defined with - including root types created by
+    /// remote updates, which type has been inferred on their first access. Callbacks are called
+    /// once the transaction, in scope of which a type was accessed, is committed.
+    ///
+    /// Returns a subscription, which will unsubscribe function when dropped.
+    #[cfg(feature = "sync")]
+    pub fn observe_type_repairs<F>(&self, f: F) -> Result<Subscription, BorrowMutError>
     where
-        T: WriteTxn,
+        F: Fn(&TransactionMut, &TypeRepair) + Send + Sync + 'static,
     {
-        let mut txn = self.transact_mut();
-        if txn.store.is_subdoc() {
-            if !txn.store.options.should_load {
-                parent_txn
-                    .subdocs_mut()
-                    .loaded
-                    .insert(self.addr(), self.clone());
-            }
-        }
-        txn.store.options.should_load = true;
+        let mut r = self.store.try_borrow_mut()?;
+        let events = r.events.get_or_init();
+        Ok(events.type_repair_events.subscribe(Box::new(f)))
     }
 
-    /// Starts destroy procedure for a current document, triggering an "destroy" callback and
-    /// invalidating all event callback subscriptions.
-    pub fn destroy<T>(&self, parent_txn: &mut T)
+    /// Subscribe callback function, that will be called whenever a root type has been accessed
+    /// as a different type than the one it was defined with - including root types created by
+    /// remote updates, which type has been inferred on their first access. Callbacks are called
+    /// once the transaction, in scope of which a type was accessed, is committed.
+    ///
+    /// Returns a subscription, which will unsubscribe function when dropped.
+    #[cfg(not(feature = "sync"))]
+    pub fn observe_type_repairs<F>(&self, f: F) -> Result<Subscription, BorrowMutError>
     where
-        T: WriteTxn,
+        F: Fn(&TransactionMut, &TypeRepair) + 'static,
     {
-        let mut txn = self.transact_mut();
-        let store = txn.store_mut();
-        let subdocs: Vec<_> = store.subdocs.values().cloned().collect();
-        for subdoc in subdocs {
-            subdoc.destroy(&mut txn);
-        }
-        if let Some(mut item) = txn.store.parent.take() {
-            let parent_ref = item.clone();
-            let is_deleted = item.is_deleted();
-            if let ItemContent::Doc(_, content) = &mut item.content {
-                let mut options = content.options().clone();
-                options.should_load = false;
-                let new_ref = Doc::subdoc(parent_ref, options);
-                if !is_deleted {
-                    parent_txn
-                        .subdocs_mut()
-                        .added
-                        .insert(new_ref.addr(), new_ref.clone());
-                }
-                parent_txn
-                    .subdocs_mut()
-                    .removed
-                    .insert(new_ref.addr(), new_ref.clone());
-
-                *content = new_ref;
-            }
-        }
-        // super.destroy(): cleanup the events
-        if let Some(events) = txn.store_mut().events.take() {
-            events.destroy_events.trigger(|cb| cb(&txn, self));
-        }
+        let mut r = self.store.try_borrow_mut()?;
+        let events = r.events.get_or_init();
+        Ok(events.type_repair_events.subscribe(Box::new(f)))
     }
 
-    /// If current document has been inserted as a sub-document, returns a reference to a parent
-    /// document, which contains it.
-    pub fn parent_doc(&self) -> Option<Doc> {
-        let store = unsafe { self.store.0.as_ptr().as_ref() }.unwrap();
-        if let Some(item) = store.parent.as_deref() {
-            if let ItemContent::Doc(parent_doc, _) = &item.content {
-                return parent_doc.clone();
-            }
-        }
-
-        None
+    /// Subscribe callback function, that will be called whenever a pending update has exceeded
+    /// the limits configured with [Doc::set_pending_policy].
+    ///
+    /// Returns a subscription, which will unsubscribe function when dropped.
+    #[cfg(feature = "sync")]
+    pub fn observe_pending_evictions<F>(&self, f: F) -> Result<Subscription, BorrowMutError>
+    where
+        F: Fn(&TransactionMut, &PendingEvictionEvent) + Send + Sync + 'static,
+    {
+        let mut r = self.store.try_borrow_mut()?;
+        let events = r.events.get_or_init();
+        Ok(events.pending_eviction_events.subscribe(Box::new(f)))
     }
 
-    pub fn branch_id(&self) -> Option<BranchID> {
-        let store = unsafe { self.store.0.as_ptr().as_ref() }.unwrap();
+    /// Subscribe callback function, that will be called whenever a pending update has exceeded
+    /// the limits configured with [Doc::set_pending_policy].
+    ///
+    /// Returns a subscription, which will unsubscribe function when dropped.
+    #[cfg(not(feature = "sync"))]
+    pub fn observe_pending_evictions<F>(&self, f: F) -> Result<Subscription, BorrowMutError>
+    where
+        F: Fn(&TransactionMut, &PendingEvictionEvent) + 'static,
+    {
+        let mut r = self.store.try_borrow_mut()?;
+        let events = r.events.get_or_init();
+        Ok(events.pending_eviction_events.subscribe(Box::new(f)))
+    }
+
+    /// Subscribe callback function, that will be called whenever a transaction has removed large
+    /// amounts of data (see: [Doc::set_destructive_policy]) or tombstones have been pruned with
+    /// [TransactionMut::gc_with]. Callbacks are called before update events are emitted.
+    ///
+    /// Returns a subscription, which will unsubscribe function when dropped.
+    #[cfg(feature = "sync")]
+    pub fn observe_destructive_ops<F>(&self, f: F) -> Result<Subscription, BorrowMutError>
+    where
+        F: Fn(&TransactionMut, &DestructiveOp) + Send + Sync + 'static,
+    {
+        let mut r = self.store.try_borrow_mut()?;
+        let events = r.events.get_or_init();
+        Ok(events.destructive_events.subscribe(Box::new(f)))
+    }
+
+    /// Subscribe callback function, that will be called whenever a transaction has removed large
+    /// amounts of data (see: [Doc::set_destructive_policy]) or tombstones have been pruned with
+    /// [TransactionMut::gc_with]. Callbacks are called before update events are emitted.
+    ///
+    /// Returns a subscription, which will unsubscribe function when dropped.
+    #[cfg(not(feature = "sync"))]
+    pub fn observe_destructive_ops<F>(&self, f: F) -> Result<Subscription, BorrowMutError>
+    where
+        F: Fn(&TransactionMut, &DestructiveOp) + 'static,
+    {
+        let mut r = self.store.try_borrow_mut()?;
+        let events = r.events.get_or_init();
+        Ok(events.destructive_events.subscribe(Box::new(f)))
+    }
+
+    /// Subscribe callback function, that will be called with panics caught in observer callbacks,
+    /// once the transaction in which they were raised has been committed. Panics are caught only
+    /// if [Options::isolate_observers] is enabled. Panics raised by this callback itself are
+    /// propagated.
+    ///
+    /// Returns a subscription, which will unsubscribe function when dropped.
+    #[cfg(feature = "sync")]
+    pub fn observe_callback_errors<F>(&self, f: F) -> Result<Subscription, BorrowMutError>
+    where
+        F: Fn(&TransactionMut, &[CallbackError]) + Send + Sync + 'static,
+    {
+        let mut r = self.store.try_borrow_mut()?;
+        let events = r.events.get_or_init();
+        Ok(events.callback_error_events.subscribe(Box::new(f)))
+    }
+
+    /// Subscribe callback function, that will be called with panics caught in observer callbacks,
+    /// once the transaction in which they were raised has been committed. Panics are caught only
+    /// if [Options::isolate_observers] is enabled. Panics raised by this callback itself are
+    /// propagated.
+    ///
+    /// Returns a subscription, which will unsubscribe function when dropped.
+    #[cfg(not(feature = "sync"))]
+    pub fn observe_callback_errors<F>(&self, f: F) -> Result<Subscription, BorrowMutError>
+    where
+        F: Fn(&TransactionMut, &[CallbackError]) + 'static,
+    {
+        let mut r = self.store.try_borrow_mut()?;
+        let events = r.events.get_or_init();
+        Ok(events.callback_error_events.subscribe(Box::new(f)))
+    }
+
+    /// Subscribe callback function, that will be called whenever a [DocRef::destroy] has been called.
+    #[cfg(feature = "sync")]
+    pub fn observe_destroy<F>(&self, f: F) -> Result<Subscription, BorrowMutError>
+    where
+        F: Fn(&TransactionMut, &Doc) + Send + Sync + 'static,
+    {
+        let mut r = self.store.try_borrow_mut()?;
+        let events = r.events.get_or_init();
+        Ok(events.destroy_events.subscribe(Box::new(f)))
+    }
+
+    /// Subscribe callback function, that will be called whenever a [DocRef::destroy] has been called.
+    #[cfg(not(feature = "sync"))]
+    pub fn observe_destroy<F>(&self, f: F) -> Result<Subscription, BorrowMutError>
+    where
+        F: Fn(&TransactionMut, &Doc) + 'static,
+    {
+        let mut r = self.store.try_borrow_mut()?;
+        let events = r.events.get_or_init();
+        Ok(events.destroy_events.subscribe(Box::new(f)))
+    }
+
+    /// Subscribe callback function, that will be called whenever a [DocRef::destroy] has been called.
+    #[cfg(feature = "sync")]
+    pub fn observe_destroy_with<K, F>(&self, key: K, f: F) -> Result<(), BorrowMutError>
+    where
+        K: Into<Origin>,
+        F: Fn(&TransactionMut, &Doc) + Send + Sync + 'static,
+    {
+        let mut r = self.store.try_borrow_mut()?;
+        let events = r.events.get_or_init();
+        events
+            .destroy_events
+            .subscribe_with(key.into(), Box::new(f));
+        Ok(())
+    }
+
+    pub fn unobserve_destroy<K>(&self, key: K) -> Result<bool, BorrowMutError>
+    where
+        K: Into<Origin>,
+    {
+        let mut r = self.store.try_borrow_mut()?;
+        let events = r.events.get_or_init();
+        Ok(events.destroy_events.unsubscribe(&key.into()))
+    }
+
+    /// Subscribe callback function, that will be called whenever a [DocRef::destroy] has been called.
+    #[cfg(not(feature = "sync"))]
+    pub fn observe_destroy_with<K, F>(&self, key: K, f: F) -> Result<(), BorrowMutError>
+    where
+        K: Into<Origin>,
+        F: Fn(&TransactionMut, &Doc) + 'static,
+    {
+        let mut r = self.store.try_borrow_mut()?;
+        let events = r.events.get_or_init();
+        events
+            .destroy_events
+            .subscribe_with(key.into(), Box::new(f));
+        Ok(())
+    }
+
+    /// Sends a load request to a parent document. Works only if current document is a sub-document
+    /// of an another document.
+    pub fn load<T>(&self, parent_txn: &mut T)
+    where
+        T: WriteTxn,
+    {
+        let mut txn = self.transact_mut();
+        if txn.store.is_subdoc() && !txn.store.options.should_load {
+            parent_txn
+                .subdocs_mut()
+                .loaded
+                .insert(self.addr(), self.clone());
+        }
+        txn.store.options.should_load = true;
+    }
+
+    /// Starts destroy procedure for a current document, triggering an "destroy" callback and
+    /// invalidating all event callback subscriptions.
+    pub fn destroy<T>(&self, parent_txn: &mut T)
+    where
+        T: WriteTxn,
+    {
+        let origin = {
+            let parent_origin = parent_txn.origin().cloned();
+            parent_txn
+                .store_mut()
+                .subdoc_origin(parent_origin.as_ref(), self)
+        };
+        // destroy must proceed even if current document has been closed already
+        let mut txn = self.transact_mut_closed(origin);
+        let store = txn.store_mut();
+        let subdocs: Vec<_> = store.subdocs.values().cloned().collect();
+        for subdoc in subdocs {
+            subdoc.destroy(&mut txn);
+        }
+        if let Some(mut item) = txn.store.parent.take() {
+            let parent_ref = item;
+            let is_deleted = item.is_deleted();
+            if let ItemContent::Doc(_, content) = &mut item.content {
+                let mut options = content.options().clone();
+                options.should_load = false;
+                let new_ref = Doc::subdoc(parent_ref, options);
+                if !is_deleted {
+                    parent_txn
+                        .subdocs_mut()
+                        .added
+                        .insert(new_ref.addr(), new_ref.clone());
+                }
+                parent_txn
+                    .subdocs_mut()
+                    .removed
+                    .insert(new_ref.addr(), new_ref.clone());
+
+                *content = new_ref;
+            }
+        }
+        // super.destroy(): cleanup the events
+        if let Some(events) = txn.store_mut().events.take() {
+            events
+                .destroy_events
+                .trigger(|cb| txn.isolate(|| cb(&txn, self)));
+            // events are gone by the time this transaction is committed, report panics right away
+            let errors = std::mem::take(txn.callback_errors.get_mut());
+            if !errors.is_empty() {
+                events
+                    .callback_error_events
+                    .trigger(|cb| txn.isolate(|| cb(&txn, &errors)));
+            }
+        }
+        let hooks = self.take_close_hooks(txn.store_mut());
+        drop(txn);
+        self.run_close_hooks(hooks);
+    }
+
+    /// Registers a cleanup hook, which will be called once current document is closed (see:
+    /// [Doc::close]) or destroyed (see: [Doc::destroy]), i.e. to flush pending changes to
+    /// a persistent storage or to close network providers.
+    ///
+    /// Hooks are called in ascending `order`. Hooks with the same order are called in the order
+    /// of their registration. If current document has been closed already, `f` is called
+    /// right away.
+    #[cfg(feature = "sync")]
+    pub fn on_close<F>(&self, order: i32, f: F) -> Result<(), BorrowMutError>
+    where
+        F: FnOnce(&Doc) + Send + Sync + 'static,
+    {
+        self.register_close_hook(order, CloseHook::Sync(Box::new(f)))
+    }
+
+    /// Registers a cleanup hook, which will be called once current document is closed (see:
+    /// [Doc::close]) or destroyed (see: [Doc::destroy]), i.e. to flush pending changes to
+    /// a persistent storage or to close network providers.
+    ///
+    /// Hooks are called in ascending `order`. Hooks with the same order are called in the order
+    /// of their registration. If current document has been closed already, `f` is called
+    /// right away.
+    #[cfg(not(feature = "sync"))]
+    pub fn on_close<F>(&self, order: i32, f: F) -> Result<(), BorrowMutError>
+    where
+        F: FnOnce(&Doc) + 'static,
+    {
+        self.register_close_hook(order, CloseHook::Sync(Box::new(f)))
+    }
+
+    /// Asynchronous counterpart of [Doc::on_close]. Future returned by `f` is awaited before
+    /// the next hook is called. When the document is closed using [Doc::close] or destroyed,
+    /// a current thread is blocked until the future completes.
+    #[cfg(feature = "async")]
+    pub fn on_close_async<F, Fut>(&self, order: i32, f: F) -> Result<(), BorrowMutError>
+    where
+        F: FnOnce(Doc) -> Fut + Send + Sync + 'static,
+        Fut: std::future::Future<Output = ()> + Send + 'static,
+    {
+        let hook: crate::store::AsyncCloseFn = Box::new(move |doc| Box::pin(f(doc)));
+        self.register_close_hook(order, CloseHook::Async(hook))
+    }
+
+    /// Closes current document. Once this method returns, no new read-write transactions can be
+    /// started over this document - [Transact::try_transact_mut] will return
+    /// [TransactionAcqError::DocumentClosed]. Read-only transactions are still allowed.
+    ///
+    /// Closing calls all hooks registered with [Doc::on_close] in their order and drops all event
+    /// subscriptions. It fails if there's another transaction in progress. Closing already closed
+    /// document is a no-op.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::sync::{Arc, Mutex};
+    /// use yrs::doc::TransactionAcqError;
+    /// use yrs::{Doc, Transact};
+    ///
+    /// let doc = Doc::new();
+    /// let log = Arc::new(Mutex::new(Vec::new()));
+    /// let l = log.clone();
+    /// doc.on_close(1, move |_| l.lock().unwrap().push("close provider")).unwrap();
+    /// let l = log.clone();
+    /// doc.on_close(0, move |_| l.lock().unwrap().push("flush")).unwrap();
+    ///
+    /// doc.close().unwrap();
+    /// assert_eq!(*log.lock().unwrap(), vec!["flush", "close provider"]);
+    /// assert!(matches!(doc.try_transact_mut(), Err(TransactionAcqError::DocumentClosed)));
+    /// ```
+    pub fn close(&self) -> Result<(), TransactionAcqError> {
+        let hooks = {
+            let mut store = self.store.try_borrow_mut()?;
+            self.take_close_hooks(&mut store)
+        };
+        self.run_close_hooks(hooks);
+        Ok(())
+    }
+
+    /// Asynchronous counterpart of [Doc::close], which awaits hooks registered with
+    /// [Doc::on_close_async] instead of blocking a current thread.
+    #[cfg(feature = "async")]
+    pub async fn close_async(&self) -> Result<(), TransactionAcqError> {
+        let hooks = {
+            let mut store = self.store.try_borrow_mut()?;
+            self.take_close_hooks(&mut store)
+        };
+        for hook in hooks {
+            match hook {
+                CloseHook::Sync(f) => f(self),
+                CloseHook::Async(f) => f(self.clone()).await,
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks if current document has been closed (see: [Doc::close]) or destroyed.
+    pub fn is_closed(&self) -> bool {
+        self.store.is_closed()
+    }
+
+    fn register_close_hook(&self, order: i32, hook: CloseHook) -> Result<(), BorrowMutError> {
+        let mut store = self.store.try_borrow_mut()?;
+        if self.store.is_closed() {
+            drop(store);
+            self.run_close_hooks(vec![hook]);
+        } else {
+            store.close_hooks.push((order, hook));
+        }
+        Ok(())
+    }
+
+    /// Marks a store as closed, drops its event subscriptions and returns its cleanup hooks
+    /// in the order of their execution. Returns no hooks if the store has been closed already.
+    fn take_close_hooks(&self, store: &mut Store) -> Vec<CloseHook> {
+        if self.store.close() {
+            return Vec::new();
+        }
+        store.events = None;
+        let mut hooks = std::mem::take(&mut store.close_hooks);
+        hooks.sort_by_key(|(order, _)| *order);
+        hooks.into_iter().map(|(_, hook)| hook).collect()
+    }
+
+    /// Creates a read-write transaction regardless of whether current document has been closed.
+    #[cfg_attr(feature = "borrow-tracker", track_caller)]
+    fn transact_mut_closed(&self, origin: Option<Origin>) -> TransactionMut<'_> {
+        let store = match self.store.try_borrow_mut() {
+            Ok(store) => store,
+            #[cfg(feature = "borrow-tracker")]
+            Err(e) => borrow_tracker::acquisition_failed(self, e),
+            #[cfg(not(feature = "borrow-tracker"))]
+            Err(_) => panic!("there's another active transaction at the moment"),
+        };
+        let txn = TransactionMut::new(self.clone(), store, origin);
+        #[cfg(feature = "borrow-tracker")]
+        let txn = txn.tracked(self);
+        txn
+    }
+
+    fn run_close_hooks(&self, hooks: Vec<CloseHook>) {
+        for hook in hooks {
+            match hook {
+                CloseHook::Sync(f) => f(self),
+                #[cfg(feature = "async")]
+                CloseHook::Async(f) => crate::stream::block_on(f(self.clone())),
+            }
+        }
+    }
+
+    /// If current document has been inserted as a sub-document, returns a reference to a parent
+    /// document, which contains it.
+    pub fn parent_doc(&self) -> Option<Doc> {
+        let store = unsafe { self.store.as_ptr().as_ref() }.unwrap();
+        if let Some(item) = store.parent.as_deref() {
+            if let ItemContent::Doc(parent_doc, _) = &item.content {
+                return parent_doc.clone();
+            }
+        }
+
+        None
+    }
+
+    pub fn branch_id(&self) -> Option<BranchID> {
+        let store = unsafe { self.store.as_ptr().as_ref() }.unwrap();
         if let Some(item) = store.parent {
             Some(BranchID::Nested(item.id))
         } else {
@@ -699,12 +1450,456 @@ impl Doc {
         }
     }
 
-    pub fn ptr_eq(a: &Doc, b: &Doc) -> bool {
-        Arc::ptr_eq(&a.store.0, &b.store.0)
+    pub fn ptr_eq(a: &Doc, b: &Doc) -> bool {
+        Arc::ptr_eq(&a.store.0, &b.store.0)
+    }
+
+    /// Returns information about all transactions of this document, which are currently active,
+    /// including source code locations they were created at.
+    #[cfg(feature = "borrow-tracker")]
+    pub fn active_transactions(&self) -> Vec<borrow_tracker::ActiveTransaction> {
+        borrow_tracker::active_transactions(self)
+    }
+
+    pub(crate) fn addr(&self) -> DocAddr {
+        DocAddr::new(self)
+    }
+
+    /// Creates a new document, which contents are a deep copy of a given `template` document.
+    ///
+    /// New document doesn't share any history with its template: it's created with a fresh
+    /// client identifier and guid, and all of its contents are written as brand new insertions.
+    /// Offset kind and garbage collection settings are inherited from the template.
+    ///
+    /// While copying, placeholders in form of `{{name}}` are replaced using provided
+    /// `substitutions`:
+    /// - string values (i.e. map entries or array elements) which consist of a single placeholder
+    ///   are replaced with a substitution value registered under its name,
+    /// - placeholders found inside of text chunks or other string values are replaced with
+    ///   a string representation of a corresponding substitution value.
+    ///
+    /// Placeholders without matching substitutions are left untouched. XML nodes are copied as is.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::collections::HashMap;
+    /// use yrs::{Any, Doc, GetString, Map, Text, Transact};
+    ///
+    /// let template = Doc::new();
+    /// let text = template.get_or_insert_text("text");
+    /// let map = template.get_or_insert_map("map");
+    /// {
+    ///     let mut txn = template.transact_mut();
+    ///     text.push(&mut txn, "Dear {{name}},");
+    ///     map.insert(&mut txn, "age", "{{age}}");
+    /// }
+    ///
+    /// let mut substitutions = HashMap::new();
+    /// substitutions.insert("name".to_string(), Any::from("Alice"));
+    /// substitutions.insert("age".to_string(), Any::from(30));
+    /// let doc = Doc::instantiate_template(&template, &substitutions);
+    ///
+    /// let text = doc.get_or_insert_text("text");
+    /// let map = doc.get_or_insert_map("map");
+    /// let txn = doc.transact();
+    /// assert_eq!(text.get_string(&txn), "Dear Alice,");
+    /// assert_eq!(map.get(&txn, "age"), Some(30.into()));
+    /// ```
+    pub fn instantiate_template(template: &Doc, substitutions: &HashMap<String, Any>) -> Doc {
+        let doc = Doc::with_options(template.derived_options());
+        {
+            let src = template.transact();
+            let mut txn = doc.transact_mut();
+            let placeholders = Placeholders(substitutions);
+            for (name, value) in src.root_refs() {
+                let prelim = placeholders.substitute(&value, &src);
+                copy_root(&mut txn, name, prelim);
+            }
+        }
+        doc
+    }
+
+    /// Creates a new document, containing a copy of the visible contents of the selected root
+    /// types and nested values of a current document. Each of the `paths` must start with a root
+    /// type name (as [PathSegment::Key]), followed by map keys and array indexes leading to
+    /// a selected value.
+    ///
+    /// Selected values are placed in a new document under the same paths: maps and arrays along
+    /// the way are recreated, but they only contain selected entries. Array elements keep their
+    /// relative order, but are re-indexed. Paths which don't lead to existing values are skipped,
+    /// while paths pointing inside of types other than maps and arrays (i.e. text or XML nodes)
+    /// select their entire contents.
+    ///
+    /// Just like [Doc::instantiate_template], new document doesn't share any history with
+    /// a current one and uses a fresh client identifier and guid.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::collections::VecDeque;
+    /// use yrs::types::PathSegment;
+    /// use yrs::{any, Doc, Map, MapPrelim, Transact};
+    /// use yrs::types::ToJson;
+    ///
+    /// let doc = Doc::new();
+    /// let map = doc.get_or_insert_map("map");
+    /// {
+    ///     let mut txn = doc.transact_mut();
+    ///     map.insert(&mut txn, "a", MapPrelim::from([("b", 1), ("c", 2)]));
+    ///     map.insert(&mut txn, "d", 3);
+    /// }
+    ///
+    /// let path = VecDeque::from(vec![
+    ///     PathSegment::Key("map".into()),
+    ///     PathSegment::Key("a".into()),
+    ///     PathSegment::Key("c".into()),
+    /// ]);
+    /// let extracted = doc.extract(&[path]);
+    /// let txn = extracted.transact();
+    /// assert_eq!(extracted.to_json(&txn), any!({"map": {"a": {"c": 2}}}));
+    /// ```
+    pub fn extract(&self, paths: &[Path]) -> Doc {
+        let mut roots: HashMap<Arc<str>, Selection> = HashMap::new();
+        for path in paths {
+            let mut segments = path.iter();
+            if let Some(PathSegment::Key(root)) = segments.next() {
+                roots
+                    .entry(root.clone())
+                    .or_insert_with(Selection::empty)
+                    .select(segments);
+            }
+        }
+
+        let doc = Doc::with_options(self.derived_options());
+        {
+            let src = self.transact();
+            let mut txn = doc.transact_mut();
+            for (name, value) in src.root_refs() {
+                if let Some(selection) = roots.get(name) {
+                    if let Some(prelim) = selection.extract(value, &src) {
+                        copy_root(&mut txn, name, prelim);
+                    }
+                }
+            }
+        }
+        doc
+    }
+
+    /// Copies contents of a shared collection living under a given `path` of `other` document into
+    /// a current one, placing it under the same path.
+    ///
+    /// Whenever it's safe to do so, imported contents retain the client identifiers and clocks they
+    /// had in the `other` document, so that attribution information (i.e. who inserted which
+    /// piece of content) survives document splits and merges. This happens when:
+    /// - `path` points to an entire root type, and
+    /// - none of the clients who contributed to that root type have contributed to a current
+    ///   document yet, and there are no pending updates awaiting to be integrated.
+    ///
+    /// In such case blocks of these clients, which don't belong to an imported root type, are
+    /// recorded as garbage collected: a synthetic history, which lets imported blocks keep their
+    /// original clocks. Because of that, a current document should no longer receive updates
+    /// from these clients produced for the `other` document.
+    ///
+    /// Otherwise contents are copied just like in case of [Doc::extract], as new insertions made
+    /// by a current document's client.
+    ///
+    /// Returns `true` if original identifiers have been preserved.
+    pub fn import_content_from(&self, other: &Doc, path: &Path) -> bool {
+        if Doc::ptr_eq(self, other) {
+            return false;
+        }
+        let mut segments = path.iter();
+        let root_name = match segments.next() {
+            Some(PathSegment::Key(name)) => name.clone(),
+            _ => return false,
+        };
+        let src = other.transact();
+        let mut txn = self.transact_mut();
+        let root = match src.store().get_type(root_name.clone()) {
+            Some(root) => root,
+            None => return false,
+        };
+
+        if path.len() == 1 {
+            let store = src.store();
+            let clients = store.subtree_clients(root);
+            let local = txn.store();
+            let safe = local.pending.is_none()
+                && local.pending_ds.is_none()
+                && clients.iter().all(|c| local.blocks.get_clock(c) == 0);
+            if safe {
+                let mut encoder = EncoderV1::new();
+                store.encode_subtree(root, &clients, &mut encoder);
+                if let Ok(update) = Update::decode_v1(&encoder.to_vec()) {
+                    txn.apply_update(update);
+                    return true;
+                }
+            }
+        }
+
+        let mut selection = Selection::empty();
+        selection.select(segments);
+        if let Some(prelim) = selection.extract(root.into(), &src) {
+            copy_root(&mut txn, &root_name, prelim);
+        }
+        false
+    }
+
+    /// Creates a new document, which represents the state of a current document exactly at the
+    /// moment when a given `snapshot` was made. New document shares the history of a current one
+    /// up to that point, including tombstones of elements deleted before the snapshot. It uses
+    /// a fresh client identifier and guid, just like [Doc::extract].
+    ///
+    /// Since restoring requires contents of deleted elements, a current document must have garbage
+    /// collection disabled (see: [Options::skip_gc]), otherwise an error is returned.
+    ///
+    /// Use [TransactionMut::revert_to_snapshot] in order to roll back a current document instead.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use yrs::{Doc, GetString, Options, ReadTxn, Text, Transact};
+    ///
+    /// let doc = Doc::with_options(Options {
+    ///     skip_gc: true,
+    ///     ..Options::default()
+    /// });
+    /// let text = doc.get_or_insert_text("text");
+    /// text.push(&mut doc.transact_mut(), "hello");
+    /// let snapshot = doc.transact().snapshot();
+    /// text.push(&mut doc.transact_mut(), " world");
+    ///
+    /// let restored = doc.restore_from_snapshot(&snapshot).unwrap();
+    /// let text = restored.get_or_insert_text("text");
+    /// assert_eq!(text.get_string(&restored.transact()), "hello");
+    /// ```
+    pub fn restore_from_snapshot(&self, snapshot: &Snapshot) -> Result<Doc, crate::Error> {
+        let src = self.transact();
+        let mut encoder = EncoderV1::new();
+        src.encode_state_from_snapshot(snapshot, &mut encoder)?;
+        let update = Update::decode_v1(&encoder.to_vec())?;
+        let doc = Doc::with_options(self.derived_options());
+        {
+            let mut txn = doc.transact_mut();
+            // define root types up front, so that they are not restored as undefined ones
+            for (name, branch) in src.store().types.iter() {
+                let type_ref = branch.type_ref().clone();
+                txn.store_mut().get_or_create_type(name.clone(), type_ref);
+            }
+            txn.apply_update(update);
+        }
+        Ok(doc)
+    }
+
+    /// Returns a read-only view of a current document at the moment when a given `snapshot` was
+    /// made. It works like [Doc::restore_from_snapshot], except that returned document is closed
+    /// (see: [Doc::close]), so it can be read but not modified.
+    ///
+    /// See [crate::history] for replaying stored updates and producing snapshots to check out.
+    pub fn checkout(&self, snapshot: &Snapshot) -> Result<Doc, crate::Error> {
+        let doc = self.restore_from_snapshot(snapshot)?;
+        // newly restored document is not shared yet, so it can always be closed
+        doc.close().unwrap();
+        Ok(doc)
+    }
+
+    /// Returns options for a document derived from a current one, that doesn't share its identity.
+    fn derived_options(&self) -> Options {
+        let options = self.options();
+        Options {
+            offset_kind: options.offset_kind,
+            skip_gc: options.skip_gc,
+            gc_policy: options.gc_policy.clone(),
+            strict_types: options.strict_types,
+            ..Options::default()
+        }
+    }
+}
+
+/// A tree of values selected by [Doc::extract].
+enum Selection {
+    /// Entire value has been selected.
+    All,
+    /// Only specific entries of a map have been selected.
+    Keys(HashMap<Arc<str>, Selection>),
+    /// Only specific elements of an array have been selected.
+    Indexes(BTreeMap<u32, Selection>),
+}
+
+impl Selection {
+    fn empty() -> Self {
+        Selection::Keys(HashMap::new())
+    }
+
+    fn select<'a, I>(&mut self, mut segments: I)
+    where
+        I: Iterator<Item = &'a PathSegment>,
+    {
+        match segments.next() {
+            None => *self = Selection::All,
+            Some(segment) => {
+                if let Selection::Keys(keys) = self {
+                    // a fresh node can be turned into either kind of selection
+                    if keys.is_empty() {
+                        if let PathSegment::Index(_) = segment {
+                            *self = Selection::Indexes(BTreeMap::new());
+                        }
+                    }
+                }
+                match (self, segment) {
+                    (Selection::Keys(keys), PathSegment::Key(key)) => keys
+                        .entry(key.clone())
+                        .or_insert_with(Selection::empty)
+                        .select(segments),
+                    (Selection::Indexes(indexes), PathSegment::Index(index)) => indexes
+                        .entry(*index)
+                        .or_insert_with(Selection::empty)
+                        .select(segments),
+                    _ => { /* entire value already selected or mismatched path */ }
+                }
+            }
+        }
+    }
+
+    fn extract<T: ReadTxn>(&self, value: Out, txn: &T) -> Option<In> {
+        let value = match value {
+            // root types decoded from remote updates may have no type information attached
+            Out::UndefinedRef(branch) => infer_type_from_content(branch),
+            other => other,
+        };
+        match (self, value) {
+            (Selection::Keys(keys), Out::YMap(map)) => Some(In::Map(
+                keys.iter()
+                    .filter_map(|(key, selection)| {
+                        let value = map.get(txn, key)?;
+                        Some((key.clone(), selection.extract(value, txn)?))
+                    })
+                    .collect(),
+            )),
+            (Selection::Indexes(indexes), Out::YArray(array)) => Some(In::Array(
+                indexes
+                    .iter()
+                    .filter_map(|(&index, selection)| {
+                        let value = array.get(txn, index)?;
+                        selection.extract(value, txn)
+                    })
+                    .collect(),
+            )),
+            (Selection::All, value) => Some(value.as_prelim(txn)),
+            (_, Out::YMap(_)) | (_, Out::YArray(_)) | (_, Out::Any(_)) => None,
+            (_, value) => Some(value.as_prelim(txn)),
+        }
+    }
+}
+
+/// Writes contents of a given `prelim` into a root type of a matching kind, stored under a given
+/// `name`. Prelims which cannot be represented as root types are ignored.
+fn copy_root(txn: &mut TransactionMut, name: &str, prelim: In) {
+    fn ptr<S: SharedRef>(root: S) -> BranchPtr {
+        BranchPtr::from(root.as_ref())
+    }
+
+    let root = match &prelim {
+        In::Text(_) => ptr(txn.get_or_insert_text(name)),
+        In::Map(_) => ptr(txn.get_or_insert_map(name)),
+        In::Array(_) => ptr(txn.get_or_insert_array(name)),
+        In::XmlFragment(_) => ptr(txn.get_or_insert_xml_fragment(name)),
+        _ => return,
+    };
+    prelim.integrate(txn, root);
+}
+
+/// Helper used to replace `{{name}}` placeholders while copying document contents.
+struct Placeholders<'a>(&'a HashMap<String, Any>);
+
+impl<'a> Placeholders<'a> {
+    fn substitute<T: ReadTxn>(&self, value: &Out, txn: &T) -> In {
+        match value {
+            Out::Any(any) => In::Any(self.substitute_any(any)),
+            Out::YText(text) => In::Text(
+                text.diff(txn, YChange::identity)
+                    .into_iter()
+                    .map(|diff| {
+                        let insert = match diff.insert {
+                            // text chunks are always substituted in place
+                            Out::Any(Any::String(chunk)) => {
+                                In::Any(Any::String(self.substitute_str(&chunk).into()))
+                            }
+                            other => self.substitute(&other, txn),
+                        };
+                        Delta::Inserted(insert, diff.attributes)
+                    })
+                    .collect(),
+            ),
+            Out::YMap(map) => In::Map(
+                map.iter(txn)
+                    .map(|(key, value)| (key, self.substitute(&value, txn)))
+                    .collect(),
+            ),
+            Out::YArray(array) => In::Array(
+                array
+                    .iter(txn)
+                    .map(|value| self.substitute(&value, txn))
+                    .collect(),
+            ),
+            Out::UndefinedRef(branch) => self.substitute(&infer_type_from_content(*branch), txn),
+            other => other.as_prelim(txn),
+        }
+    }
+
+    fn substitute_any(&self, value: &Any) -> Any {
+        match value {
+            Any::String(str) => match Self::placeholder_name(str).and_then(|k| self.0.get(k)) {
+                Some(value) => value.clone(),
+                None => Any::String(self.substitute_str(str).into()),
+            },
+            Any::Array(values) => {
+                Any::Array(values.iter().map(|v| self.substitute_any(v)).collect())
+            }
+            Any::Map(entries) => Any::from(
+                entries
+                    .iter()
+                    .map(|(k, v)| (k.clone(), self.substitute_any(v)))
+                    .collect::<HashMap<_, _>>(),
+            ),
+            other => other.clone(),
+        }
+    }
+
+    fn substitute_str(&self, str: &str) -> String {
+        let mut result = String::with_capacity(str.len());
+        let mut remaining = str;
+        while let Some(start) = remaining.find("{{") {
+            let (before, rest) = remaining.split_at(start);
+            result.push_str(before);
+            match rest.find("}}") {
+                Some(end) => {
+                    let placeholder = &rest[..end + 2];
+                    match self.0.get(&rest[2..end]) {
+                        Some(Any::String(value)) => result.push_str(value),
+                        Some(value) => result.push_str(&value.to_string()),
+                        None => result.push_str(placeholder),
+                    }
+                    remaining = &rest[end + 2..];
+                }
+                None => {
+                    remaining = rest;
+                    break;
+                }
+            }
+        }
+        result.push_str(remaining);
+        result
     }
 
-    pub(crate) fn addr(&self) -> DocAddr {
-        DocAddr::new(&self)
+    fn placeholder_name(str: &str) -> Option<&str> {
+        let name = str.strip_prefix("{{")?.strip_suffix("}}")?;
+        if name.contains("{{") || name.contains("}}") {
+            None
+        } else {
+            Some(name)
+        }
     }
 }
 
@@ -784,6 +1979,34 @@ pub struct Options {
     ///
     /// Default value: `true`.
     pub should_load: bool,
+    /// Rule used to order concurrent insertions made at the same position. This rule must be
+    /// configured the same way by all collaborating peers, otherwise their document states will
+    /// diverge. It's not being replicated (i.e. for subdocuments).
+    ///
+    /// Default value: [ConflictOrder::ClientId].
+    pub conflict_order: ConflictOrder,
+    /// Policy used to determine which deleted elements can be garbage collected, when transaction
+    /// is committed. It's not being replicated (i.e. for subdocuments).
+    ///
+    /// Default value: [GcPolicy::default].
+    pub gc_policy: GcPolicy,
+    /// If `true`, accessing a root-level collection as a type, which conflicts with the one it
+    /// was defined with (or with its contents in case of collections created by remote updates)
+    /// fails instead of reinterpreting the collection as a requested type. Fallible access is
+    /// possible with [Root::try_get_or_create] - other methods, like [Doc::get_or_insert_map],
+    /// panic in such case. It's not being replicated (i.e. for subdocuments).
+    ///
+    /// Default value: `false`.
+    ///
+    /// [Root::try_get_or_create]: crate::Root::try_get_or_create
+    pub strict_types: bool,
+    /// If `true`, panics raised by observer callbacks called while committing a transaction are
+    /// caught, so that the remaining callbacks are still called and the commit is always
+    /// completed. Caught panics are reported to [Doc::observe_callback_errors] subscribers.
+    /// It's not being replicated (i.e. for subdocuments).
+    ///
+    /// Default value: `false`.
+    pub isolate_observers: bool,
 }
 
 impl Options {
@@ -796,6 +2019,10 @@ impl Options {
             skip_gc: false,
             auto_load: false,
             should_load: true,
+            conflict_order: ConflictOrder::ClientId,
+            gc_policy: GcPolicy::default(),
+            strict_types: false,
+            isolate_observers: false,
         }
     }
 
@@ -808,10 +2035,14 @@ impl Options {
             skip_gc: false,
             auto_load: false,
             should_load: true,
+            conflict_order: ConflictOrder::ClientId,
+            gc_policy: GcPolicy::default(),
+            strict_types: false,
+            isolate_observers: false,
         }
     }
 
-    fn as_any(&self) -> Any {
+    pub(crate) fn as_any(&self) -> Any {
         let mut m = HashMap::new();
         m.insert("gc".to_owned(), (!self.skip_gc).into());
         if let Some(collection_id) = self.collection_id.as_ref() {
@@ -847,12 +2078,21 @@ impl Encode for Options {
 
 impl Decode for Options {
     fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, Error> {
+        let guid: Uuid = decoder.read_string()?.into();
+        let opts = decoder.read_any()?;
+        Ok(Options::from_any(guid, &opts))
+    }
+}
+
+impl Options {
+    /// Restores options of a sub-document from its `guid` and options serialized with
+    /// [Options::as_any].
+    pub(crate) fn from_any(guid: Uuid, opts: &Any) -> Self {
         let mut options = Options::default();
         options.should_load = false; // for decoding shouldLoad is false by default
-        let guid = decoder.read_string()?;
-        options.guid = guid.into();
+        options.guid = guid;
 
-        if let Any::Map(opts) = decoder.read_any()? {
+        if let Any::Map(opts) = opts {
             for (k, v) in opts.iter() {
                 match (k.as_str(), v) {
                     ("gc", Any::Bool(gc)) => options.skip_gc = !*gc,
@@ -867,7 +2107,7 @@ impl Decode for Options {
             }
         }
 
-        Ok(options)
+        options
     }
 }
 
@@ -881,6 +2121,73 @@ pub enum OffsetKind {
     Utf16,
 }
 
+/// Determines the order in which concurrent insertions made at the same position are placed.
+///
+/// Concurrent insertions into sequences (i.e. array elements or text chunks) are placed next to
+/// each other. For map entries, the insertion placed last determines the value that wins.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum ConflictOrder {
+    /// Concurrent insertions are ordered by their client identifiers: insertions of clients with
+    /// lower identifiers are placed first, so that map entries set by clients with higher
+    /// identifiers win. This is the default and the only rule compatible with Yjs.
+    #[default]
+    ClientId,
+    /// Concurrent insertions are ordered by application-supplied client priorities, i.e. to let
+    /// the server always win. Insertions of clients with higher priority are placed first in
+    /// sequences, and override concurrent updates of map entries made by clients with lower
+    /// priority. Clients without assigned priority have a priority of 0. Ties are resolved
+    /// using [ConflictOrder::ClientId] rule.
+    Priority(HashMap<ClientID, u32>),
+}
+
+impl ConflictOrder {
+    /// Checks if an insertion made by client `a` should be placed before a concurrent insertion
+    /// made by client `b`. `is_map_entry` informs if both insertions are made to the same map
+    /// entry.
+    pub(crate) fn precedes(&self, a: ClientID, b: ClientID, is_map_entry: bool) -> bool {
+        match self {
+            ConflictOrder::ClientId => a < b,
+            ConflictOrder::Priority(priorities) => {
+                let pa = priorities.get(&a).copied().unwrap_or_default();
+                let pb = priorities.get(&b).copied().unwrap_or_default();
+                if pa == pb {
+                    a < b
+                } else if is_map_entry {
+                    pa < pb
+                } else {
+                    pa > pb
+                }
+            }
+        }
+    }
+}
+
+/// Determines which deleted elements (tombstones) can be garbage collected. Default policy collects
+/// all of them. GC policy is local to a document replica and it's not being replicated.
+///
+/// Policy configured in [Options::gc_policy] is used whenever a transaction is committed, unless
+/// [Options::skip_gc] is set. Documents with garbage collection disabled can still be compacted
+/// on demand with [TransactionMut::gc_with].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GcPolicy {
+    /// Number of the most recent transactions, which deleted any content, whose tombstones should
+    /// be kept. For [Options::gc_policy] it also determines how many of such transactions are
+    /// being tracked by a document, and therefore recognized by [TransactionMut::gc_with].
+    ///
+    /// Default value: `0`.
+    pub keep_recent: u32,
+    /// Named snapshots, which should remain restorable. Tombstones of elements visible in any of
+    /// them are kept, so that [crate::ReadTxn::encode_state_from_snapshot] and
+    /// [Doc::restore_from_snapshot] produce valid results for these snapshots.
+    ///
+    /// Default value: empty.
+    pub snapshots: HashMap<String, Snapshot>,
+    /// Names of root-level types, which contents should never be garbage collected.
+    ///
+    /// Default value: empty.
+    pub excluded_roots: HashSet<Arc<str>>,
+}
+
 /// Trait implemented by [Doc] and shared types, used for carrying over the responsibilities of
 /// creating new transactions, used as a unit of work in Yrs.
 pub trait Transact {
@@ -891,6 +2198,7 @@ pub trait Transact {
     /// While it's possible to have multiple read-only transactions active at the same time,
     /// this method will return a [TransactionAcqError::SharedAcqFailed] error whenever called
     /// while a read-write transaction (see: [Self::try_transact_mut]) is active at the same time.
+    #[cfg_attr(feature = "borrow-tracker", track_caller)]
     fn try_transact(&self) -> Result<Transaction, TransactionAcqError>;
 
     /// Creates and returns a read-write capable transaction. This transaction can be used to
@@ -902,6 +2210,7 @@ pub trait Transact {
     /// Only one read-write transaction can be active at the same time. If any other transaction -
     /// be it a read-write or read-only one - is active at the same time, this method will return
     /// a [TransactionAcqError::ExclusiveAcqFailed] error.
+    #[cfg_attr(feature = "borrow-tracker", track_caller)]
     fn try_transact_mut(&self) -> Result<TransactionMut, TransactionAcqError>;
 
     /// Creates and returns a read-write capable transaction with an `origin` classifier attached.
@@ -916,6 +2225,7 @@ pub trait Transact {
     /// Only one read-write transaction can be active at the same time. If any other transaction -
     /// be it a read-write or read-only one - is active at the same time, this method will return
     /// a [TransactionAcqError::ExclusiveAcqFailed] error.
+    #[cfg_attr(feature = "borrow-tracker", track_caller)]
     fn try_transact_mut_with<T>(&self, origin: T) -> Result<TransactionMut, TransactionAcqError>
     where
         T: Into<Origin>;
@@ -931,6 +2241,7 @@ pub trait Transact {
     ///
     /// Only one read-write transaction can be active at the same time. If any other transaction -
     /// be it a read-write or read-only one - is active at the same time, this method will panic.
+    #[cfg_attr(feature = "borrow-tracker", track_caller)]
     fn transact_mut_with<T>(&self, origin: T) -> TransactionMut
     where
         T: Into<Origin>,
@@ -945,6 +2256,7 @@ pub trait Transact {
     /// While it's possible to have multiple read-only transactions active at the same time,
     /// this method will panic whenever called while a read-write transaction
     /// (see: [Self::transact_mut]) is active at the same time.
+    #[cfg_attr(feature = "borrow-tracker", track_caller)]
     fn transact(&self) -> Transaction {
         self.try_transact()
             .expect("there's another active read-write transaction at the moment")
@@ -958,6 +2270,7 @@ pub trait Transact {
     ///
     /// Only one read-write transaction can be active at the same time. If any other transaction -
     /// be it a read-write or read-only one - is active at the same time, this method will panic.
+    #[cfg_attr(feature = "borrow-tracker", track_caller)]
     fn transact_mut(&self) -> TransactionMut {
         self.try_transact_mut()
             .expect("there's another active transaction at the moment")
@@ -965,25 +2278,69 @@ pub trait Transact {
 }
 
 impl Transact for Doc {
+    #[cfg_attr(feature = "borrow-tracker", track_caller)]
     fn try_transact(&self) -> Result<Transaction, TransactionAcqError> {
-        Ok(Transaction::new(self.store.try_borrow()?))
+        let txn = Transaction::new(self.store.try_borrow()?);
+        #[cfg(feature = "borrow-tracker")]
+        let txn = txn.tracked(self);
+        Ok(txn)
     }
 
+    #[cfg_attr(feature = "borrow-tracker", track_caller)]
     fn try_transact_mut(&self) -> Result<TransactionMut, TransactionAcqError> {
         let store = self.store.try_borrow_mut()?;
-        Ok(TransactionMut::new(self.clone(), store, None))
+        if self.store.is_closed() {
+            return Err(TransactionAcqError::DocumentClosed);
+        }
+        let txn = TransactionMut::new(self.clone(), store, None);
+        #[cfg(feature = "borrow-tracker")]
+        let txn = txn.tracked(self);
+        Ok(txn)
     }
 
+    #[cfg_attr(feature = "borrow-tracker", track_caller)]
     fn try_transact_mut_with<T>(&self, origin: T) -> Result<TransactionMut, TransactionAcqError>
     where
         T: Into<Origin>,
     {
         let store = self.store.try_borrow_mut()?;
-        Ok(TransactionMut::new(
-            self.clone(),
-            store,
-            Some(origin.into()),
-        ))
+        if self.store.is_closed() {
+            return Err(TransactionAcqError::DocumentClosed);
+        }
+        let txn = TransactionMut::new(self.clone(), store, Some(origin.into()));
+        #[cfg(feature = "borrow-tracker")]
+        let txn = txn.tracked(self);
+        Ok(txn)
+    }
+
+    #[cfg(feature = "borrow-tracker")]
+    #[track_caller]
+    fn transact_mut_with<T>(&self, origin: T) -> TransactionMut
+    where
+        T: Into<Origin>,
+    {
+        match self.try_transact_mut_with(origin) {
+            Ok(txn) => txn,
+            Err(e) => borrow_tracker::acquisition_failed(self, e),
+        }
+    }
+
+    #[cfg(feature = "borrow-tracker")]
+    #[track_caller]
+    fn transact(&self) -> Transaction {
+        match self.try_transact() {
+            Ok(txn) => txn,
+            Err(e) => borrow_tracker::acquisition_failed(self, e),
+        }
+    }
+
+    #[cfg(feature = "borrow-tracker")]
+    #[track_caller]
+    fn transact_mut(&self) -> TransactionMut {
+        match self.try_transact_mut() {
+            Ok(txn) => txn,
+            Err(e) => borrow_tracker::acquisition_failed(self, e),
+        }
     }
 }
 
@@ -995,6 +2352,8 @@ pub enum TransactionAcqError {
     ExclusiveAcqFailed,
     #[error("All references to a parent document containing this structure has been dropped.")]
     DocumentDropped,
+    #[error("Document has been closed and no longer accepts read-write transactions.")]
+    DocumentClosed,
 }
 
 impl From<BorrowError> for TransactionAcqError {
@@ -1039,18 +2398,20 @@ impl DocAddr {
 #[cfg(test)]
 mod test {
     use crate::block::ItemContent;
+    use crate::id_set::IdRange;
     use crate::test_utils::exchange_updates;
-    use crate::transaction::{ReadTxn, TransactionMut};
-    use crate::types::ToJson;
+    use crate::transaction::{Origin, ReadTxn, TransactionMut, MAX_DEFERRED_TRANSACTIONS};
+    use crate::types::{Path, PathSegment, ToJson};
     use crate::update::Update;
     use crate::updates::decoder::Decode;
     use crate::updates::encoder::{Encode, Encoder, EncoderV1};
     use crate::{
-        any, Any, Array, ArrayPrelim, ArrayRef, Doc, GetString, Map, MapPrelim, MapRef, OffsetKind,
-        Options, StateVector, Subscription, Text, TextRef, Transact, Uuid, WriteTxn,
-        XmlElementPrelim, XmlFragment, XmlFragmentRef, XmlTextPrelim, XmlTextRef,
+        any, Any, Array, ArrayPrelim, ArrayRef, BlockRange, DeleteSet, Doc, GcPolicy, GetString,
+        In, Map, MapPrelim, MapRef, Observable, OffsetKind, Options, Out, StateDelta, StateVector,
+        Subscription, Text, TextPrelim, TextRef, Transact, Uuid, WriteTxn, XmlElementPrelim,
+        XmlFragment, XmlFragmentRef, XmlTextPrelim, XmlTextRef, ID,
     };
-    use std::collections::BTreeSet;
+    use std::collections::{BTreeSet, HashMap};
 
     use arc_swap::ArcSwapOption;
     use assert_matches2::assert_matches;
@@ -2294,13 +3655,619 @@ mod test {
             txn.encode_state_from_snapshot(&snap, &mut encoder).unwrap();
             encoder.to_vec()
         };
-
-        let doc = Doc::with_client_id(1);
-        let txt = doc.get_or_insert_text("test");
-        let mut txn = doc.transact_mut();
-        txn.apply_update(Update::decode_v1(&update).unwrap());
-        let str = txt.get_string(&txn);
-        assert_eq!(&str, "hello");
+
+        let doc = Doc::with_client_id(1);
+        let txt = doc.get_or_insert_text("test");
+        let mut txn = doc.transact_mut();
+        txn.apply_update(Update::decode_v1(&update).unwrap());
+        let str = txt.get_string(&txn);
+        assert_eq!(&str, "hello");
+    }
+
+    #[test]
+    fn restore_from_snapshot() {
+        let options = Options {
+            client_id: 1,
+            skip_gc: true,
+            ..Options::default()
+        };
+        let d1 = Doc::with_options(options);
+        let text = d1.get_or_insert_text("text");
+        let map = d1.get_or_insert_map("map");
+        let array = d1.get_or_insert_array("array");
+        {
+            let mut txn = d1.transact_mut();
+            text.push(&mut txn, "hello world");
+            map.insert(&mut txn, "a", 1);
+            map.insert(&mut txn, "b", 2);
+            map.insert(&mut txn, "nested", MapPrelim::from([("c", 3)]));
+            array.insert_range(&mut txn, 0, [1, 2, 3, 4]);
+            map.insert(&mut txn, "removed", "before");
+        }
+        map.remove(&mut d1.transact_mut(), "removed");
+
+        let d2 = Doc::with_options(Options {
+            client_id: 2,
+            skip_gc: true,
+            ..Options::default()
+        });
+        d2.get_or_insert_map("map");
+        exchange_updates(&[&d1, &d2]);
+
+        let snapshot = d1.transact().snapshot();
+        let expected = d1.to_json(&d1.transact());
+
+        // changes made after the snapshot, locally and by remote peers
+        {
+            let mut txn = d1.transact_mut();
+            text.remove_range(&mut txn, 2, 5);
+            text.insert(&mut txn, 0, ">> ");
+            map.insert(&mut txn, "a", "changed");
+            map.remove(&mut txn, "nested");
+            map.insert(&mut txn, "new", 4);
+            array.remove_range(&mut txn, 1, 2);
+            array.push_back(&mut txn, 5);
+        }
+        {
+            let text = d2.get_or_insert_text("text");
+            let array = d2.get_or_insert_array("array");
+            let mut txn = d2.transact_mut();
+            text.push(&mut txn, "!");
+            array.insert(&mut txn, 0, 0);
+        }
+        exchange_updates(&[&d1, &d2]);
+        assert_ne!(d1.to_json(&d1.transact()), expected);
+
+        let restored = d1.restore_from_snapshot(&snapshot).unwrap();
+        assert_ne!(restored.client_id(), d1.client_id());
+        assert_eq!(restored.to_json(&restored.transact()), expected);
+
+        assert!(d1.transact_mut().revert_to_snapshot(&snapshot).unwrap());
+        assert_eq!(d1.to_json(&d1.transact()), expected);
+        assert_eq!(text.get_string(&d1.transact()), "hello world");
+
+        // reverting is propagated to other peers as regular changes
+        exchange_updates(&[&d1, &d2]);
+        assert_eq!(d2.to_json(&d2.transact()), expected);
+
+        // reverting document which is already in a snapshot state is a no-op
+        assert!(!d1.transact_mut().revert_to_snapshot(&snapshot).unwrap());
+
+        // shared types created after the checkpoint cannot be discarded
+        let checkpoint = d1.transact().store().checkpoint();
+        let nested = map.insert(&mut d1.transact_mut(), "new", MapPrelim::from([("d", 4)]));
+        assert!(d1.transact_mut().restore(&checkpoint).is_err());
+        assert_eq!(nested.len(&d1.transact()), 1);
+
+        // documents with garbage collection enabled cannot be restored
+        let d3 = Doc::with_client_id(3);
+        let snapshot = d3.transact().snapshot();
+        assert!(d3.restore_from_snapshot(&snapshot).is_err());
+        assert!(d3.transact_mut().revert_to_snapshot(&snapshot).is_err());
+    }
+
+    #[test]
+    fn restore_checkpoint() {
+        let d1 = Doc::with_options(Options {
+            client_id: 1,
+            skip_gc: true,
+            ..Options::default()
+        });
+        let text = d1.get_or_insert_text("text");
+        let map = d1.get_or_insert_map("map");
+        let array = d1.get_or_insert_array("array");
+        {
+            let mut txn = d1.transact_mut();
+            text.push(&mut txn, "hello world");
+            map.insert(&mut txn, "a", 1);
+            map.insert(&mut txn, "nested", MapPrelim::from([("c", 3)]));
+            array.insert_range(&mut txn, 0, [1, 2, 3, 4]);
+        }
+        let d2 = Doc::with_options(Options {
+            client_id: 2,
+            skip_gc: true,
+            ..Options::default()
+        });
+        exchange_updates(&[&d1, &d2]);
+
+        let checkpoint = d1.transact().store().checkpoint();
+        let expected = d1.to_json(&d1.transact());
+
+        // remote changes received after the checkpoint
+        {
+            let text = d2.get_or_insert_text("text");
+            text.push(&mut d2.transact_mut(), "!");
+            let sv = d1.transact().state_vector();
+            let update = d2.transact().encode_state_as_update_v1(&sv);
+            let mut txn = d1.transact_mut();
+            txn.apply_update(Update::decode_v1(&update).unwrap());
+        }
+        // speculative local changes
+        {
+            let mut txn = d1.transact_mut();
+            text.remove_range(&mut txn, 2, 5);
+            text.insert(&mut txn, 0, ">> ");
+            map.insert(&mut txn, "a", "changed");
+            map.remove(&mut txn, "nested");
+            map.insert(&mut txn, "new", 4);
+            array.remove_range(&mut txn, 1, 2);
+        }
+        array.push_back(&mut d1.transact_mut(), 5);
+        assert_ne!(d1.to_json(&d1.transact()), expected);
+
+        {
+            let mut txn = d1.transact_mut();
+            text.push(&mut txn, "?");
+            assert!(txn.restore(&checkpoint).is_err());
+        }
+        assert!(d1.transact_mut().restore(&checkpoint).unwrap());
+        assert_eq!(d1.to_json(&d1.transact()), expected);
+        assert_eq!(text.get_string(&d1.transact()), "hello world");
+        assert_eq!(text.len(&d1.transact()), 11);
+        assert_eq!(map.len(&d1.transact()), 2);
+        assert_eq!(array.len(&d1.transact()), 4);
+        assert_eq!(&d1.transact().state_vector(), checkpoint.state_vector());
+        assert_ne!(d1.client_id(), 1);
+
+        // document keeps working after restore and stays in sync with other peers
+        text.insert(&mut d1.transact_mut(), 0, "> ");
+        map.insert(&mut d1.transact_mut(), "b", 2);
+        exchange_updates(&[&d1, &d2]);
+        assert_eq!(text.get_string(&d1.transact()), "> hello world!");
+        d2.get_or_insert_map("map");
+        d2.get_or_insert_array("array");
+        assert_eq!(d1.to_json(&d1.transact()), d2.to_json(&d2.transact()));
+
+        // shared types created after the checkpoint cannot be discarded
+        let checkpoint = d1.transact().store().checkpoint();
+        let nested = map.insert(&mut d1.transact_mut(), "new", MapPrelim::from([("d", 4)]));
+        assert!(d1.transact_mut().restore(&checkpoint).is_err());
+        assert_eq!(nested.len(&d1.transact()), 1);
+
+        // documents with garbage collection enabled cannot be restored
+        let d3 = Doc::with_client_id(3);
+        let checkpoint = d3.transact().store().checkpoint();
+        assert!(d3.transact_mut().restore(&checkpoint).is_err());
+    }
+
+    #[test]
+    fn restore_nested_checkpoints() {
+        let doc = Doc::with_options(Options {
+            client_id: 1,
+            skip_gc: true,
+            ..Options::default()
+        });
+        let text = doc.get_or_insert_text("text");
+        text.push(&mut doc.transact_mut(), "abcdef");
+
+        let first = doc.transact().store().checkpoint();
+        text.remove_range(&mut doc.transact_mut(), 0, 1);
+        let second = doc.transact().store().checkpoint();
+        // checkpoints made without any changes in between are equal
+        assert_eq!(doc.transact().store().checkpoint(), second);
+        assert_ne!(first, second);
+        text.remove_range(&mut doc.transact_mut(), 0, 1);
+        text.push(&mut doc.transact_mut(), "g");
+        let third = doc.transact().store().checkpoint();
+        text.remove_range(&mut doc.transact_mut(), 0, 2);
+        assert_eq!(text.get_string(&doc.transact()), "efg");
+
+        assert!(doc.transact_mut().restore(&third).unwrap());
+        assert_eq!(text.get_string(&doc.transact()), "cdefg");
+        assert!(doc.transact_mut().restore(&second).unwrap());
+        assert_eq!(text.get_string(&doc.transact()), "bcdef");
+
+        // restoring a checkpoint discards checkpoints made after it
+        assert!(doc.transact_mut().restore(&third).is_err());
+        assert!(doc.transact_mut().restore(&first).unwrap());
+        assert_eq!(text.get_string(&doc.transact()), "abcdef");
+        assert!(doc.transact_mut().restore(&second).is_err());
+
+        // checkpoints of other documents are rejected
+        let other = Doc::with_options(Options {
+            skip_gc: true,
+            ..Options::default()
+        });
+        let checkpoint = other.transact().store().checkpoint();
+        assert!(doc.transact_mut().restore(&checkpoint).is_err());
+
+        // log is dropped once no checkpoint refers to it
+        drop((first, second, third));
+        text.remove_range(&mut doc.transact_mut(), 0, 1);
+        assert!(doc.transact().store().delete_log.lock().unwrap().is_none());
+    }
+
+    #[test]
+    fn isolate_observer_panics() {
+        let doc = Doc::with_options(Options {
+            client_id: 1,
+            isolate_observers: true,
+            ..Options::default()
+        });
+        let text = doc.get_or_insert_text("text");
+        let _s1 = text.observe(|_, _| panic!("text observer failed"));
+        let seen = Arc::new(AtomicU32::new(0));
+        let _s2 = {
+            let seen = seen.clone();
+            text.observe(move |_, _| {
+                seen.fetch_add(1, Ordering::SeqCst);
+            })
+        };
+        let _s3 = doc
+            .observe_after_transaction(|_| panic!("after transaction failed: {}", 1))
+            .unwrap();
+        let updates = Arc::new(AtomicU32::new(0));
+        let _s4 = {
+            let updates = updates.clone();
+            doc.observe_update_v1(move |_, _| {
+                updates.fetch_add(1, Ordering::SeqCst);
+            })
+            .unwrap()
+        };
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let _s5 = {
+            let errors = errors.clone();
+            doc.observe_callback_errors(move |_, e| errors.lock().unwrap().extend_from_slice(e))
+                .unwrap()
+        };
+
+        text.push(&mut doc.transact_mut(), "hello");
+        text.push(&mut doc.transact_mut(), " world");
+
+        // remaining callbacks are called and transactions are committed despite panics
+        assert_eq!(seen.load(Ordering::SeqCst), 2);
+        assert_eq!(updates.load(Ordering::SeqCst), 2);
+        assert_eq!(text.get_string(&doc.transact()), "hello world");
+        let errors: Vec<_> = errors
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|e| e.message.to_string())
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                "text observer failed",
+                "after transaction failed: 1",
+                "text observer failed",
+                "after transaction failed: 1",
+            ]
+        );
+    }
+
+    #[test]
+    fn isolate_subdoc_destroy_panics() {
+        let doc = Doc::with_client_id(1);
+        let map = doc.get_or_insert_map("map");
+        let subdoc = Doc::with_options(Options {
+            isolate_observers: true,
+            ..Options::default()
+        });
+        let subdoc = map.insert(&mut doc.transact_mut(), "sub", subdoc);
+        let _s1 = subdoc
+            .observe_destroy(|_, _| panic!("destroy observer failed"))
+            .unwrap();
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let _s2 = {
+            let errors = errors.clone();
+            subdoc
+                .observe_callback_errors(move |_, e| errors.lock().unwrap().extend_from_slice(e))
+                .unwrap()
+        };
+
+        // subdoc is destroyed while committing a parent transaction
+        map.remove(&mut doc.transact_mut(), "sub");
+        assert_eq!(map.len(&doc.transact()), 0);
+        let errors: Vec<_> = errors
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|e| e.message.to_string())
+            .collect();
+        assert_eq!(errors, vec!["destroy observer failed"]);
+    }
+
+    #[test]
+    fn read_only_copy() {
+        let doc = Doc::with_client_id(1);
+        let text = doc.get_or_insert_text("text");
+        let map = doc.get_or_insert_map("map");
+        let xml = doc.get_or_insert_xml_fragment("xml");
+        {
+            let mut txn = doc.transact_mut();
+            text.push(&mut txn, "hello world");
+            map.insert(&mut txn, "a", 1);
+            map.insert(&mut txn, "nested", MapPrelim::from([("b", 2)]));
+            xml.push_back(&mut txn, XmlElementPrelim::empty("p"));
+        }
+        let (copy, expected) = {
+            let mut txn = doc.transact_mut();
+            text.remove_range(&mut txn, 5, 6);
+            let copy = txn.read_only_copy();
+            // copies made without any changes in between are shared
+            assert_eq!(txn.read_only_copy().guid(), copy.guid());
+            (copy, doc.to_json(&txn))
+        };
+
+        // changes made after copying are not visible in a copy
+        {
+            let mut txn = doc.transact_mut();
+            text.insert(&mut txn, 0, ">> ");
+            map.remove(&mut txn, "nested");
+            map.insert(&mut txn, "c", 3);
+            xml.remove_range(&mut txn, 0, 1);
+        }
+        assert_ne!(doc.to_json(&doc.transact()), expected);
+        assert_ne!(copy.guid(), doc.guid());
+        assert_ne!(copy.client_id(), doc.client_id());
+        assert_ne!(doc.transact().read_only_copy().guid(), copy.guid());
+
+        let handle = std::thread::spawn(move || {
+            let txn = copy.transact();
+            let text = txn.get_text("text").unwrap();
+            let xml = txn.get_xml_fragment("xml").unwrap();
+            assert_eq!(text.get_string(&txn), "hello");
+            assert_eq!(xml.get_string(&txn), "<p></p>");
+            assert_eq!(copy.to_json(&txn), expected);
+            drop(txn);
+            assert!(copy.try_transact_mut().is_err());
+        });
+        handle.join().unwrap();
+        assert_eq!(text.get_string(&doc.transact()), ">> hello");
+    }
+
+    #[test]
+    fn encode_diff_since_snapshot() {
+        let doc = Doc::with_options(Options {
+            client_id: 1,
+            skip_gc: true,
+            ..Options::default()
+        });
+        let text = doc.get_or_insert_text("text");
+        let array = doc.get_or_insert_array("array");
+        {
+            let mut txn = doc.transact_mut();
+            text.push(&mut txn, "hello world");
+            array.insert_range(&mut txn, 0, [1, 2, 3, 4]);
+            array.remove(&mut txn, 0);
+        }
+        let snapshot = doc.transact().snapshot();
+        {
+            let mut txn = doc.transact_mut();
+            text.remove_range(&mut txn, 5, 6);
+            text.push(&mut txn, "!");
+            array.remove(&mut txn, 0);
+            array.push_back(&mut txn, 5);
+        }
+
+        let restored = doc.restore_from_snapshot(&snapshot).unwrap();
+        let txn = doc.transact();
+        let v1 = txn.encode_diff_since_snapshot_v1(&snapshot);
+        let v2 = txn.encode_diff_since_snapshot_v2(&snapshot);
+
+        // deletions already known to the snapshot are not included
+        let update = Update::decode_v1(&v1).unwrap();
+        assert_eq!(
+            update.delete_set.range(&1),
+            Some(&IdRange::Fragmented(vec![5..11, 12..13]))
+        );
+
+        restored
+            .transact_mut()
+            .apply_update(Update::decode_v2(&v2).unwrap());
+        assert_eq!(restored.to_json(&restored.transact()), doc.to_json(&txn));
+    }
+
+    #[test]
+    fn transaction_state_delta() {
+        let d1 = Doc::with_client_id(1);
+        let text = d1.get_or_insert_text("text");
+        text.push(&mut d1.transact_mut(), "abc");
+
+        let d2 = Doc::with_client_id(2);
+        let remote = d2.get_or_insert_text("text");
+        remote.push(&mut d2.transact_mut(), "xy");
+
+        let update = d1
+            .transact()
+            .encode_state_as_update_v1(&StateVector::default());
+        let mut txn = d2.transact_mut();
+        txn.apply_update(Update::decode_v1(&update).unwrap());
+        remote.push(&mut txn, "z");
+        let delta = txn.state_delta();
+        assert_eq!(
+            delta.ranges(),
+            &[BlockRange::new(1, 0, 3), BlockRange::new(2, 2, 3)]
+        );
+        assert!(delta.contains(&ID::new(2, 2)));
+        assert!(!delta.contains(&ID::new(2, 1)));
+        txn.commit();
+        assert_eq!(
+            StateDelta::new(txn.before_state(), txn.after_state()),
+            delta
+        );
+        assert_eq!(&delta.end(), txn.after_state());
+        drop(txn);
+
+        let bytes = delta.encode_v1();
+        assert_eq!(StateDelta::decode_v1(&bytes).unwrap(), delta);
+        assert!(d2.transact_mut().state_delta().is_empty());
+    }
+
+    #[test]
+    fn apply_update_from_reader() {
+        // yields encoded update in small chunks
+        struct Chunked<'a>(&'a [u8]);
+        impl<'a> std::io::Read for Chunked<'a> {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                let len = buf.len().min(self.0.len()).min(7);
+                buf[..len].copy_from_slice(&self.0[..len]);
+                self.0 = &self.0[len..];
+                Ok(len)
+            }
+        }
+
+        let d1 = Doc::with_client_id(1);
+        let d2 = Doc::with_client_id(2);
+        let t1 = d1.get_or_insert_text("text");
+        let t2 = d2.get_or_insert_text("text");
+        {
+            // prepending characters one by one produces blocks which can't be squashed
+            let mut txn = d1.transact_mut();
+            for _ in 0..1500 {
+                t1.insert(&mut txn, 0, "a");
+            }
+        }
+        exchange_updates(&[&d1, &d2]);
+        {
+            // client 2 blocks are encoded before the client 1 blocks they depend on
+            let mut txn = d2.transact_mut();
+            for i in 0..100 {
+                t2.insert(&mut txn, i * 10, "b");
+            }
+            t2.remove_range(&mut txn, 500, 100);
+        }
+        exchange_updates(&[&d1, &d2]);
+        let bytes = d1
+            .transact()
+            .encode_state_as_update_v1(&StateVector::default());
+
+        let d3 = Doc::new();
+        let t3 = d3.get_or_insert_text("text");
+        d3.transact_mut()
+            .apply_update_from(Chunked(&bytes))
+            .unwrap();
+        let txn = d3.transact();
+        assert!(txn.store().pending.is_none());
+        assert_eq!(t3.get_string(&txn), t1.get_string(&d1.transact()));
+        assert_eq!(txn.state_vector(), d1.transact().state_vector());
+        drop(txn);
+
+        let d4 = Doc::new();
+        let res = d4
+            .transact_mut()
+            .apply_update_from(Chunked(&bytes[..bytes.len() - 10]));
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn gc_policy() {
+        fn is_collected(doc: &Doc, id: ID) -> bool {
+            let txn = doc.transact();
+            let item = txn.store().blocks.get_item(&id).unwrap();
+            matches!(item.content, ItemContent::Deleted(_))
+        }
+
+        // keep tombstones of the most recent transaction
+        let mut options = Options::with_client_id(1);
+        options.gc_policy.keep_recent = 1;
+        let doc = Doc::with_options(options);
+        let text = doc.get_or_insert_text("text");
+        text.push(&mut doc.transact_mut(), "abc");
+        text.remove_range(&mut doc.transact_mut(), 0, 1);
+        assert!(!is_collected(&doc, ID::new(1, 0)));
+        text.push(&mut doc.transact_mut(), "d"); // transactions with no deletions don't count
+        assert!(!is_collected(&doc, ID::new(1, 0)));
+        text.remove_range(&mut doc.transact_mut(), 0, 1);
+        assert!(is_collected(&doc, ID::new(1, 0)));
+        assert!(!is_collected(&doc, ID::new(1, 1)));
+
+        // never collect contents of excluded root types
+        let mut options = Options::with_client_id(2);
+        options.gc_policy.excluded_roots.insert("text".into());
+        let doc = Doc::with_options(options);
+        let text = doc.get_or_insert_text("text");
+        let other = doc.get_or_insert_text("other");
+        {
+            let mut txn = doc.transact_mut();
+            text.push(&mut txn, "abc");
+            other.push(&mut txn, "abc");
+        }
+        {
+            let mut txn = doc.transact_mut();
+            text.remove_range(&mut txn, 0, 1);
+            other.remove_range(&mut txn, 0, 1);
+        }
+        assert!(!is_collected(&doc, ID::new(2, 0)));
+        assert!(is_collected(&doc, ID::new(2, 3)));
+
+        // collect on demand, keeping named snapshots restorable
+        let doc = Doc::with_options(Options {
+            client_id: 3,
+            skip_gc: true,
+            ..Options::default()
+        });
+        let text = doc.get_or_insert_text("text");
+        text.push(&mut doc.transact_mut(), "hello world");
+        let snapshot = doc.transact().snapshot();
+        text.remove_range(&mut doc.transact_mut(), 0, 6);
+        text.push(&mut doc.transact_mut(), "!");
+        text.remove_range(&mut doc.transact_mut(), 5, 1);
+        assert!(!is_collected(&doc, ID::new(3, 11)));
+
+        let mut policy = GcPolicy::default();
+        policy.snapshots.insert("v1".into(), snapshot.clone());
+        doc.transact_mut().gc_with(&policy);
+        assert!(!is_collected(&doc, ID::new(3, 0)));
+        assert!(is_collected(&doc, ID::new(3, 11)));
+        assert_eq!(text.get_string(&doc.transact()), "world");
+
+        let restored = doc.restore_from_snapshot(&snapshot).unwrap();
+        let restored_text = restored.get_or_insert_text("text");
+        assert_eq!(
+            restored_text.get_string(&restored.transact()),
+            "hello world"
+        );
+
+        doc.transact_mut().gc_with(&GcPolicy::default());
+        assert!(is_collected(&doc, ID::new(3, 0)));
+    }
+
+    #[test]
+    fn deferred_transactions() {
+        let doc = Doc::with_client_id(1);
+        let text = doc.get_or_insert_text("text");
+        let origins = Arc::new(Mutex::new(Vec::new()));
+        let _sub = {
+            let target = text.clone();
+            let origins = origins.clone();
+            text.observe(move |txn, _| {
+                origins.lock().unwrap().push(txn.origin().cloned());
+                let text = target.clone();
+                txn.defer(move |txn| {
+                    if !text.get_string(txn).ends_with('.') {
+                        text.push(txn, ".");
+                    }
+                });
+            })
+        };
+
+        text.push(&mut doc.transact_mut_with("origin"), "hello");
+        assert_eq!(text.get_string(&doc.transact()), "hello.");
+        // deferred transactions inherit origin of a transaction that scheduled them
+        let origin = Some(Origin::from("origin"));
+        assert_eq!(*origins.lock().unwrap(), vec![origin.clone(), origin]);
+        drop(_sub);
+
+        // observers endlessly rescheduling changes are stopped
+        let array = doc.get_or_insert_array("array");
+        let _sub = {
+            let target = array.clone();
+            array.observe(move |txn, _| {
+                let array = target.clone();
+                txn.defer(move |txn| {
+                    array.push_back(txn, 1);
+                });
+            })
+        };
+        array.push_back(&mut doc.transact_mut(), 1);
+        assert_eq!(
+            array.len(&doc.transact()),
+            1 + MAX_DEFERRED_TRANSACTIONS as u32
+        );
+        // document is still usable afterwards
+        text.push(&mut doc.transact_mut(), "!");
+        assert_eq!(text.get_string(&doc.transact()), "hello.!");
     }
 
     #[test]
@@ -2388,4 +4355,817 @@ mod test {
             Err(crate::encoding::read::Error::EndOfBuffer(_))
         );
     }
+
+    #[test]
+    fn instantiate_template() {
+        let template = Doc::with_client_id(1);
+        let text = template.get_or_insert_text("text");
+        let map = template.get_or_insert_map("map");
+        {
+            let mut txn = template.transact_mut();
+            text.push(
+                &mut txn,
+                "Hello {{name}}, welcome to {{place}}! {{unknown}}",
+            );
+            map.insert(&mut txn, "title", "{{title}}");
+            map.insert(&mut txn, "count", "{{count}}");
+            let nested = map.insert(&mut txn, "nested", ArrayPrelim::default());
+            nested.push_back(&mut txn, "by {{name}}");
+        }
+
+        // root types decoded from an update have no type information attached
+        let remote = Doc::with_client_id(2);
+        {
+            let mut txn = remote.transact_mut();
+            let update = template
+                .transact()
+                .encode_state_as_update_v1(&StateVector::default());
+            txn.apply_update(Update::decode_v1(&update).unwrap());
+        }
+
+        let mut substitutions = HashMap::new();
+        substitutions.insert("name".to_string(), Any::from("Alice"));
+        substitutions.insert("place".to_string(), Any::from("Wonderland"));
+        substitutions.insert("title".to_string(), Any::from("Ms."));
+        substitutions.insert("count".to_string(), Any::from(3));
+
+        for template in [template, remote] {
+            let doc = Doc::instantiate_template(&template, &substitutions);
+            assert_ne!(doc.client_id(), template.client_id());
+            assert_ne!(doc.guid(), template.guid());
+
+            let txn = doc.transact();
+            let sv = txn.state_vector();
+            assert_eq!(sv.get(&template.client_id()), 0);
+            assert_eq!(
+                doc.to_json(&txn),
+                any!({
+                    "text": "Hello Alice, welcome to Wonderland! {{unknown}}",
+                    "map": {
+                        "title": "Ms.",
+                        "count": 3,
+                        "nested": ["by Alice"]
+                    }
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn extract_paths() {
+        let doc = Doc::with_client_id(1);
+        let text = doc.get_or_insert_text("text");
+        let map = doc.get_or_insert_map("map");
+        let array = doc.get_or_insert_array("array");
+        {
+            let mut txn = doc.transact_mut();
+            text.push(&mut txn, "hello");
+            map.insert(&mut txn, "a", MapPrelim::from([("b", 1), ("c", 2)]));
+            map.insert(&mut txn, "d", 3);
+            array.push_back(&mut txn, MapPrelim::from([("x", 1), ("y", 2)]));
+            array.push_back(&mut txn, "skipped");
+            array.push_back(&mut txn, "selected");
+        }
+        let key = |k: &str| PathSegment::Key(k.into());
+        let paths = [
+            Path::from(vec![key("text")]),
+            Path::from(vec![key("map"), key("a"), key("c")]),
+            Path::from(vec![key("map"), key("missing")]),
+            Path::from(vec![key("array"), PathSegment::Index(2)]),
+            Path::from(vec![key("array"), PathSegment::Index(0), key("x")]),
+            Path::from(vec![key("missing")]),
+        ];
+
+        let extracted = doc.extract(&paths);
+        assert_ne!(extracted.client_id(), doc.client_id());
+        let txn = extracted.transact();
+        assert_eq!(txn.state_vector().get(&doc.client_id()), 0);
+        assert_eq!(
+            extracted.to_json(&txn),
+            any!({
+                "text": "hello",
+                "map": { "a": { "c": 2 } },
+                "array": [{ "x": 1 }, "selected"]
+            })
+        );
+    }
+
+    #[test]
+    fn import_content_preserving_ids() {
+        let d1 = Doc::with_client_id(1);
+        let d2 = Doc::with_client_id(2);
+        let t1 = d1.get_or_insert_text("text");
+        let m1 = d1.get_or_insert_map("other");
+        t1.push(&mut d1.transact_mut(), "hello");
+        m1.insert(&mut d1.transact_mut(), "key", "value");
+        t1.push(&mut d1.transact_mut(), "!");
+        exchange_updates(&[&d1, &d2]);
+        let t2 = d2.get_or_insert_text("text");
+        t2.insert(&mut d2.transact_mut(), 5, " world");
+        t2.remove_range(&mut d2.transact_mut(), 0, 1);
+        exchange_updates(&[&d1, &d2]);
+
+        let target = Doc::with_client_id(3);
+        let key = |k: &str| PathSegment::Key(k.into());
+        assert!(target.import_content_from(&d1, &Path::from(vec![key("text")])));
+
+        let text = target.get_or_insert_text("text");
+        {
+            let txn = target.transact();
+            assert_eq!(text.get_string(&txn), "ello world!");
+            // original client ids and clocks have been retained
+            let sv = txn.state_vector();
+            let src_sv = d1.transact().state_vector();
+            assert_eq!(sv.get(&1), src_sv.get(&1));
+            assert_eq!(sv.get(&2), src_sv.get(&2));
+            assert_eq!(sv.get(&3), 0);
+            assert!(txn.get_map("other").is_none());
+        }
+
+        // imported content can be further edited and replicated
+        text.push(&mut target.transact_mut(), "?");
+        let replica = Doc::with_client_id(4);
+        exchange_updates(&[&target, &replica]);
+        let txn = replica.transact();
+        let text = txn.get_text("text").unwrap();
+        assert_eq!(text.get_string(&txn), "ello world!?");
+        drop(txn);
+
+        // clients 1 and 2 are already known, so contents are copied
+        let map_path = Path::from(vec![key("other"), key("key")]);
+        assert!(!target.import_content_from(&d1, &map_path));
+        let txn = target.transact();
+        let map = txn.get_map("other").unwrap();
+        assert_eq!(map.get(&txn, "key"), Some("value".into()));
+        assert_eq!(txn.state_vector().get(&3), 2);
+    }
+
+    #[test]
+    fn rate_limited_updates() {
+        let time = Arc::new(std::sync::atomic::AtomicU64::new(0));
+        let clock = {
+            let time = time.clone();
+            Arc::new(move || time.load(Ordering::SeqCst))
+        };
+        let doc = Doc::with_client_id(1);
+        doc.set_update_interval_with_clock(100, clock).unwrap();
+        let text = doc.get_or_insert_text("text");
+        let updates = Arc::new(Mutex::new(Vec::new()));
+        let _sub = {
+            let updates = updates.clone();
+            doc.observe_update_v1(move |_, e| updates.lock().unwrap().push(e.update.clone()))
+                .unwrap()
+        };
+
+        text.push(&mut doc.transact_mut(), "a");
+        time.store(50, Ordering::SeqCst);
+        text.push(&mut doc.transact_mut(), "b");
+        assert!(updates.lock().unwrap().is_empty());
+
+        time.store(100, Ordering::SeqCst);
+        text.push(&mut doc.transact_mut(), "c");
+        assert_eq!(updates.lock().unwrap().len(), 1);
+
+        time.store(120, Ordering::SeqCst);
+        text.push(&mut doc.transact_mut(), "d");
+        assert_eq!(updates.lock().unwrap().len(), 1);
+        doc.flush_updates().unwrap();
+        assert_eq!(updates.lock().unwrap().len(), 2);
+        // nothing to flush
+        doc.flush_updates().unwrap();
+        assert_eq!(updates.lock().unwrap().len(), 2);
+
+        let remote = Doc::with_client_id(2);
+        let remote_text = remote.get_or_insert_text("text");
+        for update in updates.lock().unwrap().iter() {
+            let mut txn = remote.transact_mut();
+            txn.apply_update(Update::decode_v1(update).unwrap());
+        }
+        assert_eq!(remote_text.get_string(&remote.transact()), "abcd");
+
+        // disable rate limiting
+        doc.set_update_interval_with_clock(0, Arc::new(|| 0))
+            .unwrap();
+        text.push(&mut doc.transact_mut(), "e");
+        assert_eq!(updates.lock().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn write_block_ranges_in_chunks() {
+        let doc = Doc::with_client_id(1);
+        let text = doc.get_or_insert_text("text");
+        let remote = Doc::with_client_id(2);
+        let remote_text = remote.get_or_insert_text("text");
+        text.push(&mut doc.transact_mut(), "hello ");
+        remote_text.push(&mut remote.transact_mut(), "world");
+        exchange_updates(&[&doc, &remote]);
+        text.insert(&mut doc.transact_mut(), 6, "big ");
+
+        // transfer state in chunks of at most 3 clock ticks per client, in reverse order
+        let txn = doc.transact();
+        let store = txn.store();
+        let mut chunks = Vec::new();
+        for range in store.block_ranges_from(&StateVector::default()) {
+            let mut start = range.start;
+            while start < range.end {
+                let end = (start + 3).min(range.end);
+                let mut encoder = EncoderV1::new();
+                store
+                    .write_block_ranges(&[BlockRange::new(range.client, start, end)], &mut encoder);
+                DeleteSet::new().encode(&mut encoder);
+                chunks.push(encoder.to_vec());
+                start = end;
+            }
+        }
+        assert_eq!(chunks.len(), 4 + 2);
+
+        let peer = Doc::with_client_id(3);
+        let peer_text = peer.get_or_insert_text("text");
+        for chunk in chunks.iter().rev() {
+            let mut txn = peer.transact_mut();
+            txn.apply_update(Update::decode_v1(chunk).unwrap());
+        }
+        assert_eq!(peer_text.get_string(&peer.transact()), "hello big world");
+    }
+
+    #[test]
+    fn encode_state_as_update_chunked() {
+        let doc = Doc::with_client_id(1);
+        let text = doc.get_or_insert_text("text");
+        for i in 0..20 {
+            // inserting at the beginning prevents blocks from being squashed together
+            text.insert(&mut doc.transact_mut(), 0, &format!("chunk-{:02} ", i));
+        }
+        text.remove_range(&mut doc.transact_mut(), 0, 18);
+        text.remove_range(&mut doc.transact_mut(), 36, 9);
+        let expected = text.get_string(&doc.transact());
+
+        let chunks = doc
+            .transact()
+            .encode_state_as_update_chunked(&StateVector::default(), 64);
+        assert!(chunks.len() > 1);
+        for chunk in chunks.iter() {
+            assert!(chunk.len() <= 64, "chunk of {} bytes", chunk.len());
+        }
+
+        let peer = Doc::with_client_id(2);
+        let peer_text = peer.get_or_insert_text("text");
+        for chunk in chunks.iter().rev() {
+            let mut txn = peer.transact_mut();
+            txn.apply_update(Update::decode_v1(chunk).unwrap());
+        }
+        assert_eq!(peer_text.get_string(&peer.transact()), expected);
+
+        // nothing to send to up to date peer
+        let sv = peer.transact().state_vector();
+        let chunks = doc.transact().encode_state_as_update_chunked(&sv, 64);
+        assert_eq!(chunks.len(), 1); // delete set only
+
+        let empty = Doc::new();
+        let chunks = empty
+            .transact()
+            .encode_state_as_update_chunked(&StateVector::default(), 64);
+        assert!(chunks.is_empty());
+    }
+
+    #[test]
+    fn try_apply_malformed_updates() {
+        let doc = Doc::with_client_id(1);
+        let text = doc.get_or_insert_text("text");
+        let array = doc.get_or_insert_array("array");
+        {
+            let mut txn = doc.transact_mut();
+            text.push(&mut txn, "hello world");
+            text.remove_range(&mut txn, 2, 3);
+            array.push_back(&mut txn, MapPrelim::from([("key", -123456789i64)]));
+            array.insert(&mut txn, 0, ArrayPrelim::from([1.5]));
+        }
+        let v1 = doc
+            .transact()
+            .encode_state_as_update_v1(&StateVector::default());
+        let v2 = doc
+            .transact()
+            .encode_state_as_update_v2(&StateVector::default());
+
+        let peer = Doc::with_client_id(2);
+        peer.try_apply_update_v1(&v1).unwrap();
+        assert_eq!(
+            peer.get_or_insert_text("text").get_string(&peer.transact()),
+            "he world"
+        );
+        {
+            let _txn = peer.transact();
+            assert_matches!(peer.try_apply_update_v1(&v1), Err(crate::Error::Borrow(_)));
+        }
+        assert_matches!(
+            peer.try_apply_update_v1(&[1, 1, 1, 0, 4, 1, 2, 0xff, 0xfe]), // invalid UTF-8
+            Err(crate::Error::Encoding(_))
+        );
+
+        // malformed input never panics
+        let mut rng = fastrand::Rng::with_seed(0xdeadbeef);
+        for _ in 0..5000 {
+            for (valid, v2) in [(&v1, false), (&v2, true)] {
+                let mut update = valid.clone();
+                for _ in 0..rng.usize(1..4) {
+                    let i = rng.usize(0..update.len());
+                    update[i] = rng.u8(..);
+                }
+                if rng.bool() {
+                    update.truncate(rng.usize(0..update.len()));
+                }
+                let doc = Doc::with_client_id(3);
+                let _ = if v2 {
+                    doc.try_apply_update_v2(&update)
+                } else {
+                    doc.try_apply_update_v1(&update)
+                };
+            }
+        }
+    }
+
+    #[test]
+    fn low_level_op_api() {
+        use crate::branch::BranchPtr;
+        use crate::{Assoc, StickyIndex, ID};
+
+        let doc = Doc::with_client_id(1);
+        let array = doc.get_or_insert_array("array");
+        array.insert_range(&mut doc.transact_mut(), 0, ["a", "b", "c"]);
+
+        let mut txn = doc.transact_mut();
+        let x = txn
+            .insert_between(&array, Some(&ID::new(1, 0)), Some(&ID::new(1, 1)), "x")
+            .unwrap();
+        assert_eq!(x, ID::new(1, 3));
+        assert_eq!(array.to_json(&txn), any!(["a", "x", "b", "c"]));
+
+        // unknown origins are rejected
+        assert!(txn
+            .insert_between(&array, Some(&ID::new(2, 0)), None, "y")
+            .is_err());
+        // origins must be adjacent elements of the same sequence
+        assert!(txn
+            .insert_between(&array, Some(&ID::new(1, 0)), Some(&ID::new(1, 2)), "y")
+            .is_err());
+        assert!(txn
+            .insert_between(&array, Some(&ID::new(1, 2)), Some(&ID::new(1, 0)), "y")
+            .is_err());
+        let other = txn.get_or_insert_array("other");
+        assert!(txn
+            .insert_between(&other, Some(&ID::new(1, 2)), None, "y")
+            .is_err());
+        assert_eq!(array.to_json(&txn), any!(["a", "x", "b", "c"]));
+
+        assert!(txn.delete_range(&ID::new(1, 1), 2));
+        assert_eq!(array.to_json(&txn), any!(["a", "x"]));
+        assert!(!txn.delete_range(&ID::new(1, 10), 1));
+        assert!(!txn.delete_range(&ID::new(2, 0), 1));
+
+        let branch = BranchPtr::from(array.as_ref());
+        let start = StickyIndex::at(&txn, branch, 0, Assoc::After).unwrap();
+        let end = StickyIndex::at(&txn, branch, 1, Assoc::Before).unwrap();
+        txn.move_range(&array, Some(&x), None, start, end).unwrap();
+        assert_eq!(array.to_json(&txn), any!(["x", "a"]));
+        drop(txn);
+
+        let remote = Doc::with_client_id(2);
+        let update = doc
+            .transact()
+            .encode_state_as_update_v1(&StateVector::default());
+        remote.try_apply_update_v1(&update).unwrap();
+        let array = remote.get_or_insert_array("array");
+        assert_eq!(array.to_json(&remote.transact()), any!(["x", "a"]));
+    }
+
+    #[test]
+    fn move_type() {
+        fn path(segments: &[PathSegment]) -> Path {
+            segments.iter().cloned().collect()
+        }
+        let key = |k: &str| PathSegment::Key(k.into());
+        let index = PathSegment::Index;
+
+        let doc = Doc::with_client_id(1);
+        let root = doc.get_or_insert_map("root");
+        let list = doc.get_or_insert_array("list");
+        let nested: MapRef = {
+            let mut txn = doc.transact_mut();
+            list.insert_range(&mut txn, 0, [1, 2]);
+            root.insert(&mut txn, "a", MapPrelim::from([("x", 1)]));
+            list.insert(&mut txn, 2, MapPrelim::from([("x", 1)]))
+        };
+        let calls = Arc::new(AtomicU32::new(0));
+        let calls_c = calls.clone();
+        let _sub = nested.observe(move |_, _| {
+            calls_c.fetch_add(1, Ordering::SeqCst);
+        });
+
+        // move within the same array preserves identity and observers
+        let mut txn = doc.transact_mut();
+        let moved = txn
+            .move_type(
+                &path(&[key("list"), index(2)]),
+                &path(&[key("list"), index(0)]),
+            )
+            .unwrap();
+        assert_eq!(list.get(&txn, 0), Some(moved));
+        assert_eq!(list.to_json(&txn), any!([{"x": 1}, 1, 2]));
+        drop(txn);
+        nested.insert(&mut doc.transact_mut(), "y", 2);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // invalid moves are rejected without changing the document
+        let mut txn = doc.transact_mut();
+        assert!(txn
+            .move_type(
+                &path(&[key("list"), index(0)]),
+                &path(&[key("list"), index(0), key("z")])
+            )
+            .is_err());
+        assert!(txn
+            .move_type(&path(&[key("root")]), &path(&[key("list"), index(0)]))
+            .is_err());
+        assert!(txn
+            .move_type(
+                &path(&[key("list"), index(0)]),
+                &path(&[key("list"), index(3)])
+            )
+            .is_err());
+        assert!(txn
+            .move_type(
+                &path(&[key("root"), key("a")]),
+                &path(&[key("list"), index(4)])
+            )
+            .is_err());
+        assert!(txn
+            .move_type(
+                &path(&[key("root"), key("a")]),
+                &path(&[key("root"), index(0)])
+            )
+            .is_err());
+        assert_eq!(root.to_json(&txn), any!({"a": {"x": 1}}));
+        assert_eq!(list.to_json(&txn), any!([{"x": 1, "y": 2}, 1, 2]));
+
+        // moving forward within the same array places the type under the target index
+        txn.move_type(
+            &path(&[key("list"), index(0)]),
+            &path(&[key("list"), index(2)]),
+        )
+        .unwrap();
+        assert_eq!(list.to_json(&txn), any!([1, 2, {"x": 1, "y": 2}]));
+    }
+
+    #[test]
+    fn move_type_between_parents() {
+        fn path(segments: &[PathSegment]) -> Path {
+            segments.iter().cloned().collect()
+        }
+        let key = |k: &str| PathSegment::Key(k.into());
+        let index = PathSegment::Index;
+
+        let d1 = Doc::with_client_id(1);
+        let root = d1.get_or_insert_map("root");
+        let list = d1.get_or_insert_array("list");
+        {
+            let mut txn = d1.transact_mut();
+            list.insert_range(&mut txn, 0, [1, 2]);
+            root.insert(
+                &mut txn,
+                "a",
+                MapPrelim::from([
+                    ("x", In::from(1)),
+                    ("text", In::from(TextPrelim::new("hi"))),
+                ]),
+            );
+        }
+        let d2 = Doc::with_client_id(2);
+        exchange_updates(&[&d1, &d2]);
+        let updates = Arc::new(AtomicU32::new(0));
+        let updates_c = updates.clone();
+        let _sub = d1
+            .observe_update_v1(move |_, _| {
+                updates_c.fetch_add(1, Ordering::SeqCst);
+            })
+            .unwrap();
+
+        // map -> array
+        let moved = d1
+            .transact_mut()
+            .move_type(
+                &path(&[key("root"), key("a")]),
+                &path(&[key("list"), index(1)]),
+            )
+            .unwrap();
+        assert_eq!(updates.load(Ordering::SeqCst), 1);
+        let txn = d1.transact();
+        assert_eq!(root.to_json(&txn), any!({}));
+        assert_eq!(list.to_json(&txn), any!([1, {"x": 1, "text": "hi"}, 2]));
+        assert_eq!(list.get(&txn, 1), Some(moved));
+        drop(txn);
+
+        // array -> map under another key
+        let moved = d1
+            .transact_mut()
+            .move_type(
+                &path(&[key("list"), index(1)]),
+                &path(&[key("root"), key("b")]),
+            )
+            .unwrap();
+        assert_eq!(updates.load(Ordering::SeqCst), 2);
+        let txn = d1.transact();
+        assert_eq!(root.to_json(&txn), any!({"b": {"x": 1, "text": "hi"}}));
+        assert_eq!(list.to_json(&txn), any!([1, 2]));
+        assert_eq!(root.get(&txn, "b"), Some(moved));
+        drop(txn);
+
+        // renaming a map key
+        d1.transact_mut()
+            .move_type(
+                &path(&[key("root"), key("b")]),
+                &path(&[key("root"), key("c")]),
+            )
+            .unwrap();
+
+        exchange_updates(&[&d1, &d2]);
+        let root2 = d2.get_or_insert_map("root");
+        let list2 = d2.get_or_insert_array("list");
+        let txn = d2.transact();
+        assert_eq!(root2.to_json(&txn), any!({"c": {"x": 1, "text": "hi"}}));
+        assert_eq!(list2.to_json(&txn), any!([1, 2]));
+    }
+
+    #[test]
+    fn branch_entries_and_iter() {
+        let doc = Doc::with_client_id(1);
+        {
+            let mut txn = doc.transact_mut();
+            let map = txn.get_or_insert_map("map");
+            map.insert(&mut txn, "a", 1);
+            map.insert(&mut txn, "b", "two");
+            map.insert(&mut txn, "a", 3);
+            map.remove(&mut txn, "b");
+            let array = txn.get_or_insert_array("array");
+            array.insert_range(&mut txn, 0, [1, 2, 3]);
+            array.remove(&mut txn, 1);
+        }
+
+        // remote peer doesn't know the types of root collections
+        let remote = Doc::with_client_id(2);
+        let update = doc
+            .transact()
+            .encode_state_as_update_v1(&StateVector::default());
+        remote.try_apply_update_v1(&update).unwrap();
+
+        let txn = remote.transact();
+        let mut roots: Vec<_> = txn.root_refs().collect();
+        roots.sort_by(|a, b| a.0.cmp(b.0));
+        match &roots[..] {
+            [("array", Out::UndefinedRef(array)), ("map", Out::UndefinedRef(map))] => {
+                let values: Vec<_> = array.iter(&txn).collect();
+                assert_eq!(values, vec![Out::Any(1.into()), Out::Any(3.into())]);
+                assert_eq!(map.iter(&txn).count(), 0);
+
+                let entries: Vec<_> = map.entries(&txn).collect();
+                assert_eq!(entries, vec![("a", Out::Any(3.into()))]);
+                assert_eq!(array.entries(&txn).count(), 0);
+            }
+            other => panic!("unexpected root types: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn observe_roots() {
+        use crate::types::TypeRef;
+
+        let doc = Doc::with_client_id(1);
+        let map = doc.get_or_insert_map("map");
+        let text = doc.get_or_insert_text("text");
+
+        let remote = Doc::with_client_id(2);
+        let added = Arc::new(Mutex::new(Vec::new()));
+        let _sub = {
+            let added = added.clone();
+            remote
+                .observe_roots(move |_, e| {
+                    let mut added = added.lock().unwrap();
+                    for root in e.added() {
+                        added.push((root.name.to_string(), root.type_ref.clone()));
+                    }
+                })
+                .unwrap()
+        };
+        let sync = || {
+            let sv = remote.transact().state_vector();
+            let update = doc.transact().encode_state_as_update_v1(&sv);
+            remote.try_apply_update_v1(&update).unwrap();
+        };
+
+        map.insert(&mut doc.transact_mut(), "key", 1);
+        text.insert(&mut doc.transact_mut(), 0, "hello");
+        sync();
+        let mut roots = std::mem::take(&mut *added.lock().unwrap());
+        roots.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            roots,
+            vec![
+                ("map".to_string(), TypeRef::Undefined),
+                ("text".to_string(), TypeRef::Undefined)
+            ]
+        );
+
+        // changes to already known roots are not reported
+        map.insert(&mut doc.transact_mut(), "key", 2);
+        sync();
+        remote.get_or_insert_map("map");
+        assert!(added.lock().unwrap().is_empty());
+
+        remote.get_or_insert_array("array");
+        assert_eq!(
+            *added.lock().unwrap(),
+            vec![("array".to_string(), TypeRef::Array)]
+        );
+    }
+
+    #[test]
+    fn type_repairs() {
+        use crate::types::TypeRef;
+        use crate::Root;
+
+        let doc = Doc::with_client_id(1);
+        let map = doc.get_or_insert_map("map");
+        map.insert(&mut doc.transact_mut(), "key", 1);
+        let update = doc
+            .transact()
+            .encode_state_as_update_v1(&StateVector::default());
+
+        let remote = Doc::with_client_id(2);
+        remote.try_apply_update_v1(&update).unwrap();
+        let repairs = Arc::new(Mutex::new(Vec::new()));
+        let _sub = {
+            let repairs = repairs.clone();
+            remote
+                .observe_type_repairs(move |_, r| repairs.lock().unwrap().push(r.clone()))
+                .unwrap()
+        };
+
+        remote.get_or_insert_map("map");
+        remote.get_or_insert_map("map");
+        remote.get_or_insert_array("map");
+        let repairs = repairs.lock().unwrap();
+        assert_eq!(repairs.len(), 2);
+        assert_eq!(repairs[0].defined, TypeRef::Undefined);
+        assert_eq!(repairs[0].requested, TypeRef::Map);
+        assert!(!repairs[0].conflict);
+        assert_eq!(repairs[1].defined, TypeRef::Map);
+        assert_eq!(repairs[1].requested, TypeRef::Array);
+        assert!(repairs[1].conflict);
+
+        // in strict mode conflicting access is rejected
+        let strict = Doc::with_options(Options {
+            strict_types: true,
+            ..Options::with_client_id(3)
+        });
+        strict.try_apply_update_v1(&update).unwrap();
+        let mut txn = strict.transact_mut();
+        let err = Root::<ArrayRef>::new("map")
+            .try_get_or_create(&mut txn)
+            .unwrap_err();
+        assert!(err.conflict);
+        assert_eq!(
+            err.to_string(),
+            "root type `map` defined as (undefined) has been accessed as Array"
+        );
+        let map = Root::<MapRef>::new("map")
+            .try_get_or_create(&mut txn)
+            .unwrap();
+        assert_eq!(map.get(&txn, "key"), Some(Out::from(1.0)));
+        assert!(Root::<TextRef>::new("map")
+            .try_get_or_create(&mut txn)
+            .is_err());
+    }
+
+    #[test]
+    fn close_runs_hooks_in_order() {
+        let doc = Doc::with_client_id(1);
+        let text = doc.get_or_insert_text("text");
+        text.push(&mut doc.transact_mut(), "abc");
+        let log = Arc::new(Mutex::new(Vec::new()));
+        for (order, name) in [(2, "provider"), (0, "persistence"), (2, "metrics")] {
+            let log = log.clone();
+            let text = text.clone();
+            doc.on_close(order, move |doc| {
+                // hooks can still read the document state
+                let content = text.get_string(&doc.transact());
+                log.lock().unwrap().push(format!("{}:{}", name, content));
+            })
+            .unwrap();
+        }
+        let _sub = doc.observe_update_v1(|_, _| {}).unwrap();
+
+        {
+            let _txn = doc.transact();
+            assert!(doc.close().is_err());
+        }
+        assert!(!doc.is_closed());
+        doc.close().unwrap();
+        assert!(doc.is_closed());
+        assert_eq!(
+            *log.lock().unwrap(),
+            vec!["persistence:abc", "provider:abc", "metrics:abc"]
+        );
+        assert!(matches!(
+            doc.try_transact_mut(),
+            Err(crate::doc::TransactionAcqError::DocumentClosed)
+        ));
+        assert!(doc.store.try_borrow().unwrap().events.is_none());
+
+        // closing is idempotent and late hooks are called right away
+        doc.close().unwrap();
+        let l = log.clone();
+        doc.on_close(0, move |_| l.lock().unwrap().push("late".into()))
+            .unwrap();
+        assert_eq!(log.lock().unwrap().len(), 4);
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn store_is_send_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<crate::store::Store>();
+        assert_send_sync::<Doc>();
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn transaction_is_send() {
+        fn assert_send<T: Send>() {}
+        assert_send::<crate::TransactionMut>();
+    }
+
+    #[test]
+    fn destroy_runs_close_hooks() {
+        let doc = Doc::with_client_id(1);
+        let array = doc.get_or_insert_array("array");
+        let subdoc = array.insert(&mut doc.transact_mut(), 0, Doc::new());
+        let called = Arc::new(AtomicU32::new(0));
+        let c = called.clone();
+        subdoc
+            .on_close(0, move |_| {
+                c.fetch_add(1, Ordering::SeqCst);
+            })
+            .unwrap();
+
+        subdoc.destroy(&mut doc.transact_mut());
+        assert_eq!(called.load(Ordering::SeqCst), 1);
+        assert!(subdoc.is_closed());
+        subdoc.close().unwrap();
+        assert_eq!(called.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn subdoc_transactions_inherit_origin() {
+        let doc = Doc::with_client_id(1);
+        let array = doc.get_or_insert_array("array");
+        let origins = Arc::new(Mutex::new(Vec::new()));
+
+        let subdoc = Doc::new();
+        let o = origins.clone();
+        subdoc
+            .observe_after_transaction_with("test", move |txn| {
+                o.lock().unwrap().push(txn.origin().cloned());
+            })
+            .unwrap();
+        let subdoc = array.insert(&mut doc.transact_mut_with("user"), 0, subdoc);
+        assert_eq!(
+            std::mem::take(&mut *origins.lock().unwrap()),
+            vec![Some(Origin::from("user")); 2]
+        );
+
+        doc.map_subdoc_origin(|origin, _| {
+            (origin != &Origin::from("hidden")).then(|| Origin::from("parent"))
+        })
+        .unwrap();
+        let o = origins.clone();
+        let _sub = subdoc
+            .observe_destroy(move |txn, _| {
+                o.lock().unwrap().push(txn.origin().cloned());
+            })
+            .unwrap();
+        array.remove(&mut doc.transact_mut_with("user"), 0);
+        assert_eq!(
+            std::mem::take(&mut *origins.lock().unwrap()),
+            vec![Some(Origin::from("parent"))]
+        );
+
+        let subdoc = array.insert(&mut doc.transact_mut_with("hidden"), 0, Doc::new());
+        let o = origins.clone();
+        let _sub = subdoc
+            .observe_destroy(move |txn, _| {
+                o.lock().unwrap().push(txn.origin().cloned());
+            })
+            .unwrap();
+        array.remove(&mut doc.transact_mut_with("hidden"), 0);
+        assert_eq!(*origins.lock().unwrap(), vec![None]);
+    }
 }
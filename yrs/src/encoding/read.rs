@@ -25,6 +25,9 @@ pub enum Error {
 
     #[error("{0}")]
     Custom(String),
+
+    #[error("maximum nesting depth of {0} has been exceeded")]
+    DepthLimitExceeded(usize),
 }
 
 impl Error {
@@ -33,6 +36,42 @@ impl Error {
     }
 }
 
+/// Limits enforced by decoders working in a strict mode, meant for decoding untrusted input.
+/// See: [Decode::decode_v1_strict].
+///
+/// [Decode::decode_v1_strict]: crate::updates::decoder::Decode::decode_v1_strict
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodeLimits {
+    /// Maximum nesting depth of decoded [Any] values. Default: 128.
+    ///
+    /// [Any]: crate::Any
+    pub max_depth: usize,
+}
+
+impl Default for DecodeLimits {
+    fn default() -> Self {
+        DecodeLimits { max_depth: 128 }
+    }
+}
+
+/// See: [Read::bounded_len].
+pub(crate) fn bounded_len(
+    len: usize,
+    remaining: Option<usize>,
+    limits: Option<&DecodeLimits>,
+) -> Result<usize, Error> {
+    match remaining {
+        Some(remaining) if len > remaining => {
+            if limits.is_some() {
+                Err(Error::EndOfBuffer(len))
+            } else {
+                Ok(remaining)
+            }
+        }
+        _ => Ok(len),
+    }
+}
+
 #[derive(Default)]
 pub struct Cursor<'a> {
     pub buf: &'a [u8],
@@ -47,6 +86,18 @@ impl<'a> Cursor<'a> {
     pub fn has_content(&self) -> bool {
         self.next != self.buf.len()
     }
+
+    /// Take a slice of the next `len` bytes and advance the position by `len`. Unlike
+    /// [Read::read_exact], returned slice is borrowed for the lifetime of an underlying buffer.
+    pub(crate) fn read_borrowed(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        if self.next.saturating_add(len) > self.buf.len() {
+            Err(Error::EndOfBuffer(len))
+        } else {
+            let slice = &self.buf[self.next..(self.next + len)];
+            self.next += len;
+            Ok(slice)
+        }
+    }
 }
 
 impl<'a, R> From<&'a R> for Cursor<'a>
@@ -61,13 +112,7 @@ where
 impl<'a> Read for Cursor<'a> {
     /// Take a slice of the next `len` bytes and advance the position by `len`.
     fn read_exact(&mut self, len: usize) -> Result<&[u8], Error> {
-        if self.next + len > self.buf.len() {
-            Err(Error::EndOfBuffer(len))
-        } else {
-            let slice = &self.buf[self.next..(self.next + len)];
-            self.next += len as usize;
-            Ok(slice)
-        }
+        self.read_borrowed(len)
     }
 
     /// Read a single byte.
@@ -79,11 +124,33 @@ impl<'a> Read for Cursor<'a> {
             Err(Error::EndOfBuffer(1))
         }
     }
+
+    #[inline]
+    fn remaining(&self) -> Option<usize> {
+        Some(self.buf.len().saturating_sub(self.next))
+    }
 }
 
 pub trait Read: Sized {
     fn read_exact(&mut self, len: usize) -> Result<&[u8], Error>;
 
+    /// Returns the number of bytes left to read, if it's known.
+    fn remaining(&self) -> Option<usize> {
+        None
+    }
+
+    /// Returns limits enforced by current reader if it works in a strict mode.
+    fn limits(&self) -> Option<&DecodeLimits> {
+        None
+    }
+
+    /// Validates a length prefix `len` of a collection, which elements take at least one byte
+    /// each, against the number of remaining bytes. In strict mode, an error is returned if `len`
+    /// exceeds it. Otherwise returns a capacity which is safe to preallocate.
+    fn bounded_len(&self, len: usize) -> Result<usize, Error> {
+        bounded_len(len, self.remaining(), self.limits())
+    }
+
     /// Read a single byte.
     fn read_u8(&mut self) -> Result<u8, Error> {
         let buf = self.read_exact(1)?;
@@ -134,7 +201,7 @@ pub trait Read: Sized {
     /// Read string of variable length.
     fn read_string(&mut self) -> Result<&str, Error> {
         let buf = self.read_buf()?;
-        Ok(unsafe { std::str::from_utf8_unchecked(buf) })
+        std::str::from_utf8(buf).map_err(|_| Error::UnexpectedValue)
     }
 
     /// Read float32 in big endian order
@@ -1,8 +1,12 @@
 mod de;
 mod ser;
+mod value;
+mod view;
 
 pub use de::from_any;
-pub use ser::to_any;
+pub use ser::{to_any, AnySerializeError};
+pub use value::{FromValue, FromValueError};
+pub use view::SerdeView;
 
 #[cfg(test)]
 mod test {
@@ -269,4 +273,38 @@ mod test {
         let any2: Any = serde_json::from_value(json).unwrap();
         assert_eq!(any, any2);
     }
+
+    #[test]
+    fn serde_view_and_deserialized_prelim() {
+        use crate::{Array, ArrayRef, Doc, In, Map, MapRef, Out, Text, Transact};
+
+        let payload = json!({
+            "name": "doc",
+            "tags": ["a", "b"],
+            "nested": { "count": 2, "ok": true }
+        });
+        let input: In = serde_json::from_value(payload.clone()).unwrap();
+
+        let doc = Doc::new();
+        let root = doc.get_or_insert_map("root");
+        let mut txn = doc.transact_mut();
+        let value: Out = root.insert(&mut txn, "value", input);
+        // nested objects and arrays are ingested as shared collections
+        let value: MapRef = value.cast().unwrap();
+        let tags: ArrayRef = value.get(&txn, "tags").unwrap().cast().unwrap();
+        tags.push_back(&mut txn, "c");
+        let text = value.insert(&mut txn, "text", crate::TextPrelim::new("hello"));
+        text.push(&mut txn, " world");
+
+        let json = serde_json::to_value(SerdeView::new(&txn, &value)).unwrap();
+        assert_eq!(
+            json,
+            json!({
+                "name": "doc",
+                "tags": ["a", "b", "c"],
+                "nested": { "count": 2, "ok": true },
+                "text": "hello world"
+            })
+        );
+    }
 }
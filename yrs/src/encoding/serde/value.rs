@@ -0,0 +1,450 @@
+use crate::any::Any;
+use crate::encoding::read;
+use crate::encoding::serde::de::AnyDeserializer;
+use crate::types::{Path, PathSegment, ToJson};
+use crate::{Out, ReadTxn};
+use serde::de::value::MapAccessDeserializer;
+use serde::de::{
+    DeserializeOwned, DeserializeSeed, IntoDeserializer, MapAccess, SeqAccess, Visitor,
+};
+use serde::Deserializer;
+use std::collections::hash_map;
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
+
+/// Projection of values stored in a document into user-defined Rust types.
+///
+/// It's implemented for every type implementing [serde::Deserialize], so deriving
+/// `#[derive(Deserialize)]` on a struct or enum is enough to read it straight out of a [MapRef],
+/// [ArrayRef] or any other [Out] value, instead of chaining `cast::<MapRef>()` calls and extracting
+/// keys manually. Unlike [from_any], failures are reported together with the [Path] to a value
+/// which couldn't be projected.
+///
+/// # Example
+///
+/// ```rust
+/// use serde::Deserialize;
+/// use yrs::encoding::serde::FromValue;
+/// use yrs::{Any, Doc, Map, MapPrelim, Transact};
+///
+/// #[derive(Debug, PartialEq, Deserialize)]
+/// struct User {
+///     name: String,
+///     age: u32,
+/// }
+///
+/// let doc = Doc::new();
+/// let users = doc.get_or_insert_map("users");
+/// let mut txn = doc.transact_mut();
+/// users.insert(&mut txn, "alice", MapPrelim::from([("name", Any::from("Alice")), ("age", Any::from(30))]));
+/// users.insert(&mut txn, "bob", MapPrelim::from([("name", Any::from("Bob")), ("age", Any::from("unknown"))]));
+///
+/// let alice = users.get(&txn, "alice").unwrap();
+/// let alice = User::from_out(&txn, &alice).unwrap();
+/// assert_eq!(alice, User { name: "Alice".into(), age: 30 });
+///
+/// let bob = users.get(&txn, "bob").unwrap();
+/// let err = User::from_out(&txn, &bob).unwrap_err();
+/// assert_eq!(err.to_string(), "failed to read value at `$.age`: couldn't deserialize to target type of u32");
+/// ```
+///
+/// [MapRef]: crate::MapRef
+/// [ArrayRef]: crate::ArrayRef
+/// [from_any]: crate::encoding::serde::from_any
+pub trait FromValue: Sized {
+    /// Projects a given [Any] value into an instance of this type.
+    fn from_any(value: &Any) -> Result<Self, FromValueError>;
+
+    /// Projects a given [Out] value into an instance of this type. Shared collections are
+    /// converted using their [ToJson] representation first.
+    fn from_out<T: ReadTxn>(txn: &T, value: &Out) -> Result<Self, FromValueError> {
+        Self::from_any(&value.to_json(txn))
+    }
+}
+
+impl<T: DeserializeOwned> FromValue for T {
+    fn from_any(value: &Any) -> Result<Self, FromValueError> {
+        T::deserialize(PathDeserializer { value })
+    }
+}
+
+/// Error returned by [FromValue] when a value couldn't be projected into a requested type.
+#[derive(Debug)]
+pub struct FromValueError {
+    /// Path to a value, which couldn't be projected, relative to the root of the projection.
+    pub path: Path,
+    /// The reason of the failure.
+    pub source: read::Error,
+}
+
+impl FromValueError {
+    pub(crate) fn with_segment(mut self, segment: PathSegment) -> Self {
+        self.path.push_front(segment);
+        self
+    }
+}
+
+impl From<read::Error> for FromValueError {
+    fn from(source: read::Error) -> Self {
+        FromValueError {
+            path: Path::new(),
+            source,
+        }
+    }
+}
+
+impl Display for FromValueError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "failed to read value at `$")?;
+        for segment in self.path.iter() {
+            match segment {
+                PathSegment::Key(key) => write!(f, ".{}", key)?,
+                PathSegment::Index(index) => write!(f, "[{}]", index)?,
+            }
+        }
+        write!(f, "`: {}", self.source)
+    }
+}
+
+impl std::error::Error for FromValueError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+impl serde::de::Error for FromValueError {
+    fn custom<T>(msg: T) -> Self
+    where
+        T: Display,
+    {
+        read::Error::Custom(msg.to_string()).into()
+    }
+}
+
+/// Deserializer keeping track of the position within deserialized [Any] tree. Primitive values
+/// are handled by [AnyDeserializer], while nested arrays and maps are traversed here, so that
+/// their errors can be annotated with a corresponding [PathSegment].
+struct PathDeserializer<'a> {
+    value: &'a Any,
+}
+
+macro_rules! delegate {
+    ($($method:ident),*) => {
+        $(
+            fn $method<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+            where
+                V: Visitor<'de>,
+            {
+                Ok(AnyDeserializer::new(self.value).$method(visitor)?)
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for PathDeserializer<'de> {
+    type Error = FromValueError;
+
+    delegate!(
+        deserialize_bool,
+        deserialize_i8,
+        deserialize_i16,
+        deserialize_i32,
+        deserialize_i64,
+        deserialize_u8,
+        deserialize_u16,
+        deserialize_u32,
+        deserialize_u64,
+        deserialize_f32,
+        deserialize_f64,
+        deserialize_char,
+        deserialize_str,
+        deserialize_string,
+        deserialize_bytes,
+        deserialize_byte_buf,
+        deserialize_unit,
+        deserialize_identifier
+    );
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.value {
+            Any::Array(_) => self.deserialize_seq(visitor),
+            Any::Map(_) => self.deserialize_map(visitor),
+            _ => Ok(AnyDeserializer::new(self.value).deserialize_any(visitor)?),
+        }
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.value {
+            Any::Null | Any::Undefined => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_unit_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.value {
+            Any::Array(array) => visitor.visit_seq(PathSeqAccess {
+                iter: array.iter(),
+                index: 0,
+            }),
+            _ => Err(read::Error::type_mismatch::<V::Value>().into()),
+        }
+    }
+
+    fn deserialize_tuple<V>(self, _len: usize, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.value {
+            Any::Map(map) => visitor.visit_map(PathMapAccess::new(map)),
+            _ => Err(read::Error::type_mismatch::<V::Value>().into()),
+        }
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.value {
+            Any::String(s) => visitor.visit_enum(s.as_ref().into_deserializer()),
+            Any::Map(map) => {
+                visitor.visit_enum(MapAccessDeserializer::new(PathMapAccess::new(map)))
+            }
+            _ => Err(read::Error::type_mismatch::<V::Value>().into()),
+        }
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+}
+
+struct PathSeqAccess<'a> {
+    iter: std::slice::Iter<'a, Any>,
+    index: u32,
+}
+
+impl<'de> SeqAccess<'de> for PathSeqAccess<'de> {
+    type Error = FromValueError;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Self::Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        match self.iter.next() {
+            None => Ok(None),
+            Some(value) => {
+                let index = self.index;
+                self.index += 1;
+                seed.deserialize(PathDeserializer { value })
+                    .map(Some)
+                    .map_err(|e| e.with_segment(PathSegment::Index(index)))
+            }
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+struct PathMapAccess<'a> {
+    iter: hash_map::Iter<'a, String, Any>,
+    current: Option<(&'a String, &'a Any)>,
+}
+
+impl<'a> PathMapAccess<'a> {
+    fn new(map: &'a Arc<std::collections::HashMap<String, Any>>) -> Self {
+        PathMapAccess {
+            iter: map.iter(),
+            current: None,
+        }
+    }
+}
+
+impl<'de> MapAccess<'de> for PathMapAccess<'de> {
+    type Error = FromValueError;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        match self.iter.next() {
+            None => Ok(None),
+            Some((key, value)) => {
+                self.current = Some((key, value));
+                let key: serde::de::value::BorrowedStrDeserializer<'de, FromValueError> =
+                    serde::de::value::BorrowedStrDeserializer::new(key.as_str());
+                seed.deserialize(key).map(Some)
+            }
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Self::Error>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        match self.current.take() {
+            Some((key, value)) => seed
+                .deserialize(PathDeserializer { value })
+                .map_err(|e| e.with_segment(PathSegment::Key(Arc::from(key.as_str())))),
+            None => Err(serde::de::Error::custom("value is missing")),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::any::Any;
+    use crate::encoding::read;
+    use crate::encoding::serde::FromValue;
+    use crate::types::{Path, PathSegment};
+    use crate::{any, Array, ArrayPrelim, Doc, Map, MapPrelim, Transact};
+    use serde::Deserialize;
+    use std::sync::Arc;
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    enum Shape {
+        Circle { radius: f64 },
+        Square(f64),
+        Empty,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Layer {
+        name: String,
+        hidden: Option<bool>,
+        shapes: Vec<Shape>,
+    }
+
+    #[test]
+    fn from_value_projection() {
+        let doc = Doc::new();
+        let root = doc.get_or_insert_map("root");
+        let mut txn = doc.transact_mut();
+        let layer = root.insert(&mut txn, "layer", MapPrelim::default());
+        layer.insert(&mut txn, "name", "background");
+        let shapes = layer.insert(&mut txn, "shapes", ArrayPrelim::default());
+        shapes.push_back(&mut txn, any!({"Circle": {"radius": 2.5}}));
+        shapes.push_back(&mut txn, any!({"Square": 4}));
+        shapes.push_back(&mut txn, "Empty");
+
+        let value = root.get(&txn, "layer").unwrap();
+        assert_eq!(
+            Layer::from_out(&txn, &value).unwrap(),
+            Layer {
+                name: "background".into(),
+                hidden: None,
+                shapes: vec![
+                    Shape::Circle { radius: 2.5 },
+                    Shape::Square(4.0),
+                    Shape::Empty
+                ],
+            }
+        );
+
+        // errors point to the value which couldn't be projected
+        shapes.push_back(&mut txn, any!({"Circle": {"radius": "big"}}));
+        let value = root.get(&txn, "layer").unwrap();
+        let err = Layer::from_out(&txn, &value).unwrap_err();
+        assert_eq!(
+            err.path,
+            vec![
+                PathSegment::Key(Arc::from("shapes")),
+                PathSegment::Index(3),
+                PathSegment::Key(Arc::from("Circle")),
+                PathSegment::Key(Arc::from("radius")),
+            ]
+            .into_iter()
+            .collect::<Path>()
+        );
+        assert!(matches!(err.source, read::Error::TypeMismatch("f64")));
+        assert_eq!(
+            err.to_string(),
+            "failed to read value at `$.shapes[3].Circle.radius`: couldn't deserialize to target type of f64"
+        );
+
+        let err = Layer::from_any(&any!({"shapes": []})).unwrap_err();
+        assert!(err.path.is_empty());
+        assert_eq!(err.source.to_string(), "missing field `name`");
+
+        let err = Vec::<u8>::from_any(&Any::from(vec![Any::from(1), Any::from(-1)])).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "failed to read value at `$[1]`: while reading, an unexpected value was found"
+        );
+    }
+}
@@ -0,0 +1,96 @@
+use crate::types::map::MapRef;
+use crate::{Any, Array, ArrayRef, GetString, Out, ReadTxn, TextRef, XmlFragmentRef};
+use serde::ser::{SerializeMap, SerializeSeq};
+use serde::{Serialize, Serializer};
+
+/// A read-only view over a shared collection within a given transaction, which implements
+/// [Serialize]. This way contents of a document can be serialized into any serde-compatible
+/// format, without converting them into [Any] first.
+///
+/// Serialization follows the same rules as [ToJson]: maps and arrays are serialized
+/// recursively, while text and XML collections are serialized as strings.
+///
+/// # Example
+///
+/// ```rust
+/// use yrs::encoding::serde::SerdeView;
+/// use yrs::{Doc, Map, Transact};
+///
+/// let doc = Doc::new();
+/// let map = doc.get_or_insert_map("map");
+/// let mut txn = doc.transact_mut();
+/// map.insert(&mut txn, "key", "value");
+///
+/// let json = serde_json::to_string(&SerdeView::new(&txn, &map)).unwrap();
+/// assert_eq!(json, r#"{"key":"value"}"#);
+/// ```
+///
+/// [ToJson]: crate::types::ToJson
+pub struct SerdeView<'a, T, R> {
+    txn: &'a R,
+    value: &'a T,
+}
+
+impl<'a, T, R: ReadTxn> SerdeView<'a, T, R> {
+    pub fn new(txn: &'a R, value: &'a T) -> Self {
+        SerdeView { txn, value }
+    }
+}
+
+impl<'a, R: ReadTxn> Serialize for SerdeView<'a, Out, R> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.value {
+            Out::Any(any) => any.serialize(serializer),
+            Out::YText(v) => SerdeView::new(self.txn, v).serialize(serializer),
+            Out::YArray(v) => SerdeView::new(self.txn, v).serialize(serializer),
+            Out::YMap(v) => SerdeView::new(self.txn, v).serialize(serializer),
+            Out::YXmlElement(v) => serializer.serialize_str(&v.get_string(self.txn)),
+            Out::YXmlText(v) => serializer.serialize_str(&v.get_string(self.txn)),
+            Out::YXmlFragment(v) => SerdeView::new(self.txn, v).serialize(serializer),
+            Out::YDoc(doc) => {
+                let mut map = serializer.serialize_map(Some(1))?;
+                map.serialize_entry("guid", doc.guid().as_ref())?;
+                map.end()
+            }
+            #[cfg(feature = "weak")]
+            Out::YWeakLink(_) => serializer.serialize_none(),
+            Out::UndefinedRef(_) => serializer.serialize_none(),
+        }
+    }
+}
+
+impl<'a, R: ReadTxn> Serialize for SerdeView<'a, MapRef, R> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let branch = self.value.as_ref();
+        let mut map = serializer.serialize_map(None)?;
+        for (key, item) in branch.map.iter() {
+            if !item.is_deleted() {
+                let value = item.content.get_last().unwrap_or(Out::Any(Any::Null));
+                map.serialize_entry(key.as_ref(), &SerdeView::new(self.txn, &value))?;
+            }
+        }
+        map.end()
+    }
+}
+
+impl<'a, R: ReadTxn> Serialize for SerdeView<'a, ArrayRef, R> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.value.len(self.txn) as usize))?;
+        for value in self.value.iter(self.txn) {
+            seq.serialize_element(&SerdeView::new(self.txn, &value))?;
+        }
+        seq.end()
+    }
+}
+
+impl<'a, R: ReadTxn> Serialize for SerdeView<'a, TextRef, R> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.value.get_string(self.txn))
+    }
+}
+
+impl<'a, R: ReadTxn> Serialize for SerdeView<'a, XmlFragmentRef, R> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.value.get_string(self.txn))
+    }
+}
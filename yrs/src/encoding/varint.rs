@@ -261,18 +261,18 @@ fn read_var_u32<R: Read>(r: &mut R) -> Result<u32, Error> {
 
 fn read_var_i64<R: Read>(reader: &mut R) -> Result<i64, Error> {
     let mut r = reader.read_u8()?;
-    let mut num = (r & 0b00111111 as u8) as i64;
+    let mut num = i64::from(r & 0b00111111);
     let mut len: u32 = 6;
-    let is_negative = r & 0b01000000 as u8 > 0;
-    if r & 0b10000000 as u8 == 0 {
-        return Ok(if is_negative { -num } else { num });
+    let is_negative = r & 0b01000000 > 0;
+    if r & 0b10000000 == 0 {
+        return Ok(if is_negative { num.wrapping_neg() } else { num });
     }
     loop {
         r = reader.read_u8()?;
-        num |= (r as i64 & 0b01111111 as i64) << len;
+        num |= i64::from(r & 0b01111111).wrapping_shl(len);
         len += 7;
-        if r < 0b10000000 as u8 {
-            return Ok(if is_negative { -num } else { num });
+        if r < 0b10000000 {
+            return Ok(if is_negative { num.wrapping_neg() } else { num });
         }
         if len > 70 {
             return Err(Error::InvalidVarInt);
@@ -354,19 +354,19 @@ impl SignedVarInt for i64 {
 
     fn read_signed<R: Read>(reader: &mut R) -> Result<Signed<Self>, Error> {
         let mut r = reader.read_u8()?;
-        let mut num = (r & 0b00111111 as u8) as i64;
+        let mut num = i64::from(r & 0b00111111);
         let mut len: u32 = 6;
-        let is_negative = r & 0b01000000 as u8 > 0;
-        if r & 0b10000000 as u8 == 0 {
-            let num = if is_negative { -num } else { num };
+        let is_negative = r & 0b01000000 > 0;
+        if r & 0b10000000 == 0 {
+            let num = if is_negative { num.wrapping_neg() } else { num };
             return Ok(Signed::new(num, is_negative));
         }
         loop {
             r = reader.read_u8()?;
-            num |= (r as i64 & 0b01111111 as i64) << len;
+            num |= i64::from(r & 0b01111111).wrapping_shl(len);
             len += 7;
-            if r < 0b10000000 as u8 {
-                let num = if is_negative { -num } else { num };
+            if r < 0b10000000 {
+                let num = if is_negative { num.wrapping_neg() } else { num };
                 return Ok(Signed::new(num, is_negative));
             }
             if len > 70 {
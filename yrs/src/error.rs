@@ -1,9 +1,69 @@
+use crate::doc::TransactionAcqError;
+use crate::encoding::read;
+use atomic_refcell::{BorrowError, BorrowMutError};
 use thiserror::Error;
 
+/// Error returned by fallible Yrs operations. Errors are grouped into a few broad categories,
+/// so that callers can decide how to react to them - i.e. drop a connection sending malformed
+/// payloads or retry on borrow conflicts - without matching every specific cause.
+///
+/// Decoding and integration of updates never panics on malformed input: decoding failures are
+/// reported as [Error::Encoding] or [Error::Capacity], while parts of a decoded update which
+/// don't match the document structure are skipped during integration.
 #[derive(Debug, Error)]
+#[non_exhaustive]
 pub enum Error {
-    #[error("{0}")]
-    ReadError(#[from] crate::encoding::read::Error),
-    #[error("Cannot execute this operation when document garbage collection is set")]
-    Gc,
+    /// Binary or JSON payload could not be decoded, because it was malformed or truncated.
+    #[error("failed to decode payload: {0}")]
+    Encoding(read::Error),
+
+    /// Decoded update could not be integrated, because it's inconsistent with the state of
+    /// a document.
+    #[error("failed to integrate update: {0}")]
+    Integration(String),
+
+    /// Document store is already borrowed by another, conflicting transaction.
+    #[error("document is already borrowed: {0}")]
+    Borrow(#[from] TransactionAcqError),
+
+    /// Operation was called with arguments or in a document state, which it doesn't support.
+    #[error("invalid operation: {0}")]
+    Validation(String),
+
+    /// Operation would exceed available memory or a configured limit.
+    #[error("capacity exceeded: {0}")]
+    Capacity(String),
+}
+
+impl Error {
+    /// Error returned by operations, which require a document with garbage collection disabled.
+    pub(crate) fn gc_enabled() -> Self {
+        Error::Validation(
+            "cannot execute this operation when document garbage collection is set".into(),
+        )
+    }
+}
+
+impl From<read::Error> for Error {
+    fn from(e: read::Error) -> Self {
+        match e {
+            read::Error::NotEnoughMemory(e) => Error::Capacity(e.to_string()),
+            read::Error::DepthLimitExceeded(depth) => {
+                Error::Capacity(read::Error::DepthLimitExceeded(depth).to_string())
+            }
+            other => Error::Encoding(other),
+        }
+    }
+}
+
+impl From<BorrowError> for Error {
+    fn from(e: BorrowError) -> Self {
+        Error::Borrow(e.into())
+    }
+}
+
+impl From<BorrowMutError> for Error {
+    fn from(e: BorrowMutError) -> Self {
+        Error::Borrow(e.into())
+    }
 }
@@ -1,7 +1,10 @@
+use crate::branch::BranchPtr;
 use crate::doc::DocAddr;
 use crate::transaction::Subdocs;
-use crate::{DeleteSet, Doc, StateVector, TransactionMut};
+use crate::types::TypeRef;
+use crate::{DeleteSet, Doc, Out, StateVector, TransactionMut};
 use std::collections::HashMap;
+use std::sync::Arc;
 
 /// An update event passed to a callback subscribed with [Doc::observe_update_v1]/[Doc::observe_update_v2].
 pub struct UpdateEvent {
@@ -93,3 +96,46 @@ impl<'a> ExactSizeIterator for SubdocsEventIter<'a> {
         self.0.len()
     }
 }
+
+/// Event passed to callbacks subscribed with [Doc::observe_roots]. It describes root types, which
+/// have been defined for the first time within the scope of a committed transaction - either
+/// locally or by applying a remote update.
+#[derive(Debug, Clone)]
+pub struct RootsEvent {
+    added: Vec<NewRoot>,
+}
+
+impl RootsEvent {
+    pub(crate) fn new(roots: Vec<BranchPtr>) -> Self {
+        let added = roots
+            .into_iter()
+            .filter_map(|branch| {
+                let name = branch.name.clone()?;
+                Some(NewRoot {
+                    name,
+                    type_ref: branch.type_ref().clone(),
+                    value: branch.into(),
+                })
+            })
+            .collect();
+        RootsEvent { added }
+    }
+
+    /// Returns all root types added to a current document within a scope of committed transaction.
+    pub fn added(&self) -> &[NewRoot] {
+        &self.added
+    }
+}
+
+/// Root type reported by [RootsEvent].
+#[derive(Debug, Clone, PartialEq)]
+pub struct NewRoot {
+    /// Name under which the root type has been defined.
+    pub name: Arc<str>,
+    /// Type of the root. Root types defined by remote updates carry no type information, in
+    /// which case this is [TypeRef::Undefined] until the type is accessed with one of the
+    /// `get_or_insert_*` methods.
+    pub type_ref: TypeRef,
+    /// Reference to the root type.
+    pub value: Out,
+}
@@ -1,22 +1,82 @@
-use crate::block::{BlockCell, ClientID, GC};
+use crate::block::{BlockCell, ClientID, Item, ItemContent, GC};
+use crate::block_store::BlockStore;
+use crate::branch::BranchPtr;
+use crate::doc::GcPolicy;
+use crate::id_set::DeleteSet;
+use crate::types::TypePtr;
 use crate::{TransactionMut, ID};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 
 #[derive(Default)]
 pub(crate) struct GCCollector {
     items: HashMap<ClientID, Vec<u32>>,
+    /// Number of elements, which contents have been discarded by [GCCollector::mark_all].
+    collected: u32,
 }
 
 impl GCCollector {
-    pub fn collect(txn: &mut TransactionMut) {
+    /// Garbage collects tombstones of a committed transaction according to a document
+    /// [GcPolicy]. If policy requires to keep the most recent tombstones, transaction delete set
+    /// is remembered and collected once it becomes old enough. Returns the number of elements,
+    /// which contents have been discarded.
+    pub fn collect(txn: &mut TransactionMut) -> u32 {
+        let store = &mut *txn.store;
+        let keep_recent = store.options.gc_policy.keep_recent as usize;
+        let mut expired = None;
+        if keep_recent > 0 && !txn.delete_set.is_empty() {
+            store.recent_deletes.push_back(txn.delete_set.clone());
+            while store.recent_deletes.len() > keep_recent {
+                expired = store.recent_deletes.pop_front();
+            }
+        }
+        if store.options.skip_gc {
+            return 0;
+        }
+        let delete_set = match &expired {
+            Some(ds) => ds,
+            None if keep_recent == 0 => &txn.delete_set,
+            None => return 0,
+        };
+
         let mut gc = Self::default();
-        gc.mark_all(txn);
+        let retained = Retained {
+            policy: &store.options.gc_policy,
+            recent: store.recent_deletes.iter().collect(),
+            map_history: &store.map_history,
+        };
+        gc.mark_all(&mut store.blocks, delete_set, &retained);
+        gc.trim_map_history(&txn.changed, &retained);
+        let collected = gc.collected;
         gc.collect_all_marked(txn);
+        collected
     }
 
-    fn mark_all(&mut self, txn: &mut TransactionMut) {
-        for (client, range) in txn.delete_set.iter() {
-            if let Some(blocks) = txn.store.blocks.get_client_mut(client) {
+    /// Garbage collects all tombstones existing in a document, which are not retained by a given
+    /// `policy`. This works regardless of [crate::Options::skip_gc] setting. Returns the number
+    /// of elements, which contents have been discarded.
+    pub fn collect_with(txn: &mut TransactionMut, policy: &GcPolicy) -> u32 {
+        let store = &mut *txn.store;
+        let delete_set = DeleteSet::from(&store.blocks);
+        let recent = &store.recent_deletes;
+        let keep_recent = recent.len().min(policy.keep_recent as usize);
+        let recent: Vec<_> = recent.iter().skip(recent.len() - keep_recent).collect();
+
+        let mut gc = Self::default();
+        let retained = Retained {
+            policy,
+            recent,
+            map_history: &store.map_history,
+        };
+        gc.mark_all(&mut store.blocks, &delete_set, &retained);
+        let collected = gc.collected;
+        gc.collect_all_marked(txn);
+        collected
+    }
+
+    fn mark_all(&mut self, blocks: &mut BlockStore, delete_set: &DeleteSet, retained: &Retained) {
+        for (client, range) in delete_set.iter() {
+            if let Some(blocks) = blocks.get_client_mut(client) {
                 for delete_item in range.iter().rev() {
                     let mut start = delete_item.start;
                     if let Some(mut i) = blocks.find_pivot(start) {
@@ -28,7 +88,14 @@ impl GCCollector {
                                 break;
                             } else {
                                 if let BlockCell::Block(item) = block {
-                                    item.gc(self, false);
+                                    if !Self::keeps_history(retained.map_history, item)
+                                        && !retained.contains(item)
+                                    {
+                                        if !matches!(item.content, ItemContent::Deleted(_)) {
+                                            self.collected += item.len();
+                                        }
+                                        item.gc(self, false);
+                                    }
                                 }
                                 i += 1;
                             }
@@ -39,6 +106,63 @@ impl GCCollector {
         }
     }
 
+    /// Checks if a given item is an overwritten map entry, which should be kept as a part of map
+    /// history.
+    fn keeps_history(map_history: &HashMap<BranchPtr, u32>, item: &Item) -> bool {
+        if item.parent_sub.is_none() || map_history.is_empty() {
+            return false;
+        }
+        match &item.parent {
+            TypePtr::Branch(parent) => map_history.contains_key(parent),
+            _ => false,
+        }
+    }
+
+    /// Garbage collects overwritten values of map entries changed within current transaction,
+    /// which exceed the history limit of their map.
+    fn trim_map_history(
+        &mut self,
+        changed: &HashMap<TypePtr, HashSet<Option<Arc<str>>>>,
+        retained: &Retained,
+    ) {
+        if retained.map_history.is_empty() {
+            return;
+        }
+        for (parent, keys) in changed.iter() {
+            let (branch, limit) = match parent {
+                TypePtr::Branch(branch) => match retained.map_history.get(branch) {
+                    Some(&limit) => (*branch, limit),
+                    None => continue,
+                },
+                _ => continue,
+            };
+            for key in keys.iter().flatten() {
+                let mut current = branch.map.get(key).copied();
+                if let Some(item) = current {
+                    if !item.is_deleted() {
+                        // current value is not a part of the history
+                        current = item.left;
+                    }
+                }
+                let mut kept = 0;
+                while let Some(mut item) = current {
+                    if let ItemContent::Deleted(_) = &item.content {
+                        // older values have already been collected
+                        break;
+                    }
+                    if kept >= limit {
+                        if !retained.contains(&item) {
+                            item.gc(self, false);
+                        }
+                    } else {
+                        kept += item.len();
+                    }
+                    current = item.left;
+                }
+            }
+        }
+    }
+
     /// Marks item with a given [ID] as a candidate for being GCed.
     pub(crate) fn mark(&mut self, id: &ID) {
         let client = self.items.entry(id.client).or_default();
@@ -64,3 +188,68 @@ impl GCCollector {
         }
     }
 }
+
+/// Tombstones, which should not be garbage collected according to a [GcPolicy].
+struct Retained<'a> {
+    policy: &'a GcPolicy,
+    recent: Vec<&'a DeleteSet>,
+    map_history: &'a HashMap<BranchPtr, u32>,
+}
+
+impl<'a> Retained<'a> {
+    /// Checks if a given deleted item should be kept. Since items of different age may be
+    /// squashed together, an item is kept if any part of it should be kept.
+    fn contains(&self, item: &Item) -> bool {
+        self.is_recent(item) || self.is_in_snapshot(item) || self.is_excluded(item)
+    }
+
+    fn is_recent(&self, item: &Item) -> bool {
+        let start = item.id.clock;
+        let end = start + item.len();
+        self.recent
+            .iter()
+            .any(|ds| match ds.range(&item.id.client) {
+                Some(range) => range.iter().any(|r| r.start < end && start < r.end),
+                None => false,
+            })
+    }
+
+    fn is_in_snapshot(&self, item: &Item) -> bool {
+        let start = item.id.clock;
+        self.policy.snapshots.values().any(|snapshot| {
+            let end = snapshot
+                .state_map
+                .get(&item.id.client)
+                .min(start + item.len());
+            if start >= end {
+                return false; // item was inserted after the snapshot
+            }
+            match snapshot.delete_set.range(&item.id.client) {
+                Some(range) => !range.iter().any(|r| r.start <= start && end <= r.end),
+                None => true,
+            }
+        })
+    }
+
+    fn is_excluded(&self, item: &Item) -> bool {
+        if self.policy.excluded_roots.is_empty() {
+            return false;
+        }
+        let mut parent = item.parent.clone();
+        loop {
+            match parent {
+                TypePtr::Branch(branch) => match branch.item {
+                    Some(item) => parent = item.parent.clone(),
+                    None => {
+                        return match &branch.name {
+                            Some(name) => self.policy.excluded_roots.contains(name),
+                            None => false,
+                        }
+                    }
+                },
+                TypePtr::Named(name) => return self.policy.excluded_roots.contains(&name),
+                _ => return false,
+            }
+        }
+    }
+}
@@ -0,0 +1,177 @@
+//! Time-travel over the history of a document.
+//!
+//! Documents are usually persisted as a base state followed by a log of incremental updates.
+//! [HistoryIter] replays such a log on top of a base state and yields a [Checkpoint] after every
+//! update, describing the state of a document at that point together with user-defined metadata
+//! stored alongside the update (i.e. its author or timestamp). Any checkpoint can be turned into
+//! a read-only view of a document using [HistoryIter::checkout] or [Doc::checkout].
+//!
+//! # Example
+//!
+//! ```rust
+//! use yrs::history::HistoryIter;
+//! use yrs::updates::decoder::Decode;
+//! use yrs::{Doc, GetString, ReadTxn, Text, Transact, Update};
+//!
+//! let doc = Doc::with_client_id(1);
+//! let text = doc.get_or_insert_text("text");
+//! let mut log = Vec::new();
+//! for (author, chunk) in [("alice", "hello"), ("bob", " world")] {
+//!     let sv = doc.transact().state_vector();
+//!     text.push(&mut doc.transact_mut(), chunk);
+//!     log.push((doc.transact().encode_diff_v1(&sv), author));
+//! }
+//!
+//! let updates = log
+//!     .into_iter()
+//!     .map(|(bytes, author)| (Update::decode_v1(&bytes).unwrap(), author));
+//! let mut history = HistoryIter::new(Update::new(), updates);
+//! let checkpoints: Vec<_> = history.by_ref().collect();
+//! assert_eq!(checkpoints[1].metadata, "bob");
+//!
+//! let view = history.checkout(&checkpoints[0].snapshot).unwrap();
+//! let txn = view.transact();
+//! let text = txn.get_text("text").unwrap();
+//! assert_eq!(text.get_string(&txn), "hello");
+//! ```
+
+use crate::{DeleteSet, Doc, Options, ReadTxn, Snapshot, StateDelta, Transact, Update};
+
+/// State of a document after an update produced by [HistoryIter] has been applied.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Checkpoint<M> {
+    /// Snapshot of a document state right after an update has been applied.
+    pub snapshot: Snapshot,
+    /// Ranges of blocks inserted by an update, grouped by their authors' client identifiers.
+    pub inserted: StateDelta,
+    /// Blocks deleted by an update.
+    pub deleted: DeleteSet,
+    /// User-defined metadata stored together with an update.
+    pub metadata: M,
+}
+
+/// Iterator replaying a sequence of stored updates on top of a base state, producing
+/// a [Checkpoint] after every applied update. See [module level docs](crate::history) for
+/// details.
+///
+/// Updates are integrated into a document with garbage collection disabled, so that every
+/// produced checkpoint can be checked out later on.
+pub struct HistoryIter<I> {
+    doc: Doc,
+    updates: I,
+}
+
+impl<I, M> HistoryIter<I>
+where
+    I: Iterator<Item = (Update, M)>,
+{
+    /// Creates a new history iterator, which will apply `updates` (together with their metadata)
+    /// on top of a document state described by `base` update. Use [Update::new] to start from
+    /// an empty document.
+    pub fn new<U>(base: Update, updates: U) -> Self
+    where
+        U: IntoIterator<IntoIter = I>,
+    {
+        let doc = Doc::with_options(Options {
+            skip_gc: true,
+            ..Options::default()
+        });
+        doc.transact_mut().apply_update(base);
+        HistoryIter {
+            doc,
+            updates: updates.into_iter(),
+        }
+    }
+}
+
+impl<I> HistoryIter<I> {
+    /// Returns a document containing all updates applied so far.
+    pub fn doc(&self) -> &Doc {
+        &self.doc
+    }
+
+    /// Returns a read-only document representing a state at the moment when a given `snapshot`
+    /// has been made. Snapshot must have been produced by one of the checkpoints yielded by
+    /// current iterator so far.
+    pub fn checkout(&self, snapshot: &Snapshot) -> Result<Doc, crate::Error> {
+        self.doc.checkout(snapshot)
+    }
+}
+
+impl<I, M> Iterator for HistoryIter<I>
+where
+    I: Iterator<Item = (Update, M)>,
+{
+    type Item = Checkpoint<M>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (update, metadata) = self.updates.next()?;
+        let mut txn = self.doc.transact_mut();
+        txn.apply_update(update);
+        Some(Checkpoint {
+            snapshot: txn.snapshot(),
+            inserted: txn.state_delta(),
+            deleted: txn.delete_set().clone(),
+            metadata,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::history::HistoryIter;
+    use crate::updates::decoder::Decode;
+    use crate::{Doc, GetString, ReadTxn, Text, Transact, Update};
+
+    #[test]
+    fn replay_history() {
+        let d1 = Doc::with_client_id(1);
+        let d2 = Doc::with_client_id(2);
+        let t1 = d1.get_or_insert_text("text");
+        let t2 = d2.get_or_insert_text("text");
+        t1.push(&mut d1.transact_mut(), "abc");
+        let base = Update::decode_v1(&d1.transact().encode_state_as_update_v1(&Default::default()))
+            .unwrap();
+        let base_sv = d1.transact().state_vector();
+
+        let mut log = Vec::new();
+        let sv = d2.transact().state_vector();
+        d2.transact_mut()
+            .apply_update(Update::decode_v1(&d1.transact().encode_diff_v1(&sv)).unwrap());
+        let sv = d2.transact().state_vector();
+        t2.insert(&mut d2.transact_mut(), 1, "xy");
+        log.push((d2.transact().encode_diff_v1(&sv), 10));
+        t2.remove_range(&mut d2.transact_mut(), 0, 2);
+        log.push((
+            d2.transact().encode_diff_v1(&d2.transact().state_vector()),
+            20,
+        ));
+
+        let updates = log
+            .into_iter()
+            .map(|(bytes, ts)| (Update::decode_v1(&bytes).unwrap(), ts));
+        let mut history = HistoryIter::new(base, updates);
+        assert_eq!(history.doc().transact().state_vector(), base_sv);
+
+        let first = history.next().unwrap();
+        assert_eq!(first.metadata, 10);
+        assert_eq!(first.inserted.len(), 1);
+        assert_eq!(first.inserted.get(&2).unwrap().len(), 2);
+        assert!(first.deleted.is_empty());
+
+        let second = history.next().unwrap();
+        assert_eq!(second.metadata, 20);
+        assert!(second.inserted.is_empty());
+        assert!(!second.deleted.is_empty());
+        assert!(history.next().is_none());
+
+        for (checkpoint, expected) in [(&first, "axybc"), (&second, "ybc")] {
+            let view = history.checkout(&checkpoint.snapshot).unwrap();
+            let txn = view.transact();
+            let text = txn.get_text("text").unwrap();
+            assert_eq!(text.get_string(&txn), expected);
+            drop(txn);
+            assert!(view.try_transact_mut().is_err());
+        }
+    }
+}
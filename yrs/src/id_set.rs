@@ -79,6 +79,38 @@ impl IdRange {
         }
     }
 
+    /// Returns an [IdRange] containing all clock values of a current range, which are not
+    /// included in the `other` range.
+    pub fn subtract(&self, other: &IdRange) -> IdRange {
+        let mut excluded = other.clone();
+        excluded.squash();
+        let excluded: Vec<&Range<u32>> = excluded.iter().collect();
+        let mut ranges: Vec<&Range<u32>> = self.iter().collect();
+        ranges.sort_by_key(|r| r.start);
+
+        let mut result = IdRange::with_capacity(0);
+        let mut i = 0;
+        for range in ranges {
+            let mut start = range.start;
+            while i < excluded.len() && excluded[i].end <= start {
+                i += 1;
+            }
+            let mut j = i;
+            while j < excluded.len() && excluded[j].start < range.end {
+                let e = excluded[j];
+                if e.start > start {
+                    result.push(start..e.start);
+                }
+                start = start.max(e.end);
+                j += 1;
+            }
+            if start < range.end {
+                result.push(start..range.end);
+            }
+        }
+        result
+    }
+
     /// Check if given clock exists within current [IdRange].
     pub fn contains(&self, clock: u32) -> bool {
         match self {
@@ -274,7 +306,8 @@ impl Decode for IdRange {
                 Ok(IdRange::Continuous(range))
             }
             len => {
-                let mut ranges = Vec::with_capacity(len as usize);
+                let mut ranges = Vec::new();
+                ranges.try_reserve(decoder.bounded_len(len as usize)?)?;
                 let mut i = 0;
                 while i < len {
                     ranges.push(Range::decode(decoder)?);
@@ -414,6 +447,7 @@ impl Decode for IdSet {
     fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, Error> {
         let mut set = Self::new();
         let client_len: u32 = decoder.read_var()?;
+        decoder.bounded_len(client_len as usize)?;
         let mut i = 0;
         while i < client_len {
             decoder.reset_ds_cur_val();
@@ -569,6 +603,22 @@ impl DeleteSet {
         self.0.get(client_id)
     }
 
+    /// Returns a delete set containing all deletions of a current delete set, which are not
+    /// included in the `other` one.
+    pub fn subtract(&self, other: &DeleteSet) -> DeleteSet {
+        let mut result = DeleteSet::new();
+        for (&client, range) in self.iter() {
+            let range = match other.range(&client) {
+                Some(excluded) => range.subtract(excluded),
+                None => range.clone(),
+            };
+            if !range.is_empty() {
+                result.0.insert_range(client, range);
+            }
+        }
+        result
+    }
+
     pub(crate) fn try_squash_with(&mut self, store: &mut Store) {
         // try to merge deleted / gc'd items
         for (&client, range) in self.iter() {
@@ -781,6 +831,23 @@ mod test {
         );
     }
 
+    #[test]
+    fn id_range_subtract() {
+        assert!(IdRange::Continuous(2..5)
+            .subtract(&IdRange::Continuous(0..10))
+            .is_empty());
+
+        assert_eq!(
+            IdRange::Continuous(0..10).subtract(&IdRange::Fragmented(vec![7..8, 2..4])),
+            IdRange::Fragmented(vec![0..2, 4..7, 8..10])
+        );
+
+        assert_eq!(
+            IdRange::Fragmented(vec![0..3, 5..9]).subtract(&IdRange::Continuous(2..6)),
+            IdRange::Fragmented(vec![0..2, 6..9])
+        );
+    }
+
     #[test]
     fn id_range_contains() {
         assert!(!IdRange::Continuous(1..3).contains(0));
@@ -6,6 +6,8 @@ use crate::types::TypeRef;
 use crate::{
     Any, ArrayPrelim, Doc, MapPrelim, Out, TransactionMut, XmlElementPrelim, XmlFragmentPrelim,
 };
+use serde::{Deserialize, Deserializer};
+use std::sync::Arc;
 
 /// A wrapper around [Out] type that enables it to be used as a type to be inserted into
 /// shared collections. If [In] contains a shared type, it will be inserted as a deep
@@ -96,3 +98,28 @@ impl_from_any!(std::sync::Arc<str>);
 impl_from_any!(&str);
 impl_from_any!(Vec<u8>);
 impl_from_any!(&[u8]);
+
+impl In {
+    /// Converts a given `any` value into [In], turning nested maps and arrays into preliminary
+    /// shared collections ([MapPrelim] and [ArrayPrelim]), so that once inserted, they can be
+    /// modified collaboratively.
+    pub fn nested(any: Any) -> Self {
+        match any {
+            Any::Map(map) => {
+                let map = Arc::try_unwrap(map).unwrap_or_else(|map| (*map).clone());
+                In::Map(map.into_iter().map(|(k, v)| (k, In::nested(v))).collect())
+            }
+            Any::Array(array) => In::Array(array.iter().cloned().map(In::nested).collect()),
+            other => In::Any(other),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for In {
+    /// Deserializes [In] from any self-describing serde format (i.e. JSON, TOML or YAML).
+    /// Objects and sequences are turned into preliminary shared collections - see [In::nested].
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let any = Any::deserialize(deserializer)?;
+        Ok(In::nested(any))
+    }
+}
@@ -0,0 +1,377 @@
+//! Export of the internal structure of a document for debugging tools.
+//!
+//! [export] dumps the block store of a document - including deleted items, garbage collected
+//! ranges and pending updates - using the object layout of Yjs structures (`Item`, `GC`,
+//! `Content*` classes), which is understood by Yjs inspector tooling. This allows web-based
+//! debuggers to visualize the documents hosted by Rust peers.
+
+use crate::block::{BlockCell, ClientID, Item, ItemContent, ID};
+use crate::id_set::DeleteSet;
+use crate::types::{TypePtr, TypeRef};
+use crate::updates::encoder::Encode;
+use crate::{Any, ReadTxn};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+/// Returns a snapshot of the internal structure of a document visible to a given `txn`.
+///
+/// # Example
+///
+/// ```rust
+/// use yrs::inspector::{export, Struct};
+/// use yrs::{Doc, Text, Transact};
+///
+/// let doc = Doc::with_client_id(1);
+/// let text = doc.get_or_insert_text("text");
+/// text.push(&mut doc.transact_mut(), "hello");
+///
+/// let dump = export(&doc.transact());
+/// assert_eq!(dump.share["text"].type_name, "YText");
+/// assert!(matches!(dump.store.clients[&1][0], Struct::Item(_)));
+///
+/// // serialized using the same field names as Yjs structures
+/// let json = dump.to_json();
+/// assert!(json.contains(r#""content":{"type":"ContentString","str":"hello"}"#));
+/// ```
+pub fn export<T: ReadTxn>(txn: &T) -> DocDump {
+    let store = txn.store();
+    let share = store
+        .types
+        .iter()
+        .map(|(name, branch)| {
+            let shared = SharedType {
+                type_name: type_name(branch.type_ref()),
+                start: branch.start.map(|item| item.id),
+                length: branch.content_len,
+            };
+            (name.clone(), shared)
+        })
+        .collect();
+    let mut clients = BTreeMap::new();
+    for (&client, blocks) in store.blocks.iter() {
+        let structs = blocks
+            .iter()
+            .map(|cell| match cell {
+                BlockCell::GC(gc) => Struct::GC(GcStruct {
+                    id: ID::new(client, gc.start),
+                    length: gc.len(),
+                }),
+                BlockCell::Block(item) => Struct::Item(ItemStruct::new(item)),
+            })
+            .collect();
+        clients.insert(client, structs);
+    }
+    let pending_structs = store.pending.as_ref().map(|pending| PendingStructs {
+        missing: pending
+            .missing
+            .iter()
+            .map(|(&c, &clock)| (c, clock))
+            .collect(),
+        update: pending.update.encode_v1(),
+    });
+    DocDump {
+        guid: store.options.guid.clone(),
+        client_id: store.options.client_id,
+        share,
+        store: StructStore {
+            clients,
+            pending_structs,
+            pending_ds: store.pending_ds.as_ref().map(delete_ranges),
+        },
+    }
+}
+
+/// Internal structure of a document, produced by [export].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DocDump {
+    /// Unique identifier of a document.
+    pub guid: Arc<str>,
+    /// Identifier of a peer owning a document.
+    #[serde(rename = "clientID")]
+    pub client_id: ClientID,
+    /// Root types of a document, indexed by their names.
+    pub share: BTreeMap<Arc<str>, SharedType>,
+    /// Blocks of a document, including the ones waiting for their missing dependencies.
+    pub store: StructStore,
+}
+
+impl DocDump {
+    /// Serializes current dump into a JSON string.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
+
+/// Root type of a document.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SharedType {
+    /// Name of a Yjs class corresponding to a type of this collection, i.e. `YMap` or `YText`.
+    #[serde(rename = "type")]
+    pub type_name: &'static str,
+    /// Identifier of the first block of a sequence stored in this collection.
+    pub start: Option<ID>,
+    /// Number of elements visible in a sequence stored in this collection.
+    pub length: u32,
+}
+
+/// Blocks of a document grouped by their clients.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StructStore {
+    /// Integrated blocks of each client, ordered by their clocks.
+    pub clients: BTreeMap<ClientID, Vec<Struct>>,
+    /// Blocks received, but not yet integrated due to missing dependencies.
+    pub pending_structs: Option<PendingStructs>,
+    /// Deletions received, but not yet applied due to missing blocks, as `[clock, length]` pairs.
+    pub pending_ds: Option<BTreeMap<ClientID, Vec<[u32; 2]>>>,
+}
+
+/// Update waiting for the blocks it depends on.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PendingStructs {
+    /// The lowest clocks of the clients, which must be integrated before a pending update.
+    pub missing: BTreeMap<ClientID, u32>,
+    /// Pending update encoded using lib0 v1 encoding.
+    pub update: Vec<u8>,
+}
+
+/// Single block of a document.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type")]
+pub enum Struct {
+    Item(ItemStruct),
+    GC(GcStruct),
+}
+
+/// Range of blocks, which have been deleted and garbage collected.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct GcStruct {
+    pub id: ID,
+    pub length: u32,
+}
+
+/// Block containing user data. It's kept in a document even after being deleted.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ItemStruct {
+    pub id: ID,
+    pub length: u32,
+    pub origin: Option<ID>,
+    pub right_origin: Option<ID>,
+    pub left: Option<ID>,
+    pub right: Option<ID>,
+    /// Parent collection - a name of the root type or an identifier of a nested type.
+    pub parent: Option<Parent>,
+    pub parent_sub: Option<Arc<str>>,
+    pub redone: Option<ID>,
+    pub deleted: bool,
+    pub keep: bool,
+    pub countable: bool,
+    pub content: Content,
+}
+
+impl ItemStruct {
+    fn new(item: &Item) -> Self {
+        let parent = match &item.parent {
+            TypePtr::Branch(branch) => match branch.item {
+                Some(ptr) => Some(Parent::Nested(ptr.id)),
+                None => branch.name.clone().map(Parent::Root),
+            },
+            TypePtr::Named(name) => Some(Parent::Root(name.clone())),
+            TypePtr::ID(id) => Some(Parent::Nested(*id)),
+            TypePtr::Unknown => None,
+        };
+        ItemStruct {
+            id: item.id,
+            length: item.len,
+            origin: item.origin,
+            right_origin: item.right_origin,
+            left: item.left.map(|ptr| ptr.last_id()),
+            right: item.right.map(|ptr| ptr.id),
+            parent,
+            parent_sub: item.parent_sub.clone(),
+            redone: item.redone,
+            deleted: item.is_deleted(),
+            keep: item.info.is_keep(),
+            countable: item.is_countable(),
+            content: Content::new(&item.content),
+        }
+    }
+}
+
+/// Parent collection of an [ItemStruct].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(untagged)]
+pub enum Parent {
+    Root(Arc<str>),
+    Nested(ID),
+}
+
+/// Content of an [ItemStruct], named after corresponding Yjs classes.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type")]
+pub enum Content {
+    ContentAny {
+        arr: Vec<Any>,
+    },
+    ContentBinary {
+        content: Vec<u8>,
+    },
+    ContentDeleted {
+        len: u32,
+    },
+    ContentDoc {
+        guid: Arc<str>,
+    },
+    ContentJSON {
+        arr: Vec<String>,
+    },
+    ContentEmbed {
+        embed: Any,
+    },
+    ContentFormat {
+        key: Arc<str>,
+        value: Any,
+    },
+    ContentString {
+        str: String,
+    },
+    ContentType {
+        #[serde(rename = "typeName")]
+        type_name: &'static str,
+    },
+    ContentMove {
+        priority: i32,
+    },
+}
+
+impl Content {
+    fn new(content: &ItemContent) -> Self {
+        match content {
+            ItemContent::Any(arr) => Content::ContentAny { arr: arr.clone() },
+            ItemContent::Binary(content) => Content::ContentBinary {
+                content: content.clone(),
+            },
+            ItemContent::Deleted(len) => Content::ContentDeleted { len: *len },
+            ItemContent::Doc(_, doc) => Content::ContentDoc {
+                guid: doc.guid().clone(),
+            },
+            ItemContent::JSON(arr) => Content::ContentJSON { arr: arr.clone() },
+            ItemContent::Embed(embed) => Content::ContentEmbed {
+                embed: embed.clone(),
+            },
+            ItemContent::Format(key, value) => Content::ContentFormat {
+                key: key.clone(),
+                value: value.as_ref().clone(),
+            },
+            ItemContent::String(str) => Content::ContentString {
+                str: str.as_str().to_string(),
+            },
+            ItemContent::Type(branch) => Content::ContentType {
+                type_name: type_name(branch.type_ref()),
+            },
+            ItemContent::Move(m) => Content::ContentMove {
+                priority: m.priority,
+            },
+        }
+    }
+}
+
+/// Returns a name of a Yjs class corresponding to a given type.
+fn type_name(type_ref: &TypeRef) -> &'static str {
+    match type_ref {
+        TypeRef::Array => "YArray",
+        TypeRef::Map => "YMap",
+        TypeRef::Text => "YText",
+        TypeRef::XmlElement(_) => "YXmlElement",
+        TypeRef::XmlFragment => "YXmlFragment",
+        TypeRef::XmlHook => "YXmlHook",
+        TypeRef::XmlText => "YXmlText",
+        TypeRef::SubDoc => "Doc",
+        #[cfg(feature = "weak")]
+        TypeRef::WeakLink(_) => "YWeakLink",
+        TypeRef::Counter => "YCounter",
+        TypeRef::GSet => "YGSet",
+        TypeRef::TwoPhaseSet => "YTwoPhaseSet",
+        TypeRef::Fixed => "YFixed",
+        TypeRef::Undefined => "AbstractType",
+    }
+}
+
+fn delete_ranges(ds: &DeleteSet) -> BTreeMap<ClientID, Vec<[u32; 2]>> {
+    ds.iter()
+        .map(|(&client, ranges)| {
+            let ranges = ranges.iter().map(|r| [r.start, r.end - r.start]).collect();
+            (client, ranges)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::inspector::{export, Content, Parent, Struct};
+    use crate::updates::decoder::Decode;
+    use crate::{Doc, Map, MapPrelim, ReadTxn, StateVector, Text, Transact, Update, ID};
+
+    #[test]
+    fn export_structure() {
+        let doc = Doc::with_client_id(1);
+        let map = doc.get_or_insert_map("map");
+        let text = doc.get_or_insert_text("text");
+        {
+            let mut txn = doc.transact_mut();
+            let nested = map.insert(&mut txn, "nested", MapPrelim::default());
+            nested.insert(&mut txn, "key", "value");
+            text.push(&mut txn, "hello");
+            text.remove_range(&mut txn, 0, 2);
+        }
+
+        let dump = export(&doc.transact());
+        assert_eq!(dump.client_id, 1);
+        assert_eq!(dump.share["map"].type_name, "YMap");
+        assert_eq!(dump.share["text"].length, 3);
+        let items: Vec<_> = dump.store.clients[&1]
+            .iter()
+            .map(|s| match s {
+                Struct::Item(item) => item,
+                Struct::GC(_) => panic!("unexpected GC"),
+            })
+            .collect();
+        assert_eq!(items[0].parent, Some(Parent::Root("map".into())));
+        assert_eq!(items[0].parent_sub.as_deref(), Some("nested"));
+        assert_eq!(items[0].content, Content::ContentType { type_name: "YMap" });
+        assert_eq!(items[1].parent, Some(Parent::Nested(ID::new(1, 0))));
+        assert!(items[2].deleted);
+        // content of deleted items is garbage collected
+        assert_eq!(items[2].content, Content::ContentDeleted { len: 2 });
+        assert!(!items[3].deleted);
+        assert!(dump.store.pending_structs.is_none());
+
+        // blocks waiting for missing dependencies
+        let other = Doc::with_client_id(2);
+        let sv = doc.transact().state_vector();
+        text.push(&mut doc.transact_mut(), "!");
+        let diff = doc.transact().encode_state_as_update_v1(&sv);
+        other
+            .transact_mut()
+            .apply_update(Update::decode_v1(&diff).unwrap());
+        let dump = export(&other.transact());
+        let pending = dump.store.pending_structs.as_ref().unwrap();
+        assert_eq!(pending.missing.get(&1), Some(&6));
+        assert!(dump.store.clients.is_empty());
+        assert!(dump
+            .to_json()
+            .contains(r#""pendingStructs":{"missing":{"1":6}"#));
+
+        let update = doc
+            .transact()
+            .encode_state_as_update_v1(&StateVector::default());
+        other
+            .transact_mut()
+            .apply_update(Update::decode_v1(&update).unwrap());
+        assert!(export(&other.transact()).store.pending_structs.is_none());
+    }
+}
@@ -0,0 +1,315 @@
+//! Invariants checked over the contents of a document after every transaction commit.
+//!
+//! Concurrent changes made by different peers are always merged, even if the result doesn't
+//! make sense from the application point of view - i.e. an array expected to be sorted may end up
+//! unsorted after two peers have inserted elements at the same position. An [Invariant] allows to
+//! detect such cases as soon as they happen, report them as [Violation]s and optionally repair
+//! them in a follow-up transaction.
+
+use crate::observer::Observer;
+use crate::types::DeepObservable;
+use crate::{Subscription, TransactionMut};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+type CheckFn = Box<dyn Fn(&TransactionMut) -> Result<(), String> + Send + Sync + 'static>;
+
+type RepairFn = Box<dyn Fn(&mut TransactionMut) + Send + Sync + 'static>;
+
+type ViolationFn = Box<dyn Fn(&TransactionMut, &Violation) + Send + Sync + 'static>;
+
+/// Information about an [Invariant] found not to hold after a transaction commit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    /// Name of the violated invariant.
+    pub invariant: Arc<str>,
+    /// Description of the violation returned by the invariant check.
+    pub message: String,
+    /// What has been done to repair the violation.
+    pub repair: RepairStatus,
+}
+
+/// Status of a repair of a [Violation].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RepairStatus {
+    /// Violated invariant has no repair function defined.
+    None,
+    /// Repair function has been scheduled to run in a follow-up transaction.
+    Scheduled,
+    /// Violation has been detected after changes made by a repair function. No further repairs
+    /// are scheduled in such case, to avoid repair functions triggering each other endlessly.
+    Failed,
+}
+
+/// A condition over the contents of a shared collection, which is verified every time a committed
+/// transaction has changed that collection or any of its nested collections - no matter if these
+/// changes were made locally or by remote peers.
+///
+/// When a check fails, a [Violation] is passed to callbacks subscribed with
+/// [Invariant::observe_violations]. Invariants created with [Invariant::with_repair] additionally
+/// run their repair function in a follow-up transaction (see [TransactionMut::defer]), which
+/// inherits the origin of a transaction that caused the violation.
+///
+/// Since all peers observe the same changes, they may all attempt to repair the same violation
+/// concurrently. For that reason repair functions should be idempotent and produce the same
+/// result no matter how many times they were applied.
+///
+/// Invariant stays active until it's dropped.
+///
+/// # Example
+///
+/// ```rust
+/// use yrs::invariant::Invariant;
+/// use yrs::{Array, Doc, Out, Transact};
+///
+/// let doc = Doc::new();
+/// let array = doc.get_or_insert_array("sorted");
+/// let _sorted = Invariant::with_repair(
+///     "sorted",
+///     &array,
+///     {
+///         let array = array.clone();
+///         move |txn| {
+///             let values: Vec<f64> = array.iter(txn).filter_map(|v| v.cast().ok()).collect();
+///             if values.windows(2).all(|w| w[0] <= w[1]) {
+///                 Ok(())
+///             } else {
+///                 Err(format!("array is not sorted: {:?}", values))
+///             }
+///         }
+///     },
+///     {
+///         let array = array.clone();
+///         move |txn| {
+///             let mut values: Vec<f64> = array.iter(txn).filter_map(|v| v.cast().ok()).collect();
+///             values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+///             let len = array.len(txn);
+///             array.remove_range(txn, 0, len);
+///             array.insert_range(txn, 0, values);
+///         }
+///     },
+/// );
+///
+/// array.insert_range(&mut doc.transact_mut(), 0, [1, 3, 2]);
+/// let values: Vec<Out> = array.iter(&doc.transact()).collect();
+/// assert_eq!(values, vec![Out::from(1.0), Out::from(2.0), Out::from(3.0)]);
+/// ```
+pub struct Invariant {
+    inner: Arc<Inner>,
+    _subscription: Subscription,
+}
+
+struct Inner {
+    name: Arc<str>,
+    check: CheckFn,
+    repair: Option<RepairFn>,
+    repairing: AtomicBool,
+    observers: Observer<ViolationFn>,
+}
+
+impl Invariant {
+    /// Creates a new invariant identified by `name`, which verifies the contents of a `target`
+    /// collection using a `check` function. Check returns an error message when the invariant
+    /// doesn't hold.
+    pub fn new<N, S, C>(name: N, target: &S, check: C) -> Self
+    where
+        N: Into<Arc<str>>,
+        S: DeepObservable,
+        C: Fn(&TransactionMut) -> Result<(), String> + Send + Sync + 'static,
+    {
+        Self::create(name.into(), target, Box::new(check), None)
+    }
+
+    /// Creates a new invariant identified by `name`, which verifies the contents of a `target`
+    /// collection using a `check` function. When a check fails, `repair` function is scheduled
+    /// to run in a follow-up transaction.
+    pub fn with_repair<N, S, C, R>(name: N, target: &S, check: C, repair: R) -> Self
+    where
+        N: Into<Arc<str>>,
+        S: DeepObservable,
+        C: Fn(&TransactionMut) -> Result<(), String> + Send + Sync + 'static,
+        R: Fn(&mut TransactionMut) + Send + Sync + 'static,
+    {
+        Self::create(name.into(), target, Box::new(check), Some(Box::new(repair)))
+    }
+
+    fn create<S>(name: Arc<str>, target: &S, check: CheckFn, repair: Option<RepairFn>) -> Self
+    where
+        S: DeepObservable,
+    {
+        let inner = Arc::new(Inner {
+            name,
+            check,
+            repair,
+            repairing: AtomicBool::new(false),
+            observers: Observer::new(),
+        });
+        let weak = Arc::downgrade(&inner);
+        let subscription = target.observe_deep(move |txn, _| {
+            if let Some(inner) = weak.upgrade() {
+                Inner::verify(&inner, txn);
+            }
+        });
+        Invariant {
+            inner,
+            _subscription: subscription,
+        }
+    }
+
+    /// Returns a name of this invariant.
+    pub fn name(&self) -> &Arc<str> {
+        &self.inner.name
+    }
+
+    /// Subscribes a callback `f`, which is called whenever this invariant has been found violated
+    /// after a transaction commit. This method returns a subscription, which will automatically
+    /// unsubscribe current callback when dropped.
+    pub fn observe_violations<F>(&self, f: F) -> Subscription
+    where
+        F: Fn(&TransactionMut, &Violation) + Send + Sync + 'static,
+    {
+        self.inner.observers.subscribe(Box::new(f))
+    }
+}
+
+impl Inner {
+    fn verify(inner: &Arc<Self>, txn: &TransactionMut) {
+        let message = match (inner.check)(txn) {
+            Ok(()) => return,
+            Err(message) => message,
+        };
+        let repair = if inner.repairing.load(Ordering::Acquire) {
+            RepairStatus::Failed
+        } else if inner.repair.is_some() {
+            let inner = inner.clone();
+            txn.defer(move |txn| {
+                if let Some(repair) = inner.repair.as_ref() {
+                    inner.repairing.store(true, Ordering::Release);
+                    repair(txn);
+                    // commit here, so that the outcome of a repair is verified while the flag is set
+                    txn.commit();
+                    inner.repairing.store(false, Ordering::Release);
+                }
+            });
+            RepairStatus::Scheduled
+        } else {
+            RepairStatus::None
+        };
+        let violation = Violation {
+            invariant: inner.name.clone(),
+            message,
+            repair,
+        };
+        inner.observers.trigger(|f| f(txn, &violation));
+    }
+}
+
+impl std::fmt::Debug for Invariant {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Invariant")
+            .field("name", &self.inner.name)
+            .field("repairable", &self.inner.repair.is_some())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::invariant::{Invariant, RepairStatus};
+    use crate::test_utils::exchange_updates;
+    use crate::{Doc, Map, Transact};
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn report_violations() {
+        let doc = Doc::with_client_id(1);
+        let map = doc.get_or_insert_map("map");
+        let invariant = Invariant::new("lowercase keys", &map, {
+            let map = map.clone();
+            move |txn| match map.keys(txn).find(|k| k.chars().any(char::is_uppercase)) {
+                Some(key) => Err(format!("invalid key: {}", key)),
+                None => Ok(()),
+            }
+        });
+        let violations = Arc::new(Mutex::new(Vec::new()));
+        let _sub = {
+            let violations = violations.clone();
+            invariant.observe_violations(move |_, v| violations.lock().unwrap().push(v.clone()))
+        };
+
+        map.insert(&mut doc.transact_mut(), "key", 1);
+        assert!(violations.lock().unwrap().is_empty());
+
+        map.insert(&mut doc.transact_mut(), "Key", 2);
+        let violations = violations.lock().unwrap();
+        assert_eq!(violations.len(), 1);
+        assert_eq!(&*violations[0].invariant, "lowercase keys");
+        assert_eq!(violations[0].message, "invalid key: Key");
+        assert_eq!(violations[0].repair, RepairStatus::None);
+    }
+
+    #[test]
+    fn repair_concurrent_changes() {
+        let d1 = Doc::with_client_id(1);
+        let d2 = Doc::with_client_id(2);
+        let m1 = d1.get_or_insert_map("map");
+        let m2 = d2.get_or_insert_map("map");
+        // at most one of the "a" and "b" keys can be set
+        let invariant = Invariant::with_repair(
+            "exclusive",
+            &m1,
+            {
+                let map = m1.clone();
+                move |txn| {
+                    if map.contains_key(txn, "a") && map.contains_key(txn, "b") {
+                        Err("both keys are set".into())
+                    } else {
+                        Ok(())
+                    }
+                }
+            },
+            {
+                let map = m1.clone();
+                move |txn| {
+                    map.remove(txn, "b");
+                }
+            },
+        );
+        let statuses = Arc::new(Mutex::new(Vec::new()));
+        let _sub = {
+            let statuses = statuses.clone();
+            invariant.observe_violations(move |_, v| statuses.lock().unwrap().push(v.repair))
+        };
+
+        m1.insert(&mut d1.transact_mut(), "a", 1);
+        m2.insert(&mut d2.transact_mut(), "b", 2);
+        exchange_updates(&[&d1, &d2]);
+
+        assert_eq!(*statuses.lock().unwrap(), vec![RepairStatus::Scheduled]);
+        assert!(m1.contains_key(&d1.transact(), "a"));
+        assert!(!m1.contains_key(&d1.transact(), "b"));
+    }
+
+    #[test]
+    fn failed_repair() {
+        let doc = Doc::with_client_id(1);
+        let map = doc.get_or_insert_map("map");
+        let invariant = Invariant::with_repair("never", &map, |_| Err("always violated".into()), {
+            let map = map.clone();
+            move |txn| {
+                map.insert(txn, "repaired", true);
+            }
+        });
+        let statuses = Arc::new(Mutex::new(Vec::new()));
+        let _sub = {
+            let statuses = statuses.clone();
+            invariant.observe_violations(move |_, v| statuses.lock().unwrap().push(v.repair))
+        };
+
+        map.insert(&mut doc.transact_mut(), "key", 1);
+        assert_eq!(
+            *statuses.lock().unwrap(),
+            vec![RepairStatus::Scheduled, RepairStatus::Failed]
+        );
+    }
+}
@@ -573,28 +573,58 @@
 mod alt;
 pub mod block;
 mod block_store;
+mod delta_buffer;
 pub mod doc;
 mod event;
 mod id_set;
 mod store;
+#[cfg(feature = "async")]
+pub mod stream;
 mod transaction;
 pub mod types;
 mod update;
 pub mod updates;
 mod utils;
+mod xml_index;
 
 pub mod any;
 pub mod atomic;
+pub mod backup;
+pub mod batch;
+pub mod blame;
+mod block_index;
 mod block_iter;
+#[cfg(feature = "borrow-tracker")]
+pub mod borrow_tracker;
 pub mod branch;
+pub mod computed;
+#[cfg(feature = "sync")]
+pub mod concurrent;
+pub mod convergence;
+pub mod cursor;
+pub mod decorations;
+pub mod destructive;
 pub mod encoding;
 mod error;
 mod gc;
+pub mod history;
 mod input;
+pub mod inspector;
+pub mod invariant;
 pub mod iter;
+pub mod lsp;
 mod moving;
+pub mod multi_doc;
 pub mod observer;
 mod out;
+pub mod pending;
+pub mod persistence;
+#[cfg(feature = "sync")]
+pub mod pool;
+pub mod query;
+pub mod rows;
+#[cfg(feature = "signals")]
+pub mod signals;
 mod slice;
 mod state_vector;
 pub mod sync;
@@ -603,6 +633,7 @@ mod test_utils;
 #[cfg(test)]
 mod tests;
 pub mod undo;
+pub mod visit;
 
 pub use crate::alt::{
     diff_updates_v1, diff_updates_v2, encode_state_vector_from_update_v1,
@@ -614,11 +645,17 @@ pub use crate::branch::BranchID;
 pub use crate::branch::Hook;
 pub use crate::branch::Nested;
 pub use crate::branch::Root;
+pub use crate::branch::TypeRepair;
+pub use crate::doc::ConflictOrder;
 pub use crate::doc::Doc;
+pub use crate::doc::GcPolicy;
 pub use crate::doc::OffsetKind;
 pub use crate::doc::Options;
 pub use crate::doc::Transact;
-pub use crate::event::{SubdocsEvent, SubdocsEventIter, TransactionCleanupEvent, UpdateEvent};
+pub use crate::error::Error;
+pub use crate::event::{
+    NewRoot, RootsEvent, SubdocsEvent, SubdocsEventIter, TransactionCleanupEvent, UpdateEvent,
+};
 pub use crate::id_set::DeleteSet;
 pub use crate::input::In;
 pub use crate::moving::Assoc;
@@ -626,26 +663,55 @@ pub use crate::moving::IndexScope;
 pub use crate::moving::IndexedSequence;
 pub use crate::moving::Offset;
 pub use crate::moving::StickyIndex;
-pub use crate::observer::{Observer, Subscription};
+pub use crate::moving::StickyRange;
+pub use crate::moving::StickyRangeEvent;
+pub use crate::observer::{CallbackError, Observer, Subscription};
 pub use crate::out::Out;
 pub use crate::state_vector::Snapshot;
+pub use crate::state_vector::StateDelta;
 pub use crate::state_vector::StateVector;
+pub use crate::store::BlockRange;
 pub use crate::store::Store;
+pub use crate::store::StoreCheckpoint;
 pub use crate::transaction::Origin;
 pub use crate::transaction::ReadTxn;
 pub use crate::transaction::RootRefs;
 pub use crate::transaction::Transaction;
 pub use crate::transaction::TransactionMut;
 pub use crate::transaction::WriteTxn;
+pub use crate::transaction::MAX_DEFERRED_TRANSACTIONS;
+pub use crate::transaction::STREAM_BATCH_LEN;
 pub use crate::types::array::Array;
+pub use crate::types::array::ArrayCursor;
+pub use crate::types::array::ArrayPage;
 pub use crate::types::array::ArrayPrelim;
 pub use crate::types::array::ArrayRef;
+pub use crate::types::counter::CounterEvent;
+pub use crate::types::counter::CounterPrelim;
+pub use crate::types::counter::CounterRef;
+pub use crate::types::fixed::FixedLayout;
+pub use crate::types::fixed::FixedPrelim;
+pub use crate::types::fixed::FixedRef;
 pub use crate::types::map::Map;
+pub use crate::types::map::MapEntry;
 pub use crate::types::map::MapPrelim;
 pub use crate::types::map::MapRef;
+pub use crate::types::map::OccupiedMapEntry;
+pub use crate::types::map::VacantMapEntry;
+pub use crate::types::set::GSetPrelim;
+pub use crate::types::set::GSetRef;
+pub use crate::types::set::Set;
+pub use crate::types::set::SetEvent;
+pub use crate::types::set::TwoPhaseSetPrelim;
+pub use crate::types::set::TwoPhaseSetRef;
+pub use crate::types::text::FormatSpans;
 pub use crate::types::text::Text;
 pub use crate::types::text::TextPrelim;
 pub use crate::types::text::TextRef;
+pub use crate::types::text::{utf16_to_utf8_index, utf8_to_utf16_index};
+pub use crate::types::throttle::MapThrottle;
+pub use crate::types::typed_map::TypedMap;
+pub use crate::types::typed_map::TypedMapError;
 #[cfg(feature = "weak")]
 pub use crate::types::weak::{Quotable, WeakPrelim, WeakRef};
 pub use crate::types::xml::Xml;
@@ -655,6 +721,7 @@ pub use crate::types::xml::XmlFragment;
 pub use crate::types::xml::XmlFragmentPrelim;
 pub use crate::types::xml::XmlFragmentRef;
 pub use crate::types::xml::XmlOut;
+pub use crate::types::xml::XmlParseError;
 pub use crate::types::xml::XmlTextPrelim;
 pub use crate::types::xml::XmlTextRef;
 pub use crate::types::DeepObservable;
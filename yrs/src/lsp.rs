@@ -0,0 +1,282 @@
+//! Helpers for collaborative code editors speaking the Language Server Protocol.
+//!
+//! LSP addresses text by [Position]s - zero-based lines and UTF-16 code unit offsets within that
+//! line - while [Text] collections use indexes measured in units configured by
+//! [Options::offset_kind]. Functions in this module convert between the two, as well as from/to
+//! [StickyIndex], and translate [TextEvent]s into [TextDocumentContentChangeEvent]s.
+//!
+//! [Options::offset_kind]: crate::Options::offset_kind
+
+use crate::block::ItemContent;
+use crate::branch::{Branch, BranchPtr};
+use crate::types::input_edit::{text_changes, TextCursor};
+use crate::types::text::TextEvent;
+use crate::{Assoc, OffsetKind, ReadTxn, StickyIndex, Text, TransactionMut};
+
+/// Position in a text document expressed as a zero-based line and a zero-based UTF-16 code
+/// unit offset within that line, as defined by the Language Server Protocol.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Position {
+    pub line: u32,
+    pub character: u32,
+}
+
+impl Position {
+    pub fn new(line: u32, character: u32) -> Self {
+        Position { line, character }
+    }
+}
+
+impl TextCursor for Position {
+    fn advance(&mut self, str: &str) {
+        for c in str.chars() {
+            if c == '\n' {
+                self.line += 1;
+                self.character = 0;
+            } else {
+                self.character += c.len_utf16() as u32;
+            }
+        }
+    }
+}
+
+/// A range in a text document, with an exclusive `end` position.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Range {
+    pub start: Position,
+    pub end: Position,
+}
+
+impl Range {
+    pub fn new(start: Position, end: Position) -> Self {
+        Range { start, end }
+    }
+}
+
+/// An incremental change of a text document, as defined by the Language Server Protocol:
+/// text within a given `range` has been replaced with a new `text`. Changes are meant to be
+/// applied in order.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct TextDocumentContentChangeEvent {
+    pub range: Option<Range>,
+    pub text: String,
+}
+
+/// Converts an LSP `position` within a given `text` into an index used by [Text] methods.
+/// As required by LSP, if the `position.character` is greater than the line length, it defaults
+/// back to the line length. Returns `None` if the `position.line` is beyond the end of the text
+/// or the position points into the middle of a surrogate pair.
+pub fn position_to_index<T: ReadTxn, X: Text>(
+    txn: &T,
+    text: &X,
+    position: Position,
+) -> Option<u32> {
+    let kind = txn.store().options.offset_kind;
+    let mut current = Position::default();
+    let mut index = 0;
+    let mut ptr = text.as_ref().start;
+    while let Some(item) = ptr.as_deref() {
+        if !item.is_deleted() && item.is_countable() {
+            if let ItemContent::String(s) = &item.content {
+                for c in s.chars() {
+                    if current.line == position.line {
+                        if current.character == position.character || c == '\n' {
+                            return Some(index);
+                        } else if current.character > position.character {
+                            return None;
+                        }
+                    }
+                    current.advance(c.encode_utf8(&mut [0; 4]));
+                    index += match kind {
+                        OffsetKind::Bytes => c.len_utf8() as u32,
+                        OffsetKind::Utf16 => c.len_utf16() as u32,
+                    };
+                }
+            } else {
+                index += item.content_len(kind);
+            }
+        }
+        ptr = item.right;
+    }
+    if current.line == position.line && current.character <= position.character {
+        Some(index)
+    } else {
+        None
+    }
+}
+
+/// Converts an `index` (as used by [Text] methods) within a given `text` into an LSP position.
+/// Returns `None` if `index` is out of bounds or doesn't fall on a character boundary.
+pub fn index_to_position<T: ReadTxn, X: Text>(txn: &T, text: &X, index: u32) -> Option<Position> {
+    let kind = txn.store().options.offset_kind;
+    let mut current = Position::default();
+    let mut remaining = index;
+    let mut ptr = text.as_ref().start;
+    while let Some(item) = ptr.as_deref() {
+        if remaining == 0 {
+            break;
+        }
+        if !item.is_deleted() && item.is_countable() {
+            let len = item.content_len(kind);
+            if let ItemContent::String(s) = &item.content {
+                if remaining >= len {
+                    current.advance(s.as_str());
+                } else {
+                    for c in s.chars() {
+                        if remaining == 0 {
+                            break;
+                        }
+                        let char_len = match kind {
+                            OffsetKind::Bytes => c.len_utf8() as u32,
+                            OffsetKind::Utf16 => c.len_utf16() as u32,
+                        };
+                        if char_len > remaining {
+                            return None;
+                        }
+                        remaining -= char_len;
+                        current.advance(c.encode_utf8(&mut [0; 4]));
+                    }
+                    return Some(current);
+                }
+            } else if remaining < len {
+                return None;
+            }
+            remaining -= len;
+        }
+        ptr = item.right;
+    }
+    if remaining == 0 {
+        Some(current)
+    } else {
+        None
+    }
+}
+
+/// Returns a [StickyIndex] pointing to an LSP `position` within a given `text`, which will keep
+/// pointing at the same place even in the face of concurrent updates.
+pub fn position_to_sticky_index<T: ReadTxn, X: Text>(
+    txn: &T,
+    text: &X,
+    position: Position,
+    assoc: Assoc,
+) -> Option<StickyIndex> {
+    let index = position_to_index(txn, text, position)?;
+    StickyIndex::at(txn, BranchPtr::from(text.as_ref()), index, assoc)
+}
+
+/// Resolves a given `sticky_index` into an LSP position within a given `text`. Returns `None` if
+/// `sticky_index` doesn't point to a `text`.
+pub fn sticky_index_to_position<T: ReadTxn, X: Text>(
+    txn: &T,
+    text: &X,
+    sticky_index: &StickyIndex,
+) -> Option<Position> {
+    let offset = sticky_index.get_offset(txn)?;
+    if offset.branch != BranchPtr::from(text.as_ref()) {
+        return None;
+    }
+    index_to_position(txn, text, offset.index)
+}
+
+/// Translates changes described by a given [TextEvent] into a list of LSP content change events,
+/// which can be sent as part of `textDocument/didChange` notification.
+pub fn content_changes(
+    txn: &TransactionMut,
+    event: &TextEvent,
+) -> Vec<TextDocumentContentChangeEvent> {
+    let branch: &Branch = event.target().as_ref();
+    text_changes::<Position>(branch, txn)
+        .into_iter()
+        .map(|(start, end, text)| TextDocumentContentChangeEvent {
+            range: Some(Range::new(start, end)),
+            text,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::lsp::{
+        content_changes, index_to_position, position_to_index, position_to_sticky_index,
+        sticky_index_to_position, Position, Range, TextDocumentContentChangeEvent,
+    };
+    use crate::{Assoc, Doc, Observable, OffsetKind, Options, Text, Transact};
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn position_index_conversion() {
+        let doc = Doc::with_options(Options {
+            offset_kind: OffsetKind::Utf16,
+            ..Options::default()
+        });
+        let text = doc.get_or_insert_text("text");
+        let mut txn = doc.transact_mut();
+        text.push(&mut txn, "fn a() {\n");
+        text.push(&mut txn, "  😀 = 1;\n}");
+
+        let cases = [
+            (Position::new(0, 0), 0),
+            (Position::new(0, 8), 8),
+            (Position::new(1, 0), 9),
+            (Position::new(1, 2), 11),
+            (Position::new(1, 4), 13),
+            (Position::new(2, 1), 20),
+        ];
+        for (pos, index) in cases {
+            assert_eq!(position_to_index(&txn, &text, pos), Some(index));
+            assert_eq!(index_to_position(&txn, &text, index), Some(pos));
+        }
+        // character beyond line length is clamped
+        assert_eq!(
+            position_to_index(&txn, &text, Position::new(0, 100)),
+            Some(8)
+        );
+        // inside of surrogate pair
+        assert_eq!(position_to_index(&txn, &text, Position::new(1, 3)), None);
+        assert_eq!(index_to_position(&txn, &text, 12), None);
+        // out of bounds
+        assert_eq!(position_to_index(&txn, &text, Position::new(3, 0)), None);
+        assert_eq!(index_to_position(&txn, &text, 21), None);
+
+        let sticky =
+            position_to_sticky_index(&txn, &text, Position::new(1, 2), Assoc::After).unwrap();
+        text.insert(&mut txn, 0, "// comment\n");
+        assert_eq!(
+            sticky_index_to_position(&txn, &text, &sticky),
+            Some(Position::new(2, 2))
+        );
+    }
+
+    #[test]
+    fn text_event_content_changes() {
+        let doc = Doc::with_client_id(1);
+        let text = doc.get_or_insert_text("text");
+        text.push(&mut doc.transact_mut(), "let a = 1;\nlet b = 2;");
+
+        let changes = Arc::new(Mutex::new(Vec::new()));
+        let _sub = {
+            let changes = changes.clone();
+            text.observe(move |txn, e| changes.lock().unwrap().extend(content_changes(txn, e)))
+        };
+
+        {
+            let mut txn = doc.transact_mut();
+            text.remove_range(&mut txn, 4, 1); // a
+            text.insert(&mut txn, 4, "x");
+            text.insert(&mut txn, 21, "\nlet c = 3;");
+        }
+        assert_eq!(
+            changes.lock().unwrap().as_slice(),
+            &[
+                TextDocumentContentChangeEvent {
+                    range: Some(Range::new(Position::new(0, 4), Position::new(0, 5))),
+                    text: "x".into(),
+                },
+                TextDocumentContentChangeEvent {
+                    range: Some(Range::new(Position::new(1, 10), Position::new(1, 10))),
+                    text: "\nlet c = 3;".into(),
+                },
+            ]
+        );
+    }
+}
@@ -5,12 +5,14 @@ use crate::encoding::read::Error;
 use crate::transaction::TransactionMut;
 use crate::updates::decoder::{Decode, Decoder};
 use crate::updates::encoder::{Encode, Encoder};
-use crate::{BranchID, ReadTxn, WriteTxn, ID};
+use crate::{BranchID, Doc, ReadTxn, Subscription, Transact, WriteTxn, ID};
+use atomic_refcell::BorrowMutError;
 use serde::de::Visitor;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::collections::HashSet;
 use std::fmt::Formatter;
-use std::sync::Arc;
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct Move {
@@ -99,14 +101,20 @@ impl Move {
     }
 
     fn get_item_ptr<T: ReadTxn>(txn: &T, id: &ID, assoc: Assoc) -> Option<ItemPtr> {
+        // Blocks are split at the boundaries of a moved range when a move is integrated, but they
+        // can be merged back later on, ie. once the range has been deleted or the move turned out
+        // to be a no-op. In that case none of the elements of a block are moved by current move,
+        // so the block itself can be used as a boundary without splitting it.
         if assoc == Assoc::After {
             let slice = txn.store().blocks.get_item_clean_start(id)?;
-            debug_assert!(slice.adjacent()); //TODO: remove once confirmed that slice always fits block range
             Some(slice.ptr)
         } else {
             let slice = txn.store().blocks.get_item_clean_end(id)?;
-            debug_assert!(slice.adjacent()); //TODO: remove once confirmed that slice always fits block range
-            slice.ptr.right
+            if slice.adjacent() {
+                slice.ptr.right
+            } else {
+                Some(slice.ptr)
+            }
         }
     }
 
@@ -637,6 +645,146 @@ impl std::fmt::Display for StickyIndex {
     }
 }
 
+/// A range of elements inside of a shared sequence, which boundaries are defined by a pair of
+/// [StickyIndex]es. Like [StickyIndex] itself, it keeps track of the same elements even when other
+/// changes are applied before, after or inside of it, which makes it useful i.e. for comment anchors
+/// or user selections.
+///
+/// Range created via [StickyRange::at] associates its start with the first element inside of the
+/// range and its end with the last one, so that elements inserted directly at its boundaries are not
+/// included.
+///
+/// # Example
+///
+/// ```rust
+/// use yrs::{Doc, IndexedSequence, Text, Transact};
+///
+/// let doc = Doc::new();
+/// let txt = doc.get_or_insert_text("text");
+/// let mut txn = doc.transact_mut();
+/// txt.insert(&mut txn, 0, "hello world");
+///
+/// let range = txt.sticky_range(&mut txn, 6..11).unwrap(); // => 'hello [world]'
+///
+/// txt.insert(&mut txn, 0, "oh, "); // => 'oh, hello [world]'
+/// txt.push(&mut txn, "!"); // => 'oh, hello [world]!'
+/// assert_eq!(range.get_offsets(&txn), Some(10..15));
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct StickyRange {
+    /// Sticky index marking the beginning of a range (inclusive).
+    pub start: StickyIndex,
+    /// Sticky index marking the end of a range (exclusive).
+    pub end: StickyIndex,
+}
+
+impl StickyRange {
+    pub fn new(start: StickyIndex, end: StickyIndex) -> Self {
+        StickyRange { start, end }
+    }
+
+    /// Creates a new [StickyRange] spanning over a given `range` of human-readable indexes inside
+    /// of a `branch`. Returns `None` if range exceeds the length of a collection.
+    pub fn at<T: ReadTxn>(txn: &T, branch: BranchPtr, range: Range<u32>) -> Option<Self> {
+        let end = range.end.max(range.start);
+        if end > branch.content_len {
+            return None;
+        }
+        let start = StickyIndex::at(txn, branch, range.start, Assoc::After)
+            .or_else(|| StickyIndex::at(txn, branch, range.start, Assoc::Before))?;
+        let end = StickyIndex::at(txn, branch, end, Assoc::Before)?;
+        Some(Self::new(start, end))
+    }
+
+    /// Maps current [StickyRange] onto a range of human-readable indexes inside of its collection,
+    /// valid at the current point in time. If all elements of a range have been removed, a returned
+    /// range is empty.
+    ///
+    /// Returns `None` if collection containing this range doesn't exist in a document.
+    pub fn get_offsets<T: ReadTxn>(&self, txn: &T) -> Option<Range<u32>> {
+        let start = self.start.get_offset(txn)?.index;
+        let end = self.end.get_offset(txn)?.index;
+        Some(start..end.max(start))
+    }
+
+    /// Subscribes a callback `f` to be called after every committed transaction of a given `doc`,
+    /// which has changed the offsets of this range, as returned by [StickyRange::get_offsets].
+    #[cfg(feature = "sync")]
+    pub fn observe<F>(&self, doc: &Doc, f: F) -> Result<Subscription, BorrowMutError>
+    where
+        F: Fn(&TransactionMut, &StickyRangeEvent) + Send + Sync + 'static,
+    {
+        let range = self.clone();
+        let last = Mutex::new(self.get_offsets(&doc.transact()));
+        doc.observe_after_transaction(move |txn| {
+            if let Some(e) = range.update_offsets(txn, &last) {
+                f(txn, &e)
+            }
+        })
+    }
+
+    /// Subscribes a callback `f` to be called after every committed transaction of a given `doc`,
+    /// which has changed the offsets of this range, as returned by [StickyRange::get_offsets].
+    #[cfg(not(feature = "sync"))]
+    pub fn observe<F>(&self, doc: &Doc, f: F) -> Result<Subscription, BorrowMutError>
+    where
+        F: Fn(&TransactionMut, &StickyRangeEvent) + 'static,
+    {
+        let range = self.clone();
+        let last = Mutex::new(self.get_offsets(&doc.transact()));
+        doc.observe_after_transaction(move |txn| {
+            if let Some(e) = range.update_offsets(txn, &last) {
+                f(txn, &e)
+            }
+        })
+    }
+
+    fn update_offsets(
+        &self,
+        txn: &TransactionMut,
+        last: &Mutex<Option<Range<u32>>>,
+    ) -> Option<StickyRangeEvent> {
+        let current = self.get_offsets(txn);
+        let mut last = last.lock().unwrap();
+        if *last == current {
+            None
+        } else {
+            let old = std::mem::replace(&mut *last, current.clone());
+            Some(StickyRangeEvent { old, new: current })
+        }
+    }
+}
+
+impl Encode for StickyRange {
+    fn encode<E: Encoder>(&self, encoder: &mut E) {
+        self.start.encode(encoder);
+        self.end.encode(encoder);
+    }
+}
+
+impl Decode for StickyRange {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, Error> {
+        let start = StickyIndex::decode(decoder)?;
+        let end = StickyIndex::decode(decoder)?;
+        Ok(Self::new(start, end))
+    }
+}
+
+impl std::fmt::Display for StickyRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}..{}", self.start, self.end)
+    }
+}
+
+/// Event passed to callbacks subscribed via [StickyRange::observe].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StickyRangeEvent {
+    /// Offsets of the observed range before the transaction.
+    pub old: Option<Range<u32>>,
+    /// Offsets of the observed range after the transaction.
+    pub new: Option<Range<u32>>,
+}
+
 /// Struct describing context in which [StickyIndex] is placed. For items pointing inside of
 /// the shared typed sequence it's always [StickyIndex::Relative] which refers to a block [ID]
 /// found under corresponding position.
@@ -817,6 +965,12 @@ pub trait IndexedSequence: AsRef<Branch> {
     ) -> Option<StickyIndex> {
         StickyIndex::at(txn, BranchPtr::from(self.as_ref()), index, assoc)
     }
+
+    /// Returns a [StickyRange] equivalent to a human-readable `range` of indexes.
+    /// Returns `None` if `range` exceeds the length of current sequence.
+    fn sticky_range(&self, txn: &mut TransactionMut, range: Range<u32>) -> Option<StickyRange> {
+        StickyRange::at(txn, BranchPtr::from(self.as_ref()), range)
+    }
 }
 
 /// [Offset] is a result of mapping of [StickyIndex] onto document store at a current
@@ -844,9 +998,11 @@ impl Offset {
 #[cfg(test)]
 mod test {
     use crate::moving::Assoc;
+    use crate::moving::StickyRangeEvent;
     use crate::updates::decoder::Decode;
     use crate::updates::encoder::Encode;
-    use crate::{Doc, IndexedSequence, StickyIndex, Text, TextRef, Transact};
+    use crate::{Doc, IndexedSequence, StickyIndex, StickyRange, Text, TextRef, Transact};
+    use std::sync::{Arc, Mutex};
 
     fn check_sticky_indexes(doc: &Doc, text: &TextRef) {
         // test if all positions are encoded and restored correctly
@@ -958,4 +1114,65 @@ mod test {
         assert_eq!(pos_right.index, 2);
         assert_eq!(pos_left.index, 1);
     }
+
+    #[test]
+    fn sticky_range_offsets() {
+        let doc = Doc::with_client_id(1);
+        let txt = doc.get_or_insert_text("test");
+        let mut txn = doc.transact_mut();
+        txt.insert(&mut txn, 0, "abcdef");
+
+        let range = txt.sticky_range(&mut txn, 2..4).unwrap(); // ab[cd]ef
+        let encoded = range.encode_v1();
+        let range = StickyRange::decode_v1(&encoded).unwrap();
+        assert_eq!(range.get_offsets(&txn), Some(2..4));
+
+        // inserts at the boundaries are not included
+        txt.insert(&mut txn, 2, "x");
+        txt.insert(&mut txn, 5, "y");
+        assert_eq!(range.get_offsets(&txn), Some(3..5)); // abx[cd]yef
+
+        txt.insert(&mut txn, 4, "z");
+        assert_eq!(range.get_offsets(&txn), Some(3..6)); // abx[czd]yef
+
+        txt.remove_range(&mut txn, 2, 5);
+        assert_eq!(range.get_offsets(&txn), Some(2..2)); // ab[]ef
+
+        let empty = txt.sticky_range(&mut txn, 4..4).unwrap();
+        assert_eq!(empty.get_offsets(&txn), Some(4..4));
+        assert!(txt.sticky_range(&mut txn, 2..5).is_none());
+    }
+
+    #[test]
+    fn sticky_range_observe() {
+        let doc = Doc::with_client_id(1);
+        let txt = doc.get_or_insert_text("test");
+        txt.insert(&mut doc.transact_mut(), 0, "hello world");
+        let range = txt.sticky_range(&mut doc.transact_mut(), 0..5).unwrap();
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let events_c = events.clone();
+        let _sub = range
+            .observe(&doc, move |_, e| events_c.lock().unwrap().push(e.clone()))
+            .unwrap();
+
+        txt.push(&mut doc.transact_mut(), "!");
+        assert!(events.lock().unwrap().is_empty());
+
+        txt.insert(&mut doc.transact_mut(), 0, ">> ");
+        txt.remove_range(&mut doc.transact_mut(), 4, 2);
+        assert_eq!(
+            events.lock().unwrap().as_slice(),
+            &[
+                StickyRangeEvent {
+                    old: Some(0..5),
+                    new: Some(3..8)
+                },
+                StickyRangeEvent {
+                    old: Some(3..8),
+                    new: Some(3..6)
+                }
+            ]
+        );
+    }
 }
@@ -0,0 +1,441 @@
+//! Transactions spanning over multiple documents.
+//!
+//! Changes made to a parent document and its sub-documents are normally committed and sent
+//! independently from each other, which means that a remote peer may observe a state in which only
+//! some of these changes have been applied. [MultiDocTransaction] acquires read-write transactions
+//! over a set of documents at once, commits them together and packages their updates into a single
+//! [MultiDocUpdate], which can be sent over the wire and applied by a remote peer as one unit.
+//!
+//! # Example
+//!
+//! ```rust
+//! use yrs::multi_doc::{with_subdocs, MultiDocTransaction, MultiDocUpdate};
+//! use yrs::updates::decoder::Decode;
+//! use yrs::updates::encoder::Encode;
+//! use yrs::{Doc, Map, Text, Transact, WriteTxn};
+//!
+//! let doc = Doc::new();
+//! let root = doc.get_or_insert_map("root");
+//! let subdoc = root.insert(&mut doc.transact_mut(), "subdoc", Doc::new());
+//!
+//! let docs = with_subdocs(&doc);
+//! let update = {
+//!     let mut txn = MultiDocTransaction::new(&docs).unwrap();
+//!     root.insert(txn.get_mut(doc.guid()).unwrap(), "title", "hello");
+//!     let sub_txn = txn.get_mut(subdoc.guid()).unwrap();
+//!     sub_txn.get_or_insert_text("text").push(sub_txn, "world");
+//!     txn.commit()
+//! };
+//!
+//! // updates of both documents are sent as a single payload
+//! let payload = update.encode_v1();
+//! let update = MultiDocUpdate::decode_v1(&payload).unwrap();
+//! assert_eq!(update.updates.len(), 2);
+//! ```
+
+use crate::doc::DocAddr;
+use crate::encoding::read::Error as ReadError;
+use crate::updates::decoder::{Decode, Decoder};
+use crate::updates::encoder::{Encode, Encoder};
+use crate::{Doc, Error, Origin, ReadTxn, StateVector, Transact, TransactionMut, Update, Uuid};
+use std::collections::HashMap;
+
+/// A set of read-write transactions acquired over multiple documents at once.
+///
+/// Transactions are always acquired in a deterministic order - sorted by document guids - so that
+/// competing coordinators never end up holding partially overlapping sets of documents. If any of
+/// the documents is already borrowed, none of the transactions is acquired. Since transactions are
+/// identified by document guids, all documents must have distinct guids.
+///
+/// Once dropped without calling [MultiDocTransaction::commit], all transactions are committed
+/// separately, just like individual [TransactionMut]s.
+pub struct MultiDocTransaction<'doc> {
+    txns: Vec<TransactionMut<'doc>>,
+}
+
+impl<'doc> MultiDocTransaction<'doc> {
+    /// Acquires read-write transactions over all given `docs`. Duplicated documents are ignored.
+    pub fn new<I>(docs: I) -> Result<Self, Error>
+    where
+        I: IntoIterator<Item = &'doc Doc>,
+    {
+        Self::acquire(docs, None)
+    }
+
+    /// Acquires read-write transactions over all given `docs`, marking all of them with
+    /// a given `origin`. Duplicated documents are ignored.
+    pub fn with_origin<I, O>(docs: I, origin: O) -> Result<Self, Error>
+    where
+        I: IntoIterator<Item = &'doc Doc>,
+        O: Into<Origin>,
+    {
+        Self::acquire(docs, Some(origin.into()))
+    }
+
+    fn acquire<I>(docs: I, origin: Option<Origin>) -> Result<Self, Error>
+    where
+        I: IntoIterator<Item = &'doc Doc>,
+    {
+        let mut docs: Vec<&'doc Doc> = docs.into_iter().collect();
+        docs.sort_by(|a, b| (a.guid(), a.addr()).cmp(&(b.guid(), b.addr())));
+        docs.dedup_by_key(|doc| DocAddr::new(doc));
+        if let Some(w) = docs.windows(2).find(|w| w[0].guid() == w[1].guid()) {
+            return Err(duplicate_guid(w[0].guid()));
+        }
+        let mut txns = Vec::with_capacity(docs.len());
+        for doc in docs {
+            // on failure, transactions acquired so far are released when dropped
+            let txn = match &origin {
+                None => doc.try_transact_mut()?,
+                Some(origin) => doc.try_transact_mut_with(origin.clone())?,
+            };
+            txns.push(txn);
+        }
+        Ok(MultiDocTransaction { txns })
+    }
+
+    /// Returns a number of documents participating in this transaction.
+    pub fn len(&self) -> usize {
+        self.txns.len()
+    }
+
+    /// Checks if this transaction doesn't span over any documents.
+    pub fn is_empty(&self) -> bool {
+        self.txns.is_empty()
+    }
+
+    /// Returns a transaction acquired over a document with a given `guid`.
+    pub fn get(&self, guid: &str) -> Option<&TransactionMut<'doc>> {
+        self.txns
+            .iter()
+            .find(|txn| txn.doc().guid().as_ref() == guid)
+    }
+
+    /// Returns a transaction acquired over a document with a given `guid`.
+    pub fn get_mut(&mut self, guid: &str) -> Option<&mut TransactionMut<'doc>> {
+        self.txns
+            .iter_mut()
+            .find(|txn| txn.doc().guid().as_ref() == guid)
+    }
+
+    /// Returns an iterator over all transactions, ordered by their document guids.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut TransactionMut<'doc>> {
+        self.txns.iter_mut()
+    }
+
+    /// Commits all transactions and returns an update containing changes made to all documents.
+    ///
+    /// Transactions are committed in order, but none of the documents is released before all of
+    /// them have been committed. For that reason, observers of one of the participating documents
+    /// cannot open transactions over the others.
+    pub fn commit(mut self) -> MultiDocUpdate {
+        for txn in self.txns.iter_mut() {
+            txn.commit();
+        }
+        let updates = self
+            .txns
+            .iter()
+            .filter(|txn| txn.before_state() != txn.after_state() || !txn.delete_set().is_empty())
+            .map(|txn| (txn.doc().guid().clone(), txn.encode_update_v1()))
+            .collect();
+        MultiDocUpdate { updates }
+    }
+}
+
+impl<'doc> std::fmt::Debug for MultiDocTransaction<'doc> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list()
+            .entries(self.txns.iter().map(|txn| txn.doc().guid()))
+            .finish()
+    }
+}
+
+/// Returns a given `doc` together with all of its sub-documents, including the nested ones.
+/// Sub-documents of documents currently borrowed by a read-write transaction are not included.
+pub fn with_subdocs(doc: &Doc) -> Vec<Doc> {
+    let mut result = vec![doc.clone()];
+    let mut i = 0;
+    while i < result.len() {
+        let subdocs: Vec<Doc> = match result[i].try_transact() {
+            Ok(txn) => txn.subdocs().cloned().collect(),
+            Err(_) => Vec::new(),
+        };
+        result.extend(subdocs);
+        i += 1;
+    }
+    result
+}
+
+/// Updates of multiple documents, produced by [MultiDocTransaction::commit], which are meant to be
+/// sent and applied together. Each update is encoded using lib0 v1 encoding.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct MultiDocUpdate {
+    /// Updates ordered by the guids of documents they should be applied to.
+    pub updates: Vec<(Uuid, Vec<u8>)>,
+}
+
+impl MultiDocUpdate {
+    /// Checks if this update doesn't contain any changes.
+    pub fn is_empty(&self) -> bool {
+        self.updates.is_empty()
+    }
+
+    /// Applies all updates to the corresponding documents from a given set of `docs`.
+    ///
+    /// All updates are decoded, all documents are borrowed and every update is checked to depend
+    /// only on the blocks already known to its document before any of the updates is applied, so
+    /// that either all of the updates are integrated or none of them. Updates, which would have
+    /// to be stashed as pending, are rejected with [Error::Integration].
+    pub fn apply<'doc, I>(&self, docs: I) -> Result<(), Error>
+    where
+        I: IntoIterator<Item = &'doc Doc>,
+    {
+        self.apply_internal(docs, None)
+    }
+
+    /// Applies all updates to the corresponding documents from a given set of `docs`, using
+    /// transactions marked with a given `origin`. See: [MultiDocUpdate::apply].
+    pub fn apply_with<'doc, I, O>(&self, docs: I, origin: O) -> Result<(), Error>
+    where
+        I: IntoIterator<Item = &'doc Doc>,
+        O: Into<Origin>,
+    {
+        self.apply_internal(docs, Some(origin.into()))
+    }
+
+    fn apply_internal<'doc, I>(&self, docs: I, origin: Option<Origin>) -> Result<(), Error>
+    where
+        I: IntoIterator<Item = &'doc Doc>,
+    {
+        let mut by_guid: HashMap<&str, &'doc Doc> = HashMap::new();
+        for doc in docs {
+            if let Some(other) = by_guid.insert(doc.guid().as_ref(), doc) {
+                if other.addr() != doc.addr() {
+                    return Err(duplicate_guid(doc.guid()));
+                }
+            }
+        }
+        let mut targets = Vec::with_capacity(self.updates.len());
+        let mut updates = Vec::with_capacity(self.updates.len());
+        for (guid, data) in self.updates.iter() {
+            match by_guid.get(guid.as_ref()) {
+                Some(doc) => targets.push(*doc),
+                None => {
+                    return Err(Error::Validation(format!(
+                        "document '{}' is not present",
+                        guid
+                    )))
+                }
+            }
+            updates.push((guid, Update::decode_v1(data)?));
+        }
+        let mut txn = MultiDocTransaction::acquire(targets, origin)?;
+        for (guid, update) in updates.iter() {
+            if let Some(txn) = txn.get(guid) {
+                check_integrable(update, &txn.state_vector())?;
+            }
+        }
+        for (guid, update) in updates {
+            if let Some(txn) = txn.get_mut(guid) {
+                txn.apply_update(update);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn duplicate_guid(guid: &Uuid) -> Error {
+    Error::Validation(format!("multiple documents share the same guid '{}'", guid))
+}
+
+/// Checks if all blocks and deletions of an `update` can be integrated into a document with
+/// a given `local` state, without waiting for any missing blocks.
+fn check_integrable(update: &Update, local: &StateVector) -> Result<(), Error> {
+    if let Some((client, start, end)) = update.blocks.missing_ranges(local).first() {
+        return Err(Error::Integration(format!(
+            "update depends on missing blocks of client {} in range {}..{}",
+            client, start, end
+        )));
+    }
+    let update_state = update.state_vector();
+    for (client, range) in update.delete_set.iter() {
+        let known = local.get(client).max(update_state.get(client));
+        if range.iter().any(|r| r.end > known) {
+            return Err(Error::Integration(format!(
+                "update deletes blocks of client {} which are not known to a document",
+                client
+            )));
+        }
+    }
+    Ok(())
+}
+
+impl Encode for MultiDocUpdate {
+    fn encode<E: Encoder>(&self, encoder: &mut E) {
+        encoder.write_var(self.updates.len());
+        for (guid, update) in self.updates.iter() {
+            encoder.write_string(guid);
+            encoder.write_buf(update);
+        }
+    }
+}
+
+impl Decode for MultiDocUpdate {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, ReadError> {
+        let len: usize = decoder.read_var()?;
+        let mut updates = Vec::new();
+        for _ in 0..len {
+            let guid: Uuid = decoder.read_string()?.into();
+            let update = decoder.read_buf()?.to_vec();
+            updates.push((guid, update));
+        }
+        Ok(MultiDocUpdate { updates })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::doc::TransactionAcqError;
+    use crate::multi_doc::{with_subdocs, MultiDocTransaction, MultiDocUpdate};
+    use crate::test_utils::exchange_updates;
+    use crate::updates::decoder::Decode;
+    use crate::updates::encoder::Encode;
+    use crate::{Doc, Error, GetString, Map, Options, ReadTxn, Text, Transact, WriteTxn};
+
+    #[test]
+    fn commit_and_apply_together() {
+        let parent = Doc::with_options(Options::with_guid_and_client_id("parent".into(), 1));
+        let root = parent.get_or_insert_map("root");
+        root.insert(
+            &mut parent.transact_mut(),
+            "sub",
+            Doc::with_options(Options::with_guid_and_client_id("sub".into(), 1)),
+        );
+        let untouched = Doc::with_options(Options::with_guid_and_client_id("untouched".into(), 1));
+
+        let remote = Doc::with_options(Options::with_guid_and_client_id("parent".into(), 2));
+        exchange_updates(&[&parent, &remote]);
+        let remote_sub: Doc = remote
+            .get_or_insert_map("root")
+            .get(&remote.transact(), "sub")
+            .unwrap()
+            .cast()
+            .unwrap();
+
+        let mut docs = with_subdocs(&parent);
+        assert_eq!(docs.len(), 2);
+        docs.push(untouched.clone());
+        let update = {
+            let mut txn = MultiDocTransaction::new(&docs).unwrap();
+            assert_eq!(txn.len(), 3);
+            let sub_txn = txn.get_mut("sub").unwrap();
+            let text = sub_txn.get_or_insert_text("text");
+            text.push(sub_txn, "hello");
+            root.insert(txn.get_mut("parent").unwrap(), "title", "world");
+            txn.commit()
+        };
+        // only documents which have changed are included
+        let guids: Vec<_> = update.updates.iter().map(|(g, _)| g.as_ref()).collect();
+        assert_eq!(guids, vec!["parent", "sub"]);
+
+        let update = MultiDocUpdate::decode_v1(&update.encode_v1()).unwrap();
+
+        // all target documents must be present
+        assert!(update.apply(std::slice::from_ref(&remote)).is_err());
+        assert_eq!(remote.get_or_insert_map("root").len(&remote.transact()), 1);
+
+        update.apply(&[remote.clone(), remote_sub.clone()]).unwrap();
+        let txt = remote_sub.get_or_insert_text("text");
+        assert_eq!(txt.get_string(&remote_sub.transact()), "hello");
+        let title = remote
+            .get_or_insert_map("root")
+            .get(&remote.transact(), "title");
+        assert_eq!(title, Some("world".into()));
+    }
+
+    #[test]
+    fn acquire_all_or_nothing() {
+        let a = Doc::with_options(Options::with_guid_and_client_id("a".into(), 1));
+        let b = Doc::with_options(Options::with_guid_and_client_id("b".into(), 1));
+        let c = Doc::with_options(Options::with_guid_and_client_id("c".into(), 1));
+        let docs = [c.clone(), a.clone(), b.clone(), a.clone()];
+        {
+            let _read = b.transact();
+            let result = MultiDocTransaction::new(&docs);
+            assert!(matches!(
+                result,
+                Err(Error::Borrow(TransactionAcqError::ExclusiveAcqFailed))
+            ));
+            // documents acquired before failure have been released
+            assert!(a.try_transact_mut().is_ok());
+        }
+        let mut txn = MultiDocTransaction::new(&docs).unwrap();
+        let guids: Vec<_> = txn
+            .iter_mut()
+            .map(|txn| txn.doc().guid().to_string())
+            .collect();
+        assert_eq!(guids, vec!["a", "b", "c"]);
+        assert!(txn.commit().is_empty());
+    }
+
+    #[test]
+    fn duplicated_guids() {
+        let a1 = Doc::with_options(Options::with_guid_and_client_id("a".into(), 1));
+        let a2 = Doc::with_options(Options::with_guid_and_client_id("a".into(), 2));
+        assert!(matches!(
+            MultiDocTransaction::new(&[a1.clone(), a2.clone()]),
+            Err(Error::Validation(_))
+        ));
+
+        let update = {
+            let docs = [a1.clone()];
+            let mut txn = MultiDocTransaction::new(&docs).unwrap();
+            let a_txn = txn.get_mut("a").unwrap();
+            a_txn.get_or_insert_text("text").push(a_txn, "hello");
+            txn.commit()
+        };
+        assert!(matches!(
+            update.apply(&[a1.clone(), a2.clone()]),
+            Err(Error::Validation(_))
+        ));
+    }
+
+    #[test]
+    fn apply_all_or_nothing() {
+        let a = Doc::with_options(Options::with_guid_and_client_id("a".into(), 1));
+        let b = Doc::with_options(Options::with_guid_and_client_id("b".into(), 1));
+        let text_a = a.get_or_insert_text("text");
+        let text_b = b.get_or_insert_text("text");
+        text_b.push(&mut b.transact_mut(), "hello");
+        // replica of "b", which knows only about "hello"
+        let old_b = Doc::with_options(Options::with_guid_and_client_id("b".into(), 3));
+        exchange_updates(&[&b, &old_b]);
+        let docs = [a.clone(), b.clone()];
+        let update = {
+            let mut txn = MultiDocTransaction::new(&docs).unwrap();
+            text_a.push(txn.get_mut("a").unwrap(), "foo");
+            text_b.push(txn.get_mut("b").unwrap(), " world");
+            txn.commit()
+        };
+
+        let remote_a = Doc::with_options(Options::with_guid_and_client_id("a".into(), 2));
+        let remote_b = Doc::with_options(Options::with_guid_and_client_id("b".into(), 2));
+        let remote_text_a = remote_a.get_or_insert_text("text");
+        let remote_text_b = remote_b.get_or_insert_text("text");
+
+        // update of "b" depends on "hello", which remote peer doesn't know yet
+        let result = update.apply(&[remote_a.clone(), remote_b.clone()]);
+        assert!(matches!(result, Err(Error::Integration(_))));
+        assert_eq!(remote_text_a.get_string(&remote_a.transact()), "");
+        assert_eq!(remote_text_b.get_string(&remote_b.transact()), "");
+        assert!(remote_b.transact().store().pending_update().is_none());
+
+        exchange_updates(&[&old_b, &remote_b]);
+        update.apply(&[remote_a.clone(), remote_b.clone()]).unwrap();
+        assert_eq!(remote_text_a.get_string(&remote_a.transact()), "foo");
+        assert_eq!(
+            remote_text_b.get_string(&remote_b.transact()),
+            "hello world"
+        );
+    }
+}
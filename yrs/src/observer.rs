@@ -1,9 +1,49 @@
+use std::any::Any;
+use std::panic::{catch_unwind, AssertUnwindSafe};
 use std::sync::{Arc, Weak};
 
 use arc_swap::{ArcSwapOption, AsRaw, Guard};
+use thiserror::Error;
 
 use crate::Origin;
 
+/// Panic raised by an observer callback, which has been caught while committing a transaction.
+/// Panics are caught only when [Options::isolate_observers] is enabled. Caught panics are
+/// reported to [Doc::observe_callback_errors] subscribers once a transaction is committed.
+///
+/// [Options::isolate_observers]: crate::Options::isolate_observers
+/// [Doc::observe_callback_errors]: crate::Doc::observe_callback_errors
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("observer callback panicked: {message}")]
+pub struct CallbackError {
+    /// Message of a caught panic.
+    pub message: Arc<str>,
+}
+
+impl CallbackError {
+    fn from_panic(payload: Box<dyn Any + Send>) -> Self {
+        let message = if let Some(msg) = payload.downcast_ref::<&str>() {
+            Arc::from(*msg)
+        } else if let Some(msg) = payload.downcast_ref::<String>() {
+            Arc::from(msg.as_str())
+        } else {
+            Arc::from("non-string panic payload")
+        };
+        CallbackError { message }
+    }
+}
+
+/// Calls a given observer callback `f`. If `isolate` is true, a panic raised by the callback is
+/// caught and returned as an error instead of being propagated.
+pub(crate) fn call_isolated<F: FnOnce()>(isolate: bool, f: F) -> Result<(), CallbackError> {
+    if isolate {
+        catch_unwind(AssertUnwindSafe(f)).map_err(CallbackError::from_panic)
+    } else {
+        f();
+        Ok(())
+    }
+}
+
 /// Data structure used to handle publish/subscribe callbacks of specific type. Observers perform
 /// subscriber changes in thread-safe manner, using atomic hardware intrinsics.
 pub struct Observer<F> {
@@ -91,9 +131,9 @@ where
         }
     }
 
-    /// Subscribes a callback parameter to a current [Observer].
-    /// Returns a subscription object which - when dropped - will unsubscribe current callback.
-    /// If the `id` was already present in the observer, current callback will be ignored.
+    /// Subscribes a callback parameter to a current [Observer] under a given `id`, which can be
+    /// used to unsubscribe it with [Observer::unsubscribe]. If the `id` was already present in
+    /// the observer, previously subscribed callback will be replaced by the current one.
     pub fn subscribe_with(&self, id: Origin, callback: F) {
         let inner = self.inner();
         let mut node = Arc::new(Node::new(id.clone(), callback));
@@ -117,6 +157,23 @@ where
         // remove all previous nodes that share the same ID
         Self::remove(head.clone(), &id);
     }
+
+    /// Subscribes a callback parameter to a current [Observer] under a newly generated key, which
+    /// is returned. Unlike [Observer::subscribe], the callback stays subscribed until it's
+    /// explicitly removed with [Observer::unsubscribe] called with returned key. This is useful
+    /// when subscription lifetime cannot be managed by keeping a [Subscription] alive, i.e.
+    /// across FFI boundaries.
+    pub fn subscribe_forever(&self, callback: F) -> Origin {
+        let id = new_key();
+        self.subscribe_with(id.clone(), callback);
+        id
+    }
+}
+
+/// Generates a new random key used to identify subscribed callbacks.
+pub(crate) fn new_key() -> Origin {
+    let mut rng = fastrand::Rng::new();
+    Origin::from(rng.usize(0..usize::MAX))
 }
 
 #[cfg(feature = "sync")]
@@ -125,9 +182,7 @@ where
     F: Send + Sync + 'static,
 {
     pub fn subscribe(&self, callback: F) -> Subscription {
-        let mut rng = fastrand::Rng::new();
-        let id = rng.usize(0..usize::MAX);
-        let origin = Origin::from(id);
+        let origin = new_key();
         self.subscribe_with(origin.clone(), callback);
         Arc::new(Cancel {
             id: origin,
@@ -142,9 +197,7 @@ where
     F: 'static,
 {
     pub fn subscribe(&self, callback: F) -> Subscription {
-        let mut rng = fastrand::Rng::new();
-        let id = rng.usize(0..usize::MAX);
-        let origin = Origin::from(id);
+        let origin = new_key();
         self.subscribe_with(origin.clone(), callback);
         Arc::new(Cancel {
             id: origin,
@@ -285,6 +338,8 @@ mod test {
     use std::sync::Arc;
 
     use crate::observer::Observer;
+    use crate::types::{DeepObservable, Observable};
+    use crate::{Doc, Map, Transact};
 
     #[test]
     fn subscription() {
@@ -379,6 +434,39 @@ mod test {
         assert_eq!(rx.try_recv().unwrap(), "b-2");
     }
 
+    #[test]
+    fn subscribe_forever() {
+        let doc = Doc::with_client_id(1);
+        let map = doc.get_or_insert_map("map");
+        let calls = Arc::new(AtomicU32::new(0));
+        let (shallow, deep) = {
+            let a = calls.clone();
+            let b = calls.clone();
+            (
+                map.observe_forever(move |_, _| {
+                    a.fetch_add(1, Ordering::SeqCst);
+                }),
+                map.observe_deep_forever(move |_, _| {
+                    b.fetch_add(10, Ordering::SeqCst);
+                }),
+            )
+        };
+        assert_ne!(shallow, deep);
+
+        // callbacks stay subscribed without keeping any handle alive
+        map.insert(&mut doc.transact_mut(), "a", 1);
+        assert_eq!(calls.load(Ordering::SeqCst), 11);
+
+        assert!(map.unobserve(shallow.clone()));
+        assert!(!map.unobserve(shallow));
+        map.insert(&mut doc.transact_mut(), "b", 2);
+        assert_eq!(calls.load(Ordering::SeqCst), 21);
+
+        assert!(map.unobserve_deep(deep));
+        map.insert(&mut doc.transact_mut(), "c", 3);
+        assert_eq!(calls.load(Ordering::SeqCst), 21);
+    }
+
     struct DropCounter(Arc<AtomicI32>);
 
     impl DropCounter {
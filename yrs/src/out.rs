@@ -2,7 +2,7 @@ use crate::block::{ItemContent, ItemPtr};
 use crate::branch::{Branch, BranchPtr};
 use crate::types::{AsPrelim, ToJson};
 use crate::{
-    any, Any, ArrayRef, Doc, GetString, In, MapPrelim, MapRef, ReadTxn, TextRef, XmlElementRef,
+    any, Any, ArrayRef, Doc, GetString, In, MapRef, ReadTxn, TextRef, XmlElementRef,
     XmlFragmentRef, XmlTextRef,
 };
 use std::convert::TryFrom;
@@ -118,12 +118,14 @@ impl AsPrelim for Out {
             Out::YDoc(v) => In::Doc(v.clone()),
             #[cfg(feature = "weak")]
             Out::YWeakLink(v) => In::WeakLink(v.as_prelim(txn)),
-            Out::UndefinedRef(v) => infer_type_from_content(*v, txn),
+            Out::UndefinedRef(v) => infer_type_from_content(*v).as_prelim(txn),
         }
     }
 }
 
-fn infer_type_from_content<T: ReadTxn>(branch: BranchPtr, txn: &T) -> In {
+/// Infers a type of a shared collection, which type information is unknown (i.e. root types
+/// decoded from remote updates) based on its contents.
+pub(crate) fn infer_type_from_content(branch: BranchPtr) -> Out {
     let has_map = !branch.map.is_empty();
     let mut ptr = branch.start;
     let has_list = ptr.is_some();
@@ -140,12 +142,12 @@ fn infer_type_from_content<T: ReadTxn>(branch: BranchPtr, txn: &T) -> In {
     }
 
     match (has_map, has_list, possible_text) {
-        (true, false, false) => In::Map(MapRef::from(branch).as_prelim(txn)),
-        (false, true, false) => In::Array(ArrayRef::from(branch).as_prelim(txn)),
-        (false, _, true) => In::Text(TextRef::from(branch).as_prelim(txn)),
-        (true, _, true) => In::XmlText(XmlTextRef::from(branch).as_prelim(txn)),
-        (true, true, false) => In::XmlElement(XmlElementRef::from(branch).as_prelim(txn)),
-        _ => In::Map(MapPrelim::default()), // if we have no content, default to map
+        (true, false, false) => Out::YMap(MapRef::from(branch)),
+        (false, true, false) => Out::YArray(ArrayRef::from(branch)),
+        (false, _, true) => Out::YText(TextRef::from(branch)),
+        (true, _, true) => Out::YXmlText(XmlTextRef::from(branch)),
+        (true, true, false) => Out::YXmlElement(XmlElementRef::from(branch)),
+        _ => Out::YMap(MapRef::from(branch)), // if we have no content, default to map
     }
 }
 
@@ -215,6 +217,24 @@ impl ToJson for Out {
             Out::UndefinedRef(_) => Any::Undefined,
         }
     }
+
+    fn to_json_writer<T: ReadTxn, W: std::io::Write>(
+        &self,
+        txn: &T,
+        writer: &mut W,
+    ) -> std::io::Result<()> {
+        match self {
+            Out::Any(a) => serde_json::to_writer(writer, a)?,
+            Out::YText(v) => serde_json::to_writer(writer, &v.get_string(txn))?,
+            Out::YArray(v) => v.to_json_writer(txn, writer)?,
+            Out::YMap(v) => v.to_json_writer(txn, writer)?,
+            Out::YXmlElement(v) => serde_json::to_writer(writer, &v.get_string(txn))?,
+            Out::YXmlText(v) => serde_json::to_writer(writer, &v.get_string(txn))?,
+            Out::YXmlFragment(v) => v.to_json_writer(txn, writer)?,
+            other => serde_json::to_writer(writer, &other.to_json(txn))?,
+        }
+        Ok(())
+    }
 }
 
 impl std::fmt::Display for Out {
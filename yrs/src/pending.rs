@@ -0,0 +1,414 @@
+//! Limits on the updates waiting for their missing dependencies.
+//!
+//! Blocks of a remote update can be integrated only after all blocks they depend on are known to
+//! a document. Until then, they are stashed in a pending update (see: [Store::pending_update]).
+//! When the missing blocks never arrive - i.e. because the peer that produced them went offline
+//! before sending them to anyone - all subsequent updates depending on them are parked in a
+//! pending update forever. A [PendingPolicy] allows to limit how large and how old a pending
+//! update can grow, and what should happen with it once these limits are exceeded.
+//!
+//! [Store::pending_update]: crate::Store::pending_update
+
+use crate::block::BlockRange;
+use crate::store::BlockRange as ClockRange;
+use crate::sync::{Clock, Timestamp};
+use crate::update::Update;
+use crate::updates::encoder::Encode;
+use crate::{StateVector, TransactionMut, ID};
+use std::sync::Arc;
+
+/// Limits on a pending update, configured with [Doc::set_pending_policy]. All limits are optional.
+/// When any of them is exceeded, a configured [PendingAction] is performed and subscribers of
+/// [Doc::observe_pending_evictions] are notified.
+///
+/// Limits are verified every time an update is applied.
+///
+/// [Doc::set_pending_policy]: crate::Doc::set_pending_policy
+/// [Doc::observe_pending_evictions]: crate::Doc::observe_pending_evictions
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PendingPolicy {
+    /// Maximum number of blocks stored in a pending update.
+    pub max_blocks: Option<usize>,
+    /// Maximum size (in bytes) of a pending update encoded using lib0 v1 encoding.
+    pub max_bytes: Option<usize>,
+    /// Maximum time (in milliseconds) since the pending update has been created.
+    pub max_age_millis: Option<Timestamp>,
+    /// Action performed when any of the limits has been exceeded.
+    pub action: PendingAction,
+}
+
+/// Action performed on a pending update, which exceeded limits of a [PendingPolicy].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PendingAction {
+    /// Discards the pending update together with pending delete set.
+    #[default]
+    Drop,
+    /// Discards the pending update together with pending delete set, passing them encoded to
+    /// [PendingEvictionEvent::update] first, so that they can be stored externally and applied
+    /// again once the missing updates arrive.
+    Persist,
+    /// Fills the missing ranges of blocks with garbage collected blocks and integrates the
+    /// pending update. Blocks depending on the missing ones are integrated as garbage collected
+    /// as well, while the independent ones become visible. Filled ranges are reported through
+    /// [PendingEvictionEvent::holes].
+    ///
+    /// Since the missing ranges are considered to be known from now on, they won't be integrated
+    /// if they arrive later on. The document state may diverge from the state of peers who have
+    /// them.
+    ForceIntegrate,
+}
+
+/// Limit of a [PendingPolicy], which has been exceeded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PendingLimit {
+    /// Number of blocks stored in a pending update.
+    Blocks(usize),
+    /// Size of a pending update in bytes.
+    Bytes(usize),
+    /// Time since the pending update has been created (in milliseconds).
+    Age(Timestamp),
+}
+
+/// Event passed to callbacks subscribed with [Doc::observe_pending_evictions] whenever pending
+/// update has exceeded limits of a [PendingPolicy].
+///
+/// [Doc::observe_pending_evictions]: crate::Doc::observe_pending_evictions
+#[derive(Debug, Clone, PartialEq)]
+pub struct PendingEvictionEvent {
+    /// Limit which has been exceeded.
+    pub limit: PendingLimit,
+    /// Action performed on the pending update.
+    pub action: PendingAction,
+    /// State vector of the blocks, the pending update has been waiting for.
+    pub missing: StateVector,
+    /// A lib0 v1 encoded pending update (including pending delete set) removed from the document.
+    /// Present only for [PendingAction::Persist].
+    pub update: Option<Vec<u8>>,
+    /// Ranges of blocks, which have been filled with garbage collected blocks. Present only for
+    /// [PendingAction::ForceIntegrate].
+    pub holes: Vec<ClockRange>,
+}
+
+pub(crate) struct PendingState {
+    pub policy: PendingPolicy,
+    pub clock: Arc<dyn Clock>,
+    /// Timestamp when current pending update has been created.
+    pub since: Option<Timestamp>,
+}
+
+impl PendingState {
+    pub fn new(policy: PendingPolicy, clock: Arc<dyn Clock>) -> Self {
+        PendingState {
+            policy,
+            clock,
+            since: None,
+        }
+    }
+
+    fn exceeded(&mut self, pending: &Update) -> Option<PendingLimit> {
+        let now = self.clock.now();
+        let since = *self.since.get_or_insert(now);
+        let policy = &self.policy;
+        let blocks = pending.blocks.len();
+        if matches!(policy.max_blocks, Some(max) if blocks > max) {
+            return Some(PendingLimit::Blocks(blocks));
+        }
+        if let Some(max) = policy.max_bytes {
+            let bytes = pending.encode_v1().len();
+            if bytes > max {
+                return Some(PendingLimit::Bytes(bytes));
+            }
+        }
+        let age = now.saturating_sub(since);
+        if matches!(policy.max_age_millis, Some(max) if age > max) {
+            return Some(PendingLimit::Age(age));
+        }
+        None
+    }
+}
+
+/// Verifies the pending update of a document against its [PendingPolicy] and performs configured
+/// action if any of the limits has been exceeded.
+pub(crate) fn enforce(txn: &mut TransactionMut) {
+    let store = txn.store_mut();
+    let mut state = match store.pending_state.take() {
+        Some(state) => state,
+        None => return,
+    };
+    let limit = match store.pending.as_ref() {
+        None => {
+            state.since = None;
+            None
+        }
+        Some(pending) => state.exceeded(&pending.update),
+    };
+    let action = state.policy.action;
+    store.pending_state = Some(state);
+    let limit = match limit {
+        Some(limit) => limit,
+        None => return,
+    };
+
+    let pending = store.pending.take().unwrap();
+    let pending_ds = store.pending_ds.take();
+    let mut event = PendingEvictionEvent {
+        limit,
+        action,
+        missing: pending.missing.clone(),
+        update: None,
+        holes: Vec::new(),
+    };
+    match action {
+        PendingAction::Drop => {}
+        PendingAction::Persist => {
+            let mut update = pending.update;
+            if let Some(ds) = pending_ds {
+                update.delete_set = ds;
+            }
+            event.update = Some(update.encode_v1());
+        }
+        PendingAction::ForceIntegrate => {
+            event.holes = force_integrate(txn, pending.update, pending_ds);
+        }
+    }
+    if let Some(state) = txn.store_mut().pending_state.as_mut() {
+        state.since = None;
+    }
+    if let Some(events) = txn.store().events.as_ref() {
+        events
+            .pending_eviction_events
+            .trigger(|cb| txn.isolate(|| cb(txn, &event)));
+    }
+}
+
+fn force_integrate(
+    txn: &mut TransactionMut,
+    mut update: Update,
+    pending_ds: Option<crate::DeleteSet>,
+) -> Vec<ClockRange> {
+    let mut holes = Vec::new();
+    loop {
+        let local = txn.store().blocks.get_state_vector();
+        let missing = update.blocks.missing_ranges(&local);
+        let progress = !missing.is_empty();
+        let store = txn.store_mut();
+        for (client, start, end) in missing {
+            store
+                .blocks
+                .push_gc(BlockRange::new(ID::new(client, start), end - start));
+            holes.push(ClockRange::new(client, start, end));
+        }
+        txn.apply_update_unchecked(update);
+        let store = txn.store_mut();
+        match store.pending.take() {
+            Some(pending) if progress => update = pending.update,
+            other => {
+                store.pending = other;
+                break;
+            }
+        }
+    }
+    if let Some(ds) = pending_ds {
+        let mut update = Update::new();
+        update.delete_set = ds;
+        txn.apply_update_unchecked(update);
+    }
+    holes
+}
+
+#[cfg(test)]
+mod test {
+    use crate::pending::{PendingAction, PendingLimit, PendingPolicy};
+    use crate::{BlockRange, Doc, GetString, Map, Options, ReadTxn, Text, Transact};
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    /// Returns updates produced by each of the given transactions.
+    fn updates(doc: &Doc, edits: &[&dyn Fn(&Doc)]) -> Vec<Vec<u8>> {
+        edits
+            .iter()
+            .map(|edit| {
+                let sv = doc.transact().state_vector();
+                edit(doc);
+                doc.transact().encode_state_as_update_v1(&sv)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn drop_by_block_count() {
+        let d1 = Doc::with_client_id(1);
+        let text = d1.get_or_insert_text("text");
+        let u = updates(
+            &d1,
+            &[
+                &|d| text.push(&mut d.transact_mut(), "a"),
+                &|d| text.push(&mut d.transact_mut(), "b"),
+                &|d| text.push(&mut d.transact_mut(), "c"),
+            ],
+        );
+
+        let d2 = Doc::with_client_id(2);
+        let policy = PendingPolicy {
+            max_blocks: Some(1),
+            ..PendingPolicy::default()
+        };
+        d2.set_pending_policy(Some(policy)).unwrap();
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let _sub = {
+            let events = events.clone();
+            d2.observe_pending_evictions(move |_, e| events.lock().unwrap().push(e.clone()))
+                .unwrap()
+        };
+
+        d2.try_apply_update_v1(&u[1]).unwrap();
+        assert!(d2.transact().store().pending_update().is_some());
+        assert!(events.lock().unwrap().is_empty());
+
+        d2.try_apply_update_v1(&u[2]).unwrap();
+        assert!(d2.transact().store().pending_update().is_none());
+        let events = events.lock().unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].limit, PendingLimit::Blocks(2));
+        assert_eq!(events[0].action, PendingAction::Drop);
+        assert_eq!(events[0].update, None);
+    }
+
+    #[test]
+    fn isolate_eviction_observer_panics() {
+        let d1 = Doc::with_client_id(1);
+        let text = d1.get_or_insert_text("text");
+        let u = updates(
+            &d1,
+            &[
+                &|d| text.push(&mut d.transact_mut(), "a"),
+                &|d| text.push(&mut d.transact_mut(), "b"),
+                &|d| text.push(&mut d.transact_mut(), "c"),
+            ],
+        );
+
+        let d2 = Doc::with_options(Options {
+            client_id: 2,
+            isolate_observers: true,
+            ..Options::default()
+        });
+        let policy = PendingPolicy {
+            max_blocks: Some(1),
+            ..PendingPolicy::default()
+        };
+        d2.set_pending_policy(Some(policy)).unwrap();
+        let _s1 = d2
+            .observe_pending_evictions(|_, _| panic!("eviction observer failed"))
+            .unwrap();
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let _s2 = {
+            let errors = errors.clone();
+            d2.observe_callback_errors(move |_, e| errors.lock().unwrap().extend_from_slice(e))
+                .unwrap()
+        };
+
+        d2.try_apply_update_v1(&u[1]).unwrap();
+        d2.try_apply_update_v1(&u[2]).unwrap();
+        assert!(d2.transact().store().pending_update().is_none());
+        let errors: Vec<_> = errors
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|e| e.message.to_string())
+            .collect();
+        assert_eq!(errors, vec!["eviction observer failed"]);
+    }
+
+    #[test]
+    fn persist_by_age() {
+        let d1 = Doc::with_client_id(1);
+        let text = d1.get_or_insert_text("text");
+        let u = updates(
+            &d1,
+            &[
+                &|d| text.push(&mut d.transact_mut(), "a"),
+                &|d| text.push(&mut d.transact_mut(), "b"),
+                &|d| text.push(&mut d.transact_mut(), "c"),
+            ],
+        );
+
+        let d2 = Doc::with_client_id(2);
+        let now = Arc::new(AtomicU64::new(0));
+        let policy = PendingPolicy {
+            max_age_millis: Some(1000),
+            action: PendingAction::Persist,
+            ..PendingPolicy::default()
+        };
+        let clock = {
+            let now = now.clone();
+            move || now.load(Ordering::SeqCst)
+        };
+        d2.set_pending_policy_with_clock(Some(policy), Arc::new(clock))
+            .unwrap();
+        let persisted = Arc::new(Mutex::new(None));
+        let _sub = {
+            let persisted = persisted.clone();
+            d2.observe_pending_evictions(move |_, e| *persisted.lock().unwrap() = e.update.clone())
+                .unwrap()
+        };
+
+        d2.try_apply_update_v1(&u[1]).unwrap();
+        now.store(1001, Ordering::SeqCst);
+        d2.try_apply_update_v1(&u[2]).unwrap();
+        assert!(d2.transact().store().pending_update().is_none());
+
+        // missing update arrives later on, persisted one can be applied afterwards
+        let persisted = persisted.lock().unwrap().take().unwrap();
+        d2.try_apply_update_v1(&u[0]).unwrap();
+        d2.try_apply_update_v1(&persisted).unwrap();
+        let text = d2.get_or_insert_text("text");
+        assert_eq!(text.get_string(&d2.transact()), "abc");
+    }
+
+    #[test]
+    fn force_integrate() {
+        let d1 = Doc::with_client_id(1);
+        let text = d1.get_or_insert_text("text");
+        let map = d1.get_or_insert_map("map");
+        let u = updates(
+            &d1,
+            &[
+                &|d| text.push(&mut d.transact_mut(), "abc"),
+                &|d| text.push(&mut d.transact_mut(), "def"),
+                &|d| {
+                    map.insert(&mut d.transact_mut(), "key", "value");
+                },
+            ],
+        );
+
+        let d2 = Doc::with_client_id(2);
+        let policy = PendingPolicy {
+            max_blocks: Some(1),
+            action: PendingAction::ForceIntegrate,
+            ..PendingPolicy::default()
+        };
+        d2.set_pending_policy(Some(policy)).unwrap();
+        let holes = Arc::new(Mutex::new(Vec::new()));
+        let _sub = {
+            let holes = holes.clone();
+            d2.observe_pending_evictions(move |_, e| holes.lock().unwrap().extend(e.holes.clone()))
+                .unwrap()
+        };
+
+        d2.try_apply_update_v1(&u[1]).unwrap();
+        d2.try_apply_update_v1(&u[2]).unwrap();
+        assert!(d2.transact().store().pending_update().is_none());
+        assert_eq!(*holes.lock().unwrap(), vec![BlockRange::new(1, 0, 3)]);
+
+        let map = d2.get_or_insert_map("map");
+        let text = d2.get_or_insert_text("text");
+        let txn = d2.transact();
+        assert_eq!(map.get(&txn, "key"), Some("value".into()));
+        assert_eq!(text.get_string(&txn), "");
+        assert_eq!(
+            txn.state_vector(),
+            d1.transact().state_vector(),
+            "missing range is considered known"
+        );
+    }
+}
@@ -0,0 +1,273 @@
+use crate::encoding::read::{self, Cursor, Read};
+use crate::encoding::write::Write as _;
+use crate::persistence::{DocStore, Error};
+use crate::updates::decoder::Decode;
+use crate::updates::encoder::Encode;
+use crate::Update;
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Tag of a file record containing an update encoded using lib0 v1 encoding.
+const RECORD_V1: u8 = 1;
+/// Tag of a file record containing an update encoded using lib0 v2 encoding.
+const RECORD_V2: u8 = 2;
+
+/// File extension used by files storing document updates.
+const EXTENSION: &str = "yupdates";
+
+/// A reference implementation of [DocStore], which keeps updates of every document in
+/// a separate append-only file inside of a given directory. Each record of a file consists of
+/// a version tag of lib0 encoding used by a stored update, followed by a length-prefixed update
+/// binary itself.
+///
+/// Once the number of records stored in a single file reaches a configured compaction threshold,
+/// all of them are merged into a single update, which replaces the file contents.
+///
+/// If a process crashed in the middle of writing a record, that incomplete record will be
+/// discarded next time the document is loaded.
+///
+/// # Example
+///
+/// ```rust
+/// use std::sync::Arc;
+/// use yrs::persistence::FileStore;
+/// use yrs::{Doc, GetString, Options, Text, Transact};
+///
+/// let dir = std::env::temp_dir().join("yrs-file-store-example");
+/// # let _ = std::fs::remove_dir_all(&dir);
+/// let store = Arc::new(FileStore::new(&dir).unwrap());
+/// let options = Options {
+///     guid: "my-document".into(),
+///     ..Options::default()
+/// };
+///
+/// let doc = Doc::open_with(store.clone(), options.clone()).unwrap();
+/// let text = doc.get_or_insert_text("text");
+/// text.push(&mut doc.transact_mut(), "hello");
+/// drop(doc);
+///
+/// let doc = Doc::open_with(store, options).unwrap();
+/// let text = doc.get_or_insert_text("text");
+/// assert_eq!(text.get_string(&doc.transact()), "hello");
+/// # std::fs::remove_dir_all(&dir).unwrap();
+/// ```
+#[derive(Debug)]
+pub struct FileStore {
+    dir: PathBuf,
+    compaction_threshold: usize,
+    /// Number of records stored in each document file. It's also used as a lock over the files.
+    records: Mutex<HashMap<String, usize>>,
+}
+
+impl FileStore {
+    /// Default number of records after which a document file is compacted.
+    pub const DEFAULT_COMPACTION_THRESHOLD: usize = 500;
+
+    /// Creates a new file store, which will keep document files inside of a given `dir`.
+    /// Directory is created if it doesn't exist.
+    pub fn new<P: AsRef<Path>>(dir: P) -> Result<Self, Error> {
+        let dir = dir.as_ref().to_path_buf();
+        std::fs::create_dir_all(&dir)?;
+        Ok(FileStore {
+            dir,
+            compaction_threshold: Self::DEFAULT_COMPACTION_THRESHOLD,
+            records: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Sets the number of records after which a document file will be compacted.
+    pub fn with_compaction_threshold(mut self, compaction_threshold: usize) -> Self {
+        self.compaction_threshold = compaction_threshold.max(1);
+        self
+    }
+
+    /// Returns a path to a file storing updates of a document with given `name`. Characters
+    /// that could be interpreted by a file system are escaped.
+    pub fn doc_path(&self, name: &str) -> PathBuf {
+        let mut file_name = String::with_capacity(name.len() + EXTENSION.len() + 1);
+        for b in name.bytes() {
+            if b.is_ascii_alphanumeric() || b == b'-' || b == b'_' {
+                file_name.push(b as char);
+            } else {
+                file_name.push_str(&format!("%{:02X}", b));
+            }
+        }
+        file_name.push('.');
+        file_name.push_str(EXTENSION);
+        self.dir.join(file_name)
+    }
+
+    /// Reads all complete records from a document file. Incomplete record at the end of a file
+    /// (result of interrupted write) is truncated.
+    fn read_records(&self, name: &str) -> Result<Vec<Update>, Error> {
+        let path = self.doc_path(name);
+        let data = match std::fs::read(&path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut cursor = Cursor::new(&data);
+        let mut updates = Vec::new();
+        let mut valid_len = 0;
+        while cursor.has_content() {
+            let version = match cursor.read_u8() {
+                Ok(version) => version,
+                Err(_) => return Self::truncate(&path, valid_len).map(|_| updates),
+            };
+            let update = match (version, cursor.read_buf()) {
+                (RECORD_V1, Ok(data)) => Update::decode_v1(data)?,
+                (RECORD_V2, Ok(data)) => Update::decode_v2(data)?,
+                (_, Ok(_)) => return Err(read::Error::UnexpectedValue.into()),
+                (_, Err(_)) => return Self::truncate(&path, valid_len).map(|_| updates),
+            };
+            updates.push(update);
+            valid_len = cursor.next;
+        }
+        Ok(updates)
+    }
+
+    /// Discards an incomplete record at the end of a file.
+    fn truncate(path: &Path, len: usize) -> Result<(), Error> {
+        let file = OpenOptions::new().write(true).open(path)?;
+        file.set_len(len as u64)?;
+        Ok(())
+    }
+
+    fn append(&self, name: &str, version: u8, update: &[u8]) -> Result<(), Error> {
+        let mut records = self.records.lock().unwrap();
+        let mut record = Vec::with_capacity(update.len() + 6);
+        record.write_u8(version);
+        record.write_buf(update);
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.doc_path(name))?;
+        file.write_all(&record)?;
+        file.sync_data()?;
+
+        let count = records.entry(name.to_string()).or_default();
+        *count += 1;
+        if *count >= self.compaction_threshold {
+            self.compact(name, &mut records)?;
+        }
+        Ok(())
+    }
+
+    fn compact(&self, name: &str, records: &mut HashMap<String, usize>) -> Result<(), Error> {
+        let updates = self.read_records(name)?;
+        if updates.is_empty() {
+            return Ok(());
+        }
+        let update = Update::merge_updates(updates).encode_v1();
+        let mut record = Vec::with_capacity(update.len() + 6);
+        record.write_u8(RECORD_V1);
+        record.write_buf(&update);
+
+        // write compacted file aside and replace the original one atomically
+        let path = self.doc_path(name);
+        let tmp_path = path.with_extension("tmp");
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(&record)?;
+        file.sync_all()?;
+        std::fs::rename(&tmp_path, &path)?;
+        records.insert(name.to_string(), 1);
+        Ok(())
+    }
+}
+
+impl DocStore for FileStore {
+    fn load_doc(&self, name: &str) -> Result<Vec<Update>, Error> {
+        let mut records = self.records.lock().unwrap();
+        let updates = self.read_records(name)?;
+        records.insert(name.to_string(), updates.len());
+        Ok(updates)
+    }
+
+    fn store_update(&self, name: &str, update: &[u8]) -> Result<(), Error> {
+        self.append(name, RECORD_V1, update)
+    }
+
+    fn store_update_v2(&self, name: &str, update: &[u8]) -> Result<(), Error> {
+        self.append(name, RECORD_V2, update)
+    }
+
+    fn flush_doc(&self, name: &str) -> Result<(), Error> {
+        let mut records = self.records.lock().unwrap();
+        self.compact(name, &mut records)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::persistence::{DocStore, FileStore};
+    use crate::updates::encoder::Encode;
+    use crate::{Doc, GetString, Options, Text, Transact};
+    use std::io::Write;
+    use std::sync::Arc;
+
+    fn options(guid: &str) -> Options {
+        Options {
+            guid: guid.into(),
+            ..Options::default()
+        }
+    }
+
+    #[test]
+    fn file_store_persist_and_compact() {
+        let dir = std::env::temp_dir().join(format!("yrs-file-store-{}", fastrand::u64(..)));
+        let store = Arc::new(FileStore::new(&dir).unwrap().with_compaction_threshold(4));
+        {
+            let doc = Doc::open_with(store.clone(), options("doc/1")).unwrap();
+            let text = doc.get_or_insert_text("text");
+            for c in ["a", "b", "c", "d", "e"] {
+                text.push(&mut doc.transact_mut(), c);
+            }
+        }
+        assert!(store.doc_path("doc/1").ends_with("doc%2F1.yupdates"));
+        // 4 records have been compacted into one, then another one was appended
+        assert_eq!(store.load_doc("doc/1").unwrap().len(), 2);
+
+        // v2 updates are stored as they are
+        let update = {
+            let doc = Doc::with_client_id(1);
+            let text = doc.get_or_insert_text("text");
+            let mut txn = doc.transact_mut();
+            text.push(&mut txn, "!");
+            txn.encode_update_v2()
+        };
+        store.store_update_v2("doc/1", &update).unwrap();
+
+        // incomplete record at the end of a file is discarded
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(store.doc_path("doc/1"))
+            .unwrap();
+        file.write_all(&[1, 100, 1, 2, 3]).unwrap();
+        drop(file);
+
+        let doc = Doc::open_with(store.clone(), options("doc/1")).unwrap();
+        let text = doc.get_or_insert_text("text");
+        let value = text.get_string(&doc.transact());
+        assert_eq!(value.len(), 6);
+        assert!(value.contains("abcde"));
+        text.push(&mut doc.transact_mut(), "?");
+        drop(doc);
+
+        store.flush_doc("doc/1").unwrap();
+        let updates = store.load_doc("doc/1").unwrap();
+        assert_eq!(updates.len(), 1);
+        let restored = Doc::new();
+        restored
+            .try_apply_update_v1(&updates[0].encode_v1())
+            .unwrap();
+        let text = restored.get_or_insert_text("text");
+        assert_eq!(text.get_string(&restored.transact()).len(), 7);
+
+        assert!(store.load_doc("unknown").unwrap().is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
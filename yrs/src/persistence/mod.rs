@@ -0,0 +1,61 @@
+//! Persistence subsystem, which allows to store document updates incrementally as they happen
+//! and restore document state from them later on. See: [DocStore] and [Doc::open_with].
+//!
+//! A reference implementation writing updates into append-only files can be found in
+//! [FileStore].
+
+pub mod file;
+
+pub use crate::persistence::file::FileStore;
+
+use crate::encoding::read;
+use crate::updates::decoder::Decode;
+use crate::updates::encoder::Encode;
+use crate::Update;
+use thiserror::Error;
+
+/// Origin used by transactions, which restore document state from a [DocStore].
+pub const PERSISTENCE_ORIGIN: &str = "persistence";
+
+/// Storage for document updates, following the semantics of y-leveldb: every update produced
+/// by a document is appended to the store as it happens, while [DocStore::flush_doc] can be used
+/// to compact all updates stored so far into a single one.
+///
+/// Documents are identified by their names. [Doc::open_with] uses [Doc::guid] for that purpose.
+///
+/// [Doc::open_with]: crate::Doc::open_with
+/// [Doc::guid]: crate::Doc::guid
+pub trait DocStore: Send + Sync {
+    /// Returns all updates stored for a document with a given `name`, in the order in which they
+    /// were stored. Returns an empty list if no such document has been stored so far.
+    fn load_doc(&self, name: &str) -> Result<Vec<Update>, Error>;
+
+    /// Appends a new `update` (encoded using lib0 v1 encoding) to a document with a given `name`.
+    fn store_update(&self, name: &str, update: &[u8]) -> Result<(), Error>;
+
+    /// Appends a new `update` (encoded using lib0 v2 encoding) to a document with a given `name`.
+    /// By default it's re-encoded using lib0 v1 encoding and passed to [DocStore::store_update].
+    fn store_update_v2(&self, name: &str, update: &[u8]) -> Result<(), Error> {
+        let update = Update::decode_v2(update)?;
+        self.store_update(name, &update.encode_v1())
+    }
+
+    /// Compacts all updates stored for a document with a given `name` into a single update.
+    fn flush_doc(&self, name: &str) -> Result<(), Error>;
+}
+
+/// An error type returned by [DocStore] operations.
+#[derive(Debug, Error)]
+pub enum Error {
+    /// Stored updates couldn't be decoded.
+    #[error("failed to decode stored update: {0}")]
+    Decoding(#[from] read::Error),
+
+    /// Stored updates couldn't be applied onto the document.
+    #[error("failed to restore document: {0}")]
+    Doc(#[from] crate::Error),
+
+    /// Thrown in case of I/O errors.
+    #[error("IO error: {0}")]
+    IO(#[from] std::io::Error),
+}
@@ -0,0 +1,428 @@
+//! Pool of documents, which applies incoming updates on multiple threads.
+//!
+//! Servers hosting many documents receive a constant stream of updates for all of them. Applying
+//! these updates one by one on a single thread doesn't scale, while spawning a task per update
+//! breaks the order in which updates of the same document must be applied. [DocPool] distributes
+//! documents between a fixed number of worker threads: updates of different documents are applied
+//! in parallel, while updates of the same document are always applied by the same worker, in the
+//! order in which they were submitted.
+//!
+//! # Example
+//!
+//! ```rust
+//! use yrs::pool::DocPool;
+//! use yrs::{Doc, GetString, ReadTxn, StateVector, Text, Transact, WriteTxn};
+//!
+//! let pool = DocPool::new(4);
+//! let mut handles = Vec::new();
+//! for guid in ["a", "b", "c"] {
+//!     let source = Doc::new();
+//!     let text = source.get_or_insert_text("text");
+//!     text.push(&mut source.transact_mut(), guid);
+//!     let update = source.transact().encode_state_as_update_v1(&StateVector::default());
+//!     handles.push(pool.apply(guid, update));
+//! }
+//! for handle in handles {
+//!     handle.wait().unwrap();
+//! }
+//!
+//! let doc = pool.get("b").unwrap();
+//! let text = doc.transact_mut().get_or_insert_text("text");
+//! assert_eq!(text.get_string(&doc.transact()), "b");
+//! ```
+
+use crate::concurrent::ConcurrentDoc;
+use crate::encoding::read::DecodeLimits;
+use crate::error::Error;
+use crate::updates::decoder::Decode;
+use crate::{Doc, Options, Update, Uuid};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, RwLock};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// Interval after which a worker retries to apply updates to documents, which were locked by
+/// other transactions at the time of a previous attempt.
+const RETRY_INTERVAL: Duration = Duration::from_millis(1);
+
+/// A collection of documents identified by their GUIDs, which applies updates submitted with
+/// [DocPool::apply] on a fixed number of worker threads. See [module level docs](crate::pool).
+///
+/// Documents are created on the first update submitted for their GUID, unless they have been
+/// registered upfront with [DocPool::insert]. Updates are decoded in a strict mode (see:
+/// [Decode::decode_v1_strict]), as they usually come from untrusted peers.
+///
+/// Workers never block on a document locked by a transaction opened outside of the pool: updates
+/// for such document are put aside and retried later, while updates of other documents served
+/// by the same worker are applied in the meantime. If applying an update panics (i.e. in one of
+/// the document observers), the document is marked as poisoned (see: [DocPool::is_poisoned]) and
+/// all of its further updates are rejected. Other documents are not affected.
+///
+/// Dropping a pool waits until all updates submitted so far have been applied.
+pub struct DocPool {
+    docs: RwLock<HashMap<Uuid, ConcurrentDoc>>,
+    poisoned: Arc<RwLock<HashSet<Uuid>>>,
+    workers: Vec<Worker>,
+}
+
+struct Worker {
+    sender: Option<Sender<Job>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+struct Job {
+    guid: Uuid,
+    doc: ConcurrentDoc,
+    update: Vec<u8>,
+    result: Sender<Result<(), Error>>,
+}
+
+impl DocPool {
+    /// Creates a new pool, which applies updates using a given number of worker threads.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `workers` is 0.
+    pub fn new(workers: usize) -> Self {
+        assert!(workers > 0, "DocPool requires at least one worker thread");
+        let poisoned = Arc::new(RwLock::new(HashSet::new()));
+        let workers = (0..workers)
+            .map(|_| {
+                let (sender, receiver) = channel::<Job>();
+                let poisoned = poisoned.clone();
+                let handle = std::thread::spawn(move || Self::run(receiver, &poisoned));
+                Worker {
+                    sender: Some(sender),
+                    handle: Some(handle),
+                }
+            })
+            .collect();
+        DocPool {
+            docs: RwLock::new(HashMap::new()),
+            poisoned,
+            workers,
+        }
+    }
+
+    /// Main loop of a worker thread. Jobs are queued per document, so that updates of documents
+    /// locked by other transactions can be retried later without breaking their order.
+    fn run(receiver: Receiver<Job>, poisoned: &RwLock<HashSet<Uuid>>) {
+        let mut queues: HashMap<Uuid, VecDeque<Job>> = HashMap::new();
+        let mut connected = true;
+        loop {
+            if queues.is_empty() {
+                match receiver.recv() {
+                    Ok(job) => Self::enqueue(&mut queues, job),
+                    Err(_) => return, // pool has been dropped and all jobs are done
+                }
+            } else if connected {
+                match receiver.recv_timeout(RETRY_INTERVAL) {
+                    Ok(job) => Self::enqueue(&mut queues, job),
+                    Err(RecvTimeoutError::Timeout) => {}
+                    Err(RecvTimeoutError::Disconnected) => connected = false,
+                }
+            } else {
+                std::thread::sleep(RETRY_INTERVAL);
+            }
+            while let Ok(job) = receiver.try_recv() {
+                Self::enqueue(&mut queues, job);
+            }
+            queues.retain(|_, jobs| {
+                Self::apply_queued(jobs, poisoned);
+                !jobs.is_empty()
+            });
+        }
+    }
+
+    fn enqueue(queues: &mut HashMap<Uuid, VecDeque<Job>>, job: Job) {
+        queues.entry(job.guid.clone()).or_default().push_back(job);
+    }
+
+    /// Applies queued jobs of a single document in order, until all of them are done or the
+    /// document is locked by another transaction.
+    fn apply_queued(jobs: &mut VecDeque<Job>, poisoned: &RwLock<HashSet<Uuid>>) {
+        while let Some(job) = jobs.pop_front() {
+            if poisoned
+                .read()
+                .unwrap_or_else(|e| e.into_inner())
+                .contains(&job.guid)
+            {
+                // caller may not be interested in the result
+                let _ = job.result.send(Err(Self::poisoned_error(&job.guid)));
+                continue;
+            }
+            match catch_unwind(AssertUnwindSafe(|| Self::apply_job(&job))) {
+                Ok(Some(result)) => {
+                    let _ = job.result.send(result);
+                }
+                Ok(None) => {
+                    // document is locked by another transaction, retry later
+                    jobs.push_front(job);
+                    return;
+                }
+                Err(_) => {
+                    let mut poisoned = poisoned.write().unwrap_or_else(|e| e.into_inner());
+                    poisoned.insert(job.guid.clone());
+                    let _ = job.result.send(Err(Self::poisoned_error(&job.guid)));
+                }
+            }
+        }
+    }
+
+    /// Applies an update of a given job. Returns `None` if the document couldn't be locked.
+    fn apply_job(job: &Job) -> Option<Result<(), Error>> {
+        let mut txn = job.doc.try_transact_mut().ok()?;
+        match Update::decode_v1_strict(&job.update, DecodeLimits::default()) {
+            Ok(update) => {
+                txn.apply_update(update);
+                Some(Ok(()))
+            }
+            Err(e) => Some(Err(e.into())),
+        }
+    }
+
+    fn poisoned_error(guid: &Uuid) -> Error {
+        Error::Integration(format!(
+            "document {} has been poisoned by a panic while applying an update",
+            guid
+        ))
+    }
+
+    /// Returns a document with a given `guid`, if it's a part of current pool.
+    pub fn get(&self, guid: &str) -> Option<ConcurrentDoc> {
+        let docs = self.docs.read().unwrap_or_else(|e| e.into_inner());
+        docs.get(guid).cloned()
+    }
+
+    /// Registers a given document in current pool under its [Doc::guid], replacing a document
+    /// previously stored under the same GUID. Replacing a poisoned document clears its poisoned
+    /// state.
+    pub fn insert(&self, doc: Doc) -> Option<ConcurrentDoc> {
+        let mut docs = self.docs.write().unwrap_or_else(|e| e.into_inner());
+        let guid = doc.guid().clone();
+        self.clear_poison(&guid);
+        docs.insert(guid, ConcurrentDoc::new(doc))
+    }
+
+    /// Removes a document with a given `guid` from current pool, clearing its poisoned state.
+    /// Updates for this document, which have been submitted but not applied yet, are still
+    /// applied to the returned document.
+    pub fn remove(&self, guid: &str) -> Option<ConcurrentDoc> {
+        let mut docs = self.docs.write().unwrap_or_else(|e| e.into_inner());
+        self.clear_poison(guid);
+        docs.remove(guid)
+    }
+
+    /// Checks if a document with a given `guid` has been poisoned by a panic raised while one of
+    /// its updates was being applied. Updates submitted for a poisoned document are rejected
+    /// until it's replaced or removed from current pool.
+    pub fn is_poisoned(&self, guid: &str) -> bool {
+        let poisoned = self.poisoned.read().unwrap_or_else(|e| e.into_inner());
+        poisoned.contains(guid)
+    }
+
+    fn clear_poison(&self, guid: &str) {
+        let mut poisoned = self.poisoned.write().unwrap_or_else(|e| e.into_inner());
+        poisoned.remove(guid);
+    }
+
+    /// Returns a number of documents in current pool.
+    pub fn len(&self) -> usize {
+        let docs = self.docs.read().unwrap_or_else(|e| e.into_inner());
+        docs.len()
+    }
+
+    /// Checks if current pool has no documents.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Submits a lib0 v1 encoded `update` to be applied to a document with a given `guid`,
+    /// creating that document if necessary. This method doesn't block: returned [ApplyHandle] can
+    /// be used to wait for the update to be applied.
+    pub fn apply<G: Into<Uuid>>(&self, guid: G, update: Vec<u8>) -> ApplyHandle {
+        let guid = guid.into();
+        let doc = self.get(&guid).unwrap_or_else(|| {
+            let mut docs = self.docs.write().unwrap_or_else(|e| e.into_inner());
+            docs.entry(guid.clone())
+                .or_insert_with(|| {
+                    let options = Options {
+                        guid: guid.clone(),
+                        ..Options::default()
+                    };
+                    ConcurrentDoc::new(Doc::with_options(options))
+                })
+                .clone()
+        });
+        let (result, receiver) = channel();
+        let worker = &self.workers[self.worker_index(&guid)];
+        let job = Job {
+            guid,
+            doc,
+            update,
+            result,
+        };
+        if let Some(sender) = &worker.sender {
+            // workers outlive their senders, so sending can't fail
+            let _ = sender.send(job);
+        }
+        ApplyHandle(receiver)
+    }
+
+    fn worker_index(&self, guid: &str) -> usize {
+        let mut hasher = DefaultHasher::new();
+        guid.hash(&mut hasher);
+        (hasher.finish() % self.workers.len() as u64) as usize
+    }
+}
+
+impl Drop for DocPool {
+    fn drop(&mut self) {
+        for worker in self.workers.iter_mut() {
+            worker.sender.take();
+        }
+        for worker in self.workers.iter_mut() {
+            if let Some(handle) = worker.handle.take() {
+                let _ = handle.join();
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for DocPool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DocPool")
+            .field("docs", &self.len())
+            .field("workers", &self.workers.len())
+            .finish()
+    }
+}
+
+/// Handle to an update submitted with [DocPool::apply].
+#[derive(Debug)]
+pub struct ApplyHandle(Receiver<Result<(), Error>>);
+
+impl ApplyHandle {
+    /// Blocks current thread until an update has been applied. Returns an error if the update
+    /// couldn't be decoded or its document has been poisoned (see: [DocPool::is_poisoned]).
+    pub fn wait(self) -> Result<(), Error> {
+        match self.0.recv() {
+            Ok(result) => result,
+            Err(_) => Err(Error::Integration(
+                "update has been dropped before being applied".into(),
+            )),
+        }
+    }
+
+    /// Returns the result of an update application if it has finished already.
+    pub fn try_wait(&self) -> Option<Result<(), Error>> {
+        self.0.try_recv().ok()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::pool::DocPool;
+    use crate::{Doc, GetString, Options, ReadTxn, StateVector, Text, Transact, WriteTxn};
+
+    #[test]
+    fn per_doc_ordering() {
+        let pool = DocPool::new(3);
+        let mut handles = Vec::new();
+        let mut expected = Vec::new();
+        for i in 0..8 {
+            let guid = format!("doc-{}", i);
+            let source = Doc::with_client_id(1);
+            let text = source.get_or_insert_text("text");
+            for j in 0..20 {
+                // each update depends on the previous one
+                let sv = source.transact().state_vector();
+                text.push(&mut source.transact_mut(), &j.to_string());
+                let update = source.transact().encode_state_as_update_v1(&sv);
+                handles.push(pool.apply(guid.as_str(), update));
+            }
+            expected.push((guid, text.get_string(&source.transact())));
+        }
+        let handle = pool.apply("doc-0", vec![1, 2, 3]);
+        for handle in handles {
+            handle.wait().unwrap();
+        }
+        assert!(handle.wait().is_err());
+
+        assert_eq!(pool.len(), 8);
+        for (guid, expected) in expected {
+            let doc = pool.get(&guid).unwrap();
+            let text = doc.transact_mut().get_or_insert_text("text");
+            let txn = doc.transact();
+            assert!(txn.store().pending.is_none());
+            assert_eq!(text.get_string(&txn), expected);
+        }
+    }
+
+    #[test]
+    fn locked_doc_does_not_block_worker() {
+        let pool = DocPool::new(1);
+        let update = |s: &str| {
+            let source = Doc::new();
+            let text = source.get_or_insert_text("text");
+            text.push(&mut source.transact_mut(), s);
+            let update = source
+                .transact()
+                .encode_state_as_update_v1(&StateVector::default());
+            update
+        };
+        pool.apply("a", update("a")).wait().unwrap();
+        let a = pool.get("a").unwrap();
+
+        let guard = a.transact_mut();
+        let locked = pool.apply("a", update("b"));
+        // updates of other documents served by the same worker are still applied
+        pool.apply("b", update("c")).wait().unwrap();
+        assert!(locked.try_wait().is_none());
+        drop(guard);
+        locked.wait().unwrap();
+
+        let text = a.transact_mut().get_or_insert_text("text");
+        assert_eq!(text.get_string(&a.transact()).len(), 2);
+    }
+
+    #[test]
+    fn panic_poisons_only_affected_doc() {
+        let pool = DocPool::new(1);
+        let doc = Doc::with_options(Options {
+            guid: "faulty".into(),
+            ..Options::default()
+        });
+        let _sub = doc
+            .observe_update_v1(|_, _| panic!("observer failed"))
+            .unwrap();
+        pool.insert(doc);
+
+        let source = Doc::with_client_id(1);
+        let text = source.get_or_insert_text("text");
+        text.push(&mut source.transact_mut(), "hello");
+        let update = source
+            .transact()
+            .encode_state_as_update_v1(&StateVector::default());
+
+        assert!(pool.apply("faulty", update.clone()).wait().is_err());
+        assert!(pool.is_poisoned("faulty"));
+        assert!(pool.apply("faulty", update.clone()).wait().is_err());
+
+        // worker keeps serving other documents
+        pool.apply("healthy", update.clone()).wait().unwrap();
+        assert!(!pool.is_poisoned("healthy"));
+
+        // replacing a poisoned document clears its state
+        pool.insert(Doc::with_options(Options {
+            guid: "faulty".into(),
+            ..Options::default()
+        }));
+        assert!(!pool.is_poisoned("faulty"));
+        pool.apply("faulty", update).wait().unwrap();
+    }
+}
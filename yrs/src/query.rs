@@ -0,0 +1,417 @@
+//! Shaped reads of a document state, driven by a small GraphQL-like selection language.
+//!
+//! A [Selection] lists the fields which should be read. Each field can be followed by an array
+//! slice and/or by a nested selection in curly braces:
+//!
+//! - `users` reads the entire `users` value.
+//! - `users { name age }` reads only `name` and `age` fields of `users`. When applied to an array,
+//!   nested selection is applied to every element.
+//! - `posts[0:10]` reads first ten elements of `posts` array, `posts[-1]` reads its last element.
+//!   Both bounds of a slice are optional and negative indexes count from the end of an array.
+//! - `"field name"` quotes a field name containing whitespace or special characters.
+//!
+//! Fields can be separated by whitespace or commas. The whole selection can be optionally wrapped
+//! in curly braces. Top-level fields refer to the names of root-level collections.
+//!
+//! Only the requested parts of a document are visited, so that selecting a few fields of a big
+//! document doesn't require serializing the whole of it. Fields missing in a document, as well
+//! as slices and nested selections applied to values of incompatible types, are skipped.
+//!
+//! # Example
+//!
+//! ```rust
+//! use yrs::query::Selection;
+//! use yrs::{any, Array, Doc, MapPrelim, ReadTxn, Transact};
+//!
+//! let doc = Doc::new();
+//! let posts = doc.get_or_insert_array("posts");
+//! let mut txn = doc.transact_mut();
+//! for i in 0..5 {
+//!     posts.push_back(&mut txn, MapPrelim::from([("id", i), ("likes", i * 10)]));
+//! }
+//!
+//! let selection: Selection = "{ posts[1:3] { id } }".parse().unwrap();
+//! assert_eq!(txn.query(&selection), any!({"posts": [{"id": 1}, {"id": 2}]}));
+//! ```
+
+use crate::types::ToJson;
+use crate::{Any, Array, Map, Out, ReadTxn, XmlFragment, XmlOut};
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+use thiserror::Error;
+
+/// Error returned when a [Selection] couldn't be parsed.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum SelectionParseError {
+    /// Selection ended in the middle of a field, slice or nested selection.
+    #[error("unexpected end of selection at position {0}")]
+    UnexpectedEnd(usize),
+
+    /// An unexpected character has been found.
+    #[error("expected {expected} at position {position}")]
+    UnexpectedChar {
+        position: usize,
+        expected: &'static str,
+    },
+}
+
+/// A parsed list of fields to be read by [ReadTxn::query]. See [module level docs](crate::query)
+/// for the description of a selection language.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Selection {
+    fields: Vec<Field>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Field {
+    name: Arc<str>,
+    slice: Option<Slice>,
+    selection: Option<Selection>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Slice {
+    /// A single element at a given index.
+    Index(i64),
+    /// Elements within `[start, end)` range.
+    Range(Option<i64>, Option<i64>),
+}
+
+impl Slice {
+    /// Resolves (possibly negative) bounds of current slice for an array of a given `len`.
+    fn bounds(&self, len: usize) -> (usize, usize) {
+        let resolve = |i: i64| {
+            if i < 0 {
+                len.saturating_sub(i.unsigned_abs() as usize)
+            } else {
+                (i as usize).min(len)
+            }
+        };
+        match *self {
+            Slice::Index(i) => {
+                let start = resolve(i);
+                (start, (start + 1).min(len))
+            }
+            Slice::Range(start, end) => {
+                let start = start.map(resolve).unwrap_or(0);
+                let end = end.map(resolve).unwrap_or(len);
+                (start, end.max(start))
+            }
+        }
+    }
+}
+
+impl Selection {
+    /// Parses a selection from a given string.
+    pub fn parse(src: &str) -> Result<Self, SelectionParseError> {
+        let mut parser = Parser { src, pos: 0 };
+        parser.skip_separators();
+        let selection = if parser.rest().starts_with('{') {
+            parser.parse_selection()?
+        } else {
+            parser.parse_fields()?
+        };
+        parser.skip_separators();
+        if parser.rest().is_empty() {
+            Ok(selection)
+        } else {
+            Err(SelectionParseError::UnexpectedChar {
+                position: parser.pos,
+                expected: "field name",
+            })
+        }
+    }
+}
+
+impl FromStr for Selection {
+    type Err = SelectionParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Selection::parse(s)
+    }
+}
+
+struct Parser<'a> {
+    src: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.src[self.pos..]
+    }
+
+    fn skip_separators(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len()
+            - rest
+                .trim_start_matches(|c: char| c.is_whitespace() || c == ',')
+                .len();
+    }
+
+    fn expect(&mut self, c: char, expected: &'static str) -> Result<(), SelectionParseError> {
+        if self.rest().starts_with(c) {
+            self.pos += c.len_utf8();
+            Ok(())
+        } else {
+            Err(self.unexpected(expected))
+        }
+    }
+
+    fn unexpected(&self, expected: &'static str) -> SelectionParseError {
+        if self.rest().is_empty() {
+            SelectionParseError::UnexpectedEnd(self.pos)
+        } else {
+            SelectionParseError::UnexpectedChar {
+                position: self.pos,
+                expected,
+            }
+        }
+    }
+
+    /// Parses fields wrapped in curly braces.
+    fn parse_selection(&mut self) -> Result<Selection, SelectionParseError> {
+        self.expect('{', "{")?;
+        let selection = self.parse_fields()?;
+        self.skip_separators();
+        self.expect('}', "}")?;
+        Ok(selection)
+    }
+
+    /// Parses fields until the end of input or a closing brace.
+    fn parse_fields(&mut self) -> Result<Selection, SelectionParseError> {
+        let mut fields = Vec::new();
+        loop {
+            self.skip_separators();
+            if self.rest().is_empty() || self.rest().starts_with('}') {
+                return Ok(Selection { fields });
+            }
+            fields.push(self.parse_field()?);
+        }
+    }
+
+    fn parse_field(&mut self) -> Result<Field, SelectionParseError> {
+        let name = self.parse_name()?;
+        self.skip_separators();
+        let slice = if self.rest().starts_with('[') {
+            let slice = self.parse_slice()?;
+            self.skip_separators();
+            Some(slice)
+        } else {
+            None
+        };
+        let selection = if self.rest().starts_with('{') {
+            Some(self.parse_selection()?)
+        } else {
+            None
+        };
+        Ok(Field {
+            name,
+            slice,
+            selection,
+        })
+    }
+
+    fn parse_name(&mut self) -> Result<Arc<str>, SelectionParseError> {
+        let rest = self.rest();
+        if let Some(quoted) = rest.strip_prefix('"') {
+            return match quoted.find('"') {
+                Some(end) => {
+                    self.pos += end + 2;
+                    Ok(Arc::from(&quoted[..end]))
+                }
+                None => Err(SelectionParseError::UnexpectedEnd(self.src.len())),
+            };
+        }
+        let len = rest
+            .find(|c: char| !(c.is_alphanumeric() || matches!(c, '_' | '-' | '$' | '.')))
+            .unwrap_or(rest.len());
+        if len == 0 {
+            return Err(self.unexpected("field name"));
+        }
+        self.pos += len;
+        Ok(Arc::from(&rest[..len]))
+    }
+
+    fn parse_slice(&mut self) -> Result<Slice, SelectionParseError> {
+        self.expect('[', "[")?;
+        self.skip_separators();
+        let start = self.parse_index()?;
+        self.skip_separators();
+        let slice = if self.rest().starts_with(':') {
+            self.pos += 1;
+            self.skip_separators();
+            let end = self.parse_index()?;
+            Slice::Range(start, end)
+        } else {
+            match start {
+                Some(index) => Slice::Index(index),
+                None => return Err(self.unexpected("index")),
+            }
+        };
+        self.skip_separators();
+        self.expect(']', "]")?;
+        Ok(slice)
+    }
+
+    fn parse_index(&mut self) -> Result<Option<i64>, SelectionParseError> {
+        let rest = self.rest();
+        let sign = if rest.starts_with('-') { 1 } else { 0 };
+        let len = rest[sign..]
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len() - sign);
+        if len == 0 {
+            return if sign == 0 {
+                Ok(None)
+            } else {
+                Err(self.unexpected("index"))
+            };
+        }
+        match rest[..sign + len].parse() {
+            Ok(index) => {
+                self.pos += sign + len;
+                Ok(Some(index))
+            }
+            Err(_) => Err(self.unexpected("index")),
+        }
+    }
+}
+
+/// Reads values selected by a given `selection` from root-level collections of a document.
+pub(crate) fn query<T: ReadTxn>(txn: &T, selection: &Selection) -> Any {
+    let mut result = HashMap::new();
+    for field in selection.fields.iter() {
+        if let Some(root) = txn.store().get_type(field.name.clone()) {
+            if let Some(value) = select(txn, root.into(), field) {
+                result.insert(field.name.to_string(), value);
+            }
+        }
+    }
+    Any::from(result)
+}
+
+/// Applies a slice and nested selection of a given `field` to its `value`.
+fn select<T: ReadTxn>(txn: &T, value: Out, field: &Field) -> Option<Any> {
+    match field.slice {
+        None => Some(shape(txn, value, field.selection.as_ref())),
+        Some(slice) => {
+            let elements = elements(txn, &value)?;
+            let (start, end) = slice.bounds(elements.len());
+            let mut selected = elements
+                .into_iter()
+                .skip(start)
+                .take(end - start)
+                .map(|value| shape(txn, value, field.selection.as_ref()));
+            match slice {
+                Slice::Index(_) => selected.next(),
+                Slice::Range(_, _) => Some(Any::Array(selected.collect())),
+            }
+        }
+    }
+}
+
+/// Converts a given `value` into [Any], retaining only the fields requested by `selection`.
+fn shape<T: ReadTxn>(txn: &T, value: Out, selection: Option<&Selection>) -> Any {
+    let selection = match selection {
+        None => return value.to_json(txn),
+        Some(selection) => selection,
+    };
+    if let Some(elements) = elements(txn, &value) {
+        return Any::Array(
+            elements
+                .into_iter()
+                .map(|value| shape(txn, value, Some(selection)))
+                .collect(),
+        );
+    }
+    let mut result = HashMap::new();
+    for field in selection.fields.iter() {
+        if let Some(child) = child(txn, &value, &field.name) {
+            if let Some(value) = select(txn, child, field) {
+                result.insert(field.name.to_string(), value);
+            }
+        }
+    }
+    Any::from(result)
+}
+
+/// Returns a value stored under a given `key` of a map-like `value`.
+fn child<T: ReadTxn>(txn: &T, value: &Out, key: &str) -> Option<Out> {
+    match value {
+        Out::YMap(map) => map.get(txn, key),
+        Out::Any(Any::Map(map)) => map.get(key).cloned().map(Out::Any),
+        Out::UndefinedRef(branch) => branch.get(txn, key),
+        _ => None,
+    }
+}
+
+/// Returns elements of an array-like `value`.
+fn elements<T: ReadTxn>(txn: &T, value: &Out) -> Option<Vec<Out>> {
+    let xml = |node: XmlOut| match node {
+        XmlOut::Element(n) => Out::YXmlElement(n),
+        XmlOut::Fragment(n) => Out::YXmlFragment(n),
+        XmlOut::Text(n) => Out::YXmlText(n),
+    };
+    match value {
+        Out::YArray(array) => Some(array.iter(txn).collect()),
+        Out::Any(Any::Array(array)) => Some(array.iter().cloned().map(Out::Any).collect()),
+        Out::YXmlFragment(fragment) => Some(fragment.children(txn).map(xml).collect()),
+        Out::YXmlElement(element) => Some(element.children(txn).map(xml).collect()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::query::{Selection, SelectionParseError};
+    use crate::{any, Array, ArrayPrelim, Doc, Map, MapPrelim, ReadTxn, TextPrelim, Transact};
+
+    #[test]
+    fn query_selection() {
+        let doc = Doc::new();
+        let users = doc.get_or_insert_map("users");
+        let tags = doc.get_or_insert_array("tags");
+        {
+            let mut txn = doc.transact_mut();
+            let alice = users.insert(&mut txn, "alice", MapPrelim::default());
+            alice.insert(&mut txn, "age", 30);
+            alice.insert(&mut txn, "bio", TextPrelim::new("hello"));
+            let posts = alice.insert(&mut txn, "posts", ArrayPrelim::default());
+            for i in 0..4 {
+                posts.push_back(&mut txn, any!({"id": i, "title": "post", "likes": [i, i]}));
+            }
+            let bob = users.insert(&mut txn, "bob", MapPrelim::default());
+            bob.insert(&mut txn, "age", 25);
+            tags.insert_range(&mut txn, 0, ["a", "b", "c"]);
+        }
+        let txn = doc.transact();
+        let query = |s: &str| txn.query(&s.parse().unwrap());
+
+        assert_eq!(
+            query("users { alice { age, bio, posts[-2:] { id likes[0] } } missing }"),
+            any!({"users": {"alice": {"age": 30, "bio": "hello", "posts": [
+                {"id": 2, "likes": 2},
+                {"id": 3, "likes": 3}
+            ]}}})
+        );
+        assert_eq!(query("{ tags[1], missing }"), any!({"tags": "b"}));
+        assert_eq!(query("tags[:2] users[0]"), any!({"tags": ["a", "b"]}));
+        assert_eq!(
+            query(r#"users { "bob" }"#),
+            any!({"users": {"bob": {"age": 25}}})
+        );
+
+        assert_eq!(
+            Selection::parse("users { age"),
+            Err(SelectionParseError::UnexpectedEnd(11))
+        );
+        assert_eq!(
+            Selection::parse("tags[a]"),
+            Err(SelectionParseError::UnexpectedChar {
+                position: 5,
+                expected: "index"
+            })
+        );
+    }
+}
@@ -0,0 +1,373 @@
+//! Export of document contents as flat relational rows.
+//!
+//! Every node of a document - shared collections as well as the values stored inside of them -
+//! is represented as a single [Row], which refers to its parent row by identifier. Such rows can
+//! be loaded directly into SQL databases or BI tools, without the need to flatten a JSON
+//! representation of a document by hand.
+//!
+//! A full export of a document is produced by [export_rows]. Afterwards the exported rows can be
+//! kept up to date incrementally using [export_changes], which only exports the subtrees of the
+//! collections changed by a given transaction.
+//!
+//! # Example
+//!
+//! ```rust
+//! use yrs::rows::{export_changes, export_rows, RowKind};
+//! use yrs::{Any, Array, Doc, Map, MapPrelim, Transact};
+//!
+//! let doc = Doc::new();
+//! let map = doc.get_or_insert_map("users");
+//! map.insert(&mut doc.transact_mut(), "alice", MapPrelim::from([("age", 30)]));
+//!
+//! let rows = export_rows(&doc.transact());
+//! let ids: Vec<_> = rows.iter().map(|row| row.id.as_str()).collect();
+//! assert_eq!(ids, vec!["/users", "/users/alice", "/users/alice/age"]);
+//! assert_eq!(rows[2].parent.as_deref(), Some("/users/alice"));
+//! assert_eq!(rows[2].kind, RowKind::Value);
+//! assert_eq!(rows[2].value, Some(Any::from(30)));
+//!
+//! let _sub = doc
+//!     .observe_after_transaction(|txn| {
+//!         let delta = export_changes(txn);
+//!         assert_eq!(delta.removed, vec!["/users".to_string()]);
+//!         assert_eq!(delta.rows.len(), 4);
+//!     })
+//!     .unwrap();
+//! map.insert(&mut doc.transact_mut(), "bob", "unknown");
+//! ```
+
+use crate::branch::{Branch, BranchPtr};
+use crate::types::{Path, PathSegment, TypePtr};
+use crate::visit::{visit_out, DocVisitor};
+use crate::{
+    Any, ArrayRef, BranchID, Doc, GetString, MapRef, Out, ReadTxn, TextRef, TransactionMut,
+    XmlElementRef, XmlFragmentRef, XmlTextRef,
+};
+use std::fmt::Write;
+
+/// Kind of a node represented by a [Row].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RowKind {
+    /// A shared [MapRef] or a map stored as a plain [Any] value.
+    Map,
+    /// A shared [ArrayRef] or an array stored as a plain [Any] value.
+    Array,
+    /// A shared [TextRef]. Row value contains its string content.
+    Text,
+    /// A [XmlFragmentRef].
+    XmlFragment,
+    /// A [XmlElementRef]. Row value contains its tag name.
+    XmlElement,
+    /// A [XmlTextRef]. Row value contains its string content.
+    XmlText,
+    /// A sub-document. Row value contains its globally unique identifier.
+    Doc,
+    /// A weak link.
+    WeakLink,
+    /// A root-level collection, which type is not known.
+    Undefined,
+    /// A scalar value.
+    Value,
+}
+
+/// A single node of a document, exported by [export_rows] or [export_changes].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Row {
+    /// Identifier of a current row: a path to the node in JSON pointer format (RFC 6901), i.e.
+    /// `/users/alice/0`. It's unique within a document.
+    pub id: String,
+    /// Identifier of a parent row. `None` for root-level collections.
+    pub parent: Option<String>,
+    /// Path to the node, starting with a name of the root-level collection it belongs to.
+    pub path: Path,
+    /// Kind of the node.
+    pub kind: RowKind,
+    /// Scalar value of the node. `None` for maps, arrays and other nodes, which content is
+    /// represented by their child rows.
+    pub value: Option<Any>,
+    /// Identifier of a shared collection represented by the node. Unlike [Row::id], it doesn't
+    /// change when the collection's position in the document changes. `None` for nodes, which
+    /// are not shared collections.
+    pub branch_id: Option<BranchID>,
+}
+
+/// Changes to the previously exported rows, produced by [export_changes].
+///
+/// In order to apply them, all rows which identifiers are equal to any of [RowDelta::removed]
+/// entries or start with such entry followed by `/` should be deleted first, then
+/// [RowDelta::rows] should be inserted.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RowDelta {
+    /// Identifiers of rows, which have been removed together with all of their descendants.
+    pub removed: Vec<String>,
+    /// Rows, which should be inserted in place of the removed ones.
+    pub rows: Vec<Row>,
+}
+
+impl RowDelta {
+    /// Checks if there are no changes.
+    pub fn is_empty(&self) -> bool {
+        self.removed.is_empty() && self.rows.is_empty()
+    }
+}
+
+/// Exports the whole contents of a document as flat rows. Rows are returned in depth-first
+/// order, so that every row is preceded by its parent.
+pub fn export_rows<T: ReadTxn>(txn: &T) -> Vec<Row> {
+    let mut exporter = RowExporter::new(txn);
+    txn.visit(&mut exporter);
+    exporter.rows
+}
+
+/// Exports the subtrees of all collections changed by a given transaction. It's meant to be
+/// called from within [Doc::observe_after_transaction] callbacks in order to keep the rows
+/// produced by [export_rows] up to date. See [RowDelta] for details on how to apply the result.
+pub fn export_changes(txn: &TransactionMut) -> RowDelta {
+    let mut paths: Vec<(Path, BranchPtr)> = txn
+        .changed
+        .keys()
+        .filter_map(|ptr| match ptr {
+            TypePtr::Branch(branch) => Some((branch_path(*branch)?, *branch)),
+            _ => None,
+        })
+        .collect();
+    // parents go first, so that changes of their descendants can be skipped
+    paths.sort_by_key(|(path, _)| path.len());
+    let mut changed: Vec<(Path, BranchPtr)> = Vec::with_capacity(paths.len());
+    for (path, branch) in paths {
+        if !changed.iter().any(|(parent, _)| starts_with(&path, parent)) {
+            changed.push((path, branch));
+        }
+    }
+    changed.sort_by_key(|(path, _)| pointer(path));
+
+    let mut delta = RowDelta::default();
+    let mut exporter = RowExporter::new(txn);
+    for (mut path, branch) in changed {
+        delta.removed.push(pointer(&path));
+        let value: Out = branch.into();
+        visit_out(txn, &mut path, &value, &mut exporter);
+    }
+    delta.rows = exporter.rows;
+    delta
+}
+
+fn starts_with(path: &Path, prefix: &Path) -> bool {
+    path.len() >= prefix.len() && path.iter().zip(prefix.iter()).all(|(a, b)| a == b)
+}
+
+/// Returns a path to a given branch or `None` if it, or any of its parents, has been deleted.
+fn branch_path(branch: BranchPtr) -> Option<Path> {
+    let mut root = branch;
+    while let Some(item) = root.item {
+        if item.is_deleted() {
+            return None;
+        }
+        root = *item.parent.as_branch()?;
+    }
+    let mut path = Branch::path(root, branch);
+    path.push_front(PathSegment::Key(root.name.clone()?));
+    Some(path)
+}
+
+/// Formats a given path as a JSON pointer.
+fn pointer(path: &Path) -> String {
+    let mut s = String::new();
+    for segment in path.iter() {
+        s.push('/');
+        match segment {
+            PathSegment::Key(key) => s.push_str(&key.replace('~', "~0").replace('/', "~1")),
+            PathSegment::Index(index) => write!(s, "{}", index).unwrap(),
+        }
+    }
+    s
+}
+
+struct RowExporter<'a, T> {
+    txn: &'a T,
+    rows: Vec<Row>,
+}
+
+impl<'a, T: ReadTxn> RowExporter<'a, T> {
+    fn new(txn: &'a T) -> Self {
+        RowExporter {
+            txn,
+            rows: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, path: &Path, kind: RowKind, value: Option<Any>, branch: Option<&Branch>) {
+        let id = pointer(path);
+        let parent = if path.len() > 1 {
+            // parent identifier is a prefix of the current one
+            Some(id[..id.rfind('/').unwrap()].to_string())
+        } else {
+            None
+        };
+        self.rows.push(Row {
+            id,
+            parent,
+            path: path.clone(),
+            kind,
+            value,
+            branch_id: branch.map(Branch::id),
+        });
+    }
+
+    fn push_any(&mut self, path: &mut Path, value: &Any) {
+        match value {
+            Any::Array(values) => {
+                self.push(path, RowKind::Array, None, None);
+                for (i, value) in values.iter().enumerate() {
+                    path.push_back(PathSegment::Index(i as u32));
+                    self.push_any(path, value);
+                    path.pop_back();
+                }
+            }
+            Any::Map(entries) => {
+                self.push(path, RowKind::Map, None, None);
+                let mut entries: Vec<_> = entries.iter().collect();
+                entries.sort_by(|a, b| a.0.cmp(b.0));
+                for (key, value) in entries {
+                    path.push_back(PathSegment::Key(key.as_str().into()));
+                    self.push_any(path, value);
+                    path.pop_back();
+                }
+            }
+            value => self.push(path, RowKind::Value, Some(value.clone()), None),
+        }
+    }
+}
+
+impl<'a, T: ReadTxn> DocVisitor for RowExporter<'a, T> {
+    fn enter_map(&mut self, path: &Path, map: &MapRef) -> bool {
+        self.push(path, RowKind::Map, None, Some(map.as_ref()));
+        true
+    }
+
+    fn enter_array(&mut self, path: &Path, array: &ArrayRef) -> bool {
+        self.push(path, RowKind::Array, None, Some(array.as_ref()));
+        true
+    }
+
+    fn visit_text(&mut self, path: &Path, text: &TextRef) {
+        let value = Any::from(text.get_string(self.txn));
+        self.push(path, RowKind::Text, Some(value), Some(text.as_ref()));
+    }
+
+    fn enter_xml_fragment(&mut self, path: &Path, fragment: &XmlFragmentRef) -> bool {
+        self.push(path, RowKind::XmlFragment, None, Some(fragment.as_ref()));
+        true
+    }
+
+    fn enter_xml_element(&mut self, path: &Path, element: &XmlElementRef) -> bool {
+        let tag = element.try_tag().map(|tag| Any::from(tag.to_string()));
+        self.push(path, RowKind::XmlElement, tag, Some(element.as_ref()));
+        true
+    }
+
+    fn visit_xml_text(&mut self, path: &Path, text: &XmlTextRef) {
+        let value = Any::from(text.get_string(self.txn));
+        self.push(path, RowKind::XmlText, Some(value), Some(text.as_ref()));
+    }
+
+    fn enter_undefined(&mut self, path: &Path, branch: &Branch) -> bool {
+        self.push(path, RowKind::Undefined, None, Some(branch));
+        true
+    }
+
+    fn visit_any(&mut self, path: &Path, value: &Any) {
+        self.push_any(&mut path.clone(), value);
+    }
+
+    fn visit_doc(&mut self, path: &Path, doc: &Doc) {
+        let guid = Any::from(doc.guid().to_string());
+        self.push(path, RowKind::Doc, Some(guid), None);
+    }
+
+    #[cfg(feature = "weak")]
+    fn visit_weak_link(&mut self, path: &Path, link: &crate::WeakRef<BranchPtr>) {
+        self.push(path, RowKind::WeakLink, None, Some(link.as_ref()));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::rows::{export_changes, export_rows, Row, RowDelta, RowKind};
+    use crate::{Any, Array, ArrayPrelim, BranchID, Doc, Map, Text, TextPrelim, Transact};
+    use std::sync::{Arc, Mutex};
+
+    fn apply(rows: &mut Vec<Row>, delta: RowDelta) {
+        rows.retain(|row| {
+            !delta
+                .removed
+                .iter()
+                .any(|id| row.id == *id || row.id.starts_with(&format!("{}/", id)))
+        });
+        rows.extend(delta.rows);
+        rows.sort_by(|a, b| a.id.cmp(&b.id));
+    }
+
+    #[test]
+    fn incremental_export() {
+        let doc = Doc::with_client_id(1);
+        let map = doc.get_or_insert_map("m/ap");
+        let list = doc.get_or_insert_array("list");
+        let text = {
+            let mut txn = doc.transact_mut();
+            list.insert_range(&mut txn, 0, [1, 2]);
+            list.push_back(&mut txn, Any::from(vec![Any::from(true)]));
+            map.insert(&mut txn, "text", TextPrelim::new("hello"))
+        };
+        let mut rows = export_rows(&doc.transact());
+        let summary: Vec<_> = rows
+            .iter()
+            .map(|r| (r.id.as_str(), r.parent.as_deref(), r.kind, r.value.clone()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("/list", None, RowKind::Array, None),
+                ("/list/0", Some("/list"), RowKind::Value, Some(Any::from(1))),
+                ("/list/1", Some("/list"), RowKind::Value, Some(Any::from(2))),
+                ("/list/2", Some("/list"), RowKind::Array, None),
+                (
+                    "/list/2/0",
+                    Some("/list/2"),
+                    RowKind::Value,
+                    Some(true.into())
+                ),
+                ("/m~1ap", None, RowKind::Map, None),
+                (
+                    "/m~1ap/text",
+                    Some("/m~1ap"),
+                    RowKind::Text,
+                    Some("hello".into())
+                ),
+            ]
+        );
+        assert_eq!(rows[5].branch_id, Some(BranchID::Root("m/ap".into())));
+
+        let deltas = Arc::new(Mutex::new(Vec::new()));
+        let d = deltas.clone();
+        let _sub = doc
+            .observe_after_transaction(move |txn| d.lock().unwrap().push(export_changes(txn)))
+            .unwrap();
+        {
+            let mut txn = doc.transact_mut();
+            text.push(&mut txn, " world");
+            list.remove(&mut txn, 0);
+            list.push_back(&mut txn, ArrayPrelim::from([3]));
+        }
+        {
+            // nested changes are covered by the changes of their parents
+            let mut txn = doc.transact_mut();
+            let nested = map.insert(&mut txn, "nested", ArrayPrelim::default());
+            nested.push_back(&mut txn, 4);
+        }
+        for delta in deltas.lock().unwrap().drain(..) {
+            apply(&mut rows, delta);
+        }
+        assert_eq!(rows, export_rows(&doc.transact()));
+    }
+}
@@ -0,0 +1,269 @@
+//! Fine-grained reactive reads of a document state.
+//!
+//! A [Signal] wraps a read function over a document. Unlike [Computed], it doesn't need to be
+//! told which collections to observe: while the function is running, every shared collection it
+//! reads (i.e. using [Map::get], [Array::iter], [GetString::get_string] or [ToJson::to_json])
+//! is recorded as its dependency. Once a committed transaction changes any of these collections,
+//! the function is re-evaluated, its dependencies are updated and - if the result is different
+//! from the previous one - subscribers are notified.
+//!
+//! This makes signals a good fit for binding document state to reactive UI frameworks (i.e.
+//! leptos or futures-signals): a callback registered with [Signal::observe] can forward new
+//! values into a framework-specific signal, while only the views depending on the changed parts
+//! of a document are being refreshed.
+//!
+//! # Example
+//!
+//! ```rust
+//! use std::sync::{Arc, Mutex};
+//! use yrs::signals::Signal;
+//! use yrs::{Array, Doc, GetString, Map, Out, Text, TextPrelim, TextRef, Transact};
+//!
+//! let doc = Doc::new();
+//! let users = doc.get_or_insert_map("users");
+//! let log = doc.get_or_insert_array("log");
+//! users.insert(&mut doc.transact_mut(), "alice", TextPrelim::new("Alice"));
+//!
+//! let name = Signal::new(&doc, {
+//!     let users = users.clone();
+//!     move |txn| match users.get(txn, "alice") {
+//!         Some(Out::YText(text)) => text.get_string(txn),
+//!         _ => String::new(),
+//!     }
+//! })
+//! .unwrap();
+//! assert_eq!(*name.get(), "Alice");
+//!
+//! let seen = Arc::new(Mutex::new(Vec::new()));
+//! let _sub = name.observe({
+//!     let seen = seen.clone();
+//!     move |_, value| seen.lock().unwrap().push(value.clone())
+//! });
+//!
+//! log.push_back(&mut doc.transact_mut(), "unrelated change");
+//! let alice: TextRef = users.get(&doc.transact(), "alice").unwrap().cast().unwrap();
+//! alice.push(&mut doc.transact_mut(), " Smith");
+//! assert_eq!(*seen.lock().unwrap(), vec!["Alice Smith".to_string()]);
+//! ```
+//!
+//! [Computed]: crate::computed::Computed
+//! [Map::get]: crate::Map::get
+//! [Array::iter]: crate::Array::iter
+//! [GetString::get_string]: crate::GetString::get_string
+//! [ToJson::to_json]: crate::types::ToJson::to_json
+
+use crate::branch::{Branch, BranchPtr};
+use crate::doc::TransactionAcqError;
+use crate::observer::Observer;
+use crate::types::TypePtr;
+use crate::{Doc, ReadTxn, Store, Subscription, Transact, TransactionMut};
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+
+/// Read-only view over a document state passed to the functions of [Signal] values. It
+/// implements [ReadTxn] and records every shared collection read through it.
+pub struct SignalTxn<'a> {
+    store: &'a Store,
+    dependencies: RefCell<HashSet<BranchPtr>>,
+}
+
+impl<'a> ReadTxn for SignalTxn<'a> {
+    #[inline]
+    fn store(&self) -> &Store {
+        self.store
+    }
+
+    fn track(&self, branch: &Branch) {
+        self.dependencies
+            .borrow_mut()
+            .insert(BranchPtr::from(branch));
+    }
+}
+
+type SignalFn<T> = Box<dyn Fn(&SignalTxn) -> T + Send + Sync + 'static>;
+
+type SignalObserveFn<T> = Box<dyn Fn(&TransactionMut, &T) + Send + Sync + 'static>;
+
+/// A value read from a document, which is kept up to date as the shared collections it depends
+/// on are changed. See [module level docs](crate::signals) for details.
+///
+/// The value is evaluated eagerly: once on creation and then after every committed transaction,
+/// which has changed any of the collections read during the last evaluation.
+pub struct Signal<T> {
+    inner: Arc<Inner<T>>,
+    _subscription: Subscription,
+}
+
+struct Inner<T> {
+    read: SignalFn<T>,
+    state: Mutex<Option<State<T>>>,
+    observers: Observer<SignalObserveFn<T>>,
+}
+
+struct State<T> {
+    value: Arc<T>,
+    dependencies: HashSet<BranchPtr>,
+}
+
+impl<T> Signal<T>
+where
+    T: PartialEq + Send + Sync + 'static,
+{
+    /// Creates a new signal over a given `doc`, evaluating its value using function `f`.
+    ///
+    /// Returns an error if a read-write transaction over `doc` is currently active.
+    pub fn new<F>(doc: &Doc, f: F) -> Result<Self, TransactionAcqError>
+    where
+        F: Fn(&SignalTxn) -> T + Send + Sync + 'static,
+    {
+        let inner = Arc::new(Inner {
+            read: Box::new(f),
+            state: Mutex::new(None),
+            observers: Observer::new(),
+        });
+        let weak = Arc::downgrade(&inner);
+        let subscription = doc.observe_after_transaction(move |txn| {
+            if let Some(inner) = weak.upgrade() {
+                inner.refresh(txn);
+            }
+        })?;
+        let state = inner.evaluate(doc.try_transact()?.store());
+        *inner.state.lock().unwrap() = Some(state);
+        Ok(Signal {
+            inner,
+            _subscription: subscription,
+        })
+    }
+
+    /// Returns a current value.
+    pub fn get(&self) -> Arc<T> {
+        let state = self.inner.state.lock().unwrap();
+        state.as_ref().unwrap().value.clone()
+    }
+
+    /// Checks if a given shared collection has been read during the last evaluation, meaning
+    /// that changing it will cause current signal to be re-evaluated.
+    pub fn depends_on<S: AsRef<Branch>>(&self, shared_ref: &S) -> bool {
+        let ptr = BranchPtr::from(shared_ref.as_ref());
+        let state = self.inner.state.lock().unwrap();
+        state.as_ref().unwrap().dependencies.contains(&ptr)
+    }
+
+    /// Subscribes a callback `f`, which is called after a transaction commit whenever the value
+    /// has changed. This method returns a subscription, which will automatically unsubscribe
+    /// current callback when dropped.
+    pub fn observe<F>(&self, f: F) -> Subscription
+    where
+        F: Fn(&TransactionMut, &T) + Send + Sync + 'static,
+    {
+        self.inner.observers.subscribe(Box::new(f))
+    }
+}
+
+impl<T> Inner<T>
+where
+    T: PartialEq + 'static,
+{
+    fn evaluate(&self, store: &Store) -> State<T> {
+        let txn = SignalTxn {
+            store,
+            dependencies: RefCell::default(),
+        };
+        let value = Arc::new((self.read)(&txn));
+        State {
+            value,
+            dependencies: txn.dependencies.into_inner(),
+        }
+    }
+
+    fn refresh(&self, txn: &TransactionMut) {
+        let affected = match &*self.state.lock().unwrap() {
+            Some(state) => txn.changed.keys().any(|ptr| match ptr {
+                TypePtr::Branch(branch) => state.dependencies.contains(branch),
+                _ => false,
+            }),
+            None => false,
+        };
+        if !affected {
+            return;
+        }
+        let state = self.evaluate(txn.store());
+        let value = state.value.clone();
+        let prev = self.state.lock().unwrap().replace(state);
+        if prev.map(|prev| *prev.value != *value).unwrap_or(true) {
+            self.observers.trigger(|f| f(txn, &value));
+        }
+    }
+}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for Signal<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = self.inner.state.lock().unwrap();
+        f.debug_struct("Signal")
+            .field("value", &state.as_ref().map(|s| &s.value))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::signals::Signal;
+    use crate::{Array, Doc, Map, MapPrelim, MapRef, Out, Transact};
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn reevaluate_on_tracked_changes() {
+        let doc = Doc::with_client_id(1);
+        let root = doc.get_or_insert_map("root");
+        let other = doc.get_or_insert_array("other");
+        {
+            let mut txn = doc.transact_mut();
+            root.insert(&mut txn, "enabled", false);
+            root.insert(&mut txn, "a", MapPrelim::from([("value", 1)]));
+            root.insert(&mut txn, "b", MapPrelim::from([("value", 2)]));
+        }
+        let calls = Arc::new(AtomicU32::new(0));
+        let signal = Signal::new(&doc, {
+            let root = root.clone();
+            let calls = calls.clone();
+            move |txn| {
+                calls.fetch_add(1, Ordering::SeqCst);
+                // read `b` only when enabled - dependencies change between evaluations
+                let key = match root.get(txn, "enabled") {
+                    Some(Out::Any(crate::Any::Bool(true))) => "b",
+                    _ => "a",
+                };
+                let nested: MapRef = root.get(txn, key)?.cast().ok()?;
+                nested.get(txn, "value")?.cast::<i64>().ok()
+            }
+        })
+        .unwrap();
+        assert_eq!(*signal.get(), Some(1));
+        let a: MapRef = root.get(&doc.transact(), "a").unwrap().cast().unwrap();
+        let b: MapRef = root.get(&doc.transact(), "b").unwrap().cast().unwrap();
+        assert!(signal.depends_on(&root));
+        assert!(signal.depends_on(&a));
+        assert!(!signal.depends_on(&b));
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let _sub = {
+            let seen = seen.clone();
+            signal.observe(move |_, value| seen.lock().unwrap().push(*value))
+        };
+
+        other.push_back(&mut doc.transact_mut(), 1);
+        b.insert(&mut doc.transact_mut(), "value", 20);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        a.insert(&mut doc.transact_mut(), "value", 10);
+        root.insert(&mut doc.transact_mut(), "enabled", true);
+        assert!(signal.depends_on(&b));
+        assert!(!signal.depends_on(&a));
+        a.insert(&mut doc.transact_mut(), "value", 100);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        assert_eq!(*seen.lock().unwrap(), vec![Some(10), Some(20)]);
+        assert_eq!(*signal.get(), Some(20));
+    }
+}
@@ -3,10 +3,12 @@ use crate::encoding::read::Error;
 use crate::updates::decoder::{Decode, Decoder};
 use crate::updates::encoder::{Encode, Encoder};
 use crate::utils::client_hasher::ClientHasher;
-use crate::{DeleteSet, ID};
+use crate::{BlockRange, DeleteSet, ID};
+use std::cmp::Ordering;
 use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 use std::hash::BuildHasherDefault;
+use std::iter::FromIterator;
 
 /// State vector is a compact representation of all known blocks inserted and integrated into
 /// a given document. This descriptor can be serialized and used to determine a difference between
@@ -15,7 +17,7 @@ use std::hash::BuildHasherDefault;
 ///
 /// Another popular name for the concept represented by state vector is
 /// [Version Vector](https://en.wikipedia.org/wiki/Version_vector).
-#[derive(Default, Debug, Clone, PartialEq, Eq)]
+#[derive(Default, Debug, Clone)]
 pub struct StateVector(HashMap<ClientID, u32, BuildHasherDefault<ClientHasher>>);
 
 impl StateVector {
@@ -102,12 +104,85 @@ impl StateVector {
             *e = (*e).max(clock);
         }
     }
+
+    /// Returns clock ranges known to a current state vector, which are missing in the `other` one.
+    /// These are the blocks, which a peer described by the `other` state vector needs to receive
+    /// in order to catch up with current state.
+    pub fn diff(&self, other: &StateVector) -> StateDelta {
+        StateDelta::new(other, self)
+    }
+}
+
+impl FromIterator<(ClientID, u32)> for StateVector {
+    fn from_iter<T: IntoIterator<Item = (ClientID, u32)>>(iter: T) -> Self {
+        let mut sv = StateVector::default();
+        for (client, clock) in iter {
+            sv.set_max(client, clock);
+        }
+        sv
+    }
+}
+
+impl PartialEq for StateVector {
+    /// Checks if two state vectors describe the same state. Just like in [PartialOrd], clients
+    /// with a clock of 0 are treated as missing.
+    fn eq(&self, other: &Self) -> bool {
+        self.iter()
+            .all(|(client, &clock)| other.get(client) == clock)
+            && other
+                .iter()
+                .all(|(client, &clock)| self.get(client) == clock)
+    }
+}
+
+impl Eq for StateVector {}
+
+impl PartialOrd for StateVector {
+    /// Compares causal order of two state vectors, treating missing clients as having a clock of 0.
+    /// Returns:
+    ///
+    /// - `Some(Ordering::Less)` if current state vector is behind the `other` one,
+    /// - `Some(Ordering::Greater)` if current state vector is ahead of the `other` one,
+    /// - `Some(Ordering::Equal)` if both describe the same state,
+    /// - `None` if state vectors are concurrent: each one has observed blocks unknown to the other.
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        let behind = other
+            .iter()
+            .any(|(client, &clock)| self.get(client) < clock);
+        let ahead = self
+            .iter()
+            .any(|(client, &clock)| other.get(client) < clock);
+        match (behind, ahead) {
+            (false, false) => Some(Ordering::Equal),
+            (true, false) => Some(Ordering::Less),
+            (false, true) => Some(Ordering::Greater),
+            (true, true) => None,
+        }
+    }
+}
+
+impl std::fmt::Display for StateVector {
+    /// Formats state vector as a list of `client:clock` pairs ordered by client, eg. `{1:3, 2:10}`.
+    /// Clients with a clock of 0 are skipped.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut entries: Vec<_> = self.iter().filter(|(_, &clock)| clock != 0).collect();
+        entries.sort();
+        write!(f, "{{")?;
+        for (i, (client, clock)) in entries.into_iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}:{}", client, clock)?;
+        }
+        write!(f, "}}")
+    }
 }
 
 impl Decode for StateVector {
     fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, Error> {
         let len = decoder.read_var::<u32>()? as usize;
-        let mut sv = HashMap::with_capacity_and_hasher(len, BuildHasherDefault::default());
+        let mut sv = HashMap::with_hasher(BuildHasherDefault::default());
+        sv.try_reserve(decoder.bounded_len(len)?)?;
         let mut i = 0;
         while i < len {
             let client = decoder.read_var()?;
@@ -129,6 +204,113 @@ impl Encode for StateVector {
     }
 }
 
+/// Per-client clock ranges `[start, end)` which separate two [StateVector]s, ordered by client.
+///
+/// It's most commonly obtained from [TransactionMut::state_delta], where it describes the blocks
+/// inserted by a given transaction, and can be used to index replicated updates by the clock
+/// ranges they contain.
+///
+/// [TransactionMut::state_delta]: crate::TransactionMut::state_delta
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct StateDelta(Vec<BlockRange>);
+
+impl StateDelta {
+    /// Computes clock ranges present in `after` state vector, but not in `before` one. Clients,
+    /// which clocks in `after` are not greater than in `before`, are omitted.
+    pub fn new(before: &StateVector, after: &StateVector) -> Self {
+        let mut ranges: Vec<BlockRange> = after
+            .iter()
+            .filter_map(|(&client, &end)| {
+                let start = before.get(&client);
+                if end > start {
+                    Some(BlockRange::new(client, start, end))
+                } else {
+                    None
+                }
+            })
+            .collect();
+        ranges.sort_by_key(|range| range.client);
+        StateDelta(ranges)
+    }
+
+    /// Checks if current delta contains no clock ranges.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Returns a number of clients which clocks have been advanced.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns clock ranges of current delta, ordered by client.
+    pub fn ranges(&self) -> &[BlockRange] {
+        &self.0
+    }
+
+    /// Returns a clock range of a given `client`, if it's a part of current delta.
+    pub fn get(&self, client: &ClientID) -> Option<&BlockRange> {
+        let i = self.0.binary_search_by_key(client, |r| r.client).ok()?;
+        Some(&self.0[i])
+    }
+
+    /// Checks if a block with a given `id` falls into current delta.
+    pub fn contains(&self, id: &ID) -> bool {
+        match self.get(&id.client) {
+            Some(range) => range.start <= id.clock && id.clock < range.end,
+            None => false,
+        }
+    }
+
+    /// Returns a state vector made of lower bounds of all clock ranges.
+    pub fn start(&self) -> StateVector {
+        let mut sv = StateVector::default();
+        for range in self.0.iter() {
+            sv.set_max(range.client, range.start);
+        }
+        sv
+    }
+
+    /// Returns a state vector made of upper bounds of all clock ranges.
+    pub fn end(&self) -> StateVector {
+        let mut sv = StateVector::default();
+        for range in self.0.iter() {
+            sv.set_max(range.client, range.end);
+        }
+        sv
+    }
+}
+
+impl Decode for StateDelta {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, Error> {
+        let len = decoder.read_var::<u32>()? as usize;
+        let mut ranges: Vec<BlockRange> = Vec::new();
+        ranges.try_reserve(decoder.bounded_len(len)?)?;
+        for _ in 0..len {
+            let client = decoder.read_var()?;
+            let start: u32 = decoder.read_var()?;
+            let len: u32 = decoder.read_var()?;
+            let end = start.checked_add(len).ok_or(Error::UnexpectedValue)?;
+            if len == 0 || ranges.last().map(|r| r.client >= client).unwrap_or(false) {
+                return Err(Error::UnexpectedValue);
+            }
+            ranges.push(BlockRange::new(client, start, end));
+        }
+        Ok(StateDelta(ranges))
+    }
+}
+
+impl Encode for StateDelta {
+    fn encode<E: Encoder>(&self, encoder: &mut E) {
+        encoder.write_var(self.len());
+        for range in self.0.iter() {
+            encoder.write_var(range.client);
+            encoder.write_var(range.start);
+            encoder.write_var(range.len());
+        }
+    }
+}
+
 /// Snapshot describes a state of a document store at a given point in (logical) time. In practice
 /// it's a combination of [StateVector] (a summary of all observed insert/update operations)
 /// and a [DeleteSet] (a summary of all observed deletions).
@@ -167,3 +349,42 @@ impl Decode for Snapshot {
         Ok(Snapshot::new(sm, ds))
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{BlockRange, StateVector};
+    use std::cmp::Ordering;
+    use std::iter::FromIterator;
+
+    #[test]
+    fn state_vector_algebra() {
+        let a = StateVector::from_iter([(1, 3), (2, 5)]);
+        let b = StateVector::from_iter([(1, 3), (2, 7), (3, 0)]);
+        let c = StateVector::from_iter([(1, 4), (2, 2)]);
+
+        assert_eq!(a.partial_cmp(&b), Some(Ordering::Less));
+        assert_eq!(b.partial_cmp(&a), Some(Ordering::Greater));
+        assert_eq!(a.partial_cmp(&a.clone()), Some(Ordering::Equal));
+        assert_eq!(a.partial_cmp(&c), None);
+        assert!(a < b);
+
+        assert_eq!(b.diff(&a).ranges(), &[BlockRange::new(2, 5, 7)]);
+        assert_eq!(a.diff(&c).ranges(), &[BlockRange::new(2, 2, 5)]);
+        assert!(a.diff(&b).is_empty());
+
+        let mut merged = a.clone();
+        merged.merge(c);
+        assert_eq!(merged.to_string(), "{1:4, 2:5}");
+        assert_eq!(StateVector::default().to_string(), "{}");
+    }
+
+    #[test]
+    fn state_vector_zero_clocks() {
+        let a = StateVector::from_iter([(1, 3)]);
+        let b = StateVector::from_iter([(1, 3), (2, 0)]);
+        assert_eq!(a, b);
+        assert_eq!(a.partial_cmp(&b), Some(Ordering::Equal));
+        assert_eq!(b.to_string(), "{1:3}");
+        assert_ne!(a, StateVector::from_iter([(1, 3), (2, 1)]));
+    }
+}
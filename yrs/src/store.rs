@@ -1,25 +1,35 @@
-use crate::block::{BlockCell, ClientID, ItemContent, ItemPtr};
+use crate::blame::BlockMetaLog;
+use crate::block::{BlockCell, ClientID, Item, ItemContent, ItemPtr};
 use crate::block_store::BlockStore;
-use crate::branch::{Branch, BranchPtr};
+use crate::branch::{Branch, BranchPtr, TypeRepair};
+use crate::delta_buffer::DeltaBuffer;
+use crate::destructive::{DestructiveOp, DestructivePolicy};
 use crate::doc::{DocAddr, Options};
 use crate::error::Error;
-use crate::event::SubdocsEvent;
+use crate::event::{RootsEvent, SubdocsEvent};
 use crate::id_set::DeleteSet;
-use crate::slice::ItemSlice;
-use crate::types::{Path, PathSegment, TypeRef};
-use crate::update::PendingUpdate;
-use crate::updates::encoder::{Encode, Encoder};
+use crate::observer::{call_isolated, CallbackError};
+use crate::pending::{PendingEvictionEvent, PendingState};
+use crate::slice::{BlockSlice, GCSlice, ItemSlice};
+use crate::sync::{Clock, Timestamp};
+use crate::types::map::ConflictResolver;
+use crate::types::{Path, PathSegment, TypePtr, TypeRef};
+use crate::update::{PendingUpdate, Update};
+use crate::updates::encoder::{Encode, Encoder, EncoderV1};
+use crate::xml_index::XmlIdIndex;
 use crate::StateVector;
 use crate::{
-    Doc, Observer, OffsetKind, Snapshot, TransactionCleanupEvent, TransactionMut, UpdateEvent,
-    Uuid, ID,
+    merge_updates_v1, merge_updates_v2, Doc, Observer, OffsetKind, Origin, Snapshot,
+    TransactionCleanupEvent, TransactionMut, UpdateEvent, Uuid, ID,
 };
 use atomic_refcell::{AtomicRef, AtomicRefCell, AtomicRefMut, BorrowError, BorrowMutError};
 use std::borrow::Borrow;
+use std::cmp::Reverse;
 use std::collections::hash_map::Entry;
-use std::collections::{HashMap, HashSet};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::ops::Deref;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 
 /// Store is a core element of a document. It contains all of the information, like block store
 /// map of root types, pending updates waiting to be applied once a missing update information
@@ -32,6 +42,14 @@ pub struct Store {
     /// which can be called concurrently by remote peers in a conflict-free manner.
     pub(crate) types: HashMap<Arc<str>, Arc<Branch>>,
 
+    /// Root types defined since the last transaction commit. Reported to [Doc::observe_roots]
+    /// subscribers.
+    pub(crate) new_roots: Vec<BranchPtr>,
+
+    /// Root types, which have been accessed as a different type since the last transaction
+    /// commit. Reported to [Doc::observe_type_repairs] subscribers.
+    pub(crate) type_repairs: Vec<TypeRepair>,
+
     /// Registry of all alive nodes in the document store.
     pub(crate) node_registry: HashSet<BranchPtr>,
 
@@ -49,6 +67,13 @@ pub struct Store {
     /// into `blocks`.
     pub(crate) pending_ds: Option<DeleteSet>,
 
+    /// Limits on the pending update configured with [Doc::set_pending_policy].
+    pub(crate) pending_state: Option<Box<PendingState>>,
+
+    /// Configuration of destructive operations reported to [Doc::observe_destructive_ops]
+    /// subscribers.
+    pub(crate) destructive_policy: DestructivePolicy,
+
     pub(crate) subdocs: HashMap<DocAddr, Doc>,
 
     pub(crate) events: Option<Box<StoreEvents>>,
@@ -59,6 +84,111 @@ pub struct Store {
 
     /// Dependencies between items and weak links pointing to these items.
     pub(crate) linked_by: HashMap<ItemPtr, HashSet<BranchPtr>>,
+
+    /// Weak links, which quoted elements have been deleted. They are invalidated once their
+    /// quoted elements have been garbage collected.
+    #[cfg(feature = "weak")]
+    pub(crate) orphaned_links: HashSet<BranchPtr>,
+
+    /// Buffer of the most recent updates, used to serve diffs for nearly up-to-date peers.
+    pub(crate) delta_buffer: Option<Box<DeltaBuffer>>,
+
+    /// Index of XML elements by the value of a designated attribute.
+    pub(crate) xml_id_index: Option<Box<XmlIdIndex>>,
+
+    /// Maps which keep a limited number of overwritten values per key instead of garbage
+    /// collecting them. See [MapRef::set_history_limit].
+    pub(crate) map_history: HashMap<BranchPtr, u32>,
+
+    /// Resolvers of concurrent writes to the keys of maps. See [MapRef::set_resolver].
+    pub(crate) map_resolvers: HashMap<BranchPtr, HashMap<Arc<str>, ConflictResolver>>,
+
+    /// Identifiers of blocks, which should never be squashed together with their left neighbors,
+    /// together with a number of times each of them has been marked. See [TransactionMut::split_point].
+    pub(crate) split_points: HashMap<ID, u32>,
+
+    /// Delete sets of the most recent transactions, which tombstones should be kept according to
+    /// [crate::GcPolicy::keep_recent].
+    pub(crate) recent_deletes: VecDeque<DeleteSet>,
+
+    /// The most recent segment of a log of blocks deleted since [StoreCheckpoint]s have been made.
+    /// Present only as long as any checkpoint is alive.
+    pub(crate) delete_log: Mutex<Option<Arc<DeleteLog>>>,
+
+    /// The most recent copy made by [crate::ReadTxn::read_only_copy], shared by all copies made
+    /// until the document changes.
+    pub(crate) frozen_copy: Mutex<Option<FrozenCopy>>,
+
+    /// Cleanup hooks registered with [Doc::on_close], together with their execution order.
+    pub(crate) close_hooks: Vec<(i32, CloseHook)>,
+
+    /// Timestamps and authors of blocks. See [Doc::record_block_meta].
+    pub(crate) block_meta: Option<Box<BlockMetaLog>>,
+
+    /// Maps origins of current document transactions onto the origins of transactions started
+    /// over its sub-documents. See [Doc::map_subdoc_origin].
+    pub(crate) subdoc_origin: Option<SubdocOriginFn>,
+}
+
+/// A point in the history of a document created with [Store::checkpoint]. Document state can be
+/// rolled back to it using [TransactionMut::restore].
+#[derive(Debug, Clone)]
+pub struct StoreCheckpoint {
+    pub(crate) state: StateVector,
+    pub(crate) log: Arc<DeleteLog>,
+}
+
+impl StoreCheckpoint {
+    /// Returns a state vector of a document at the moment when current checkpoint was made.
+    pub fn state_vector(&self) -> &StateVector {
+        &self.state
+    }
+}
+
+impl PartialEq for StoreCheckpoint {
+    fn eq(&self, other: &Self) -> bool {
+        self.state == other.state && Arc::ptr_eq(&self.log, &other.log)
+    }
+}
+
+/// A segment of a log of blocks deleted since a [StoreCheckpoint] has been made. Every checkpoint
+/// holds a segment, which was the most recent one at the moment when it was made, while the store
+/// appends deleted blocks to the most recent segment only. This way checkpoints made at different
+/// times share the common tail of the log, and the log is dropped once no checkpoint refers to it.
+#[derive(Debug, Default)]
+pub(crate) struct DeleteLog {
+    deleted: Mutex<DeleteSet>,
+    next: Mutex<Option<Arc<DeleteLog>>>,
+}
+
+/// A read-only copy of a document together with a checkpoint of a state it was made at.
+pub(crate) struct FrozenCopy {
+    checkpoint: StoreCheckpoint,
+    roots: usize,
+    doc: Doc,
+}
+
+/// A continuous range of block clocks `[start, end)` produced by a single `client`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BlockRange {
+    pub client: ClientID,
+    pub start: u32,
+    pub end: u32,
+}
+
+impl BlockRange {
+    pub fn new(client: ClientID, start: u32, end: u32) -> Self {
+        BlockRange { client, start, end }
+    }
+
+    /// Returns the number of clock ticks covered by current range.
+    pub fn len(&self) -> u32 {
+        self.end.saturating_sub(self.start)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
 }
 
 impl Store {
@@ -67,14 +197,31 @@ impl Store {
         Store {
             options,
             types: HashMap::default(),
+            new_roots: Vec::default(),
+            type_repairs: Vec::default(),
             node_registry: HashSet::default(),
             blocks: BlockStore::default(),
             subdocs: HashMap::default(),
             linked_by: HashMap::default(),
+            #[cfg(feature = "weak")]
+            orphaned_links: HashSet::default(),
             events: None,
             pending: None,
             pending_ds: None,
+            pending_state: None,
+            destructive_policy: DestructivePolicy::default(),
             parent: None,
+            delta_buffer: None,
+            xml_id_index: None,
+            map_history: HashMap::default(),
+            map_resolvers: HashMap::default(),
+            split_points: HashMap::default(),
+            recent_deletes: VecDeque::default(),
+            delete_log: Mutex::default(),
+            frozen_copy: Mutex::default(),
+            close_hooks: Vec::default(),
+            block_meta: None,
+            subdoc_origin: None,
         }
     }
 
@@ -90,10 +237,270 @@ impl Store {
         self.pending_ds.as_ref()
     }
 
+    /// Returns ranges of blocks, which are missing in order to integrate a pending update (see:
+    /// [Store::pending_update]). These can be requested from the remote peers, i.e. using
+    /// [SyncMessage::Missing].
+    ///
+    /// [SyncMessage::Missing]: crate::sync::SyncMessage::Missing
+    pub fn missing_ranges(&self) -> Vec<BlockRange> {
+        match self.pending.as_ref() {
+            None => Vec::new(),
+            Some(pending) => {
+                let local = self.blocks.get_state_vector();
+                pending
+                    .update
+                    .blocks
+                    .missing_ranges(&local)
+                    .into_iter()
+                    .map(|(client, start, end)| BlockRange::new(client, start, end))
+                    .collect()
+            }
+        }
+    }
+
     pub fn is_subdoc(&self) -> bool {
         self.parent.is_some()
     }
 
+    /// Returns an origin of a transaction started over a given `subdoc` as a consequence of
+    /// a transaction with a given `origin` committed over current document.
+    pub(crate) fn subdoc_origin(&self, origin: Option<&Origin>, subdoc: &Doc) -> Option<Origin> {
+        let origin = origin?;
+        match self.subdoc_origin.as_ref() {
+            Some(f) => f(origin, subdoc),
+            None => Some(origin.clone()),
+        }
+    }
+
+    /// Creates a checkpoint, which current document state can be rolled back to later on using
+    /// [TransactionMut::restore], i.e. to discard speculative edits previewed before being
+    /// accepted. Only a state vector is captured, so the cost of a checkpoint doesn't depend on
+    /// the size of a document. As long as a checkpoint is alive, the store keeps a log of blocks
+    /// deleted since it has been made, which is shared with all checkpoints made later on.
+    /// Checkpoints can only be restored in documents with [Options::skip_gc] enabled.
+    pub fn checkpoint(&self) -> StoreCheckpoint {
+        let mut tail = self.delete_log.lock().unwrap();
+        let log = match tail.as_ref() {
+            // nothing has been deleted since the last checkpoint, so their logs are the same
+            Some(log) if log.deleted.lock().unwrap().is_empty() => log.clone(),
+            _ => {
+                let log = Arc::new(DeleteLog::default());
+                if let Some(prev) = tail.replace(log.clone()) {
+                    *prev.next.lock().unwrap() = Some(log.clone());
+                }
+                log
+            }
+        };
+        StoreCheckpoint {
+            state: self.blocks.get_state_vector(),
+            log,
+        }
+    }
+
+    /// Records a deleted block range in a log of alive checkpoints (see: [Store::checkpoint]).
+    pub(crate) fn log_delete(&mut self, id: ID, len: u32) {
+        let tail = self.delete_log.get_mut().unwrap();
+        if let Some(log) = tail.as_ref() {
+            if Arc::strong_count(log) == 1 {
+                // no checkpoint refers to the log anymore
+                *tail = None;
+            } else {
+                log.deleted.lock().unwrap().insert(id, len);
+            }
+        }
+    }
+
+    /// Returns blocks deleted since a given `checkpoint` has been made. Fails if checkpoint
+    /// was not made by current store or has been discarded by restoring an earlier checkpoint.
+    pub(crate) fn deleted_since(&self, checkpoint: &StoreCheckpoint) -> Result<DeleteSet, Error> {
+        let tail = self.delete_log.lock().unwrap();
+        let mut deleted = DeleteSet::new();
+        let mut log = checkpoint.log.clone();
+        loop {
+            deleted.merge(log.deleted.lock().unwrap().clone());
+            let next = log.next.lock().unwrap().clone();
+            match next {
+                Some(next) => log = next,
+                None => break,
+            }
+        }
+        match tail.as_ref() {
+            Some(tail) if Arc::ptr_eq(tail, &log) => {
+                deleted.squash();
+                Ok(deleted)
+            }
+            _ => Err(Error::Validation(
+                "checkpoint doesn't belong to current document state".into(),
+            )),
+        }
+    }
+
+    /// Resets a log of a given `checkpoint` after the document has been rolled back to it.
+    /// All checkpoints made after it are discarded.
+    pub(crate) fn reset_delete_log(&mut self, checkpoint: &StoreCheckpoint) {
+        let log = &checkpoint.log;
+        *log.deleted.lock().unwrap() = DeleteSet::new();
+        let mut next = log.next.lock().unwrap().take();
+        while let Some(log) = next {
+            // break the links, so that later checkpoints no longer reach the store log
+            next = log.next.lock().unwrap().take();
+        }
+        *self.delete_log.get_mut().unwrap() = Some(log.clone());
+    }
+
+    /// Returns a read-only document with a copy of current store (see: [Store::deep_copy]). Copies
+    /// made while the store doesn't change share the same blocks.
+    pub(crate) fn read_only_copy(&self) -> Doc {
+        let checkpoint = self.checkpoint();
+        let mut frozen = self.frozen_copy.lock().unwrap();
+        if let Some(copy) = frozen.as_ref() {
+            if copy.checkpoint == checkpoint && copy.roots == self.types.len() {
+                return copy.doc.clone();
+            }
+        }
+        let doc = Doc::from_store(self.deep_copy());
+        *frozen = Some(FrozenCopy {
+            checkpoint,
+            roots: self.types.len(),
+            doc: doc.clone(),
+        });
+        doc
+    }
+
+    /// Returns a deep copy of all blocks and root types integrated into current store, which can
+    /// be read independently of it. Pointers between copied blocks and branches are rewired to
+    /// point to the copies. Returned store has no event subscribers and gets its own guid and
+    /// client ID, so that it's never mistaken for the original document.
+    /// Sub-documents are shared with current store rather than copied.
+    pub(crate) fn deep_copy(&self) -> Store {
+        let defaults = Options::default();
+        let mut options = self.options.clone();
+        options.guid = defaults.guid;
+        options.client_id = defaults.client_id;
+        let mut store = Store::new(options);
+        let mut items: HashMap<ItemPtr, ItemPtr> = HashMap::new();
+        let mut branches: HashMap<BranchPtr, BranchPtr> = HashMap::new();
+        for (name, branch) in self.types.iter() {
+            let copy = Self::copy_branch(branch);
+            branches.insert(BranchPtr::from(branch), BranchPtr::from(&copy));
+            store.types.insert(name.clone(), copy);
+        }
+        for (&client, blocks) in self.blocks.iter() {
+            for cell in blocks.iter() {
+                match cell {
+                    BlockCell::GC(gc) => {
+                        let id = ID::new(client, gc.start);
+                        store
+                            .blocks
+                            .push_gc(crate::block::BlockRange::new(id, gc.end - gc.start + 1));
+                    }
+                    BlockCell::Block(item) => {
+                        let content = match &item.content {
+                            ItemContent::Type(branch) => {
+                                let copy = Self::copy_branch(branch);
+                                branches.insert(BranchPtr::from(branch), BranchPtr::from(&copy));
+                                ItemContent::Type(copy)
+                            }
+                            other => other.clone(),
+                        };
+                        let mut copy = Box::new(Item {
+                            id: item.id,
+                            len: item.len,
+                            left: item.left,
+                            right: item.right,
+                            origin: item.origin,
+                            right_origin: item.right_origin,
+                            content,
+                            parent: item.parent.clone(),
+                            redone: item.redone,
+                            parent_sub: item.parent_sub.clone(),
+                            moved: item.moved,
+                            info: item.info,
+                        });
+                        items.insert(ItemPtr::from(item), ItemPtr::from(&mut copy));
+                        store.blocks.push_block(copy);
+                    }
+                }
+            }
+        }
+
+        // rewire pointers to the copied blocks and branches
+        let item = |ptr: Option<ItemPtr>| ptr.and_then(|ptr| items.get(&ptr).copied());
+        for &ptr in items.values() {
+            let mut copy = ptr;
+            copy.left = item(copy.left);
+            copy.right = item(copy.right);
+            copy.moved = item(copy.moved);
+            if let TypePtr::Branch(parent) = &copy.parent {
+                if let Some(&parent) = branches.get(parent) {
+                    copy.parent = TypePtr::Branch(parent);
+                }
+            }
+            if let ItemContent::Move(m) = &mut copy.content {
+                if let Some(overrides) = m.overrides.as_mut() {
+                    *overrides = overrides.iter().filter_map(|&p| item(Some(p))).collect();
+                }
+            }
+        }
+        for &ptr in branches.values() {
+            let mut copy = ptr;
+            copy.start = item(copy.start);
+            copy.item = item(copy.item);
+            for value in copy.map.values_mut() {
+                *value = items[value];
+            }
+            #[cfg(feature = "weak")]
+            if let TypeRef::WeakLink(source) = &copy.type_ref {
+                let first_item = source.first_item.get().and_then(|p| item(Some(*p)));
+                let source = crate::types::weak::LinkSource::new(
+                    source.quote_start.clone(),
+                    source.quote_end.clone(),
+                );
+                if let Some(first_item) = first_item {
+                    source.first_item.swap(first_item);
+                }
+                copy.type_ref = TypeRef::WeakLink(Arc::new(source));
+            }
+        }
+        store.node_registry = (self.node_registry.iter())
+            .filter_map(|b| branches.get(b).copied())
+            .collect();
+        for (ptr, links) in self.linked_by.iter() {
+            if let Some(&ptr) = items.get(ptr) {
+                let links = links.iter().filter_map(|b| branches.get(b).copied());
+                store.linked_by.insert(ptr, links.collect());
+            }
+        }
+        #[cfg(feature = "weak")]
+        {
+            store.orphaned_links = (self.orphaned_links.iter())
+                .filter_map(|b| branches.get(b).copied())
+                .collect();
+        }
+        if let Some(index) = self.xml_id_index.as_deref() {
+            let mut index = XmlIdIndex::new(index.attr().clone());
+            for root in store.types.values() {
+                index.index_tree(BranchPtr::from(root));
+            }
+            store.xml_id_index = Some(Box::new(index));
+        }
+        store
+    }
+
+    /// Creates a copy of a given `branch`, which still points to the blocks of the original.
+    fn copy_branch(branch: &Branch) -> Arc<Branch> {
+        let mut copy = Branch::new(branch.type_ref.clone());
+        let inner = Arc::get_mut(&mut copy).unwrap();
+        inner.start = branch.start;
+        inner.map = branch.map.clone();
+        inner.item = branch.item;
+        inner.name = branch.name.clone();
+        inner.block_len = branch.block_len;
+        inner.content_len = branch.content_len;
+        inner.map_len = branch.map_len;
+        copy
+    }
+
     /// Get the latest clock sequence number observed and integrated into a current store client.
     /// This is exclusive value meaning it describes a clock value of the beginning of the next
     /// block that's about to be inserted. You cannot use that clock value to find any existing
@@ -116,20 +523,43 @@ impl Store {
         key: K,
         type_ref: TypeRef,
     ) -> BranchPtr {
+        match self.try_get_or_create_type(key, type_ref) {
+            Ok(branch) => branch,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Returns a branch reference to a complex type identified by its name, creating it if it
+    /// didn't exist. If the type already exists but was defined with a different type, this change
+    /// is recorded for [Doc::observe_type_repairs] subscribers - or an error is returned if
+    /// [Options::strict_types] is enabled and the types are in conflict.
+    pub(crate) fn try_get_or_create_type<K: Into<Arc<str>>>(
+        &mut self,
+        key: K,
+        type_ref: TypeRef,
+    ) -> Result<BranchPtr, TypeRepair> {
         let key = key.into();
+        let strict = self.options.strict_types;
         match self.types.entry(key.clone()) {
             Entry::Occupied(mut e) => {
                 let branch = Arc::get_mut(e.get_mut()).unwrap();
+                if let Some(repair) = branch.type_repair(&type_ref) {
+                    if strict && repair.conflict {
+                        return Err(repair);
+                    }
+                    self.type_repairs.push(repair);
+                }
                 branch.repair_type_ref(type_ref);
-                BranchPtr::from(e.get_mut())
+                Ok(BranchPtr::from(e.get_mut()))
             }
             Entry::Vacant(e) => {
                 let mut branch = Branch::new(type_ref);
                 let mut branch_ref = BranchPtr::from(&mut branch);
                 branch_ref.name = Some(key);
                 self.node_registry.insert(branch_ref);
+                self.new_roots.push(branch_ref);
                 e.insert(branch);
-                branch_ref
+                Ok(branch_ref)
             }
         }
     }
@@ -142,7 +572,7 @@ impl Store {
         encoder: &mut E,
     ) -> Result<(), Error> {
         if !self.options.skip_gc {
-            return Err(Error::Gc);
+            return Err(Error::gc_enabled());
         }
         self.write_blocks_to(&snapshot.state_map, encoder);
         snapshot.delete_set.encode(encoder);
@@ -150,7 +580,9 @@ impl Store {
         Ok(())
     }
 
-    pub(crate) fn write_blocks_to<E: Encoder>(&self, sv: &StateVector, encoder: &mut E) {
+    /// Writes all blocks known to a current store up to a given state vector `sv` (exclusive)
+    /// into a given `encoder` (without a delete set).
+    pub fn write_blocks_to<E: Encoder>(&self, sv: &StateVector, encoder: &mut E) {
         let local_sv = self.blocks.get_state_vector();
         let mut diff = Vec::with_capacity(sv.len());
         for (&client_id, &clock) in sv.iter() {
@@ -201,34 +633,246 @@ impl Store {
         delete_set.encode(encoder);
     }
 
-    pub(crate) fn write_blocks_from<E: Encoder>(&self, sv: &StateVector, encoder: &mut E) {
-        let local_sv = self.blocks.get_state_vector();
-        let mut diff = Self::diff_state_vectors(&local_sv, sv);
+    /// Returns a diff for a given state vector `sv` computed from the buffer of recent updates,
+    /// if it's enabled and contains all blocks missing by `sv`. State vectors don't tell which
+    /// deletions a peer is missing, so a diff always carries a full delete set of this store.
+    pub(crate) fn buffered_diff(&self, sv: &StateVector) -> Option<Update> {
+        let mut update = self.delta_buffer.as_ref()?.diff(sv)?;
+        update.delete_set = DeleteSet::from(&self.blocks);
+        Some(update)
+    }
+
+    /// Writes all blocks missing by a given state vector `sv` into a given `encoder` (without
+    /// a delete set).
+    pub fn write_blocks_from<E: Encoder>(&self, sv: &StateVector, encoder: &mut E) {
+        let ranges = self.block_ranges_from(sv);
+        self.write_block_ranges(&ranges, encoder);
+    }
 
+    /// Returns ranges of blocks missing by a given state vector `sv`, one per client. Returned
+    /// ranges can be split further (i.e. by clock windows) and passed over to
+    /// [Store::write_block_ranges], which enables transferring the document state in chunks
+    /// and resuming the transfer from the last sent range.
+    pub fn block_ranges_from(&self, sv: &StateVector) -> Vec<BlockRange> {
+        let local_sv = self.blocks.get_state_vector();
+        let diff = Self::diff_state_vectors(&local_sv, sv);
+        let mut ranges: Vec<_> = diff
+            .into_iter()
+            .map(|(client, clock)| BlockRange::new(client, clock, local_sv.get(&client)))
+            .collect();
         // Write items with higher client ids first
         // This heavily improves the conflict algorithm.
-        diff.sort_by(|a, b| b.0.cmp(&a.0));
+        ranges.sort_by_key(|r| std::cmp::Reverse(r.client));
+        ranges
+    }
 
-        encoder.write_var(diff.len());
-        for (client, clock) in diff {
-            let blocks = self.blocks.get_client(&client).unwrap();
-            let clock = clock.max(blocks.get(0).map(|i| i.clock_start()).unwrap_or_default()); // make sure the first id exists
-            let start = blocks.find_pivot(clock).unwrap();
+    /// Writes blocks from given `ranges` into a given `encoder` (without a delete set), in the
+    /// same format as [Store::write_blocks_from]. Blocks crossing the range boundaries are
+    /// trimmed. Parts of ranges outside of the blocks known to a current store are skipped.
+    pub fn write_block_ranges<E: Encoder>(&self, ranges: &[BlockRange], encoder: &mut E) {
+        let mut to_write = Vec::with_capacity(ranges.len());
+        for range in ranges {
+            if let Some(blocks) = self.blocks.get_client(&range.client) {
+                // make sure the first id exists
+                let first_clock = blocks.get(0).map(|i| i.clock_start()).unwrap_or_default();
+                let start = range.start.max(first_clock);
+                let end = range.end.min(blocks.clock());
+                if start < end {
+                    to_write.push((range.client, blocks, start, end));
+                }
+            }
+        }
+
+        encoder.write_var(to_write.len());
+        for (client, blocks, start, end) in to_write {
+            let first = blocks.find_pivot(start).unwrap();
+            let last = blocks.find_pivot(end - 1).unwrap();
             // write # encoded structs
-            encoder.write_var(blocks.len() - start);
+            encoder.write_var(last - first + 1);
             encoder.write_client(client);
-            encoder.write_var(clock);
-            let first_block = blocks.get(start).unwrap();
-            // write first struct with an offset
-            let offset = clock - first_block.clock_start();
-            let mut slice = first_block.as_slice();
-            slice.trim_start(offset);
-            slice.encode(encoder);
-            for i in (start + 1)..blocks.len() {
-                let block = &blocks[i];
-                block.as_slice().encode(encoder);
+            encoder.write_var(start);
+            for i in first..=last {
+                let mut slice = blocks[i].as_slice();
+                if i == first {
+                    // write first struct with an offset
+                    slice.trim_start(start - slice.clock_start());
+                }
+                if i == last {
+                    slice.trim_end(slice.clock_end() - (end - 1));
+                }
+                slice.encode(encoder);
+            }
+        }
+    }
+
+    /// Splits blocks missing by a given state vector `sv` into groups of [BlockRange]s, so that
+    /// each group - once written with [Store::write_block_ranges] using lib0 v1 encoding, followed
+    /// by an empty delete set - doesn't exceed `max_bytes`. Ranges are split only along block
+    /// boundaries, therefore a single block bigger than `max_bytes` ends up in a group of its own.
+    pub(crate) fn chunk_block_ranges(
+        &self,
+        sv: &StateVector,
+        max_bytes: usize,
+    ) -> Vec<Vec<BlockRange>> {
+        // upper bounds of var-int encoded headers: number of clients + empty delete set
+        const CHUNK_HEADER_LEN: usize = 6;
+        // number of structs + client id + start clock
+        const RANGE_HEADER_LEN: usize = 20;
+
+        let mut chunks = Vec::new();
+        let mut chunk = Vec::new();
+        let mut chunk_len = CHUNK_HEADER_LEN;
+        for range in self.block_ranges_from(sv) {
+            let blocks = match self.blocks.get_client(&range.client) {
+                Some(blocks) => blocks,
+                None => continue,
+            };
+            let first = match blocks.find_pivot(range.start) {
+                Some(first) => first,
+                None => continue,
+            };
+            let mut current: Option<BlockRange> = None;
+            for i in first..blocks.len() {
+                let mut slice = blocks[i].as_slice();
+                if slice.clock_start() < range.start {
+                    slice.trim_start(range.start - slice.clock_start());
+                }
+                let mut encoder = EncoderV1::new();
+                slice.encode(&mut encoder);
+                let block_len = encoder.to_vec().len();
+                let mut len = match current {
+                    None => RANGE_HEADER_LEN + block_len,
+                    Some(_) => block_len,
+                };
+                let is_empty = current.is_none() && chunk.is_empty();
+                if !is_empty && chunk_len + len > max_bytes {
+                    chunk.extend(current.take());
+                    chunks.push(std::mem::take(&mut chunk));
+                    chunk_len = CHUNK_HEADER_LEN;
+                    len = RANGE_HEADER_LEN + block_len;
+                }
+                chunk_len += len;
+                let end = slice.clock_end() + 1;
+                match &mut current {
+                    Some(current) => current.end = end,
+                    None => current = Some(BlockRange::new(range.client, slice.clock_start(), end)),
+                }
+            }
+            chunk.extend(current);
+        }
+        if !chunk.is_empty() {
+            chunks.push(chunk);
+        }
+        chunks
+    }
+
+    /// Splits a given delete set `ds` into smaller ones, so that each of them - once encoded as
+    /// a lib0 v1 update with no blocks - doesn't exceed `max_bytes`. A single deleted range is
+    /// never split, therefore delete set always produces at least one chunk.
+    pub(crate) fn chunk_delete_set(ds: &DeleteSet, max_bytes: usize) -> Vec<DeleteSet> {
+        // upper bounds of var-int encoded headers: empty block list + number of clients
+        const CHUNK_HEADER_LEN: usize = 6;
+        // client id + number of ranges
+        const CLIENT_HEADER_LEN: usize = 15;
+        // start clock + length
+        const RANGE_LEN: usize = 10;
+
+        let mut chunks = Vec::new();
+        let mut chunk = DeleteSet::new();
+        let mut chunk_len = CHUNK_HEADER_LEN;
+        for (&client, ranges) in ds.iter() {
+            let mut has_client = false;
+            for range in ranges.iter() {
+                let mut len = match has_client {
+                    true => RANGE_LEN,
+                    false => CLIENT_HEADER_LEN + RANGE_LEN,
+                };
+                if !chunk.is_empty() && chunk_len + len > max_bytes {
+                    chunks.push(std::mem::take(&mut chunk));
+                    chunk_len = CHUNK_HEADER_LEN;
+                    len = CLIENT_HEADER_LEN + RANGE_LEN;
+                }
+                chunk_len += len;
+                has_client = true;
+                chunk.insert(ID::new(client, range.start), range.end - range.start);
+            }
+        }
+        if !chunk.is_empty() || chunks.is_empty() {
+            chunks.push(chunk);
+        }
+        chunks
+    }
+
+    /// Returns identifiers of all clients, which have contributed blocks to a given shared
+    /// collection `root` or any of the collections nested inside of it.
+    pub(crate) fn subtree_clients(&self, root: BranchPtr) -> Vec<ClientID> {
+        let mut clients = Vec::new();
+        for (&client, blocks) in self.blocks.iter() {
+            let contributed = blocks
+                .iter()
+                .filter_map(BlockCell::as_item)
+                .any(|item| root.is_parent_of(Some(item)));
+            if contributed {
+                clients.push(client);
+            }
+        }
+        clients
+    }
+
+    /// Encodes all blocks of given `clients`, which belong to a shared collection `root` (or any of
+    /// the collections nested inside of it) together with a matching delete set.
+    ///
+    /// Blocks outside of `root` sub-tree are encoded as GC ranges, so that the encoded update
+    /// doesn't contain any clock gaps and can be integrated without waiting for missing updates.
+    pub(crate) fn encode_subtree<E: Encoder>(
+        &self,
+        root: BranchPtr,
+        clients: &[ClientID],
+        encoder: &mut E,
+    ) {
+        let mut client_blocks: Vec<_> = clients
+            .iter()
+            .filter_map(|client| Some((*client, self.blocks.get_client(client)?)))
+            .collect();
+        // Write items with higher client ids first
+        client_blocks.sort_by_key(|(client, _)| Reverse(*client));
+
+        encoder.write_var(client_blocks.len());
+        for (client, blocks) in client_blocks {
+            let mut slices: Vec<BlockSlice> = Vec::with_capacity(blocks.len());
+            for block in blocks.iter() {
+                match block.as_item() {
+                    Some(item) if root.is_parent_of(Some(item)) => {
+                        slices.push(BlockSlice::Item(ItemSlice::from(item)))
+                    }
+                    _ => {
+                        let (start, end) = block.clock_range();
+                        if let Some(BlockSlice::GC(gc)) = slices.last_mut() {
+                            gc.end = end;
+                        } else {
+                            slices.push(BlockSlice::GC(GCSlice { start, end }));
+                        }
+                    }
+                }
+            }
+            encoder.write_var(slices.len());
+            encoder.write_client(client);
+            encoder.write_var(0);
+            for slice in slices.iter() {
+                slice.encode(encoder);
+            }
+        }
+
+        // deleted ranges outside of `root` sub-tree are GC ranges anyway
+        let mut delete_set = DeleteSet::new();
+        for (client, ranges) in DeleteSet::from(&self.blocks).iter() {
+            if clients.contains(client) {
+                for range in ranges.iter() {
+                    delete_set.insert(ID::new(*client, range.start), range.end - range.start);
+                }
             }
         }
+        delete_set.encode(encoder);
     }
 
     fn diff_state_vectors(local_sv: &StateVector, remote_sv: &StateVector) -> Vec<(ClientID, u32)> {
@@ -368,6 +1012,8 @@ impl Store {
     pub(crate) fn deregister(&mut self, branch: &mut Arc<Branch>) {
         let ptr = BranchPtr::from(branch);
         self.node_registry.remove(&ptr);
+        self.map_history.remove(&ptr);
+        self.map_resolvers.remove(&ptr);
     }
 }
 
@@ -417,28 +1063,58 @@ impl std::fmt::Display for Store {
     }
 }
 
+/// Document [Store] shared between all references to the same document.
+#[derive(Debug)]
+pub(crate) struct StoreCell {
+    pub(crate) store: AtomicRefCell<Store>,
+    /// Set once [Doc::close] has been called. Closed documents refuse to start new read-write
+    /// transactions. Kept outside of the store, so that it can be checked without borrowing it.
+    pub(crate) closed: AtomicBool,
+}
+
+// Access to the store is synchronized by its atomic ref cell, the same way it is for all [Doc]s
+// sharing it.
+unsafe impl Send for StoreCell {}
+unsafe impl Sync for StoreCell {}
+
 #[repr(transparent)]
 #[derive(Debug, Clone)]
-pub(crate) struct StoreRef(pub(crate) Arc<AtomicRefCell<Store>>);
+pub(crate) struct StoreRef(pub(crate) Arc<StoreCell>);
 
 impl StoreRef {
     pub fn try_borrow(&self) -> Result<AtomicRef<Store>, BorrowError> {
-        self.0.try_borrow()
+        self.0.store.try_borrow()
     }
 
     pub fn try_borrow_mut(&self) -> Result<AtomicRefMut<Store>, BorrowMutError> {
-        self.0.try_borrow_mut()
+        self.0.store.try_borrow_mut()
     }
 
     pub fn options(&self) -> &Options {
-        let store = unsafe { self.0.as_ptr().as_ref().unwrap() };
+        let store = unsafe { self.0.store.as_ptr().as_ref().unwrap() };
         &store.options
     }
+
+    pub(crate) fn as_ptr(&self) -> *mut Store {
+        self.0.store.as_ptr()
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.0.closed.load(Ordering::Acquire)
+    }
+
+    /// Marks current store as closed. Returns `true` if it has been closed already.
+    pub fn close(&self) -> bool {
+        self.0.closed.swap(true, Ordering::AcqRel)
+    }
 }
 
 impl From<Store> for StoreRef {
     fn from(store: Store) -> Self {
-        StoreRef(Arc::new(AtomicRefCell::new(store)))
+        StoreRef(Arc::new(StoreCell {
+            store: AtomicRefCell::new(store),
+            closed: AtomicBool::new(false),
+        }))
     }
 }
 
@@ -476,6 +1152,29 @@ pub type UpdateFn = Box<dyn Fn(&TransactionMut, &UpdateEvent) + Send + Sync + 's
 pub type SubdocsFn = Box<dyn Fn(&TransactionMut, &SubdocsEvent) + Send + Sync + 'static>;
 #[cfg(feature = "sync")]
 pub type DestroyFn = Box<dyn Fn(&TransactionMut, &Doc) + Send + Sync + 'static>;
+#[cfg(feature = "sync")]
+pub type RootsFn = Box<dyn Fn(&TransactionMut, &RootsEvent) + Send + Sync + 'static>;
+#[cfg(feature = "sync")]
+pub type TypeRepairFn = Box<dyn Fn(&TransactionMut, &TypeRepair) + Send + Sync + 'static>;
+#[cfg(feature = "sync")]
+pub type PendingEvictionFn =
+    Box<dyn Fn(&TransactionMut, &PendingEvictionEvent) + Send + Sync + 'static>;
+#[cfg(feature = "sync")]
+pub type DestructiveOpFn = Box<dyn Fn(&TransactionMut, &DestructiveOp) + Send + Sync + 'static>;
+#[cfg(feature = "sync")]
+pub type CallbackErrorFn = Box<dyn Fn(&TransactionMut, &[CallbackError]) + Send + Sync + 'static>;
+
+#[cfg(feature = "sync")]
+pub type CloseFn = Box<dyn FnOnce(&Doc) + Send + Sync + 'static>;
+#[cfg(feature = "sync")]
+pub type SubdocOriginFn = Box<dyn Fn(&Origin, &Doc) -> Option<Origin> + Send + Sync + 'static>;
+#[cfg(feature = "async")]
+pub type AsyncCloseFn = Box<
+    dyn FnOnce(Doc) -> std::pin::Pin<Box<dyn std::future::Future<Output = ()> + Send>>
+        + Send
+        + Sync
+        + 'static,
+>;
 
 #[cfg(not(feature = "sync"))]
 pub type TransactionCleanupFn = Box<dyn Fn(&TransactionMut, &TransactionCleanupEvent) + 'static>;
@@ -487,6 +1186,27 @@ pub type UpdateFn = Box<dyn Fn(&TransactionMut, &UpdateEvent) + 'static>;
 pub type SubdocsFn = Box<dyn Fn(&TransactionMut, &SubdocsEvent) + 'static>;
 #[cfg(not(feature = "sync"))]
 pub type DestroyFn = Box<dyn Fn(&TransactionMut, &Doc) + 'static>;
+#[cfg(not(feature = "sync"))]
+pub type RootsFn = Box<dyn Fn(&TransactionMut, &RootsEvent) + 'static>;
+#[cfg(not(feature = "sync"))]
+pub type TypeRepairFn = Box<dyn Fn(&TransactionMut, &TypeRepair) + 'static>;
+#[cfg(not(feature = "sync"))]
+pub type PendingEvictionFn = Box<dyn Fn(&TransactionMut, &PendingEvictionEvent) + 'static>;
+#[cfg(not(feature = "sync"))]
+pub type DestructiveOpFn = Box<dyn Fn(&TransactionMut, &DestructiveOp) + 'static>;
+#[cfg(not(feature = "sync"))]
+pub type CallbackErrorFn = Box<dyn Fn(&TransactionMut, &[CallbackError]) + 'static>;
+#[cfg(not(feature = "sync"))]
+pub type CloseFn = Box<dyn FnOnce(&Doc) + 'static>;
+#[cfg(not(feature = "sync"))]
+pub type SubdocOriginFn = Box<dyn Fn(&Origin, &Doc) -> Option<Origin> + 'static>;
+
+/// Cleanup hook registered with [Doc::on_close] or [Doc::on_close_async].
+pub(crate) enum CloseHook {
+    Sync(CloseFn),
+    #[cfg(feature = "async")]
+    Async(AsyncCloseFn),
+}
 
 #[derive(Default)]
 pub struct StoreEvents {
@@ -510,6 +1230,49 @@ pub struct StoreEvents {
     pub subdocs_events: Observer<SubdocsFn>,
 
     pub destroy_events: Observer<DestroyFn>,
+
+    /// Handles subscriptions for events about newly defined root types.
+    pub roots_events: Observer<RootsFn>,
+
+    /// Handles subscriptions for events about root types accessed as a different type than
+    /// the one they were defined with.
+    pub type_repair_events: Observer<TypeRepairFn>,
+
+    /// Handles subscriptions for events about pending updates exceeding configured limits.
+    pub pending_eviction_events: Observer<PendingEvictionFn>,
+
+    /// Handles subscriptions for events about destructive operations.
+    pub destructive_events: Observer<DestructiveOpFn>,
+
+    /// Handles subscriptions for reports about panics caught in observer callbacks.
+    pub callback_error_events: Observer<CallbackErrorFn>,
+
+    /// If set, updates emitted to `update_v1_events`/`update_v2_events` are merged and emitted
+    /// at most once per configured interval.
+    pub(crate) update_limiter: Option<Box<UpdateLimiter>>,
+}
+
+/// State of rate-limited update emission configured with [Doc::set_update_interval].
+pub(crate) struct UpdateLimiter {
+    pub interval: Timestamp,
+    pub clock: Arc<dyn Clock>,
+    pub last_emit: Timestamp,
+    pub flush_requested: bool,
+    pub pending_v1: Vec<Vec<u8>>,
+    pub pending_v2: Vec<Vec<u8>>,
+}
+
+impl UpdateLimiter {
+    pub fn new(interval: Timestamp, clock: Arc<dyn Clock>) -> Self {
+        UpdateLimiter {
+            interval,
+            clock,
+            last_emit: 0,
+            flush_requested: false,
+            pending_v1: Vec::new(),
+            pending_v2: Vec::new(),
+        }
+    }
 }
 
 impl StoreEvents {
@@ -519,7 +1282,7 @@ impl StoreEvents {
                 // produce update only if anything changed
                 let update = UpdateEvent::new_v1(txn);
                 self.update_v1_events
-                    .trigger(|callback| callback(txn, &update));
+                    .trigger(|callback| txn.isolate(|| callback(txn, &update)));
             }
         }
     }
@@ -529,20 +1292,132 @@ impl StoreEvents {
             if !txn.delete_set.is_empty() || txn.after_state != txn.before_state {
                 // produce update only if anything changed
                 let update = UpdateEvent::new_v2(txn);
-                self.update_v2_events.trigger(|fun| fun(txn, &update));
+                self.update_v2_events
+                    .trigger(|fun| txn.isolate(|| fun(txn, &update)));
+            }
+        }
+    }
+
+    /// Emits updates produced by a given transaction through a given `limiter`: they are buffered
+    /// and merged together until the limiter's interval passes or the flush was requested.
+    pub(crate) fn emit_updates_limited(&self, txn: &TransactionMut, limiter: &mut UpdateLimiter) {
+        if !txn.delete_set.is_empty() || txn.after_state != txn.before_state {
+            if self.update_v1_events.has_subscribers() {
+                limiter.pending_v1.push(txn.encode_update_v1());
+            }
+            if self.update_v2_events.has_subscribers() {
+                limiter.pending_v2.push(txn.encode_update_v2());
+            }
+        }
+        if limiter.pending_v1.is_empty() && limiter.pending_v2.is_empty() {
+            limiter.flush_requested = false;
+            return;
+        }
+        let now = limiter.clock.now();
+        if limiter.flush_requested || now.saturating_sub(limiter.last_emit) >= limiter.interval {
+            limiter.flush_requested = false;
+            limiter.last_emit = now;
+            if !limiter.pending_v1.is_empty() {
+                let updates = std::mem::take(&mut limiter.pending_v1);
+                let update = UpdateEvent {
+                    update: merge_updates_v1(&updates).expect("buffered updates are valid"),
+                };
+                self.update_v1_events
+                    .trigger(|fun| txn.isolate(|| fun(txn, &update)));
+            }
+            if !limiter.pending_v2.is_empty() {
+                let updates = std::mem::take(&mut limiter.pending_v2);
+                let update = UpdateEvent {
+                    update: merge_updates_v2(&updates).expect("buffered updates are valid"),
+                };
+                self.update_v2_events
+                    .trigger(|fun| txn.isolate(|| fun(txn, &update)));
             }
         }
     }
 
     pub fn emit_after_transaction(&self, txn: &mut TransactionMut) {
-        self.after_transaction_events.trigger(|fun| fun(txn));
+        let isolate = txn.store.options.isolate_observers;
+        self.after_transaction_events.trigger(|fun| {
+            if let Err(e) = call_isolated(isolate, || fun(txn)) {
+                txn.callback_errors.get_mut().push(e);
+            }
+        });
     }
 
     pub fn emit_transaction_cleanup(&self, txn: &TransactionMut) {
         if self.transaction_cleanup_events.has_subscribers() {
             let event = TransactionCleanupEvent::new(txn);
             self.transaction_cleanup_events
-                .trigger(|fun| fun(txn, &event));
+                .trigger(|fun| txn.isolate(|| fun(txn, &event)));
         }
     }
 }
+
+#[cfg(feature = "async")]
+impl StoreEvents {
+    /// Asynchronous counterpart of subscribing to [StoreEvents::update_v1_events]. Updates are
+    /// queued on transaction commit and passed to `f` by a returned [ObserverTask], which needs
+    /// to be spawned on an async runtime. Futures returned by `f` are awaited one at a time, in
+    /// the order of commits.
+    ///
+    /// Returns a subscription, which will unsubscribe function when dropped. Task completes
+    /// once the subscription has been dropped and all queued updates have been processed.
+    ///
+    /// [ObserverTask]: crate::stream::ObserverTask
+    pub fn observe_update_v1_async<F, Fut>(
+        &self,
+        f: F,
+    ) -> (crate::Subscription, crate::stream::ObserverTask)
+    where
+        F: Fn(crate::stream::EncodedUpdate) -> Fut + Send + 'static,
+        Fut: std::future::Future<Output = ()> + Send + 'static,
+    {
+        let (sender, task) = crate::stream::ObserverTask::new(f);
+        let subscription = self.update_v1_events.subscribe(Box::new(move |txn, e| {
+            sender.send(crate::stream::EncodedUpdate {
+                update: e.update.clone(),
+                origin: txn.origin().cloned(),
+            })
+        }));
+        (subscription, task)
+    }
+
+    /// Asynchronous counterpart of subscribing to [StoreEvents::update_v2_events]. Works the same
+    /// way as [StoreEvents::observe_update_v1_async], except that updates are encoded using lib0
+    /// v2 encoding.
+    pub fn observe_update_v2_async<F, Fut>(
+        &self,
+        f: F,
+    ) -> (crate::Subscription, crate::stream::ObserverTask)
+    where
+        F: Fn(crate::stream::EncodedUpdate) -> Fut + Send + 'static,
+        Fut: std::future::Future<Output = ()> + Send + 'static,
+    {
+        let (sender, task) = crate::stream::ObserverTask::new(f);
+        let subscription = self.update_v2_events.subscribe(Box::new(move |txn, e| {
+            sender.send(crate::stream::EncodedUpdate {
+                update: e.update.clone(),
+                origin: txn.origin().cloned(),
+            })
+        }));
+        (subscription, task)
+    }
+
+    /// Asynchronous counterpart of subscribing to [StoreEvents::transaction_cleanup_events]. See
+    /// [StoreEvents::observe_update_v1_async] for details.
+    pub fn observe_transaction_cleanup_async<F, Fut>(
+        &self,
+        f: F,
+    ) -> (crate::Subscription, crate::stream::ObserverTask)
+    where
+        F: Fn(TransactionCleanupEvent) -> Fut + Send + 'static,
+        Fut: std::future::Future<Output = ()> + Send + 'static,
+    {
+        let (sender, task) = crate::stream::ObserverTask::new(f);
+        let subscription = self
+            .transaction_cleanup_events
+            .subscribe(Box::new(move |_, e| sender.send(e.clone())));
+        (subscription, task)
+    }
+}
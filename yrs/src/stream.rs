@@ -0,0 +1,597 @@
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+use crate::{merge_updates_v1, Doc, Origin, Subscription};
+use atomic_refcell::BorrowMutError;
+
+/// Update produced by [UpdateStream]: a lib0 v1 encoded update together with the origin of
+/// a transaction, which committed it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncodedUpdate {
+    /// A lib0 v1 encoded update, which can be applied using [Update::decode_v1]. Updates passed
+    /// to [Doc::observe_update_v2_async] callbacks use lib0 v2 encoding instead.
+    ///
+    /// [Update::decode_v1]: crate::Update::decode_v1
+    /// [Doc::observe_update_v2_async]: crate::Doc::observe_update_v2_async
+    pub update: Vec<u8>,
+    /// Origin of a transaction which produced this update. If an update is a result of merging
+    /// several buffered updates of different origins, the origin of the most recent one is kept.
+    pub origin: Option<Origin>,
+}
+
+/// Policy applied by [UpdateStream] when a number of buffered updates reaches its capacity,
+/// because the consumer is not polling fast enough.
+///
+/// Since updates are produced synchronously on transaction commit, the producer cannot be
+/// suspended. Instead the buffer is reduced in one of the following ways.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Overflow {
+    /// Merge all buffered updates into a single one. No changes are lost, at the cost of
+    /// merging on the producer side.
+    #[default]
+    Merge,
+    /// Drop the oldest buffered update. Consumer will miss changes and will need to resync
+    /// using a state vector exchange.
+    DropOldest,
+}
+
+/// Options used to configure [UpdateStream] returned by [Doc::update_stream].
+///
+/// [Doc::update_stream]: crate::Doc::update_stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UpdateStreamOptions {
+    /// Maximum number of updates buffered before [UpdateStreamOptions::overflow] policy kicks in.
+    /// Default: 64.
+    pub capacity: usize,
+    /// Policy applied when buffer capacity is reached. Default: [Overflow::Merge].
+    pub overflow: Overflow,
+}
+
+impl Default for UpdateStreamOptions {
+    fn default() -> Self {
+        UpdateStreamOptions {
+            capacity: 64,
+            overflow: Overflow::Merge,
+        }
+    }
+}
+
+/// Queue of values passed from observer callbacks to an asynchronous consumer: an [UpdateStream]
+/// or an [ObserverTask].
+struct Channel<T> {
+    queue: VecDeque<T>,
+    waker: Option<Waker>,
+    /// Number of values discarded by the sender, see [Overflow::DropOldest].
+    dropped: usize,
+    closed: bool,
+}
+
+impl<T> Channel<T> {
+    fn new() -> Arc<Mutex<Self>> {
+        Arc::new(Mutex::new(Channel {
+            queue: VecDeque::new(),
+            waker: None,
+            dropped: 0,
+            closed: false,
+        }))
+    }
+
+    /// Pops the next value from the queue. Registers the waker of a given context if the queue
+    /// is empty, or returns `None` if it's also closed.
+    fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        match self.queue.pop_front() {
+            Some(value) => Poll::Ready(Some(value)),
+            None if self.closed => Poll::Ready(None),
+            None => {
+                self.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+
+    fn wake(&mut self) {
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// Sending half of a [Channel], owned by an observer callback. Callbacks are dropped when their
+/// subscription is released or when the document is closed or dropped. At that point the
+/// channel is closed and the receiving side ends after consuming all values sent until then.
+pub(crate) struct Sender<T> {
+    channel: Arc<Mutex<Channel<T>>>,
+}
+
+impl<T> Sender<T> {
+    pub fn send(&self, value: T) {
+        self.send_with(|channel| channel.queue.push_back(value))
+    }
+
+    fn send_with<F>(&self, f: F)
+    where
+        F: FnOnce(&mut Channel<T>),
+    {
+        let mut channel = self.channel.lock().unwrap();
+        f(&mut channel);
+        channel.wake();
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut channel = self.channel.lock().unwrap();
+        channel.closed = true;
+        channel.wake();
+    }
+}
+
+/// Asynchronous stream of updates committed by transactions over a [Doc], created with
+/// [Doc::update_stream]. Stream ends once the document has been closed (see: [Doc::close]) or
+/// dropped, after all updates buffered until then have been consumed.
+///
+/// It implements `futures::Stream`, so it can be used with stream combinators of any async
+/// runtime. It can also be awaited directly with [UpdateStream::next].
+///
+/// [Doc::close]: crate::Doc::close
+/// [Doc]: crate::Doc
+/// [Doc::update_stream]: crate::Doc::update_stream
+pub struct UpdateStream {
+    channel: Arc<Mutex<Channel<EncodedUpdate>>>,
+    _subscription: Subscription,
+}
+
+impl UpdateStream {
+    pub(crate) fn new(doc: &Doc, options: UpdateStreamOptions) -> Result<Self, BorrowMutError> {
+        let channel = Channel::new();
+        let sender = Sender {
+            channel: channel.clone(),
+        };
+        let subscription = doc.observe_update_v1(move |txn, e| {
+            let update = EncodedUpdate {
+                update: e.update.clone(),
+                origin: txn.origin().cloned(),
+            };
+            sender.send_with(|channel| channel.push(update, &options));
+        })?;
+        Ok(UpdateStream {
+            channel,
+            _subscription: subscription,
+        })
+    }
+
+    /// Attempts to pull out the next update from this stream. Returns [Poll::Pending] and
+    /// registers the waker of a given context if there are no buffered updates. Returns `None`
+    /// once the stream has ended.
+    pub fn poll_next(&self, cx: &mut Context<'_>) -> Poll<Option<EncodedUpdate>> {
+        self.channel.lock().unwrap().poll_recv(cx)
+    }
+
+    /// Returns a future resolving to the next update from this stream or `None` if the stream
+    /// has ended.
+    pub fn next(&self) -> Next<'_> {
+        Next { stream: self }
+    }
+
+    /// Returns the next update if there's any buffered, without waiting.
+    pub fn try_next(&self) -> Option<EncodedUpdate> {
+        self.channel.lock().unwrap().queue.pop_front()
+    }
+
+    /// Returns a number of updates dropped so far due to [Overflow::DropOldest] policy.
+    pub fn dropped(&self) -> usize {
+        self.channel.lock().unwrap().dropped
+    }
+
+    /// Returns `true` if the document producing updates has been closed or dropped. Updates
+    /// buffered before that can still be consumed.
+    pub fn is_closed(&self) -> bool {
+        self.channel.lock().unwrap().closed
+    }
+}
+
+impl futures_core::Stream for UpdateStream {
+    type Item = EncodedUpdate;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        UpdateStream::poll_next(self.get_mut(), cx)
+    }
+}
+
+impl futures_core::FusedStream for UpdateStream {
+    fn is_terminated(&self) -> bool {
+        let channel = self.channel.lock().unwrap();
+        channel.closed && channel.queue.is_empty()
+    }
+}
+
+impl std::fmt::Debug for UpdateStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let channel = self.channel.lock().unwrap();
+        f.debug_struct("UpdateStream")
+            .field("buffered", &channel.queue.len())
+            .field("dropped", &channel.dropped)
+            .field("closed", &channel.closed)
+            .finish()
+    }
+}
+
+impl Channel<EncodedUpdate> {
+    fn push(&mut self, update: EncodedUpdate, options: &UpdateStreamOptions) {
+        if self.queue.len() >= options.capacity.max(1) {
+            match options.overflow {
+                Overflow::DropOldest => {
+                    self.queue.pop_front();
+                    self.dropped += 1;
+                }
+                Overflow::Merge => {
+                    let mut updates: Vec<_> = self.queue.drain(..).map(|u| u.update).collect();
+                    updates.push(update.update);
+                    let update = EncodedUpdate {
+                        update: merge_updates_v1(&updates)
+                            .expect("updates produced by a document are always valid"),
+                        origin: update.origin,
+                    };
+                    self.queue.push_back(update);
+                    return;
+                }
+            }
+        }
+        self.queue.push_back(update);
+    }
+}
+
+/// Future returned by [UpdateStream::next].
+#[derive(Debug)]
+pub struct Next<'a> {
+    stream: &'a UpdateStream,
+}
+
+impl<'a> Future for Next<'a> {
+    type Output = Option<EncodedUpdate>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.stream.poll_next(cx)
+    }
+}
+
+type BoxFuture = Pin<Box<dyn Future<Output = ()> + Send + 'static>>;
+
+type NextFn = Box<dyn FnMut(&mut Context<'_>) -> Poll<Option<BoxFuture>> + Send + 'static>;
+
+/// Future processing events delivered to callbacks registered with `observe_*_async` methods,
+/// i.e. [Doc::observe_update_v1_async]. Events are queued on transaction commit and callbacks are
+/// called here, one event at a time, in the order of commits - so that long-running work, like
+/// writing updates to a database, doesn't block committing transactions.
+///
+/// The task is runtime-agnostic: it needs to be spawned on the async runtime of choice (i.e.
+/// `tokio::spawn(task)`). It completes once the corresponding [Subscription] has been dropped and
+/// all events queued before that have been processed.
+///
+/// [Doc::observe_update_v1_async]: crate::Doc::observe_update_v1_async
+pub struct ObserverTask {
+    next: NextFn,
+    current: Option<BoxFuture>,
+}
+
+impl ObserverTask {
+    pub(crate) fn new<T, F, Fut>(f: F) -> (Sender<T>, Self)
+    where
+        T: Send + 'static,
+        F: Fn(T) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let channel = Channel::new();
+        let sender = Sender {
+            channel: channel.clone(),
+        };
+        let next = move |cx: &mut Context<'_>| {
+            let value = channel.lock().unwrap().poll_recv(cx);
+            value.map(|value| value.map(|value| Box::pin(f(value)) as BoxFuture))
+        };
+        let task = ObserverTask {
+            next: Box::new(next),
+            current: None,
+        };
+        (sender, task)
+    }
+}
+
+impl Future for ObserverTask {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        loop {
+            if let Some(current) = this.current.as_mut() {
+                match current.as_mut().poll(cx) {
+                    Poll::Ready(()) => this.current = None,
+                    Poll::Pending => return Poll::Pending,
+                }
+            }
+            match (this.next)(cx) {
+                Poll::Ready(Some(future)) => this.current = Some(future),
+                Poll::Ready(None) => return Poll::Ready(()),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for ObserverTask {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ObserverTask")
+            .field("busy", &self.current.is_some())
+            .finish()
+    }
+}
+
+/// Drives a given future to completion on a current thread, parking it whenever the future
+/// is not ready. Used to await asynchronous hooks from synchronous contexts like [Doc::close].
+pub(crate) fn block_on<F: Future>(future: F) -> F::Output {
+    struct ThreadWaker(std::thread::Thread);
+
+    impl std::task::Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    let waker = Waker::from(Arc::new(ThreadWaker(std::thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    let mut future = Box::pin(future);
+    loop {
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => std::thread::park(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::stream::{Overflow, UpdateStreamOptions};
+    use crate::types::EntryChange;
+    use crate::updates::decoder::Decode;
+    use crate::{
+        DeepObservable, Doc, GetString, Map, Origin, Out, ReadTxn, Text, Transact, Update,
+    };
+    use std::collections::HashMap;
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::task::{Context, Poll, Wake, Waker};
+
+    struct CountingWaker(AtomicUsize);
+
+    impl Wake for CountingWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn update_stream() {
+        let doc = Doc::with_client_id(1);
+        let text = doc.get_or_insert_text("text");
+        let stream = doc.update_stream(UpdateStreamOptions::default()).unwrap();
+
+        let counter = Arc::new(CountingWaker(AtomicUsize::new(0)));
+        let waker = Waker::from(counter.clone());
+        let mut cx = Context::from_waker(&waker);
+        let mut next = stream.next();
+        assert!(Pin::new(&mut next).poll(&mut cx).is_pending());
+
+        text.push(&mut doc.transact_mut_with("test"), "hello");
+        assert_eq!(counter.0.load(Ordering::SeqCst), 1);
+        let update = match Pin::new(&mut next).poll(&mut cx) {
+            Poll::Ready(Some(update)) => update,
+            _ => panic!("expected update to be ready"),
+        };
+        assert_eq!(update.origin, Some(Origin::from("test")));
+
+        let remote = Doc::with_client_id(2);
+        let remote_text = remote.get_or_insert_text("text");
+        remote
+            .transact_mut()
+            .apply_update(Update::decode_v1(&update.update).unwrap());
+        assert_eq!(remote_text.get_string(&remote.transact()), "hello");
+        assert!(stream.try_next().is_none());
+    }
+
+    #[test]
+    fn update_stream_overflow() {
+        let doc = Doc::with_client_id(1);
+        let text = doc.get_or_insert_text("text");
+        let merged = doc
+            .update_stream(UpdateStreamOptions {
+                capacity: 2,
+                overflow: Overflow::Merge,
+            })
+            .unwrap();
+        let dropping = doc
+            .update_stream(UpdateStreamOptions {
+                capacity: 2,
+                overflow: Overflow::DropOldest,
+            })
+            .unwrap();
+
+        for c in ["a", "b", "c", "d", "e"] {
+            text.push(&mut doc.transact_mut(), c);
+        }
+
+        let remote = Doc::with_client_id(2);
+        let remote_text = remote.get_or_insert_text("text");
+        let mut count = 0;
+        while let Some(u) = merged.try_next() {
+            let mut txn = remote.transact_mut();
+            txn.apply_update(Update::decode_v1(&u.update).unwrap());
+            count += 1;
+        }
+        assert!(count <= 2);
+        assert_eq!(remote_text.get_string(&remote.transact()), "abcde");
+
+        assert_eq!(dropping.dropped(), 3);
+        assert!(dropping.try_next().is_some());
+        assert!(dropping.try_next().is_some());
+        assert!(dropping.try_next().is_none());
+    }
+
+    #[test]
+    fn update_stream_ends_with_doc() {
+        fn poll<S: futures_core::Stream + Unpin>(stream: &mut S) -> Poll<Option<S::Item>> {
+            let waker = Waker::from(Arc::new(CountingWaker(AtomicUsize::new(0))));
+            let mut cx = Context::from_waker(&waker);
+            Pin::new(stream).poll_next(&mut cx)
+        }
+
+        let doc = Doc::with_client_id(1);
+        let text = doc.get_or_insert_text("text");
+        let mut closed = doc.update_stream(UpdateStreamOptions::default()).unwrap();
+        text.push(&mut doc.transact_mut(), "hello");
+        assert!(poll(&mut closed).is_ready());
+        assert!(poll(&mut closed).is_pending());
+
+        // updates buffered before closing the document can still be consumed
+        text.push(&mut doc.transact_mut(), " world");
+        doc.close().unwrap();
+        assert!(closed.is_closed());
+        assert!(matches!(poll(&mut closed), Poll::Ready(Some(_))));
+        assert!(matches!(poll(&mut closed), Poll::Ready(None)));
+
+        let doc = Doc::with_client_id(2);
+        let mut dropped = doc.update_stream(UpdateStreamOptions::default()).unwrap();
+        assert!(poll(&mut dropped).is_pending());
+        drop(doc);
+        assert!(matches!(poll(&mut dropped), Poll::Ready(None)));
+        assert!(futures_core::FusedStream::is_terminated(&dropped));
+    }
+
+    #[test]
+    fn observe_async() {
+        let doc = Doc::with_client_id(1);
+        let map = doc.get_or_insert_map("map");
+        let origins = Arc::new(Mutex::new(Vec::new()));
+        let (update_sub, mut update_task) = {
+            let origins = origins.clone();
+            doc.observe_update_v1_async(move |u| {
+                let origins = origins.clone();
+                async move { origins.lock().unwrap().push(u.origin) }
+            })
+            .unwrap()
+        };
+        let changes = Arc::new(Mutex::new(Vec::new()));
+        let (deep_sub, mut deep_task) = {
+            let changes = changes.clone();
+            map.observe_deep_async(move |events| {
+                let changes = changes.clone();
+                async move { changes.lock().unwrap().extend(events) }
+            })
+        };
+
+        map.insert(&mut doc.transact_mut_with("a"), "key", 1);
+        map.insert(&mut doc.transact_mut_with("b"), "key", 2);
+        // callbacks are not called on the commit path
+        assert!(origins.lock().unwrap().is_empty());
+        assert!(changes.lock().unwrap().is_empty());
+
+        let waker = Waker::from(Arc::new(CountingWaker(AtomicUsize::new(0))));
+        let mut cx = Context::from_waker(&waker);
+        assert!(Pin::new(&mut update_task).poll(&mut cx).is_pending());
+        assert!(Pin::new(&mut deep_task).poll(&mut cx).is_pending());
+        assert_eq!(
+            *origins.lock().unwrap(),
+            vec![Some(Origin::from("a")), Some(Origin::from("b"))]
+        );
+        let changes = changes.lock().unwrap();
+        assert_eq!(changes.len(), 2);
+        assert_eq!(
+            changes[1].keys,
+            HashMap::from([(
+                "key".into(),
+                EntryChange::Updated(Out::from(1.0), Out::from(2.0))
+            )])
+        );
+
+        // tasks complete once their subscriptions are dropped
+        drop(update_sub);
+        drop(deep_sub);
+        assert!(Pin::new(&mut update_task).poll(&mut cx).is_ready());
+        assert!(Pin::new(&mut deep_task).poll(&mut cx).is_ready());
+    }
+
+    #[test]
+    fn observe_store_events_async() {
+        let doc = Doc::with_client_id(1);
+        let text = doc.get_or_insert_text("text");
+        let cleanups = Arc::new(Mutex::new(Vec::new()));
+        let (cleanup_sub, mut cleanup_task) = {
+            let cleanups = cleanups.clone();
+            doc.observe_transaction_cleanup_async(move |e| {
+                let cleanups = cleanups.clone();
+                async move { cleanups.lock().unwrap().push(e.after_state) }
+            })
+            .unwrap()
+        };
+        let updates = Arc::new(Mutex::new(Vec::new()));
+        let (update_sub, mut update_task) = {
+            let updates = updates.clone();
+            doc.observe_update_v2_async(move |u| {
+                let updates = updates.clone();
+                async move { updates.lock().unwrap().push(u.update) }
+            })
+            .unwrap()
+        };
+
+        text.push(&mut doc.transact_mut(), "hello");
+        assert!(cleanups.lock().unwrap().is_empty());
+        assert!(updates.lock().unwrap().is_empty());
+
+        let waker = Waker::from(Arc::new(CountingWaker(AtomicUsize::new(0))));
+        let mut cx = Context::from_waker(&waker);
+        assert!(Pin::new(&mut cleanup_task).poll(&mut cx).is_pending());
+        assert!(Pin::new(&mut update_task).poll(&mut cx).is_pending());
+        assert_eq!(
+            *cleanups.lock().unwrap(),
+            vec![doc.transact().state_vector()]
+        );
+        let remote = Doc::with_client_id(2);
+        let remote_text = remote.get_or_insert_text("text");
+        for u in updates.lock().unwrap().iter() {
+            remote
+                .transact_mut()
+                .apply_update(Update::decode_v2(u).unwrap());
+        }
+        assert_eq!(remote_text.get_string(&remote.transact()), "hello");
+
+        drop(cleanup_sub);
+        drop(update_sub);
+        assert!(Pin::new(&mut cleanup_task).poll(&mut cx).is_ready());
+        assert!(Pin::new(&mut update_task).poll(&mut cx).is_ready());
+    }
+
+    #[test]
+    fn close_awaits_async_hooks() {
+        let doc = Doc::with_client_id(1);
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let l = log.clone();
+        doc.on_close_async(1, move |doc| async move {
+            let flushed = doc
+                .transact()
+                .encode_state_as_update_v1(&Default::default());
+            l.lock().unwrap().push(format!("flush:{}", flushed.len()));
+        })
+        .unwrap();
+        let l = log.clone();
+        doc.on_close(0, move |_| l.lock().unwrap().push("stop".to_string()))
+            .unwrap();
+
+        crate::stream::block_on(doc.close_async()).unwrap();
+        assert_eq!(*log.lock().unwrap(), vec!["stop", "flush:2"]);
+        assert!(doc.try_transact_mut().is_err());
+    }
+}
@@ -2,6 +2,7 @@ use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 use std::fmt::Formatter;
 use std::sync::Arc;
+use std::time::Duration;
 
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
@@ -14,6 +15,10 @@ use crate::{Doc, Observer, Origin};
 
 const NULL_STR: &str = "null";
 
+/// Time after which states of remote clients, which didn't send any updates, are considered
+/// outdated. Matches the timeout used by y-protocols. See: [Awareness::check_outdated].
+pub const OUTDATED_TIMEOUT: Duration = Duration::from_secs(30);
+
 #[cfg(feature = "sync")]
 type AwarenessUpdateFn = Box<dyn Fn(&Awareness, &Event, Option<&Origin>) + Send + Sync + 'static>;
 
@@ -277,6 +282,49 @@ impl Awareness {
         }
     }
 
+    /// Performs periodic maintenance of awareness states. It's meant to be called in regular
+    /// intervals - y-protocols does so every `timeout / 10`. Usually [OUTDATED_TIMEOUT] is used as
+    /// a `timeout`, which matches the one used by y-protocols.
+    ///
+    /// - If local state has not been updated for half of the `timeout`, it's renewed (its clock is
+    ///   incremented), so that it's not considered outdated by other peers.
+    /// - States of remote clients, which have not been updated for longer than `timeout`, are
+    ///   removed. Observers are notified with an origin of `"timeout"`.
+    ///
+    /// Returns IDs of the removed remote clients.
+    pub fn check_outdated(&mut self, timeout: Duration) -> Vec<ClientID> {
+        let now = self.clock.now();
+        let timeout = timeout.as_millis() as Timestamp;
+        let local_id = self.doc.client_id();
+        if let Some(state) = self.states.get(&local_id) {
+            let last_updated = self.meta.get(&local_id).map(|m| m.last_updated);
+            if timeout / 2 <= now.saturating_sub(last_updated.unwrap_or_default()) {
+                let state = state.clone();
+                self.set_local_state_raw(state);
+            }
+        }
+
+        let mut removed = Vec::new();
+        for (&client_id, meta) in self.meta.iter() {
+            if client_id != local_id
+                && timeout <= now.saturating_sub(meta.last_updated)
+                && self.states.contains_key(&client_id)
+            {
+                removed.push(client_id);
+            }
+        }
+        for client_id in removed.iter() {
+            self.states.remove(client_id);
+        }
+        if !removed.is_empty() {
+            let e = Event::new(Vec::default(), Vec::default(), removed.clone());
+            let origin = Origin::from("timeout");
+            self.on_change.trigger(|fun| fun(self, &e, Some(&origin)));
+            self.on_update.trigger(|fun| fun(self, &e, Some(&origin)));
+        }
+        removed
+    }
+
     /// Returns a serializable update object which is representation of a current Awareness state.
     pub fn update(&self) -> Result<AwarenessUpdate, Error> {
         let clients = self.states.keys().cloned();
@@ -755,4 +803,45 @@ mod test {
         assert_eq!(local.states, remote.states);
         Ok(())
     }
+
+    #[test]
+    fn awareness_outdated_states() {
+        use crate::sync::awareness::OUTDATED_TIMEOUT;
+        use std::sync::atomic::{AtomicU64, Ordering};
+
+        let now = Arc::new(AtomicU64::new(1000));
+        let clock = {
+            let now = now.clone();
+            move || now.load(Ordering::SeqCst)
+        };
+        let mut remote = Awareness::new(Doc::with_client_id(2));
+        remote.set_local_state(json!({"x":1})).unwrap();
+        let mut local = Awareness::with_clock(Doc::with_client_id(1), clock);
+        local.set_local_state(json!({"y":2})).unwrap();
+        local.apply_update(remote.update().unwrap()).unwrap();
+
+        let removed = Arc::new(ArcSwapOption::default());
+        let _sub = {
+            let removed = removed.clone();
+            local.on_change(move |_, e, origin| {
+                removed.store(Some(Arc::new((e.removed().to_vec(), origin.cloned()))));
+            })
+        };
+
+        // local state is renewed after half of the timeout
+        now.fetch_add(OUTDATED_TIMEOUT.as_millis() as u64 / 2, Ordering::SeqCst);
+        assert!(local.check_outdated(OUTDATED_TIMEOUT).is_empty());
+        assert_eq!(local.meta()[&1].clock, 2);
+        assert_eq!(local.meta()[&1].last_updated, now.load(Ordering::SeqCst));
+
+        // remote state is removed after the timeout
+        now.fetch_add(OUTDATED_TIMEOUT.as_millis() as u64 / 2, Ordering::SeqCst);
+        assert_eq!(local.check_outdated(OUTDATED_TIMEOUT), vec![2]);
+        assert_eq!(local.state::<Value>(2), None);
+        assert_eq!(local.state::<Value>(1), Some(json!({"y":2})));
+        assert_eq!(
+            removed.load_full().as_deref(),
+            Some(&(vec![2], Some("timeout".into())))
+        );
+    }
 }
@@ -1,5 +1,6 @@
 pub mod awareness;
 pub mod protocol;
+pub mod replay;
 pub mod time;
 
 pub use crate::sync::awareness::Awareness;
@@ -10,5 +11,7 @@ pub use crate::sync::protocol::Message;
 pub use crate::sync::protocol::MessageReader;
 pub use crate::sync::protocol::Protocol;
 pub use crate::sync::protocol::SyncMessage;
+pub use crate::sync::replay::DuplicateEvent;
+pub use crate::sync::replay::ReplayGuard;
 pub use crate::sync::time::Clock;
 pub use crate::sync::time::Timestamp;
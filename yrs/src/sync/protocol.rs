@@ -1,8 +1,10 @@
 use crate::encoding::read;
+use crate::encoding::read::{Cursor, Read};
+use crate::encoding::write::Write;
 use crate::sync::{awareness, Awareness, AwarenessUpdate};
-use crate::updates::decoder::{Decode, Decoder};
-use crate::updates::encoder::{Encode, Encoder};
-use crate::{ReadTxn, StateVector, Transact, Update};
+use crate::updates::decoder::{Decode, Decoder, DecoderV1};
+use crate::updates::encoder::{Encode, Encoder, EncoderV1};
+use crate::{BlockRange, ReadTxn, StateVector, Transact, Update};
 use thiserror::Error;
 
 /*
@@ -90,6 +92,43 @@ pub trait Protocol {
         self.handle_sync_step2(awareness, update)
     }
 
+    /// Returns a [SyncMessage::Missing] request for the ranges of blocks, which are required to
+    /// integrate a pending update of a current `awareness` document. Returns `None` if nothing is
+    /// missing. It can be sent after an update has been applied, so that remote peers can fill
+    /// the gaps without comparing full state vectors.
+    fn missing_request(&self, awareness: &Awareness) -> Option<Message> {
+        let ranges = awareness.doc().transact().store().missing_ranges();
+        if ranges.is_empty() {
+            None
+        } else {
+            Some(Message::Sync(SyncMessage::Missing(ranges)))
+        }
+    }
+
+    /// Handle request for the missing ranges of blocks. By default replies with an update
+    /// containing all blocks of requested clients starting from the requested clocks, or nothing
+    /// if current `awareness` document doesn't have any of them.
+    fn handle_missing(
+        &self,
+        awareness: &Awareness,
+        ranges: Vec<BlockRange>,
+    ) -> Result<Option<Message>, Error> {
+        let txn = awareness.doc().transact();
+        let mut sv = txn.state_vector();
+        let mut found = false;
+        for range in ranges.iter() {
+            if range.start < sv.get(&range.client) {
+                sv.set_min(range.client, range.start);
+                found = true;
+            }
+        }
+        if !found {
+            return Ok(None);
+        }
+        let update = txn.encode_state_as_update_v1(&sv);
+        Ok(Some(Message::Sync(SyncMessage::Update(update))))
+    }
+
     /// Handle authorization message. By default if reason for auth denial has been provided,
     /// send back [Error::PermissionDenied].
     fn handle_auth(
@@ -122,6 +161,45 @@ pub trait Protocol {
         Ok(None)
     }
 
+    /// Dispatches incoming `msg` to a corresponding handler method, returning a reply message
+    /// (if any) which should be send back to the sender.
+    fn handle_message(
+        &self,
+        awareness: &mut Awareness,
+        msg: Message,
+    ) -> Result<Option<Message>, Error> {
+        match msg {
+            Message::Sync(SyncMessage::SyncStep1(sv)) => self.handle_sync_step1(awareness, sv),
+            Message::Sync(SyncMessage::SyncStep2(update)) => {
+                let update = Update::decode_v1(&update)?;
+                self.handle_sync_step2(awareness, update)
+            }
+            Message::Sync(SyncMessage::Update(update)) => {
+                let update = Update::decode_v1(&update)?;
+                self.handle_update(awareness, update)
+            }
+            Message::Sync(SyncMessage::Missing(ranges)) => self.handle_missing(awareness, ranges),
+            Message::Auth(deny_reason) => self.handle_auth(awareness, deny_reason),
+            Message::AwarenessQuery => self.handle_awareness_query(awareness),
+            Message::Awareness(update) => self.handle_awareness_update(awareness, update),
+            Message::Custom(tag, data) => self.missing_handle(awareness, tag, data),
+        }
+    }
+
+    /// Decodes all messages packed one after another inside of a binary `data` payload and
+    /// handles them in order using [Protocol::handle_message]. Returns a list of replies, which
+    /// should be send back to the sender.
+    fn handle(&self, awareness: &mut Awareness, data: &[u8]) -> Result<Vec<Message>, Error> {
+        let mut decoder = DecoderV1::new(Cursor::new(data));
+        let mut replies = Vec::new();
+        for msg in MessageReader::new(&mut decoder) {
+            if let Some(reply) = self.handle_message(awareness, msg?)? {
+                replies.push(reply);
+            }
+        }
+        Ok(replies)
+    }
+
     /// Y-sync protocol enables to extend its own settings with custom handles. These can be
     /// implemented here. By default it returns an [Error::Unsupported].
     fn missing_handle(
@@ -222,12 +300,20 @@ pub const MSG_SYNC_STEP_1: u8 = 0;
 pub const MSG_SYNC_STEP_2: u8 = 1;
 /// Tag id for [SyncMessage::Update].
 pub const MSG_SYNC_UPDATE: u8 = 2;
+/// Tag id for [SyncMessage::Missing].
+pub const MSG_SYNC_MISSING: u8 = 3;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum SyncMessage {
     SyncStep1(StateVector),
     SyncStep2(Vec<u8>),
     Update(Vec<u8>),
+    /// Request for the ranges of blocks, which are missing in order to integrate updates received
+    /// so far (see: [Store::missing_ranges]). This message is an extension of y-sync protocol
+    /// and is not understood by Yjs peers.
+    ///
+    /// [Store::missing_ranges]: crate::Store::missing_ranges
+    Missing(Vec<BlockRange>),
 }
 
 impl Encode for SyncMessage {
@@ -245,6 +331,17 @@ impl Encode for SyncMessage {
                 encoder.write_var(MSG_SYNC_UPDATE);
                 encoder.write_buf(u);
             }
+            SyncMessage::Missing(ranges) => {
+                encoder.write_var(MSG_SYNC_MISSING);
+                let mut inner = EncoderV1::new();
+                inner.write_var(ranges.len());
+                for range in ranges.iter() {
+                    inner.write_var(range.client);
+                    inner.write_var(range.start);
+                    inner.write_var(range.len());
+                }
+                encoder.write_buf(inner.to_vec());
+            }
         }
     }
 }
@@ -266,6 +363,19 @@ impl Decode for SyncMessage {
                 let buf = decoder.read_buf()?;
                 Ok(SyncMessage::Update(buf.into()))
             }
+            MSG_SYNC_MISSING => {
+                let buf = decoder.read_buf()?;
+                let mut inner = DecoderV1::new(Cursor::new(buf));
+                let len: usize = inner.read_var()?;
+                let mut ranges = Vec::with_capacity(len.min(buf.len()));
+                for _ in 0..len {
+                    let client = inner.read_var()?;
+                    let start: u32 = inner.read_var()?;
+                    let len: u32 = inner.read_var()?;
+                    ranges.push(BlockRange::new(client, start, start.saturating_add(len)));
+                }
+                Ok(SyncMessage::Missing(ranges))
+            }
             _ => Err(read::Error::UnexpectedValue),
         }
     }
@@ -336,7 +446,7 @@ mod test {
     use crate::sync::{Awareness, Protocol};
     use crate::updates::decoder::{Decode, DecoderV1};
     use crate::updates::encoder::{Encode, Encoder, EncoderV1};
-    use crate::{Doc, GetString, ReadTxn, StateVector, Text, Transact, Update};
+    use crate::{BlockRange, Doc, GetString, ReadTxn, StateVector, Text, Transact, Update};
     use serde_json::json;
     use std::collections::HashMap;
 
@@ -373,6 +483,10 @@ mod test {
                 .to_string(),
             )),
             crate::sync::Message::AwarenessQuery,
+            crate::sync::Message::Sync(crate::sync::SyncMessage::Missing(vec![
+                BlockRange::new(1, 0, 3),
+                BlockRange::new(u32::MAX as u64 + 1, 10, 12),
+            ])),
         ];
 
         for msg in messages {
@@ -467,6 +581,61 @@ mod test {
         assert_eq!(txt.get_string(&a2.doc().transact()), "hello".to_owned());
     }
 
+    #[test]
+    fn protocol_missing_request() {
+        let protocol = crate::sync::DefaultProtocol;
+
+        let mut a1 = Awareness::new(Doc::with_client_id(1));
+        let mut a2 = Awareness::new(Doc::with_client_id(2));
+
+        let txt = a1.doc().get_or_insert_text("test");
+        let mut updates = Vec::new();
+        for chunk in ["a", "b", "c"] {
+            let mut txn = a1.doc().transact_mut();
+            txt.push(&mut txn, chunk);
+            updates.push(txn.encode_update_v1());
+        }
+
+        assert!(protocol.missing_request(&a2).is_none());
+
+        // apply only the last update: it depends on the blocks a2 doesn't have yet
+        protocol
+            .handle_update(&mut a2, Update::decode_v1(&updates[2]).unwrap())
+            .unwrap();
+        let request = protocol.missing_request(&a2).unwrap();
+        assert_eq!(
+            request,
+            crate::sync::Message::Sync(crate::sync::SyncMessage::Missing(vec![BlockRange::new(
+                1, 0, 2
+            )]))
+        );
+
+        let reply = protocol
+            .handle_message(
+                &mut a1,
+                crate::sync::Message::decode_v1(&request.encode_v1()).unwrap(),
+            )
+            .unwrap();
+        let update = match reply {
+            Some(crate::sync::Message::Sync(crate::sync::SyncMessage::Update(u))) => u,
+            other => panic!("expected update, got {:?}", other),
+        };
+        protocol
+            .handle_update(&mut a2, Update::decode_v1(&update).unwrap())
+            .unwrap();
+
+        assert!(protocol.missing_request(&a2).is_none());
+        let txt = a2.doc().transact().get_text("test").unwrap();
+        assert_eq!(txt.get_string(&a2.doc().transact()), "abc");
+
+        // peer which doesn't have any of the requested blocks doesn't reply
+        let a3 = Awareness::new(Doc::with_client_id(3));
+        let reply = protocol
+            .handle_missing(&a3, vec![BlockRange::new(1, 0, 2)])
+            .unwrap();
+        assert!(reply.is_none());
+    }
+
     #[test]
     fn protocol_awareness_sync() {
         let protocol = crate::sync::DefaultProtocol;
@@ -489,4 +658,39 @@ mod test {
 
         assert_eq!(a2.clients(), &HashMap::from([(1, "{\"x\":3}".to_owned())]));
     }
+
+    #[test]
+    fn protocol_handle_messages() {
+        let protocol = crate::sync::DefaultProtocol;
+
+        let mut server = Awareness::new(Doc::with_client_id(1));
+        let mut client = Awareness::new(Doc::with_client_id(2));
+        {
+            let txt = server.doc().get_or_insert_text("test");
+            txt.push(&mut server.doc().transact_mut(), "hello");
+        }
+
+        let mut encoder = EncoderV1::new();
+        protocol.start(&client, &mut encoder).unwrap();
+        let replies = protocol.handle(&mut server, &encoder.to_vec()).unwrap();
+        assert_eq!(replies.len(), 1);
+
+        let mut encoder = EncoderV1::new();
+        for reply in replies {
+            reply.encode(&mut encoder);
+        }
+        let replies = protocol.handle(&mut client, &encoder.to_vec()).unwrap();
+        assert!(replies.is_empty());
+
+        let txt = client.doc().get_or_insert_text("test");
+        assert_eq!(txt.get_string(&client.doc().transact()), "hello");
+
+        let reply = protocol
+            .handle_message(&mut client, crate::sync::Message::AwarenessQuery)
+            .unwrap();
+        assert_eq!(
+            reply,
+            Some(crate::sync::Message::Awareness(client.update().unwrap()))
+        );
+    }
 }
@@ -0,0 +1,336 @@
+use std::collections::HashMap;
+
+use crate::encoding::read::Error;
+use crate::updates::decoder::Decode;
+use crate::{Observer, Origin, TransactionMut, Update};
+
+#[cfg(feature = "sync")]
+type DuplicateFn = Box<dyn Fn(&DuplicateEvent) + Send + Sync + 'static>;
+
+#[cfg(not(feature = "sync"))]
+type DuplicateFn = Box<dyn Fn(&DuplicateEvent) + 'static>;
+
+/// Replay protection for updates received over unreliable transports, which may deliver the same
+/// message more than once.
+///
+/// Every sender (identified by its [Origin]) is expected to tag its updates with a monotonically
+/// increasing sequence number. Since unreliable transports may also reorder messages, for each
+/// origin [ReplayGuard] remembers the highest sequence number seen together with a sliding window
+/// of [REPLAY_WINDOW] sequence numbers preceding it. Updates arriving out of order within that
+/// window are accepted as long as they have not been seen before. Repeated updates and updates
+/// older than the window are dropped - before they are even decoded - and reported to
+/// [ReplayGuard::on_duplicate] callbacks.
+///
+/// Block integration itself is idempotent, so applying the same update twice is safe, but it still
+/// pays the cost of decoding and integrity checks.
+///
+/// # Example
+///
+/// ```rust
+/// use yrs::sync::ReplayGuard;
+/// use yrs::{Doc, Origin, ReadTxn, Text, Transact, StateVector};
+///
+/// let remote = Doc::new();
+/// remote.get_or_insert_text("text").push(&mut remote.transact_mut(), "hello");
+/// let update = remote.transact().encode_state_as_update_v1(&StateVector::default());
+///
+/// let doc = Doc::new();
+/// let mut guard = ReplayGuard::new();
+/// let origin = Origin::from("peer-a");
+/// let mut txn = doc.transact_mut();
+/// assert_eq!(guard.apply_update_v1(&mut txn, &origin, 1, &update).unwrap(), true);
+/// // the same message delivered again is dropped without decoding
+/// assert_eq!(guard.apply_update_v1(&mut txn, &origin, 1, &update).unwrap(), false);
+/// ```
+pub struct ReplayGuard {
+    last_seen: HashMap<Origin, Window>,
+    on_duplicate: Observer<DuplicateFn>,
+}
+
+impl ReplayGuard {
+    /// Creates a new [ReplayGuard] with no sequence numbers recorded.
+    pub fn new() -> Self {
+        ReplayGuard {
+            last_seen: HashMap::new(),
+            on_duplicate: Observer::new(),
+        }
+    }
+
+    /// Returns the highest sequence number accepted so far from a given `origin`.
+    pub fn last_seen(&self, origin: &Origin) -> Option<u64> {
+        self.last_seen.get(origin).map(|w| w.highest)
+    }
+
+    /// Forgets a sequence number tracked for a given `origin` (i.e. when the sender has restarted
+    /// its sequence from scratch).
+    pub fn reset(&mut self, origin: &Origin) -> Option<u64> {
+        self.last_seen.remove(origin).map(|w| w.highest)
+    }
+
+    /// Checks if a message with a given sequence number `seq` from a given `origin` has not been
+    /// seen before. If so, the sequence number is recorded and `true` is returned. Otherwise,
+    /// [ReplayGuard::on_duplicate] callbacks are triggered and `false` is returned.
+    pub fn check(&mut self, origin: &Origin, seq: u64) -> bool {
+        match self.last_seen.get_mut(origin) {
+            None => {
+                self.last_seen.insert(origin.clone(), Window::new(seq));
+                true
+            }
+            Some(window) => {
+                if window.insert(seq) {
+                    true
+                } else {
+                    let e = DuplicateEvent {
+                        origin: origin.clone(),
+                        seq,
+                        last_seen: window.highest,
+                    };
+                    self.on_duplicate.trigger(|fun| fun(&e));
+                    false
+                }
+            }
+        }
+    }
+
+    /// Decodes and applies a lib0 v1 encoded `update` with a given sequence number `seq` received
+    /// from a given `origin`, unless it has been already seen. Returns `true` if the update was
+    /// applied, or `false` if it was dropped as a duplicate.
+    ///
+    /// Sequence number is not recorded if the update could not be decoded, so that the sender
+    /// can retry.
+    pub fn apply_update_v1(
+        &mut self,
+        txn: &mut TransactionMut,
+        origin: &Origin,
+        seq: u64,
+        update: &[u8],
+    ) -> Result<bool, Error> {
+        self.apply(txn, origin, seq, || Update::decode_v1(update))
+    }
+
+    /// Decodes and applies a lib0 v2 encoded `update` with a given sequence number `seq` received
+    /// from a given `origin`, unless it has been already seen. Returns `true` if the update was
+    /// applied, or `false` if it was dropped as a duplicate.
+    ///
+    /// Sequence number is not recorded if the update could not be decoded, so that the sender
+    /// can retry.
+    pub fn apply_update_v2(
+        &mut self,
+        txn: &mut TransactionMut,
+        origin: &Origin,
+        seq: u64,
+        update: &[u8],
+    ) -> Result<bool, Error> {
+        self.apply(txn, origin, seq, || Update::decode_v2(update))
+    }
+
+    fn apply<F>(
+        &mut self,
+        txn: &mut TransactionMut,
+        origin: &Origin,
+        seq: u64,
+        decode: F,
+    ) -> Result<bool, Error>
+    where
+        F: FnOnce() -> Result<Update, Error>,
+    {
+        let prev = self.last_seen.get(origin).copied();
+        if !self.check(origin, seq) {
+            return Ok(false);
+        }
+        match decode() {
+            Ok(update) => {
+                txn.apply_update(update);
+                Ok(true)
+            }
+            Err(e) => {
+                match prev {
+                    None => self.last_seen.remove(origin),
+                    Some(prev) => self.last_seen.insert(origin.clone(), prev),
+                };
+                Err(e)
+            }
+        }
+    }
+
+    /// Subscribes a callback, which will be called whenever a duplicated message is dropped.
+    #[cfg(feature = "sync")]
+    pub fn on_duplicate<F>(&self, f: F) -> crate::Subscription
+    where
+        F: Fn(&DuplicateEvent) + Send + Sync + 'static,
+    {
+        self.on_duplicate.subscribe(Box::new(f))
+    }
+
+    /// Subscribes a callback, which will be called whenever a duplicated message is dropped.
+    #[cfg(not(feature = "sync"))]
+    pub fn on_duplicate<F>(&self, f: F) -> crate::Subscription
+    where
+        F: Fn(&DuplicateEvent) + 'static,
+    {
+        self.on_duplicate.subscribe(Box::new(f))
+    }
+}
+
+impl Default for ReplayGuard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Debug for ReplayGuard {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ReplayGuard")
+            .field("last_seen", &self.last_seen)
+            .finish()
+    }
+}
+
+/// Number of sequence numbers preceding the highest one seen, which are tracked by [ReplayGuard]
+/// for each origin. Messages delayed by more than that are dropped.
+pub const REPLAY_WINDOW: u64 = 64;
+
+/// Sequence numbers seen from a single origin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Window {
+    /// Highest sequence number seen.
+    highest: u64,
+    /// Bitmap of seen sequence numbers: `n`-th bit is set if `highest - n` has been seen.
+    seen: u64,
+}
+
+impl Window {
+    fn new(seq: u64) -> Self {
+        Window {
+            highest: seq,
+            seen: 1,
+        }
+    }
+
+    /// Records a given sequence number. Returns `false` if it has been seen already or it's too
+    /// old to tell.
+    fn insert(&mut self, seq: u64) -> bool {
+        if seq > self.highest {
+            let shift = seq - self.highest;
+            self.seen = if shift < REPLAY_WINDOW {
+                (self.seen << shift) | 1
+            } else {
+                1
+            };
+            self.highest = seq;
+            true
+        } else {
+            let offset = self.highest - seq;
+            if offset >= REPLAY_WINDOW || self.seen & (1 << offset) != 0 {
+                false
+            } else {
+                self.seen |= 1 << offset;
+                true
+            }
+        }
+    }
+}
+
+/// Event emitted by [ReplayGuard] when a message has been dropped as a duplicate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateEvent {
+    /// Origin of a dropped message.
+    pub origin: Origin,
+    /// Sequence number of a dropped message.
+    pub seq: u64,
+    /// Highest sequence number seen from the same origin. Messages with sequence numbers lower by
+    /// [REPLAY_WINDOW] or more are dropped even if they have not been seen before.
+    pub last_seen: u64,
+}
+
+#[cfg(test)]
+mod test {
+    use crate::sync::replay::REPLAY_WINDOW;
+    use crate::sync::ReplayGuard;
+    use crate::{Doc, GetString, Origin, ReadTxn, StateVector, Text, Transact};
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn drop_duplicates() {
+        let remote = Doc::with_client_id(1);
+        let text = remote.get_or_insert_text("text");
+        text.push(&mut remote.transact_mut(), "a");
+        let u1 = remote
+            .transact()
+            .encode_state_as_update_v1(&StateVector::default());
+        let sv = remote.transact().state_vector();
+        text.push(&mut remote.transact_mut(), "b");
+        let u2 = remote.transact().encode_state_as_update_v1(&sv);
+
+        let doc = Doc::with_client_id(2);
+        let text = doc.get_or_insert_text("text");
+        let mut guard = ReplayGuard::new();
+        let dropped = Arc::new(Mutex::new(Vec::new()));
+        let _sub = {
+            let dropped = dropped.clone();
+            guard.on_duplicate(move |e| dropped.lock().unwrap().push((e.seq, e.last_seen)))
+        };
+        let a = Origin::from("a");
+        let b = Origin::from("b");
+
+        let mut txn = doc.transact_mut();
+        assert!(guard.apply_update_v1(&mut txn, &a, 1, &u1).unwrap());
+        assert!(guard.apply_update_v1(&mut txn, &a, 2, &u2).unwrap());
+        assert!(!guard.apply_update_v1(&mut txn, &a, 1, &u1).unwrap());
+        assert!(!guard.apply_update_v1(&mut txn, &a, 2, &u2).unwrap());
+        // sequences are tracked separately for each origin
+        assert!(guard.apply_update_v1(&mut txn, &b, 1, &u1).unwrap());
+        // malformed updates don't advance the sequence
+        assert!(guard.apply_update_v1(&mut txn, &b, 2, &[1]).is_err());
+        assert_eq!(guard.last_seen(&b), Some(1));
+        assert_eq!(text.get_string(&txn), "ab");
+        assert_eq!(dropped.lock().unwrap().as_slice(), &[(1, 2), (2, 2)]);
+    }
+    #[test]
+    fn accept_out_of_order() {
+        let remote = Doc::with_client_id(1);
+        let text = remote.get_or_insert_text("text");
+        let mut updates = Vec::new();
+        for c in ["a", "b", "c"].iter() {
+            let sv = remote.transact().state_vector();
+            text.push(&mut remote.transact_mut(), c);
+            updates.push(remote.transact().encode_state_as_update_v1(&sv));
+        }
+
+        let doc = Doc::with_client_id(2);
+        let text = doc.get_or_insert_text("text");
+        let mut guard = ReplayGuard::new();
+        let a = Origin::from("a");
+        let mut txn = doc.transact_mut();
+        assert!(guard.apply_update_v1(&mut txn, &a, 3, &updates[2]).unwrap());
+        assert!(guard.apply_update_v1(&mut txn, &a, 1, &updates[0]).unwrap());
+        assert!(!guard.apply_update_v1(&mut txn, &a, 1, &updates[0]).unwrap());
+        // malformed updates don't mark a sequence number as seen
+        assert!(guard.apply_update_v1(&mut txn, &a, 2, &[1]).is_err());
+        assert!(guard.apply_update_v1(&mut txn, &a, 2, &updates[1]).unwrap());
+        assert!(!guard.apply_update_v1(&mut txn, &a, 3, &updates[2]).unwrap());
+        assert_eq!(guard.last_seen(&a), Some(3));
+        assert_eq!(text.get_string(&txn), "abc");
+    }
+
+    #[test]
+    fn sliding_window() {
+        let mut guard = ReplayGuard::new();
+        let a = Origin::from("a");
+        assert!(guard.check(&a, 10));
+        assert!(guard.check(&a, 10 + REPLAY_WINDOW - 1));
+        // still within the window
+        assert!(guard.check(&a, 11));
+        assert!(!guard.check(&a, 11));
+        assert!(!guard.check(&a, 10));
+        // window moved past a sequence number, which has never been seen
+        assert!(guard.check(&a, 10 + REPLAY_WINDOW + 2));
+        assert!(!guard.check(&a, 12));
+        assert!(guard.check(&a, 13));
+        // jumping further than the window size forgets all previous numbers
+        assert!(guard.check(&a, 1000));
+        assert!(!guard.check(&a, 13 + REPLAY_WINDOW));
+        assert!(guard.check(&a, 999));
+        assert_eq!(guard.last_seen(&a), Some(1000));
+    }
+}
@@ -1,19 +1,30 @@
-use crate::block::{Item, ItemContent, ItemPtr, Prelim, ID};
+use crate::blame::{Actor, ActorKind, BlockMeta, BlockMetaUpdate};
+use crate::block::{BlockCell, ClientID, Item, ItemContent, ItemPtr, Prelim, ID};
+use crate::block_iter::{BlockIter, CursorCache};
+#[cfg(feature = "borrow-tracker")]
+use crate::borrow_tracker::{BorrowGuard, TransactionKind};
 use crate::branch::{Branch, BranchPtr};
-use crate::doc::DocAddr;
+use crate::destructive::DestructiveOp;
+use crate::doc::{DocAddr, GcPolicy};
 use crate::error::Error;
-use crate::event::SubdocsEvent;
+use crate::event::{RootsEvent, SubdocsEvent};
 use crate::gc::GCCollector;
 use crate::id_set::DeleteSet;
 use crate::iter::TxnIterator;
+use crate::observer::call_isolated;
 use crate::slice::BlockSlice;
 use crate::store::{Store, StoreEvents, SubdocGuids, SubdocsIter};
-use crate::types::{Event, Events, RootRef, SharedRef, TypePtr};
+use crate::types::{AsPrelim, Event, Path, PathSegment, RootRef, SharedRef, TypePtr, TypeRef};
+use crate::undo::UndoStack;
 use crate::update::Update;
+use crate::updates::borrowed::BorrowedUpdate;
+use crate::updates::decoder::Decode;
 use crate::utils::OptionExt;
+use crate::xml_index::XmlIdIndex;
 use crate::*;
 use atomic_refcell::{AtomicRef, AtomicRefMut};
 use smallvec::SmallVec;
+use std::cell::RefCell;
 use std::collections::{HashMap, HashSet, VecDeque};
 use std::fmt::Formatter;
 use std::hash::Hash;
@@ -27,6 +38,19 @@ use updates::encoder::*;
 pub trait ReadTxn: Sized {
     fn store(&self) -> &Store;
 
+    /// Returns a cache of positions visited by index-based reads within current transaction,
+    /// if transaction maintains any.
+    #[doc(hidden)]
+    fn cursors(&self) -> Option<&CursorCache> {
+        None
+    }
+
+    /// Called whenever contents of a given `branch` are being read using current transaction.
+    /// Used to track dependencies of reactive reads.
+    #[doc(hidden)]
+    #[inline]
+    fn track(&self, _branch: &Branch) {}
+
     /// Returns state vector describing current state of the updates.
     fn state_vector(&self) -> StateVector {
         self.store().blocks.get_state_vector()
@@ -59,12 +83,77 @@ pub trait ReadTxn: Sized {
     }
 
     fn encode_diff_v1(&self, state_vector: &StateVector) -> Vec<u8> {
+        if let Some(update) = self.store().buffered_diff(state_vector) {
+            return update.encode_v1();
+        }
         let mut encoder = EncoderV1::new();
         self.encode_diff(state_vector, &mut encoder);
         encoder.to_vec()
     }
 
+    /// Returns metadata (timestamp and author) recorded for a block with a given `id`.
+    /// See: [Doc::record_block_meta].
+    fn block_meta(&self, id: &ID) -> Option<BlockMeta> {
+        self.store().block_meta.as_ref()?.get(id).cloned()
+    }
+
+    /// Returns metadata of blocks, which are not included in a given `state_vector`. It's meant to
+    /// be sent to a remote peer alongside a corresponding [ReadTxn::encode_diff_v1] update and
+    /// applied there using [TransactionMut::apply_block_meta].
+    fn block_meta_update(&self, state_vector: &StateVector) -> BlockMetaUpdate {
+        match self.store().block_meta.as_deref() {
+            Some(log) => log.diff(state_vector),
+            None => BlockMetaUpdate::default(),
+        }
+    }
+
+    /// Reads only the parts of a document state requested by a given `selection` and returns them
+    /// as a JSON-like [Any] tree. See [crate::query] for the description of a selection language.
+    fn query(&self, selection: &crate::query::Selection) -> Any {
+        crate::query::query(self, selection)
+    }
+
+    /// Returns a read-only, deep copy of a current document state, which stays unaffected by any
+    /// changes made to the original document afterwards. Returned document can be sent to another
+    /// thread and read there after current transaction has finished - i.e. to index its contents
+    /// in the background - without a need to encode and decode its whole state.
+    ///
+    /// Copies made while the document doesn't change share the same blocks, so that calling this
+    /// method repeatedly - i.e. once per transaction - is cheap. However blocks of the document
+    /// are linked with each other and updated in place, so once the document changes, the next
+    /// copy has to copy every block and shared type again: its time and memory cost grows with
+    /// the size of the document.
+    ///
+    /// Returned document is read-only: attempts to open a read-write transaction over it will fail.
+    /// It has no observers, gets its own [crate::Options::guid] and client ID, and shares its
+    /// sub-documents with the original one.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use yrs::{Doc, GetString, ReadTxn, Text, Transact};
+    ///
+    /// let doc = Doc::new();
+    /// let text = doc.get_or_insert_text("text");
+    /// text.push(&mut doc.transact_mut(), "hello");
+    ///
+    /// let copy = doc.transact().read_only_copy();
+    /// text.push(&mut doc.transact_mut(), " world");
+    ///
+    /// let handle = std::thread::spawn(move || {
+    ///     let txn = copy.transact();
+    ///     txn.get_text("text").unwrap().get_string(&txn)
+    /// });
+    /// assert_eq!(handle.join().unwrap(), "hello");
+    /// ```
+    fn read_only_copy(&self) -> Doc {
+        self.store().read_only_copy()
+    }
+
     fn encode_diff_v2(&self, state_vector: &StateVector) -> Vec<u8> {
+        if let Some(update) = self.store().buffered_diff(state_vector) {
+            return update.encode_v2();
+        }
         let mut encoder = EncoderV2::new();
         self.encode_diff(state_vector, &mut encoder);
         encoder.to_vec()
@@ -92,6 +181,84 @@ pub trait ReadTxn: Sized {
         merge_pending_v2(encoder.to_vec(), self.store())
     }
 
+    /// Encodes the difference between a document state described by a given `snapshot` and the
+    /// current state of a document: blocks inserted since the snapshot has been made, together
+    /// with a delete set limited to deletions not known to the snapshot. Applying it on a document
+    /// restored from the `snapshot` (see: [crate::Doc::restore_from_snapshot]) brings it to the
+    /// current state, which makes it suitable for storing incremental version history.
+    fn encode_diff_since_snapshot<E: Encoder>(&self, snapshot: &Snapshot, encoder: &mut E) {
+        let store = self.store();
+        store.write_blocks_from(&snapshot.state_map, encoder);
+        let ds = DeleteSet::from(&store.blocks).subtract(&snapshot.delete_set);
+        ds.encode(encoder);
+    }
+
+    /// Same as [ReadTxn::encode_diff_since_snapshot], but returns lib0 v1 encoded update.
+    fn encode_diff_since_snapshot_v1(&self, snapshot: &Snapshot) -> Vec<u8> {
+        let mut encoder = EncoderV1::new();
+        self.encode_diff_since_snapshot(snapshot, &mut encoder);
+        encoder.to_vec()
+    }
+
+    /// Same as [ReadTxn::encode_diff_since_snapshot], but returns lib0 v2 encoded update.
+    fn encode_diff_since_snapshot_v2(&self, snapshot: &Snapshot) -> Vec<u8> {
+        let mut encoder = EncoderV2::new();
+        self.encode_diff_since_snapshot(snapshot, &mut encoder);
+        encoder.to_vec()
+    }
+
+    /// Encodes a backup of changes made in a document since a given `snapshot` was taken: blocks
+    /// inserted and deleted after it. Backups made since an empty snapshot contain a full
+    /// document state. Backups can be restored with [TransactionMut::apply_backup] - see
+    /// [crate::backup] for details.
+    fn encode_backup_since(&self, snapshot: &Snapshot) -> Vec<u8> {
+        crate::backup::Backup::encode(self, snapshot)
+    }
+
+    /// Encodes all blocks missing by a given state vector `sv` as a sequence of lib0 v1 encoded
+    /// updates, each one of which doesn't exceed `max_chunk_bytes` - useful for transports which
+    /// limit the size of a single message.
+    ///
+    /// Every returned update can be decoded and applied on its own. Updates are split along
+    /// block boundaries, so a single block bigger than `max_chunk_bytes` is emitted as a separate,
+    /// oversized update. Delete set is sent as part of the last update if it fits there, or
+    /// in trailing updates with no blocks otherwise. Updates applied out of order are integrated
+    /// once their missing predecessors arrive. Pending updates are not included.
+    ///
+    /// Returns an empty vector if there's nothing to send.
+    fn encode_state_as_update_chunked(
+        &self,
+        sv: &StateVector,
+        max_chunk_bytes: usize,
+    ) -> Vec<Vec<u8>> {
+        let store = self.store();
+        let encode = |ranges: &[BlockRange], ds: &DeleteSet| {
+            let mut encoder = EncoderV1::new();
+            store.write_block_ranges(ranges, &mut encoder);
+            ds.encode(&mut encoder);
+            encoder.to_vec()
+        };
+        let chunks = store.chunk_block_ranges(sv, max_chunk_bytes);
+        let mut updates: Vec<_> = chunks
+            .iter()
+            .map(|ranges| encode(ranges, &DeleteSet::new()))
+            .collect();
+        let ds = DeleteSet::from(&store.blocks);
+        if !ds.is_empty() {
+            if let Some(last) = chunks.last() {
+                let update = encode(last, &ds);
+                if update.len() <= max_chunk_bytes {
+                    *updates.last_mut().unwrap() = update;
+                    return updates;
+                }
+            }
+            for ds in Store::chunk_delete_set(&ds, max_chunk_bytes) {
+                updates.push(encode(&[], &ds));
+            }
+        }
+        updates
+    }
+
     /// Check if given node is alive. Returns false if node has been deleted.
     fn is_alive<B>(&self, node: &B) -> bool
     where
@@ -107,6 +274,15 @@ pub trait ReadTxn: Sized {
         RootRefs(store.types.iter())
     }
 
+    /// Traverses the structure of a whole document - starting from its root-level collections -
+    /// and calls corresponding methods of a given `visitor` for every visited node.
+    /// See: [DocVisitor].
+    ///
+    /// [DocVisitor]: crate::visit::DocVisitor
+    fn visit<V: crate::visit::DocVisitor>(&self, visitor: &mut V) {
+        crate::visit::visit_doc(self, visitor)
+    }
+
     /// Returns a collection of globally unique identifiers of sub documents linked within
     /// the structures of this document store.
     fn subdoc_guids(&self) -> SubdocGuids {
@@ -184,6 +360,11 @@ pub trait WriteTxn: Sized {
     fn store_mut(&mut self) -> &mut Store;
     fn subdocs_mut(&mut self) -> &mut Subdocs;
 
+    /// Returns origin of the transaction if any was defined.
+    fn origin(&self) -> Option<&Origin> {
+        None
+    }
+
     /// Returns a [TextRef] data structure stored under a given `name`. Text structures are used for
     /// collaborative text editing: they expose operations to append and remove chunks of text,
     /// which are free to execute concurrently by multiple peers over remote boundaries.
@@ -213,6 +394,34 @@ pub trait WriteTxn: Sized {
         MapRef::root(name).get_or_create(self)
     }
 
+    /// Returns a [CounterRef] data structure stored under a given `name`. Counters are numeric
+    /// values, which can be incremented concurrently by many peers without overwriting each
+    /// other's changes.
+    ///
+    /// If no structure under defined `name` existed before, it will be created and returned
+    /// instead.
+    fn get_or_insert_counter<N: Into<Arc<str>>>(&mut self, name: N) -> CounterRef {
+        CounterRef::root(name).get_or_create(self)
+    }
+
+    /// Returns a [GSetRef] data structure stored under a given `name`. Grow-only sets store unique
+    /// values, which can be inserted but never removed.
+    ///
+    /// If no structure under defined `name` existed before, it will be created and returned
+    /// instead.
+    fn get_or_insert_gset<N: Into<Arc<str>>>(&mut self, name: N) -> GSetRef {
+        GSetRef::root(name).get_or_create(self)
+    }
+
+    /// Returns a [TwoPhaseSetRef] data structure stored under a given `name`. Two-phase sets store
+    /// unique values, which can be removed - but once removed, they cannot be inserted again.
+    ///
+    /// If no structure under defined `name` existed before, it will be created and returned
+    /// instead.
+    fn get_or_insert_two_phase_set<N: Into<Arc<str>>>(&mut self, name: N) -> TwoPhaseSetRef {
+        TwoPhaseSetRef::root(name).get_or_create(self)
+    }
+
     /// Returns an [ArrayRef] data structure stored under a given `name`. Array structures are used for
     /// storing a sequences of elements in ordered manner, positioning given element accordingly
     /// to its index.
@@ -287,11 +496,27 @@ fn merge_pending_v2(update: Vec<u8>, store: &Store) -> Vec<u8> {
 #[derive(Debug)]
 pub struct Transaction<'doc> {
     store: AtomicRef<'doc, Store>,
+    cursors: CursorCache,
+    #[cfg(feature = "borrow-tracker")]
+    pub(crate) borrow: Option<BorrowGuard>,
 }
 
 impl<'doc> Transaction<'doc> {
     pub(crate) fn new(store: AtomicRef<'doc, Store>) -> Self {
-        Transaction { store }
+        Transaction {
+            store,
+            cursors: CursorCache::default(),
+            #[cfg(feature = "borrow-tracker")]
+            borrow: None,
+        }
+    }
+
+    /// Registers this transaction in a borrow tracker of a given `doc`.
+    #[cfg(feature = "borrow-tracker")]
+    #[track_caller]
+    pub(crate) fn tracked(mut self, doc: &Doc) -> Self {
+        self.borrow = Some(BorrowGuard::new(doc, TransactionKind::ReadOnly));
+        self
     }
 }
 
@@ -300,6 +525,11 @@ impl<'doc> ReadTxn for Transaction<'doc> {
     fn store(&self) -> &Store {
         self.store.deref()
     }
+
+    #[inline]
+    fn cursors(&self) -> Option<&CursorCache> {
+        Some(&self.cursors)
+    }
 }
 
 /// Read-write transaction. It can be used to modify an underlying state of the corresponding [Doc].
@@ -333,8 +563,25 @@ pub struct TransactionMut<'doc> {
     pub(crate) changed_parent_types: Vec<BranchPtr>,
     pub(crate) subdocs: Option<Box<Subdocs>>,
     pub(crate) origin: Option<Origin>,
+    /// Actor making changes within current transaction. See [TransactionMut::set_actor].
+    pub(crate) actor: Option<Actor>,
+    /// True if any remote update has been applied within the scope of current transaction.
+    pub(crate) remote: bool,
+    /// Values of map entries overwritten by concurrent writes, which have a resolver registered
+    /// with [MapRef::set_resolver].
+    pub(crate) map_conflicts: Vec<(BranchPtr, Arc<str>, Any)>,
+    /// Positions visited by index-based reads, invalidated whenever their branch is modified.
+    pub(crate) cursors: CursorCache,
+    /// Panics caught in observer callbacks. See [TransactionMut::isolate].
+    pub(crate) callback_errors: RefCell<Vec<CallbackError>>,
     doc: Doc,
     committed: bool,
+    /// Registration of this transaction in a borrow tracker.
+    #[cfg(feature = "borrow-tracker")]
+    pub(crate) borrow: Option<BorrowGuard>,
+    /// Transactions scheduled with [TransactionMut::defer]. It's declared last, so that it's
+    /// dropped after the document store has been released.
+    deferred: Deferred,
 }
 
 impl<'doc> ReadTxn for TransactionMut<'doc> {
@@ -342,6 +589,11 @@ impl<'doc> ReadTxn for TransactionMut<'doc> {
     fn store(&self) -> &Store {
         self.store.deref()
     }
+
+    #[inline]
+    fn cursors(&self) -> Option<&CursorCache> {
+        Some(&self.cursors)
+    }
 }
 
 impl<'doc> WriteTxn for TransactionMut<'doc> {
@@ -353,6 +605,11 @@ impl<'doc> WriteTxn for TransactionMut<'doc> {
     fn subdocs_mut(&mut self) -> &mut Subdocs {
         self.subdocs.get_or_init()
     }
+
+    #[inline]
+    fn origin(&self) -> Option<&Origin> {
+        self.origin.as_ref()
+    }
 }
 
 impl<'doc> Drop for TransactionMut<'doc> {
@@ -368,6 +625,7 @@ impl<'doc> TransactionMut<'doc> {
             store,
             doc,
             origin,
+            actor: None,
             before_state: begin_timestamp,
             merge_blocks: Vec::default(),
             delete_set: DeleteSet::new(),
@@ -376,10 +634,25 @@ impl<'doc> TransactionMut<'doc> {
             changed_parent_types: Vec::default(),
             prev_moved: HashMap::default(),
             subdocs: None,
+            remote: false,
+            map_conflicts: Vec::new(),
+            cursors: CursorCache::default(),
+            callback_errors: RefCell::default(),
             committed: false,
+            #[cfg(feature = "borrow-tracker")]
+            borrow: None,
+            deferred: Deferred::default(),
         }
     }
 
+    /// Registers this transaction in a borrow tracker of a given `doc`.
+    #[cfg(feature = "borrow-tracker")]
+    #[track_caller]
+    pub(crate) fn tracked(mut self, doc: &Doc) -> Self {
+        self.borrow = Some(BorrowGuard::new(doc, TransactionKind::ReadWrite));
+        self
+    }
+
     pub fn doc(&self) -> &Doc {
         &self.doc
     }
@@ -398,6 +671,14 @@ impl<'doc> TransactionMut<'doc> {
         &self.after_state
     }
 
+    /// Returns per-client clock ranges of the blocks inserted in the scope of current transaction:
+    /// a difference between [TransactionMut::before_state] and the current state of a document.
+    /// Unlike [TransactionMut::after_state], it's available before the transaction is committed.
+    pub fn state_delta(&self) -> StateDelta {
+        let after = self.store.blocks.get_state_vector();
+        StateDelta::new(&self.before_state, &after)
+    }
+
     /// Data about deletions performed in the scope of current transaction.
     pub fn delete_set(&self) -> &DeleteSet {
         &self.delete_set
@@ -409,6 +690,172 @@ impl<'doc> TransactionMut<'doc> {
         self.origin.as_ref()
     }
 
+    /// Marks changes made within current transaction as made by a given actor. This information
+    /// is available to transaction and event observers via [TransactionMut::actor], can be used to
+    /// scope undo/redo operations (see: [UndoOptions::tracked_actors]) and - when enabled with
+    /// [Doc::record_block_meta] - it's recorded as the metadata of created blocks, so that
+    /// automated edits can be told apart from the human ones by [Text::blame].
+    ///
+    /// Transactions without an actor assigned are considered to be made by [ActorKind::Human].
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::sync::Arc;
+    /// use yrs::blame::ActorKind;
+    /// use yrs::{Doc, Text, Transact};
+    ///
+    /// let doc = Doc::new();
+    /// doc.record_block_meta(Some(Arc::from("alice"))).unwrap();
+    /// let text = doc.get_or_insert_text("text");
+    /// text.push(&mut doc.transact_mut(), "hello");
+    /// {
+    ///     let mut txn = doc.transact_mut();
+    ///     txn.set_actor(ActorKind::Automation, "spellcheck");
+    ///     text.push(&mut txn, " world");
+    /// }
+    ///
+    /// let blame = text.blame(&doc.transact());
+    /// let (range, _, meta) = &blame[1];
+    /// let meta = meta.as_ref().unwrap();
+    /// assert_eq!(range.clone(), 5..11);
+    /// assert_eq!(meta.kind, ActorKind::Automation);
+    /// assert_eq!(meta.author.as_deref(), Some("spellcheck"));
+    /// ```
+    ///
+    /// [UndoOptions::tracked_actors]: crate::undo::Options::tracked_actors
+    /// [Doc::record_block_meta]: crate::Doc::record_block_meta
+    /// [Text::blame]: crate::Text::blame
+    pub fn set_actor<S: Into<Arc<str>>>(&mut self, kind: ActorKind, id: S) {
+        self.actor = Some(Actor::new(kind, id));
+    }
+
+    /// Returns an actor assigned to current transaction with [TransactionMut::set_actor].
+    pub fn actor(&self) -> Option<&Actor> {
+        self.actor.as_ref()
+    }
+
+    /// Schedules a given function to be executed within a new read-write transaction, right after
+    /// current transaction has been committed and dropped. Deferred transactions inherit the
+    /// origin and actor of current transaction and are executed in the order they were scheduled.
+    ///
+    /// Since read-write transactions are exclusive, this is the way to make follow-up changes from
+    /// inside of observer callbacks, which only get a read-only access to a committed transaction.
+    /// Deferred functions can schedule further deferred transactions themselves. To protect
+    /// against observers endlessly triggering each other, at most [MAX_DEFERRED_TRANSACTIONS]
+    /// are executed in a row, and any remaining ones are discarded.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use yrs::{Doc, GetString, Observable, Text, Transact};
+    ///
+    /// let doc = Doc::new();
+    /// let text = doc.get_or_insert_text("text");
+    /// let target = text.clone();
+    /// let _sub = text.observe(move |txn, _| {
+    ///     let text = target.clone();
+    ///     txn.defer(move |txn| {
+    ///         // make sure that the text always ends with a dot
+    ///         if !text.get_string(txn).ends_with('.') {
+    ///             text.push(txn, ".");
+    ///         }
+    ///     });
+    /// });
+    ///
+    /// text.push(&mut doc.transact_mut(), "hello");
+    /// assert_eq!(text.get_string(&doc.transact()), "hello.");
+    /// ```
+    #[cfg(feature = "sync")]
+    pub fn defer<F>(&self, f: F)
+    where
+        F: FnOnce(&mut TransactionMut) + Send + 'static,
+    {
+        self.push_deferred(Box::new(f))
+    }
+
+    /// Schedules a given function to be executed within a new read-write transaction, right after
+    /// current transaction has been committed and dropped. Deferred transactions inherit the
+    /// origin and actor of current transaction and are executed in the order they were scheduled.
+    ///
+    /// Since read-write transactions are exclusive, this is the way to make follow-up changes from
+    /// inside of observer callbacks, which only get a read-only access to a committed transaction.
+    /// Deferred functions can schedule further deferred transactions themselves. To protect
+    /// against observers endlessly triggering each other, at most [MAX_DEFERRED_TRANSACTIONS]
+    /// are executed in a row, and any remaining ones are discarded.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use yrs::{Doc, GetString, Observable, Text, Transact};
+    ///
+    /// let doc = Doc::new();
+    /// let text = doc.get_or_insert_text("text");
+    /// let target = text.clone();
+    /// let _sub = text.observe(move |txn, _| {
+    ///     let text = target.clone();
+    ///     txn.defer(move |txn| {
+    ///         // make sure that the text always ends with a dot
+    ///         if !text.get_string(txn).ends_with('.') {
+    ///             text.push(txn, ".");
+    ///         }
+    ///     });
+    /// });
+    ///
+    /// text.push(&mut doc.transact_mut(), "hello");
+    /// assert_eq!(text.get_string(&doc.transact()), "hello.");
+    /// ```
+    #[cfg(not(feature = "sync"))]
+    pub fn defer<F>(&self, f: F)
+    where
+        F: FnOnce(&mut TransactionMut) + 'static,
+    {
+        self.push_deferred(Box::new(f))
+    }
+
+    fn push_deferred(&self, task: DeferredFn) {
+        let mut queue = self.deferred.0.borrow_mut();
+        let queue = queue.get_or_insert_with(|| {
+            Box::new(DeferredQueue {
+                doc: self.doc.clone(),
+                origin: self.origin.clone(),
+                actor: self.actor.clone(),
+                tasks: VecDeque::new(),
+            })
+        });
+        queue.tasks.push_back(task);
+    }
+
+    /// Marks a block starting at a given `id` as a split point: it will never be squashed together
+    /// with the block preceding it, even when both blocks were inserted one after another by
+    /// the same client. Split points are used by [UndoManager] to keep the blocks inserted in
+    /// different capture intervals separate.
+    ///
+    /// Split points are reference counted: the same `id` can be marked many times - i.e. by
+    /// multiple undo managers - and it remains a split point until each of these marks has been
+    /// removed with [TransactionMut::remove_split_point].
+    ///
+    /// [UndoManager]: crate::UndoManager
+    pub fn split_point(&mut self, id: ID) {
+        *self.store.split_points.entry(id).or_default() += 1;
+    }
+
+    /// Removes a single mark of a split point previously set with [TransactionMut::split_point].
+    /// Once all of its marks are removed, a block starting at a given `id` can be squashed again.
+    /// Returns `false` if no split point was set.
+    pub fn remove_split_point(&mut self, id: &ID) -> bool {
+        match self.store.split_points.get_mut(id) {
+            None => false,
+            Some(count) => {
+                *count -= 1;
+                if *count == 0 {
+                    self.store.split_points.remove(id);
+                }
+                true
+            }
+        }
+    }
+
     /// Returns a list of root level types changed in a scope of the current transaction. This
     /// list is not filled right away, but as a part of [TransactionMut::commit] process.
     pub fn changed_parent_types(&self) -> &[BranchPtr] {
@@ -588,10 +1035,22 @@ impl<'doc> TransactionMut<'doc> {
                     parent.block_len -= item.len();
                     parent.content_len -= item.content_len(store.options.offset_kind);
                 }
+            } else if let Some(key) = &item.parent_sub {
+                if let TypePtr::Branch(mut parent) = item.parent {
+                    if parent.map.get(key) == Some(&item) {
+                        parent.map_len -= 1;
+                    }
+                }
             }
 
             item.mark_as_deleted();
+            if item.parent_sub.is_none() {
+                if let TypePtr::Branch(mut parent) = item.parent {
+                    parent.index_updated(item);
+                }
+            }
             self.delete_set.insert(item.id.clone(), item.len());
+            self.store.log_delete(item.id, item.len());
             if let Some(parent) = item.parent.as_branch() {
                 self.add_changed_type(*parent, item.parent_sub.clone());
             } else {
@@ -612,6 +1071,7 @@ impl<'doc> TransactionMut<'doc> {
                     #[cfg(feature = "weak")]
                     if let crate::types::TypeRef::WeakLink(source) = &branch_ptr.type_ref {
                         source.unlink_all(self, branch_ptr);
+                        self.store.orphaned_links.remove(&branch_ptr);
                     }
                     let mut ptr = branch_ptr.start;
                     self.changed.remove(&TypePtr::Branch(branch_ptr));
@@ -641,6 +1101,7 @@ impl<'doc> TransactionMut<'doc> {
                             if source.is_single() {
                                 source.first_item.take();
                             }
+                            self.store.orphaned_links.insert(link);
                         }
                     }
                 }
@@ -672,7 +1133,108 @@ impl<'doc> TransactionMut<'doc> {
     /// Remote update integration requires that all to-be-integrated blocks must have their direct
     /// predecessors already in place. Out of order updates from the same peer will be stashed
     /// internally and their integration will be postponed until missing blocks arrive first.
+    /// Limits on how long and how large such pending update can grow can be configured with
+    /// [Doc::set_pending_policy].
     pub fn apply_update(&mut self, update: Update) {
+        self.apply_update_unchecked(update);
+        crate::pending::enforce(self);
+    }
+
+    /// Applies an update decoded with [Update::decode_v1_borrowed]. Only the contents of blocks,
+    /// which are not known to a current document yet, are copied.
+    pub fn apply_update_borrowed(&mut self, update: BorrowedUpdate) {
+        let update = update.into_update_since(&self.state_vector());
+        self.apply_update(update);
+    }
+
+    /// Applies a backup produced by [ReadTxn::encode_backup_since] on top of a current document
+    /// state. Incremental backups must be applied in order, after the backups preceding them.
+    ///
+    /// Returns an error if a backup is malformed or current document doesn't contain the state
+    /// a backup was made on top of, i.e. because a previous incremental backup is missing.
+    pub fn apply_backup(&mut self, backup: &[u8]) -> Result<(), Error> {
+        let backup = crate::backup::Backup::decode(backup)?;
+        backup.check_base(&self.snapshot())?;
+        self.apply_update(backup.update);
+        Ok(())
+    }
+
+    /// Merges block metadata received from a remote peer (see: [ReadTxn::block_meta_update]).
+    /// Metadata already known for a given block is not overridden.
+    pub fn apply_block_meta(&mut self, update: BlockMetaUpdate) {
+        if !update.is_empty() {
+            self.store.block_meta.get_or_init().apply(update);
+        }
+    }
+
+    /// Applies a lib0 v1 encoded update read from a given `reader`. Unlike [Self::apply_update],
+    /// the update doesn't have to be loaded into memory first: its blocks are decoded and
+    /// integrated incrementally, in batches of up to [STREAM_BATCH_LEN] blocks.
+    ///
+    /// If decoding fails midway, blocks integrated up to that point remain in the document.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use yrs::{Doc, GetString, ReadTxn, StateVector, Text, Transact};
+    ///
+    /// let source = Doc::new();
+    /// let text = source.get_or_insert_text("text");
+    /// text.push(&mut source.transact_mut(), "hello world");
+    /// let bytes = source.transact().encode_state_as_update_v1(&StateVector::default());
+    ///
+    /// let doc = Doc::new();
+    /// let text = doc.get_or_insert_text("text");
+    /// doc.transact_mut().apply_update_from(bytes.as_slice()).unwrap();
+    /// assert_eq!(text.get_string(&doc.transact()), "hello world");
+    /// ```
+    pub fn apply_update_from<R: std::io::Read>(&mut self, reader: R) -> Result<(), Error> {
+        use crate::encoding::read::Read;
+        use crate::update::{BlockCarrier, UpdateBlocks};
+        use crate::updates::decoder::{Decoder, StreamDecoderV1};
+
+        let mut decoder = StreamDecoderV1::new(reader);
+        let mut batch = UpdateBlocks::default();
+        let mut batch_len = 0;
+        let clients_len: u32 = decoder.read_var()?;
+        for _ in 0..clients_len {
+            let blocks_len: u32 = decoder.read_var()?;
+            let client = decoder.read_client()?;
+            let mut clock: u32 = decoder.read_var()?;
+            for _ in 0..blocks_len {
+                let id = ID::new(client, clock);
+                if let Some(block) = Update::decode_block(id, &mut decoder)? {
+                    let len = block.len();
+                    clock = clock
+                        .checked_add(len)
+                        .ok_or(crate::encoding::read::Error::UnexpectedValue)?;
+                    if len != 0 || matches!(block, BlockCarrier::Item(_)) {
+                        batch.add_block(block);
+                        batch_len += 1;
+                    }
+                }
+                if batch_len == STREAM_BATCH_LEN {
+                    let blocks = std::mem::take(&mut batch);
+                    self.apply_update_unchecked(Update {
+                        blocks,
+                        delete_set: DeleteSet::default(),
+                    });
+                    batch_len = 0;
+                }
+            }
+        }
+        let delete_set = DeleteSet::decode(&mut decoder)?;
+        self.apply_update(Update {
+            blocks: batch,
+            delete_set,
+        });
+        Ok(())
+    }
+
+    /// Applies an [Update] without verifying the resulting pending update against
+    /// a [PendingPolicy][crate::pending::PendingPolicy].
+    pub(crate) fn apply_update_unchecked(&mut self, update: Update) {
+        self.remote = true;
         let (remaining, remaining_ds) = update.integrate(self);
         let mut retry = false;
         {
@@ -720,8 +1282,8 @@ impl<'doc> TransactionMut<'doc> {
                 let ds = store.pending_ds.take().unwrap_or_default();
                 let mut ds_update = Update::new();
                 ds_update.delete_set = ds;
-                self.apply_update(pending.update);
-                self.apply_update(ds_update)
+                self.apply_update_unchecked(pending.update);
+                self.apply_update_unchecked(ds_update)
             }
         }
     }
@@ -775,6 +1337,281 @@ impl<'doc> TransactionMut<'doc> {
         Some(block_ptr)
     }
 
+    /// Low-level operation API: inserts a `value` into a sequence of a given `parent` collection
+    /// as a new block, using `left` and `right` as its origins - IDs of elements, which should be
+    /// its direct neighbours at the moment of insertion. `None` stands for the beginning and the
+    /// end of a sequence respectively. Origins are resolved and integrated the same way as
+    /// insertions coming from remote peers, so this method can be used to build custom shared
+    /// types or to replay operation logs from other systems.
+    ///
+    /// Returns an ID of a newly inserted block. Returns an error if any of the origins couldn't be
+    /// found in current document, doesn't belong to a `parent` sequence, or if there are any
+    /// non-deleted elements between the origins.
+    pub fn insert_between<P, V>(
+        &mut self,
+        parent: &P,
+        left: Option<&ID>,
+        right: Option<&ID>,
+        value: V,
+    ) -> Result<ID, Error>
+    where
+        P: AsRef<Branch>,
+        V: Prelim,
+    {
+        let parent = BranchPtr::from(parent.as_ref());
+        let store = self.store_mut();
+        let not_found = |id: &ID| Error::Validation(format!("element {} not found", id));
+        let left = match left {
+            None => None,
+            Some(id) => {
+                let slice = store
+                    .blocks
+                    .get_item_clean_end(id)
+                    .ok_or_else(|| not_found(id))?;
+                Some(store.materialize(slice))
+            }
+        };
+        let right = match right {
+            None => None,
+            Some(id) => {
+                let slice = store
+                    .blocks
+                    .get_item_clean_start(id)
+                    .ok_or_else(|| not_found(id))?;
+                Some(store.materialize(slice))
+            }
+        };
+        for item in left.iter().chain(right.iter()) {
+            if item.parent.as_branch() != Some(&parent) || item.parent_sub.is_some() {
+                return Err(Error::Validation(format!(
+                    "element {} doesn't belong to a parent sequence",
+                    item.id
+                )));
+            }
+        }
+        let mut next = match left {
+            Some(item) => item.right,
+            None => parent.start,
+        };
+        while next != right {
+            match next {
+                Some(item) if item.is_deleted() => next = item.right,
+                _ => {
+                    return Err(Error::Validation(
+                        "left and right origins are not adjacent".into(),
+                    ))
+                }
+            }
+        }
+        let pos = block::ItemPosition {
+            parent: TypePtr::Branch(parent),
+            left,
+            right,
+            index: 0,
+            current_attrs: None,
+        };
+        let item = self
+            .create_item(&pos, value, None)
+            .ok_or_else(|| Error::Validation("value cannot be inserted".into()))?;
+        Ok(*item.id())
+    }
+
+    /// Low-level operation API: deletes all elements within `len` consecutive clock values
+    /// starting at a given `id`, no matter which collections they belong to.
+    ///
+    /// Returns `false` if some part of that range refers to elements that are not present in
+    /// current document, in which case that part is skipped.
+    pub fn delete_range(&mut self, id: &ID, len: u32) -> bool {
+        let complete = self.store.blocks.get_clock(&id.client) >= id.clock.saturating_add(len);
+        let mut ds = DeleteSet::new();
+        ds.insert(*id, len);
+        self.apply_delete(&ds);
+        complete
+    }
+
+    /// Low-level operation API: moves a range of elements of a `parent` sequence, delimited by
+    /// `start` and `end` sticky indexes, to a new position between `left` and `right` origins.
+    /// See [TransactionMut::insert_between] for origin semantics.
+    ///
+    /// Returns an ID of a newly inserted move block, or an error if origins are not valid.
+    pub fn move_range<P>(
+        &mut self,
+        parent: &P,
+        left: Option<&ID>,
+        right: Option<&ID>,
+        start: StickyIndex,
+        end: StickyIndex,
+    ) -> Result<ID, Error>
+    where
+        P: AsRef<Branch>,
+    {
+        let m = crate::moving::Move::new(start, end, -1);
+        self.insert_between(parent, left, right, m)
+    }
+
+    /// Moves a shared type living under `from` path to a new position described by `to` path.
+    /// Both paths start with a name of a root type and end with either a key inside of a parent
+    /// map or an index inside of a parent array. Root types themselves cannot be moved.
+    ///
+    /// When both paths point to the same parent array, the type is moved using move semantics,
+    /// preserving its identity together with its observers, weak links and sticky indexes - see
+    /// [Array::move_to]. Otherwise (eg. when moving into another parent collection or renaming
+    /// a map key) a deep copy of the type is inserted under `to` path and the original is
+    /// deleted within the same transaction, so that remote peers and undo manager observe both
+    /// changes as a single atomic operation. In that case a returned type has a new identity.
+    ///
+    /// All paths are validated before any change is made: on error the document stays unchanged.
+    ///
+    /// Returns a shared type under its new location.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::collections::VecDeque;
+    /// use yrs::types::{PathSegment, ToJson};
+    /// use yrs::{any, Array, Doc, Map, MapPrelim, Transact};
+    ///
+    /// let doc = Doc::new();
+    /// let array = doc.get_or_insert_array("array");
+    /// let map = doc.get_or_insert_map("map");
+    /// let mut txn = doc.transact_mut();
+    /// array.insert(&mut txn, 0, MapPrelim::from([("a", 1)]));
+    /// array.insert(&mut txn, 1, MapPrelim::from([("b", 2)]));
+    ///
+    /// let from = VecDeque::from([PathSegment::Key("array".into()), PathSegment::Index(1)]);
+    /// let to = VecDeque::from([PathSegment::Key("array".into()), PathSegment::Index(0)]);
+    /// txn.move_type(&from, &to).unwrap();
+    /// assert_eq!(array.to_json(&txn), any!([{"b": 2}, {"a": 1}]));
+    ///
+    /// let from = VecDeque::from([PathSegment::Key("array".into()), PathSegment::Index(1)]);
+    /// let to = VecDeque::from([PathSegment::Key("map".into()), PathSegment::Key("a".into())]);
+    /// txn.move_type(&from, &to).unwrap();
+    /// assert_eq!(array.to_json(&txn), any!([{"b": 2}]));
+    /// assert_eq!(map.to_json(&txn), any!({"a": {"a": 1}}));
+    /// ```
+    pub fn move_type(&mut self, from: &Path, to: &Path) -> Result<Out, Error> {
+        let source = self
+            .type_at_path(from)
+            .ok_or_else(|| Error::Validation(format!("no shared type under {:?}", from)))?;
+        let mut from_parent_path = from.clone();
+        let from_segment = from_parent_path.pop_back();
+        let mut to_parent_path = to.clone();
+        let to_segment = to_parent_path.pop_back();
+        if from_parent_path.is_empty() || to_parent_path.is_empty() {
+            return Err(Error::Validation("root types cannot be moved".into()));
+        }
+        if to_parent_path.len() >= from.len()
+            && to_parent_path.iter().zip(from).all(|(a, b)| a == b)
+        {
+            return Err(Error::Validation(format!(
+                "shared type cannot be moved into itself: {:?}",
+                to
+            )));
+        }
+        let from_parent = self.type_at_path(&from_parent_path).ok_or_else(|| {
+            Error::Validation(format!("no shared type under {:?}", from_parent_path))
+        })?;
+        let to_parent = self.type_at_path(&to_parent_path).ok_or_else(|| {
+            Error::Validation(format!("no shared type under {:?}", to_parent_path))
+        })?;
+
+        // validate destination before making any changes
+        match (&to_segment, to_parent.type_ref()) {
+            (Some(PathSegment::Key(_)), TypeRef::Map) => { /* any key is valid */ }
+            (Some(PathSegment::Index(index)), TypeRef::Array) => {
+                // when moving within the same array, its length doesn't change
+                let len = if from_parent == to_parent {
+                    to_parent.content_len - 1
+                } else {
+                    to_parent.content_len
+                };
+                if *index > len {
+                    return Err(Error::Validation(format!(
+                        "index {} is outside of the bounds of an array",
+                        index
+                    )));
+                }
+            }
+            _ => {
+                return Err(Error::Validation(format!(
+                    "{:?} doesn't point to a map entry or an array element",
+                    to
+                )))
+            }
+        }
+
+        match (from_segment, to_segment) {
+            (Some(PathSegment::Index(from_index)), Some(PathSegment::Index(to_index)))
+                if from_parent == to_parent =>
+            {
+                // native move preserving identity of a moved type
+                let array = ArrayRef::from(from_parent);
+                let to_index = if to_index > from_index {
+                    to_index + 1
+                } else {
+                    to_index
+                };
+                array.move_to(self, from_index, to_index);
+                Ok(source.into())
+            }
+            (from_segment, to_segment) => {
+                if from_parent.type_ref() != &TypeRef::Map
+                    && from_parent.type_ref() != &TypeRef::Array
+                {
+                    return Err(Error::Validation(format!(
+                        "{:?} doesn't point to a map entry or an array element",
+                        from
+                    )));
+                }
+                let source: Out = source.into();
+                let prelim = source.as_prelim(self);
+                match from_segment {
+                    Some(PathSegment::Key(key)) => {
+                        MapRef::from(from_parent).remove(self, &key);
+                    }
+                    Some(PathSegment::Index(index)) => {
+                        ArrayRef::from(from_parent).remove(self, index);
+                    }
+                    None => unreachable!(),
+                }
+                let result = match to_segment {
+                    Some(PathSegment::Key(key)) => {
+                        MapRef::from(to_parent).insert(self, key, prelim)
+                    }
+                    Some(PathSegment::Index(index)) => {
+                        ArrayRef::from(to_parent).insert(self, index, prelim)
+                    }
+                    None => unreachable!(),
+                };
+                Ok(result)
+            }
+        }
+    }
+
+    /// Resolves a shared type under a given `path`. Unlike [Store::get_type_from_path] array
+    /// indexes are resolved the same way as [Array::get] does, respecting moved elements.
+    fn type_at_path(&self, path: &Path) -> Option<BranchPtr> {
+        let mut segments = path.iter();
+        let mut current = match segments.next() {
+            Some(PathSegment::Key(root_name)) => self.store.get_type(root_name.clone())?,
+            _ => return None,
+        };
+        for segment in segments {
+            let value = match segment {
+                PathSegment::Key(key) => current.get(self, key)?,
+                PathSegment::Index(index) => {
+                    let mut walker = BlockIter::new(current);
+                    if !walker.try_forward(self, *index) {
+                        return None;
+                    }
+                    walker.read_value(self)?
+                }
+            };
+            current = BranchPtr::from(value.try_branch()?);
+        }
+        Some(current)
+    }
+
     fn call_type_observers(
         changed_parent_types: &mut Vec<BranchPtr>,
         all_links: &HashMap<ItemPtr, HashSet<BranchPtr>>,
@@ -786,7 +1623,7 @@ impl<'doc> TransactionMut<'doc> {
         let mut current = branch;
         loop {
             changed_parent_types.push(current);
-            if current.deep_observers.has_subscribers() {
+            if current.has_deep_observers() {
                 let entries = changed_parents.entry(current).or_default();
                 entries.push(event_cache.len() - 1);
             }
@@ -830,10 +1667,29 @@ impl<'doc> TransactionMut<'doc> {
             return;
         }
         self.committed = true;
+        self.cursors.clear();
 
         // 1. sort and merge delete set
         self.delete_set.squash();
         self.after_state = self.store.blocks.get_state_vector();
+        if !self.delete_set.is_empty() || self.after_state != self.before_state {
+            // read-only copy no longer reflects current document state
+            *self.store.frozen_copy.get_mut().unwrap() = None;
+        }
+        if let Some(mut index) = self.store.xml_id_index.take() {
+            index.apply(self);
+            self.store.xml_id_index = Some(index);
+        }
+        if !self.map_conflicts.is_empty() {
+            crate::types::map::resolve_conflicts(self);
+        }
+        let store = &mut *self.store;
+        if let Some(log) = store.block_meta.as_deref_mut() {
+            let client = store.options.client_id;
+            let end = store.blocks.get_clock(&client);
+            let start = self.before_state.get(&client);
+            log.record(client, start, end, self.actor.as_ref());
+        }
         // 2. emit 'beforeObserverCalls'
         // 3. for each change observed by the transaction call 'afterTransaction'
         if !self.changed.is_empty() {
@@ -872,8 +1728,18 @@ impl<'doc> TransactionMut<'doc> {
 
                 // We don't need to check for events.length
                 // because we know it has at least one element
-                let events = Events::new(&mut unsorted);
-                branch.trigger_deep(self, &events);
+                branch.trigger_deep(self, &unsorted);
+            }
+        }
+
+        if let Some(events) = self.store.events.as_ref() {
+            if events.destructive_events.has_subscribers() {
+                let ops = crate::destructive::detect(self, &self.store.destructive_policy);
+                for op in ops.iter() {
+                    events
+                        .destructive_events
+                        .trigger(|cb| self.isolate(|| cb(self, op)));
+                }
             }
         }
 
@@ -883,14 +1749,23 @@ impl<'doc> TransactionMut<'doc> {
         }
 
         // 4. try GC delete set
-        if !self.store.options.skip_gc {
-            GCCollector::collect(self);
+        let collected = GCCollector::collect(self);
+        #[cfg(feature = "weak")]
+        self.invalidate_links();
+        if collected > 0 && collected >= self.store.destructive_policy.gc_threshold {
+            if let Some(events) = self.store.events.as_ref() {
+                let op = DestructiveOp::GarbageCollected { collected };
+                events
+                    .destructive_events
+                    .trigger(|cb| self.isolate(|| cb(self, &op)));
+            }
         }
 
         // 5. try merge delete set
         self.delete_set.try_squash_with(&mut self.store);
 
         // 6. get transaction after state and try to merge to left
+        let split_points = std::mem::take(&mut self.store.split_points);
         for (client, &clock) in self.after_state.iter() {
             let before_clock = self.before_state.get(client);
             if before_clock != clock {
@@ -898,7 +1773,9 @@ impl<'doc> TransactionMut<'doc> {
                 let first_change = blocks.find_pivot(before_clock).unwrap().max(1);
                 let mut i = blocks.len() - 1;
                 while i >= first_change {
-                    blocks.squash_left(i);
+                    if !split_points.contains_key(&ID::new(*client, blocks[i].clock_start())) {
+                        blocks.squash_left(i);
+                    }
                     i -= 1;
                 }
             }
@@ -908,22 +1785,79 @@ impl<'doc> TransactionMut<'doc> {
         for id in self.merge_blocks.iter() {
             if let Some(blocks) = self.store.blocks.get_client_mut(&id.client) {
                 if let Some(replaced_pos) = blocks.find_pivot(id.clock) {
-                    if replaced_pos + 1 < blocks.len() {
-                        blocks.squash_left(replaced_pos + 1);
-                    } else if replaced_pos > 0 {
-                        blocks.squash_left(replaced_pos);
+                    let index = if replaced_pos + 1 < blocks.len() {
+                        replaced_pos + 1
+                    } else {
+                        replaced_pos
+                    };
+                    let start = ID::new(id.client, blocks[index].clock_start());
+                    if index > 0 && !split_points.contains_key(&start) {
+                        blocks.squash_left(index);
                     }
                 }
             }
         }
-
+        self.store.split_points = split_points;
+        // merged blocks have been freed, positions cached by observers may point to them
+        self.cursors.clear();
+
+        let mut limiter = self
+            .store
+            .events
+            .as_mut()
+            .and_then(|events| events.update_limiter.take());
         if let Some(events) = self.store.events.as_ref() {
             // 8. emit 'afterTransactionCleanup'
             events.emit_transaction_cleanup(self);
-            // 9. emit 'update'
-            events.emit_update_v1(self);
-            // 10. emit 'updateV2'
-            events.emit_update_v2(self);
+            if let Some(limiter) = limiter.as_mut() {
+                // 9-10. emit merged 'update' and 'updateV2' if interval has passed
+                events.emit_updates_limited(self, limiter);
+            } else {
+                // 9. emit 'update'
+                events.emit_update_v1(self);
+                // 10. emit 'updateV2'
+                events.emit_update_v2(self);
+            }
+        }
+        if let Some(events) = self.store.events.as_mut() {
+            if events.update_limiter.is_none() {
+                events.update_limiter = limiter;
+            }
+        }
+
+        if self.store.delta_buffer.is_some()
+            && (!self.delete_set.is_empty() || self.after_state != self.before_state)
+        {
+            let update = self.encode_update_v1();
+            let before = self.before_state.clone();
+            let after = self.after_state.clone();
+            if let Some(buffer) = self.store.delta_buffer.as_mut() {
+                buffer.push(before, after, update);
+            }
+        }
+
+        // emit root types defined within this transaction
+        let new_roots = std::mem::take(&mut self.store.new_roots);
+        if !new_roots.is_empty() {
+            if let Some(events) = self.store.events.as_ref() {
+                if events.roots_events.has_subscribers() {
+                    let e = RootsEvent::new(new_roots);
+                    events
+                        .roots_events
+                        .trigger(|cb| self.isolate(|| cb(self, &e)));
+                }
+            }
+        }
+
+        let type_repairs = std::mem::take(&mut self.store.type_repairs);
+        if !type_repairs.is_empty() {
+            if let Some(events) = self.store.events.as_ref() {
+                for repair in type_repairs.iter() {
+                    events
+                        .type_repair_events
+                        .trigger(|cb| self.isolate(|| cb(self, repair)));
+                }
+            }
         }
 
         // 11. add and remove subdocs
@@ -931,7 +1865,10 @@ impl<'doc> TransactionMut<'doc> {
         if let Some(mut subdocs) = self.subdocs.take() {
             let client_id = store.options.client_id;
             for (guid, subdoc) in subdocs.added.iter_mut() {
-                let mut txn = subdoc.transact_mut();
+                let mut txn = match store.subdoc_origin(self.origin.as_ref(), subdoc) {
+                    Some(origin) => subdoc.transact_mut_with(origin),
+                    None => subdoc.transact_mut(),
+                };
                 txn.store.options.client_id = client_id;
                 if txn.store.options.collection_id.is_none() {
                     txn.store.options.collection_id = store.options.collection_id.clone();
@@ -946,7 +1883,9 @@ impl<'doc> TransactionMut<'doc> {
             let mut removed = if let Some(events) = store.events.as_ref() {
                 if events.subdocs_events.has_subscribers() {
                     let e = SubdocsEvent::new(subdocs);
-                    events.subdocs_events.trigger(|cb| cb(self, &e));
+                    events
+                        .subdocs_events
+                        .trigger(|cb| self.isolate(|| cb(self, &e)));
                     e.removed
                 } else {
                     subdocs.removed
@@ -959,9 +1898,49 @@ impl<'doc> TransactionMut<'doc> {
                 subdoc.destroy(self);
             }
         }
+
+        // 12. report panics caught in observer callbacks
+        let errors = std::mem::take(self.callback_errors.get_mut());
+        if !errors.is_empty() {
+            if let Some(events) = self.store.events.as_ref() {
+                events
+                    .callback_error_events
+                    .trigger(|cb| self.isolate(|| cb(self, &errors)));
+            }
+        }
+    }
+
+    /// Calls a given observer callback `f`. When [Options::isolate_observers] is enabled, a panic
+    /// raised by the callback is caught and reported once this transaction is committed.
+    ///
+    /// [Options::isolate_observers]: crate::Options::isolate_observers
+    pub(crate) fn isolate<F: FnOnce()>(&self, f: F) {
+        if let Err(e) = call_isolated(self.store.options.isolate_observers, f) {
+            self.callback_errors.borrow_mut().push(e);
+        }
+    }
+
+    /// Records a value of `parent` map entry under a given `key`, which has lost to another value
+    /// written concurrently, if there's a resolver registered for that key.
+    pub(crate) fn add_map_conflict(
+        &mut self,
+        parent: BranchPtr,
+        key: &Arc<str>,
+        lost: &ItemContent,
+    ) {
+        let has_resolver = match self.store.map_resolvers.get(&parent) {
+            Some(resolvers) => resolvers.contains_key(key),
+            None => false,
+        };
+        if has_resolver {
+            if let Some(Out::Any(value)) = lost.get_last() {
+                self.map_conflicts.push((parent, key.clone(), value));
+            }
+        }
     }
 
     pub(crate) fn add_changed_type(&mut self, parent: BranchPtr, parent_sub: Option<Arc<str>>) {
+        self.cursors.invalidate(&parent);
         let trigger = if let Some(ptr) = parent.item {
             (ptr.id().clock < self.before_state.get(&ptr.id().client)) && !ptr.is_deleted()
         } else {
@@ -1017,6 +1996,381 @@ impl<'doc> TransactionMut<'doc> {
         }
     }
 
+    /// Reverts contents of a document to the state they had at the moment when a given `snapshot`
+    /// was made. Unlike [Doc::restore_from_snapshot], this method changes current document:
+    /// elements inserted after the snapshot are deleted, while elements deleted after the snapshot
+    /// are inserted back as new blocks. Such changes are propagated to other peers just like any
+    /// other edit, so this method can be used to implement a "revert to version" feature of
+    /// collaborative documents.
+    ///
+    /// Deleted contents can only be restored when document garbage collection is disabled
+    /// (see: [Options::skip_gc]), otherwise an error is returned. Returns `true` if any changes
+    /// have been made.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use yrs::{Doc, GetString, Options, ReadTxn, Text, Transact};
+    ///
+    /// let doc = Doc::with_options(Options {
+    ///     skip_gc: true,
+    ///     ..Options::default()
+    /// });
+    /// let text = doc.get_or_insert_text("text");
+    /// text.push(&mut doc.transact_mut(), "hello world");
+    /// let snapshot = doc.transact().snapshot();
+    ///
+    /// text.remove_range(&mut doc.transact_mut(), 0, 6);
+    /// text.push(&mut doc.transact_mut(), "!");
+    /// assert_eq!(text.get_string(&doc.transact()), "world!");
+    ///
+    /// doc.transact_mut().revert_to_snapshot(&snapshot).unwrap();
+    /// assert_eq!(text.get_string(&doc.transact()), "hello world");
+    /// ```
+    pub fn revert_to_snapshot(&mut self, snapshot: &Snapshot) -> Result<bool, Error> {
+        if !self.store.options.skip_gc {
+            return Err(Error::gc_enabled());
+        }
+        self.split_by_snapshot(snapshot);
+
+        // elements deleted after the snapshot: if they have already been brought back by
+        // a previous revert, keep the restored copy instead of redoing them again
+        let mut to_redo = HashSet::new();
+        let mut restored = DeleteSet::new();
+        let deleted: Vec<_> = DeleteSet::from(&self.store.blocks)
+            .deleted_blocks()
+            .collect(self);
+        for slice in deleted {
+            if let BlockSlice::Item(slice) = slice {
+                let ptr = self.store.materialize(slice);
+                let id = ptr.id();
+                if snapshot.is_visible(id) {
+                    let last = match ptr.redone {
+                        Some(redone) => match self.store.follow_redone(&redone) {
+                            Some(slice) => self.store.materialize(slice),
+                            None => continue,
+                        },
+                        None => ptr,
+                    };
+                    if last.is_deleted() {
+                        to_redo.insert(last);
+                    } else {
+                        restored.insert(*last.id(), last.len());
+                    }
+                }
+            }
+        }
+
+        // elements inserted after the snapshot
+        let mut insertions = DeleteSet::new();
+        for (client, &clock) in self.store.blocks.get_state_vector().iter() {
+            let start = snapshot.state_map.get(client);
+            if clock > start {
+                insertions.insert(ID::new(*client, start), clock - start);
+            }
+        }
+        let mut to_delete = Vec::new();
+        let inserted: Vec<_> = insertions.deleted_blocks().collect(self);
+        for slice in inserted {
+            if let BlockSlice::Item(slice) = slice {
+                let ptr = self.store.materialize(slice);
+                if !ptr.is_deleted() && !restored.is_deleted(ptr.id()) {
+                    to_delete.push(ptr);
+                }
+            }
+        }
+
+        let mut changed = false;
+        let stack = UndoStack::<()>::default();
+        for &ptr in to_redo.iter() {
+            let mut ptr = ptr;
+            changed |= ptr
+                .redo(self, &to_redo, &insertions, &stack, &stack)
+                .is_some();
+        }
+        // delete in reverse order, so that children are deleted before their parents
+        for &ptr in to_delete.iter().rev() {
+            changed |= self.delete(ptr);
+        }
+        Ok(changed)
+    }
+
+    /// Rolls a document state back to a given `checkpoint` (see: [Store::checkpoint]). Unlike
+    /// [TransactionMut::revert_to_snapshot], this method doesn't produce any new changes: blocks
+    /// inserted after the checkpoint are physically removed from the document, while blocks
+    /// deleted after the checkpoint are brought back in place. This makes it possible to try out
+    /// speculative local edits (i.e. suggested changes previewed before being accepted) and
+    /// discard them without keeping a second copy of the document around.
+    ///
+    /// Restoring visits only the blocks inserted or deleted after the checkpoint, so its cost
+    /// grows with the number of discarded changes rather than with the size of the document.
+    /// Restoring a checkpoint discards all checkpoints made after it.
+    ///
+    /// Since discarded changes are erased from document history, they must not have been sent to
+    /// other peers. If local changes have been discarded, current document gets a new client ID,
+    /// so that identifiers of discarded blocks are never reused. Event subscribers are not
+    /// notified about the rollback.
+    ///
+    /// Checkpoints can only be restored when document garbage collection is disabled
+    /// (see: [Options::skip_gc]) and within a transaction, which hasn't made any changes yet.
+    /// Changes moving elements (see: [Array::move_to]), creating weak links, nested shared types
+    /// or sub-documents cannot be discarded, since references to them may still be held outside
+    /// of the transaction. Returns `true` if any changes have been discarded.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use yrs::{Doc, GetString, Options, ReadTxn, Text, Transact, WriteTxn};
+    ///
+    /// let doc = Doc::with_options(Options {
+    ///     skip_gc: true,
+    ///     ..Options::default()
+    /// });
+    /// let text = doc.get_or_insert_text("text");
+    /// text.push(&mut doc.transact_mut(), "hello world");
+    /// let checkpoint = doc.transact().store().checkpoint();
+    ///
+    /// // preview a suggested change
+    /// text.remove_range(&mut doc.transact_mut(), 0, 5);
+    /// text.insert(&mut doc.transact_mut(), 0, "goodbye");
+    /// assert_eq!(text.get_string(&doc.transact()), "goodbye world");
+    ///
+    /// // discard it
+    /// doc.transact_mut().restore(&checkpoint).unwrap();
+    /// assert_eq!(text.get_string(&doc.transact()), "hello world");
+    /// assert_eq!(&doc.transact().state_vector(), checkpoint.state_vector());
+    /// ```
+    pub fn restore(&mut self, checkpoint: &StoreCheckpoint) -> Result<bool, Error> {
+        if !self.store.options.skip_gc {
+            return Err(Error::gc_enabled());
+        }
+        let state = self.store.blocks.get_state_vector();
+        if state != self.before_state || !self.delete_set.is_empty() {
+            return Err(Error::Validation(
+                "cannot restore a checkpoint within a transaction which has made changes".into(),
+            ));
+        }
+        let deleted = self.store.deleted_since(checkpoint)?;
+        let checkpoint_state = checkpoint.state_vector();
+        self.split_by_snapshot(&Snapshot::new(checkpoint_state.clone(), deleted.clone()));
+
+        // blocks inserted after the checkpoint
+        let mut inserted = Vec::new();
+        for (client, &clock) in state.iter() {
+            let start = checkpoint_state.get(client);
+            if clock > start {
+                if let Some(blocks) = self.store.blocks.get_client(client) {
+                    let first = blocks.find_pivot(start).unwrap_or(blocks.len());
+                    for index in first..blocks.len() {
+                        if let Some(BlockCell::Block(item)) = blocks.get(index) {
+                            inserted.push(ItemPtr::from(item));
+                        }
+                    }
+                }
+            }
+        }
+
+        // blocks deleted after the checkpoint
+        let mut undeleted = Vec::new();
+        let deleted: Vec<_> = deleted.deleted_blocks().collect(self);
+        for slice in deleted {
+            if let BlockSlice::Item(slice) = slice {
+                let ptr = self.store.materialize(slice);
+                if ptr.is_deleted() && ptr.id.clock < checkpoint_state.get(&ptr.id.client) {
+                    undeleted.push(ptr);
+                }
+            }
+        }
+
+        let unsupported = |item: &ItemPtr| match &item.content {
+            ItemContent::Move(_) => true,
+            #[cfg(feature = "weak")]
+            ItemContent::Type(branch) => matches!(branch.type_ref, TypeRef::WeakLink(_)),
+            _ => false,
+        };
+        if inserted.iter().chain(undeleted.iter()).any(unsupported) {
+            return Err(Error::Validation(
+                "cannot restore a checkpoint over moved elements or weak links".into(),
+            ));
+        }
+        // references to shared types and sub-documents can outlive current transaction, so
+        // blocks holding them cannot be freed
+        let referenced =
+            |item: &ItemPtr| matches!(&item.content, ItemContent::Type(_) | ItemContent::Doc(_, _));
+        if inserted.iter().any(referenced) {
+            return Err(Error::Validation(
+                "cannot restore a checkpoint over shared types or sub-documents created after it"
+                    .into(),
+            ));
+        }
+
+        let kind = self.store.options.offset_kind;
+        for &ptr in inserted.iter() {
+            let mut item = ptr;
+            if let TypePtr::Branch(mut parent) = item.parent {
+                if !item.is_deleted() {
+                    if item.parent_sub.is_none() && item.is_countable() {
+                        parent.block_len -= item.len();
+                        parent.content_len -= item.content_len(kind);
+                    } else if let Some(key) = &item.parent_sub {
+                        if parent.map.get(key) == Some(&ptr) {
+                            parent.map_len -= 1;
+                        }
+                    }
+                }
+                // unlink removed block from its neighbors
+                match item.left {
+                    Some(mut left) => left.right = item.right,
+                    None if item.parent_sub.is_none() => parent.start = item.right,
+                    None => {}
+                }
+                if let Some(mut right) = item.right {
+                    right.left = item.left;
+                }
+                if let Some(key) = &item.parent_sub {
+                    if parent.map.get(key) == Some(&ptr) {
+                        match item.left {
+                            Some(left) => parent.map.insert(key.clone(), left),
+                            None => parent.map.remove(key),
+                        };
+                    }
+                }
+                parent.index = None;
+            }
+            match &mut item.content {
+                ItemContent::Type(inner) => self.store.deregister(inner),
+                ItemContent::Doc(_, doc) => {
+                    self.store.subdocs.remove(&doc.addr());
+                }
+                _ => {}
+            }
+            self.store.linked_by.remove(&ptr);
+        }
+
+        for &ptr in undeleted.iter() {
+            let mut item = ptr;
+            item.info.clear_deleted();
+            if let TypePtr::Branch(mut parent) = item.parent {
+                if item.parent_sub.is_none() && item.is_countable() {
+                    parent.block_len += item.len();
+                    parent.content_len += item.content_len(kind);
+                } else if let Some(key) = &item.parent_sub {
+                    if parent.map.get(key) == Some(&ptr) {
+                        parent.map_len += 1;
+                    }
+                }
+                parent.index = None;
+            }
+            match &mut item.content {
+                ItemContent::Type(inner) => {
+                    self.store.register(inner);
+                }
+                ItemContent::Doc(_, doc) => {
+                    self.store.subdocs.insert(doc.addr(), doc.clone());
+                }
+                _ => {}
+            }
+        }
+
+        // drop removed blocks once all of them have been unlinked
+        for (client, _) in state.iter() {
+            let start = checkpoint_state.get(client);
+            self.store.blocks.truncate(client, start);
+        }
+        self.store.reset_delete_log(checkpoint);
+        *self.store.frozen_copy.get_mut().unwrap() = None;
+        let client_id = self.store.options.client_id;
+        if state.get(&client_id) > checkpoint_state.get(&client_id) {
+            self.store.options.client_id = fastrand::u32(0..u32::MAX) as ClientID;
+        }
+        if let Some(index) = self.store.xml_id_index.as_deref() {
+            let mut index = XmlIdIndex::new(index.attr().clone());
+            for root in self.store.types.values() {
+                index.index_tree(BranchPtr::from(root));
+            }
+            self.store.xml_id_index = Some(Box::new(index));
+        }
+        if let Some(buffer) = self.store.delta_buffer.as_mut() {
+            buffer.clear();
+        }
+        self.cursors.clear();
+        self.before_state = self.store.blocks.get_state_vector();
+        Ok(!inserted.is_empty() || !undeleted.is_empty())
+    }
+
+    /// Garbage collects deleted elements (tombstones) of a current document, which are not
+    /// retained by a given `policy`. Unlike automatic garbage collection performed on transaction
+    /// commit, this method works even for documents with [crate::Options::skip_gc] enabled, so
+    /// that long-lived documents can bound their memory usage, while still keeping the ability
+    /// to restore chosen snapshots (see: [GcPolicy::snapshots]).
+    ///
+    /// Tombstones of the most recent transactions can only be retained if they are tracked by
+    /// the document (see: [GcPolicy::keep_recent] of [crate::Options::gc_policy]).
+    ///
+    /// Number of elements which contents have been discarded is reported to
+    /// [crate::Doc::observe_destructive_ops] subscribers.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use yrs::{Doc, GcPolicy, GetString, Options, ReadTxn, Text, Transact};
+    ///
+    /// let doc = Doc::with_options(Options {
+    ///     skip_gc: true,
+    ///     ..Options::default()
+    /// });
+    /// let text = doc.get_or_insert_text("text");
+    /// text.push(&mut doc.transact_mut(), "hello world");
+    /// let snapshot = doc.transact().snapshot();
+    /// text.remove_range(&mut doc.transact_mut(), 5, 6);
+    ///
+    /// let mut policy = GcPolicy::default();
+    /// policy.snapshots.insert("v1".into(), snapshot.clone());
+    /// doc.transact_mut().gc_with(&policy);
+    ///
+    /// // snapshot is still restorable
+    /// let restored = doc.restore_from_snapshot(&snapshot).unwrap();
+    /// let text = restored.get_or_insert_text("text");
+    /// assert_eq!(text.get_string(&restored.transact()), "hello world");
+    /// ```
+    pub fn gc_with(&mut self, policy: &GcPolicy) {
+        self.cursors.clear();
+        let collected = GCCollector::collect_with(self, policy);
+        #[cfg(feature = "weak")]
+        self.invalidate_links();
+        if collected > 0 {
+            if let Some(events) = self.store.events.as_ref() {
+                let op = DestructiveOp::GarbageCollected { collected };
+                events
+                    .destructive_events
+                    .trigger(|cb| self.isolate(|| cb(self, &op)));
+            }
+        }
+    }
+
+    /// Checks weak links, which quoted elements have been deleted, and notifies observers of
+    /// those which have become dangling after their quoted elements have been garbage collected.
+    #[cfg(feature = "weak")]
+    fn invalidate_links(&mut self) {
+        if self.store.orphaned_links.is_empty() {
+            return;
+        }
+        let orphaned = std::mem::take(&mut self.store.orphaned_links);
+        for link in orphaned {
+            if let crate::types::TypeRef::WeakLink(source) = &link.type_ref {
+                if !source.is_dangling(self) {
+                    self.store.orphaned_links.insert(link);
+                    continue;
+                }
+                // quoted elements no longer exist, don't keep pointers to them
+                source.first_item.take();
+                let event = Event::Weak(crate::types::weak::WeakEvent::invalidated(link));
+                link.observers
+                    .trigger(|fun| self.isolate(|| fun(self, &event)));
+            }
+        }
+    }
+
     #[cfg(feature = "weak")]
     fn link(&mut self, mut source: ItemPtr, link: BranchPtr) {
         source.info.set_linked();
@@ -1058,6 +2412,69 @@ impl<'doc> Iterator for RootRefs<'doc> {
     }
 }
 
+/// Maximum number of transactions scheduled with [TransactionMut::defer], which can be executed
+/// one after another, once the transaction that scheduled them has been dropped.
+pub const MAX_DEFERRED_TRANSACTIONS: usize = 100;
+
+/// Maximum number of blocks decoded by [TransactionMut::apply_update_from] before they are
+/// integrated into a document.
+pub const STREAM_BATCH_LEN: usize = 1024;
+
+#[cfg(feature = "sync")]
+type DeferredFn = Box<dyn FnOnce(&mut TransactionMut) + Send>;
+#[cfg(not(feature = "sync"))]
+type DeferredFn = Box<dyn FnOnce(&mut TransactionMut)>;
+
+/// Queue of transactions scheduled with [TransactionMut::defer]. These are executed when a queue
+/// is dropped.
+#[derive(Default)]
+struct Deferred(RefCell<Option<Box<DeferredQueue>>>);
+
+struct DeferredQueue {
+    doc: Doc,
+    origin: Option<Origin>,
+    actor: Option<Actor>,
+    tasks: VecDeque<DeferredFn>,
+}
+
+impl Drop for Deferred {
+    fn drop(&mut self) {
+        let queue = match self.0.get_mut().take() {
+            Some(queue) if !std::thread::panicking() => queue,
+            _ => return,
+        };
+        let DeferredQueue {
+            doc,
+            origin,
+            actor,
+            mut tasks,
+        } = *queue;
+        let mut remaining = MAX_DEFERRED_TRANSACTIONS;
+        while let Some(task) = tasks.pop_front() {
+            if remaining == 0 {
+                break;
+            }
+            remaining -= 1;
+            let txn = match &origin {
+                Some(origin) => doc.try_transact_mut_with(origin.clone()),
+                None => doc.try_transact_mut(),
+            };
+            let mut txn = match txn {
+                Ok(txn) => txn,
+                Err(_) => break,
+            };
+            txn.actor = actor.clone();
+            task(&mut txn);
+            txn.commit();
+            // follow-ups scheduled by nested transaction are executed by this loop, so that
+            // the limit applies to all of them
+            if let Some(nested) = txn.deferred.0.get_mut().take() {
+                tasks.extend(nested.tasks);
+            }
+        }
+    }
+}
+
 #[derive(Default)]
 pub struct Subdocs {
     pub(crate) added: HashMap<DocAddr, Doc>,
@@ -8,15 +8,20 @@ use crate::types::{
     event_change_set, AsPrelim, Branch, BranchPtr, Change, ChangeSet, DefaultPrelim, In, Out, Path,
     RootRef, SharedRef, ToJson, TypeRef,
 };
-use crate::{Any, Assoc, DeepObservable, IndexedSequence, Observable, ReadTxn, ID};
+use crate::updates::decoder::{Decode, Decoder};
+use crate::updates::encoder::{Encode, Encoder};
+use crate::{Any, Assoc, DeepObservable, IndexedSequence, Observable, ReadTxn, Subscription, ID};
 use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
 use std::borrow::Borrow;
 use std::cell::UnsafeCell;
 use std::collections::HashSet;
 use std::convert::{TryFrom, TryInto};
+use std::hash::Hash;
 use std::iter::FromIterator;
 use std::marker::PhantomData;
-use std::ops::{Deref, DerefMut};
+use std::ops::{Deref, DerefMut, Range};
+use std::sync::Mutex;
 
 /// A collection used to store data in an indexed sequence structure. This type is internally
 /// implemented as a double linked list, which may squash values inserted directly one after another
@@ -90,6 +95,7 @@ impl crate::Quotable for ArrayRef {}
 
 impl ToJson for ArrayRef {
     fn to_json<T: ReadTxn>(&self, txn: &T) -> Any {
+        txn.track(&self.0);
         let mut walker = BlockIter::new(self.0);
         let len = self.0.len();
         let mut buf = vec![Out::default(); len as usize];
@@ -104,6 +110,21 @@ impl ToJson for ArrayRef {
             )
         }
     }
+
+    fn to_json_writer<T: ReadTxn, W: std::io::Write>(
+        &self,
+        txn: &T,
+        writer: &mut W,
+    ) -> std::io::Result<()> {
+        writer.write_all(b"[")?;
+        for (i, value) in self.iter(txn).enumerate() {
+            if i != 0 {
+                writer.write_all(b",")?;
+            }
+            value.to_json_writer(txn, writer)?;
+        }
+        writer.write_all(b"]")
+    }
 }
 
 impl Eq for ArrayRef {}
@@ -170,7 +191,8 @@ impl DefaultPrelim for ArrayRef {
 
 pub trait Array: AsRef<Branch> + Sized {
     /// Returns a number of elements stored in current array.
-    fn len<T: ReadTxn>(&self, _txn: &T) -> u32 {
+    fn len<T: ReadTxn>(&self, txn: &T) -> u32 {
+        txn.track(self.as_ref());
         self.as_ref().len()
     }
 
@@ -262,6 +284,7 @@ pub trait Array: AsRef<Branch> + Sized {
     /// Retrieves a value stored at a given `index`. Returns `None` when provided index was out
     /// of the range of a current array.
     fn get<T: ReadTxn>(&self, txn: &T, index: u32) -> Option<Out> {
+        txn.track(self.as_ref());
         let mut walker = BlockIter::new(BranchPtr::from(self.as_ref()));
         if walker.try_forward(txn, index) {
             walker.read_value(txn)
@@ -367,6 +390,10 @@ pub trait Array: AsRef<Branch> + Sized {
     /// inside of moved range will be moved as well after synchronization (although it make take
     /// more than one sync roundtrip to achieve convergence).
     ///
+    /// Whole range is moved using a single move block, no matter how many elements it contains.
+    /// Moving a range into itself or right after its last element, as well as using `start`
+    /// greater than `end`, is a no-op.
+    ///
     /// `assoc_start`/`assoc_end` flags are used to mark if ranges should include elements that
     /// might have been inserted concurrently at the edges of the range definition.
     ///
@@ -394,7 +421,7 @@ pub trait Array: AsRef<Branch> + Sized {
         assoc_end: Assoc,
         target: u32,
     ) {
-        if start <= target && target <= end {
+        if start > end || (start <= target && target <= end + 1) {
             // It doesn't make sense to move a range into the same range (it's basically a no-op).
             return;
         }
@@ -417,8 +444,112 @@ pub trait Array: AsRef<Branch> + Sized {
     /// Returns an iterator, that can be used to lazely traverse over all values stored in a current
     /// array.
     fn iter<'a, T: ReadTxn + 'a>(&self, txn: &'a T) -> ArrayIter<&'a T, T> {
+        txn.track(self.as_ref());
         ArrayIter::from_ref(self.as_ref(), txn)
     }
+
+    /// Returns an iterator, that can be used to lazely traverse over all values stored in a current
+    /// array. Unlike [Array::iter], returned iterator takes ownership over a transaction, so that
+    /// it can be returned from a function or passed into an async stream without being bound to
+    /// the lifetime of a transaction reference.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use yrs::{Array, Doc, Out, Transact};
+    ///
+    /// fn values(doc: &Doc) -> impl Iterator<Item = Out> + '_ {
+    ///     let array = doc.get_or_insert_array("array");
+    ///     array.iter_owned(doc.transact())
+    /// }
+    ///
+    /// let doc = Doc::new();
+    /// let array = doc.get_or_insert_array("array");
+    /// array.insert_range(&mut doc.transact_mut(), 0, [1, 2, 3]);
+    /// let values: Vec<_> = values(&doc).collect();
+    /// assert_eq!(values, vec![Out::from(1.0), Out::from(2.0), Out::from(3.0)]);
+    /// ```
+    fn iter_owned<T: ReadTxn>(&self, txn: T) -> ArrayIterOwned<T> {
+        ArrayIter::from_ref_owned(self.as_ref(), txn)
+    }
+
+    /// Inserts a `value` at the given `index`, but only if none of the elements already stored in
+    /// current array has the same key as that value. Keys are computed by `key_fn` from a JSON-like
+    /// representation of both `value` and existing elements.
+    ///
+    /// Returns `true` if `value` was inserted, `false` if a duplicate was found.
+    ///
+    /// Uniqueness check only takes into account the local state of an array: concurrent inserts
+    /// of the same key made by different peers will both be present after synchronization. Use
+    /// [Array::remove_duplicates] after applying remote updates to deduplicate them.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use yrs::{Any, Array, Doc, Transact};
+    ///
+    /// let doc = Doc::new();
+    /// let array = doc.get_or_insert_array("array");
+    /// let mut txn = doc.transact_mut();
+    ///
+    /// assert!(array.insert_unique(&mut txn, 0, "a", |v: &Any| v.clone()));
+    /// assert!(array.insert_unique(&mut txn, 1, "b", |v: &Any| v.clone()));
+    /// assert!(!array.insert_unique(&mut txn, 0, "b", |v: &Any| v.clone()));
+    /// assert_eq!(array.len(&txn), 2);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// This method will panic if provided `index` is greater than the current length of an array.
+    fn insert_unique<V, K, F>(
+        &self,
+        txn: &mut TransactionMut,
+        index: u32,
+        value: V,
+        key_fn: F,
+    ) -> bool
+    where
+        V: Into<Any>,
+        K: PartialEq,
+        F: Fn(&Any) -> K,
+    {
+        let value = value.into();
+        let key = key_fn(&value);
+        let exists = self.iter(txn).any(|v| key_fn(&v.to_json(txn)) == key);
+        if exists {
+            false
+        } else {
+            self.insert(txn, index, value);
+            true
+        }
+    }
+
+    /// Removes all elements with duplicated keys, leaving only the first occurrence of each key.
+    /// Keys are computed by `key_fn` from a JSON-like representation of array elements.
+    ///
+    /// This method is meant to be used as a cleanup pass after integrating remote updates, as
+    /// a complement of [Array::insert_unique]. Since every peer keeps the first element in the
+    /// same (converged) order, all of them will remove the same elements.
+    ///
+    /// Returns a number of removed elements.
+    fn remove_duplicates<K, F>(&self, txn: &mut TransactionMut, key_fn: F) -> u32
+    where
+        K: Eq + Hash,
+        F: Fn(&Any) -> K,
+    {
+        let mut keys = HashSet::new();
+        let mut duplicates = Vec::new();
+        for (index, value) in self.iter(txn).enumerate() {
+            if !keys.insert(key_fn(&value.to_json(txn))) {
+                duplicates.push(index as u32);
+            }
+        }
+        // remove from the back, so that indexes of remaining duplicates stay valid
+        for &index in duplicates.iter().rev() {
+            self.remove(txn, index);
+        }
+        duplicates.len() as u32
+    }
 }
 
 pub struct ArrayIter<B, T>
@@ -457,6 +588,34 @@ where
     }
 }
 
+impl<T> ArrayIter<T, T>
+where
+    T: ReadTxn,
+{
+    fn from_ref_owned(array: &Branch, txn: T) -> Self {
+        ArrayIter {
+            inner: BlockIter::new(BranchPtr::from(array)),
+            txn,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<B, T> ArrayIter<B, T>
+where
+    B: Borrow<T>,
+    T: ReadTxn,
+{
+    /// Returns a transaction used by current iterator.
+    pub fn txn(&self) -> &T {
+        self.txn.borrow()
+    }
+}
+
+/// Iterator over the values of an [Array], which owns a transaction it reads from.
+/// See: [Array::iter_owned].
+pub type ArrayIterOwned<T> = ArrayIter<T, T>;
+
 impl<B, T> Iterator for ArrayIter<B, T>
 where
     B: Borrow<T>,
@@ -636,19 +795,555 @@ impl ArrayEvent {
     }
 }
 
+impl ArrayRef {
+    /// Subscribes a given callback to changes affecting only a window of elements of current
+    /// array, initially spanning over a given `range` of indexes. It's meant for virtualized list
+    /// views, which only display a small part of a large array and don't need to process changes
+    /// happening outside of it.
+    ///
+    /// Window boundaries are kept as [StickyIndex]es, so that they keep pointing to the same
+    /// elements as the array is being modified: inserting or removing elements before the window
+    /// shifts it, while new elements inserted between the first and the last element of
+    /// a window extend it. Elements inserted right before the first or right after the last
+    /// element of a window are considered to be outside of it.
+    ///
+    /// A callback is only called when elements within a window have been added or removed.
+    /// It receives an [ArrayWindowEvent] with a current window range and a delta, which indexes
+    /// are relative to the beginning of a window.
+    ///
+    /// Returns a [Subscription] which, when dropped, will unsubscribe current callback.
+    #[cfg(feature = "sync")]
+    pub fn observe_window<T, F>(&self, txn: &T, range: Range<u32>, f: F) -> Subscription
+    where
+        T: ReadTxn,
+        F: Fn(&TransactionMut, &ArrayWindowEvent) + Send + Sync + 'static,
+    {
+        let window = ArrayWindow::new(txn, self.0, range);
+        self.observe(move |txn, e| {
+            if let Some(e) = window.apply(txn, e) {
+                f(txn, &e)
+            }
+        })
+    }
+
+    /// Subscribes a given callback to changes affecting only a window of elements of current
+    /// array, initially spanning over a given `range` of indexes. It's meant for virtualized list
+    /// views, which only display a small part of a large array and don't need to process changes
+    /// happening outside of it.
+    ///
+    /// Window boundaries are kept as [StickyIndex]es, so that they keep pointing to the same
+    /// elements as the array is being modified: inserting or removing elements before the window
+    /// shifts it, while new elements inserted between the first and the last element of
+    /// a window extend it. Elements inserted right before the first or right after the last
+    /// element of a window are considered to be outside of it.
+    ///
+    /// A callback is only called when elements within a window have been added or removed.
+    /// It receives an [ArrayWindowEvent] with a current window range and a delta, which indexes
+    /// are relative to the beginning of a window.
+    ///
+    /// Returns a [Subscription] which, when dropped, will unsubscribe current callback.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::sync::{Arc, Mutex};
+    /// use yrs::types::Change;
+    /// use yrs::{Array, Doc, Transact};
+    ///
+    /// let doc = Doc::new();
+    /// let array = doc.get_or_insert_array("array");
+    /// array.insert_range(&mut doc.transact_mut(), 0, 0..100);
+    ///
+    /// let events = Arc::new(Mutex::new(Vec::new()));
+    /// let events_c = events.clone();
+    /// let _sub = array.observe_window(&doc.transact(), 10..20, move |_, e| {
+    ///     events_c.lock().unwrap().push((e.range.clone(), e.delta.clone()));
+    /// });
+    ///
+    /// array.insert(&mut doc.transact_mut(), 0, -1); // outside of the window
+    /// assert!(events.lock().unwrap().is_empty());
+    ///
+    /// array.remove(&mut doc.transact_mut(), 13); // 4th element of the window
+    /// assert_eq!(events.lock().unwrap().pop(), Some((11..20, vec![
+    ///     Change::Retain(2),
+    ///     Change::Removed(1),
+    /// ])));
+    /// ```
+    #[cfg(not(feature = "sync"))]
+    pub fn observe_window<T, F>(&self, txn: &T, range: Range<u32>, f: F) -> Subscription
+    where
+        T: ReadTxn,
+        F: Fn(&TransactionMut, &ArrayWindowEvent) + 'static,
+    {
+        let window = ArrayWindow::new(txn, self.0, range);
+        self.observe(move |txn, e| {
+            if let Some(e) = window.apply(txn, e) {
+                f(txn, &e)
+            }
+        })
+    }
+
+    /// Returns a page of up to `limit` consecutive values of current array, starting right after
+    /// the position pointed by a given `cursor` or at the beginning of an array if no cursor was
+    /// provided. It's meant for exposing collaborative lists through paginated APIs, ie. to REST
+    /// clients.
+    ///
+    /// Returned [ArrayPage] contains a continuation cursor, which can be passed to a subsequent
+    /// call in order to read the next page. Cursors are anchored to the last element of the
+    /// page they were returned with (see [StickyIndex]), rather than to a numeric offset. For
+    /// that reason paging stays consistent in face of concurrent changes: elements inserted or
+    /// removed before the cursor don't cause the next page to skip or repeat any elements, while
+    /// elements inserted after it will be returned by subsequent pages. Cursors remain valid
+    /// even if the element they are anchored to has been removed.
+    ///
+    /// Cursors can be serialized with [Encode] (or serde) and handed over to clients as opaque
+    /// tokens.
+    ///
+    /// Returns `None` if provided `cursor` doesn't belong to current array or refers to elements
+    /// which are not known to current document yet.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use yrs::{Array, Doc, Transact};
+    ///
+    /// let doc = Doc::new();
+    /// let array = doc.get_or_insert_array("array");
+    /// array.insert_range(&mut doc.transact_mut(), 0, ["a", "b", "c", "d", "e"]);
+    ///
+    /// let page = array.page(&doc.transact(), None, 2).unwrap();
+    /// assert_eq!(page.values, vec!["a".into(), "b".into()]);
+    ///
+    /// // concurrent changes don't affect the continuation of paging
+    /// array.remove(&mut doc.transact_mut(), 0);
+    /// array.insert(&mut doc.transact_mut(), 0, "z");
+    ///
+    /// let page = array.page(&doc.transact(), page.next.as_ref(), 2).unwrap();
+    /// assert_eq!(page.values, vec!["c".into(), "d".into()]);
+    /// let page = array.page(&doc.transact(), page.next.as_ref(), 2).unwrap();
+    /// assert_eq!(page.values, vec!["e".into()]);
+    /// assert!(page.next.is_none());
+    /// ```
+    ///
+    /// [Encode]: crate::updates::encoder::Encode
+    pub fn page<T: ReadTxn>(
+        &self,
+        txn: &T,
+        cursor: Option<&ArrayCursor>,
+        limit: u32,
+    ) -> Option<ArrayPage> {
+        let start = match cursor {
+            None => 0,
+            Some(cursor) => {
+                let offset = cursor.0.get_offset(txn)?;
+                if offset.branch != self.0 {
+                    return None;
+                }
+                offset.index
+            }
+        };
+        let len = self.len(txn);
+        let count = limit.min(len.saturating_sub(start));
+        let mut values = vec![Out::default(); count as usize];
+        if count > 0 {
+            let mut walker = BlockIter::new(self.0);
+            walker.try_forward(txn, start);
+            let read = walker.slice(txn, &mut values);
+            values.truncate(read as usize);
+        }
+        let end = start + values.len() as u32;
+        let next = if end < len {
+            // anchor to the last returned element, so that elements inserted after it
+            // concurrently will be returned in the next page
+            StickyIndex::at(txn, self.0, end, Assoc::Before).map(ArrayCursor)
+        } else {
+            None
+        };
+        Some(ArrayPage { values, next })
+    }
+}
+
+/// A page of values returned by [ArrayRef::page].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArrayPage {
+    /// Values stored in an array, in their order.
+    pub values: Vec<Out>,
+    /// Cursor pointing right after the last value of current page, which can be used to read
+    /// the next page. `None` if there were no more values in an array at the time of reading.
+    pub next: Option<ArrayCursor>,
+}
+
+/// Opaque continuation cursor returned by [ArrayRef::page]. It can be serialized with [Encode]
+/// or serde in order to be passed to a remote client and deserialized back.
+///
+/// [Encode]: crate::updates::encoder::Encode
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ArrayCursor(StickyIndex);
+
+impl Encode for ArrayCursor {
+    #[inline]
+    fn encode<E: Encoder>(&self, encoder: &mut E) {
+        self.0.encode(encoder)
+    }
+}
+
+impl Decode for ArrayCursor {
+    #[inline]
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, Error> {
+        Ok(ArrayCursor(StickyIndex::decode(decoder)?))
+    }
+}
+
+/// Event passed to callbacks subscribed with [ArrayRef::observe_window].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArrayWindowEvent {
+    /// Range of indexes occupied by an observed window after the changes have been applied.
+    pub range: Range<u32>,
+    /// Changes made within an observed window. Indexes are relative to the beginning of a window.
+    pub delta: Vec<Change>,
+}
+
+struct ArrayWindow {
+    start: StickyIndex,
+    end: StickyIndex,
+    /// Window range as of the last observed change.
+    range: Mutex<Range<u32>>,
+}
+
+impl ArrayWindow {
+    fn new<T: ReadTxn>(txn: &T, array: BranchPtr, range: Range<u32>) -> Self {
+        let len = array.len();
+        let range = range.start.min(len)..range.end.min(len).max(range.start.min(len));
+        let start = StickyIndex::at(txn, array, range.start, Assoc::After)
+            .unwrap_or_else(|| StickyIndex::from_type(txn, &array, Assoc::After));
+        let end = StickyIndex::at(txn, array, range.end, Assoc::Before)
+            .unwrap_or_else(|| StickyIndex::from_type(txn, &array, Assoc::Before));
+        ArrayWindow {
+            start,
+            end,
+            range: Mutex::new(range),
+        }
+    }
+
+    /// Remaps changes of a given array event onto current window. Returns `None` if none of
+    /// the changes affected the window.
+    fn apply(&self, txn: &TransactionMut, e: &ArrayEvent) -> Option<ArrayWindowEvent> {
+        let start = self.start.get_offset(txn).map(|o| o.index).unwrap_or(0);
+        let end = self.end.get_offset(txn).map(|o| o.index).unwrap_or(0);
+        let new = start..end.max(start);
+        let old = std::mem::replace(&mut *self.range.lock().unwrap(), new.clone());
+
+        fn overlap(from: u32, len: u32, window: &Range<u32>) -> Range<u32> {
+            let start = from.max(window.start);
+            let end = (from + len).min(window.end);
+            start..end.max(start)
+        }
+
+        let mut delta = Vec::new();
+        let mut changed = false;
+        let (mut old_index, mut new_index) = (0, 0);
+        for change in e.delta(txn) {
+            match change {
+                Change::Retain(len) => {
+                    let retained = overlap(old_index, *len, &old).len() as u32;
+                    if retained > 0 {
+                        match delta.last_mut() {
+                            Some(Change::Retain(n)) => *n += retained,
+                            _ => delta.push(Change::Retain(retained)),
+                        }
+                    }
+                    old_index += len;
+                    new_index += len;
+                }
+                Change::Removed(len) => {
+                    let removed = overlap(old_index, *len, &old).len() as u32;
+                    if removed > 0 {
+                        delta.push(Change::Removed(removed));
+                        changed = true;
+                    }
+                    old_index += len;
+                }
+                Change::Added(values) => {
+                    let len = values.len() as u32;
+                    let added = overlap(new_index, len, &new);
+                    if !added.is_empty() {
+                        let from = (added.start - new_index) as usize;
+                        let to = (added.end - new_index) as usize;
+                        delta.push(Change::Added(values[from..to].to_vec()));
+                        changed = true;
+                    }
+                    new_index += len;
+                }
+            }
+        }
+        if !changed {
+            return None;
+        }
+        if let Some(Change::Retain(_)) = delta.last() {
+            delta.pop();
+        }
+        Some(ArrayWindowEvent { range: new, delta })
+    }
+}
+
 #[cfg(test)]
 mod test {
     use crate::test_utils::{exchange_updates, run_scenario, RngExt};
+    use crate::types::array::{ArrayCursor, ArrayPage, ArrayWindowEvent};
     use crate::types::map::MapPrelim;
     use crate::types::{Change, DeepObservable, Event, Out, Path, PathSegment, ToJson};
     use crate::{
-        any, Any, Array, ArrayPrelim, Assoc, Doc, Map, MapRef, Observable, SharedRef, StateVector,
-        Transact, Update, WriteTxn, ID,
+        any, Any, Array, ArrayPrelim, Assoc, ConflictOrder, Doc, Map, MapRef, Observable, Options,
+        SharedRef, StateVector, Transact, Update, WriteTxn, ID,
     };
     use std::collections::{HashMap, HashSet};
     use std::iter::FromIterator;
     use std::sync::{Arc, Mutex};
 
+    #[test]
+    fn iter_owned() {
+        fn nested_values(doc: &Doc) -> impl Iterator<Item = Vec<(Arc<str>, Out)>> + '_ {
+            let array = doc.get_or_insert_array("array");
+            let mut iter = array.iter_owned(doc.transact());
+            std::iter::from_fn(move || {
+                let map: MapRef = iter.next()?.cast().ok()?;
+                let mut entries: Vec<_> = map
+                    .iter(iter.txn())
+                    .map(|(key, value)| (Arc::<str>::from(key), value))
+                    .collect();
+                entries.sort_by(|a, b| a.0.cmp(&b.0));
+                Some(entries)
+            })
+        }
+
+        let doc = Doc::with_client_id(1);
+        let array = doc.get_or_insert_array("array");
+        {
+            let mut txn = doc.transact_mut();
+            array.push_back(&mut txn, MapPrelim::from([("a", 1), ("b", 2)]));
+            let map = array.push_back(&mut txn, MapPrelim::from([("c", 3), ("d", 4)]));
+            map.remove(&mut txn, "c");
+        }
+        let values: Vec<_> = nested_values(&doc).collect();
+        assert_eq!(
+            values,
+            vec![
+                vec![("a".into(), Out::from(1.0)), ("b".into(), Out::from(2.0))],
+                vec![("d".into(), Out::from(4.0))],
+            ]
+        );
+
+        let root = doc.get_or_insert_map("root");
+        root.insert(&mut doc.transact_mut(), "key", "value");
+        let entries: Vec<_> = root.iter_owned(doc.transact()).collect();
+        assert_eq!(entries, vec![("key".into(), Out::from("value"))]);
+
+        // owned iterator keeps the transaction alive until it's dropped
+        let iter = array.iter_owned(doc.transact_mut());
+        assert!(doc.try_transact().is_err());
+        assert_eq!(iter.count(), 2);
+        assert!(doc.try_transact().is_ok());
+    }
+
+    #[test]
+    fn sequential_get() {
+        fn assert_sequential<T: crate::ReadTxn>(array: &crate::ArrayRef, txn: &T) {
+            let expected: Vec<Out> = array.iter(txn).collect();
+            let actual: Vec<Out> = (0..array.len(txn))
+                .map(|i| array.get(txn, i).unwrap())
+                .collect();
+            assert_eq!(actual, expected);
+            assert_eq!(array.get(txn, array.len(txn)), None);
+        }
+
+        let doc = Doc::with_client_id(1);
+        let array = doc.get_or_insert_array("array");
+        {
+            let mut txn = doc.transact_mut();
+            for i in 0..300 {
+                array.push_back(&mut txn, i);
+            }
+            assert_sequential(&array, &txn);
+
+            // cached positions are invalidated by changes made within the same transaction
+            array.remove_range(&mut txn, 10, 5);
+            array.insert(&mut txn, 3, "a");
+            assert_sequential(&array, &txn);
+            array.move_to(&mut txn, 0, 250);
+            array.move_range_to(&mut txn, 20, Assoc::After, 40, Assoc::Before, 100);
+            assert_sequential(&array, &txn);
+            array.remove_range(&mut txn, 0, 5);
+            assert_sequential(&array, &txn);
+        }
+        let txn = doc.transact();
+        assert_sequential(&array, &txn);
+        // random access in reverse order
+        let expected: Vec<Out> = array.iter(&txn).collect();
+        for i in (0..array.len(&txn)).rev() {
+            assert_eq!(array.get(&txn, i).as_ref(), Some(&expected[i as usize]));
+        }
+    }
+
+    #[test]
+    fn cached_positions_after_blocks_merge() {
+        let doc = Doc::with_client_id(1);
+        let array = doc.get_or_insert_array("array");
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let _s1 = {
+            let array = array.clone();
+            let seen = seen.clone();
+            array.clone().observe(move |txn, _| {
+                seen.lock().unwrap().push(array.get(txn, 1));
+            })
+        };
+        // blocks read above are merged together before cleanup callbacks are called
+        let _s2 = {
+            let array = array.clone();
+            let seen = seen.clone();
+            doc.observe_transaction_cleanup(move |txn, _| {
+                seen.lock().unwrap().push(array.get(txn, 1));
+            })
+            .unwrap()
+        };
+        {
+            let mut txn = doc.transact_mut();
+            array.push_back(&mut txn, 1);
+            array.push_back(&mut txn, 2);
+            array.push_back(&mut txn, 3);
+        }
+        let expected = Some(Out::Any(2.into()));
+        assert_eq!(*seen.lock().unwrap(), vec![expected.clone(), expected]);
+    }
+
+    #[test]
+    fn observe_window() {
+        let d1 = Doc::with_client_id(1);
+        let a1 = d1.get_or_insert_array("array");
+        a1.insert_range(&mut d1.transact_mut(), 0, 0..100);
+        let d2 = Doc::with_client_id(2);
+        let a2 = d2.get_or_insert_array("array");
+        exchange_updates(&[&d1, &d2]);
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let events_c = events.clone();
+        let _sub = a2.observe_window(&d2.transact(), 10..20, move |_, e| {
+            events_c.lock().unwrap().push(e.clone());
+        });
+        let take = || std::mem::take(&mut *events.lock().unwrap());
+
+        // changes outside of the window, including its edges
+        {
+            let mut txn = d1.transact_mut();
+            a1.insert(&mut txn, 0, -1); // window: 11..21
+            a1.remove_range(&mut txn, 50, 10);
+            a1.insert(&mut txn, 11, -2); // right before the window: 12..22
+            a1.insert(&mut txn, 22, -3); // right after the window
+        }
+        exchange_updates(&[&d1, &d2]);
+        assert!(take().is_empty());
+
+        // changes inside of the window
+        {
+            let mut txn = d1.transact_mut();
+            a1.remove_range(&mut txn, 14, 2); // window: 12..20
+            a1.insert_range(&mut txn, 15, [-4, -5]); // window: 12..22
+        }
+        exchange_updates(&[&d1, &d2]);
+        assert_eq!(
+            take(),
+            vec![ArrayWindowEvent {
+                range: 12..22,
+                delta: vec![
+                    Change::Retain(2),
+                    Change::Removed(2),
+                    Change::Retain(1),
+                    Change::Added(vec![Any::from(-4).into(), Any::from(-5).into()]),
+                ]
+            }]
+        );
+
+        // changes spanning over the window boundaries
+        a2.remove_range(&mut d2.transact_mut(), 5, 10);
+        assert_eq!(
+            take(),
+            vec![ArrayWindowEvent {
+                range: 5..12,
+                delta: vec![Change::Removed(3)]
+            }]
+        );
+        let values: Vec<_> = a2.iter(&d2.transact()).skip(5).take(7).collect();
+        assert_eq!(
+            values,
+            vec![
+                Out::from(-4),
+                Out::from(-5),
+                Out::from(15),
+                Out::from(16),
+                Out::from(17),
+                Out::from(18),
+                Out::from(19)
+            ]
+        );
+    }
+
+    #[test]
+    fn page() {
+        let d1 = Doc::with_client_id(1);
+        let a1 = d1.get_or_insert_array("array");
+        let d2 = Doc::with_client_id(2);
+        let a2 = d2.get_or_insert_array("array");
+        a1.insert_range(&mut d1.transact_mut(), 0, 0..10);
+        exchange_updates(&[&d1, &d2]);
+
+        let page = a1.page(&d1.transact(), None, 4).unwrap();
+        assert_eq!(page.values, (0..4).map(Out::from).collect::<Vec<_>>());
+
+        // cursor can be handed over to another peer as an opaque token
+        let token = page.next.unwrap().encode_v1();
+        let cursor = ArrayCursor::decode_v1(&token).unwrap();
+
+        // concurrent changes: d1 removes the last element returned so far and inserts elements
+        // before the cursor, while d2 inserts an element right after it
+        {
+            let mut txn = d1.transact_mut();
+            a1.remove(&mut txn, 3);
+            a1.insert_range(&mut txn, 0, ["x", "y"]);
+        }
+        a2.insert(&mut d2.transact_mut(), 4, "z");
+        exchange_updates(&[&d1, &d2]);
+
+        let page = a2.page(&d2.transact(), Some(&cursor), 4).unwrap();
+        assert_eq!(page.values, vec!["z".into(), 4.into(), 5.into(), 6.into()]);
+        let page = a2.page(&d2.transact(), page.next.as_ref(), 4).unwrap();
+        assert_eq!(page.values, (7..10).map(Out::from).collect::<Vec<_>>());
+        assert_eq!(page.next, None);
+
+        // cursor of a different collection is rejected
+        let other = d1.get_or_insert_array("other");
+        other.insert_range(&mut d1.transact_mut(), 0, [1, 2, 3]);
+        let cursor = other.page(&d1.transact(), None, 1).unwrap().next.unwrap();
+        assert_eq!(a1.page(&d1.transact(), Some(&cursor), 1), None);
+
+        // cursor referring to the changes not yet seen by a document
+        assert_eq!(a2.page(&d2.transact(), Some(&cursor), 1), None);
+
+        // empty array and zero-length pages
+        let empty = d1.get_or_insert_array("empty");
+        let page = empty.page(&d1.transact(), None, 10).unwrap();
+        assert_eq!(
+            page,
+            ArrayPage {
+                values: vec![],
+                next: None
+            }
+        );
+        let page = a1.page(&d1.transact(), None, 0).unwrap();
+        assert!(page.values.is_empty());
+        let page = a1.page(&d1.transact(), page.next.as_ref(), 1).unwrap();
+        assert_eq!(page.values, vec!["x".into()]);
+    }
+
     #[test]
     fn push_back() {
         let doc = Doc::with_client_id(1);
@@ -1159,9 +1854,10 @@ mod test {
         assert_eq!(c2.swap(None), Some(Arc::new(a2.hook())));
     }
 
+    use crate::block::ItemContent;
     use crate::transaction::ReadTxn;
     use crate::updates::decoder::Decode;
-    use crate::updates::encoder::{Encoder, EncoderV1};
+    use crate::updates::encoder::{Encode, Encoder, EncoderV1};
     use arc_swap::ArcSwapOption;
     use fastrand::Rng;
     use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
@@ -1477,7 +2173,6 @@ mod test {
     }
 
     #[test]
-    #[ignore] //TODO: investigate (see: https://github.com/y-crdt/y-crdt/pull/266)
     fn move_range_to() {
         let doc = Doc::with_client_id(1);
         let arr = doc.get_or_insert_array("array");
@@ -1699,6 +2394,76 @@ mod test {
         );
     }
 
+    #[test]
+    fn move_range_to_single_block() {
+        let d1 = Doc::with_client_id(1);
+        let a1 = d1.get_or_insert_array("array");
+
+        let d2 = Doc::with_client_id(2);
+        let a2 = d2.get_or_insert_array("array");
+
+        let e1 = Arc::new(ArcSwapOption::default());
+        let inner = e1.clone();
+        let _s1 = a1.observe(move |txn, e| {
+            inner.store(Some(Arc::new(e.delta(txn).to_vec())));
+        });
+
+        let e2 = Arc::new(ArcSwapOption::default());
+        let inner = e2.clone();
+        let _s2 = a2.observe(move |txn, e| {
+            inner.store(Some(Arc::new(e.delta(txn).to_vec())));
+        });
+
+        a1.insert_range(&mut d1.transact_mut(), 0, [0, 1, 2, 3, 4, 5]);
+        exchange_updates(&[&d1, &d2]);
+
+        a1.move_range_to(&mut d1.transact_mut(), 1, Assoc::After, 3, Assoc::Before, 6);
+        assert_eq!(a1.to_json(&d1.transact()), vec![0, 4, 5, 1, 2, 3].into());
+        assert_eq!(
+            e1.load_full(),
+            Some(Arc::new(vec![
+                Change::Retain(1),
+                Change::Removed(3),
+                Change::Retain(2),
+                Change::Added(vec![1.into(), 2.into(), 3.into()])
+            ]))
+        );
+
+        // whole range has been moved using a single move block
+        let moves = {
+            let _txn = d1.transact();
+            let mut count = 0;
+            let mut next = a1.as_ref().start;
+            while let Some(item) = next {
+                if let ItemContent::Move(_) = &item.content {
+                    count += 1;
+                }
+                next = item.right;
+            }
+            count
+        };
+        assert_eq!(moves, 1);
+
+        exchange_updates(&[&d1, &d2]);
+        assert_eq!(a2.to_json(&d2.transact()), vec![0, 4, 5, 1, 2, 3].into());
+        assert_eq!(
+            e2.load_full(),
+            Some(Arc::new(vec![
+                Change::Retain(1),
+                Change::Removed(3),
+                Change::Retain(2),
+                Change::Added(vec![1.into(), 2.into(), 3.into()])
+            ]))
+        );
+
+        // no-op moves don't produce any changes
+        e1.store(None);
+        a1.move_range_to(&mut d1.transact_mut(), 3, Assoc::After, 4, Assoc::Before, 5);
+        a1.move_range_to(&mut d1.transact_mut(), 4, Assoc::After, 3, Assoc::Before, 0);
+        assert_eq!(a1.to_json(&d1.transact()), vec![0, 4, 5, 1, 2, 3].into());
+        assert_eq!(e1.load_full(), None);
+    }
+
     #[test]
     fn multi_threading() {
         use std::sync::{Arc, RwLock};
@@ -1792,4 +2557,95 @@ mod test {
             vec![1.into(), 2.into()]
         );
     }
+
+    #[test]
+    fn conflict_order_priority() {
+        fn docs(order: ConflictOrder) -> (Doc, Doc) {
+            let mut o1 = Options::with_client_id(1);
+            o1.conflict_order = order.clone();
+            let mut o2 = Options::with_client_id(2);
+            o2.conflict_order = order;
+            (Doc::with_options(o1), Doc::with_options(o2))
+        }
+
+        fn concurrent_inserts(d1: &Doc, d2: &Doc) -> (Vec<Out>, Out) {
+            let a1 = d1.get_or_insert_array("array");
+            let a2 = d2.get_or_insert_array("array");
+            let m1 = d1.get_or_insert_map("map");
+            let m2 = d2.get_or_insert_map("map");
+            {
+                let mut txn = d1.transact_mut();
+                a1.insert(&mut txn, 0, 1);
+                m1.insert(&mut txn, "key", 1);
+            }
+            {
+                let mut txn = d2.transact_mut();
+                a2.insert(&mut txn, 0, 2);
+                m2.insert(&mut txn, "key", 2);
+            }
+            exchange_updates(&[d1, d2]);
+            let t1 = d1.transact();
+            let t2 = d2.transact();
+            assert_eq!(a1.to_json(&t1), a2.to_json(&t2));
+            assert_eq!(m1.to_json(&t1), m2.to_json(&t2));
+            (a1.iter(&t1).collect(), m1.get(&t1, "key").unwrap())
+        }
+
+        // by default lower client ids go first, higher client ids win map conflicts
+        let (d1, d2) = docs(ConflictOrder::default());
+        let (array, value) = concurrent_inserts(&d1, &d2);
+        assert_eq!(array, vec![Out::from(1.0), Out::from(2.0)]);
+        assert_eq!(value, Out::from(2.0));
+
+        // client with higher priority goes first and wins map conflicts
+        let priorities = HashMap::from_iter([(2, 1)]);
+        let (d1, d2) = docs(ConflictOrder::Priority(priorities));
+        let (array, value) = concurrent_inserts(&d1, &d2);
+        assert_eq!(array, vec![Out::from(2.0), Out::from(1.0)]);
+        assert_eq!(value, Out::from(2.0));
+
+        let priorities = HashMap::from_iter([(1, 1)]);
+        let (d1, d2) = docs(ConflictOrder::Priority(priorities));
+        let (array, value) = concurrent_inserts(&d1, &d2);
+        assert_eq!(array, vec![Out::from(1.0), Out::from(2.0)]);
+        assert_eq!(value, Out::from(1.0));
+    }
+
+    #[test]
+    fn insert_unique() {
+        let d1 = Doc::with_client_id(1);
+        let d2 = Doc::with_client_id(2);
+        let a1 = d1.get_or_insert_array("array");
+        let a2 = d2.get_or_insert_array("array");
+        let id = |v: &Any| match v {
+            Any::Map(m) => m.get("id").map(ToString::to_string),
+            _ => None,
+        };
+
+        {
+            let mut txn = d1.transact_mut();
+            assert!(a1.insert_unique(&mut txn, 0, any!({"id": 1, "v": "a"}), id));
+            assert!(!a1.insert_unique(&mut txn, 1, any!({"id": 1, "v": "b"}), id));
+            assert!(a1.insert_unique(&mut txn, 1, any!({"id": 2, "v": "c"}), id));
+        }
+        exchange_updates(&[&d1, &d2]);
+
+        // concurrent inserts of the same key are not detected locally
+        assert!(a1.insert_unique(&mut d1.transact_mut(), 0, any!({"id": 3, "v": "d"}), id));
+        assert!(a2.insert_unique(&mut d2.transact_mut(), 2, any!({"id": 3, "v": "e"}), id));
+        exchange_updates(&[&d1, &d2]);
+        assert_eq!(a1.len(&d1.transact()), 4);
+
+        // cleanup pass removes the same elements on both peers
+        assert_eq!(a1.remove_duplicates(&mut d1.transact_mut(), id), 1);
+        assert_eq!(a2.remove_duplicates(&mut d2.transact_mut(), id), 1);
+        exchange_updates(&[&d1, &d2]);
+        let t1 = d1.transact();
+        let t2 = d2.transact();
+        assert_eq!(a1.to_json(&t1), a2.to_json(&t2));
+        assert_eq!(
+            a1.to_json(&t1),
+            any!([{"id": 3, "v": "d"}, {"id": 1, "v": "a"}, {"id": 2, "v": "c"}])
+        );
+    }
 }
@@ -0,0 +1,361 @@
+//! Counter shared type.
+//!
+//! [CounterRef] is a numeric collection, which can be incremented concurrently by many peers.
+//! Unlike a number stored as a [Map] value, where concurrent updates overwrite each other, every
+//! increment is preserved and the value of a counter is a sum of all of them.
+
+use crate::block::{ClientID, ItemContent, ItemPtr, Prelim};
+use crate::branch::{Branch, BranchPtr};
+use crate::transaction::TransactionMut;
+use crate::types::{
+    event_keys, DeepObservable, EntryChange, Observable, Path, RootRef, SharedRef, ToJson, TypeRef,
+};
+use crate::{Any, Map, MapRef, Out, ReadTxn};
+use std::cell::UnsafeCell;
+use std::collections::HashSet;
+use std::convert::TryFrom;
+use std::ops::Deref;
+use std::sync::Arc;
+
+/// A collaborative counter. Its value is a sum of increments made by all peers: concurrent
+/// increments never overwrite each other.
+///
+/// Counters can be defined as root types (see [Doc::get_or_insert_counter]) or nested inside of
+/// other collections using [CounterPrelim]. When read through their parent collection (i.e.
+/// [Map::get] or [ToJson::to_json]), counters are represented as plain numbers.
+///
+/// Counters are not supported by Yjs.
+///
+/// # Example
+///
+/// ```rust
+/// use yrs::{Doc, ReadTxn, StateVector, Transact, Update};
+/// use yrs::updates::decoder::Decode;
+///
+/// let d1 = Doc::with_client_id(1);
+/// let c1 = d1.get_or_insert_counter("likes");
+/// let d2 = Doc::with_client_id(2);
+/// let c2 = d2.get_or_insert_counter("likes");
+///
+/// c1.increment(&mut d1.transact_mut(), 2.0);
+/// c2.increment(&mut d2.transact_mut(), 3.0);
+///
+/// let u1 = d1.transact().encode_state_as_update_v1(&StateVector::default());
+/// let u2 = d2.transact().encode_state_as_update_v1(&StateVector::default());
+/// d1.transact_mut().apply_update(Update::decode_v1(&u2).unwrap());
+/// d2.transact_mut().apply_update(Update::decode_v1(&u1).unwrap());
+///
+/// assert_eq!(c1.get(&d1.transact()), 5.0);
+/// assert_eq!(c2.get(&d2.transact()), 5.0);
+/// ```
+///
+/// [Doc::get_or_insert_counter]: crate::Doc::get_or_insert_counter
+#[repr(transparent)]
+#[derive(Debug, Clone)]
+pub struct CounterRef(BranchPtr);
+
+impl CounterRef {
+    /// Returns a current value of this counter.
+    pub fn get<T: ReadTxn>(&self, _txn: &T) -> f64 {
+        counter_value(&self.0)
+    }
+
+    /// Adds a `delta` (which can be negative) to this counter and returns its updated value.
+    pub fn increment(&self, txn: &mut TransactionMut, delta: f64) -> f64 {
+        counter_increment(txn, self.0, delta)
+    }
+
+    /// Returns a sum of increments made by a given `client`.
+    pub fn contribution<T: ReadTxn>(&self, _txn: &T, client: crate::block::ClientID) -> f64 {
+        let key = client.to_string();
+        match self.0.map.get(key.as_str()) {
+            Some(item) if !item.is_deleted() => item_value(&item.content),
+            _ => 0.0,
+        }
+    }
+}
+
+impl RootRef for CounterRef {
+    fn type_ref() -> TypeRef {
+        TypeRef::Counter
+    }
+}
+impl SharedRef for CounterRef {}
+
+impl DeepObservable for CounterRef {}
+impl Observable for CounterRef {
+    type Event = CounterEvent;
+}
+
+impl ToJson for CounterRef {
+    fn to_json<T: ReadTxn>(&self, txn: &T) -> Any {
+        Any::Number(self.get(txn))
+    }
+}
+
+impl AsRef<Branch> for CounterRef {
+    fn as_ref(&self) -> &Branch {
+        self.0.deref()
+    }
+}
+
+impl Eq for CounterRef {}
+impl PartialEq for CounterRef {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.id() == other.0.id()
+    }
+}
+
+impl From<BranchPtr> for CounterRef {
+    fn from(inner: BranchPtr) -> Self {
+        CounterRef(inner)
+    }
+}
+
+impl TryFrom<ItemPtr> for CounterRef {
+    type Error = ItemPtr;
+
+    fn try_from(value: ItemPtr) -> Result<Self, Self::Error> {
+        match value.as_branch() {
+            Some(branch) if branch.type_ref == TypeRef::Counter => Ok(CounterRef::from(branch)),
+            _ => Err(value),
+        }
+    }
+}
+
+/// Returns a current value of a counter stored in a given `branch`.
+///
+/// Counters keep the accumulated contributions of every client separately, as entries of
+/// a branch's map component, keyed by client identifier. Since every client only ever updates
+/// its own entry, concurrent increments never overwrite each other: the value of a counter is
+/// a sum of all contributions.
+pub(crate) fn counter_value(branch: &Branch) -> f64 {
+    let mut contributions: Vec<_> = branch
+        .map
+        .iter()
+        .filter(|(_, item)| !item.is_deleted())
+        .map(|(key, item)| (key.parse::<ClientID>().ok(), key, item_value(&item.content)))
+        .collect();
+    // floating point addition is not associative: summing in a hash map order could make peers
+    // with the same state observe different values
+    contributions.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| a.1.cmp(b.1)));
+    let mut sum = 0.0;
+    for (_, _, value) in contributions {
+        sum += value;
+    }
+    sum
+}
+
+/// Adds a `delta` to a contribution of a current transaction's client to a counter stored in
+/// a given `branch`. Returns an updated value of a counter.
+pub(crate) fn counter_increment(txn: &mut TransactionMut, branch: BranchPtr, delta: f64) -> f64 {
+    let key: Arc<str> = txn.store().options.client_id.to_string().into();
+    let current = match branch.map.get(&key) {
+        Some(item) if !item.is_deleted() => item_value(&item.content),
+        _ => 0.0,
+    };
+    MapRef::from(branch).insert(txn, key, current + delta);
+    counter_value(&branch)
+}
+
+fn item_value(content: &ItemContent) -> f64 {
+    match content {
+        ItemContent::Any(values) => values.last().and_then(as_number).unwrap_or_default(),
+        _ => 0.0,
+    }
+}
+
+pub(crate) fn as_number(value: &Any) -> Option<f64> {
+    match value {
+        Any::Number(n) => Some(*n),
+        Any::BigInt(n) => Some(*n as f64),
+        _ => None,
+    }
+}
+
+/// A preliminary counter. It's used to initialize a new [CounterRef], which initial value is
+/// contributed by the client of a transaction, which integrates it.
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct CounterPrelim(pub f64);
+
+impl Prelim for CounterPrelim {
+    type Return = CounterRef;
+
+    fn into_content(self, _txn: &mut TransactionMut) -> (ItemContent, Option<Self>) {
+        (ItemContent::Type(Branch::new(TypeRef::Counter)), Some(self))
+    }
+
+    fn integrate(self, txn: &mut TransactionMut, inner_ref: BranchPtr) {
+        if self.0 != 0.0 {
+            counter_increment(txn, inner_ref, self.0);
+        }
+    }
+}
+
+/// Event generated by [CounterRef::observe] method. Emitted during transaction commit phase.
+pub struct CounterEvent {
+    pub(crate) current_target: BranchPtr,
+    target: CounterRef,
+    keys: UnsafeCell<Result<f64, HashSet<Option<Arc<str>>>>>,
+}
+
+impl CounterEvent {
+    pub(crate) fn new(branch_ref: BranchPtr, key_changes: HashSet<Option<Arc<str>>>) -> Self {
+        CounterEvent {
+            current_target: branch_ref,
+            target: CounterRef::from(branch_ref),
+            keys: UnsafeCell::new(Err(key_changes)),
+        }
+    }
+
+    /// Returns a [CounterRef] instance which emitted this event.
+    pub fn target(&self) -> &CounterRef {
+        &self.target
+    }
+
+    /// Returns a path from root type down to [CounterRef] instance which emitted this event.
+    pub fn path(&self) -> Path {
+        Branch::path(self.current_target, self.target.0)
+    }
+
+    /// Returns a total change of a counter value made within bounds of current transaction.
+    pub fn delta(&self, txn: &TransactionMut) -> f64 {
+        let keys = unsafe { self.keys.get().as_mut().unwrap() };
+        match keys {
+            Ok(delta) => *delta,
+            Err(subs) => {
+                let mut delta = 0.0;
+                for change in event_keys(txn, self.target.0, subs).values() {
+                    delta += match change {
+                        EntryChange::Inserted(new) => out_value(new),
+                        EntryChange::Updated(old, new) => out_value(new) - out_value(old),
+                        EntryChange::Removed(old) => -out_value(old),
+                    };
+                }
+                *keys = Ok(delta);
+                delta
+            }
+        }
+    }
+}
+
+fn out_value(value: &Out) -> f64 {
+    match value {
+        Out::Any(any) => as_number(any).unwrap_or_default(),
+        _ => 0.0,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::types::counter::{CounterPrelim, CounterRef};
+    use crate::types::ToJson;
+    use crate::updates::decoder::Decode;
+    use crate::{
+        any, Doc, Map, Observable, ReadTxn, SharedRef, StateVector, Transact, Update, WriteTxn,
+    };
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn concurrent_increments() {
+        let d1 = Doc::with_client_id(1);
+        let c1 = d1.get_or_insert_counter("counter");
+        let d2 = Doc::with_client_id(2);
+        let c2 = d2.get_or_insert_counter("counter");
+
+        assert_eq!(c1.increment(&mut d1.transact_mut(), 5.0), 5.0);
+        assert_eq!(c1.increment(&mut d1.transact_mut(), -2.0), 3.0);
+        assert_eq!(c2.increment(&mut d2.transact_mut(), 10.0), 10.0);
+
+        let u1 = d1
+            .transact()
+            .encode_state_as_update_v1(&StateVector::default());
+        let u2 = d2
+            .transact()
+            .encode_state_as_update_v2(&StateVector::default());
+        d1.transact_mut()
+            .apply_update(Update::decode_v2(&u2).unwrap());
+        d2.transact_mut()
+            .apply_update(Update::decode_v1(&u1).unwrap());
+
+        assert_eq!(c1.get(&d1.transact()), 13.0);
+        assert_eq!(c2.get(&d2.transact()), 13.0);
+        assert_eq!(c1.contribution(&d1.transact(), 1), 3.0);
+        assert_eq!(c1.contribution(&d1.transact(), 2), 10.0);
+        assert_eq!(c2.to_json(&d2.transact()), any!(13.0));
+    }
+
+    #[test]
+    fn sum_independent_of_update_order() {
+        let updates: Vec<_> = [(1, 1e16), (2, 1.0), (3, -1e16)]
+            .iter()
+            .map(|&(client, delta)| {
+                let doc = Doc::with_client_id(client);
+                let counter = doc.get_or_insert_counter("counter");
+                counter.increment(&mut doc.transact_mut(), delta);
+                let update = doc
+                    .transact()
+                    .encode_state_as_update_v1(&StateVector::default());
+                update
+            })
+            .collect();
+
+        let d1 = Doc::with_client_id(4);
+        let c1 = d1.get_or_insert_counter("counter");
+        for u in updates.iter() {
+            d1.transact_mut()
+                .apply_update(Update::decode_v1(u).unwrap());
+        }
+        let d2 = Doc::with_client_id(5);
+        let c2 = d2.get_or_insert_counter("counter");
+        for u in updates.iter().rev() {
+            d2.transact_mut()
+                .apply_update(Update::decode_v1(u).unwrap());
+        }
+        assert_eq!(
+            c1.get(&d1.transact()).to_bits(),
+            c2.get(&d2.transact()).to_bits()
+        );
+    }
+
+    #[test]
+    fn nested_counter() {
+        let d1 = Doc::with_client_id(1);
+        let mut txn = d1.transact_mut();
+        let map = txn.get_or_insert_map("map");
+        let counter = map.insert(&mut txn, "likes", CounterPrelim(2.0));
+        counter.increment(&mut txn, 1.0);
+        let hook = counter.hook();
+        assert_eq!(map.to_json(&txn), any!({"likes": 3.0}));
+        drop(txn);
+
+        let d2 = Doc::with_client_id(2);
+        let u = d1
+            .transact()
+            .encode_state_as_update_v2(&StateVector::default());
+        let mut txn = d2.transact_mut();
+        txn.apply_update(Update::decode_v2(&u).unwrap());
+        let counter: CounterRef = hook.get(&txn).unwrap();
+        assert_eq!(counter.increment(&mut txn, 4.0), 7.0);
+    }
+
+    #[test]
+    fn observe_counter() {
+        let doc = Doc::with_client_id(1);
+        let counter = doc.get_or_insert_counter("counter");
+        let deltas = Arc::new(Mutex::new(Vec::new()));
+        let d = deltas.clone();
+        let _sub = counter.observe(move |txn, e| {
+            d.lock().unwrap().push((e.delta(txn), e.target().get(txn)));
+        });
+
+        counter.increment(&mut doc.transact_mut(), 2.0);
+        {
+            let mut txn = doc.transact_mut();
+            counter.increment(&mut txn, 3.0);
+            counter.increment(&mut txn, -1.0);
+        }
+        assert_eq!(*deltas.lock().unwrap(), vec![(2.0, 2.0), (2.0, 4.0)]);
+    }
+}
@@ -0,0 +1,280 @@
+//! Fixed-layout shared type.
+//!
+//! [FixedRef] stores a small struct of numeric fields (i.e. x/y/width/height transforms of
+//! whiteboard elements). Every field is a separate last-writer-wins register, so that updating
+//! a single field produces an update containing only that field, and concurrent updates of
+//! different fields made by different peers are all preserved.
+
+use crate::block::{ItemContent, ItemPtr, Prelim};
+use crate::branch::{Branch, BranchPtr};
+use crate::transaction::TransactionMut;
+use crate::types::counter::as_number;
+use crate::types::{SharedRef, ToJson, TypeRef};
+use crate::{Any, Map, MapRef, ReadTxn};
+use std::convert::TryFrom;
+use std::ops::Deref;
+
+/// Trait implemented by small structs of numeric fields with a fixed layout (i.e. x/y/width/height
+/// transforms of whiteboard elements), which can be stored as a [FixedRef] - usually within a [Map]
+/// using [Map::insert_fixed] and read back using [Map::get_fixed].
+///
+/// [Map]: crate::Map
+/// [Map::insert_fixed]: crate::Map::insert_fixed
+/// [Map::get_fixed]: crate::Map::get_fixed
+pub trait FixedLayout: Sized {
+    /// Number of fields of a current layout.
+    const FIELDS: usize;
+
+    /// Writes fields of a current struct into a given slice of [Self::FIELDS] length.
+    fn write_fields(&self, fields: &mut [f64]);
+
+    /// Reads the struct back from a given slice of [Self::FIELDS] length.
+    fn read_fields(fields: &[f64]) -> Self;
+}
+
+impl<const N: usize> FixedLayout for [f64; N] {
+    const FIELDS: usize = N;
+
+    fn write_fields(&self, fields: &mut [f64]) {
+        fields.copy_from_slice(self)
+    }
+
+    fn read_fields(fields: &[f64]) -> Self {
+        let mut result = [0.0; N];
+        result.copy_from_slice(fields);
+        result
+    }
+}
+
+/// A collaborative struct of numeric fields with a fixed layout (see: [FixedLayout]).
+///
+/// Unlike an [Any::Map] stored as a [Map] entry - which is rewritten as a whole, field names
+/// included, every time any of its fields changes - fields of a [FixedRef] are overwritten in
+/// place: writing a struct produces an update containing only the fields, which values have
+/// changed, while fields left untouched produce no update at all. Concurrent updates of
+/// different fields are merged, while concurrent updates of the same field are resolved like
+/// concurrent [Map::insert]s.
+///
+/// When read through their parent collection (i.e. [Map::get] or [ToJson::to_json]), fixed-layout
+/// structs are represented as arrays of numbers. Their changes are reported to deep observers as
+/// map events keyed by field index.
+///
+/// Fixed-layout structs are not supported by Yjs.
+///
+/// # Example
+///
+/// ```rust
+/// use yrs::{Doc, FixedPrelim, Transact};
+/// use yrs::{Map, ReadTxn};
+///
+/// let doc = Doc::new();
+/// let shapes = doc.get_or_insert_map("shapes");
+/// let mut txn = doc.transact_mut();
+///
+/// let rect = shapes.insert(&mut txn, "rect", FixedPrelim([10.0, 20.0, 100.0, 50.0]));
+/// // only the first two fields are written
+/// assert!(rect.set(&mut txn, &[15.0, 25.0, 100.0, 50.0]));
+/// assert_eq!(rect.get(&txn), Some([15.0, 25.0, 100.0, 50.0]));
+/// assert_eq!(rect.field(&txn, 1), Some(25.0));
+/// ```
+///
+/// [Map]: crate::Map
+/// [Map::get]: crate::Map::get
+/// [Map::insert]: crate::Map::insert
+#[repr(transparent)]
+#[derive(Debug, Clone)]
+pub struct FixedRef(BranchPtr);
+
+impl FixedRef {
+    /// Returns a struct stored in current [FixedRef]. Returns `None` if any of the fields of
+    /// a given layout is missing.
+    pub fn get<T: ReadTxn, V: FixedLayout>(&self, txn: &T) -> Option<V> {
+        let mut fields = vec![0.0; V::FIELDS];
+        for (index, field) in fields.iter_mut().enumerate() {
+            *field = self.field(txn, index)?;
+        }
+        Some(V::read_fields(&fields))
+    }
+
+    /// Returns a value of a single field under a given `index`.
+    pub fn field<T: ReadTxn>(&self, _txn: &T, index: usize) -> Option<f64> {
+        let key = index.to_string();
+        field_value(self.0.map.get(key.as_str())?)
+    }
+
+    /// Overwrites fields of current [FixedRef] with the fields of a given `value`. Only the fields,
+    /// which values have changed, are written. Returns `false` if no field has changed.
+    pub fn set<V: FixedLayout>(&self, txn: &mut TransactionMut, value: &V) -> bool {
+        let mut fields = vec![0.0; V::FIELDS];
+        value.write_fields(&mut fields);
+        let mut changed = false;
+        for (index, field) in fields.into_iter().enumerate() {
+            changed |= self.set_field(txn, index, field);
+        }
+        changed
+    }
+
+    /// Overwrites a single field under a given `index`. Returns `false` if it already had
+    /// a given `value`.
+    pub fn set_field(&self, txn: &mut TransactionMut, index: usize, value: f64) -> bool {
+        if self.field(txn, index).map(f64::to_bits) == Some(value.to_bits()) {
+            return false;
+        }
+        MapRef::from(self.0).insert(txn, index.to_string(), value);
+        true
+    }
+}
+
+impl SharedRef for FixedRef {}
+
+impl ToJson for FixedRef {
+    fn to_json<T: ReadTxn>(&self, _txn: &T) -> Any {
+        fixed_value(&self.0)
+    }
+}
+
+impl AsRef<Branch> for FixedRef {
+    fn as_ref(&self) -> &Branch {
+        self.0.deref()
+    }
+}
+
+impl Eq for FixedRef {}
+impl PartialEq for FixedRef {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.id() == other.0.id()
+    }
+}
+
+impl From<BranchPtr> for FixedRef {
+    fn from(inner: BranchPtr) -> Self {
+        FixedRef(inner)
+    }
+}
+
+impl TryFrom<ItemPtr> for FixedRef {
+    type Error = ItemPtr;
+
+    fn try_from(value: ItemPtr) -> Result<Self, Self::Error> {
+        match value.as_branch() {
+            Some(branch) if branch.type_ref == TypeRef::Fixed => Ok(FixedRef::from(branch)),
+            _ => Err(value),
+        }
+    }
+}
+
+/// Returns a [FixedRef] stored in a given map entry `item`, if it's not deleted.
+pub(crate) fn fixed_entry(item: &ItemPtr) -> Option<FixedRef> {
+    if item.is_deleted() {
+        None
+    } else {
+        FixedRef::try_from(*item).ok()
+    }
+}
+
+fn field_value(item: &ItemPtr) -> Option<f64> {
+    if item.is_deleted() {
+        return None;
+    }
+    match &item.content {
+        ItemContent::Any(values) => values.last().and_then(as_number),
+        _ => None,
+    }
+}
+
+/// Returns fields of a fixed-layout struct stored in a given `branch` as an array of numbers,
+/// ordered by their index.
+pub(crate) fn fixed_value(branch: &Branch) -> Any {
+    let mut fields: Vec<_> = branch
+        .map
+        .iter()
+        .filter_map(|(key, item)| Some((key.parse::<usize>().ok()?, field_value(item)?)))
+        .collect();
+    fields.sort_by_key(|(index, _)| *index);
+    Any::Array(
+        fields
+            .into_iter()
+            .map(|(_, value)| Any::Number(value))
+            .collect(),
+    )
+}
+
+/// A preliminary fixed-layout struct. It's used to initialize a new [FixedRef].
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct FixedPrelim<V>(pub V);
+
+impl<V: FixedLayout> Prelim for FixedPrelim<V> {
+    type Return = FixedRef;
+
+    fn into_content(self, _txn: &mut TransactionMut) -> (ItemContent, Option<Self>) {
+        (ItemContent::Type(Branch::new(TypeRef::Fixed)), Some(self))
+    }
+
+    fn integrate(self, txn: &mut TransactionMut, inner_ref: BranchPtr) {
+        FixedRef::from(inner_ref).set(txn, &self.0);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::test_utils::exchange_updates;
+    use crate::types::fixed::FixedPrelim;
+    use crate::types::ToJson;
+    use crate::{any, Doc, Map, Transact, WriteTxn};
+
+    #[test]
+    fn concurrent_field_updates() {
+        let d1 = Doc::with_client_id(1);
+        let m1 = d1.get_or_insert_map("shapes");
+        let d2 = Doc::with_client_id(2);
+        let m2 = d2.get_or_insert_map("shapes");
+
+        let r1 = m1.insert(
+            &mut d1.transact_mut(),
+            "rect",
+            FixedPrelim([0.0, 0.0, 10.0, 10.0]),
+        );
+        exchange_updates(&[&d1, &d2]);
+
+        // peers move and resize the same rectangle concurrently
+        assert!(r1.set(&mut d1.transact_mut(), &[5.0, 5.0, 10.0, 10.0]));
+        assert!(m2.insert_fixed(&mut d2.transact_mut(), "rect", &[0.0, 0.0, 20.0, 30.0]));
+        exchange_updates(&[&d1, &d2]);
+
+        for (doc, map) in [(&d1, &m1), (&d2, &m2)] {
+            let txn = doc.transact();
+            assert_eq!(map.get_fixed(&txn, "rect"), Some([5.0, 5.0, 20.0, 30.0]));
+            assert_eq!(map.to_json(&txn), any!({"rect": [5.0, 5.0, 20.0, 30.0]}));
+        }
+        assert_eq!(r1.get(&d1.transact()), Some([5.0, 5.0, 20.0, 30.0]));
+        assert_eq!(r1.get::<_, [f64; 5]>(&d1.transact()), None);
+    }
+
+    #[test]
+    fn field_updates_are_delta_encoded() {
+        let doc = Doc::with_client_id(1);
+        let mut txn = doc.transact_mut();
+        let map = txn.get_or_insert_map("shapes");
+        let rect = map.insert(&mut txn, "rect", FixedPrelim([0.0, 0.0, 10.0, 10.0]));
+        drop(txn);
+
+        let one_field = {
+            let mut txn = doc.transact_mut();
+            assert!(rect.set(&mut txn, &[1.5, 0.0, 10.0, 10.0]));
+            txn.encode_update_v1().len()
+        };
+        let two_fields = {
+            let mut txn = doc.transact_mut();
+            assert!(rect.set(&mut txn, &[2.5, 1.5, 10.0, 10.0]));
+            txn.encode_update_v1().len()
+        };
+        assert!(one_field < two_fields);
+
+        // unchanged fields are not written
+        let mut txn = doc.transact_mut();
+        assert!(!rect.set(&mut txn, &[2.5, 1.5, 10.0, 10.0]));
+        assert!(!rect.set_field(&mut txn, 3, 10.0));
+        assert!(txn.encode_update_v1().len() <= 2);
+    }
+}
@@ -0,0 +1,197 @@
+use crate::block::ItemContent;
+use crate::branch::Branch;
+use crate::TransactionMut;
+
+/// A position within a text, expressed the way incremental parsers such as tree-sitter expect it:
+/// as a zero-based row and a column measured in UTF-8 bytes since the beginning of that row.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Point {
+    pub row: usize,
+    pub column: usize,
+}
+
+impl Point {
+    pub fn new(row: usize, column: usize) -> Self {
+        Point { row, column }
+    }
+
+    fn advance(&mut self, str: &str) {
+        match str.rfind('\n') {
+            None => self.column += str.len(),
+            Some(i) => {
+                self.row += str.matches('\n').count();
+                self.column = str.len() - i - 1;
+            }
+        }
+    }
+}
+
+/// Description of a single text edit, which mirrors the layout of tree-sitter's `InputEdit`, so
+/// that it can be passed to `Tree::edit` field by field. All byte offsets are UTF-8 based.
+///
+/// Edits produced by [TextEvent::input_edits] are ordered and meant to be applied one after
+/// another: positions of every edit already take into account all of the edits preceding it.
+///
+/// [TextEvent::input_edits]: crate::types::text::TextEvent::input_edits
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct InputEdit {
+    pub start_byte: usize,
+    pub old_end_byte: usize,
+    pub new_end_byte: usize,
+    pub start_position: Point,
+    pub old_end_position: Point,
+    pub new_end_position: Point,
+}
+
+/// Position within a text, which can be moved forward over the text contents.
+pub(crate) trait TextCursor: Copy + Default {
+    fn advance(&mut self, str: &str);
+}
+
+/// Position tracked while walking over the blocks of a text.
+#[derive(Debug, Clone, Copy, Default)]
+struct Cursor {
+    byte: usize,
+    point: Point,
+}
+
+impl TextCursor for Cursor {
+    fn advance(&mut self, str: &str) {
+        self.byte += str.len();
+        self.point.advance(str);
+    }
+}
+
+/// Computes a list of changes made over a text `branch` within the bounds of a given
+/// transaction. Each change is a `(start, old_end, inserted)` triple: a text between `start` and
+/// `old_end` has been replaced with the `inserted` string. Positions of every change already take
+/// into account all of the changes preceding it. Since blocks removed by that transaction still
+/// keep their contents until the transaction is committed, they are used to compute end positions
+/// of the removed ranges.
+pub(crate) fn text_changes<C: TextCursor>(
+    branch: &Branch,
+    txn: &TransactionMut,
+) -> Vec<(C, C, String)> {
+    let mut changes = Vec::new();
+    // position in the text with all preceding changes already applied
+    let mut current = C::default();
+    // pending change: (start, old end, inserted text)
+    let mut pending: Option<(C, C, String)> = None;
+    let mut ptr = branch.start;
+    while let Some(item) = ptr.as_deref() {
+        if let ItemContent::String(s) = &item.content {
+            let added = txn.has_added(&item.id);
+            let deleted = item.is_deleted();
+            let was_present = !added && (!deleted || txn.has_deleted(&item.id));
+            let is_present = !deleted;
+            match (was_present, is_present) {
+                (true, true) => {
+                    if let Some((start, old_end, text)) = pending.take() {
+                        current = start;
+                        current.advance(&text);
+                        changes.push((start, old_end, text));
+                    }
+                    current.advance(s.as_str());
+                }
+                (true, false) => {
+                    let (_, old_end, _) = pending.get_or_insert((current, current, String::new()));
+                    old_end.advance(s.as_str());
+                }
+                (false, true) => {
+                    let (_, _, text) = pending.get_or_insert((current, current, String::new()));
+                    text.push_str(s.as_str());
+                }
+                (false, false) => { /* inserted and removed within the same transaction */ }
+            }
+        }
+        ptr = item.right;
+    }
+    changes.extend(pending);
+    changes
+}
+
+/// Computes a list of input edits made over a text `branch` within the bounds of a given
+/// transaction.
+pub(crate) fn input_edits(branch: &Branch, txn: &TransactionMut) -> Vec<InputEdit> {
+    text_changes::<Cursor>(branch, txn)
+        .into_iter()
+        .map(|(start, old_end, text)| {
+            let mut new_end = start;
+            new_end.advance(&text);
+            InputEdit {
+                start_byte: start.byte,
+                old_end_byte: old_end.byte,
+                new_end_byte: new_end.byte,
+                start_position: start.point,
+                old_end_position: old_end.point,
+                new_end_position: new_end.point,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::types::input_edit::{InputEdit, Point};
+    use crate::{Doc, Observable, Text, Transact};
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn text_event_input_edits() {
+        let doc = Doc::with_client_id(1);
+        let text = doc.get_or_insert_text("text");
+        text.push(&mut doc.transact_mut(), "hello\nworld");
+
+        let edits = Arc::new(Mutex::new(Vec::new()));
+        let _sub = {
+            let edits = edits.clone();
+            text.observe(move |txn, e| edits.lock().unwrap().extend(e.input_edits(txn)))
+        };
+
+        {
+            let mut txn = doc.transact_mut();
+            text.insert(&mut txn, 6, "big ");
+            text.remove_range(&mut txn, 0, 5);
+        }
+        assert_eq!(
+            edits.lock().unwrap().as_slice(),
+            &[
+                InputEdit {
+                    start_byte: 0,
+                    old_end_byte: 5,
+                    new_end_byte: 0,
+                    start_position: Point::new(0, 0),
+                    old_end_position: Point::new(0, 5),
+                    new_end_position: Point::new(0, 0),
+                },
+                InputEdit {
+                    start_byte: 1,
+                    old_end_byte: 1,
+                    new_end_byte: 5,
+                    start_position: Point::new(1, 0),
+                    old_end_position: Point::new(1, 0),
+                    new_end_position: Point::new(1, 4),
+                },
+            ]
+        );
+
+        edits.lock().unwrap().clear();
+        {
+            // "\nbig world" => "\nbig\nnew world"
+            let mut txn = doc.transact_mut();
+            text.remove_range(&mut txn, 4, 1);
+            text.insert(&mut txn, 4, "\nnew ");
+        }
+        assert_eq!(
+            edits.lock().unwrap().as_slice(),
+            &[InputEdit {
+                start_byte: 4,
+                old_end_byte: 5,
+                new_end_byte: 9,
+                start_position: Point::new(1, 3),
+                old_end_position: Point::new(1, 4),
+                new_end_position: Point::new(2, 4),
+            }]
+        );
+    }
+}
@@ -2,6 +2,8 @@ use crate::block::{EmbedPrelim, ItemContent, ItemPosition, ItemPtr, Prelim};
 use crate::encoding::read::Error;
 use crate::encoding::serde::from_any;
 use crate::transaction::TransactionMut;
+use crate::types::counter::{as_number, counter_increment, CounterPrelim};
+use crate::types::fixed::{fixed_entry, FixedLayout, FixedPrelim};
 use crate::types::{
     event_keys, AsPrelim, Branch, BranchPtr, DefaultPrelim, Entries, EntryChange, In, Out, Path,
     RootRef, SharedRef, ToJson, TypeRef,
@@ -76,6 +78,7 @@ impl Observable for MapRef {
 
 impl ToJson for MapRef {
     fn to_json<T: ReadTxn>(&self, txn: &T) -> Any {
+        txn.track(&self.0);
         let inner = self.0;
         let mut res = HashMap::new();
         for (key, item) in inner.map.iter() {
@@ -86,6 +89,28 @@ impl ToJson for MapRef {
         }
         Any::from(res)
     }
+
+    fn to_json_writer<T: ReadTxn, W: std::io::Write>(
+        &self,
+        txn: &T,
+        writer: &mut W,
+    ) -> std::io::Result<()> {
+        writer.write_all(b"{")?;
+        let mut first = true;
+        for (key, item) in self.0.map.iter() {
+            if !item.is_deleted() {
+                if !first {
+                    writer.write_all(b",")?;
+                }
+                first = false;
+                serde_json::to_writer(&mut *writer, key.as_ref())?;
+                writer.write_all(b":")?;
+                let last = item.content.get_last().unwrap_or(Out::Any(Any::Null));
+                last.to_json_writer(txn, writer)?;
+            }
+        }
+        writer.write_all(b"}")
+    }
 }
 
 impl AsRef<Branch> for MapRef {
@@ -149,37 +174,269 @@ impl DefaultPrelim for MapRef {
     }
 }
 
-pub trait Map: AsRef<Branch> + Sized {
-    /// Returns a number of entries stored within current map.
-    fn len<T: ReadTxn>(&self, _txn: &T) -> u32 {
-        let mut len = 0;
-        let inner = self.as_ref();
-        for item in inner.map.values() {
-            //TODO: maybe it would be better to just cache len in the map itself?
+impl MapRef {
+    /// Enables (or changes) a field history mode of current map, in which up to `limit` most
+    /// recently overwritten or removed values are kept for every key and can be read using
+    /// [MapRef::history]. Setting `limit` to 0 disables this mode.
+    ///
+    /// While this mode is enabled, overwritten values are kept in a document as tombstones instead
+    /// of being garbage collected. Only the values overwritten after this mode has been enabled
+    /// are retained. Values set and overwritten by remote peers, which don't keep the history
+    /// themselves, are only retained if current document received them before they were
+    /// overwritten. This setting is local to a current document instance and it's not part of
+    /// the document state exchanged with other peers.
+    pub fn set_history_limit(&self, txn: &mut TransactionMut, limit: u32) {
+        let store = txn.store_mut();
+        if limit == 0 {
+            store.map_history.remove(&self.0);
+        } else {
+            store.map_history.insert(self.0, limit);
+        }
+    }
+
+    /// Returns historical values of a given `key`, which have been overwritten or removed, starting
+    /// from the most recent one. Current value of a `key` is not included.
+    ///
+    /// Values are only retained when field history mode has been enabled using
+    /// [MapRef::set_history_limit]. Otherwise this method returns an empty list.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use yrs::{Doc, Map, Transact};
+    ///
+    /// let doc = Doc::new();
+    /// let map = doc.get_or_insert_map("map");
+    /// map.set_history_limit(&mut doc.transact_mut(), 2);
+    ///
+    /// for title in ["draft", "review", "final", "published"] {
+    ///     map.insert(&mut doc.transact_mut(), "title", title);
+    /// }
+    ///
+    /// let txn = doc.transact();
+    /// assert_eq!(map.get(&txn, "title"), Some("published".into()));
+    /// assert_eq!(map.history(&txn, "title"), vec!["final".into(), "review".into()]);
+    /// ```
+    pub fn history<T: ReadTxn>(&self, txn: &T, key: &str) -> Vec<Out> {
+        let limit = match txn.store().map_history.get(&self.0) {
+            Some(&limit) => limit as usize,
+            None => return Vec::new(),
+        };
+        let mut values = Vec::new();
+        let mut current = self.0.map.get(key).copied();
+        if let Some(item) = current {
             if !item.is_deleted() {
-                len += 1;
+                current = item.left;
+            }
+        }
+        while let Some(item) = current.as_deref() {
+            if values.len() >= limit || matches!(item.content, ItemContent::Deleted(_)) {
+                break;
             }
+            // entries squashed together keep their values in order of insertion
+            values.extend(item.content.get_content().into_iter().rev());
+            current = item.left;
         }
-        len
+        values.truncate(limit);
+        values
+    }
+
+    /// Registers a `resolver` of concurrent writes to a given `key` of current map, replacing
+    /// the one registered before.
+    ///
+    /// By default, when two peers set the same key concurrently, one of the values wins and the
+    /// other one is discarded. With a resolver registered, every time a remote update causes
+    /// such conflict, a resolver is called with the winning value and the discarded one, and its
+    /// result is written under a `key` in a follow-up transaction (see [TransactionMut::defer]),
+    /// unless it's equal to the winning value. Resolvers are consulted only for JSON-like values,
+    /// not for shared collections.
+    ///
+    /// Since every peer resolves the same conflicts independently, resolver must be registered
+    /// by all of them, and its result must not depend on the order of its arguments (i.e. keeping
+    /// the greater number or a union of sets). This setting is local to a current document
+    /// instance and it's not part of the document state exchanged with other peers.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use yrs::{Any, Doc, Map, ReadTxn, StateVector, Transact, Update};
+    /// use yrs::updates::decoder::Decode;
+    ///
+    /// let keep_max = |a: &Any, b: &Any| match (a, b) {
+    ///     (Any::Number(x), Any::Number(y)) => Any::Number(x.max(*y)),
+    ///     _ => a.clone(),
+    /// };
+    ///
+    /// let d1 = Doc::with_client_id(1);
+    /// let m1 = d1.get_or_insert_map("scores");
+    /// m1.set_resolver(&mut d1.transact_mut(), "best", keep_max);
+    /// m1.insert(&mut d1.transact_mut(), "best", 10);
+    ///
+    /// let d2 = Doc::with_client_id(2);
+    /// let m2 = d2.get_or_insert_map("scores");
+    /// m2.set_resolver(&mut d2.transact_mut(), "best", keep_max);
+    /// m2.insert(&mut d2.transact_mut(), "best", 7);
+    ///
+    /// let update = d2.transact().encode_state_as_update_v1(&StateVector::default());
+    /// d1.transact_mut().apply_update(Update::decode_v1(&update).unwrap());
+    ///
+    /// assert_eq!(m1.get(&d1.transact(), "best"), Some(10.into()));
+    /// ```
+    pub fn set_resolver<K, F>(&self, txn: &mut TransactionMut, key: K, resolver: F)
+    where
+        K: Into<Arc<str>>,
+        F: Fn(&Any, &Any) -> Any + Send + Sync + 'static,
+    {
+        let resolvers = txn.store_mut().map_resolvers.entry(self.0).or_default();
+        resolvers.insert(key.into(), Arc::new(resolver));
+    }
+
+    /// Removes a resolver of concurrent writes registered for a given `key` using
+    /// [MapRef::set_resolver]. Returns `true` if there was a resolver registered.
+    pub fn remove_resolver(&self, txn: &mut TransactionMut, key: &str) -> bool {
+        let store = txn.store_mut();
+        match store.map_resolvers.get_mut(&self.0) {
+            None => false,
+            Some(resolvers) => {
+                let removed = resolvers.remove(key).is_some();
+                if resolvers.is_empty() {
+                    store.map_resolvers.remove(&self.0);
+                }
+                removed
+            }
+        }
+    }
+
+    /// Returns an entry under a given `key` of a current map for in-place manipulation. Entry
+    /// holds onto a read-write transaction, so that no other changes can happen in between
+    /// checking the entry and updating it.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use yrs::{Doc, Map, Out, Transact};
+    ///
+    /// let doc = Doc::new();
+    /// let map = doc.get_or_insert_map("map");
+    /// let mut txn = doc.transact_mut();
+    ///
+    /// let value = map.entry(&mut txn, "key").or_insert("a");
+    /// assert_eq!(value, Out::from("a"));
+    ///
+    /// // existing value is not replaced
+    /// let value = map.entry(&mut txn, "key").or_insert_with(|| "b");
+    /// assert_eq!(value, Out::from("a"));
+    /// ```
+    pub fn entry<'a, 'doc, K>(
+        &self,
+        txn: &'a mut TransactionMut<'doc>,
+        key: K,
+    ) -> MapEntry<'a, 'doc>
+    where
+        K: Into<Arc<str>>,
+    {
+        let key = key.into();
+        match self.0.map.get(&key) {
+            Some(&item) if !item.is_deleted() => MapEntry::Occupied(OccupiedMapEntry {
+                map: self.clone(),
+                txn,
+                key,
+                item,
+            }),
+            _ => MapEntry::Vacant(VacantMapEntry {
+                map: self.clone(),
+                txn,
+                key,
+            }),
+        }
+    }
+
+    /// Returns a [MapRef] stored under a given `key`, inserting a new empty one if the entry was
+    /// not present. See [Map::get_or_init] for details.
+    pub fn get_or_init_map<K>(&self, txn: &mut TransactionMut, key: K) -> MapRef
+    where
+        K: Into<Arc<str>>,
+    {
+        self.get_or_init(txn, key)
+    }
+
+    /// Returns an [ArrayRef] stored under a given `key`, inserting a new empty one if the entry
+    /// was not present. See [Map::get_or_init] for details.
+    pub fn get_or_init_array<K>(&self, txn: &mut TransactionMut, key: K) -> ArrayRef
+    where
+        K: Into<Arc<str>>,
+    {
+        self.get_or_init(txn, key)
+    }
+
+    /// Returns a [TextRef] stored under a given `key`, inserting a new empty one if the entry was
+    /// not present. See [Map::get_or_init] for details.
+    pub fn get_or_init_text<K>(&self, txn: &mut TransactionMut, key: K) -> TextRef
+    where
+        K: Into<Arc<str>>,
+    {
+        self.get_or_init(txn, key)
+    }
+}
+
+pub trait Map: AsRef<Branch> + Sized {
+    /// Returns a number of entries stored within current map.
+    fn len<T: ReadTxn>(&self, txn: &T) -> u32 {
+        txn.track(self.as_ref());
+        self.as_ref().map_len
+    }
+
+    /// Checks if current map has no entries.
+    fn is_empty<T: ReadTxn>(&self, txn: &T) -> bool {
+        self.len(txn) == 0
     }
 
     /// Returns an iterator that enables to traverse over all keys of entries stored within
     /// current map. These keys are not ordered.
     fn keys<'a, T: ReadTxn + 'a>(&'a self, txn: &'a T) -> Keys<'a, &'a T, T> {
+        txn.track(self.as_ref());
         Keys::new(self.as_ref(), txn)
     }
 
     /// Returns an iterator that enables to traverse over all values stored within current map.
     fn values<'a, T: ReadTxn + 'a>(&'a self, txn: &'a T) -> Values<'a, &'a T, T> {
+        txn.track(self.as_ref());
         Values::new(self.as_ref(), txn)
     }
 
     /// Returns an iterator that enables to traverse over all entries - tuple of key-value pairs -
     /// stored within current map.
     fn iter<'a, T: ReadTxn + 'a>(&'a self, txn: &'a T) -> MapIter<'a, &'a T, T> {
+        txn.track(self.as_ref());
         MapIter::new(self.as_ref(), txn)
     }
 
+    /// Returns an iterator that enables to traverse over all entries - tuple of key-value pairs -
+    /// stored within current map. Unlike [Map::iter], returned iterator takes ownership over
+    /// a transaction, so that it can be returned from a function or passed into an async stream
+    /// without being bound to the lifetime of a transaction reference.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::sync::Arc;
+    /// use yrs::{Doc, Map, Out, Transact};
+    ///
+    /// fn entries(doc: &Doc) -> impl Iterator<Item = (Arc<str>, Out)> + '_ {
+    ///     let map = doc.get_or_insert_map("map");
+    ///     map.iter_owned(doc.transact())
+    /// }
+    ///
+    /// let doc = Doc::new();
+    /// let map = doc.get_or_insert_map("map");
+    /// map.insert(&mut doc.transact_mut(), "key", "value");
+    /// let entries: Vec<_> = entries(&doc).collect();
+    /// assert_eq!(entries, vec![("key".into(), Out::from("value"))]);
+    /// ```
+    fn iter_owned<T: ReadTxn>(&self, txn: T) -> MapIterOwned<T> {
+        MapIterOwned::new(self.as_ref(), txn)
+    }
+
     /// Inserts a new `value` under given `key` into current map. Returns an integrated value.
     fn insert<K, V>(&self, txn: &mut TransactionMut, key: K, value: V) -> V::Return
     where
@@ -254,6 +511,55 @@ pub trait Map: AsRef<Branch> + Sized {
         true
     }
 
+    /// Increments a numeric value stored under a given `key` by `delta` and returns the updated
+    /// value. Unlike read-modify-write done with [Map::insert], increments made concurrently by
+    /// different peers are all preserved and summed up.
+    ///
+    /// The first increment turns an entry into a counter (seeded with an existing numeric value, if
+    /// there was one). Counters are still read as plain numbers by [Map::get], [Map::iter] or
+    /// [ToJson::to_json]. Keep in mind that concurrent initialization of a counter under the same
+    /// key is resolved like any other concurrent map update, so only one of them will be kept.
+    ///
+    /// Counters are not supported by Yjs.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use yrs::{Doc, Map, Out, Transact};
+    ///
+    /// let doc = Doc::new();
+    /// let map = doc.get_or_insert_map("map");
+    /// let mut txn = doc.transact_mut();
+    ///
+    /// map.insert(&mut txn, "likes", 1);
+    /// assert_eq!(map.increment(&mut txn, "likes", 2.0), 3.0);
+    /// assert_eq!(map.get(&txn, "likes"), Some(Out::from(3.0)));
+    /// ```
+    fn increment<K>(&self, txn: &mut TransactionMut, key: K, delta: f64) -> f64
+    where
+        K: Into<Arc<str>>,
+    {
+        let key = key.into();
+        let branch = self.as_ref();
+        if let Some(item) = branch.map.get(&key) {
+            if !item.is_deleted() {
+                if let ItemContent::Type(inner) = &item.content {
+                    if inner.type_ref == TypeRef::Counter {
+                        let counter = BranchPtr::from(inner.as_ref());
+                        return counter_increment(txn, counter, delta);
+                    }
+                }
+            }
+        }
+        let initial = match self.get(txn, &key) {
+            Some(Out::Any(value)) => as_number(&value).unwrap_or_default(),
+            _ => 0.0,
+        };
+        let value = initial + delta;
+        self.insert(txn, key, CounterPrelim(value));
+        value
+    }
+
     /// Returns an existing instance of a type stored under a given `key` within current map.
     /// If the given entry was not found, has been deleted or its type is different from expected,
     /// that entry will be reset to a given type and its reference will be returned.
@@ -301,10 +607,52 @@ pub trait Map: AsRef<Branch> + Sized {
     /// Returns a value stored under a given `key` within current map, or `None` if no entry
     /// with such `key` existed.
     fn get<T: ReadTxn>(&self, txn: &T, key: &str) -> Option<Out> {
+        txn.track(self.as_ref());
         let ptr = BranchPtr::from(self.as_ref());
         ptr.get(txn, key)
     }
 
+    /// Inserts a fixed-layout struct under a given `key` as a [FixedRef]. If the entry already
+    /// holds a [FixedRef], its fields are overwritten in place: only the fields, which values have
+    /// changed, are written (see: [FixedRef::set]). Returns `false` if nothing has changed, so that
+    /// repeated overwrites (i.e. during drag operations) don't produce redundant updates.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use yrs::{Doc, Map, Transact};
+    ///
+    /// let doc = Doc::new();
+    /// let shapes = doc.get_or_insert_map("shapes");
+    /// let mut txn = doc.transact_mut();
+    ///
+    /// assert!(shapes.insert_fixed(&mut txn, "rect", &[10.0, 20.0, 100.0, 50.0]));
+    /// assert!(!shapes.insert_fixed(&mut txn, "rect", &[10.0, 20.0, 100.0, 50.0]));
+    /// assert_eq!(shapes.get_fixed(&txn, "rect"), Some([10.0, 20.0, 100.0, 50.0]));
+    /// ```
+    fn insert_fixed<K, V>(&self, txn: &mut TransactionMut, key: K, value: &V) -> bool
+    where
+        K: Into<Arc<str>>,
+        V: FixedLayout,
+    {
+        let key = key.into();
+        if let Some(fixed) = self.as_ref().map.get(&key).and_then(fixed_entry) {
+            return fixed.set(txn, value);
+        }
+        // create an empty struct first, then write all of its fields
+        let fixed = self.insert(txn, key, FixedPrelim([0.0; 0]));
+        fixed.set(txn, value);
+        true
+    }
+
+    /// Returns a fixed-layout struct stored under a given `key` using [Map::insert_fixed].
+    /// Returns `None` if the entry doesn't exist or its layout doesn't match.
+    fn get_fixed<T: ReadTxn, V: FixedLayout>(&self, txn: &T, key: &str) -> Option<V> {
+        txn.track(self.as_ref());
+        let fixed = fixed_entry(self.as_ref().map.get(key)?)?;
+        fixed.get(txn)
+    }
+
     /// Returns a value stored under a given `key` within current map, deserializing it into expected
     /// type if found. If value was not found, the `Any::Null` will be substituted and deserialized
     /// instead (i.e. into instance of `Option` type, if so desired).
@@ -371,7 +719,8 @@ pub trait Map: AsRef<Branch> + Sized {
     }
 
     /// Checks if an entry with given `key` can be found within current map.
-    fn contains_key<T: ReadTxn>(&self, _txn: &T, key: &str) -> bool {
+    fn contains_key<T: ReadTxn>(&self, txn: &T, key: &str) -> bool {
+        txn.track(self.as_ref());
         if let Some(item) = self.as_ref().map.get(key) {
             !item.is_deleted()
         } else {
@@ -418,6 +767,48 @@ where
     }
 }
 
+/// An unordered iterator over the entries of a [Map], which owns a transaction it reads from.
+/// See: [Map::iter_owned].
+#[derive(Debug)]
+pub struct MapIterOwned<T> {
+    entries: std::vec::IntoIter<(Arc<str>, ItemPtr)>,
+    txn: T,
+}
+
+impl<T: ReadTxn> MapIterOwned<T> {
+    fn new(branch: &Branch, txn: T) -> Self {
+        // transaction owned by the iterator keeps the map from being modified in the meantime
+        let entries: Vec<_> = branch
+            .map
+            .iter()
+            .filter(|(_, item)| !item.is_deleted())
+            .map(|(key, item)| (key.clone(), *item))
+            .collect();
+        MapIterOwned {
+            entries: entries.into_iter(),
+            txn,
+        }
+    }
+
+    /// Returns a transaction owned by current iterator.
+    pub fn txn(&self) -> &T {
+        &self.txn
+    }
+}
+
+impl<T: ReadTxn> Iterator for MapIterOwned<T> {
+    type Item = (Arc<str>, Out);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (key, item) = self.entries.next()?;
+            if let Some(content) = item.content.get_last() {
+                return Some((key, content));
+            }
+        }
+    }
+}
+
 /// An unordered iterator over the keys of a [Map].
 #[derive(Debug)]
 pub struct Keys<'a, B, T>(Entries<'a, B, T>);
@@ -480,6 +871,155 @@ where
     }
 }
 
+/// A view into a single entry of a [MapRef], returned by [MapRef::entry].
+pub enum MapEntry<'a, 'doc> {
+    /// Entry with a value present under its key.
+    Occupied(OccupiedMapEntry<'a, 'doc>),
+    /// Entry without a value, either because it was never set or it has been removed.
+    Vacant(VacantMapEntry<'a, 'doc>),
+}
+
+impl<'a, 'doc> MapEntry<'a, 'doc> {
+    /// Returns a key of a current entry.
+    pub fn key(&self) -> &str {
+        match self {
+            MapEntry::Occupied(e) => e.key(),
+            MapEntry::Vacant(e) => e.key(),
+        }
+    }
+
+    /// Inserts a given `value` if current entry is vacant. Returns a value of the entry.
+    pub fn or_insert<V: Prelim>(self, value: V) -> Out {
+        self.or_insert_with(|| value)
+    }
+
+    /// Inserts a value produced by a given function if current entry is vacant. Function is not
+    /// called if entry already has a value. Returns a value of the entry.
+    pub fn or_insert_with<V, F>(self, f: F) -> Out
+    where
+        V: Prelim,
+        F: FnOnce() -> V,
+    {
+        match self {
+            MapEntry::Occupied(e) => e.get(),
+            MapEntry::Vacant(VacantMapEntry { map, txn, key }) => {
+                map.insert(txn, key.clone(), f());
+                map.get(txn, &key).unwrap()
+            }
+        }
+    }
+
+    /// Calls a given function with an occupied entry, before any potential inserts.
+    pub fn and_modify<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce(&mut OccupiedMapEntry<'a, 'doc>),
+    {
+        if let MapEntry::Occupied(e) = &mut self {
+            f(e);
+        }
+        self
+    }
+}
+
+/// An occupied entry of a [MapRef]. It's a part of [MapEntry].
+pub struct OccupiedMapEntry<'a, 'doc> {
+    map: MapRef,
+    txn: &'a mut TransactionMut<'doc>,
+    key: Arc<str>,
+    item: ItemPtr,
+}
+
+impl<'a, 'doc> OccupiedMapEntry<'a, 'doc> {
+    /// Returns a key of a current entry.
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Returns a value of a current entry.
+    pub fn get(&self) -> Out {
+        self.item.content.get_last().unwrap()
+    }
+
+    /// Replaces a value of a current entry with a given one. Returns an integrated value.
+    pub fn insert<V: Prelim>(&mut self, value: V) -> V::Return {
+        let result = self.map.insert(self.txn, self.key.clone(), value);
+        self.item = self.map.0.map[&self.key];
+        result
+    }
+
+    /// Removes a current entry, returning its value.
+    pub fn remove(self) -> Out {
+        self.map.remove(self.txn, &self.key).unwrap()
+    }
+
+    /// Returns a transaction which current entry was obtained with.
+    pub fn txn(&mut self) -> &mut TransactionMut<'doc> {
+        self.txn
+    }
+}
+
+/// A vacant entry of a [MapRef]. It's a part of [MapEntry].
+pub struct VacantMapEntry<'a, 'doc> {
+    map: MapRef,
+    txn: &'a mut TransactionMut<'doc>,
+    key: Arc<str>,
+}
+
+impl<'a, 'doc> VacantMapEntry<'a, 'doc> {
+    /// Returns a key of a current entry.
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Sets a value of a current entry. Returns an integrated value.
+    pub fn insert<V: Prelim>(self, value: V) -> V::Return {
+        self.map.insert(self.txn, self.key, value)
+    }
+}
+
+/// Function resolving concurrent writes to the same key of a map. See [MapRef::set_resolver].
+pub type ConflictResolver = Arc<dyn Fn(&Any, &Any) -> Any + Send + Sync + 'static>;
+
+/// Resolves map entry conflicts recorded by a transaction, scheduling writes of the resolved
+/// values in a follow-up transaction.
+pub(crate) fn resolve_conflicts(txn: &mut TransactionMut) {
+    let conflicts = std::mem::take(&mut txn.map_conflicts);
+    let mut resolved: Vec<(BranchPtr, Arc<str>, Any, Any)> = Vec::new();
+    for (branch, key, lost) in conflicts {
+        let resolver = match txn.store.map_resolvers.get(&branch) {
+            Some(resolvers) => match resolvers.get(&key) {
+                Some(resolver) => resolver.clone(),
+                None => continue,
+            },
+            None => continue,
+        };
+        let entry = resolved
+            .iter_mut()
+            .find(|(b, k, _, _)| *b == branch && *k == key);
+        match entry {
+            Some((_, _, _, value)) => *value = resolver(value, &lost),
+            None => {
+                let current = match branch.map.get(&key) {
+                    Some(item) if !item.is_deleted() => match item.content.get_last() {
+                        Some(Out::Any(value)) => value,
+                        _ => continue,
+                    },
+                    _ => continue,
+                };
+                let value = resolver(&current, &lost);
+                resolved.push((branch, key, current, value));
+            }
+        }
+    }
+    for (branch, key, current, value) in resolved {
+        if value != current {
+            txn.defer(move |txn| {
+                MapRef::from(branch).insert(txn, key, value);
+            });
+        }
+    }
+}
+
 impl From<BranchPtr> for MapRef {
     fn from(inner: BranchPtr) -> Self {
         MapRef(inner)
@@ -620,13 +1160,15 @@ mod test {
     use crate::test_utils::{exchange_updates, run_scenario, RngExt};
     use crate::transaction::ReadTxn;
     use crate::types::text::TextPrelim;
-    use crate::types::{DeepObservable, EntryChange, Event, Out, Path, PathSegment, ToJson};
+    use crate::types::{
+        DeepObservable, EntryChange, Event, Out, Path, PathFilter, PathSegment, ToJson,
+    };
     use crate::updates::decoder::Decode;
     use crate::updates::encoder::{Encoder, EncoderV1};
     use crate::{
-        any, Any, Array, ArrayPrelim, ArrayRef, Doc, GetString, In, Map, MapPrelim, MapRef,
-        Observable, StateVector, Text, TextRef, Transact, Update, WriteTxn, XmlFragment,
-        XmlFragmentRef, XmlTextPrelim, XmlTextRef,
+        any, Any, Array, ArrayPrelim, ArrayRef, Doc, FixedLayout, GetString, In, Map, MapEntry,
+        MapPrelim, MapRef, Observable, Options, StateVector, Text, TextRef, Transact, Update,
+        WriteTxn, XmlFragment, XmlFragmentRef, XmlTextPrelim, XmlTextRef,
     };
     use arc_swap::ArcSwapOption;
     use fastrand::Rng;
@@ -758,6 +1300,57 @@ mod test {
         // remove 'other-stuff'
         assert_eq!(m1.remove(&mut t1, &key2), Some(Out::from("c1")));
         assert_eq!(m1.len(&t1), 0);
+        assert!(m1.is_empty(&t1));
+    }
+
+    #[test]
+    fn map_len_concurrent() {
+        fn live_entries(map: &MapRef) -> u32 {
+            let branch: &crate::branch::Branch = map.as_ref();
+            branch
+                .map
+                .values()
+                .filter(|item| !item.is_deleted())
+                .count() as u32
+        }
+
+        let d1 = Doc::with_client_id(1);
+        // removed nested map must not be garbage collected, as it's checked at the end
+        let d2 = Doc::with_options(Options {
+            skip_gc: true,
+            ..Options::with_client_id(2)
+        });
+        let m1 = d1.get_or_insert_map("map");
+        let m2 = d2.get_or_insert_map("map");
+
+        m1.insert(&mut d1.transact_mut(), "a", 1);
+        let nested = m1.insert(&mut d1.transact_mut(), "nested", MapPrelim::default());
+        nested.insert(&mut d1.transact_mut(), "x", 1);
+        exchange_updates(&[&d1, &d2]);
+
+        // concurrent updates and removals of the same keys
+        m1.insert(&mut d1.transact_mut(), "a", 2);
+        m1.insert(&mut d1.transact_mut(), "b", 1);
+        m2.remove(&mut d2.transact_mut(), "a");
+        m2.insert(&mut d2.transact_mut(), "b", 2);
+        m2.insert(&mut d2.transact_mut(), "c", 1);
+        let nested2 = m2
+            .get(&d2.transact(), "nested")
+            .unwrap()
+            .cast::<MapRef>()
+            .unwrap();
+        nested2.insert(&mut d2.transact_mut(), "y", 1);
+        m1.remove(&mut d1.transact_mut(), "nested");
+        exchange_updates(&[&d1, &d2]);
+
+        for (doc, map) in [(&d1, &m1), (&d2, &m2)] {
+            let txn = doc.transact();
+            assert_eq!(map.len(&txn), live_entries(map));
+            assert_eq!(map.len(&txn), 3);
+        }
+        // entries of a removed map are removed as well
+        assert_eq!(nested2.len(&d2.transact()), 0);
+        assert_eq!(nested2.len(&d2.transact()), live_entries(&nested2));
     }
 
     #[test]
@@ -1159,6 +1752,60 @@ mod test {
         );
     }
 
+    #[test]
+    fn observe_deep_filtered() {
+        let doc = Doc::with_client_id(1);
+        let map = doc.get_or_insert_map("map");
+        let users: ArrayRef = map.insert(&mut doc.transact_mut(), "users", ArrayPrelim::default());
+        let user: MapRef = users.push_back(&mut doc.transact_mut(), MapPrelim::default());
+        let other: MapRef = map.insert(&mut doc.transact_mut(), "other", MapPrelim::default());
+
+        let paths = Arc::new(Mutex::new(vec![]));
+        let paths_copy = paths.clone();
+        let filter = PathFilter::new().key("users").any().key("name");
+        let _sub = map.observe_deep_filtered(filter, move |_txn, e| {
+            let path: Vec<Path> = e.iter().map(Event::path).collect();
+            paths_copy.lock().unwrap().push(path);
+        });
+
+        // unrelated changes are filtered out
+        other.insert(&mut doc.transact_mut(), "name", "x");
+        map.insert(&mut doc.transact_mut(), "key", 1);
+        user.insert(&mut doc.transact_mut(), "age", 30);
+        assert!(paths.lock().unwrap().is_empty());
+
+        // changes at the filtered path
+        user.insert(&mut doc.transact_mut(), "name", "Alice");
+        let name: TextRef = user.insert(&mut doc.transact_mut(), "name", TextPrelim::new("B"));
+        name.push(&mut doc.transact_mut(), "ob");
+        // changes of ancestors
+        users.push_back(&mut doc.transact_mut(), 1);
+
+        let user_path = Path::from(vec![
+            PathSegment::Key("users".into()),
+            PathSegment::Index(0),
+        ]);
+        let mut name_path = user_path.clone();
+        name_path.push_back(PathSegment::Key("name".into()));
+        let users_path = Path::from(vec![PathSegment::Key("users".into())]);
+        assert_eq!(
+            paths.lock().unwrap().as_slice(),
+            &[
+                vec![user_path.clone()],
+                vec![user_path],
+                vec![name_path],
+                vec![users_path]
+            ]
+        );
+
+        let filter = PathFilter::from(Path::from(vec![PathSegment::Key("users".into())]));
+        assert!(filter.matches_path(&Path::from(vec![
+            PathSegment::Key("users".into()),
+            PathSegment::Index(3)
+        ])));
+        assert!(!filter.matches_path(&Path::from(vec![])));
+    }
+
     #[test]
     fn get_or_init() {
         let doc = Doc::with_client_id(1);
@@ -1191,6 +1838,61 @@ mod test {
         assert_eq!(m.get_string(&txn), "c".to_string());
     }
 
+    #[test]
+    fn entry() {
+        let doc = Doc::with_client_id(1);
+        let mut txn = doc.transact_mut();
+        let map = txn.get_or_insert_map("map");
+
+        match map.entry(&mut txn, "key") {
+            MapEntry::Vacant(e) => assert_eq!(e.key(), "key"),
+            MapEntry::Occupied(_) => panic!("expected vacant entry"),
+        }
+        assert_eq!(map.entry(&mut txn, "key").or_insert(1), Out::from(1));
+        let value = map
+            .entry(&mut txn, "key")
+            .or_insert_with(|| -> i32 { panic!("value should not be created") });
+        assert_eq!(value, Out::from(1));
+
+        let value = map
+            .entry(&mut txn, "key")
+            .and_modify(|e| {
+                assert_eq!(e.get(), Out::from(1));
+                e.insert(2);
+            })
+            .or_insert(3);
+        assert_eq!(value, Out::from(2));
+        assert_eq!(map.get(&txn, "key"), Some(Out::from(2)));
+
+        // removed entries are vacant
+        match map.entry(&mut txn, "key") {
+            MapEntry::Occupied(e) => assert_eq!(e.remove(), Out::from(2)),
+            MapEntry::Vacant(_) => panic!("expected occupied entry"),
+        }
+        assert!(matches!(map.entry(&mut txn, "key"), MapEntry::Vacant(_)));
+
+        // nested shared types
+        let nested = map.get_or_init_map(&mut txn, "map");
+        nested.insert(&mut txn, "a", 1);
+        let nested = map.get_or_init_map(&mut txn, "map");
+        assert_eq!(nested.get(&txn, "a"), Some(Out::from(1)));
+
+        let array = map.get_or_init_array(&mut txn, "array");
+        array.push_back(&mut txn, 1);
+        let array = map.get_or_init_array(&mut txn, "array");
+        assert_eq!(array.len(&txn), 1);
+
+        let text = map.get_or_init_text(&mut txn, "text");
+        text.push(&mut txn, "hello");
+        let text = map.get_or_init_text(&mut txn, "text");
+        assert_eq!(text.get_string(&txn), "hello");
+
+        let value = map
+            .entry(&mut txn, "text")
+            .or_insert_with(MapPrelim::default);
+        assert!(matches!(value, Out::YText(_)));
+    }
+
     #[test]
     fn try_update() {
         let doc = Doc::new();
@@ -1337,4 +2039,276 @@ mod test {
 
         assert!(value == 1.into() || value == 2.into())
     }
+
+    #[test]
+    fn increment() {
+        let d1 = Doc::with_client_id(1);
+        let m1 = d1.get_or_insert_map("map");
+        let d2 = Doc::with_client_id(2);
+        let m2 = d2.get_or_insert_map("map");
+
+        m1.insert(&mut d1.transact_mut(), "likes", 1);
+        assert_eq!(m1.increment(&mut d1.transact_mut(), "likes", 1.0), 2.0);
+        exchange_updates(&[&d1, &d2]);
+
+        // concurrent increments are summed up
+        assert_eq!(m1.increment(&mut d1.transact_mut(), "likes", 3.0), 5.0);
+        assert_eq!(m2.increment(&mut d2.transact_mut(), "likes", -0.5), 1.5);
+        exchange_updates(&[&d1, &d2]);
+
+        for (doc, map) in [(&d1, &m1), (&d2, &m2)] {
+            let txn = doc.transact();
+            assert_eq!(map.get(&txn, "likes"), Some(Out::from(4.5)));
+            assert_eq!(map.to_json(&txn), any!({"likes": 4.5}));
+        }
+    }
+
+    #[test]
+    fn insert_fixed_layout() {
+        #[derive(Debug, PartialEq)]
+        struct Transform {
+            x: f64,
+            y: f64,
+            w: f64,
+            h: f64,
+        }
+
+        impl FixedLayout for Transform {
+            const FIELDS: usize = 4;
+
+            fn write_fields(&self, fields: &mut [f64]) {
+                fields.copy_from_slice(&[self.x, self.y, self.w, self.h]);
+            }
+
+            fn read_fields(fields: &[f64]) -> Self {
+                Transform {
+                    x: fields[0],
+                    y: fields[1],
+                    w: fields[2],
+                    h: fields[3],
+                }
+            }
+        }
+
+        let d1 = Doc::with_client_id(1);
+        let m1 = d1.get_or_insert_map("shapes");
+        let d2 = Doc::with_client_id(2);
+        let m2 = d2.get_or_insert_map("shapes");
+
+        let mut t = Transform {
+            x: 1.5,
+            y: -2.0,
+            w: 100.0,
+            h: 50.0,
+        };
+        {
+            let mut txn = d1.transact_mut();
+            assert!(m1.insert_fixed(&mut txn, "rect", &t));
+            m1.insert(
+                &mut txn,
+                "other",
+                any!({"x": 1.5, "y": -2.0, "w": 100.0, "h": 50.0}),
+            );
+        }
+
+        // moving a shape writes only the fields that have changed
+        t.x = 2.5;
+        let fixed_len = {
+            let mut txn = d1.transact_mut();
+            assert!(m1.insert_fixed(&mut txn, "rect", &t));
+            txn.encode_update_v1().len()
+        };
+        let map_len = {
+            let mut txn = d1.transact_mut();
+            m1.insert(
+                &mut txn,
+                "other",
+                any!({"x": 2.5, "y": -2.0, "w": 100.0, "h": 50.0}),
+            );
+            txn.encode_update_v1().len()
+        };
+        assert!(fixed_len < map_len);
+
+        // overwriting with the same value doesn't produce any update
+        {
+            let mut txn = d1.transact_mut();
+            assert!(!m1.insert_fixed(&mut txn, "rect", &t));
+            assert!(txn.encode_update_v1().len() <= 2);
+        }
+
+        exchange_updates(&[&d1, &d2]);
+        let txn = d2.transact();
+        assert_eq!(m2.get_fixed(&txn, "rect"), Some(t));
+        assert_eq!(
+            m2.get(&txn, "rect"),
+            Some(Out::Any(any!([2.5, -2.0, 100.0, 50.0])))
+        );
+        // layout mismatch
+        assert_eq!(m2.get_fixed::<_, [f64; 5]>(&txn, "rect"), None);
+        assert_eq!(m2.get_fixed::<_, Transform>(&txn, "other"), None);
+    }
+
+    #[test]
+    fn history() {
+        let d1 = Doc::with_client_id(1);
+        let m1 = d1.get_or_insert_map("map");
+        let d2 = Doc::with_client_id(2);
+        let m2 = d2.get_or_insert_map("map");
+
+        // history is not kept by default
+        m1.insert(&mut d1.transact_mut(), "a", 1);
+        m1.insert(&mut d1.transact_mut(), "a", 2);
+        assert!(m1.history(&d1.transact(), "a").is_empty());
+
+        m1.set_history_limit(&mut d1.transact_mut(), 3);
+        for i in 3..=6 {
+            m1.insert(&mut d1.transact_mut(), "a", i);
+        }
+        {
+            let txn = d1.transact();
+            assert_eq!(m1.get(&txn, "a"), Some(6.into()));
+            assert_eq!(m1.history(&txn, "a"), vec![5.into(), 4.into(), 3.into()]);
+            assert!(m1.history(&txn, "b").is_empty());
+        }
+
+        // changes made by remote peers and removed keys
+        exchange_updates(&[&d1, &d2]);
+        // values are only retained if they have been seen by a current document: peers not
+        // keeping the history send overwritten values already garbage collected
+        m2.insert(&mut d2.transact_mut(), "a", "remote");
+        exchange_updates(&[&d1, &d2]);
+        m2.remove(&mut d2.transact_mut(), "a");
+        exchange_updates(&[&d1, &d2]);
+        {
+            let txn = d1.transact();
+            assert_eq!(m1.get(&txn, "a"), None);
+            assert_eq!(
+                m1.history(&txn, "a"),
+                vec!["remote".into(), 6.into(), 5.into()]
+            );
+        }
+        // other peer doesn't keep the history
+        assert!(m2.history(&d2.transact(), "a").is_empty());
+
+        // values beyond the limit are garbage collected
+        m1.set_history_limit(&mut d1.transact_mut(), 1);
+        m1.insert(&mut d1.transact_mut(), "a", 7);
+        assert_eq!(m1.history(&d1.transact(), "a"), vec!["remote".into()]);
+        m1.set_history_limit(&mut d1.transact_mut(), 3);
+        assert_eq!(m1.history(&d1.transact(), "a"), vec!["remote".into()]);
+
+        // history of nested maps
+        let nested = m1.insert(&mut d1.transact_mut(), "nested", MapPrelim::default());
+        nested.set_history_limit(&mut d1.transact_mut(), 2);
+        {
+            let mut txn = d1.transact_mut();
+            nested.insert(&mut txn, "x", "a");
+            nested.insert(&mut txn, "x", "b");
+            nested.insert(&mut txn, "x", "c");
+        }
+        assert_eq!(
+            nested.history(&d1.transact(), "x"),
+            vec!["b".into(), "a".into()]
+        );
+
+        // disabling history mode
+        m1.set_history_limit(&mut d1.transact_mut(), 0);
+        assert!(m1.history(&d1.transact(), "a").is_empty());
+        m1.insert(&mut d1.transact_mut(), "a", 8);
+        assert_eq!(m1.get(&d1.transact(), "a"), Some(8.into()));
+
+        exchange_updates(&[&d1, &d2]);
+        assert_eq!(m1.to_json(&d1.transact()), m2.to_json(&d2.transact()));
+    }
+
+    #[test]
+    fn to_json_writer() {
+        let doc = Doc::with_client_id(1);
+        let map = doc.get_or_insert_map("map");
+        let xml = doc.get_or_insert_xml_fragment("xml");
+        let mut txn = doc.transact_mut();
+        map.insert(&mut txn, "number", 1.5);
+        map.insert(&mut txn, "quote", "say \"hi\"\n");
+        let array = map.insert(&mut txn, "array", ArrayPrelim::from([1, 2]));
+        array.push_back(&mut txn, MapPrelim::from([("nested", true)]));
+        let text = map.insert(&mut txn, "text", TextPrelim::new("hello"));
+        text.push(&mut txn, " world");
+        xml.push_back(&mut txn, XmlTextPrelim::new("<a href=\"b\">"));
+        map.insert(&mut txn, "removed", "x");
+        map.remove(&mut txn, "removed");
+
+        let mut buf = Vec::new();
+        map.to_json_writer(&txn, &mut buf).unwrap();
+        let json = Any::from_json(std::str::from_utf8(&buf).unwrap()).unwrap();
+        assert_eq!(json, map.to_json(&txn));
+
+        let mut buf = Vec::new();
+        xml.to_json_writer(&txn, &mut buf).unwrap();
+        let json = Any::from_json(std::str::from_utf8(&buf).unwrap()).unwrap();
+        assert_eq!(json, xml.to_json(&txn));
+
+        let mut buf = Vec::new();
+        Out::YArray(array.clone())
+            .to_json_writer(&txn, &mut buf)
+            .unwrap();
+        assert_eq!(
+            std::str::from_utf8(&buf).unwrap(),
+            r#"[1,2,{"nested":true}]"#
+        );
+    }
+
+    #[test]
+    fn conflict_resolver() {
+        fn keep_max(a: &Any, b: &Any) -> Any {
+            match (a, b) {
+                (Any::Number(x), Any::Number(y)) => Any::Number(x.max(*y)),
+                _ => a.clone(),
+            }
+        }
+
+        // the greater value wins no matter which peer has written it
+        for (v1, v2) in [(3, 5), (5, 3)] {
+            let d1 = Doc::with_client_id(1);
+            let d2 = Doc::with_client_id(2);
+            let m1 = d1.get_or_insert_map("map");
+            let m2 = d2.get_or_insert_map("map");
+            m1.set_resolver(&mut d1.transact_mut(), "max", keep_max);
+            m2.set_resolver(&mut d2.transact_mut(), "max", keep_max);
+
+            {
+                let mut t1 = d1.transact_mut();
+                m1.insert(&mut t1, "max", v1);
+                m1.insert(&mut t1, "other", v1);
+            }
+            {
+                let mut t2 = d2.transact_mut();
+                m2.insert(&mut t2, "max", v2);
+                m2.insert(&mut t2, "other", v2);
+            }
+            exchange_updates(&[&d1, &d2]);
+            exchange_updates(&[&d1, &d2]);
+
+            let t1 = d1.transact();
+            let t2 = d2.transact();
+            assert_eq!(m1.get(&t1, "max"), Some(Out::from(5.0)));
+            assert_eq!(m2.get(&t2, "max"), Some(Out::from(5.0)));
+            // keys without resolver keep last-writer-wins semantics
+            assert_eq!(m1.get(&t1, "other"), m2.get(&t2, "other"));
+        }
+
+        // sequential overwrites are not conflicts
+        let d1 = Doc::with_client_id(1);
+        let d2 = Doc::with_client_id(2);
+        let m1 = d1.get_or_insert_map("map");
+        let m2 = d2.get_or_insert_map("map");
+        m2.set_resolver(&mut d2.transact_mut(), "max", keep_max);
+        m1.insert(&mut d1.transact_mut(), "max", 5);
+        exchange_updates(&[&d1, &d2]);
+        m1.insert(&mut d1.transact_mut(), "max", 1);
+        exchange_updates(&[&d1, &d2]);
+        assert_eq!(m2.get(&d2.transact(), "max"), Some(Out::from(1.0)));
+
+        assert!(m2.remove_resolver(&mut d2.transact_mut(), "max"));
+        assert!(!m2.remove_resolver(&mut d2.transact_mut(), "max"));
+    }
 }
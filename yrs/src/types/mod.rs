@@ -12,11 +12,13 @@ pub use text::Text;
 pub use text::TextRef;
 
 use crate::block::{Item, ItemContent, ItemPtr, Prelim};
-use crate::branch::{Branch, BranchPtr};
+use crate::branch::{Branch, BranchPtr, FilteredDeepObserver};
 use crate::encoding::read::Error;
 use crate::transaction::TransactionMut;
 use crate::types::array::{ArrayEvent, ArrayRef};
+use crate::types::counter::CounterEvent;
 use crate::types::map::MapEvent;
+use crate::types::set::{SetEvent, SetOut};
 use crate::types::text::TextEvent;
 #[cfg(feature = "weak")]
 use crate::types::weak::{LinkSource, WeakEvent, WeakRef};
@@ -26,11 +28,19 @@ use crate::updates::encoder::{Encode, Encoder};
 use crate::*;
 
 pub mod array;
+pub mod counter;
+pub mod fixed;
+pub mod input_edit;
 pub mod map;
+pub mod set;
 pub mod text;
+pub mod throttle;
+pub mod typed_map;
 #[cfg(feature = "weak")]
 pub mod weak;
 pub mod xml;
+mod xml_markup;
+pub mod xml_schema;
 
 /// Type ref identifier for an [ArrayRef] type.
 pub const TYPE_REFS_ARRAY: u8 = 0;
@@ -56,9 +66,22 @@ pub const TYPE_REFS_XML_TEXT: u8 = 6;
 /// Type ref identifier for a [WeakRef] type.
 pub const TYPE_REFS_WEAK: u8 = 7;
 
+/// Type ref identifier for a [CounterRef] type, also used by [Map::increment]. It's not supported
+/// by Yjs.
+pub const TYPE_REFS_COUNTER: u8 = 8;
+
 /// Type ref identifier for a [DocRef] type.
 pub const TYPE_REFS_DOC: u8 = 9;
 
+/// Type ref identifier for a [GSetRef] type. It's not supported by Yjs.
+pub const TYPE_REFS_GSET: u8 = 10;
+
+/// Type ref identifier for a [TwoPhaseSetRef] type. It's not supported by Yjs.
+pub const TYPE_REFS_TWO_PHASE_SET: u8 = 11;
+
+/// Type ref identifier for a [FixedRef] type. It's not supported by Yjs.
+pub const TYPE_REFS_FIXED: u8 = 12;
+
 /// Placeholder type ref identifier for non-specialized AbstractType. Used only for root-level types
 /// which have been integrated from remote peers before they were defined locally.
 pub const TYPE_REFS_UNDEFINED: u8 = 15;
@@ -76,6 +99,10 @@ pub enum TypeRef {
     SubDoc = TYPE_REFS_DOC,
     #[cfg(feature = "weak")]
     WeakLink(Arc<LinkSource>) = TYPE_REFS_WEAK,
+    Counter = TYPE_REFS_COUNTER,
+    GSet = TYPE_REFS_GSET,
+    TwoPhaseSet = TYPE_REFS_TWO_PHASE_SET,
+    Fixed = TYPE_REFS_FIXED,
     Undefined = TYPE_REFS_UNDEFINED,
 }
 
@@ -92,6 +119,10 @@ impl TypeRef {
             TypeRef::SubDoc => TYPE_REFS_DOC,
             #[cfg(feature = "weak")]
             TypeRef::WeakLink(_) => TYPE_REFS_WEAK,
+            TypeRef::Counter => TYPE_REFS_COUNTER,
+            TypeRef::GSet => TYPE_REFS_GSET,
+            TypeRef::TwoPhaseSet => TYPE_REFS_TWO_PHASE_SET,
+            TypeRef::Fixed => TYPE_REFS_FIXED,
             TypeRef::Undefined => TYPE_REFS_UNDEFINED,
         }
     }
@@ -110,6 +141,10 @@ impl std::fmt::Display for TypeRef {
             TypeRef::SubDoc => write!(f, "Doc"),
             #[cfg(feature = "weak")]
             TypeRef::WeakLink(_) => write!(f, "WeakRef"),
+            TypeRef::Counter => write!(f, "Counter"),
+            TypeRef::GSet => write!(f, "GSet"),
+            TypeRef::TwoPhaseSet => write!(f, "TwoPhaseSet"),
+            TypeRef::Fixed => write!(f, "Fixed"),
             TypeRef::Undefined => write!(f, "(undefined)"),
         }
     }
@@ -152,6 +187,10 @@ impl Encode for TypeRef {
                     encoder.write_var(end.clock);
                 }
             }
+            TypeRef::Counter => encoder.write_type_ref(TYPE_REFS_COUNTER),
+            TypeRef::GSet => encoder.write_type_ref(TYPE_REFS_GSET),
+            TypeRef::TwoPhaseSet => encoder.write_type_ref(TYPE_REFS_TWO_PHASE_SET),
+            TypeRef::Fixed => encoder.write_type_ref(TYPE_REFS_FIXED),
             TypeRef::Undefined => encoder.write_type_ref(TYPE_REFS_UNDEFINED),
         }
     }
@@ -193,6 +232,10 @@ impl Decode for TypeRef {
                 let end = StickyIndex::from_id(end_id, end_assoc);
                 Ok(TypeRef::WeakLink(Arc::new(LinkSource::new(start, end))))
             }
+            TYPE_REFS_COUNTER => Ok(TypeRef::Counter),
+            TYPE_REFS_GSET => Ok(TypeRef::GSet),
+            TYPE_REFS_TWO_PHASE_SET => Ok(TypeRef::TwoPhaseSet),
+            TYPE_REFS_FIXED => Ok(TypeRef::Fixed),
             TYPE_REFS_UNDEFINED => Ok(TypeRef::Undefined),
             _ => Err(Error::UnexpectedValue),
         }
@@ -246,7 +289,22 @@ pub trait Observable: AsRef<Branch> {
         })
     }
 
-    /// Unsubscribes a given callback identified by key, that was previously subscribed using [Self::observe_with].
+    /// Subscribes a given callback to be triggered whenever current y-type is changed, just like
+    /// [Self::observe]. Callback stays subscribed until it's explicitly unsubscribed with
+    /// [Self::unobserve] called with returned key, so there's no need to keep any subscription
+    /// handle alive.
+    fn observe_forever<F>(&self, f: F) -> Origin
+    where
+        F: Fn(&TransactionMut, &Self::Event) + Send + Sync + 'static,
+        Event: AsRef<Self::Event>,
+    {
+        let key = crate::observer::new_key();
+        self.observe_with(key.clone(), f);
+        key
+    }
+
+    /// Unsubscribes a given callback identified by key, that was previously subscribed using
+    /// [Self::observe_with] or [Self::observe_forever].
     fn unobserve<K: Into<Origin>>(&self, key: K) -> bool {
         let mut branch = BranchPtr::from(self.as_ref());
         branch.unobserve(&key.into())
@@ -300,7 +358,22 @@ pub trait Observable: AsRef<Branch> {
         })
     }
 
-    /// Unsubscribes a given callback identified by key, that was previously subscribed using [Self::observe_with].
+    /// Subscribes a given callback to be triggered whenever current y-type is changed, just like
+    /// [Self::observe]. Callback stays subscribed until it's explicitly unsubscribed with
+    /// [Self::unobserve] called with returned key, so there's no need to keep any subscription
+    /// handle alive.
+    fn observe_forever<F>(&self, f: F) -> Origin
+    where
+        F: Fn(&TransactionMut, &Self::Event) + 'static,
+        Event: AsRef<Self::Event>,
+    {
+        let key = crate::observer::new_key();
+        self.observe_with(key.clone(), f);
+        key
+    }
+
+    /// Unsubscribes a given callback identified by key, that was previously subscribed using
+    /// [Self::observe_with] or [Self::observe_forever].
     fn unobserve<K: Into<Origin>>(&self, key: K) -> bool {
         let mut branch = BranchPtr::from(self.as_ref());
         branch.unobserve(&key.into())
@@ -404,12 +477,69 @@ pub trait DeepObservable: AsRef<Branch> {
             .subscribe_with(key.into(), Box::new(f))
     }
 
+    /// Subscribe a callback `f` for all events emitted by this and nested collaborative types,
+    /// just like [Self::observe_deep]. Callback stays subscribed until it's explicitly
+    /// unsubscribed with [Self::unobserve_deep] called with returned key, so there's no need to
+    /// keep any subscription handle alive.
+    fn observe_deep_forever<F>(&self, f: F) -> Origin
+    where
+        F: Fn(&TransactionMut, &Events) + Send + Sync + 'static,
+    {
+        let branch = self.as_ref();
+        branch.deep_observers.subscribe_forever(Box::new(f))
+    }
+
     /// Unsubscribe a callback identified by a given key, that was previously subscribed using
-    /// [Self::observe_deep_with].
+    /// [Self::observe_deep_with] or [Self::observe_deep_forever].
     fn unobserve_deep<K: Into<Origin>>(&self, key: K) -> bool {
         let branch = self.as_ref();
         branch.deep_observers.unsubscribe(&key.into())
     }
+
+    /// Subscribe a callback `f` for events emitted by this and nested collaborative types, which
+    /// match a given path `filter`. Events are matched before being passed to callback, so
+    /// changes made outside of the filtered paths don't trigger it at all. See [PathFilter] for
+    /// matching rules.
+    ///
+    /// This method returns a subscription, which will automatically unsubscribe current callback
+    /// when dropped.
+    fn observe_deep_filtered<F>(&self, filter: PathFilter, f: F) -> Subscription
+    where
+        F: Fn(&TransactionMut, &Events) + Send + Sync + 'static,
+    {
+        let branch = self.as_ref();
+        branch
+            .filtered_deep_observers
+            .subscribe(FilteredDeepObserver::new(filter, Box::new(f)))
+    }
+
+    /// Asynchronous counterpart of [Self::observe_deep]. Events are captured on transaction
+    /// commit as owned [BatchedEvent]s - one per changed collection - and passed to `f` by
+    /// a returned [ObserverTask], which needs to be spawned on an async runtime. Futures returned
+    /// by `f` are awaited one at a time, in the order of commits.
+    ///
+    /// This method returns a subscription, which will automatically unsubscribe current callback
+    /// when dropped. Task completes once the subscription has been dropped and all queued events
+    /// have been processed.
+    ///
+    /// [BatchedEvent]: crate::batch::BatchedEvent
+    /// [ObserverTask]: crate::stream::ObserverTask
+    #[cfg(feature = "async")]
+    fn observe_deep_async<F, Fut>(&self, f: F) -> (Subscription, crate::stream::ObserverTask)
+    where
+        F: Fn(Vec<crate::batch::BatchedEvent>) -> Fut + Send + 'static,
+        Fut: std::future::Future<Output = ()> + Send + 'static,
+    {
+        let (sender, task) = crate::stream::ObserverTask::new(f);
+        let subscription = self.observe_deep(move |txn, events| {
+            let events = events
+                .iter()
+                .filter_map(|e| crate::batch::BatchedEvent::from_event(txn, e))
+                .collect();
+            sender.send(events);
+        });
+        (subscription, task)
+    }
 }
 
 /// Trait implemented by all Y-types, allowing for observing events which are emitted by
@@ -455,12 +585,69 @@ pub trait DeepObservable: AsRef<Branch> {
             .subscribe_with(key.into(), Box::new(f))
     }
 
+    /// Subscribe a callback `f` for all events emitted by this and nested collaborative types,
+    /// just like [Self::observe_deep]. Callback stays subscribed until it's explicitly
+    /// unsubscribed with [Self::unobserve_deep] called with returned key, so there's no need to
+    /// keep any subscription handle alive.
+    fn observe_deep_forever<F>(&self, f: F) -> Origin
+    where
+        F: Fn(&TransactionMut, &Events) + 'static,
+    {
+        let branch = self.as_ref();
+        branch.deep_observers.subscribe_forever(Box::new(f))
+    }
+
     /// Unsubscribe a callback identified by a given key, that was previously subscribed using
-    /// [Self::observe_deep_with].
+    /// [Self::observe_deep_with] or [Self::observe_deep_forever].
     fn unobserve_deep<K: Into<Origin>>(&self, key: K) -> bool {
         let branch = self.as_ref();
         branch.deep_observers.unsubscribe(&key.into())
     }
+
+    /// Subscribe a callback `f` for events emitted by this and nested collaborative types, which
+    /// match a given path `filter`. Events are matched before being passed to callback, so
+    /// changes made outside of the filtered paths don't trigger it at all. See [PathFilter] for
+    /// matching rules.
+    ///
+    /// This method returns a subscription, which will automatically unsubscribe current callback
+    /// when dropped.
+    fn observe_deep_filtered<F>(&self, filter: PathFilter, f: F) -> Subscription
+    where
+        F: Fn(&TransactionMut, &Events) + 'static,
+    {
+        let branch = self.as_ref();
+        branch
+            .filtered_deep_observers
+            .subscribe(FilteredDeepObserver::new(filter, Box::new(f)))
+    }
+
+    /// Asynchronous counterpart of [Self::observe_deep]. Events are captured on transaction
+    /// commit as owned [BatchedEvent]s - one per changed collection - and passed to `f` by
+    /// a returned [ObserverTask], which needs to be spawned on an async runtime. Futures returned
+    /// by `f` are awaited one at a time, in the order of commits.
+    ///
+    /// This method returns a subscription, which will automatically unsubscribe current callback
+    /// when dropped. Task completes once the subscription has been dropped and all queued events
+    /// have been processed.
+    ///
+    /// [BatchedEvent]: crate::batch::BatchedEvent
+    /// [ObserverTask]: crate::stream::ObserverTask
+    #[cfg(feature = "async")]
+    fn observe_deep_async<F, Fut>(&self, f: F) -> (Subscription, crate::stream::ObserverTask)
+    where
+        F: Fn(Vec<crate::batch::BatchedEvent>) -> Fut + Send + 'static,
+        Fut: std::future::Future<Output = ()> + Send + 'static,
+    {
+        let (sender, task) = crate::stream::ObserverTask::new(f);
+        let subscription = self.observe_deep(move |txn, events| {
+            let events = events
+                .iter()
+                .filter_map(|e| crate::batch::BatchedEvent::from_event(txn, e))
+                .collect();
+            sender.send(events);
+        });
+        (subscription, task)
+    }
 }
 
 impl std::fmt::Display for Branch {
@@ -545,6 +732,15 @@ impl std::fmt::Display for Branch {
                     write!(f, "WeakRef({}..{})", w.quote_start, w.quote_end)
                 }
             }
+            TypeRef::Counter => {
+                write!(f, "Counter({})", counter::counter_value(self))
+            }
+            TypeRef::GSet | TypeRef::TwoPhaseSet => {
+                write!(f, "{}({})", self.type_ref, set::set_value(self))
+            }
+            TypeRef::Fixed => {
+                write!(f, "Fixed({})", fixed::fixed_value(self))
+            }
             TypeRef::Undefined => {
                 write!(f, "UnknownRef")?;
                 if let Some(start) = self.start.as_ref() {
@@ -717,6 +913,141 @@ impl Serialize for PathSegment {
     }
 }
 
+/// A pattern matched against paths of events emitted by shared types nested inside of
+/// an observed collection (see: [DeepObservable::observe_deep_filtered]). Filter consists of
+/// segments, each matching a single map key, array index or - in case of a wildcard - any
+/// segment of an event [Path].
+///
+/// An event matches a filter when:
+/// - it was emitted by a shared type living at or under any path matched by the filter, or
+/// - it was emitted by one of the shared types on the way to the filtered path and its changes
+///   may affect it, i.e. a map event modifying a key used by the filter.
+///
+/// # Example
+///
+/// ```rust
+/// use yrs::types::PathFilter;
+///
+/// // matches changes of `name` entry of any element of `users` array
+/// let filter = PathFilter::new().key("users").any().key("name");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PathFilter {
+    segments: Vec<Option<PathSegment>>,
+}
+
+impl PathFilter {
+    /// Creates a new filter, which matches all events.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Extends current filter with a segment matching a given map `key`.
+    pub fn key<K: Into<Arc<str>>>(mut self, key: K) -> Self {
+        self.segments.push(Some(PathSegment::Key(key.into())));
+        self
+    }
+
+    /// Extends current filter with a segment matching a given array `index`.
+    pub fn index(mut self, index: u32) -> Self {
+        self.segments.push(Some(PathSegment::Index(index)));
+        self
+    }
+
+    /// Extends current filter with a wildcard segment matching any key or index.
+    pub fn any(mut self) -> Self {
+        self.segments.push(None);
+        self
+    }
+
+    /// Checks if a given event [Path] lies at or under any of the paths matched by this filter.
+    pub fn matches_path(&self, path: &Path) -> bool {
+        path.len() >= self.segments.len()
+            && self
+                .segments
+                .iter()
+                .zip(path.iter())
+                .all(|(filter, segment)| Self::matches_segment(filter, segment))
+    }
+
+    fn matches_segment(filter: &Option<PathSegment>, segment: &PathSegment) -> bool {
+        match filter {
+            None => true,
+            Some(filter) => filter == segment,
+        }
+    }
+
+    /// Checks if an event, which path leads from `current_target` to `target` is matched by this
+    /// filter. Path segments are computed on the fly, without allocating a [Path].
+    pub(crate) fn matches(&self, txn: &TransactionMut, event: &Event) -> bool {
+        if self.segments.is_empty() {
+            return true;
+        }
+        let (from, to) = event.branches();
+        let mut depth = 0;
+        let mut child = to;
+        while let Some(item) = child.item {
+            if from.item == child.item {
+                break;
+            }
+            depth += 1;
+            child = *item.parent.as_branch().unwrap();
+        }
+
+        let mut level = depth;
+        let mut child = to;
+        while level > 0 {
+            level -= 1;
+            let item = child.item.unwrap();
+            let parent = *item.parent.as_branch().unwrap();
+            if let Some(filter) = self.segments.get(level) {
+                let matches = match (filter, &item.parent_sub) {
+                    (None, _) => true,
+                    (Some(PathSegment::Key(key)), Some(sub)) => key == sub,
+                    (Some(PathSegment::Index(index)), None) => {
+                        let mut i = 0;
+                        let mut c = parent.start;
+                        while let Some(ptr) = c {
+                            if ptr.id() == item.id() {
+                                break;
+                            }
+                            if !ptr.is_deleted() && ptr.is_countable() {
+                                i += ptr.len();
+                            }
+                            c = ptr.right;
+                        }
+                        *index == i
+                    }
+                    _ => false,
+                };
+                if !matches {
+                    return false;
+                }
+            }
+            child = parent;
+        }
+
+        if depth >= self.segments.len() {
+            // change happened at or under the filtered path
+            true
+        } else {
+            // change happened in one of the ancestors of the filtered path
+            match (&self.segments[depth], event) {
+                (Some(PathSegment::Key(key)), Event::Map(e)) => e.keys(txn).contains_key(key),
+                _ => true,
+            }
+        }
+    }
+}
+
+impl From<Path> for PathFilter {
+    fn from(path: Path) -> Self {
+        PathFilter {
+            segments: path.into_iter().map(Some).collect(),
+        }
+    }
+}
+
 pub(crate) struct ChangeSet<D> {
     added: HashSet<ID>,
     deleted: HashSet<ID>,
@@ -1028,8 +1359,8 @@ pub(crate) fn event_change_set(txn: &TransactionMut, start: Option<ItemPtr>) ->
 pub struct Events<'a>(Vec<&'a Event>);
 
 impl<'a> Events<'a> {
-    pub(crate) fn new(events: &Vec<&'a Event>) -> Self {
-        let mut events = events.clone();
+    pub(crate) fn new(events: &[&'a Event]) -> Self {
+        let mut events = events.to_vec();
         events.sort_by(|&a, &b| {
             let path1 = a.path();
             let path2 = b.path();
@@ -1073,6 +1404,8 @@ pub enum Event {
     XmlText(XmlTextEvent),
     #[cfg(feature = "weak")]
     Weak(WeakEvent),
+    Counter(CounterEvent),
+    Set(SetEvent),
 }
 
 impl AsRef<TextEvent> for Event {
@@ -1136,6 +1469,26 @@ impl AsRef<WeakEvent> for Event {
     }
 }
 
+impl AsRef<CounterEvent> for Event {
+    fn as_ref(&self) -> &CounterEvent {
+        if let Event::Counter(e) = self {
+            e
+        } else {
+            panic!("subscribed callback expected CounterRef collection");
+        }
+    }
+}
+
+impl AsRef<SetEvent> for Event {
+    fn as_ref(&self) -> &SetEvent {
+        if let Event::Set(e) = self {
+            e
+        } else {
+            panic!("subscribed callback expected set collection");
+        }
+    }
+}
+
 impl Event {
     pub(crate) fn set_current_target(&mut self, target: BranchPtr) {
         match self {
@@ -1146,6 +1499,28 @@ impl Event {
             Event::XmlFragment(e) => e.current_target = target,
             #[cfg(feature = "weak")]
             Event::Weak(e) => e.current_target = target,
+            Event::Counter(e) => e.current_target = target,
+            Event::Set(e) => e.current_target = target,
+        }
+    }
+
+    /// Returns an observed collection which triggered callback (see:
+    /// [Event::set_current_target]) together with a shared type which emitted current event.
+    pub(crate) fn branches(&self) -> (BranchPtr, BranchPtr) {
+        fn ptr<B: AsRef<Branch>>(branch: &B) -> BranchPtr {
+            BranchPtr::from(branch.as_ref())
+        }
+
+        match self {
+            Event::Text(e) => (e.current_target, ptr(e.target())),
+            Event::Array(e) => (e.current_target, ptr(e.target())),
+            Event::Map(e) => (e.current_target, ptr(e.target())),
+            Event::XmlText(e) => (e.current_target, ptr(e.target())),
+            Event::XmlFragment(e) => (e.current_target, ptr(e.target())),
+            #[cfg(feature = "weak")]
+            Event::Weak(e) => (e.current_target, e.target),
+            Event::Counter(e) => (e.current_target, ptr(e.target())),
+            Event::Set(e) => (e.current_target, e.target),
         }
     }
 
@@ -1160,10 +1535,14 @@ impl Event {
             Event::XmlFragment(e) => e.path(),
             #[cfg(feature = "weak")]
             Event::Weak(e) => e.path(),
+            Event::Counter(e) => e.path(),
+            Event::Set(e) => e.path(),
         }
     }
 
-    /// Returns a shared data types which triggered current [Event].
+    /// Returns a shared data types which triggered current [Event]. Counters and sets are returned
+    /// as their current value - use [CounterEvent::target] or [SetEvent::target] to access
+    /// a collection itself.
     pub fn target(&self) -> Out {
         match self {
             Event::Text(e) => Out::YText(e.target().clone()),
@@ -1177,6 +1556,11 @@ impl Event {
             },
             #[cfg(feature = "weak")]
             Event::Weak(e) => Out::YWeakLink(e.as_target().clone()),
+            Event::Counter(e) => Out::Any(Any::Number(counter::counter_value(e.target().as_ref()))),
+            Event::Set(e) => match e.target() {
+                SetOut::GSet(set) => Out::Any(set::set_value(set.as_ref())),
+                SetOut::TwoPhaseSet(set) => Out::Any(set::set_value(set.as_ref())),
+            },
         }
     }
 }
@@ -1184,4 +1568,16 @@ impl Event {
 pub trait ToJson {
     /// Converts all contents of a current type into a JSON-like representation.
     fn to_json<T: ReadTxn>(&self, txn: &T) -> Any;
+
+    /// Serializes all contents of a current type as JSON directly into a given `writer`, producing
+    /// the same output as serializing the result of [ToJson::to_json], but without building
+    /// an intermediate [Any] tree for nested shared collections.
+    fn to_json_writer<T: ReadTxn, W: std::io::Write>(
+        &self,
+        txn: &T,
+        writer: &mut W,
+    ) -> std::io::Result<()> {
+        serde_json::to_writer(writer, &self.to_json(txn))?;
+        Ok(())
+    }
 }
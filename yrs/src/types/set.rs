@@ -0,0 +1,586 @@
+//! Set shared types.
+//!
+//! Sets store unique [Any] values. Elements are kept as entries of a branch's map component, keyed
+//! by a canonical representation of their value, so that the same element inserted concurrently
+//! by different peers is only stored once. Two flavors are available:
+//!
+//! - [GSetRef] is a grow-only set. Once inserted, elements can never be removed.
+//! - [TwoPhaseSetRef] allows elements to be removed, but removal is permanent: an element which
+//!   has been removed cannot be inserted again. Removal always wins over concurrent insertion.
+//!
+//! Sets are not supported by Yjs.
+
+use crate::block::{ItemContent, ItemPtr, Prelim};
+use crate::branch::{Branch, BranchPtr};
+use crate::transaction::TransactionMut;
+use crate::types::{
+    event_keys, DeepObservable, EntryChange, Observable, Path, RootRef, SharedRef, ToJson, TypeRef,
+};
+use crate::{Any, Map, MapRef, Out, ReadTxn};
+use std::cell::UnsafeCell;
+use std::collections::{BTreeMap, HashSet};
+use std::convert::TryFrom;
+use std::fmt::Write;
+use std::ops::Deref;
+use std::sync::Arc;
+
+/// Prefix of the keys used by [TwoPhaseSetRef] to store removed elements. It never collides with
+/// canonical representation of an element.
+const TOMBSTONE: char = '~';
+
+/// Common methods of set shared types.
+pub trait Set: AsRef<Branch> + Sized {
+    /// Inserts a new `element` into a current set. Returns `true` if the element was not present
+    /// in the set before.
+    ///
+    /// For [TwoPhaseSetRef] elements that have been removed cannot be inserted again - in such
+    /// case `false` is returned and the set is not modified.
+    fn insert<V: Into<Any>>(&self, txn: &mut TransactionMut, element: V) -> bool {
+        let element = element.into();
+        let key = element_key(&element);
+        let branch = self.as_ref();
+        if is_present(branch, &key) || is_removed(branch, &key) {
+            return false;
+        }
+        let map = MapRef::from(BranchPtr::from(branch));
+        map.insert(txn, key, element);
+        true
+    }
+
+    /// Checks if a given `element` is present in a current set.
+    fn contains<T: ReadTxn>(&self, _txn: &T, element: &Any) -> bool {
+        let key = element_key(element);
+        let branch = self.as_ref();
+        is_present(branch, &key) && !is_removed(branch, &key)
+    }
+
+    /// Returns a number of elements stored in a current set.
+    fn len<T: ReadTxn>(&self, txn: &T) -> u32 {
+        self.iter(txn).count() as u32
+    }
+
+    /// Checks if a current set has no elements.
+    fn is_empty<T: ReadTxn>(&self, txn: &T) -> bool {
+        self.iter(txn).next().is_none()
+    }
+
+    /// Returns an iterator over the elements of a current set. Elements are ordered by their
+    /// canonical representation, which is the same on every peer.
+    fn iter<'a, T: ReadTxn + 'a>(&'a self, _txn: &'a T) -> SetIter<'a> {
+        SetIter::new(self.as_ref())
+    }
+}
+
+/// Iterator over the elements of a [Set].
+pub struct SetIter<'a> {
+    branch: &'a Branch,
+    keys: std::vec::IntoIter<&'a Arc<str>>,
+}
+
+impl<'a> SetIter<'a> {
+    fn new(branch: &'a Branch) -> Self {
+        let mut keys: Vec<_> = branch
+            .map
+            .iter()
+            .filter(|(key, item)| !item.is_deleted() && !key.starts_with(TOMBSTONE))
+            .map(|(key, _)| key)
+            .collect();
+        keys.sort();
+        SetIter {
+            branch,
+            keys: keys.into_iter(),
+        }
+    }
+}
+
+impl<'a> Iterator for SetIter<'a> {
+    type Item = Any;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let key = self.keys.next()?;
+            if !is_removed(self.branch, key) {
+                return self
+                    .branch
+                    .map
+                    .get(key)
+                    .and_then(|item| element(&item.content));
+            }
+        }
+    }
+}
+
+/// A grow-only set. Elements can be inserted, but never removed. Concurrent insertions of the same
+/// element are merged.
+///
+/// # Example
+///
+/// ```rust
+/// use yrs::{Any, Doc, Set, Transact};
+///
+/// let doc = Doc::new();
+/// let set = doc.get_or_insert_gset("tags");
+/// let mut txn = doc.transact_mut();
+///
+/// assert!(set.insert(&mut txn, "rust"));
+/// assert!(set.insert(&mut txn, "crdt"));
+/// assert!(!set.insert(&mut txn, "rust"));
+///
+/// assert!(set.contains(&txn, &Any::from("crdt")));
+/// assert_eq!(set.iter(&txn).collect::<Vec<_>>(), vec![Any::from("crdt"), Any::from("rust")]);
+/// ```
+#[repr(transparent)]
+#[derive(Debug, Clone)]
+pub struct GSetRef(BranchPtr);
+
+impl RootRef for GSetRef {
+    fn type_ref() -> TypeRef {
+        TypeRef::GSet
+    }
+}
+impl SharedRef for GSetRef {}
+impl Set for GSetRef {}
+
+/// A two-phase set. Elements can be inserted and removed, but once removed they cannot be inserted
+/// again. Removal wins over concurrent insertion of the same element.
+///
+/// # Example
+///
+/// ```rust
+/// use yrs::{Any, Doc, Set, Transact};
+///
+/// let doc = Doc::new();
+/// let set = doc.get_or_insert_two_phase_set("members");
+/// let mut txn = doc.transact_mut();
+///
+/// set.insert(&mut txn, "alice");
+/// assert!(set.remove(&mut txn, &Any::from("alice")));
+/// assert!(!set.contains(&txn, &Any::from("alice")));
+///
+/// // removed elements cannot be inserted again
+/// assert!(!set.insert(&mut txn, "alice"));
+/// ```
+#[repr(transparent)]
+#[derive(Debug, Clone)]
+pub struct TwoPhaseSetRef(BranchPtr);
+
+impl TwoPhaseSetRef {
+    /// Removes an `element` from a current set. Returns `true` if the element was present in the
+    /// set.
+    ///
+    /// Removal is permanent: it's not possible to insert a removed element again.
+    pub fn remove(&self, txn: &mut TransactionMut, element: &Any) -> bool {
+        let key = element_key(element);
+        if is_removed(&self.0, &key) {
+            return false;
+        }
+        let was_present = is_present(&self.0, &key);
+        let map = MapRef::from(self.0);
+        map.insert(txn, tombstone_key(&key), element.clone());
+        was_present
+    }
+}
+
+impl RootRef for TwoPhaseSetRef {
+    fn type_ref() -> TypeRef {
+        TypeRef::TwoPhaseSet
+    }
+}
+impl SharedRef for TwoPhaseSetRef {}
+impl Set for TwoPhaseSetRef {}
+
+macro_rules! impl_set_ref {
+    ($name:ident, $type_ref:path) => {
+        impl DeepObservable for $name {}
+        impl Observable for $name {
+            type Event = SetEvent;
+        }
+
+        impl ToJson for $name {
+            fn to_json<T: ReadTxn>(&self, _txn: &T) -> Any {
+                set_value(&self.0)
+            }
+        }
+
+        impl AsRef<Branch> for $name {
+            fn as_ref(&self) -> &Branch {
+                self.0.deref()
+            }
+        }
+
+        impl Eq for $name {}
+        impl PartialEq for $name {
+            fn eq(&self, other: &Self) -> bool {
+                self.0.id() == other.0.id()
+            }
+        }
+
+        impl From<BranchPtr> for $name {
+            fn from(inner: BranchPtr) -> Self {
+                $name(inner)
+            }
+        }
+
+        impl TryFrom<ItemPtr> for $name {
+            type Error = ItemPtr;
+
+            fn try_from(value: ItemPtr) -> Result<Self, Self::Error> {
+                match value.as_branch() {
+                    Some(branch) if branch.type_ref == $type_ref => Ok($name::from(branch)),
+                    _ => Err(value),
+                }
+            }
+        }
+    };
+}
+
+impl_set_ref!(GSetRef, TypeRef::GSet);
+impl_set_ref!(TwoPhaseSetRef, TypeRef::TwoPhaseSet);
+
+/// A preliminary grow-only set. It's used to initialize a new [GSetRef] with a given elements.
+#[repr(transparent)]
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct GSetPrelim(pub Vec<Any>);
+
+impl Prelim for GSetPrelim {
+    type Return = GSetRef;
+
+    fn into_content(self, _txn: &mut TransactionMut) -> (ItemContent, Option<Self>) {
+        (ItemContent::Type(Branch::new(TypeRef::GSet)), Some(self))
+    }
+
+    fn integrate(self, txn: &mut TransactionMut, inner_ref: BranchPtr) {
+        let set = GSetRef::from(inner_ref);
+        for element in self.0 {
+            set.insert(txn, element);
+        }
+    }
+}
+
+/// A preliminary two-phase set. It's used to initialize a new [TwoPhaseSetRef] with a given
+/// elements.
+#[repr(transparent)]
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TwoPhaseSetPrelim(pub Vec<Any>);
+
+impl Prelim for TwoPhaseSetPrelim {
+    type Return = TwoPhaseSetRef;
+
+    fn into_content(self, _txn: &mut TransactionMut) -> (ItemContent, Option<Self>) {
+        (
+            ItemContent::Type(Branch::new(TypeRef::TwoPhaseSet)),
+            Some(self),
+        )
+    }
+
+    fn integrate(self, txn: &mut TransactionMut, inner_ref: BranchPtr) {
+        let set = TwoPhaseSetRef::from(inner_ref);
+        for element in self.0 {
+            set.insert(txn, element);
+        }
+    }
+}
+
+/// Event generated by [GSetRef::observe] and [TwoPhaseSetRef::observe] methods. Emitted during
+/// transaction commit phase.
+pub struct SetEvent {
+    pub(crate) current_target: BranchPtr,
+    pub(crate) target: BranchPtr,
+    changes: UnsafeCell<Result<SetChanges, HashSet<Option<Arc<str>>>>>,
+}
+
+#[derive(Default)]
+struct SetChanges {
+    added: Vec<Any>,
+    removed: Vec<Any>,
+}
+
+impl SetEvent {
+    pub(crate) fn new(branch_ref: BranchPtr, key_changes: HashSet<Option<Arc<str>>>) -> Self {
+        SetEvent {
+            current_target: branch_ref,
+            target: branch_ref,
+            changes: UnsafeCell::new(Err(key_changes)),
+        }
+    }
+
+    /// Returns a set which emitted this event.
+    pub fn target(&self) -> SetOut {
+        match self.target.type_ref() {
+            TypeRef::TwoPhaseSet => SetOut::TwoPhaseSet(TwoPhaseSetRef::from(self.target)),
+            _ => SetOut::GSet(GSetRef::from(self.target)),
+        }
+    }
+
+    /// Returns a path from root type down to a set instance which emitted this event.
+    pub fn path(&self) -> Path {
+        Branch::path(self.current_target, self.target)
+    }
+
+    /// Returns elements added to a set within bounds of current transaction.
+    pub fn added(&self, txn: &TransactionMut) -> &[Any] {
+        &self.changes(txn).added
+    }
+
+    /// Returns elements removed from a set within bounds of current transaction.
+    pub fn removed(&self, txn: &TransactionMut) -> &[Any] {
+        &self.changes(txn).removed
+    }
+
+    fn changes(&self, txn: &TransactionMut) -> &SetChanges {
+        let changes = unsafe { self.changes.get().as_mut().unwrap() };
+        if let Err(keys) = changes {
+            let mut entries: Vec<_> = event_keys(txn, self.target, keys).into_iter().collect();
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+            let mut result = SetChanges::default();
+            for (key, change) in entries {
+                if let EntryChange::Inserted(Out::Any(value)) = change {
+                    match key.strip_prefix(TOMBSTONE) {
+                        // elements which were not visible before this transaction are neither
+                        // reported as added nor as removed
+                        Some(key) if was_present_before(txn, self.target, key) => {
+                            result.removed.push(value)
+                        }
+                        Some(_) => {}
+                        None if !is_removed(&self.target, &key) => result.added.push(value),
+                        None => {}
+                    }
+                }
+            }
+            *changes = Ok(result);
+        }
+        match changes {
+            Ok(changes) => changes,
+            Err(_) => unreachable!(),
+        }
+    }
+}
+
+/// Set reference returned by [SetEvent::target].
+#[derive(Debug, Clone, PartialEq)]
+pub enum SetOut {
+    GSet(GSetRef),
+    TwoPhaseSet(TwoPhaseSetRef),
+}
+
+/// Returns elements of a set stored in a given `branch`, as an array ordered by their canonical
+/// representation.
+pub(crate) fn set_value(branch: &Branch) -> Any {
+    let elements: Vec<Any> = SetIter::new(branch).collect();
+    Any::from(elements)
+}
+
+fn element(content: &ItemContent) -> Option<Any> {
+    match content {
+        ItemContent::Any(values) => values.last().cloned(),
+        _ => None,
+    }
+}
+
+fn is_present(branch: &Branch, key: &str) -> bool {
+    match branch.map.get(key) {
+        Some(item) => !item.is_deleted(),
+        None => false,
+    }
+}
+
+fn is_removed(branch: &Branch, key: &str) -> bool {
+    branch.map.contains_key(tombstone_key(key).as_str())
+}
+
+fn was_present_before(txn: &TransactionMut, branch: BranchPtr, key: &str) -> bool {
+    match branch.map.get(key) {
+        Some(item) => !txn.has_added(&item.id) && (!item.is_deleted() || txn.has_deleted(&item.id)),
+        None => false,
+    }
+}
+
+fn tombstone_key(key: &str) -> String {
+    let mut tombstone = String::with_capacity(key.len() + 1);
+    tombstone.push(TOMBSTONE);
+    tombstone.push_str(key);
+    tombstone
+}
+
+/// Returns a canonical representation of a given element, which is the same regardless of the
+/// order of map entries.
+fn element_key(value: &Any) -> String {
+    let mut key = String::new();
+    write_element_key(value, &mut key);
+    key
+}
+
+fn write_element_key(value: &Any, out: &mut String) {
+    match value {
+        Any::Null => out.push_str("null"),
+        Any::Undefined => out.push_str("undefined"),
+        Any::Bool(value) => write!(out, "{}", value).unwrap(),
+        Any::Number(value) => write!(out, "{}", value).unwrap(),
+        Any::BigInt(value) => write!(out, "{}n", value).unwrap(),
+        Any::String(value) => out.push_str(&serde_json::to_string(value.as_ref()).unwrap()),
+        Any::Buffer(value) => {
+            out.push_str("0x");
+            for byte in value.iter() {
+                write!(out, "{:02x}", byte).unwrap();
+            }
+        }
+        Any::Array(values) => {
+            out.push('[');
+            for (i, value) in values.iter().enumerate() {
+                if i != 0 {
+                    out.push(',');
+                }
+                write_element_key(value, out);
+            }
+            out.push(']');
+        }
+        Any::Map(entries) => {
+            let entries: BTreeMap<_, _> = entries.iter().collect();
+            out.push('{');
+            for (i, (key, value)) in entries.into_iter().enumerate() {
+                if i != 0 {
+                    out.push(',');
+                }
+                out.push_str(&serde_json::to_string(key).unwrap());
+                out.push(':');
+                write_element_key(value, out);
+            }
+            out.push('}');
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::types::set::{element_key, GSetPrelim, Set, SetEvent};
+    use crate::types::ToJson;
+    use crate::updates::decoder::Decode;
+    use crate::{any, Any, Doc, Map, Observable, ReadTxn, StateVector, Transact, Update, WriteTxn};
+    use std::sync::{Arc, Mutex};
+
+    fn sync(d1: &Doc, d2: &Doc) {
+        let u1 = d1
+            .transact()
+            .encode_state_as_update_v1(&StateVector::default());
+        let u2 = d2
+            .transact()
+            .encode_state_as_update_v2(&StateVector::default());
+        d1.transact_mut()
+            .apply_update(Update::decode_v2(&u2).unwrap());
+        d2.transact_mut()
+            .apply_update(Update::decode_v1(&u1).unwrap());
+    }
+
+    #[test]
+    fn gset_concurrent_inserts() {
+        let d1 = Doc::with_client_id(1);
+        let s1 = d1.get_or_insert_gset("set");
+        let d2 = Doc::with_client_id(2);
+        let s2 = d2.get_or_insert_gset("set");
+
+        {
+            let mut txn = d1.transact_mut();
+            assert!(s1.insert(&mut txn, "a"));
+            assert!(s1.insert(&mut txn, "b"));
+        }
+        {
+            let mut txn = d2.transact_mut();
+            assert!(s2.insert(&mut txn, "b"));
+            assert!(s2.insert(&mut txn, 1));
+        }
+        sync(&d1, &d2);
+
+        for (doc, set) in [(&d1, &s1), (&d2, &s2)] {
+            let txn = doc.transact();
+            assert_eq!(set.len(&txn), 3);
+            assert!(set.contains(&txn, &Any::from("b")));
+            assert_eq!(set.to_json(&txn), any!(["a", "b", 1]));
+        }
+    }
+
+    #[test]
+    fn two_phase_set_remove_wins() {
+        let d1 = Doc::with_client_id(1);
+        let s1 = d1.get_or_insert_two_phase_set("set");
+        let d2 = Doc::with_client_id(2);
+        let s2 = d2.get_or_insert_two_phase_set("set");
+
+        s1.insert(&mut d1.transact_mut(), "a");
+        sync(&d1, &d2);
+
+        // concurrently remove and re-insert the same element
+        assert!(s1.remove(&mut d1.transact_mut(), &Any::from("a")));
+        s2.insert(&mut d2.transact_mut(), "b");
+        assert!(s2.remove(&mut d2.transact_mut(), &Any::from("b")));
+        {
+            let d3 = Doc::with_client_id(3);
+            let s3 = d3.get_or_insert_two_phase_set("set");
+            let mut txn = d3.transact_mut();
+            assert!(s3.insert(&mut txn, "b"));
+            let update = txn.encode_update_v1();
+            drop(txn);
+            d1.transact_mut()
+                .apply_update(Update::decode_v1(&update).unwrap());
+        }
+        sync(&d1, &d2);
+
+        for (doc, set) in [(&d1, &s1), (&d2, &s2)] {
+            let mut txn = doc.transact_mut();
+            assert!(!set.contains(&txn, &Any::from("a")));
+            assert!(!set.contains(&txn, &Any::from("b")));
+            assert!(set.is_empty(&txn));
+            assert!(!set.insert(&mut txn, "a"));
+        }
+    }
+
+    #[test]
+    fn nested_set() {
+        let doc = Doc::with_client_id(1);
+        let mut txn = doc.transact_mut();
+        let map = txn.get_or_insert_map("map");
+        let set = map.insert(&mut txn, "tags", GSetPrelim(vec!["x".into(), "y".into()]));
+        set.insert(&mut txn, "z");
+        assert_eq!(map.to_json(&txn), any!({"tags": ["x", "y", "z"]}));
+    }
+
+    #[test]
+    fn observe_set() {
+        let doc = Doc::with_client_id(1);
+        let set = doc.get_or_insert_two_phase_set("set");
+        let changes = Arc::new(Mutex::new(Vec::new()));
+        let c = changes.clone();
+        let _sub = set.observe(move |txn, e: &SetEvent| {
+            c.lock()
+                .unwrap()
+                .push((e.added(txn).to_vec(), e.removed(txn).to_vec()));
+        });
+
+        {
+            let mut txn = doc.transact_mut();
+            set.insert(&mut txn, "a");
+            set.insert(&mut txn, "b");
+            set.insert(&mut txn, "c");
+            set.remove(&mut txn, &Any::from("c"));
+        }
+        set.remove(&mut doc.transact_mut(), &Any::from("a"));
+
+        let changes = changes.lock().unwrap();
+        assert_eq!(
+            *changes,
+            vec![
+                (vec![Any::from("a"), Any::from("b")], vec![]),
+                (vec![], vec![Any::from("a")]),
+            ]
+        );
+    }
+
+    #[test]
+    fn canonical_keys() {
+        assert_eq!(
+            element_key(&any!({"b": 1, "a": [true, null]})),
+            element_key(&any!({"a": [true, null], "b": 1}))
+        );
+        assert_ne!(element_key(&Any::from(1)), element_key(&Any::BigInt(1)));
+        assert_ne!(element_key(&Any::from("1")), element_key(&Any::from(1)));
+    }
+}
@@ -1,5 +1,9 @@
-use crate::block::{EmbedPrelim, Item, ItemContent, ItemPosition, ItemPtr, Prelim, Unused};
+use crate::blame::BlockMeta;
+use crate::block::{
+    ClientID, EmbedPrelim, Item, ItemContent, ItemPosition, ItemPtr, Prelim, Unused,
+};
 use crate::transaction::TransactionMut;
+use crate::types::input_edit::{input_edits, InputEdit};
 use crate::types::{
     AsPrelim, Attrs, Branch, BranchPtr, DefaultPrelim, Delta, Out, Path, RootRef, SharedRef,
     TypePtr, TypeRef,
@@ -10,7 +14,8 @@ use std::cell::UnsafeCell;
 use std::collections::HashMap;
 use std::convert::{TryFrom, TryInto};
 use std::fmt::Formatter;
-use std::ops::{Deref, DerefMut};
+use std::iter::FromIterator;
+use std::ops::{Deref, DerefMut, Range};
 
 /// A shared data type used for collaborative text editing. It enables multiple users to add and
 /// remove chunks of text in efficient manner. This type is internally represented as a mutable
@@ -117,7 +122,8 @@ impl GetString for TextRef {
     /// Converts context of this text data structure into a single string value. This method doesn't
     /// render formatting attributes or embedded content. In order to retrieve it, use
     /// [TextRef::diff] method.
-    fn get_string<T: ReadTxn>(&self, _txn: &T) -> String {
+    fn get_string<T: ReadTxn>(&self, txn: &T) -> String {
+        txn.track(&self.0);
         let mut start = self.0.start;
         let mut s = String::new();
         while let Some(item) = start.as_deref() {
@@ -156,11 +162,23 @@ impl TryFrom<Out> for TextRef {
 }
 
 pub trait Text: AsRef<Branch> + Sized {
-    /// Returns a number of characters visible in a current text data structure.
-    fn len<T: ReadTxn>(&self, _txn: &T) -> u32 {
+    /// Returns a number of characters visible in a current text data structure, expressed in
+    /// units configured by [Options::offset_kind] of a document.
+    ///
+    /// [Options::offset_kind]: crate::Options::offset_kind
+    fn len<T: ReadTxn>(&self, txn: &T) -> u32 {
+        txn.track(self.as_ref());
         self.as_ref().content_len
     }
 
+    /// Returns a number of characters visible in a current text data structure, expressed in
+    /// units of a given offset `kind`, regardless of a document configuration.
+    /// See: [Branch::len_with].
+    fn len_with<T: ReadTxn>(&self, txn: &T, kind: OffsetKind) -> u32 {
+        txn.track(self.as_ref());
+        self.as_ref().len_with(kind)
+    }
+
     /// Inserts a `chunk` of text at a given `index`.
     /// If `index` is `0`, this `chunk` will be inserted at the beginning of a current text.
     /// If `index` is equal to current data structure length, this `chunk` will be appended at
@@ -417,16 +435,30 @@ pub trait Text: AsRef<Branch> + Sized {
     ///     Diff::new("world".into(), Some(Box::new(italic_and_bold))),
     /// ]);
     /// ```
-    fn diff<T, D, F>(&self, _txn: &T, compute_ychange: F) -> Vec<Diff<D>>
+    fn diff<T, D, F>(&self, txn: &T, compute_ychange: F) -> Vec<Diff<D>>
     where
         T: ReadTxn,
         F: Fn(YChange) -> D,
     {
+        txn.track(self.as_ref());
         let mut asm = DiffAssembler::new(compute_ychange);
         asm.process(self.as_ref().start, None, None, None, None);
         asm.finish()
     }
 
+    /// Attributes visible contents of current text to their authors. Returns a list of
+    /// consecutive index ranges (expressed in units configured by [Options::offset_kind]) together
+    /// with the identifier of a client, which has inserted them, and their timestamp and author
+    /// tag if block metadata has been recorded (see: [Doc::record_block_meta]).
+    ///
+    /// Embedded contents are counted as a single element. Formatting attributes are skipped.
+    ///
+    /// [Options::offset_kind]: crate::Options::offset_kind
+    /// [Doc::record_block_meta]: crate::Doc::record_block_meta
+    fn blame<T: ReadTxn>(&self, txn: &T) -> Vec<(Range<u32>, ClientID, Option<BlockMeta>)> {
+        crate::blame::blame(self.as_ref(), txn)
+    }
+
     /// Returns the Delta representation of this YText type.
     fn diff_range<D, F>(
         &self,
@@ -449,6 +481,423 @@ pub trait Text: AsRef<Branch> + Sized {
         asm.process(self.as_ref().start, hi, lo, None, None);
         asm.finish()
     }
+
+    /// Returns the contents of current text as they were at the moment when snapshot `to` was
+    /// made, annotated with changes made since snapshot `from`. Chunks inserted in between carry
+    /// [ChangeKind::Added] and chunks visible in `from` but removed in `to` are included with
+    /// [ChangeKind::Removed]. Every change is attributed to the client, which has inserted
+    /// a changed chunk (see: [YChange::id]). Adjacent chunks of the same kind, inserted by
+    /// the same client, are merged together.
+    ///
+    /// Contents of removed chunks can only be rendered if the document keeps its tombstones
+    /// (see: [Options::skip_gc]).
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use yrs::types::text::ChangeKind;
+    /// use yrs::{Doc, Options, Out, ReadTxn, Text, Transact};
+    ///
+    /// let doc = Doc::with_options(Options {
+    ///     client_id: 1,
+    ///     skip_gc: true,
+    ///     ..Options::default()
+    /// });
+    /// let text = doc.get_or_insert_text("text");
+    /// text.push(&mut doc.transact_mut(), "hello world");
+    /// let before = doc.transact().snapshot();
+    /// text.remove_range(&mut doc.transact_mut(), 0, 6);
+    /// text.push(&mut doc.transact_mut(), "!");
+    /// let after = doc.transact().snapshot();
+    ///
+    /// let diff = text.diff_with_attribution(&mut doc.transact_mut(), &before, &after);
+    /// let changes: Vec<_> = diff
+    ///     .iter()
+    ///     .map(|d| (d.insert.clone(), d.ychange.as_ref().map(|c| (c.kind, c.id.client))))
+    ///     .collect();
+    /// assert_eq!(changes, vec![
+    ///     (Out::from("hello "), Some((ChangeKind::Removed, 1))),
+    ///     (Out::from("world"), None),
+    ///     (Out::from("!"), Some((ChangeKind::Added, 1))),
+    /// ]);
+    /// ```
+    ///
+    /// [Options::skip_gc]: crate::Options::skip_gc
+    fn diff_with_attribution(
+        &self,
+        txn: &mut TransactionMut,
+        from: &Snapshot,
+        to: &Snapshot,
+    ) -> Vec<Diff<YChange>> {
+        let chunks = self.diff_range(txn, Some(to), Some(from), YChange::identity);
+        let mut result: Vec<Diff<YChange>> = Vec::with_capacity(chunks.len());
+        for chunk in chunks {
+            if let Some(last) = result.last_mut() {
+                let same_change = match (&last.ychange, &chunk.ychange) {
+                    (None, None) => true,
+                    (Some(a), Some(b)) => a.kind == b.kind && a.id.client == b.id.client,
+                    _ => false,
+                };
+                if same_change && last.attributes == chunk.attributes {
+                    if let (Out::Any(Any::String(a)), Out::Any(Any::String(b))) =
+                        (&last.insert, &chunk.insert)
+                    {
+                        last.insert = Out::Any(Any::from(format!("{}{}", a, b)));
+                        continue;
+                    }
+                }
+            }
+            result.push(chunk);
+        }
+        result
+    }
+
+    /// Returns an iterator over the formatted spans of a current text. Each span is a range of
+    /// indexes (measured in units configured by [Options::offset_kind]) together with formatting
+    /// attributes applied to it. Unformatted parts of the text are skipped and adjacent parts
+    /// sharing the same attributes are returned as a single span.
+    ///
+    /// Spans are computed by walking the blocks of a current text once, without building its
+    /// full [Text::diff].
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use yrs::{Any, Doc, Text, Transact};
+    /// use yrs::types::Attrs;
+    ///
+    /// let doc = Doc::new();
+    /// let text = doc.get_or_insert_text("text");
+    /// let mut txn = doc.transact_mut();
+    /// text.push(&mut txn, "hello world");
+    /// let bold = Attrs::from([("b".into(), Any::Bool(true))]);
+    /// text.format(&mut txn, 0, 5, bold.clone());
+    ///
+    /// let spans: Vec<_> = text.format_spans(&txn).collect();
+    /// assert_eq!(spans, vec![(0..5, bold.clone())]);
+    /// assert_eq!(text.attributes_at(&txn, 4), Some(bold));
+    /// assert_eq!(text.attributes_at(&txn, 5), Some(Attrs::new()));
+    /// ```
+    fn format_spans<'a, T: ReadTxn>(&self, txn: &'a T) -> FormatSpans<'a, T> {
+        FormatSpans::new(self.as_ref(), txn)
+    }
+
+    /// Returns formatting attributes applied to a character at a given `index` (measured in units
+    /// configured by [Options::offset_kind]). Returns `None` if `index` is beyond the end of
+    /// a current text.
+    fn attributes_at<T: ReadTxn>(&self, txn: &T, index: u32) -> Option<Attrs> {
+        let kind = txn.store().options.offset_kind;
+        let mut attrs = Attrs::new();
+        let mut current = 0;
+        let mut ptr = self.as_ref().start;
+        while let Some(item) = ptr.as_deref() {
+            if !item.is_deleted() {
+                match &item.content {
+                    ItemContent::Format(key, value) => {
+                        update_current_attributes(&mut attrs, key, value);
+                    }
+                    _ if item.is_countable() => {
+                        current += item.content_len(kind);
+                        if index < current {
+                            return Some(attrs);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            ptr = item.right;
+        }
+        None
+    }
+
+    /// Returns a number of lines in a current text, which is a number of new line characters
+    /// it contains plus one. An empty text consists of a single empty line.
+    fn line_count<T: ReadTxn>(&self, _txn: &T) -> u32 {
+        let mut count = 1;
+        let mut ptr = self.as_ref().start;
+        while let Some(item) = ptr.as_deref() {
+            if !item.is_deleted() {
+                if let ItemContent::String(s) = &item.content {
+                    count += s.as_str().bytes().filter(|&b| b == b'\n').count() as u32;
+                }
+            }
+            ptr = item.right;
+        }
+        count
+    }
+
+    /// Returns a range of indexes occupied by a zero-based `line` within a current text, excluding
+    /// its trailing new line character. Indexes are measured in units configured by
+    /// [Options::offset_kind]. Returns `None` if `line` is beyond the end of a current text.
+    fn line_range<T: ReadTxn>(&self, txn: &T, line: u32) -> Option<std::ops::Range<u32>> {
+        let kind = txn.store().options.offset_kind;
+        let mut current = 0;
+        let mut index = 0;
+        let mut start = if line == 0 { Some(0) } else { None };
+        let mut ptr = self.as_ref().start;
+        while let Some(item) = ptr.as_deref() {
+            if !item.is_deleted() && item.is_countable() {
+                match &item.content {
+                    ItemContent::String(s) if start.is_some() || s.as_str().contains('\n') => {
+                        for c in s.chars() {
+                            if c == '\n' {
+                                if let Some(start) = start {
+                                    return Some(start..index);
+                                }
+                                current += 1;
+                                if current == line {
+                                    start = Some(index + 1);
+                                }
+                            }
+                            index += char_len(c, kind);
+                        }
+                    }
+                    _ => index += item.content_len(kind),
+                }
+            }
+            ptr = item.right;
+        }
+        start.map(|start| start..index)
+    }
+
+    /// Returns an iterator over the lines of a current text, without their trailing new line
+    /// characters. Just like [GetString::get_string], it skips embedded content.
+    ///
+    /// Lines are computed directly from the blocks of a current text, without materializing it
+    /// into a single string first.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use yrs::{Doc, Text, Transact};
+    ///
+    /// let doc = Doc::new();
+    /// let text = doc.get_or_insert_text("text");
+    /// let mut txn = doc.transact_mut();
+    /// text.push(&mut txn, "fn main() {\n");
+    /// text.push(&mut txn, "}\n");
+    /// text.insert_at_line(&mut txn, 1, 0, "    println!(\"hello\");\n");
+    ///
+    /// assert_eq!(text.line_count(&txn), 4);
+    /// let lines: Vec<_> = text.lines(&txn).collect();
+    /// assert_eq!(lines, vec!["fn main() {", "    println!(\"hello\");", "}", ""]);
+    ///
+    /// text.remove_line(&mut txn, 1);
+    /// let lines: Vec<_> = text.lines(&txn).collect();
+    /// assert_eq!(lines, vec!["fn main() {", "}", ""]);
+    /// ```
+    fn lines<'a, T: ReadTxn>(&self, txn: &'a T) -> Lines<'a, T> {
+        Lines::new(self.as_ref(), txn)
+    }
+
+    /// Inserts a `chunk` of text at a given `column` of a zero-based `line`. `column` is measured
+    /// in units configured by [Options::offset_kind].
+    ///
+    /// This method will panic if provided `line` doesn't exist or `column` is greater than the
+    /// length of that line.
+    fn insert_at_line(&self, txn: &mut TransactionMut, line: u32, column: u32, chunk: &str) {
+        match self.line_range(txn, line) {
+            Some(range) if column <= range.end - range.start => {
+                self.insert(txn, range.start + column, chunk)
+            }
+            Some(_) => panic!("Column {} is out of bounds of line {}", column, line),
+            None => panic!("Line {} doesn't exist", line),
+        }
+    }
+
+    /// Removes a zero-based `line` together with its trailing new line character. When the last
+    /// line is removed, a new line character preceding it is removed instead.
+    ///
+    /// This method will panic if provided `line` doesn't exist.
+    fn remove_line(&self, txn: &mut TransactionMut, line: u32) {
+        let range = match self.line_range(txn, line) {
+            Some(range) => range,
+            None => panic!("Line {} doesn't exist", line),
+        };
+        if range.end < self.len(txn) {
+            // new line character always takes a single unit
+            self.remove_range(txn, range.start, range.end - range.start + 1);
+        } else if range.start > 0 {
+            self.remove_range(txn, range.start - 1, range.end - range.start + 1);
+        } else if range.end > 0 {
+            self.remove_range(txn, 0, range.end);
+        }
+    }
+
+    /// Replaces the content of a current text with a `new_value`, by computing a minimal set of
+    /// insertions and deletions between the current and a new string and applying them. Unlike
+    /// removing the whole text and inserting a new one, it preserves the identity of unchanged
+    /// characters, so that concurrent edits, formatting and [StickyIndex]es pointing to them are
+    /// not lost.
+    ///
+    /// Embedded content is not a part of the compared string and is left as it is.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use yrs::{Doc, GetString, Text, Transact};
+    ///
+    /// let doc = Doc::new();
+    /// let text = doc.get_or_insert_text("text");
+    /// text.push(&mut doc.transact_mut(), "hello world");
+    ///
+    /// text.apply_diff(&mut doc.transact_mut(), "hello brave new world!");
+    /// assert_eq!(text.get_string(&doc.transact()), "hello brave new world!");
+    /// ```
+    fn apply_diff(&self, txn: &mut TransactionMut, new_value: &str) {
+        let kind = txn.store().options.offset_kind;
+        // collect visible characters together with their indexes within a current text
+        let mut old = Vec::new();
+        let mut indexes = Vec::new();
+        let mut index = 0;
+        let mut ptr = self.as_ref().start;
+        while let Some(item) = ptr.as_deref() {
+            if !item.is_deleted() && item.is_countable() {
+                if let ItemContent::String(s) = &item.content {
+                    for c in s.chars() {
+                        old.push(c);
+                        indexes.push(index);
+                        index += char_len(c, kind);
+                    }
+                } else {
+                    index += item.content_len(kind);
+                }
+            }
+            ptr = item.right;
+        }
+        indexes.push(index);
+
+        let new: Vec<char> = new_value.chars().collect();
+        // apply hunks from the end, so that indexes of preceding ones remain valid
+        for hunk in diff_chars(&old, &new).into_iter().rev() {
+            let mut runs = Vec::new();
+            for i in hunk.old.clone() {
+                let start = indexes[i];
+                let len = char_len(old[i], kind);
+                match runs.last_mut() {
+                    Some((run_start, run_len)) if *run_start + *run_len == start => *run_len += len,
+                    _ => runs.push((start, len)),
+                }
+            }
+            for (start, len) in runs.into_iter().rev() {
+                self.remove_range(txn, start, len);
+            }
+            if !hunk.new.is_empty() {
+                let chunk: String = new[hunk.new].iter().collect();
+                self.insert(txn, indexes[hunk.old.start], &chunk);
+            }
+        }
+    }
+}
+
+/// Iterator over the formatted spans of a [Text], returned by [Text::format_spans].
+pub struct FormatSpans<'a, T> {
+    ptr: Option<ItemPtr>,
+    kind: OffsetKind,
+    attrs: Attrs,
+    start: u32,
+    index: u32,
+    _txn: &'a T,
+}
+
+impl<'a, T: ReadTxn> FormatSpans<'a, T> {
+    fn new(branch: &Branch, txn: &'a T) -> Self {
+        FormatSpans {
+            ptr: branch.start,
+            kind: txn.store().options.offset_kind,
+            attrs: Attrs::new(),
+            start: 0,
+            index: 0,
+            _txn: txn,
+        }
+    }
+
+    /// Closes a span started at the last formatting change.
+    fn flush(&mut self) -> Option<(std::ops::Range<u32>, Attrs)> {
+        let start = std::mem::replace(&mut self.start, self.index);
+        if start < self.index && !self.attrs.is_empty() {
+            Some((start..self.index, self.attrs.clone()))
+        } else {
+            None
+        }
+    }
+}
+
+impl<'a, T: ReadTxn> Iterator for FormatSpans<'a, T> {
+    type Item = (std::ops::Range<u32>, Attrs);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(item) = self.ptr {
+            self.ptr = item.right;
+            if item.is_deleted() {
+                continue;
+            }
+            match &item.content {
+                ItemContent::Format(key, value) => {
+                    let mut attrs = self.attrs.clone();
+                    update_current_attributes(&mut attrs, key, value);
+                    if attrs != self.attrs {
+                        let span = self.flush();
+                        self.attrs = attrs;
+                        if span.is_some() {
+                            return span;
+                        }
+                    }
+                }
+                _ if item.is_countable() => self.index += item.content_len(self.kind),
+                _ => {}
+            }
+        }
+        self.flush()
+    }
+}
+
+/// Iterator over the lines of a [Text], returned by [Text::lines].
+pub struct Lines<'a, T> {
+    ptr: Option<ItemPtr>,
+    /// Byte offset within a string content of a block pointed by `ptr`.
+    offset: usize,
+    finished: bool,
+    _txn: &'a T,
+}
+
+impl<'a, T: ReadTxn> Lines<'a, T> {
+    fn new(branch: &Branch, txn: &'a T) -> Self {
+        Lines {
+            ptr: branch.start,
+            offset: 0,
+            finished: false,
+            _txn: txn,
+        }
+    }
+}
+
+impl<'a, T: ReadTxn> Iterator for Lines<'a, T> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        let mut line = String::new();
+        while let Some(item) = self.ptr.as_deref() {
+            if !item.is_deleted() {
+                if let ItemContent::String(s) = &item.content {
+                    let rest = &s.as_str()[self.offset..];
+                    if let Some(i) = rest.find('\n') {
+                        line.push_str(&rest[..i]);
+                        self.offset += i + 1;
+                        return Some(line);
+                    }
+                    line.push_str(rest);
+                }
+            }
+            self.ptr = item.right;
+            self.offset = 0;
+        }
+        self.finished = true;
+        Some(line)
+    }
 }
 
 impl From<BranchPtr> for TextRef {
@@ -663,7 +1112,18 @@ where
                         self.pack_str();
                         if let Some(value) = item.content.get_first() {
                             let attrs = self.attrs_boxed();
-                            self.ops.push(Diff::new(value, attrs));
+                            let change = match (hi, lo) {
+                                (Some(hi), _) if !hi.is_visible(&item.id) => {
+                                    Some(ChangeKind::Removed)
+                                }
+                                (Some(_), Some(lo)) if !lo.is_visible(&item.id) => {
+                                    Some(ChangeKind::Added)
+                                }
+                                _ => None,
+                            };
+                            let change = change
+                                .map(|kind| (self.compute_ychange)(YChange::new(kind, item.id)));
+                            self.ops.push(Diff::with_change(value, attrs, change));
                         }
                     }
                     ItemContent::Format(key, value) => {
@@ -723,6 +1183,229 @@ fn insert<P: Prelim>(
     item
 }
 
+/// Converts a UTF-16 code unit `index` within a given `text` (the way it's addressed by Yjs and
+/// JavaScript) into a UTF-8 byte index (the way it's addressed by Rust-native tooling). Returns
+/// `None` if `index` is out of bounds or points into the middle of a surrogate pair.
+///
+/// Only the block containing the `index` is being inspected character by character, all
+/// preceding blocks are skipped using their length metadata.
+///
+/// # Example
+///
+/// ```rust
+/// use yrs::{utf16_to_utf8_index, utf8_to_utf16_index, Doc, Text, Transact};
+///
+/// let doc = Doc::new();
+/// let text = doc.get_or_insert_text("text");
+/// let mut txn = doc.transact_mut();
+/// text.push(&mut txn, "zażółć 😀!");
+///
+/// assert_eq!(utf16_to_utf8_index(&txn, &text, 9), Some(15));
+/// assert_eq!(utf8_to_utf16_index(&txn, &text, 15), Some(9));
+/// assert_eq!(utf16_to_utf8_index(&txn, &text, 8), None); // inside of surrogate pair
+/// ```
+pub fn utf16_to_utf8_index<T: ReadTxn, X: Text>(_txn: &T, text: &X, index: u32) -> Option<u32> {
+    convert_index(text.as_ref(), index, OffsetKind::Utf16, OffsetKind::Bytes)
+}
+
+/// Converts a UTF-8 byte `index` within a given `text` into a UTF-16 code unit index.
+/// Returns `None` if `index` is out of bounds or doesn't fall on a character boundary.
+///
+/// This is an inverse of [utf16_to_utf8_index].
+pub fn utf8_to_utf16_index<T: ReadTxn, X: Text>(_txn: &T, text: &X, index: u32) -> Option<u32> {
+    convert_index(text.as_ref(), index, OffsetKind::Bytes, OffsetKind::Utf16)
+}
+
+/// Converts an `index` expressed in `from` units into `to` units. If a given text `branch`
+/// maintains a [BlockIndex] measured in bytes, it's used to find a block containing an `index`,
+/// otherwise blocks are visited one by one.
+///
+/// [BlockIndex]: crate::block_index::BlockIndex
+pub(crate) fn convert_index(
+    branch: &Branch,
+    index: u32,
+    from: OffsetKind,
+    to: OffsetKind,
+) -> Option<u32> {
+    let (mut ptr, mut remaining, mut result) = match branch.index.as_deref() {
+        // block lengths of text items are measured in UTF-16 code units, so an index measured in
+        // bytes can be used to find an item containing a given index in either of the units
+        Some(block_index) if block_index.kind() == OffsetKind::Bytes => {
+            let found = match from {
+                OffsetKind::Bytes => block_index.find(index),
+                OffsetKind::Utf16 => block_index.find_block(index),
+            };
+            match found {
+                Some(found) => {
+                    let (from_offset, to_offset) = match from {
+                        OffsetKind::Bytes => (found.offset, found.block_offset),
+                        OffsetKind::Utf16 => (found.block_offset, found.offset),
+                    };
+                    (Some(*found.item), index - from_offset, to_offset)
+                }
+                None => {
+                    // index is not within any of the items: it may only point to the end of a text
+                    let (from_len, to_len) = match from {
+                        OffsetKind::Bytes => (branch.content_len(), branch.len()),
+                        OffsetKind::Utf16 => (branch.len(), branch.content_len()),
+                    };
+                    return (index == from_len).then_some(to_len);
+                }
+            }
+        }
+        _ => (branch.start, index, 0),
+    };
+    while let Some(item) = ptr.as_deref() {
+        if !item.is_deleted() && item.is_countable() {
+            let len = item.content_len(from);
+            if remaining < len {
+                return match &item.content {
+                    ItemContent::String(s) => {
+                        let mut offset = 0;
+                        for c in s.chars() {
+                            if offset == remaining {
+                                return Some(result);
+                            } else if offset > remaining {
+                                return None;
+                            }
+                            offset += char_len(c, from);
+                            result += char_len(c, to);
+                        }
+                        None
+                    }
+                    _ => (remaining == 0).then_some(result),
+                };
+            }
+            remaining -= len;
+            result += item.content_len(to);
+        }
+        ptr = item.right;
+    }
+    (remaining == 0).then_some(result)
+}
+
+#[inline]
+fn char_len(c: char, kind: OffsetKind) -> u32 {
+    match kind {
+        OffsetKind::Bytes => c.len_utf8() as u32,
+        OffsetKind::Utf16 => c.len_utf16() as u32,
+    }
+}
+
+/// Maximum number of edits computed by [diff_chars] before it gives up on finding a minimal
+/// diff and replaces the whole changed region instead.
+const MAX_DIFF_EDITS: usize = 1000;
+
+/// A single change computed by [diff_chars]: characters at `old` range are replaced with
+/// characters at `new` range.
+#[derive(Debug, PartialEq)]
+struct DiffHunk {
+    old: std::ops::Range<usize>,
+    new: std::ops::Range<usize>,
+}
+
+/// Computes a minimal list of changes required to turn `old` into `new` using Myers' algorithm.
+fn diff_chars(old: &[char], new: &[char]) -> Vec<DiffHunk> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let a = &old[prefix..old.len() - suffix];
+    let b = &new[prefix..new.len() - suffix];
+    if a.is_empty() && b.is_empty() {
+        return Vec::new();
+    }
+    let whole = || {
+        vec![DiffHunk {
+            old: prefix..prefix + a.len(),
+            new: prefix..prefix + b.len(),
+        }]
+    };
+    if a.is_empty() || b.is_empty() {
+        return whole();
+    }
+
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let max = (a.len() + b.len()).min(MAX_DIFF_EDITS) as isize;
+    let offset = max + 1;
+    let mut v = vec![0isize; 2 * offset as usize + 1];
+    // trace[d] contains furthest reaching x positions for diagonals -d..=d after d edits
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+    let mut end = None;
+    'outer: for d in 0..=max {
+        for k in (-d..=d).step_by(2) {
+            let i = (k + offset) as usize;
+            let mut x = if k == -d || (k != d && v[i - 1] < v[i + 1]) {
+                v[i + 1]
+            } else {
+                v[i - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[i] = x;
+            if x >= n && y >= m {
+                trace.push(v[(offset - d) as usize..=(offset + d) as usize].to_vec());
+                end = Some(d);
+                break 'outer;
+            }
+        }
+        trace.push(v[(offset - d) as usize..=(offset + d) as usize].to_vec());
+    }
+    let d = match end {
+        Some(d) => d,
+        None => return whole(),
+    };
+
+    // backtrack edits from the end, merging adjacent ones into hunks
+    let mut hunks: Vec<DiffHunk> = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for d in (1..=d).rev() {
+        let prev = &trace[(d - 1) as usize];
+        let get = |k: isize| prev[(k + d - 1) as usize];
+        let k = x - y;
+        let prev_k = if k == -d || (k != d && get(k - 1) < get(k + 1)) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let prev_x = get(prev_k);
+        let prev_y = prev_x - prev_k;
+        // position right after the edit, diagonal moves follow it
+        let (mid_x, mid_y) = if prev_k == k + 1 {
+            (prev_x, prev_x - k)
+        } else {
+            (prev_x + 1, prev_y)
+        };
+        let (px, py) = (prefix as isize, prefix as isize);
+        let hunk = DiffHunk {
+            old: (px + prev_x) as usize..(px + mid_x) as usize,
+            new: (py + prev_y) as usize..(py + mid_y) as usize,
+        };
+        match hunks.last_mut() {
+            Some(last)
+                if mid_x == x
+                    && mid_y == y
+                    && last.old.start == hunk.old.end
+                    && last.new.start == hunk.new.end =>
+            {
+                last.old.start = hunk.old.start;
+                last.new.start = hunk.new.start;
+            }
+            _ => hunks.push(hunk),
+        }
+        x = prev_x;
+        y = prev_y;
+    }
+    hunks.reverse();
+    hunks
+}
+
 pub(crate) fn update_current_attributes(attrs: &mut Attrs, key: &str, value: &Any) {
     if let Any::Null = value {
         attrs.remove(key);
@@ -746,6 +1429,15 @@ fn find_position(this: BranchPtr, txn: &mut TransactionMut, index: u32) -> Optio
     let store = txn.store_mut();
     let encoding = store.options.offset_kind;
     let mut remaining = index;
+    if remaining > 0 {
+        // skip directly to the block containing the last element before an index
+        if let Some(found) = this.indexed(remaining - 1) {
+            pos.left = found.item.left;
+            pos.right = Some(*found.item);
+            pos.index = found.block_offset;
+            remaining -= found.offset;
+        }
+    }
     while let Some(right) = pos.right {
         if remaining == 0 {
             break;
@@ -1159,6 +1851,23 @@ impl Prelim for DeltaPrelim {
     }
 }
 
+impl<T> FromIterator<Delta<T>> for DeltaPrelim
+where
+    T: Into<In>,
+{
+    fn from_iter<I: IntoIterator<Item = Delta<T>>>(iter: I) -> Self {
+        DeltaPrelim(
+            iter.into_iter()
+                .map(|delta| match delta {
+                    Delta::Inserted(value, attrs) => Delta::Inserted(value.into(), attrs),
+                    Delta::Deleted(len) => Delta::Deleted(len),
+                    Delta::Retain(len, attrs) => Delta::Retain(len, attrs),
+                })
+                .collect(),
+        )
+    }
+}
+
 impl From<TextPrelim> for DeltaPrelim {
     fn from(value: TextPrelim) -> Self {
         DeltaPrelim(vec![Delta::Inserted(
@@ -1237,6 +1946,13 @@ impl TextEvent {
         Branch::path(self.current_target, self.target.0)
     }
 
+    /// Returns text changes made within bounds of current transaction as a list of edits with
+    /// UTF-8 byte offsets and row/column positions, which can be passed over to incremental
+    /// parsers (such as tree-sitter's `Tree::edit`) in order to reuse previously parsed trees.
+    pub fn input_edits(&self, txn: &TransactionMut) -> Vec<InputEdit> {
+        input_edits(self.target.as_ref(), txn)
+    }
+
     /// Returns a summary of text changes made over corresponding [Text] collection within
     /// bounds of current transaction.
     pub fn delta(&self, txn: &TransactionMut) -> &[Delta] {
@@ -1506,13 +2222,13 @@ mod test {
     use crate::doc::{OffsetKind, Options};
     use crate::test_utils::{exchange_updates, run_scenario, RngExt};
     use crate::transaction::ReadTxn;
-    use crate::types::text::{Attrs, ChangeKind, Delta, Diff, YChange};
+    use crate::types::text::{diff_chars, Attrs, ChangeKind, Delta, Diff, DiffHunk, YChange};
     use crate::types::Out;
     use crate::updates::decoder::Decode;
     use crate::updates::encoder::{Encode, Encoder, EncoderV1};
     use crate::{
-        any, Any, ArrayPrelim, Doc, GetString, Map, MapPrelim, MapRef, Observable, StateVector,
-        Text, Transact, Update, WriteTxn, ID,
+        any, utf16_to_utf8_index, utf8_to_utf16_index, Any, ArrayPrelim, Doc, GetString, Map,
+        MapPrelim, MapRef, Observable, StateVector, Text, Transact, Update, WriteTxn, ID,
     };
     use arc_swap::ArcSwapOption;
     use fastrand::Rng;
@@ -1521,6 +2237,205 @@ mod test {
     use std::sync::Arc;
     use std::time::Duration;
 
+    #[test]
+    fn apply_diff() {
+        let d1 = Doc::with_client_id(1);
+        let t1 = d1.get_or_insert_text("test");
+        t1.push(&mut d1.transact_mut(), "The quick fox jumps over the dog");
+        let d2 = Doc::with_client_id(2);
+        let t2 = d2.get_or_insert_text("test");
+        exchange_updates(&[&d1, &d2]);
+
+        // diff-based replacement preserves concurrent changes
+        t1.apply_diff(
+            &mut d1.transact_mut(),
+            "The quick brown fox jumped over the dog",
+        );
+        assert_eq!(
+            t1.get_string(&d1.transact()),
+            "The quick brown fox jumped over the dog"
+        );
+        t2.insert(&mut d2.transact_mut(), 29, "lazy ");
+        exchange_updates(&[&d1, &d2]);
+        let expected = "The quick brown fox jumped over the lazy dog";
+        assert_eq!(t1.get_string(&d1.transact()), expected);
+        assert_eq!(t2.get_string(&d2.transact()), expected);
+
+        // embeds are left untouched
+        let mut txn = d1.transact_mut();
+        t1.insert_embed(&mut txn, 4, Any::from("embed"));
+        t1.apply_diff(&mut txn, "A quick brown fox");
+        assert_eq!(t1.get_string(&txn), "A quick brown fox");
+        assert_eq!(t1.len(&txn), "A quick brown fox".len() as u32 + 1);
+        t1.apply_diff(&mut txn, "");
+        assert_eq!(t1.len(&txn), 1);
+        t1.apply_diff(&mut txn, "zażółć gęślą");
+        t1.apply_diff(&mut txn, "żółć gęśli jaźń");
+        assert_eq!(t1.get_string(&txn), "żółć gęśli jaźń");
+    }
+
+    #[test]
+    fn diff_chars_hunks() {
+        let chars = |s: &str| s.chars().collect::<Vec<_>>();
+        assert_eq!(diff_chars(&chars("abc"), &chars("abc")), vec![]);
+        assert_eq!(
+            diff_chars(&chars("abcdef"), &chars("axcdyyf")),
+            vec![
+                DiffHunk {
+                    old: 1..2,
+                    new: 1..2
+                },
+                DiffHunk {
+                    old: 4..5,
+                    new: 4..6
+                }
+            ]
+        );
+        // too many edits, whole changed region is replaced
+        let old: Vec<_> = (0..3000)
+            .map(|i| if i % 2 == 0 { 'a' } else { 'b' })
+            .collect();
+        let new: Vec<_> = (0..3000)
+            .map(|i| if i % 2 == 0 { 'c' } else { 'b' })
+            .collect();
+        let hunks = diff_chars(&old, &new);
+        assert_eq!(
+            hunks,
+            vec![DiffHunk {
+                old: 0..2999,
+                new: 0..2999
+            }]
+        );
+    }
+
+    #[test]
+    fn apply_diff_random() {
+        let mut rng = Rng::with_seed(0xdeadbeef);
+        let alphabet = ['a', 'b', 'c', 'ą', '😀', '\n'];
+        let doc = Doc::with_client_id(1);
+        let txt = doc.get_or_insert_text("test");
+        for _ in 0..200 {
+            let len = rng.usize(0..40);
+            let value: String = (0..len).map(|_| alphabet[rng.usize(0..6)]).collect();
+            txt.apply_diff(&mut doc.transact_mut(), &value);
+            assert_eq!(txt.get_string(&doc.transact()), value);
+        }
+    }
+
+    #[test]
+    fn line_editing() {
+        let doc = Doc::with_options(Options {
+            offset_kind: OffsetKind::Utf16,
+            ..Options::default()
+        });
+        let txt = doc.get_or_insert_text("test");
+        let mut txn = doc.transact_mut();
+        assert_eq!(txt.line_count(&txn), 1);
+        assert_eq!(txt.lines(&txn).collect::<Vec<_>>(), vec![""]);
+        assert_eq!(txt.line_range(&txn, 0), Some(0..0));
+        assert_eq!(txt.line_range(&txn, 1), None);
+
+        // lines spanning over many blocks
+        txt.push(&mut txn, "zażółć\ngęślą");
+        txt.push(&mut txn, " jaźń\n");
+        txt.insert_embed(&mut txn, 18, Any::from(1));
+        txt.push(&mut txn, "😀\n\nend");
+        assert_eq!(txt.line_count(&txn), 5);
+        assert_eq!(
+            txt.lines(&txn).collect::<Vec<_>>(),
+            vec!["zażółć", "gęślą jaźń", "😀", "", "end"]
+        );
+        assert_eq!(txt.line_range(&txn, 1), Some(7..17));
+        assert_eq!(txt.line_range(&txn, 2), Some(18..21));
+        assert_eq!(txt.line_range(&txn, 4), Some(23..26));
+
+        txt.insert_at_line(&mut txn, 1, 5, ",");
+        txt.insert_at_line(&mut txn, 3, 0, "middle");
+        txt.insert_at_line(&mut txn, 4, 3, "!");
+        assert_eq!(
+            txt.get_string(&txn),
+            "zażółć\ngęślą, jaźń\n😀\nmiddle\nend!"
+        );
+
+        txt.remove_line(&mut txn, 2);
+        assert_eq!(txt.get_string(&txn), "zażółć\ngęślą, jaźń\nmiddle\nend!");
+        txt.remove_line(&mut txn, 3);
+        assert_eq!(txt.get_string(&txn), "zażółć\ngęślą, jaźń\nmiddle");
+        txt.remove_line(&mut txn, 0);
+        txt.remove_line(&mut txn, 0);
+        assert_eq!(txt.get_string(&txn), "middle");
+        txt.remove_line(&mut txn, 0);
+        assert_eq!(txt.get_string(&txn), "");
+        assert_eq!(txt.line_count(&txn), 1);
+    }
+
+    #[test]
+    fn format_spans() {
+        let doc = Doc::with_client_id(1);
+        let txt = doc.get_or_insert_text("test");
+        let mut txn = doc.transact_mut();
+        let bold = Attrs::from([("b".into(), Any::Bool(true))]);
+        let italic = Attrs::from([("i".into(), Any::Bool(true))]);
+        let both = Attrs::from([("b".into(), Any::Bool(true)), ("i".into(), Any::Bool(true))]);
+
+        txt.push(&mut txn, "abcdefghij");
+        assert_eq!(txt.format_spans(&txn).count(), 0);
+
+        txt.format(&mut txn, 1, 4, bold.clone());
+        txt.format(&mut txn, 3, 4, italic.clone());
+        txt.insert_embed(&mut txn, 10, Any::from(1));
+        txt.format(&mut txn, 9, 2, bold.clone());
+        assert_eq!(
+            txt.format_spans(&txn).collect::<Vec<_>>(),
+            vec![
+                (1..3, bold.clone()),
+                (3..5, both.clone()),
+                (5..7, italic.clone()),
+                (9..11, bold.clone()),
+            ]
+        );
+        assert_eq!(txt.attributes_at(&txn, 0), Some(Attrs::new()));
+        assert_eq!(txt.attributes_at(&txn, 4), Some(both));
+        assert_eq!(txt.attributes_at(&txn, 6), Some(italic));
+        assert_eq!(txt.attributes_at(&txn, 10), Some(bold.clone()));
+        assert_eq!(txt.attributes_at(&txn, 11), None);
+
+        // removing formatting merges spans back
+        txt.format(&mut txn, 0, 11, Attrs::from([("i".into(), Any::Null)]));
+        assert_eq!(
+            txt.format_spans(&txn).collect::<Vec<_>>(),
+            vec![(1..5, bold.clone()), (9..11, bold)]
+        );
+    }
+
+    #[test]
+    fn len_with_offset_kinds() {
+        use crate::branch::Branch;
+
+        let doc = Doc::with_options(Options {
+            offset_kind: OffsetKind::Bytes,
+            ..Options::default()
+        });
+        let txt = doc.get_or_insert_text("test");
+        let mut txn = doc.transact_mut();
+        txt.insert(&mut txn, 0, "zażółć 🦀");
+        txt.insert_embed(&mut txn, 0, Any::from(1));
+        txt.format(&mut txn, 0, 3, Attrs::from([("b".into(), Any::Bool(true))]));
+
+        let branch: &Branch = txt.as_ref();
+        assert_eq!(txt.len(&txn), 16);
+        assert_eq!(txt.len_with(&txn, OffsetKind::Bytes), 16);
+        assert_eq!(txt.len_with(&txn, OffsetKind::Utf16), 10);
+        assert_eq!(branch.len(), 10);
+        assert_eq!(branch.content_len(), 16);
+        assert_eq!(branch.char_count(), 9);
+        assert_eq!(branch.block_count(), 3);
+
+        txt.remove_range(&mut txn, 0, 1);
+        assert_eq!(txt.len_with(&txn, OffsetKind::Bytes), 15);
+        assert_eq!(branch.char_count(), 8);
+    }
+
     #[test]
     fn insert_empty_string() {
         let doc = Doc::new();
@@ -2788,6 +3703,49 @@ mod test {
         );
     }
 
+    #[test]
+    fn diff_with_attribution() {
+        let d1 = Doc::with_options(Options {
+            client_id: 1,
+            skip_gc: true,
+            ..Default::default()
+        });
+        let d2 = Doc::with_client_id(2);
+        let t1 = d1.get_or_insert_text("text");
+        let t2 = d2.get_or_insert_text("text");
+        t1.push(&mut d1.transact_mut(), "hello world");
+        exchange_updates(&[&d1, &d2]);
+        let before = d1.transact().snapshot();
+
+        t2.insert(&mut d2.transact_mut(), 6, "big ");
+        t2.insert(&mut d2.transact_mut(), 6, "very ");
+        exchange_updates(&[&d1, &d2]);
+        t1.remove_range(&mut d1.transact_mut(), 0, 6);
+        t1.insert_embed(&mut d1.transact_mut(), 14, Any::from(1));
+        let after = d1.transact().snapshot();
+
+        let diff = t1.diff_with_attribution(&mut d1.transact_mut(), &before, &after);
+        let change = |kind, client| Some(YChange::new(kind, ID::new(client, 0)));
+        let actual: Vec<_> = diff
+            .into_iter()
+            .map(|d| {
+                let change = d
+                    .ychange
+                    .map(|c| YChange::new(c.kind, ID::new(c.id.client, 0)));
+                (d.insert, change)
+            })
+            .collect();
+        assert_eq!(
+            actual,
+            vec![
+                (Out::from("hello "), change(ChangeKind::Removed, 1)),
+                (Out::from("very big "), change(ChangeKind::Added, 2)),
+                (Out::from("world"), None),
+                (Out::from(1), change(ChangeKind::Added, 1)),
+            ]
+        );
+    }
+
     #[test]
     fn snapshot_delete_after() {
         let doc = Doc::with_options(Options {
@@ -2834,4 +3792,62 @@ mod test {
         txn.apply_update(Update::decode_v1(bin.as_slice()).unwrap());
         assert_eq!(txt.get_string(&txn), "ab");
     }
+
+    #[test]
+    fn utf16_utf8_index_conversion() {
+        let doc = Doc::with_client_id(1);
+        let text = doc.get_or_insert_text("text");
+        let mut txn = doc.transact_mut();
+        text.push(&mut txn, "aą");
+        text.insert_embed(&mut txn, 3, Any::from(1));
+        text.push(&mut txn, "😀b");
+        text.insert(&mut txn, 1, "xyz");
+        text.remove_range(&mut txn, 1, 3);
+
+        // a ą <embed> 😀 b
+        let utf16 = [0, 1, 2, 3, 5, 6];
+        let utf8 = [0, 1, 3, 4, 8, 9];
+        for (&u16, &u8) in utf16.iter().zip(utf8.iter()) {
+            assert_eq!(utf16_to_utf8_index(&txn, &text, u16), Some(u8));
+            assert_eq!(utf8_to_utf16_index(&txn, &text, u8), Some(u16));
+        }
+        assert_eq!(utf16_to_utf8_index(&txn, &text, 4), None);
+        assert_eq!(utf16_to_utf8_index(&txn, &text, 7), None);
+        assert_eq!(utf8_to_utf16_index(&txn, &text, 2), None);
+        assert_eq!(utf8_to_utf16_index(&txn, &text, 10), None);
+    }
+
+    #[test]
+    fn utf16_utf8_index_conversion_indexed() {
+        let doc = Doc::with_client_id(1);
+        let text = doc.get_or_insert_text("text");
+        let mut txn = doc.transact_mut();
+        for _ in 0..40 {
+            text.push(&mut txn, "aą😀");
+        }
+        // each chunk takes 7 bytes
+        text.format(&mut txn, 7, 21, Attrs::from([("b".into(), Any::Bool(true))]));
+        text.remove_range(&mut txn, 21, 28);
+        let branch: &crate::branch::Branch = text.as_ref();
+        assert!(branch.index.is_some());
+
+        let str = text.get_string(&txn);
+        let (mut u16, mut u8) = (0, 0);
+        for c in str.chars() {
+            assert_eq!(utf16_to_utf8_index(&txn, &text, u16), Some(u8));
+            assert_eq!(utf8_to_utf16_index(&txn, &text, u8), Some(u16));
+            if c.len_utf16() > 1 {
+                assert_eq!(utf16_to_utf8_index(&txn, &text, u16 + 1), None);
+            }
+            if c.len_utf8() > 1 {
+                assert_eq!(utf8_to_utf16_index(&txn, &text, u8 + 1), None);
+            }
+            u16 += c.len_utf16() as u32;
+            u8 += c.len_utf8() as u32;
+        }
+        assert_eq!(utf16_to_utf8_index(&txn, &text, u16), Some(u8));
+        assert_eq!(utf8_to_utf16_index(&txn, &text, u8), Some(u16));
+        assert_eq!(utf16_to_utf8_index(&txn, &text, u16 + 1), None);
+        assert_eq!(utf8_to_utf16_index(&txn, &text, u8 + 1), None);
+    }
 }
@@ -0,0 +1,191 @@
+use crate::sync::time::{Clock, Timestamp};
+use crate::{In, Map, MapRef, TransactionMut};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Opt-in write coalescing for high-frequency updates of [MapRef] entries (i.e. values changed
+/// by dragging a slider).
+///
+/// Every key can be given its own time window using [MapThrottle::set_window]. Local writes to
+/// such key, which happen within the window since its last write, are not inserted into a map
+/// right away. Instead only the most recent value is kept aside and written once the window has
+/// passed ([MapThrottle::flush_expired]) or when [MapThrottle::flush] is called. This way a burst
+/// of overwrites produces a single block instead of thousands of them. Keys without a configured
+/// window are always written through.
+///
+/// # Example
+///
+/// ```rust
+/// use yrs::{Doc, Map, MapThrottle, Out, Transact};
+/// use std::sync::Arc;
+///
+/// let doc = Doc::new();
+/// let map = doc.get_or_insert_map("settings");
+/// let mut throttle = MapThrottle::with_clock(map.clone(), Arc::new(|| 0));
+/// throttle.set_window("volume", 100);
+///
+/// let mut txn = doc.transact_mut();
+/// assert!(throttle.insert(&mut txn, "volume", 1)); // first write goes through
+/// assert!(!throttle.insert(&mut txn, "volume", 2)); // coalesced
+/// assert!(!throttle.insert(&mut txn, "volume", 3)); // coalesced
+/// assert_eq!(map.get(&txn, "volume"), Some(Out::from(1)));
+///
+/// assert_eq!(throttle.flush(&mut txn), 1);
+/// assert_eq!(map.get(&txn, "volume"), Some(Out::from(3)));
+/// ```
+pub struct MapThrottle {
+    map: MapRef,
+    clock: Arc<dyn Clock>,
+    windows: HashMap<Arc<str>, Timestamp>,
+    last_write: HashMap<Arc<str>, Timestamp>,
+    pending: HashMap<Arc<str>, In>,
+}
+
+impl MapThrottle {
+    /// Creates a new throttle for a given `map`, using OS date time to measure time windows.
+    #[cfg(not(target_family = "wasm"))]
+    pub fn new(map: MapRef) -> Self {
+        Self::with_clock(map, Arc::new(crate::sync::time::SystemClock))
+    }
+
+    /// Creates a new throttle for a given `map`, using a custom clock (returning timestamps in
+    /// milliseconds) to measure time windows.
+    pub fn with_clock(map: MapRef, clock: Arc<dyn Clock>) -> Self {
+        MapThrottle {
+            map,
+            clock,
+            windows: HashMap::new(),
+            last_write: HashMap::new(),
+            pending: HashMap::new(),
+        }
+    }
+
+    /// Returns a map, which entries are being throttled.
+    pub fn map(&self) -> &MapRef {
+        &self.map
+    }
+
+    /// Sets a time window (in milliseconds) within which successive writes to a given `key` are
+    /// coalesced. Window of 0 disables coalescing for that key.
+    pub fn set_window<K: Into<Arc<str>>>(&mut self, key: K, window_millis: Timestamp) {
+        let key = key.into();
+        if window_millis == 0 {
+            self.windows.remove(&key);
+        } else {
+            self.windows.insert(key, window_millis);
+        }
+    }
+
+    /// Inserts a `value` under a given `key`. Returns `true` if value has been written into
+    /// a map immediately, or `false` if it was coalesced and will be written on the next flush.
+    pub fn insert<K, V>(&mut self, txn: &mut TransactionMut, key: K, value: V) -> bool
+    where
+        K: Into<Arc<str>>,
+        V: Into<In>,
+    {
+        let key = key.into();
+        let now = self.clock.now();
+        if let Some(window) = self.windows.get(&key) {
+            if let Some(last) = self.last_write.get(&key) {
+                if now.saturating_sub(*last) < *window {
+                    self.pending.insert(key, value.into());
+                    return false;
+                }
+            }
+            self.last_write.insert(key.clone(), now);
+        }
+        self.pending.remove(&key);
+        self.map.insert(txn, key, value.into());
+        true
+    }
+
+    /// Checks if there are any coalesced values waiting to be written.
+    pub fn has_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    /// Writes coalesced values, which time window has already passed. Returns a number of
+    /// written entries.
+    pub fn flush_expired(&mut self, txn: &mut TransactionMut) -> usize {
+        let now = self.clock.now();
+        let expired: Vec<_> = self
+            .pending
+            .keys()
+            .filter(|key| {
+                let window = self.windows.get(*key).copied().unwrap_or_default();
+                let last = self.last_write.get(*key).copied().unwrap_or_default();
+                now.saturating_sub(last) >= window
+            })
+            .cloned()
+            .collect();
+        for key in expired.iter() {
+            if let Some(value) = self.pending.remove(key) {
+                self.last_write.insert(key.clone(), now);
+                self.map.insert(txn, key.clone(), value);
+            }
+        }
+        expired.len()
+    }
+
+    /// Writes all coalesced values, regardless of their time windows. Returns a number of
+    /// written entries.
+    pub fn flush(&mut self, txn: &mut TransactionMut) -> usize {
+        let now = self.clock.now();
+        let count = self.pending.len();
+        for (key, value) in self.pending.drain() {
+            self.last_write.insert(key.clone(), now);
+            self.map.insert(txn, key, value);
+        }
+        count
+    }
+}
+
+impl std::fmt::Debug for MapThrottle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MapThrottle")
+            .field("map", &self.map)
+            .field("windows", &self.windows)
+            .field("pending", &self.pending.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{Doc, Map, MapThrottle, Out, ReadTxn, Transact};
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+
+    #[test]
+    fn coalesce_within_window() {
+        let doc = Doc::with_client_id(1);
+        let map = doc.get_or_insert_map("map");
+        let time = Arc::new(AtomicU64::new(0));
+        let clock = {
+            let time = time.clone();
+            Arc::new(move || time.load(Ordering::SeqCst))
+        };
+        let mut throttle = MapThrottle::with_clock(map.clone(), clock);
+        throttle.set_window("slider", 100);
+
+        let mut txn = doc.transact_mut();
+        for i in 0..50 {
+            time.store(i, Ordering::SeqCst);
+            throttle.insert(&mut txn, "slider", i as u32);
+            throttle.insert(&mut txn, "other", i as u32);
+        }
+        assert_eq!(map.get(&txn, "slider"), Some(Out::from(0)));
+        assert_eq!(map.get(&txn, "other"), Some(Out::from(49)));
+        assert_eq!(throttle.flush_expired(&mut txn), 0);
+
+        time.store(100, Ordering::SeqCst);
+        assert_eq!(throttle.flush_expired(&mut txn), 1);
+        assert!(!throttle.has_pending());
+        assert_eq!(map.get(&txn, "slider"), Some(Out::from(49)));
+        drop(txn);
+
+        // only two blocks have been created for "slider" key
+        let txn = doc.transact();
+        assert_eq!(txn.state_vector().get(&1), 52);
+    }
+}
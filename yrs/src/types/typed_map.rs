@@ -0,0 +1,297 @@
+use crate::encoding::serde::{to_any, AnySerializeError, FromValue, FromValueError};
+use crate::types::{DeepObservable, PathSegment, ToJson};
+use crate::{Any, Map, MapRef, ReadTxn, Subscription, TransactionMut};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::sync::Arc;
+use thiserror::Error;
+
+/// Schema-aware wrapper over a [MapRef], which entries represent fields of a struct `T`.
+///
+/// Reads project the map contents into `T`, reporting a [FromValueError] pointing to an offending
+/// field when another peer has inserted data of incompatible type - instead of a silent `None`
+/// returned by casting individual values. Writes are validated against `T` before they are
+/// applied, so that a local peer never introduces entries which it couldn't read back.
+///
+/// Map entries not mapped to any field of `T` are left untouched.
+///
+/// # Example
+///
+/// ```rust
+/// use serde::{Deserialize, Serialize};
+/// use yrs::{Doc, Map, Transact, TypedMap};
+///
+/// #[derive(Debug, PartialEq, Serialize, Deserialize)]
+/// struct Settings {
+///     theme: String,
+///     font_size: u32,
+/// }
+///
+/// let doc = Doc::new();
+/// let settings: TypedMap<Settings> = TypedMap::new(doc.get_or_insert_map("settings"));
+/// let mut txn = doc.transact_mut();
+/// let value = Settings { theme: "dark".into(), font_size: 12 };
+/// settings.set(&mut txn, &value).unwrap();
+/// assert_eq!(settings.get(&txn).unwrap(), value);
+///
+/// // writes which don't match the schema are rejected
+/// assert!(settings.set_field(&mut txn, "font_size", "large").is_err());
+///
+/// // incompatible data inserted through untyped API is reported on read
+/// settings.map().insert(&mut txn, "font_size", "large");
+/// let err = settings.get(&txn).unwrap_err();
+/// assert_eq!(err.to_string(), "failed to read value at `$.font_size`: couldn't deserialize to target type of u32");
+/// ```
+pub struct TypedMap<T> {
+    map: MapRef,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> TypedMap<T>
+where
+    T: Serialize + DeserializeOwned,
+{
+    /// Wraps a given `map` with a schema defined by type `T`.
+    pub fn new(map: MapRef) -> Self {
+        TypedMap {
+            map,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns an underlying untyped map.
+    pub fn map(&self) -> &MapRef {
+        &self.map
+    }
+
+    /// Reads the contents of a map into `T`. Returns an error describing a path to the first
+    /// entry, which doesn't match the schema.
+    pub fn get<Tx: ReadTxn>(&self, txn: &Tx) -> Result<T, FromValueError> {
+        T::from_any(&self.map.to_json(txn))
+    }
+
+    /// Reads a single entry stored under a given `key` into `V`. Returns `None` if there's no
+    /// such entry.
+    pub fn get_field<Tx, V>(&self, txn: &Tx, key: &str) -> Result<Option<V>, FromValueError>
+    where
+        Tx: ReadTxn,
+        V: FromValue,
+    {
+        match self.map.get(txn, key) {
+            None => Ok(None),
+            Some(value) => V::from_out(txn, &value)
+                .map(Some)
+                .map_err(|e| e.with_segment(PathSegment::Key(key.into()))),
+        }
+    }
+
+    /// Checks if the contents of a map can be read into `T`.
+    pub fn validate<Tx: ReadTxn>(&self, txn: &Tx) -> Result<(), FromValueError> {
+        self.get(txn).map(|_| ())
+    }
+
+    /// Writes all fields of a given `value` into a map. Only entries which values are different
+    /// from the ones already stored are overwritten.
+    pub fn set(&self, txn: &mut TransactionMut, value: &T) -> Result<(), TypedMapError> {
+        let fields = match to_any(value)? {
+            Any::Map(fields) => fields,
+            other => return Err(TypedMapError::NotAMap(other)),
+        };
+        let current = self.fields(txn);
+        for (key, value) in fields.iter() {
+            if current.get(key) != Some(value) {
+                self.map.insert(txn, key.as_str(), value.clone());
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes a single `value` under a given `key`. Before writing, map contents with the new
+    /// value applied are validated against `T`, so an error is returned when `value` type is
+    /// incompatible with the field - or when any other entry was already invalid.
+    pub fn set_field<V>(
+        &self,
+        txn: &mut TransactionMut,
+        key: &str,
+        value: V,
+    ) -> Result<(), TypedMapError>
+    where
+        V: Serialize,
+    {
+        let value = to_any(&value)?;
+        let mut fields = self.fields(txn);
+        fields.insert(key.to_string(), value.clone());
+        T::from_any(&Any::Map(Arc::new(fields)))?;
+        self.map.insert(txn, key, value);
+        Ok(())
+    }
+
+    /// Reads the contents of a map into `T`, applies a given function `f` to it and writes
+    /// the changed fields back.
+    pub fn update<F>(&self, txn: &mut TransactionMut, f: F) -> Result<(), TypedMapError>
+    where
+        F: FnOnce(&mut T),
+    {
+        let mut value = self.get(txn)?;
+        f(&mut value);
+        self.set(txn, &value)
+    }
+
+    /// Subscribes a callback `f`, which is called with the result of reading the contents of
+    /// a map into `T` whenever this map or any of its nested collections has changed. This way
+    /// schema violations introduced by remote peers can be detected as soon as they arrive.
+    ///
+    /// This method returns a subscription, which will automatically unsubscribe current callback
+    /// when dropped.
+    pub fn observe<F>(&self, f: F) -> Subscription
+    where
+        F: Fn(&TransactionMut, Result<T, FromValueError>) + Send + Sync + 'static,
+    {
+        let map = self.map.clone();
+        self.map.observe_deep(move |txn, _| {
+            f(txn, T::from_any(&map.to_json(txn)));
+        })
+    }
+
+    fn fields<Tx: ReadTxn>(&self, txn: &Tx) -> HashMap<String, Any> {
+        match self.map.to_json(txn) {
+            Any::Map(fields) => fields.as_ref().clone(),
+            _ => HashMap::new(),
+        }
+    }
+}
+
+impl<T> Clone for TypedMap<T> {
+    fn clone(&self) -> Self {
+        TypedMap {
+            map: self.map.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for TypedMap<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TypedMap")
+            .field("map", &self.map)
+            .field("type", &std::any::type_name::<T>())
+            .finish()
+    }
+}
+
+/// Error returned when writing into a [TypedMap] fails.
+#[derive(Debug, Error)]
+pub enum TypedMapError {
+    /// Written value couldn't be serialized.
+    #[error("failed to serialize value: {0}")]
+    Serialize(#[from] AnySerializeError),
+    /// Type of a [TypedMap] doesn't serialize into a map, i.e. it's not a struct.
+    #[error("typed map value must serialize into a map, but got: {0}")]
+    NotAMap(Any),
+    /// Map contents after the write wouldn't match the schema of a [TypedMap].
+    #[error("{0}")]
+    Schema(#[from] FromValueError),
+}
+
+#[cfg(test)]
+mod test {
+    use crate::types::typed_map::TypedMapError;
+    use crate::types::{Path, PathSegment};
+    use crate::updates::decoder::Decode;
+    use crate::{Doc, Map, MapPrelim, ReadTxn, Transact, TypedMap, Update};
+    use serde::{Deserialize, Serialize};
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Shape {
+        kind: String,
+        size: f64,
+        label: Option<String>,
+    }
+
+    #[test]
+    fn set_and_get() {
+        let doc = Doc::with_client_id(1);
+        let map = doc.get_or_insert_map("shape");
+        let shape: TypedMap<Shape> = TypedMap::new(map.clone());
+        let mut txn = doc.transact_mut();
+        let value = Shape {
+            kind: "circle".into(),
+            size: 2.5,
+            label: None,
+        };
+        shape.set(&mut txn, &value).unwrap();
+        assert_eq!(shape.get(&txn).unwrap(), value);
+        assert_eq!(shape.get_field::<_, f64>(&txn, "size").unwrap(), Some(2.5));
+        assert_eq!(shape.get_field::<_, f64>(&txn, "missing").unwrap(), None);
+
+        shape
+            .update(&mut txn, |s| s.label = Some("sun".into()))
+            .unwrap();
+        assert_eq!(shape.get(&txn).unwrap().label.as_deref(), Some("sun"));
+    }
+
+    #[test]
+    fn reject_invalid_writes() {
+        let doc = Doc::with_client_id(1);
+        let map = doc.get_or_insert_map("shape");
+        let shape: TypedMap<Shape> = TypedMap::new(map.clone());
+        let mut txn = doc.transact_mut();
+        map.insert(&mut txn, "kind", "square");
+        assert!(shape.validate(&txn).is_err()); // size is missing
+
+        shape.set_field(&mut txn, "size", 1).unwrap();
+        shape.validate(&txn).unwrap();
+
+        let err = shape.set_field(&mut txn, "size", "big").unwrap_err();
+        match err {
+            TypedMapError::Schema(e) => {
+                assert_eq!(e.path, Path::from([PathSegment::Key("size".into())]))
+            }
+            other => panic!("unexpected error: {}", other),
+        }
+        assert_eq!(shape.get(&txn).unwrap().size, 1.0);
+
+        let err = TypedMap::<u32>::new(map.clone())
+            .set(&mut txn, &1)
+            .unwrap_err();
+        assert!(matches!(err, TypedMapError::NotAMap(_)));
+    }
+
+    #[test]
+    fn detect_remote_schema_drift() {
+        let d1 = Doc::with_client_id(1);
+        let shape: TypedMap<Shape> = TypedMap::new(d1.get_or_insert_map("shape"));
+        let results = Arc::new(Mutex::new(Vec::new()));
+        let _sub = {
+            let results = results.clone();
+            shape.observe(move |_, res| results.lock().unwrap().push(res.is_ok()))
+        };
+
+        let d2 = Doc::with_client_id(2);
+        let remote = d2.get_or_insert_map("shape");
+        let sync = || {
+            let sv = d1.transact().state_vector();
+            let update = d2.transact().encode_state_as_update_v1(&sv);
+            d1.transact_mut()
+                .apply_update(Update::decode_v1(&update).unwrap());
+        };
+
+        remote.insert(&mut d2.transact_mut(), "kind", "circle");
+        sync();
+        remote.insert(&mut d2.transact_mut(), "size", 1);
+        sync();
+        remote.insert(
+            &mut d2.transact_mut(),
+            "size",
+            MapPrelim::from([("value", 1)]),
+        );
+        sync();
+
+        assert_eq!(*results.lock().unwrap(), vec![false, true, false]);
+        let err = shape.get(&d1.transact()).unwrap_err();
+        assert_eq!(err.path, Path::from([PathSegment::Key("size".into())]));
+    }
+}
@@ -8,7 +8,7 @@ use std::sync::Arc;
 use thiserror::Error;
 
 use crate::atomic::AtomicRef;
-use crate::block::{EmbedPrelim, ItemContent, ItemPtr, Prelim};
+use crate::block::{BlockCell, EmbedPrelim, ItemContent, ItemPtr, Prelim};
 use crate::iter::{
     AsIter, BlockIterator, BlockSliceIterator, IntoBlockIter, MoveIter, RangeIter, TxnIterator,
     Values,
@@ -124,6 +124,19 @@ impl<P: AsRef<Branch>> WeakRef<P> {
     pub fn end_id(&self) -> Option<&ID> {
         self.source().quote_end.id()
     }
+
+    /// Checks if elements quoted by current weak link have been removed and garbage collected,
+    /// so that the link can no longer be resolved. Observers of a link are notified when it
+    /// becomes dangling with a [WeakEvent] for which [WeakEvent::is_invalidated] returns true.
+    ///
+    /// Links, which quoted elements have been deleted but not collected yet (eg. because
+    /// of [crate::Options::skip_gc]), are not dangling.
+    pub fn is_dangling<T: ReadTxn>(&self, txn: &T) -> bool {
+        match self.try_source() {
+            Some(source) => source.is_dangling(txn),
+            None => false,
+        }
+    }
 }
 
 impl<P: From<BranchPtr>> From<BranchPtr> for WeakRef<P> {
@@ -461,7 +474,8 @@ impl<T> From<WeakPrelim<T>> for In {
 
 pub struct WeakEvent {
     pub(crate) current_target: BranchPtr,
-    target: BranchPtr,
+    pub(crate) target: BranchPtr,
+    invalidated: bool,
 }
 
 impl WeakEvent {
@@ -470,9 +484,25 @@ impl WeakEvent {
         WeakEvent {
             target: branch_ref,
             current_target,
+            invalidated: false,
+        }
+    }
+
+    pub(crate) fn invalidated(branch_ref: BranchPtr) -> Self {
+        WeakEvent {
+            invalidated: true,
+            ..Self::new(branch_ref)
         }
     }
 
+    /// Checks if this event notifies that the quoted elements of a weak link have been garbage
+    /// collected, so the link has become dangling (see: [WeakRef::is_dangling]). Invalidation
+    /// events are emitted on transaction commit after garbage collection - or by
+    /// [TransactionMut::gc_with] - which happens after the regular observer calls.
+    pub fn is_invalidated(&self) -> bool {
+        self.invalidated
+    }
+
     pub fn as_target<T: From<BranchPtr>>(&self) -> WeakRef<T> {
         WeakRef(T::from(self.target))
     }
@@ -506,6 +536,50 @@ impl LinkSource {
         }
     }
 
+    /// Checks if all elements of a quoted range have been garbage collected. Single element
+    /// links, which have been moved to another element (like map entries being overwritten),
+    /// are never dangling.
+    pub(crate) fn is_dangling<T: ReadTxn>(&self, txn: &T) -> bool {
+        if self.is_single() && self.first_item.get_owned().is_some() {
+            return false;
+        }
+        let (start, end) = match (self.quote_start.id(), self.quote_end.id()) {
+            (Some(start), Some(end)) => (start, end),
+            _ => return false,
+        };
+        let mut curr = match txn.store().blocks.get_block(start) {
+            Some(BlockCell::Block(item)) => Some(ItemPtr::from(item)),
+            // blocks are turned into GC ranges only together with their parent collection,
+            // so the rest of the quoted range is gone as well
+            Some(BlockCell::GC(_)) => return true,
+            None => return false,
+        };
+        while let Some(item) = curr.as_deref() {
+            let is_last = item.contains(end);
+            // range of the clocks within current block, which belong to a quoted range
+            let from = if item.contains(start) && self.quote_start.assoc == Assoc::After {
+                start.clock + 1
+            } else {
+                item.id.clock.max(start.clock)
+            };
+            let to = if !is_last {
+                item.id.clock + item.len
+            } else if self.quote_end.assoc == Assoc::Before {
+                end.clock
+            } else {
+                end.clock + 1
+            };
+            if from < to && !matches!(item.content, ItemContent::Deleted(_)) {
+                return false;
+            }
+            if is_last {
+                break;
+            }
+            curr = item.right;
+        }
+        true
+    }
+
     /// Remove reference to current weak link from all items it quotes.
     pub(crate) fn unlink_all(&self, txn: &mut TransactionMut, branch_ptr: BranchPtr) {
         let mut i = self.first_item.take().map(|arc| *arc).to_iter().moved();
@@ -877,8 +951,8 @@ mod test {
     use crate::types::{Attrs, EntryChange, Event, Out, ToJson};
     use crate::Assoc::{After, Before};
     use crate::{
-        Array, ArrayRef, DeepObservable, Doc, GetString, Map, MapPrelim, MapRef, Observable,
-        Quotable, Text, TextRef, Transact, XmlTextRef,
+        Array, ArrayRef, DeepObservable, Doc, GcPolicy, GetString, Map, MapPrelim, MapRef,
+        Observable, Options, Quotable, Text, TextRef, Transact, XmlTextRef,
     };
 
     #[test]
@@ -1274,6 +1348,91 @@ mod test {
         assert_eq!(l2.try_deref_value(&d2.transact()), None);
     }
 
+    #[test]
+    fn invalidate_on_gc() {
+        let doc = Doc::with_options(Options {
+            skip_gc: true,
+            ..Options::default()
+        });
+        let array = doc.get_or_insert_array("array");
+        let map = doc.get_or_insert_map("map");
+        let (single, range) = {
+            let mut txn = doc.transact_mut();
+            array.insert_range(&mut txn, 0, ["A", "B", "C", "D"]);
+            let single = array.quote(&txn, 1..=1).unwrap();
+            let single = map.insert(&mut txn, "single", single);
+            let range = array.quote(&txn, 2..=3).unwrap();
+            let range = map.insert(&mut txn, "range", range);
+            (single, range)
+        };
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let _sub1 = {
+            let events = events.clone();
+            single.observe(move |_, e| events.lock().unwrap().push(("single", e.is_invalidated())))
+        };
+        let _sub2 = {
+            let events = events.clone();
+            range.observe(move |_, e| events.lock().unwrap().push(("range", e.is_invalidated())))
+        };
+
+        array.remove_range(&mut doc.transact_mut(), 1, 2); // [A, D]
+        let mut observed = std::mem::take(&mut *events.lock().unwrap());
+        observed.sort();
+        assert_eq!(observed, vec![("range", false), ("single", false)]);
+        assert!(!single.is_dangling(&doc.transact()));
+
+        doc.transact_mut().gc_with(&GcPolicy::default());
+        assert_eq!(
+            std::mem::take(&mut *events.lock().unwrap()),
+            vec![("single", true)]
+        );
+        let txn = doc.transact();
+        assert!(single.is_dangling(&txn));
+        assert_eq!(single.unquote(&txn).count(), 0);
+        // last element of quoted range still exists
+        assert!(!range.is_dangling(&txn));
+        drop(txn);
+
+        array.remove(&mut doc.transact_mut(), 1);
+        doc.transact_mut().gc_with(&GcPolicy::default());
+        assert_eq!(
+            std::mem::take(&mut *events.lock().unwrap()),
+            vec![("range", false), ("range", true)]
+        );
+        assert!(range.is_dangling(&doc.transact()));
+    }
+
+    #[test]
+    fn link_with_collected_boundaries_is_not_dangling() {
+        let doc = Doc::with_options(Options {
+            skip_gc: true,
+            ..Options::default()
+        });
+        let array = doc.get_or_insert_array("array");
+        let map = doc.get_or_insert_map("map");
+        let link = {
+            let mut txn = doc.transact_mut();
+            array.insert_range(&mut txn, 0, ["A", "B", "C", "D", "E"]);
+            let link = array.quote(&txn, 1..=3).unwrap();
+            map.insert(&mut txn, "link", link)
+        };
+
+        array.remove(&mut doc.transact_mut(), 3); // [A, B, C, E]
+        array.remove(&mut doc.transact_mut(), 1); // [A, C, E]
+        doc.transact_mut().gc_with(&GcPolicy::default());
+        let txn = doc.transact();
+        // quoted element in the middle of a range still exists
+        assert!(!link.is_dangling(&txn));
+        let values: Vec<_> = link.unquote(&txn).collect();
+        assert_eq!(values, vec![Out::from("C")]);
+        drop(txn);
+
+        array.remove(&mut doc.transact_mut(), 1);
+        doc.transact_mut().gc_with(&GcPolicy::default());
+        assert!(link.is_dangling(&doc.transact()));
+    }
+
     #[test]
     fn observe_array() {
         let d1 = Doc::with_client_id(1);
@@ -11,6 +11,7 @@ use crate::block::{EmbedPrelim, Item, ItemContent, ItemPosition, ItemPtr, Prelim
 use crate::block_iter::BlockIter;
 use crate::transaction::TransactionMut;
 use crate::types::text::{diff_between, TextEvent, YChange};
+use crate::types::xml_markup::XmlWriter;
 use crate::types::{
     event_change_set, event_keys, AsPrelim, Branch, BranchPtr, Change, ChangeSet, DefaultPrelim,
     Delta, Entries, EntryChange, MapRef, Out, Path, RootRef, SharedRef, ToJson, TypePtr, TypeRef,
@@ -20,6 +21,8 @@ use crate::{
     ReadTxn, StickyIndex, Text, TextRef, ID,
 };
 
+pub use crate::types::xml_markup::XmlParseError;
+
 pub trait XmlPrelim: Prelim {}
 
 /// Trait shared by preliminary types that can be used as XML nodes: [XmlElementPrelim],
@@ -276,16 +279,17 @@ impl GetString for XmlElementRef {
     /// Converts current XML node into a textual representation. This representation if flat, it
     /// doesn't include any indentation.
     fn get_string<T: ReadTxn>(&self, txn: &T) -> String {
+        txn.track(&self.0);
         let tag: &str = self.tag();
         let inner = self.0;
         let mut s = String::new();
         write!(&mut s, "<{}", tag).unwrap();
-        let attributes = Attributes(inner.entries(txn));
+        let attributes = Attributes(inner.item_entries(txn));
         for (k, v) in attributes {
             write!(&mut s, " {}=\"{}\"", k, v).unwrap();
         }
         write!(&mut s, ">").unwrap();
-        for i in inner.iter(txn) {
+        for i in inner.items(txn) {
             if !i.is_deleted() {
                 for content in i.content.get_content() {
                     write!(&mut s, "{}", content.to_string(txn)).unwrap();
@@ -581,7 +585,8 @@ impl Observable for XmlTextRef {
 }
 
 impl GetString for XmlTextRef {
-    fn get_string<T: ReadTxn>(&self, _txn: &T) -> String {
+    fn get_string<T: ReadTxn>(&self, txn: &T) -> String {
+        txn.track(&self.0);
         XmlTextRef::get_string_fragment(self.0.start, None, None)
     }
 }
@@ -805,9 +810,10 @@ impl GetString for XmlFragmentRef {
     /// Converts current XML node into a textual representation. This representation if flat, it
     /// doesn't include any indentation.
     fn get_string<T: ReadTxn>(&self, txn: &T) -> String {
+        txn.track(&self.0);
         let inner = self.0;
         let mut s = String::new();
-        for i in inner.iter(txn) {
+        for i in inner.items(txn) {
             if !i.is_deleted() {
                 for content in i.content.get_content() {
                     write!(&mut s, "{}", content.to_string(txn)).unwrap();
@@ -818,6 +824,32 @@ impl GetString for XmlFragmentRef {
     }
 }
 
+impl ToJson for XmlFragmentRef {
+    /// Converts current XML fragment into a JSON string containing its textual representation,
+    /// the same way [Out::YXmlFragment] is converted.
+    fn to_json<T: ReadTxn>(&self, txn: &T) -> Any {
+        Any::from(self.get_string(txn))
+    }
+
+    fn to_json_writer<T: ReadTxn, W: std::io::Write>(
+        &self,
+        txn: &T,
+        writer: &mut W,
+    ) -> std::io::Result<()> {
+        writer.write_all(b"\"")?;
+        for i in self.0.items(txn) {
+            if !i.is_deleted() {
+                for content in i.content.get_content() {
+                    // JSON escaping works char by char, so nodes can be escaped one by one
+                    let escaped = serde_json::to_string(&content.to_string(txn))?;
+                    writer.write_all(&escaped.as_bytes()[1..escaped.len() - 1])?;
+                }
+            }
+        }
+        writer.write_all(b"\"")
+    }
+}
+
 impl DeepObservable for XmlFragmentRef {}
 impl Observable for XmlFragmentRef {
     type Event = XmlEvent;
@@ -893,7 +925,7 @@ impl DefaultPrelim for XmlFragmentRef {
 /// A preliminary type that will be materialized into an [XmlFragmentRef] once it will be integrated
 /// into Yrs document.
 #[derive(Debug, Clone, PartialEq, Default)]
-pub struct XmlFragmentPrelim(Vec<XmlIn>);
+pub struct XmlFragmentPrelim(pub(crate) Vec<XmlIn>);
 
 impl XmlFragmentPrelim {
     pub fn new<I, T>(iter: I) -> Self
@@ -1014,6 +1046,7 @@ pub trait Xml: AsRef<Branch> {
     /// Returns a value of an attribute given its `attr_name`. Returns `None` if no such attribute
     /// can be found inside of a current XML element.
     fn get_attribute<T: ReadTxn>(&self, txn: &T, attr_name: &str) -> Option<String> {
+        txn.track(self.as_ref());
         let branch = self.as_ref();
         let value = branch.get(txn, attr_name)?;
         Some(value.to_string(txn))
@@ -1022,6 +1055,7 @@ pub trait Xml: AsRef<Branch> {
     /// Returns an unordered iterator over all attributes (key-value pairs), that can be found
     /// inside of a current XML element.
     fn attributes<'a, T: ReadTxn>(&'a self, txn: &'a T) -> Attributes<'a, &'a T, T> {
+        txn.track(self.as_ref());
         Attributes(Entries::new(&self.as_ref().map, txn))
     }
 
@@ -1047,12 +1081,14 @@ pub trait XmlFragment: AsRef<Branch> {
     /// It does NOT include nested children of its children - for such cases use [Self::successors]
     /// iterator.
     fn children<'a, T: ReadTxn>(&self, txn: &'a T) -> XmlNodes<'a, T> {
+        txn.track(self.as_ref());
         let iter = BlockIter::new(BranchPtr::from(self.as_ref()));
         XmlNodes::new(iter, txn)
     }
 
     /// Returns a number of elements stored in current array.
-    fn len<T: ReadTxn>(&self, _txn: &T) -> u32 {
+    fn len<T: ReadTxn>(&self, txn: &T) -> u32 {
+        txn.track(self.as_ref());
         self.as_ref().len()
     }
 
@@ -1110,7 +1146,8 @@ pub trait XmlFragment: AsRef<Branch> {
 
     /// Retrieves a value stored at a given `index`. Returns `None` when provided index was out
     /// of the range of a current array.
-    fn get<T: ReadTxn>(&self, _txn: &T, index: u32) -> Option<XmlOut> {
+    fn get<T: ReadTxn>(&self, txn: &T, index: u32) -> Option<XmlOut> {
+        txn.track(self.as_ref());
         let branch = self.as_ref();
         let (content, _) = branch.get_at(index)?;
         if let ItemContent::Type(inner) = content {
@@ -1164,6 +1201,271 @@ pub trait XmlFragment: AsRef<Branch> {
     fn successors<'a, T: ReadTxn>(&'a self, txn: &'a T) -> TreeWalker<'a, &'a T, T> {
         TreeWalker::new(self.as_ref(), txn)
     }
+
+    /// Returns a plain text content of all descendant text nodes of a current XML node, stripped
+    /// of any markup and formatting attributes. Block-level boundaries are honored by separating
+    /// the content of consecutive block elements (eg. paragraphs) with a new line character, while
+    /// `<br>` elements are replaced with a new line. A whitespace found inside of text nodes is
+    /// handled according to a given `policy`.
+    ///
+    /// Elements with well-known inline HTML tag names (like `<b>`, `<em>` or `<span>`) are
+    /// treated as inline, all other elements are considered block-level.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use yrs::types::xml::WhitespacePolicy;
+    /// use yrs::{Doc, Transact, XmlElementPrelim, XmlFragment, XmlTextPrelim};
+    ///
+    /// let doc = Doc::new();
+    /// let xml = doc.get_or_insert_xml_fragment("article");
+    /// let mut txn = doc.transact_mut();
+    /// let h1 = xml.push_back(&mut txn, XmlElementPrelim::empty("h1"));
+    /// h1.push_back(&mut txn, XmlTextPrelim::new("  Title "));
+    /// let p = xml.push_back(&mut txn, XmlElementPrelim::empty("p"));
+    /// p.push_back(&mut txn, XmlTextPrelim::new("Hello, "));
+    /// let b = p.push_back(&mut txn, XmlElementPrelim::empty("b"));
+    /// b.push_back(&mut txn, XmlTextPrelim::new("world"));
+    ///
+    /// assert_eq!(xml.text_content(&txn, WhitespacePolicy::Preserve), "  Title \nHello, world");
+    /// assert_eq!(xml.text_content(&txn, WhitespacePolicy::Collapse), "Title\nHello, world");
+    /// ```
+    fn text_content<T: ReadTxn>(&self, txn: &T, policy: WhitespacePolicy) -> String {
+        let mut writer = TextContentWriter::new(policy);
+        writer.write_children(txn, self.as_ref());
+        writer.buf
+    }
+
+    /// Returns a descendant XML element, which attribute designated by [Doc::set_xml_id_index]
+    /// is equal to a given `id`. Lookups are served by the index, without traversing the XML tree.
+    /// Returns `None` if no such element exists or if the index has not been enabled.
+    ///
+    /// The index is updated when a transaction is committed, so changes made by a transaction
+    /// which is still in progress are not reflected by this method. If many elements share
+    /// the same `id` (ie. because it was assigned concurrently by different peers), any of them
+    /// can be returned.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use yrs::{Doc, Transact, Xml, XmlElementPrelim, XmlFragment};
+    ///
+    /// let doc = Doc::new();
+    /// doc.set_xml_id_index(Some("id")).unwrap();
+    /// let xml = doc.get_or_insert_xml_fragment("xml");
+    /// let p = {
+    ///     let mut txn = doc.transact_mut();
+    ///     let div = xml.push_back(&mut txn, XmlElementPrelim::empty("div"));
+    ///     let p = div.push_back(&mut txn, XmlElementPrelim::empty("p"));
+    ///     p.insert_attribute(&mut txn, "id", "intro");
+    ///     p
+    /// };
+    ///
+    /// let txn = doc.transact();
+    /// assert_eq!(xml.get_by_id(&txn, "intro"), Some(p));
+    /// assert_eq!(xml.get_by_id(&txn, "unknown"), None);
+    /// ```
+    ///
+    /// [Doc::set_xml_id_index]: crate::Doc::set_xml_id_index
+    fn get_by_id<T: ReadTxn>(&self, txn: &T, id: &str) -> Option<XmlElementRef> {
+        let store = txn.store();
+        let index = store.xml_id_index.as_deref()?;
+        let this = self.as_ref();
+        index
+            .get(id)
+            .iter()
+            .find(|element| store.is_alive(element) && this.is_parent_of(element.item))
+            .map(|element| XmlElementRef::from(*element))
+    }
+
+    /// Parses a given XML/HTML `markup` and inserts the resulting nodes, starting at a given
+    /// `index`. Elements (together with their attributes and nested children) are inserted as
+    /// [XmlElementRef]s, while text is inserted as [XmlTextRef]s. Returns inserted nodes.
+    ///
+    /// Parsing is lenient enough to accept common HTML: attribute values may be unquoted or
+    /// missing, void elements like `<br>` or `<img>` don't need to be closed and the content of
+    /// `<script>` and `<style>` elements is kept as raw text. Comments, processing instructions
+    /// and doctype declarations are skipped, predefined and numeric character references are
+    /// decoded and text consisting only of whitespace is ignored.
+    ///
+    /// Markup is parsed before any change is made, so if it's malformed, an error is returned and
+    /// the document stays untouched.
+    ///
+    /// # Panics
+    ///
+    /// This method will panic if provided `index` is greater than the current length of this
+    /// fragment.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use yrs::{Doc, Transact, XmlFragment};
+    ///
+    /// let doc = Doc::new();
+    /// let xml = doc.get_or_insert_xml_fragment("article");
+    /// let mut txn = doc.transact_mut();
+    /// xml.insert_xml(&mut txn, 0, r#"<p class=intro>Hello, <b>world</b> &amp; all<br></p>"#)
+    ///     .unwrap();
+    ///
+    /// assert_eq!(
+    ///     xml.to_xml_string(&txn, false),
+    ///     r#"<p class="intro">Hello, <b>world</b> &amp; all<br/></p>"#
+    /// );
+    /// ```
+    fn insert_xml(
+        &self,
+        txn: &mut TransactionMut,
+        index: u32,
+        markup: &str,
+    ) -> Result<Vec<XmlOut>, XmlParseError> {
+        let nodes = crate::types::xml_markup::parse(markup)?;
+        let mut result = Vec::with_capacity(nodes.len());
+        for (i, node) in nodes.into_iter().enumerate() {
+            result.push(self.insert(txn, index + i as u32, node));
+        }
+        Ok(result)
+    }
+
+    /// Returns an XML string representation of this XML node. Unlike [GetString::get_string],
+    /// text and attribute values are escaped, attributes are written in alphabetical order and
+    /// empty elements are written as self-closing tags, so the result can be parsed back with
+    /// [XmlFragment::insert_xml]. For [XmlElementRef], the element's own tag is included.
+    ///
+    /// If `pretty` is set, nested elements are placed in separate lines and indented with two
+    /// spaces. Elements with mixed text and element content are written as they are, since
+    /// indentation would change their text.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use yrs::{Doc, Transact, XmlFragment};
+    ///
+    /// let doc = Doc::new();
+    /// let xml = doc.get_or_insert_xml_fragment("article");
+    /// let mut txn = doc.transact_mut();
+    /// xml.insert_xml(&mut txn, 0, "<ul><li>a &lt; b</li><li><i>c</i></li></ul>")
+    ///     .unwrap();
+    ///
+    /// assert_eq!(
+    ///     xml.to_xml_string(&txn, true),
+    ///     "<ul>\n  <li>a &lt; b</li>\n  <li>\n    <i>c</i>\n  </li>\n</ul>"
+    /// );
+    /// ```
+    fn to_xml_string<T: ReadTxn>(&self, txn: &T, pretty: bool) -> String {
+        let mut writer = XmlWriter::new(txn, if pretty { Some("  ") } else { None });
+        let branch = self.as_ref();
+        if let TypeRef::XmlElement(_) = branch.type_ref() {
+            writer.write_node(
+                &XmlOut::Element(XmlElementRef::from(BranchPtr::from(branch))),
+                0,
+            );
+        } else {
+            writer.write_children(branch, 0);
+        }
+        writer.buf
+    }
+}
+
+/// Defines how a whitespace inside of XML text nodes is handled by [XmlFragment::text_content].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WhitespacePolicy {
+    /// Text nodes are copied as they are.
+    #[default]
+    Preserve,
+    /// Every sequence of whitespace characters is replaced with a single space, while whitespace
+    /// at the beginning and the end of each block is removed, similar to how HTML renders text.
+    Collapse,
+}
+
+/// Names of HTML elements treated as inline by [XmlFragment::text_content].
+const INLINE_ELEMENTS: &[&str] = &[
+    "a", "abbr", "b", "bdi", "bdo", "cite", "code", "data", "del", "dfn", "em", "i", "ins", "kbd",
+    "mark", "q", "s", "samp", "small", "span", "strong", "sub", "sup", "time", "u", "var",
+];
+
+struct TextContentWriter {
+    buf: String,
+    policy: WhitespacePolicy,
+    /// Block boundary has been reached, new line should precede any further content.
+    pending_break: bool,
+    /// Collapsed whitespace, which should be written before any further content of current block.
+    pending_space: bool,
+}
+
+impl TextContentWriter {
+    fn new(policy: WhitespacePolicy) -> Self {
+        TextContentWriter {
+            buf: String::new(),
+            policy,
+            pending_break: false,
+            pending_space: false,
+        }
+    }
+
+    fn write_children<T: ReadTxn>(&mut self, txn: &T, branch: &Branch) {
+        let mut ptr = branch.start;
+        while let Some(item) = ptr.as_deref() {
+            if !item.is_deleted() {
+                if let ItemContent::Type(inner) = &item.content {
+                    self.write_node(txn, BranchPtr::from(inner));
+                }
+            }
+            ptr = item.right;
+        }
+    }
+
+    fn write_node<T: ReadTxn>(&mut self, txn: &T, branch: BranchPtr) {
+        match XmlOut::try_from(branch) {
+            Ok(XmlOut::Text(text)) => {
+                let text: &TextRef = text.as_ref();
+                self.write_text(&text.get_string(txn));
+            }
+            Ok(XmlOut::Element(element)) => {
+                let tag = element.tag();
+                if tag.eq_ignore_ascii_case("br") {
+                    self.buf.push('\n');
+                    self.pending_break = false;
+                    self.pending_space = false;
+                } else if INLINE_ELEMENTS.iter().any(|t| tag.eq_ignore_ascii_case(t)) {
+                    self.write_children(txn, &branch);
+                } else {
+                    self.block_boundary();
+                    self.write_children(txn, &branch);
+                    self.block_boundary();
+                }
+            }
+            Ok(XmlOut::Fragment(_)) => self.write_children(txn, &branch),
+            Err(_) => {}
+        }
+    }
+
+    fn block_boundary(&mut self) {
+        self.pending_break = true;
+        self.pending_space = false;
+    }
+
+    fn write_text(&mut self, text: &str) {
+        for c in text.chars() {
+            if self.policy == WhitespacePolicy::Collapse && c.is_whitespace() {
+                self.pending_space = !self.pending_break && !self.at_line_start();
+                continue;
+            }
+            if self.pending_break {
+                if !self.at_line_start() {
+                    self.buf.push('\n');
+                }
+                self.pending_break = false;
+            } else if self.pending_space {
+                self.buf.push(' ');
+            }
+            self.pending_space = false;
+            self.buf.push(c);
+        }
+    }
+
+    fn at_line_start(&self) -> bool {
+        self.buf.is_empty() || self.buf.ends_with('\n')
+    }
 }
 
 /// Iterator over the attributes (key-value pairs represented as a strings) of an [XmlElement].
@@ -1510,7 +1812,7 @@ mod test {
 
     use crate::test_utils::exchange_updates;
     use crate::transaction::ReadTxn;
-    use crate::types::xml::{Xml, XmlFragment, XmlOut};
+    use crate::types::xml::{WhitespacePolicy, Xml, XmlFragment, XmlOut, XmlParseError};
     use crate::types::{Attrs, Change, EntryChange, Out};
     use crate::updates::decoder::Decode;
     use crate::updates::encoder::{Encoder, EncoderV1};
@@ -1537,6 +1839,227 @@ mod test {
         assert_eq!(xml2.get_attribute(&t2, "height"), Some("10".to_string()));
     }
 
+    #[test]
+    fn text_content() {
+        let doc = Doc::with_client_id(1);
+        let xml = doc.get_or_insert_xml_fragment("xml");
+        let mut txn = doc.transact_mut();
+        assert_eq!(xml.text_content(&txn, WhitespacePolicy::Preserve), "");
+
+        let p1 = xml.push_back(&mut txn, XmlElementPrelim::empty("paragraph"));
+        let text = p1.push_back(&mut txn, XmlTextPrelim::new("first  line"));
+        text.format(
+            &mut txn,
+            0,
+            5,
+            HashMap::from([("bold".into(), true.into())]),
+        );
+        p1.push_back(&mut txn, XmlElementPrelim::empty("br"));
+        p1.push_back(&mut txn, XmlTextPrelim::new(" second\tline "));
+        let list = xml.push_back(&mut txn, XmlElementPrelim::empty("ul"));
+        for item in ["one", "two"] {
+            let li = list.push_back(&mut txn, XmlElementPrelim::empty("li"));
+            let span = li.push_back(&mut txn, XmlElementPrelim::empty("SPAN"));
+            span.push_back(&mut txn, XmlTextPrelim::new(item));
+            li.push_back(&mut txn, XmlTextPrelim::new("\n"));
+        }
+        let removed = xml.push_back(&mut txn, XmlElementPrelim::empty("p"));
+        removed.push_back(&mut txn, XmlTextPrelim::new("removed"));
+        xml.remove(&mut txn, 2);
+        xml.push_back(&mut txn, XmlTextPrelim::new("tail"));
+
+        assert_eq!(
+            xml.text_content(&txn, WhitespacePolicy::Preserve),
+            "first  line\n second\tline \none\ntwo\ntail"
+        );
+        assert_eq!(
+            xml.text_content(&txn, WhitespacePolicy::Collapse),
+            "first line\nsecond line\none\ntwo\ntail"
+        );
+        assert_eq!(
+            list.text_content(&txn, WhitespacePolicy::Collapse),
+            "one\ntwo"
+        );
+    }
+
+    #[test]
+    fn get_by_id() {
+        let d1 = Doc::with_client_id(1);
+        let f1 = d1.get_or_insert_xml_fragment("xml");
+        let existing = {
+            let mut txn = d1.transact_mut();
+            let div = f1.push_back(&mut txn, XmlElementPrelim::empty("div"));
+            div.insert_attribute(&mut txn, "data-uid", "a");
+            div
+        };
+        // index is built from existing document state
+        d1.set_xml_id_index(Some("data-uid")).unwrap();
+        assert_eq!(f1.get_by_id(&d1.transact(), "a"), Some(existing.clone()));
+
+        let d2 = Doc::with_client_id(2);
+        d2.set_xml_id_index(Some("data-uid")).unwrap();
+        let f2 = d2.get_or_insert_xml_fragment("xml");
+        exchange_updates(&[&d1, &d2]);
+        let a2 = f2.get_by_id(&d2.transact(), "a").unwrap();
+        assert_eq!(a2.tag().as_ref(), "div");
+
+        // nested elements inserted remotely, with attributes set in the same transaction
+        {
+            let mut txn = d1.transact_mut();
+            let p = existing.push_back(&mut txn, XmlElementPrelim::empty("p"));
+            p.insert_attribute(&mut txn, "data-uid", "b");
+            let span = p.push_back(&mut txn, XmlElementPrelim::empty("span"));
+            span.insert_attribute(&mut txn, "data-uid", "c");
+        }
+        exchange_updates(&[&d1, &d2]);
+        {
+            let txn = d2.transact();
+            let b = f2.get_by_id(&txn, "b").unwrap();
+            assert_eq!(b.tag().as_ref(), "p");
+            assert_eq!(b.get_by_id(&txn, "c").unwrap().tag().as_ref(), "span");
+            // only descendants are returned
+            assert_eq!(b.get_by_id(&txn, "a"), None);
+        }
+
+        // changed and removed attributes
+        {
+            let mut txn = d2.transact_mut();
+            let b = f2.get_by_id(&txn, "b").unwrap();
+            b.insert_attribute(&mut txn, "data-uid", "b2");
+            a2.remove_attribute(&mut txn, &"data-uid");
+        }
+        exchange_updates(&[&d1, &d2]);
+        for (doc, xml) in [(&d1, &f1), (&d2, &f2)] {
+            let txn = doc.transact();
+            assert_eq!(xml.get_by_id(&txn, "a"), None);
+            assert_eq!(xml.get_by_id(&txn, "b"), None);
+            assert_eq!(xml.get_by_id(&txn, "b2").unwrap().tag().as_ref(), "p");
+        }
+
+        // removed elements and their descendants are no longer indexed
+        f1.remove(&mut d1.transact_mut(), 0);
+        exchange_updates(&[&d1, &d2]);
+        for (doc, xml) in [(&d1, &f1), (&d2, &f2)] {
+            let txn = doc.transact();
+            assert_eq!(xml.get_by_id(&txn, "b2"), None);
+            assert_eq!(xml.get_by_id(&txn, "c"), None);
+        }
+
+        // index is updated on commit
+        let mut txn = d1.transact_mut();
+        let div = f1.push_back(&mut txn, XmlElementPrelim::empty("div"));
+        div.insert_attribute(&mut txn, "data-uid", "d");
+        assert_eq!(f1.get_by_id(&txn, "d"), None);
+        drop(txn);
+        assert_eq!(f1.get_by_id(&d1.transact(), "d"), Some(div));
+
+        d1.set_xml_id_index(None).unwrap();
+        assert_eq!(f1.get_by_id(&d1.transact(), "d"), None);
+    }
+
+    #[test]
+    fn insert_xml() {
+        let doc = Doc::with_client_id(1);
+        let xml = doc.get_or_insert_xml_fragment("xml");
+        let copy = doc.get_or_insert_xml_fragment("copy");
+        let fragment = doc.get_or_insert_xml_fragment("pretty");
+        let mut txn = doc.transact_mut();
+
+        let markup = r#"<!DOCTYPE html>
+            <div id='main' hidden data-x=1>
+              <!-- comment -->
+              <p>a &lt; b &amp;&#x20;<i title="&quot;q&quot;">c</i><br>&#169;</p>
+              <img src="x.png"/>
+              <script>if (a < b) {}</script><![CDATA[<raw>]]></div>"#;
+        let nodes = xml.insert_xml(&mut txn, 0, markup).unwrap();
+        assert_eq!(nodes.len(), 1);
+        let div = xml.get(&txn, 0).unwrap().into_xml_element().unwrap();
+        assert_eq!(div.tag().as_ref(), "div");
+        assert_eq!(div.get_attribute(&txn, "id"), Some("main".into()));
+        assert_eq!(div.get_attribute(&txn, "hidden"), Some("".into()));
+        assert_eq!(div.get_attribute(&txn, "data-x"), Some("1".into()));
+        assert_eq!(div.len(&txn), 4);
+        let p = div.get(&txn, 0).unwrap().into_xml_element().unwrap();
+        assert_eq!(p.len(&txn), 4);
+        let text = p.get(&txn, 0).unwrap().into_xml_text().unwrap();
+        assert_eq!(text.get_string(&txn), "a < b & ");
+
+        let expected = concat!(
+            r#"<div data-x="1" hidden="" id="main">"#,
+            r#"<p>a &lt; b &amp; <i title="&quot;q&quot;">c</i><br/>©</p>"#,
+            r#"<img src="x.png"/><script>if (a < b) {}</script>&lt;raw&gt;</div>"#
+        );
+        assert_eq!(xml.to_xml_string(&txn, false), expected);
+        assert_eq!(div.to_xml_string(&txn, false), expected);
+
+        // serialized markup can be parsed back into the same tree
+        copy.insert_xml(&mut txn, 0, expected).unwrap();
+        assert_eq!(copy.to_xml_string(&txn, false), expected);
+
+        // nodes are inserted at a given index
+        xml.insert_xml(&mut txn, 0, "<h1>title</h1>text").unwrap();
+        xml.insert_xml(&mut txn, 3, "<footer/>").unwrap();
+        assert_eq!(xml.len(&txn), 4);
+        let pretty = xml.to_xml_string(&txn, true);
+        assert!(pretty.starts_with("<h1>title</h1>text<div"));
+        assert!(pretty.ends_with("</div><footer/>"));
+
+        fragment
+            .insert_xml(&mut txn, 0, "<ul><li>a</li><li><b/><i/></li></ul><hr>")
+            .unwrap();
+        assert_eq!(
+            fragment.to_xml_string(&txn, true),
+            "<ul>\n  <li>a</li>\n  <li>\n    <b/>\n    <i/>\n  </li>\n</ul>\n<hr/>"
+        );
+
+        // formatted text is written as wrapping elements
+        let t = fragment.insert(&mut txn, 0, XmlTextPrelim::new(""));
+        let bold = Attrs::from([("b".into(), true.into())]);
+        t.insert(&mut txn, 0, "x<y");
+        t.format(&mut txn, 2, 1, bold);
+        assert_eq!(
+            fragment.to_xml_string(&txn, false),
+            "x&lt;<b>y</b><ul><li>a</li><li><b/><i/></li></ul><hr/>"
+        );
+    }
+
+    #[test]
+    fn insert_xml_malformed() {
+        let doc = Doc::with_client_id(1);
+        let xml = doc.get_or_insert_xml_fragment("xml");
+        let mut txn = doc.transact_mut();
+
+        assert_eq!(
+            xml.insert_xml(&mut txn, 0, "<p><b>x</p>").unwrap_err(),
+            XmlParseError::MismatchedTag {
+                position: 3,
+                expected: "b".into(),
+                found: "p".into()
+            }
+        );
+        assert_eq!(
+            xml.insert_xml(&mut txn, 0, "<p>x").unwrap_err(),
+            XmlParseError::UnclosedTag("p".into())
+        );
+        assert_eq!(
+            xml.insert_xml(&mut txn, 0, "x</p>").unwrap_err(),
+            XmlParseError::UnmatchedClosingTag("p".into())
+        );
+        assert_eq!(
+            xml.insert_xml(&mut txn, 0, "<p a='1").unwrap_err(),
+            XmlParseError::UnexpectedEnd(7)
+        );
+        assert_eq!(
+            xml.insert_xml(&mut txn, 0, "< p>").unwrap_err(),
+            XmlParseError::UnexpectedChar {
+                position: 1,
+                expected: "name"
+            }
+        );
+        // failed parsing doesn't modify the document
+        assert_eq!(xml.len(&txn), 0);
+    }
+
     #[test]
     fn tree_walker() {
         let doc = Doc::with_client_id(1);
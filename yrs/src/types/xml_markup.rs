@@ -0,0 +1,442 @@
+//! Conversion between XML/HTML markup and XML shared collections, used by
+//! [XmlFragment::insert_xml] and [XmlFragment::to_xml_string].
+
+use std::fmt::Write;
+use std::sync::Arc;
+
+use thiserror::Error;
+
+use crate::branch::{Branch, BranchPtr};
+use crate::types::text::YChange;
+use crate::types::xml::{Xml, XmlFragment, XmlIn, XmlOut};
+use crate::{Any, Out, ReadTxn, Text, XmlElementPrelim, XmlFragmentRef, XmlTextPrelim, XmlTextRef};
+
+/// HTML elements which never have any content and don't require a closing tag.
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source",
+    "track", "wbr",
+];
+
+/// HTML elements which content is not parsed as markup, but treated as a raw text.
+const RAW_TEXT_ELEMENTS: &[&str] = &["script", "style"];
+
+/// Error returned by [XmlFragment::insert_xml] when provided markup couldn't be parsed.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum XmlParseError {
+    /// Markup ended in the middle of a tag, comment or other construct.
+    #[error("unexpected end of input at position {0}")]
+    UnexpectedEnd(usize),
+
+    /// An unexpected character has been found.
+    #[error("expected {expected} at position {position}")]
+    UnexpectedChar {
+        position: usize,
+        expected: &'static str,
+    },
+
+    /// A closing tag doesn't match a tag that is currently open.
+    #[error(
+        "closing tag </{found}> at position {position} doesn't match opening tag <{expected}>"
+    )]
+    MismatchedTag {
+        position: usize,
+        expected: String,
+        found: String,
+    },
+
+    /// Markup ended before the closing tag of an element has been found.
+    #[error("element <{0}> has not been closed")]
+    UnclosedTag(String),
+
+    /// A closing tag has been found without any opening tag.
+    #[error("closing tag </{0}> has no matching opening tag")]
+    UnmatchedClosingTag(String),
+}
+
+/// Parses a given XML/HTML `markup` into a list of preliminary XML nodes.
+pub(crate) fn parse(markup: &str) -> Result<Vec<XmlIn>, XmlParseError> {
+    let mut parser = Parser {
+        src: markup,
+        pos: 0,
+    };
+    let (nodes, closing) = parser.parse_nodes()?;
+    match closing {
+        None => Ok(nodes),
+        Some(found) => Err(XmlParseError::UnmatchedClosingTag(found)),
+    }
+}
+
+struct Parser<'a> {
+    src: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.src[self.pos..]
+    }
+
+    fn expect(&mut self, s: &'static str) -> Result<(), XmlParseError> {
+        if self.rest().starts_with(s) {
+            self.pos += s.len();
+            Ok(())
+        } else if self.rest().is_empty() {
+            Err(XmlParseError::UnexpectedEnd(self.pos))
+        } else {
+            Err(XmlParseError::UnexpectedChar {
+                position: self.pos,
+                expected: s,
+            })
+        }
+    }
+
+    /// Moves past a given `terminator`, returning everything that has been skipped.
+    fn take_until(&mut self, terminator: &str) -> Result<&'a str, XmlParseError> {
+        match self.rest().find(terminator) {
+            Some(i) => {
+                let taken = &self.rest()[..i];
+                self.pos += i + terminator.len();
+                Ok(taken)
+            }
+            None => Err(XmlParseError::UnexpectedEnd(self.src.len())),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn read_name(&mut self) -> Result<&'a str, XmlParseError> {
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| c.is_whitespace() || matches!(c, '/' | '>' | '=' | '<'))
+            .unwrap_or(rest.len());
+        if len == 0 {
+            return if rest.is_empty() {
+                Err(XmlParseError::UnexpectedEnd(self.pos))
+            } else {
+                Err(XmlParseError::UnexpectedChar {
+                    position: self.pos,
+                    expected: "name",
+                })
+            };
+        }
+        self.pos += len;
+        Ok(&rest[..len])
+    }
+
+    /// Parses a sequence of sibling nodes until the end of input or a closing tag is reached.
+    /// Returns parsed nodes together with the name of a closing tag, which has been consumed.
+    fn parse_nodes(&mut self) -> Result<(Vec<XmlIn>, Option<String>), XmlParseError> {
+        let mut nodes = Vec::new();
+        let mut text = String::new();
+        loop {
+            let rest = self.rest();
+            if rest.is_empty() {
+                push_text(&mut nodes, &mut text);
+                return Ok((nodes, None));
+            } else if rest.starts_with("<!--") {
+                self.pos += 4;
+                self.take_until("-->")?;
+            } else if rest.starts_with("<![CDATA[") {
+                self.pos += 9;
+                text.push_str(self.take_until("]]>")?);
+            } else if rest.starts_with("<!") || rest.starts_with("<?") {
+                // doctype or processing instruction
+                self.take_until(">")?;
+            } else if rest.starts_with("</") {
+                self.pos += 2;
+                let name = self.read_name()?.to_string();
+                self.skip_whitespace();
+                self.expect(">")?;
+                push_text(&mut nodes, &mut text);
+                return Ok((nodes, Some(name)));
+            } else if rest.starts_with('<') {
+                push_text(&mut nodes, &mut text);
+                nodes.push(XmlIn::Element(self.parse_element()?));
+            } else {
+                let len = rest.find('<').unwrap_or(rest.len());
+                decode_entities(&rest[..len], &mut text);
+                self.pos += len;
+            }
+        }
+    }
+
+    fn parse_element(&mut self) -> Result<XmlElementPrelim, XmlParseError> {
+        let start = self.pos;
+        self.expect("<")?;
+        let tag = self.read_name()?;
+        let mut element = XmlElementPrelim::empty(tag);
+        loop {
+            self.skip_whitespace();
+            let rest = self.rest();
+            if rest.starts_with("/>") {
+                self.pos += 2;
+                return Ok(element);
+            } else if rest.starts_with('>') {
+                self.pos += 1;
+                break;
+            }
+            let (name, value) = self.parse_attribute()?;
+            element.attributes.insert(name.into(), value);
+        }
+
+        if is_one_of(tag, VOID_ELEMENTS) {
+            return Ok(element);
+        }
+        if is_one_of(tag, RAW_TEXT_ELEMENTS) {
+            let rest = self.rest();
+            let end = rest
+                .to_ascii_lowercase()
+                .find(&format!("</{}", tag.to_ascii_lowercase()))
+                .ok_or_else(|| XmlParseError::UnclosedTag(tag.to_string()))?;
+            if end > 0 {
+                let text = XmlTextPrelim::new(&rest[..end]);
+                element.children.push(text.into());
+            }
+            self.pos += end + 2;
+            self.read_name()?;
+            self.skip_whitespace();
+            self.expect(">")?;
+            return Ok(element);
+        }
+
+        let (children, closing) = self.parse_nodes()?;
+        element.children = children;
+        match closing {
+            Some(found) if found == tag => Ok(element),
+            Some(found) => Err(XmlParseError::MismatchedTag {
+                position: start,
+                expected: tag.to_string(),
+                found,
+            }),
+            None => Err(XmlParseError::UnclosedTag(tag.to_string())),
+        }
+    }
+
+    fn parse_attribute(&mut self) -> Result<(&'a str, String), XmlParseError> {
+        let name = self.read_name()?;
+        self.skip_whitespace();
+        if !self.rest().starts_with('=') {
+            // attribute without a value, ie. `<input disabled>`
+            return Ok((name, String::new()));
+        }
+        self.pos += 1;
+        self.skip_whitespace();
+        let raw = match self.rest().chars().next() {
+            Some(quote @ '"') | Some(quote @ '\'') => {
+                self.pos += 1;
+                self.take_until(if quote == '"' { "\"" } else { "'" })?
+            }
+            Some(_) => {
+                let rest = self.rest();
+                let len = rest
+                    .find(|c: char| c.is_whitespace() || c == '>')
+                    .unwrap_or(rest.len());
+                self.pos += len;
+                &rest[..len]
+            }
+            None => return Err(XmlParseError::UnexpectedEnd(self.pos)),
+        };
+        let mut value = String::with_capacity(raw.len());
+        decode_entities(raw, &mut value);
+        Ok((name, value))
+    }
+}
+
+fn is_one_of(tag: &str, tags: &[&str]) -> bool {
+    tags.iter().any(|t| tag.eq_ignore_ascii_case(t))
+}
+
+/// Appends a text node with accumulated `text`, unless it consists of whitespace only.
+fn push_text(nodes: &mut Vec<XmlIn>, text: &mut String) {
+    if !text.trim().is_empty() {
+        nodes.push(XmlTextPrelim::new(std::mem::take(text)).into());
+    } else {
+        text.clear();
+    }
+}
+
+/// Decodes predefined XML entities and numeric character references found in `src`.
+/// Unrecognized entities are copied as they are.
+fn decode_entities(src: &str, buf: &mut String) {
+    let mut rest = src;
+    while let Some(i) = rest.find('&') {
+        buf.push_str(&rest[..i]);
+        rest = &rest[i..];
+        let decoded = rest.find(';').and_then(|end| {
+            let c = match &rest[1..end] {
+                "lt" => '<',
+                "gt" => '>',
+                "amp" => '&',
+                "quot" => '"',
+                "apos" => '\'',
+                "nbsp" => '\u{a0}',
+                entity => {
+                    let code = if let Some(hex) = entity.strip_prefix("#x") {
+                        u32::from_str_radix(hex, 16).ok()?
+                    } else if let Some(hex) = entity.strip_prefix("#X") {
+                        u32::from_str_radix(hex, 16).ok()?
+                    } else {
+                        entity.strip_prefix('#')?.parse().ok()?
+                    };
+                    char::from_u32(code)?
+                }
+            };
+            Some((c, end + 1))
+        });
+        match decoded {
+            Some((c, len)) => {
+                buf.push(c);
+                rest = &rest[len..];
+            }
+            None => {
+                buf.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    buf.push_str(rest);
+}
+
+fn escape(src: &str, buf: &mut String, attribute: bool) {
+    for c in src.chars() {
+        match c {
+            '&' => buf.push_str("&amp;"),
+            '<' => buf.push_str("&lt;"),
+            '>' => buf.push_str("&gt;"),
+            '"' if attribute => buf.push_str("&quot;"),
+            c => buf.push(c),
+        }
+    }
+}
+
+/// Serializes XML nodes into markup, optionally indenting nested elements.
+pub(crate) struct XmlWriter<'a, T> {
+    txn: &'a T,
+    pub buf: String,
+    indent: Option<&'a str>,
+    /// Set while writing the content of raw text elements, which must not be escaped.
+    raw: bool,
+}
+
+impl<'a, T: ReadTxn> XmlWriter<'a, T> {
+    pub fn new(txn: &'a T, indent: Option<&'a str>) -> Self {
+        XmlWriter {
+            txn,
+            buf: String::new(),
+            indent,
+            raw: false,
+        }
+    }
+
+    pub fn write_node(&mut self, node: &XmlOut, depth: usize) {
+        match node {
+            XmlOut::Fragment(fragment) => {
+                self.write_children(fragment.as_ref(), depth);
+            }
+            XmlOut::Text(text) => self.write_text(text),
+            XmlOut::Element(element) => {
+                let tag = element.tag().clone();
+                self.buf.push('<');
+                self.buf.push_str(&tag);
+                let mut attributes: Vec<_> = element.attributes(self.txn).collect();
+                attributes.sort_by(|a, b| a.0.cmp(b.0));
+                for (name, value) in attributes {
+                    write!(self.buf, " {}=\"", name).unwrap();
+                    escape(&value, &mut self.buf, true);
+                    self.buf.push('"');
+                }
+                if element.first_child().is_none() {
+                    self.buf.push_str("/>");
+                    return;
+                }
+                self.buf.push('>');
+                let raw = self.raw;
+                self.raw = is_one_of(&tag, RAW_TEXT_ELEMENTS);
+                let indented = self.write_children(element.as_ref(), depth + 1);
+                self.raw = raw;
+                if indented {
+                    self.new_line(depth);
+                }
+                write!(self.buf, "</{}>", tag).unwrap();
+            }
+        }
+    }
+
+    /// Writes all children of a given `branch`, returning true if they were placed in separate
+    /// indented lines.
+    pub fn write_children(&mut self, branch: &Branch, depth: usize) -> bool {
+        let fragment = XmlFragmentRef::from(BranchPtr::from(branch));
+        if self.indent.is_some() && has_text(branch, self.txn) {
+            // mixed content is written as it is, since indentation would change its text
+            let indent = self.indent.take();
+            for child in fragment.children(self.txn) {
+                self.write_node(&child, depth);
+            }
+            self.indent = indent;
+            false
+        } else {
+            for (i, child) in fragment.children(self.txn).enumerate() {
+                if i > 0 || depth > 0 {
+                    self.new_line(depth);
+                }
+                self.write_node(&child, depth);
+            }
+            self.indent.is_some()
+        }
+    }
+
+    fn write_text(&mut self, text: &XmlTextRef) {
+        for chunk in text.diff(self.txn, YChange::identity) {
+            let mut attrs: Vec<(&Arc<str>, &Any)> = Vec::new();
+            if let Some(attributes) = chunk.attributes.as_deref() {
+                attrs.extend(attributes.iter());
+                attrs.sort_by(|a, b| a.0.cmp(b.0));
+            }
+            // formatting attributes are written as wrapping elements
+            for (name, value) in attrs.iter() {
+                write!(self.buf, "<{}", name).unwrap();
+                if let Any::Map(value) = value {
+                    let mut entries: Vec<_> = value.iter().collect();
+                    entries.sort_by(|a, b| a.0.cmp(b.0));
+                    for (key, value) in entries {
+                        write!(self.buf, " {}=\"", key).unwrap();
+                        escape(&value.to_string(), &mut self.buf, true);
+                        self.buf.push('"');
+                    }
+                }
+                self.buf.push('>');
+            }
+            match &chunk.insert {
+                Out::Any(Any::String(s)) if self.raw => self.buf.push_str(s),
+                Out::Any(Any::String(s)) => escape(s, &mut self.buf, false),
+                Out::Any(any) => escape(&any.to_string(), &mut self.buf, false),
+                Out::YXmlElement(e) => self.write_node(&XmlOut::Element(e.clone()), 0),
+                Out::YXmlFragment(f) => self.write_node(&XmlOut::Fragment(f.clone()), 0),
+                Out::YXmlText(t) => self.write_text(t),
+                _ => {}
+            }
+            for (name, _) in attrs.iter().rev() {
+                write!(self.buf, "</{}>", name).unwrap();
+            }
+        }
+    }
+
+    fn new_line(&mut self, depth: usize) {
+        if let Some(indent) = self.indent {
+            self.buf.push('\n');
+            for _ in 0..depth {
+                self.buf.push_str(indent);
+            }
+        }
+    }
+}
+
+/// Checks if any of the direct children of a given `branch` is a text node.
+fn has_text<T: ReadTxn>(branch: &Branch, txn: &T) -> bool {
+    XmlFragmentRef::from(BranchPtr::from(branch))
+        .children(txn)
+        .any(|child| matches!(child, XmlOut::Text(_)))
+}
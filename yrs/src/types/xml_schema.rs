@@ -0,0 +1,537 @@
+use crate::branch::{Branch, BranchPtr};
+use crate::types::xml::{XmlDeltaPrelim, XmlIn};
+use crate::types::TypeRef;
+use crate::{
+    ReadTxn, TransactionMut, Xml, XmlElementPrelim, XmlElementRef, XmlFragment, XmlOut,
+    XmlTextPrelim,
+};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use thiserror::Error;
+
+/// Registry of XML node types allowed in a collaborative document, similar to ProseMirror schemas.
+/// Each node type defines which attributes it accepts and which nodes it may contain.
+///
+/// Nodes inserted through [XmlSchema::insert] or created with node factories returned by
+/// [XmlSchema::element] are validated before they are integrated, and have default values of
+/// missing attributes filled in. Since other peers may not follow the same rules, content received
+/// from remote updates can be checked with [XmlSchema::validate].
+///
+/// # Example
+///
+/// ```rust
+/// use yrs::types::xml_schema::{AttributeSpec, NodeSpec, XmlSchema, XmlSchemaError};
+/// use yrs::{GetString, Transact, Xml, XmlElementPrelim};
+///
+/// let schema = XmlSchema::new(NodeSpec::new().children(["paragraph"]))
+///     .node("paragraph", NodeSpec::new()
+///         .attribute("align", AttributeSpec::one_of(["left", "center"]).with_default("left"))
+///         .text(true));
+///
+/// let doc = yrs::Doc::new();
+/// let root = doc.get_or_insert_xml_fragment("prosemirror");
+/// let mut txn = doc.transact_mut();
+///
+/// let p = schema.element("paragraph").unwrap().text("hello").push_back(&mut txn, &root).unwrap();
+/// assert_eq!(p.get_attribute(&txn, "align"), Some("left".to_string()));
+/// assert_eq!(root.get_string(&txn), "<paragraph align=\"left\">hello</paragraph>");
+///
+/// // nodes which don't match the schema are rejected
+/// let err = schema.insert(&mut txn, &p, 0, XmlElementPrelim::empty("paragraph"));
+/// assert!(matches!(err, Err(XmlSchemaError::ChildNotAllowed { .. })));
+/// assert!(schema.validate(&txn, &root).is_ok());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct XmlSchema {
+    root: NodeSpec,
+    nodes: HashMap<Arc<str>, NodeSpec>,
+}
+
+impl XmlSchema {
+    /// Tag used by [XmlSchemaError] to refer to the root [XmlFragment] and nodes which are not
+    /// XML elements.
+    pub const ROOT: &'static str = "#root";
+
+    /// Creates a new schema. Given `root` specification describes the contents of top level
+    /// [XmlFragment]s. Its attribute specifications are ignored.
+    pub fn new(root: NodeSpec) -> Self {
+        XmlSchema {
+            root,
+            nodes: HashMap::new(),
+        }
+    }
+
+    /// Registers XML elements with a given `tag`.
+    pub fn node<S: Into<Arc<str>>>(mut self, tag: S, spec: NodeSpec) -> Self {
+        self.nodes.insert(tag.into(), spec);
+        self
+    }
+
+    /// Returns a specification of XML elements with a given `tag`.
+    pub fn get(&self, tag: &str) -> Option<&NodeSpec> {
+        self.nodes.get(tag)
+    }
+
+    /// Returns a factory of XML elements with a given `tag`.
+    pub fn element(&self, tag: &str) -> Result<NodeFactory<'_>, XmlSchemaError> {
+        let (tag, _) = self
+            .nodes
+            .get_key_value(tag)
+            .ok_or_else(|| XmlSchemaError::UnknownNode(tag.into()))?;
+        Ok(NodeFactory {
+            schema: self,
+            prelim: XmlElementPrelim::empty(tag.clone()),
+        })
+    }
+
+    /// Inserts a given XML element as a child of a `parent` node at a given `index`. Missing
+    /// attributes of inserted element and its descendants are filled with their default values.
+    /// If the result doesn't match the schema, nothing is inserted.
+    pub fn insert<P: XmlFragment>(
+        &self,
+        txn: &mut TransactionMut,
+        parent: &P,
+        index: u32,
+        mut node: XmlElementPrelim,
+    ) -> Result<XmlElementRef, XmlSchemaError> {
+        let parent_tag = tag_of(parent.as_ref());
+        self.check_child(&parent_tag, Some(&node.tag))?;
+        self.prepare(&mut node)?;
+        Ok(parent.insert(txn, index, node))
+    }
+
+    /// Fills in default attribute values of a given `node` and its descendants, then checks if they
+    /// match the schema.
+    fn prepare(&self, node: &mut XmlElementPrelim) -> Result<(), XmlSchemaError> {
+        let spec = self.spec(&node.tag)?;
+        for (name, attr) in spec.attributes.iter() {
+            if let Some(default) = &attr.default {
+                if !node.attributes.contains_key(name) {
+                    node.attributes.insert(name.clone(), default.clone());
+                }
+            }
+        }
+        spec.check_attributes(&node.tag, node.attributes.iter().map(|(k, v)| (&**k, &**v)))?;
+        self.prepare_children(&node.tag, &mut node.children)
+    }
+
+    fn prepare_children(
+        &self,
+        tag: &Arc<str>,
+        children: &mut [XmlIn],
+    ) -> Result<(), XmlSchemaError> {
+        for child in children.iter_mut() {
+            match child {
+                XmlIn::Element(element) => {
+                    self.check_child(tag, Some(&element.tag))?;
+                    self.prepare(element)?;
+                }
+                XmlIn::Text(_) => self.check_child(tag, None)?,
+                // fragment contents are spliced into their parent
+                XmlIn::Fragment(fragment) => self.prepare_children(tag, &mut fragment.0)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks if contents of a given XML node and all of its descendants match the schema. This
+    /// includes content integrated from remote updates, which might not have been validated by
+    /// other peers.
+    pub fn validate<T, N>(&self, txn: &T, node: &N) -> Result<(), XmlSchemaError>
+    where
+        T: ReadTxn,
+        N: XmlFragment,
+    {
+        let tag = tag_of(node.as_ref());
+        if tag.as_ref() != Self::ROOT {
+            let spec = self.spec(&tag)?;
+            let element = XmlElementRef::from(BranchPtr::from(node.as_ref()));
+            let attributes: Vec<_> = element.attributes(txn).collect();
+            spec.check_attributes(&tag, attributes.iter().map(|(k, v)| (*k, v.as_str())))?;
+        }
+        for child in node.children(txn) {
+            match child {
+                XmlOut::Element(element) => {
+                    self.check_child(&tag, Some(element.tag()))?;
+                    self.validate(txn, &element)?;
+                }
+                XmlOut::Text(_) => self.check_child(&tag, None)?,
+                XmlOut::Fragment(fragment) => {
+                    for child in fragment.children(txn) {
+                        if let XmlOut::Element(element) = child {
+                            self.check_child(&tag, Some(element.tag()))?;
+                            self.validate(txn, &element)?;
+                        } else {
+                            self.check_child(&tag, None)?;
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn spec(&self, tag: &str) -> Result<&NodeSpec, XmlSchemaError> {
+        if tag == Self::ROOT {
+            Ok(&self.root)
+        } else {
+            self.nodes
+                .get(tag)
+                .ok_or_else(|| XmlSchemaError::UnknownNode(tag.into()))
+        }
+    }
+
+    /// Checks if a `parent` node can contain an element with a given `child` tag or a text node
+    /// if `child` is `None`.
+    fn check_child(
+        &self,
+        parent: &Arc<str>,
+        child: Option<&Arc<str>>,
+    ) -> Result<(), XmlSchemaError> {
+        let spec = self.spec(parent)?;
+        match child {
+            None if spec.text => Ok(()),
+            None => Err(XmlSchemaError::TextNotAllowed(parent.clone())),
+            Some(child) => {
+                self.spec(child)?;
+                if spec.children.contains(child) {
+                    Ok(())
+                } else {
+                    Err(XmlSchemaError::ChildNotAllowed {
+                        parent: parent.clone(),
+                        child: child.clone(),
+                    })
+                }
+            }
+        }
+    }
+}
+
+fn tag_of(branch: &Branch) -> Arc<str> {
+    match branch.type_ref() {
+        TypeRef::XmlElement(tag) => tag.clone(),
+        _ => XmlSchema::ROOT.into(),
+    }
+}
+
+/// Specification of XML elements registered in [XmlSchema].
+#[derive(Debug, Clone, Default)]
+pub struct NodeSpec {
+    attributes: HashMap<Arc<str>, AttributeSpec>,
+    children: HashSet<Arc<str>>,
+    text: bool,
+    allow_unknown_attributes: bool,
+}
+
+impl NodeSpec {
+    /// Creates a specification of a node without attributes and children.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allows an attribute with a given `name`.
+    pub fn attribute<S: Into<Arc<str>>>(mut self, name: S, spec: AttributeSpec) -> Self {
+        self.attributes.insert(name.into(), spec);
+        self
+    }
+
+    /// Allows attributes not defined with [NodeSpec::attribute].
+    pub fn allow_unknown_attributes(mut self) -> Self {
+        self.allow_unknown_attributes = true;
+        self
+    }
+
+    /// Allows elements with given tags to be nested inside of current node.
+    pub fn children<I, S>(mut self, tags: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<Arc<str>>,
+    {
+        self.children.extend(tags.into_iter().map(Into::into));
+        self
+    }
+
+    /// Sets if text nodes can be nested inside of current node.
+    pub fn text(mut self, allowed: bool) -> Self {
+        self.text = allowed;
+        self
+    }
+
+    fn check_attributes<'a, I>(&self, tag: &Arc<str>, attributes: I) -> Result<(), XmlSchemaError>
+    where
+        I: IntoIterator<Item = (&'a str, &'a str)>,
+    {
+        let mut present = HashSet::new();
+        for (name, value) in attributes {
+            match self.attributes.get(name) {
+                Some(spec) => {
+                    if let Some(values) = &spec.values {
+                        if !values.iter().any(|v| v == value) {
+                            return Err(XmlSchemaError::InvalidAttribute {
+                                node: tag.clone(),
+                                attribute: name.into(),
+                                value: value.into(),
+                            });
+                        }
+                    }
+                    present.insert(name);
+                }
+                None if self.allow_unknown_attributes => {}
+                None => {
+                    return Err(XmlSchemaError::UnknownAttribute {
+                        node: tag.clone(),
+                        attribute: name.into(),
+                    })
+                }
+            }
+        }
+        for (name, spec) in self.attributes.iter() {
+            if spec.required && !present.contains(name.as_ref()) {
+                return Err(XmlSchemaError::MissingAttribute {
+                    node: tag.clone(),
+                    attribute: name.clone(),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Specification of an attribute allowed by [NodeSpec].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AttributeSpec {
+    /// If true, nodes without this attribute don't match the schema.
+    pub required: bool,
+    /// Value assigned to the attribute when it's missing from a newly inserted node.
+    pub default: Option<String>,
+    /// Allowed values of the attribute. If `None`, any value is allowed.
+    pub values: Option<Vec<String>>,
+}
+
+impl AttributeSpec {
+    /// Specification of an optional attribute, which accepts any value.
+    pub fn optional() -> Self {
+        Self::default()
+    }
+
+    /// Specification of an attribute, which must be present on every node.
+    pub fn required() -> Self {
+        AttributeSpec {
+            required: true,
+            ..Self::default()
+        }
+    }
+
+    /// Specification of an optional attribute, which accepts only given values.
+    pub fn one_of<I, S>(values: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        AttributeSpec {
+            values: Some(values.into_iter().map(Into::into).collect()),
+            ..Self::default()
+        }
+    }
+
+    /// Sets a value assigned to the attribute when it's missing from a newly inserted node.
+    pub fn with_default<S: Into<String>>(mut self, value: S) -> Self {
+        self.default = Some(value.into());
+        self
+    }
+}
+
+/// Factory of XML elements of a single type registered in [XmlSchema]. Created with
+/// [XmlSchema::element].
+#[derive(Debug)]
+pub struct NodeFactory<'a> {
+    schema: &'a XmlSchema,
+    prelim: XmlElementPrelim,
+}
+
+impl<'a> NodeFactory<'a> {
+    /// Sets an attribute of a created element.
+    pub fn attribute<K: Into<Arc<str>>, V: Into<String>>(mut self, name: K, value: V) -> Self {
+        self.prelim.attributes.insert(name.into(), value.into());
+        self
+    }
+
+    /// Appends a text node to the children of a created element.
+    pub fn text<S: Into<String>>(mut self, text: S) -> Self {
+        let text: XmlDeltaPrelim = XmlTextPrelim::new(text).into();
+        self.prelim.children.push(XmlIn::Text(text));
+        self
+    }
+
+    /// Appends a node to the children of a created element.
+    pub fn child<N: Into<XmlIn>>(mut self, node: N) -> Self {
+        self.prelim.children.push(node.into());
+        self
+    }
+
+    /// Returns a created element, validated against the schema, with missing attributes filled
+    /// with their default values.
+    pub fn build(mut self) -> Result<XmlElementPrelim, XmlSchemaError> {
+        self.schema.prepare(&mut self.prelim)?;
+        Ok(self.prelim)
+    }
+
+    /// Inserts a created element as a child of a `parent` node at a given `index`.
+    pub fn insert<P: XmlFragment>(
+        self,
+        txn: &mut TransactionMut,
+        parent: &P,
+        index: u32,
+    ) -> Result<XmlElementRef, XmlSchemaError> {
+        self.schema.insert(txn, parent, index, self.prelim)
+    }
+
+    /// Inserts a created element as the last child of a `parent` node.
+    pub fn push_back<P: XmlFragment>(
+        self,
+        txn: &mut TransactionMut,
+        parent: &P,
+    ) -> Result<XmlElementRef, XmlSchemaError> {
+        let index = parent.len(txn);
+        self.insert(txn, parent, index)
+    }
+}
+
+/// Error returned when XML nodes don't match an [XmlSchema].
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum XmlSchemaError {
+    /// Node type is not registered in the schema.
+    #[error("unknown node type `{0}`")]
+    UnknownNode(Arc<str>),
+    /// Element is not allowed as a child of its parent node.
+    #[error("`{child}` node is not allowed inside of `{parent}`")]
+    ChildNotAllowed { parent: Arc<str>, child: Arc<str> },
+    /// Text nodes are not allowed as children of a given node.
+    #[error("text is not allowed inside of `{0}`")]
+    TextNotAllowed(Arc<str>),
+    /// Required attribute is missing.
+    #[error("`{node}` node is missing required `{attribute}` attribute")]
+    MissingAttribute { node: Arc<str>, attribute: Arc<str> },
+    /// Attribute is not defined by the schema.
+    #[error("`{node}` node doesn't allow `{attribute}` attribute")]
+    UnknownAttribute { node: Arc<str>, attribute: Arc<str> },
+    /// Attribute has a value outside of its allowed values.
+    #[error("`{node}` node doesn't allow `{attribute}` attribute to be `{value}`")]
+    InvalidAttribute {
+        node: Arc<str>,
+        attribute: Arc<str>,
+        value: String,
+    },
+}
+
+#[cfg(test)]
+mod test {
+    use crate::types::xml_schema::{AttributeSpec, NodeSpec, XmlSchema, XmlSchemaError};
+    use crate::{Doc, GetString, Transact, Xml, XmlElementPrelim, XmlFragment, XmlTextPrelim};
+
+    fn schema() -> XmlSchema {
+        XmlSchema::new(NodeSpec::new().children(["paragraph", "heading"]))
+            .node("paragraph", NodeSpec::new().children(["strong"]).text(true))
+            .node(
+                "heading",
+                NodeSpec::new()
+                    .attribute(
+                        "level",
+                        AttributeSpec::one_of(["1", "2", "3"]).with_default("1"),
+                    )
+                    .attribute("id", AttributeSpec::required())
+                    .text(true),
+            )
+            .node("strong", NodeSpec::new().text(true))
+    }
+
+    #[test]
+    fn insert_validated() {
+        let schema = schema();
+        let d1 = Doc::with_client_id(1);
+        let root = d1.get_or_insert_xml_fragment("xml");
+        let mut txn = d1.transact_mut();
+
+        let p = schema
+            .element("paragraph")
+            .unwrap()
+            .text("hello ")
+            .child(XmlElementPrelim::new(
+                "strong",
+                [XmlTextPrelim::new("world").into()],
+            ))
+            .push_back(&mut txn, &root)
+            .unwrap();
+        let h = schema
+            .element("heading")
+            .unwrap()
+            .attribute("id", "h1")
+            .text("title")
+            .insert(&mut txn, &root, 0)
+            .unwrap();
+        assert_eq!(h.get_attribute(&txn, "level"), Some("1".into()));
+        assert_eq!(h.get_attribute(&txn, "id"), Some("h1".into()));
+        assert_eq!(
+            p.get_string(&txn),
+            "<paragraph>hello <strong>world</strong></paragraph>"
+        );
+
+        let err = schema
+            .element("heading")
+            .unwrap()
+            .push_back(&mut txn, &root);
+        assert_eq!(
+            err.unwrap_err(),
+            XmlSchemaError::MissingAttribute {
+                node: "heading".into(),
+                attribute: "id".into()
+            }
+        );
+        let err = schema
+            .element("heading")
+            .unwrap()
+            .attribute("id", "h2")
+            .attribute("level", "7")
+            .build();
+        assert!(matches!(err, Err(XmlSchemaError::InvalidAttribute { .. })));
+        let err = schema.insert(&mut txn, &p, 0, XmlElementPrelim::empty("heading"));
+        assert_eq!(
+            err.unwrap_err(),
+            XmlSchemaError::ChildNotAllowed {
+                parent: "paragraph".into(),
+                child: "heading".into()
+            }
+        );
+        let err = schema.insert(&mut txn, &root, 0, XmlElementPrelim::empty("div"));
+        assert_eq!(err.unwrap_err(), XmlSchemaError::UnknownNode("div".into()));
+        assert_eq!(root.len(&txn), 2);
+        assert!(schema.validate(&txn, &root).is_ok());
+    }
+
+    #[test]
+    fn validate_remote_content() {
+        let schema = schema();
+        let d1 = Doc::with_client_id(1);
+        let root = d1.get_or_insert_xml_fragment("xml");
+        let mut txn = d1.transact_mut();
+        // content inserted without validation, i.e. by a remote peer
+        let p = root.push_back(&mut txn, XmlElementPrelim::empty("paragraph"));
+        p.push_back(&mut txn, XmlTextPrelim::new("text"));
+        assert!(schema.validate(&txn, &root).is_ok());
+
+        p.insert_attribute(&mut txn, "style", "color: red");
+        assert_eq!(
+            schema.validate(&txn, &root),
+            Err(XmlSchemaError::UnknownAttribute {
+                node: "paragraph".into(),
+                attribute: "style".into()
+            })
+        );
+        p.remove_attribute(&mut txn, &"style");
+        root.push_back(&mut txn, XmlTextPrelim::new("loose text"));
+        assert_eq!(
+            schema.validate(&txn, &root),
+            Err(XmlSchemaError::TextNotAllowed(XmlSchema::ROOT.into()))
+        );
+    }
+}
@@ -4,7 +4,8 @@ use std::ops::{Deref, DerefMut};
 use std::sync::atomic::{AtomicPtr, Ordering};
 use std::sync::Arc;
 
-use crate::block::ItemPtr;
+use crate::blame::ActorKind;
+use crate::block::{ClientID, ItemPtr};
 use crate::branch::{Branch, BranchPtr};
 use crate::doc::TransactionAcqError;
 use crate::iter::TxnIterator;
@@ -82,6 +83,25 @@ where
         Self::with_scope_and_options(doc, scope, Options::default())
     }
 
+    /// Creates a new instance of the [UndoManager] working in a `scope` of a particular shared
+    /// type and document, which captures and reverts only the changes made by the local peer
+    /// (identified by [Doc::client_id]). Remote changes - even when interleaved with the local
+    /// ones within the same capture interval - are never reverted and never reset the redo stack.
+    /// This matches the behavior of y-undo.
+    ///
+    /// It's equivalent of calling [UndoManager::include_clients] with a local client ID only.
+    #[cfg(not(target_family = "wasm"))]
+    pub fn local_only<T>(doc: &Doc, scope: &T) -> Self
+    where
+        T: AsRef<Branch>,
+    {
+        let options = Options {
+            tracked_clients: HashSet::from([doc.client_id()]),
+            ..Options::default()
+        };
+        Self::with_scope_and_options(doc, scope, options)
+    }
+
     #[inline]
     pub fn doc(&self) -> &Doc {
         &self.0.doc
@@ -151,6 +171,13 @@ where
                 return true;
             }
         }
+        let tracked_actors = &inner.options.tracked_actors;
+        if !inner.undoing && !inner.redoing && !tracked_actors.is_empty() {
+            let kind = txn.actor().map(|a| a.kind).unwrap_or_default();
+            if !tracked_actors.contains(&kind) {
+                return true;
+            }
+        }
         !inner
             .scope
             .iter()
@@ -167,6 +194,27 @@ where
         }
         let undoing = inner.undoing;
         let redoing = inner.redoing;
+
+        let mut insertions = DeleteSet::new();
+        for (client, &end_clock) in txn.after_state().iter() {
+            let start_clock = txn.before_state.get(client);
+            let diff = end_clock - start_clock;
+            if diff != 0 {
+                insertions.insert(ID::new(*client, start_clock), diff);
+            }
+        }
+        let mut deletions = txn.delete_set.clone();
+        let tracked_clients = &inner.options.tracked_clients;
+        if !undoing && !redoing && !tracked_clients.is_empty() {
+            insertions = Self::filter_clients(&insertions, tracked_clients);
+            if txn.remote || !tracked_clients.contains(&txn.doc().client_id()) {
+                deletions = DeleteSet::new();
+            }
+            if insertions.is_empty() && deletions.is_empty() {
+                return; // no changes made by tracked clients
+            }
+        }
+
         if undoing {
             inner.last_change = 0; // next undo should not be appended to last stack item
         } else if !redoing {
@@ -177,14 +225,6 @@ where
             }
         }
 
-        let mut insertions = DeleteSet::new();
-        for (client, &end_clock) in txn.after_state().iter() {
-            let start_clock = txn.before_state.get(client);
-            let diff = end_clock - start_clock;
-            if diff != 0 {
-                insertions.insert(ID::new(*client, start_clock), diff);
-            }
-        }
         let now = inner.options.timestamp.now();
         let stack = if undoing {
             &mut inner.redo_stack
@@ -201,12 +241,21 @@ where
             // append change to last stack op
             if let Some(last_op) = stack.last_mut() {
                 // always true - we checked if stack is empty above
-                last_op.deletions.merge(txn.delete_set.clone());
+                last_op.deletions.merge(deletions.clone());
                 last_op.insertions.merge(insertions);
             }
         } else {
-            // create a new stack op
-            let item = StackItem::new(txn.delete_set.clone(), insertions);
+            // create a new stack op, making sure that its blocks won't be squashed together with
+            // the blocks of a previous one
+            let mut split_points = Vec::new();
+            for (client, ranges) in insertions.iter() {
+                for range in ranges.iter() {
+                    let id = ID::new(*client, range.start);
+                    txn.split_point(id);
+                    split_points.push(id);
+                }
+            }
+            let item = StackItem::new(deletions.clone(), insertions, split_points);
             stack.push(item);
         }
 
@@ -214,8 +263,7 @@ where
             inner.last_change = now;
         }
         // make sure that deleted structs are not gc'd
-        let ds = txn.delete_set.clone();
-        let mut deleted = ds.deleted_blocks();
+        let mut deleted = deletions.deleted_blocks();
         while let Some(slice) = deleted.next(txn) {
             if let Some(item) = slice.as_item() {
                 if inner.scope.iter().any(|b| b.is_parent_of(Some(item))) {
@@ -243,6 +291,19 @@ where
         last_op.meta = event.meta;
     }
 
+    /// Returns a subset of `ids` belonging to given `clients`.
+    fn filter_clients(ids: &DeleteSet, clients: &HashSet<ClientID>) -> DeleteSet {
+        let mut result = DeleteSet::new();
+        for (client, ranges) in ids.iter() {
+            if clients.contains(client) {
+                for range in ranges.iter() {
+                    result.insert(ID::new(*client, range.start), range.end - range.start);
+                }
+            }
+        }
+        result
+    }
+
     fn handle_destroy(txn: &TransactionMut, inner: &mut Inner<M>) {
         let origin = Origin::from(inner as *mut Inner<M> as usize);
         if inner.options.tracked_origins.remove(&origin) {
@@ -484,6 +545,25 @@ where
         inner.options.tracked_origins.remove(&origin.into());
     }
 
+    /// Extends a list of clients tracked by current undo manager by given `clients`. Once any
+    /// client is tracked, only changes made by tracked clients will be captured and reverted
+    /// by this undo manager (see: [Options::tracked_clients]).
+    pub fn include_clients(&mut self, clients: &[ClientID]) {
+        let inner = self.inner();
+        inner
+            .options
+            .tracked_clients
+            .extend(clients.iter().copied());
+    }
+
+    /// Removes given `clients` from the list of clients tracked by a current undo manager.
+    pub fn exclude_clients(&mut self, clients: &[ClientID]) {
+        let inner = self.inner();
+        for client in clients {
+            inner.options.tracked_clients.remove(client);
+        }
+    }
+
     /// Clears all [StackItem]s stored within current UndoManager, effectively resetting its state.
     pub fn clear(&mut self) -> Result<(), TransactionAcqError> {
         let inner = self.inner();
@@ -503,6 +583,7 @@ where
     }
 
     fn clear_item(scope: &HashSet<BranchPtr>, txn: &mut TransactionMut, stack_item: StackItem<M>) {
+        stack_item.remove_split_points(txn);
         let mut deleted = stack_item.deletions.deleted_blocks();
         while let Some(slice) = deleted.next(txn) {
             if let Some(item) = slice.as_item() {
@@ -651,6 +732,7 @@ where
     ) -> Option<StackItem<M>> {
         let mut result = None;
         while let Some(item) = stack.pop() {
+            item.remove_split_points(txn);
             let mut to_redo = HashSet::<ItemPtr>::new();
             let mut to_delete = Vec::<ItemPtr>::new();
             let mut change_performed = false;
@@ -728,6 +810,13 @@ impl<M> Drop for UndoManager<M> {
         let origin = Origin::from(Arc::as_ptr(&inner) as usize);
         inner.doc.unobserve_destroy(origin.clone()).unwrap();
         inner.doc.unobserve_after_transaction(origin).unwrap();
+        // split points of the remaining stack items would prevent their blocks from being squashed
+        // long after this undo manager is gone
+        if let Ok(mut txn) = inner.doc.try_transact_mut() {
+            for item in inner.undo_stack.iter().chain(inner.redo_stack.iter()) {
+                item.remove_split_points(&mut txn);
+            }
+        }
     }
 }
 
@@ -771,6 +860,17 @@ pub struct Options {
     /// If not provided, it will track only updates made within transaction with no origin defined.
     pub tracked_origins: HashSet<Origin>,
 
+    /// List of clients tracked by corresponding [UndoManager]. If not empty, only changes made
+    /// by these clients will be captured, undone and redone. Deletions carried by remote updates
+    /// don't contain information about their author, therefore they are captured only when they
+    /// were made locally by a tracked client.
+    pub tracked_clients: HashSet<ClientID>,
+
+    /// Kinds of actors tracked by corresponding [UndoManager]. If not empty, only changes made
+    /// within transactions of these actors (see: [TransactionMut::set_actor]) will be captured.
+    /// Transactions without an actor assigned are considered to be made by [ActorKind::Human].
+    pub tracked_actors: HashSet<ActorKind>,
+
     /// Custom logic decider, that along with [tracked_origins] can be used to determine if
     /// transaction changes should be captured or not.
     pub capture_transaction: Option<CaptureTransactionFn>,
@@ -788,6 +888,8 @@ impl Default for Options {
         Options {
             capture_timeout_millis: 500,
             tracked_origins: HashSet::new(),
+            tracked_clients: HashSet::new(),
+            tracked_actors: HashSet::new(),
             capture_transaction: None,
             timestamp: Arc::new(crate::sync::time::SystemClock),
         }
@@ -808,6 +910,8 @@ impl Default for Options {
 pub struct StackItem<T> {
     deletions: DeleteSet,
     insertions: DeleteSet,
+    /// Split points set by this stack item, see: [TransactionMut::split_point].
+    split_points: Vec<ID>,
 
     /// A custom user metadata that can be attached to a particular [StackItem]. It can be used
     /// to carry over the additional information (such as ie. user cursor position) between
@@ -815,11 +919,20 @@ pub struct StackItem<T> {
     pub meta: T,
 }
 
+impl<M> StackItem<M> {
+    fn remove_split_points(&self, txn: &mut TransactionMut) {
+        for id in self.split_points.iter() {
+            txn.remove_split_point(id);
+        }
+    }
+}
+
 impl<M: Default> StackItem<M> {
-    fn new(deletions: DeleteSet, insertions: DeleteSet) -> Self {
+    fn new(deletions: DeleteSet, insertions: DeleteSet, split_points: Vec<ID>) -> Self {
         StackItem {
             deletions,
             insertions,
+            split_points,
             meta: M::default(),
         }
     }
@@ -920,11 +1033,12 @@ pub enum EventKind {
 
 #[cfg(test)]
 mod test {
-    use std::collections::HashMap;
+    use std::collections::{HashMap, HashSet};
     use std::convert::TryInto;
     use std::sync::atomic::{AtomicUsize, Ordering};
     use std::sync::Arc;
 
+    use crate::blame::ActorKind;
     use crate::test_utils::exchange_updates;
     use crate::types::text::{Diff, YChange};
     use crate::types::{Attrs, ToJson};
@@ -1265,6 +1379,83 @@ mod test {
         assert_eq!(result.load(Ordering::SeqCst), 2);
     }
 
+    #[test]
+    fn undo_tracked_clients() {
+        // sync remote updates without origin, so that they are tracked by undo manager
+        fn sync(src: &Doc, dst: &Doc) {
+            let sv = dst.transact().state_vector();
+            let update = src.transact().encode_diff_v1(&sv);
+            let mut txn = dst.transact_mut();
+            txn.apply_update(Update::decode_v1(&update).unwrap());
+        }
+
+        let d1 = Doc::with_client_id(1);
+        let txt1 = d1.get_or_insert_text("text");
+        let d2 = Doc::with_client_id(2);
+        let txt2 = d2.get_or_insert_text("text");
+
+        let mut mgr = UndoManager::new(&d1, &txt1);
+        mgr.include_clients(&[1]);
+
+        txt1.push(&mut d1.transact_mut(), "hello");
+        sync(&d1, &d2);
+        mgr.reset();
+
+        txt2.push(&mut d2.transact_mut(), " world");
+        sync(&d2, &d1);
+        mgr.reset();
+
+        txt2.remove_range(&mut d2.transact_mut(), 0, 1);
+        sync(&d2, &d1);
+        assert_eq!(txt1.get_string(&d1.transact()), "ello world");
+        assert_eq!(mgr.undo_stack().len(), 1);
+
+        mgr.undo().unwrap();
+        assert_eq!(txt1.get_string(&d1.transact()), " world");
+        assert!(!mgr.can_undo());
+
+        mgr.redo().unwrap();
+        assert_eq!(txt1.get_string(&d1.transact()), "ello world");
+
+        // once no clients are tracked, all changes are captured again
+        mgr.exclude_clients(&[1]);
+        mgr.reset();
+        txt2.push(&mut d2.transact_mut(), "!");
+        sync(&d2, &d1);
+        assert_eq!(txt1.get_string(&d1.transact()), "ello world!");
+        mgr.undo().unwrap();
+        assert_eq!(txt1.get_string(&d1.transact()), "ello world");
+    }
+
+    #[test]
+    fn undo_tracked_actors() {
+        let doc = Doc::with_client_id(1);
+        let txt = doc.get_or_insert_text("text");
+        let mut mgr = UndoManager::with_scope_and_options(
+            &doc,
+            &txt,
+            Options {
+                tracked_actors: HashSet::from([ActorKind::Human]),
+                ..Options::default()
+            },
+        );
+
+        txt.push(&mut doc.transact_mut(), "hello");
+        mgr.reset();
+        {
+            let mut txn = doc.transact_mut();
+            txn.set_actor(ActorKind::Automation, "formatter");
+            txt.push(&mut txn, " world");
+        }
+        assert_eq!(mgr.undo_stack().len(), 1);
+
+        // undoing human edits leaves automated ones in place
+        mgr.undo().unwrap();
+        assert_eq!(txt.get_string(&doc.transact()), " world");
+        mgr.redo().unwrap();
+        assert_eq!(txt.get_string(&doc.transact()), "hello world");
+    }
+
     #[test]
     fn undo_until_change_performed() {
         let d1 = Doc::with_client_id(1);
@@ -1973,4 +2164,205 @@ mod test {
             any!({"s1":{"b1":[{"b2":[[232291652, -30]]}]}})
         );
     }
+
+    #[test]
+    fn undo_keeps_capture_boundaries() {
+        let doc = Doc::with_client_id(1);
+        let txt = doc.get_or_insert_text("text");
+        let mut mgr = UndoManager::new(&doc, &txt);
+
+        txt.push(&mut doc.transact_mut(), "hello");
+        mgr.reset();
+        txt.push(&mut doc.transact_mut(), " world");
+        mgr.reset();
+        txt.push(&mut doc.transact_mut(), "!");
+
+        // blocks inserted in different capture intervals are not squashed together
+        {
+            let txn = doc.transact();
+            let blocks = txn.store().blocks.get_client(&1).unwrap();
+            assert_eq!(blocks.len(), 3);
+        }
+
+        mgr.undo().unwrap();
+        assert_eq!(txt.get_string(&doc.transact()), "hello world");
+        mgr.undo().unwrap();
+        assert_eq!(txt.get_string(&doc.transact()), "hello");
+        mgr.redo().unwrap();
+        assert_eq!(txt.get_string(&doc.transact()), "hello world");
+
+        // once undo manager is cleared, split points are removed as well
+        mgr.clear().unwrap();
+        assert!(doc.transact().store().split_points.is_empty());
+
+        // ... and when undo manager is dropped
+        drop(mgr);
+        let mut mgr = UndoManager::new(&doc, &txt);
+        txt.push(&mut doc.transact_mut(), "?");
+        mgr.reset();
+        txt.push(&mut doc.transact_mut(), "?");
+        assert!(!doc.transact().store().split_points.is_empty());
+        drop(mgr);
+        assert!(doc.transact().store().split_points.is_empty());
+    }
+
+    #[test]
+    fn split_points_shared_by_undo_managers() {
+        let doc = Doc::with_client_id(1);
+        let txt = doc.get_or_insert_text("test");
+        let mut m1 = UndoManager::new(&doc, &txt);
+        let mut m2 = UndoManager::new(&doc, &txt);
+
+        txt.push(&mut doc.transact_mut(), "hello");
+        m1.reset();
+        m2.reset();
+        txt.push(&mut doc.transact_mut(), " world");
+        assert_eq!(doc.transact().store().split_points.len(), 2);
+
+        // split points used by the remaining undo manager are kept
+        drop(m1);
+        assert_eq!(doc.transact().store().split_points.len(), 2);
+        txt.push(&mut doc.transact_mut(), "!");
+        {
+            let txn = doc.transact();
+            let blocks = txn.store().blocks.get_client(&1).unwrap();
+            assert_eq!(blocks.len(), 2);
+        }
+        m2.undo().unwrap();
+        assert_eq!(txt.get_string(&doc.transact()), "hello");
+
+        drop(m2);
+        assert!(doc.transact().store().split_points.is_empty());
+    }
+
+    #[test]
+    fn undo_local_only() {
+        let d1 = Doc::with_client_id(1);
+        let txt1 = d1.get_or_insert_text("text");
+        let d2 = Doc::with_client_id(2);
+        let txt2 = d2.get_or_insert_text("text");
+
+        let mut mgr = UndoManager::local_only(&d1, &txt1);
+
+        // remote edits interleaved with local ones within the same capture interval
+        txt1.push(&mut d1.transact_mut(), "hello");
+        exchange_updates(&[&d1, &d2]);
+        txt2.push(&mut d2.transact_mut(), " world");
+        txt2.remove_range(&mut d2.transact_mut(), 0, 1);
+        {
+            // remote update applied without origin goes through undo manager capture
+            let update = d2
+                .transact()
+                .encode_state_as_update_v1(&d1.transact().state_vector());
+            d1.transact_mut()
+                .apply_update(Update::decode_v1(&update).unwrap());
+        }
+        txt1.push(&mut d1.transact_mut(), "!");
+        assert_eq!(txt1.get_string(&d1.transact()), "ello world!");
+        assert_eq!(mgr.undo_stack().len(), 1);
+
+        mgr.undo().unwrap();
+        assert_eq!(txt1.get_string(&d1.transact()), " world");
+        assert!(!mgr.can_undo());
+
+        // remote changes don't clear redo stack
+        txt2.push(&mut d2.transact_mut(), "?");
+        {
+            let update = d2
+                .transact()
+                .encode_state_as_update_v1(&d1.transact().state_vector());
+            d1.transact_mut()
+                .apply_update(Update::decode_v1(&update).unwrap());
+        }
+        assert!(mgr.can_redo());
+
+        mgr.redo().unwrap();
+        exchange_updates(&[&d1, &d2]);
+        assert_eq!(txt1.get_string(&d1.transact()), "ello world!?");
+        assert_eq!(txt2.get_string(&d2.transact()), "ello world!?");
+    }
+
+    #[test]
+    fn undo_local_only_formatting() {
+        let d1 = Doc::with_client_id(1);
+        let txt1 = d1.get_or_insert_text("text");
+        let d2 = Doc::with_client_id(2);
+        let txt2 = d2.get_or_insert_text("text");
+        let bold = Attrs::from([("bold".into(), true.into())]);
+        let italic = Attrs::from([("italic".into(), true.into())]);
+
+        txt1.push(&mut d1.transact_mut(), "abc");
+        exchange_updates(&[&d1, &d2]);
+
+        let mut mgr = UndoManager::local_only(&d1, &txt1);
+
+        // local formatting, remote insertion within formatted range and remote formatting
+        txt1.format(&mut d1.transact_mut(), 0, 3, bold.clone());
+        exchange_updates(&[&d1, &d2]);
+        txt2.insert(&mut d2.transact_mut(), 1, "x");
+        txt2.format(&mut d2.transact_mut(), 3, 1, italic.clone());
+        exchange_updates(&[&d1, &d2]);
+        txt1.format(&mut d1.transact_mut(), 3, 1, bold.clone());
+        exchange_updates(&[&d1, &d2]);
+        assert_eq!(mgr.undo_stack().len(), 1);
+
+        mgr.undo().unwrap();
+        exchange_updates(&[&d1, &d2]);
+        for (doc, txt) in [(&d1, &txt1), (&d2, &txt2)] {
+            let txn = doc.transact();
+            assert_eq!(txt.get_string(&txn), "axbc");
+            // local bold formatting has been reverted, remote italic formatting remains
+            assert_eq!(
+                txt.diff(&txn, YChange::identity),
+                vec![
+                    Diff::new("axb".into(), None),
+                    Diff::new("c".into(), Some(Box::new(italic.clone()))),
+                ]
+            );
+        }
+
+        mgr.redo().unwrap();
+        exchange_updates(&[&d1, &d2]);
+        let txn = d1.transact();
+        let diff = txt1.diff(&txn, YChange::identity);
+        assert_eq!(diff, txt2.diff(&d2.transact(), YChange::identity));
+        assert!(diff.iter().all(|d| match &d.attributes {
+            Some(attrs) => attrs.contains_key("bold"),
+            None => false,
+        }));
+    }
+
+    #[test]
+    fn undo_local_only_moves() {
+        let d1 = Doc::with_client_id(1);
+        let arr1 = d1.get_or_insert_array("array");
+        let d2 = Doc::with_client_id(2);
+        let arr2 = d2.get_or_insert_array("array");
+
+        arr1.insert_range(&mut d1.transact_mut(), 0, [1, 2, 3]);
+        exchange_updates(&[&d1, &d2]);
+
+        let mut mgr = UndoManager::local_only(&d1, &arr1);
+
+        // local insertion moved by remote peer, interleaved with a local move
+        arr1.insert(&mut d1.transact_mut(), 3, 4);
+        exchange_updates(&[&d1, &d2]);
+        arr2.move_to(&mut d2.transact_mut(), 3, 0);
+        exchange_updates(&[&d1, &d2]);
+        arr1.move_to(&mut d1.transact_mut(), 2, 1);
+        exchange_updates(&[&d1, &d2]);
+        assert_eq!(arr1.to_json(&d1.transact()), any!([4, 2, 1, 3]));
+        assert_eq!(mgr.undo_stack().len(), 1);
+
+        // both local insertion and local move are reverted, remote move remains
+        mgr.undo().unwrap();
+        exchange_updates(&[&d1, &d2]);
+        assert_eq!(arr1.to_json(&d1.transact()), any!([1, 2, 3]));
+        assert_eq!(arr2.to_json(&d2.transact()), any!([1, 2, 3]));
+
+        mgr.redo().unwrap();
+        exchange_updates(&[&d1, &d2]);
+        assert_eq!(arr1.to_json(&d1.transact()), arr2.to_json(&d2.transact()));
+        assert_eq!(arr1.len(&d1.transact()), 4);
+    }
 }
@@ -8,13 +8,14 @@ use crate::block::{
     BlockRange, ClientID, Item, ItemContent, ItemPtr, BLOCK_GC_REF_NUMBER, BLOCK_SKIP_REF_NUMBER,
     HAS_ORIGIN, HAS_PARENT_SUB, HAS_RIGHT_ORIGIN,
 };
-use crate::encoding::read::Error;
+use crate::encoding::read::{bounded_len, Error};
 use crate::id_set::DeleteSet;
 use crate::slice::ItemSlice;
 #[cfg(test)]
 use crate::store::Store;
 use crate::transaction::TransactionMut;
 use crate::types::TypePtr;
+use crate::updates::borrowed::BorrowedUpdate;
 use crate::updates::decoder::{Decode, Decoder};
 use crate::updates::encoder::{Encode, Encoder};
 use crate::utils::client_hasher::ClientHasher;
@@ -22,7 +23,7 @@ use crate::{OffsetKind, StateVector, ID};
 
 #[derive(Debug, Default, PartialEq)]
 pub(crate) struct UpdateBlocks {
-    clients: HashMap<ClientID, VecDeque<BlockCarrier>, BuildHasherDefault<ClientHasher>>,
+    pub(crate) clients: HashMap<ClientID, VecDeque<BlockCarrier>, BuildHasherDefault<ClientHasher>>,
 }
 
 impl UpdateBlocks {
@@ -39,12 +40,147 @@ impl UpdateBlocks {
         self.clients.is_empty()
     }
 
+    /// Returns a number of blocks stored in this collection.
+    pub(crate) fn len(&self) -> usize {
+        self.clients.values().map(VecDeque::len).sum()
+    }
+
+    /// Returns ranges of blocks `(client, start, end)`, which are neither known to a `local` state
+    /// nor present in this collection, but are required in order to integrate blocks stored here.
+    pub(crate) fn missing_ranges(&self, local: &StateVector) -> Vec<(ClientID, u32, u32)> {
+        let first_clock = |client: &ClientID| -> Option<u32> {
+            let blocks = self.clients.get(client)?;
+            let block = blocks.iter().find(|b| !b.is_skip())?;
+            Some(block.id().clock)
+        };
+        let mut ends: HashMap<ClientID, u32> = HashMap::new();
+        for (client, blocks) in self.clients.iter() {
+            if let Some(clock) = first_clock(client) {
+                let end = ends.entry(*client).or_default();
+                *end = (*end).max(clock);
+            }
+            for block in blocks.iter() {
+                if let BlockCarrier::Item(item) = block {
+                    let parent = match &item.parent {
+                        TypePtr::ID(id) => Some(id),
+                        _ => None,
+                    };
+                    let deps = [item.origin.as_ref(), item.right_origin.as_ref(), parent];
+                    for dep in deps.iter().flatten() {
+                        let covered = match first_clock(&dep.client) {
+                            Some(clock) => dep.clock >= clock,
+                            None => false,
+                        };
+                        if !covered {
+                            let end = ends.entry(dep.client).or_default();
+                            *end = (*end).max(dep.clock + 1);
+                        }
+                    }
+                }
+            }
+        }
+        let mut ranges: Vec<_> = ends
+            .into_iter()
+            .filter_map(|(client, end)| {
+                let start = local.get(&client);
+                if end > start {
+                    Some((client, start, end))
+                } else {
+                    None
+                }
+            })
+            .collect();
+        ranges.sort();
+        ranges
+    }
+
     /// Returns an iterator that allows a traversal of all of the blocks
     /// which consist into this [Update].
     pub(crate) fn blocks(&self) -> Blocks<'_> {
         Blocks::new(self)
     }
 
+    /// Returns an iterator over mutable references to all of the blocks stored in this
+    /// collection.
+    pub(crate) fn blocks_mut(&mut self) -> impl Iterator<Item = &mut BlockCarrier> {
+        self.clients
+            .values_mut()
+            .flat_map(|blocks| blocks.iter_mut())
+    }
+
+    /// Discards contents of the items deleted by a given `delete_set`, splitting items which
+    /// have been deleted only partially. Deleted items are kept as tombstones - squashed together
+    /// when possible - so that blocks positioned relative to them can still be integrated, unless
+    /// their parent collection has been deleted as well: then they are replaced by GC blocks.
+    fn compact(&mut self, delete_set: &DeleteSet) {
+        for (client, blocks) in self.clients.iter_mut() {
+            let range = match delete_set.range(client) {
+                Some(range) => range,
+                None => continue,
+            };
+            let mut result = VecDeque::with_capacity(blocks.len());
+            for mut block in std::mem::take(blocks) {
+                // split items at the boundaries of deleted ranges
+                while let BlockCarrier::Item(_) = &block {
+                    let (start, end) = (block.id().clock, block.id().clock + block.len());
+                    let split = range
+                        .iter()
+                        .flat_map(|r| [r.start, r.end])
+                        .find(|&clock| start < clock && clock < end);
+                    match split.and_then(|clock| block.splice(clock - start)) {
+                        Some(right) => result.push_back(std::mem::replace(&mut block, right)),
+                        None => break,
+                    }
+                }
+                result.push_back(block);
+            }
+            for mut block in result.drain(..) {
+                if let BlockCarrier::Item(item) = &mut block {
+                    let (id, len) = (item.id, item.len);
+                    if range.contains(id.clock) {
+                        let parent_deleted = match &item.parent {
+                            TypePtr::ID(parent) => delete_set.is_deleted(parent),
+                            _ => false,
+                        };
+                        if parent_deleted {
+                            block = BlockCarrier::GC(BlockRange::new(id, len));
+                        } else {
+                            item.content = ItemContent::Deleted(len);
+                            item.info.clear_countable();
+                        }
+                    }
+                }
+                let squashed = match (blocks.back_mut(), &block) {
+                    (Some(BlockCarrier::Item(left)), BlockCarrier::Item(right)) => {
+                        Self::squash_tombstones(left, right)
+                    }
+                    _ => false,
+                };
+                if !squashed {
+                    blocks.push_back(block);
+                }
+            }
+        }
+    }
+
+    /// Appends tombstone `right` to a tombstone `left`, if it directly follows it.
+    fn squash_tombstones(left: &mut Item, right: &Item) -> bool {
+        let follows = left.id.client == right.id.client
+            && left.id.clock + left.len == right.id.clock
+            && right.origin == Some(left.last_id())
+            && right.right_origin == left.right_origin
+            && (right.parent == TypePtr::Unknown
+                || (right.parent == left.parent && right.parent_sub == left.parent_sub));
+        match (&mut left.content, &right.content) {
+            (ItemContent::Deleted(l), ItemContent::Deleted(r)) if follows => {
+                *l += *r;
+                left.len += right.len;
+                true
+            }
+            _ => false,
+        }
+    }
+
     /// Returns an iterator that allows a traversal of all of the blocks
     /// which consist into this [Update].
     pub(crate) fn into_blocks(self, ignore_skip: bool) -> IntoBlocks {
@@ -102,6 +238,12 @@ impl Update {
         self.blocks.is_empty() && self.delete_set.is_empty()
     }
 
+    /// Decodes a lib0 v1 encoded update without copying string and binary contents of its blocks,
+    /// which are copied only once the update is applied. See: [BorrowedUpdate].
+    pub fn decode_v1_borrowed(data: &[u8]) -> Result<BorrowedUpdate<'_>, Error> {
+        BorrowedUpdate::decode_v1(data)
+    }
+
     /// Returns a state vector representing an upper bound of client clocks included by blocks
     /// stored in current update.
     pub fn state_vector(&self) -> StateVector {
@@ -430,7 +572,10 @@ impl Update {
         }
     }
 
-    fn decode_block<D: Decoder>(id: ID, decoder: &mut D) -> Result<Option<BlockCarrier>, Error> {
+    pub(crate) fn decode_block<D: Decoder>(
+        id: ID,
+        decoder: &mut D,
+    ) -> Result<Option<BlockCarrier>, Error> {
         let info = decoder.read_info()?;
         match info {
             BLOCK_SKIP_REF_NUMBER => {
@@ -534,6 +679,59 @@ impl Update {
         self.delete_set.encode(encoder)
     }
 
+    /// Merges given updates like [Update::merge_updates] does, additionally discarding contents
+    /// of the elements, which have been both inserted and deleted within the same batch of
+    /// updates (partially deleted blocks are split). Deleted elements are replaced with
+    /// tombstones - or GC blocks, if their parent collection has been deleted as well - which can
+    /// be squashed together. This can significantly shrink the stored history of frequently
+    /// edited documents.
+    ///
+    /// A compacted update, once applied, produces the same document state as the original
+    /// updates, however it can no longer be used to restore snapshots from before the deletions.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use yrs::updates::decoder::Decode;
+    /// use yrs::updates::encoder::Encode;
+    /// use yrs::{Doc, GetString, ReadTxn, Text, Transact, Update};
+    ///
+    /// let doc = Doc::new();
+    /// let text = doc.get_or_insert_text("text");
+    /// let mut updates = Vec::new();
+    /// for i in 0..10 {
+    ///     let sv = doc.transact().state_vector();
+    ///     text.push(&mut doc.transact_mut(), "some long paragraph, which will be removed");
+    ///     updates.push(doc.transact().encode_diff_v1(&sv));
+    ///     if i < 9 {
+    ///         let sv = doc.transact().state_vector();
+    ///         let mut txn = doc.transact_mut();
+    ///         let len = text.len(&txn);
+    ///         text.remove_range(&mut txn, 0, len);
+    ///         drop(txn);
+    ///         updates.push(doc.transact().encode_diff_v1(&sv));
+    ///     }
+    /// }
+    /// let decode = || updates.iter().map(|u| Update::decode_v1(u).unwrap());
+    /// let merged = Update::merge_updates(decode()).encode_v1();
+    /// let compacted = Update::compact(decode()).encode_v1();
+    /// assert!(compacted.len() < merged.len() / 4);
+    ///
+    /// let restored = Doc::new();
+    /// let text = restored.get_or_insert_text("text");
+    /// restored.transact_mut().apply_update(Update::decode_v1(&compacted).unwrap());
+    /// assert_eq!(text.get_string(&restored.transact()), "some long paragraph, which will be removed");
+    /// ```
+    pub fn compact<T>(updates: T) -> Update
+    where
+        T: IntoIterator<Item = Update>,
+    {
+        let mut result = Update::merge_updates(updates);
+        result.delete_set.squash();
+        result.blocks.compact(&result.delete_set);
+        result
+    }
+
     pub fn merge_updates<T>(block_stores: T) -> Update
     where
         T: IntoIterator<Item = Update>,
@@ -716,7 +914,7 @@ impl Decode for Update {
         // read blocks
         let clients_len: u32 = decoder.read_var()?;
         let mut clients = HashMap::with_hasher(BuildHasherDefault::default());
-        clients.try_reserve(clients_len as usize)?;
+        clients.try_reserve(decoder.bounded_len(clients_len as usize)?)?;
 
         let mut blocks = UpdateBlocks { clients };
         for _ in 0..clients_len {
@@ -729,16 +927,21 @@ impl Decode for Update {
                 .entry(client)
                 .or_insert_with(|| VecDeque::new());
             // Attempt to pre-allocate memory for the blocks. If the capacity overflows and
-            // allocation fails, return an error.
-            blocks.try_reserve(blocks_len)?;
+            // allocation fails, return an error. In v2 encoding blocks are run-length encoded
+            // and may not take any of the remaining bytes, so we only cap the preallocation here.
+            blocks.try_reserve(bounded_len(blocks_len, decoder.remaining(), None)?)?;
 
             for _ in 0..blocks_len {
                 let id = ID::new(client, clock);
                 if let Some(block) = Self::decode_block(id, decoder)? {
                     // due to bug in the past it was possible for empty bugs to be generated
                     // even though they had no effect on the document store
-                    clock += block.len();
-                    blocks.push_back(block);
+                    let len = block.len();
+                    clock = clock.checked_add(len).ok_or(Error::UnexpectedValue)?;
+                    if len != 0 || matches!(block, BlockCarrier::Item(_)) {
+                        // empty GC and skip ranges are malformed: they'd wrap their end clock
+                        blocks.push_back(block);
+                    }
                 }
             }
         }
@@ -1071,12 +1274,15 @@ mod test {
     use std::sync::{Arc, Mutex};
 
     use crate::block::{Item, ItemContent};
-    use crate::encoding::read::Cursor;
+    use crate::encoding::read::{Cursor, DecodeLimits, Error};
+    use crate::types::ToJson;
     use crate::types::{Delta, TypePtr};
     use crate::update::{BlockCarrier, Update};
     use crate::updates::decoder::{Decode, DecoderV1};
+    use crate::updates::encoder::Encode;
     use crate::{
-        Doc, GetString, Options, ReadTxn, StateVector, Text, Transact, XmlFragment, XmlOut, ID,
+        Any, Array, Doc, GetString, Map, MapPrelim, Options, ReadTxn, StateVector, Text, Transact,
+        TransactionMut, XmlFragment, XmlOut, ID,
     };
 
     #[test]
@@ -1332,6 +1538,105 @@ mod test {
         assert_eq!(str, "nenor");
     }
 
+    #[test]
+    fn strict_decoding_limits() {
+        let mut nested = Any::Null;
+        for _ in 0..200 {
+            nested = Any::Array(vec![nested].into());
+        }
+        let doc = Doc::with_client_id(1);
+        let array = doc.get_or_insert_array("array");
+        array.push_back(&mut doc.transact_mut(), nested);
+        let txn = doc.transact();
+        let v1 = txn.encode_state_as_update_v1(&StateVector::default());
+        let v2 = txn.encode_state_as_update_v2(&StateVector::default());
+
+        // lenient decoding accepts any nesting depth
+        assert!(Update::decode_v1(&v1).is_ok());
+        assert!(Update::decode_v2(&v2).is_ok());
+
+        let limits = DecodeLimits::default();
+        assert!(matches!(
+            Update::decode_v1_strict(&v1, limits),
+            Err(Error::DepthLimitExceeded(128))
+        ));
+        assert!(matches!(
+            Update::decode_v2_strict(&v2, limits),
+            Err(Error::DepthLimitExceeded(128))
+        ));
+
+        let limits = DecodeLimits { max_depth: 256 };
+        assert!(Update::decode_v1_strict(&v1, limits).is_ok());
+        assert!(Update::decode_v2_strict(&v2, limits).is_ok());
+
+        // length prefixes exceeding the payload size are rejected before allocating
+        let oversized = [0xff, 0xff, 0xff, 0xff, 0x0f, 0];
+        assert!(matches!(
+            Update::decode_v1_strict(&oversized, DecodeLimits::default()),
+            Err(Error::EndOfBuffer(_))
+        ));
+        assert!(matches!(
+            StateVector::decode_v1_strict(&oversized, DecodeLimits::default()),
+            Err(Error::EndOfBuffer(_))
+        ));
+        assert!(Update::decode_v1(&oversized).is_err());
+    }
+
+    #[test]
+    fn compact_removes_deleted_contents() {
+        let doc = Doc::with_client_id(1);
+        let text = doc.get_or_insert_text("text");
+        let map = doc.get_or_insert_map("map");
+        let mut updates = Vec::new();
+        let mut record = |f: &dyn Fn(&mut TransactionMut)| {
+            let sv = doc.transact().state_vector();
+            f(&mut doc.transact_mut());
+            updates.push(doc.transact().encode_diff_v1(&sv));
+        };
+        record(&|txn| {
+            text.push(txn, "hello world");
+            map.insert(
+                txn,
+                "nested",
+                MapPrelim::from([("a", "value a"), ("b", "value b")]),
+            );
+            map.insert(txn, "kept", "value");
+        });
+        record(&|txn| {
+            text.remove_range(txn, 5, 6);
+            map.remove(txn, "nested");
+        });
+
+        let decode = || updates.iter().map(|u| Update::decode_v1(u).unwrap());
+        let merged = Update::merge_updates(decode());
+        let compacted = Update::compact(decode());
+        assert_eq!(compacted.delete_set, merged.delete_set);
+        assert!(compacted.encode_v1().len() < merged.encode_v1().len());
+        let blocks: Vec<_> = compacted.blocks.blocks().collect();
+        // entries of a removed nested map have been replaced by GC blocks
+        assert_eq!(
+            blocks
+                .iter()
+                .filter(|b| matches!(b, BlockCarrier::GC(_)))
+                .count(),
+            2
+        );
+        for block in blocks {
+            if let BlockCarrier::Item(item) = block {
+                if let ItemContent::String(s) = &item.content {
+                    assert_eq!(s.as_str(), "hello");
+                }
+            }
+        }
+
+        let restored = Doc::with_client_id(2);
+        restored.get_or_insert_text("text");
+        restored.get_or_insert_map("map");
+        restored.transact_mut().apply_update(compacted);
+        let txn = restored.transact();
+        assert_eq!(restored.to_json(&txn), doc.to_json(&doc.transact()));
+    }
+
     fn decode_update(bin: &[u8]) -> Update {
         Update::decode(&mut DecoderV1::new(Cursor::new(bin))).unwrap()
     }
@@ -0,0 +1,406 @@
+//! Updates, which borrow their contents from an encoded buffer.
+//!
+//! [Update::decode_v1] copies the contents of every decoded block - for large text insertions
+//! most of the decoding time is spent on allocating strings. [BorrowedUpdate] created with
+//! [Update::decode_v1_borrowed] validates a lib0 v1 encoded buffer in full, but string and binary
+//! contents of its blocks (as well as the names of their parents) are kept as slices borrowed
+//! from that buffer. They are copied only once they are needed for integration
+//! (see: [TransactionMut::apply_update_borrowed]), while blocks already known to a document are
+//! never copied at all.
+//!
+//! # Example
+//!
+//! ```rust
+//! use yrs::{Doc, GetString, ReadTxn, StateVector, Text, Transact, Update};
+//!
+//! let source = Doc::new();
+//! let text = source.get_or_insert_text("text");
+//! text.push(&mut source.transact_mut(), "hello world");
+//! let bytes = source.transact().encode_state_as_update_v1(&StateVector::default());
+//!
+//! let update = Update::decode_v1_borrowed(&bytes).unwrap();
+//! assert_eq!(update.state_vector(), source.transact().state_vector());
+//!
+//! let doc = Doc::new();
+//! doc.transact_mut().apply_update_borrowed(update);
+//! let text = doc.get_or_insert_text("text");
+//! assert_eq!(text.get_string(&doc.transact()), "hello world");
+//! ```
+//!
+//! [TransactionMut::apply_update_borrowed]: crate::TransactionMut::apply_update_borrowed
+
+use std::borrow::Cow;
+use std::sync::Arc;
+
+use crate::block::{
+    BlockRange, Item, ItemContent, BLOCK_GC_REF_NUMBER, BLOCK_ITEM_BINARY_REF_NUMBER,
+    BLOCK_ITEM_STRING_REF_NUMBER, BLOCK_SKIP_REF_NUMBER, HAS_ORIGIN, HAS_PARENT_SUB,
+    HAS_RIGHT_ORIGIN,
+};
+use crate::encoding::read::{Cursor, Error, Read};
+use crate::id_set::DeleteSet;
+use crate::types::TypePtr;
+use crate::update::{BlockCarrier, Update, UpdateBlocks};
+use crate::updates::decoder::{Decode, Decoder, DecoderV1};
+use crate::{OffsetKind, StateVector, ID};
+
+/// A lib0 v1 encoded update, which blocks have been validated, but their string and binary
+/// contents are still borrowed from an encoded buffer. Created with [Update::decode_v1_borrowed].
+#[derive(Debug, Clone)]
+pub struct BorrowedUpdate<'a> {
+    blocks: Vec<BorrowedBlock<'a>>,
+    delete_set: DeleteSet,
+}
+
+#[derive(Debug, Clone)]
+enum BorrowedBlock<'a> {
+    Item(BorrowedItem<'a>),
+    GC(BlockRange),
+    Skip(BlockRange),
+}
+
+#[derive(Debug, Clone)]
+struct BorrowedItem<'a> {
+    id: ID,
+    len: u32,
+    origin: Option<ID>,
+    right_origin: Option<ID>,
+    parent: BorrowedParent<'a>,
+    parent_sub: Option<Cow<'a, str>>,
+    content: BorrowedContent<'a>,
+}
+
+#[derive(Debug, Clone)]
+enum BorrowedParent<'a> {
+    Named(Cow<'a, str>),
+    ID(ID),
+    Unknown,
+}
+
+/// Content of a [BorrowedItem]. Strings and binary buffers, which usually make up the bulk of
+/// an update, are borrowed. Other kinds of content are decoded right away.
+#[derive(Debug, Clone)]
+enum BorrowedContent<'a> {
+    String(Cow<'a, str>),
+    Binary(Cow<'a, [u8]>),
+    Other(ItemContent),
+}
+
+impl<'a> BorrowedContent<'a> {
+    fn len(&self) -> u32 {
+        match self {
+            BorrowedContent::String(str) => str.encode_utf16().count() as u32,
+            BorrowedContent::Binary(_) => 1,
+            BorrowedContent::Other(content) => content.len(OffsetKind::Utf16),
+        }
+    }
+
+    fn into_owned(self) -> ItemContent {
+        match self {
+            BorrowedContent::String(str) => ItemContent::String(str.as_ref().into()),
+            BorrowedContent::Binary(buf) => ItemContent::Binary(buf.into_owned()),
+            BorrowedContent::Other(content) => content,
+        }
+    }
+}
+
+impl<'a> BorrowedBlock<'a> {
+    fn id(&self) -> &ID {
+        match self {
+            BorrowedBlock::Item(item) => &item.id,
+            BorrowedBlock::GC(range) => &range.id,
+            BorrowedBlock::Skip(range) => &range.id,
+        }
+    }
+
+    fn len(&self) -> u32 {
+        match self {
+            BorrowedBlock::Item(item) => item.len,
+            BorrowedBlock::GC(range) => range.len,
+            BorrowedBlock::Skip(range) => range.len,
+        }
+    }
+
+    /// Equivalent of [Update::decode_block], which borrows string and binary contents.
+    fn decode(id: ID, decoder: &mut DecoderV1<'a>) -> Result<Option<Self>, Error> {
+        let info = decoder.read_info()?;
+        match info {
+            BLOCK_SKIP_REF_NUMBER => {
+                let len: u32 = decoder.read_var()?;
+                Ok(Some(BorrowedBlock::Skip(BlockRange { id, len })))
+            }
+            BLOCK_GC_REF_NUMBER => {
+                let len: u32 = decoder.read_len()?;
+                Ok(Some(BorrowedBlock::GC(BlockRange { id, len })))
+            }
+            info => {
+                let cant_copy_parent_info = info & (HAS_ORIGIN | HAS_RIGHT_ORIGIN) == 0;
+                let origin = if info & HAS_ORIGIN != 0 {
+                    Some(decoder.read_left_id()?)
+                } else {
+                    None
+                };
+                let right_origin = if info & HAS_RIGHT_ORIGIN != 0 {
+                    Some(decoder.read_right_id()?)
+                } else {
+                    None
+                };
+                let parent = if cant_copy_parent_info {
+                    if decoder.read_parent_info()? {
+                        BorrowedParent::Named(Cow::Borrowed(decoder.read_borrowed_string()?))
+                    } else {
+                        BorrowedParent::ID(decoder.read_left_id()?)
+                    }
+                } else {
+                    BorrowedParent::Unknown
+                };
+                let parent_sub = if cant_copy_parent_info && (info & HAS_PARENT_SUB != 0) {
+                    Some(Cow::Borrowed(decoder.read_borrowed_string()?))
+                } else {
+                    None
+                };
+                let content = match info & 0b1111 {
+                    BLOCK_ITEM_STRING_REF_NUMBER => {
+                        BorrowedContent::String(Cow::Borrowed(decoder.read_borrowed_string()?))
+                    }
+                    BLOCK_ITEM_BINARY_REF_NUMBER => {
+                        BorrowedContent::Binary(Cow::Borrowed(decoder.read_borrowed_buf()?))
+                    }
+                    _ => BorrowedContent::Other(ItemContent::decode(decoder, info)?),
+                };
+                let len = content.len();
+                if len == 0 {
+                    return Ok(None);
+                }
+                Ok(Some(BorrowedBlock::Item(BorrowedItem {
+                    id,
+                    len,
+                    origin,
+                    right_origin,
+                    parent,
+                    parent_sub,
+                    content,
+                })))
+            }
+        }
+    }
+
+    fn into_owned(self) -> Option<BlockCarrier> {
+        match self {
+            BorrowedBlock::GC(range) => Some(BlockCarrier::GC(range)),
+            BorrowedBlock::Skip(range) => Some(BlockCarrier::Skip(range)),
+            BorrowedBlock::Item(item) => {
+                let parent = match item.parent {
+                    BorrowedParent::Named(name) => TypePtr::Named(Arc::from(name.as_ref())),
+                    BorrowedParent::ID(id) => TypePtr::ID(id),
+                    BorrowedParent::Unknown => TypePtr::Unknown,
+                };
+                let item = Item::new(
+                    item.id,
+                    None,
+                    item.origin,
+                    None,
+                    item.right_origin,
+                    parent,
+                    item.parent_sub.map(|key| Arc::from(key.as_ref())),
+                    item.content.into_owned(),
+                )?;
+                Some(BlockCarrier::from(item))
+            }
+        }
+    }
+}
+
+impl<'a> BorrowedUpdate<'a> {
+    /// Validates a lib0 v1 encoded update, borrowing string and binary contents of its blocks.
+    pub(crate) fn decode_v1(buf: &'a [u8]) -> Result<Self, Error> {
+        let mut decoder = DecoderV1::new(Cursor::new(buf));
+        let clients_len: u32 = decoder.read_var()?;
+        let mut blocks = Vec::new();
+        for _ in 0..clients_len {
+            let blocks_len: u32 = decoder.read_var()?;
+            let client = decoder.read_client()?;
+            let mut clock: u32 = decoder.read_var()?;
+            blocks.try_reserve(decoder.bounded_len(blocks_len as usize)?)?;
+            for _ in 0..blocks_len {
+                let id = ID::new(client, clock);
+                if let Some(block) = BorrowedBlock::decode(id, &mut decoder)? {
+                    let len = block.len();
+                    clock = clock.checked_add(len).ok_or(Error::UnexpectedValue)?;
+                    if len != 0 || matches!(block, BorrowedBlock::Item(_)) {
+                        // empty GC and skip ranges are malformed: they'd wrap their end clock
+                        blocks.push(block);
+                    }
+                }
+            }
+        }
+        let delete_set = DeleteSet::decode(&mut decoder)?;
+        Ok(BorrowedUpdate { blocks, delete_set })
+    }
+
+    /// Returns a state vector representing an upper bound of client clocks included by blocks
+    /// stored in current update.
+    pub fn state_vector(&self) -> StateVector {
+        let mut sv = StateVector::default();
+        for block in self.blocks.iter() {
+            let id = block.id();
+            sv.set_max(id.client, id.clock + block.len());
+        }
+        sv
+    }
+
+    /// Returns a delete set of current update.
+    pub fn delete_set(&self) -> &DeleteSet {
+        &self.delete_set
+    }
+
+    /// Checks if current update contains neither blocks nor deletions.
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty() && self.delete_set.is_empty()
+    }
+
+    /// Converts current update into an [Update] owning all of its contents.
+    pub fn into_update(self) -> Update {
+        self.into_update_since(&StateVector::default())
+    }
+
+    /// Converts current update into an [Update] owning its contents. Blocks fully covered by
+    /// a given state vector `sv` are dropped without copying their contents.
+    pub fn into_update_since(self, sv: &StateVector) -> Update {
+        let mut blocks = UpdateBlocks::default();
+        for block in self.blocks {
+            let id = block.id();
+            if id.clock + block.len() <= sv.get(&id.client) {
+                continue;
+            }
+            if let Some(block) = block.into_owned() {
+                blocks.add_block(block);
+            }
+        }
+        Update {
+            blocks,
+            delete_set: self.delete_set,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::borrow::Cow;
+
+    use crate::block::{Item, ItemContent};
+    use crate::types::{ToJson, TypePtr};
+    use crate::update::BlockCarrier;
+    use crate::updates::borrowed::{BorrowedBlock, BorrowedContent};
+    use crate::updates::decoder::Decode;
+    use crate::updates::encoder::Encode;
+    use crate::{
+        any, Any, Array, Doc, GetString, Map, MapPrelim, ReadTxn, StateVector, Text, Transact,
+        Update, ID,
+    };
+
+    #[test]
+    fn borrowed_update_roundtrip() {
+        let d1 = Doc::with_client_id(1);
+        let text = d1.get_or_insert_text("text");
+        let array = d1.get_or_insert_array("array");
+        let map = d1.get_or_insert_map("map");
+        {
+            let mut txn = d1.transact_mut();
+            text.push(&mut txn, "hello ");
+            text.push(&mut txn, "zażółć 😀");
+            array.insert_range(&mut txn, 0, [1, 2, 3]);
+            array.push_back(&mut txn, vec![1u8, 2, 3]);
+            map.insert(&mut txn, "nested", MapPrelim::from([("a", "b")]));
+            map.insert(&mut txn, "key", "value");
+            array.remove(&mut txn, 1);
+            text.remove_range(&mut txn, 0, 2);
+        }
+        let bytes = d1
+            .transact()
+            .encode_state_as_update_v1(&StateVector::default());
+        let owned = Update::decode_v1(&bytes).unwrap();
+        let borrowed = Update::decode_v1_borrowed(&bytes).unwrap();
+        assert_eq!(borrowed.state_vector(), owned.state_vector());
+        assert_eq!(borrowed.delete_set(), &owned.delete_set);
+        assert!(borrowed.clone().into_update() == owned);
+
+        let d2 = Doc::with_client_id(2);
+        d2.get_or_insert_text("text");
+        d2.get_or_insert_array("array");
+        d2.get_or_insert_map("map");
+        d2.transact_mut().apply_update_borrowed(borrowed);
+        assert_eq!(d2.to_json(&d2.transact()), d1.to_json(&d1.transact()));
+
+        // blocks already known to a document are dropped
+        let sv = d2.transact().state_vector();
+        text.push(&mut d1.transact_mut(), "!");
+        let bytes = d1
+            .transact()
+            .encode_state_as_update_v1(&StateVector::default());
+        let borrowed = Update::decode_v1_borrowed(&bytes).unwrap();
+        let update = borrowed.into_update_since(&sv);
+        assert_eq!(update.blocks.len(), 1);
+        d2.transact_mut().apply_update(update);
+        let text = d2.get_or_insert_text("text");
+        assert_eq!(text.get_string(&d2.transact()), "llo zażółć 😀!");
+
+        assert!(Update::decode_v1_borrowed(&bytes[..bytes.len() - 3]).is_err());
+    }
+
+    #[test]
+    fn borrowed_update_doesnt_copy_contents() {
+        let doc = Doc::with_client_id(1);
+        let text = doc.get_or_insert_text("text");
+        text.push(&mut doc.transact_mut(), "hello world");
+        let mut update = Update::decode_v1(
+            &doc.transact()
+                .encode_state_as_update_v1(&StateVector::default()),
+        )
+        .unwrap();
+        // binary content is produced only by Yjs, so it needs to be crafted by hand
+        let binary = Item::new(
+            ID::new(2, 0),
+            None,
+            None,
+            None,
+            None,
+            TypePtr::Named("array".into()),
+            None,
+            ItemContent::Binary(vec![1, 2, 3]),
+        )
+        .unwrap();
+        update.blocks.add_block(BlockCarrier::from(binary));
+        let bytes = update.encode_v1();
+        let range = bytes.as_ptr_range();
+
+        let update = Update::decode_v1_borrowed(&bytes).unwrap();
+        let mut borrowed = 0;
+        for block in update.blocks.iter() {
+            if let BorrowedBlock::Item(item) = block {
+                match &item.content {
+                    BorrowedContent::String(Cow::Borrowed(str)) => {
+                        assert_eq!(*str, "hello world");
+                        assert!(range.contains(&str.as_ptr()));
+                        borrowed += 1;
+                    }
+                    BorrowedContent::Binary(Cow::Borrowed(buf)) => {
+                        assert_eq!(*buf, &[1, 2, 3]);
+                        assert!(range.contains(&buf.as_ptr()));
+                        borrowed += 1;
+                    }
+                    other => panic!("unexpected content: {:?}", other),
+                }
+            }
+        }
+        assert_eq!(borrowed, 2);
+
+        let doc = Doc::with_client_id(3);
+        let array = doc.get_or_insert_array("array");
+        doc.transact_mut().apply_update_borrowed(update);
+        assert_eq!(
+            array.to_json(&doc.transact()),
+            any!([Any::from(vec![1u8, 2, 3])])
+        );
+    }
+}
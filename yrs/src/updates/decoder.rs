@@ -1,5 +1,5 @@
 use crate::block::ClientID;
-use crate::encoding::read::{Cursor, Error, Read};
+use crate::encoding::read::{Cursor, DecodeLimits, Error, Read};
 use crate::*;
 use std::sync::Arc;
 
@@ -18,6 +18,21 @@ pub trait Decode: Sized {
         let mut decoder = DecoderV2::new(Cursor::new(data))?;
         Self::decode(&mut decoder)
     }
+
+    /// Helper function for decoding 1st version of lib0 encoding from an untrusted source.
+    /// Length prefixes are validated against the size of remaining payload before any memory is
+    /// allocated and nesting depth is bounded by given `limits`, returning an error instead.
+    fn decode_v1_strict(data: &[u8], limits: DecodeLimits) -> Result<Self, Error> {
+        let mut decoder = DecoderV1::strict(Cursor::new(data), limits);
+        Self::decode(&mut decoder)
+    }
+
+    /// Helper function for decoding 2nd version of lib0 encoding from an untrusted source.
+    /// See: [Decode::decode_v1_strict].
+    fn decode_v2_strict(data: &[u8], limits: DecodeLimits) -> Result<Self, Error> {
+        let mut decoder = DecoderV2::strict(Cursor::new(data), limits)?;
+        Self::decode(&mut decoder)
+    }
 }
 
 /// Trait used by lib0 decoders. Natively lib0 encoding supports two versions:
@@ -75,11 +90,24 @@ pub trait Decoder: Read {
 /// Version 1 of lib0 decoder.
 pub struct DecoderV1<'a> {
     cursor: Cursor<'a>,
+    limits: Option<DecodeLimits>,
 }
 
 impl<'a> DecoderV1<'a> {
     pub fn new(cursor: Cursor<'a>) -> Self {
-        DecoderV1 { cursor }
+        DecoderV1 {
+            cursor,
+            limits: None,
+        }
+    }
+
+    /// Creates a decoder working in a strict mode, which validates decoded length prefixes and
+    /// nesting depth against given `limits`.
+    pub fn strict(cursor: Cursor<'a>, limits: DecodeLimits) -> Self {
+        DecoderV1 {
+            cursor,
+            limits: Some(limits),
+        }
     }
 
     fn read_id(&mut self) -> Result<ID, Error> {
@@ -87,6 +115,18 @@ impl<'a> DecoderV1<'a> {
         let clock = self.read_var()?;
         Ok(ID::new(client as ClientID, clock))
     }
+
+    /// Read a variable length buffer borrowed for the lifetime of an underlying byte slice.
+    pub(crate) fn read_borrowed_buf(&mut self) -> Result<&'a [u8], Error> {
+        let len: u32 = self.read_var()?;
+        self.cursor.read_borrowed(len as usize)
+    }
+
+    /// Read a string of variable length borrowed for the lifetime of an underlying byte slice.
+    pub(crate) fn read_borrowed_string(&mut self) -> Result<&'a str, Error> {
+        let buf = self.read_borrowed_buf()?;
+        std::str::from_utf8(buf).map_err(|_| Error::UnexpectedValue)
+    }
 }
 
 impl<'a> From<Cursor<'a>> for DecoderV1<'a> {
@@ -111,6 +151,16 @@ impl<'a> Read for DecoderV1<'a> {
     fn read_exact(&mut self, len: usize) -> Result<&[u8], Error> {
         self.cursor.read_exact(len)
     }
+
+    #[inline]
+    fn remaining(&self) -> Option<usize> {
+        self.cursor.remaining()
+    }
+
+    #[inline]
+    fn limits(&self) -> Option<&DecodeLimits> {
+        self.limits.as_ref()
+    }
 }
 
 impl<'a> Decoder for DecoderV1<'a> {
@@ -189,9 +239,146 @@ impl<'a> Decoder for DecoderV1<'a> {
     }
 }
 
+/// Version 1 of lib0 decoder, which incrementally pulls encoded data from an [std::io::Read]
+/// source instead of requiring the whole payload to be loaded into memory upfront.
+/// Only the bytes of a currently decoded value are buffered.
+pub struct StreamDecoderV1<R> {
+    reader: R,
+    buf: Vec<u8>,
+}
+
+impl<R: std::io::Read> StreamDecoderV1<R> {
+    pub fn new(reader: R) -> Self {
+        StreamDecoderV1 {
+            reader,
+            buf: Vec::new(),
+        }
+    }
+
+    /// Returns the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    fn read_id(&mut self) -> Result<ID, Error> {
+        let client: u32 = self.read_var()?;
+        let clock = self.read_var()?;
+        Ok(ID::new(client as ClientID, clock))
+    }
+
+    fn io_error(e: std::io::Error, len: usize) -> Error {
+        if e.kind() == std::io::ErrorKind::UnexpectedEof {
+            Error::EndOfBuffer(len)
+        } else {
+            Error::Custom(e.to_string())
+        }
+    }
+}
+
+impl<R: std::io::Read> Read for StreamDecoderV1<R> {
+    fn read_u8(&mut self) -> Result<u8, Error> {
+        let mut byte = [0u8; 1];
+        self.reader
+            .read_exact(&mut byte)
+            .map_err(|e| Self::io_error(e, 1))?;
+        Ok(byte[0])
+    }
+
+    fn read_exact(&mut self, len: usize) -> Result<&[u8], Error> {
+        self.buf.clear();
+        // length prefixes can't be validated upfront, so the buffer grows only as data arrives
+        let mut chunk = std::io::Read::take(&mut self.reader, len as u64);
+        let read = std::io::Read::read_to_end(&mut chunk, &mut self.buf)
+            .map_err(|e| Self::io_error(e, len))?;
+        if read != len {
+            return Err(Error::EndOfBuffer(len));
+        }
+        Ok(&self.buf)
+    }
+}
+
+impl<R: std::io::Read> Decoder for StreamDecoderV1<R> {
+    #[inline]
+    fn reset_ds_cur_val(&mut self) {
+        /* no op */
+    }
+
+    #[inline]
+    fn read_ds_clock(&mut self) -> Result<u32, Error> {
+        self.read_var()
+    }
+
+    #[inline]
+    fn read_ds_len(&mut self) -> Result<u32, Error> {
+        self.read_var()
+    }
+
+    #[inline]
+    fn read_left_id(&mut self) -> Result<ID, Error> {
+        self.read_id()
+    }
+
+    #[inline]
+    fn read_right_id(&mut self) -> Result<ID, Error> {
+        self.read_id()
+    }
+
+    #[inline]
+    fn read_client(&mut self) -> Result<ClientID, Error> {
+        let client: u32 = self.read_var()?;
+        Ok(client as ClientID)
+    }
+
+    #[inline]
+    fn read_info(&mut self) -> Result<u8, Error> {
+        self.read_u8()
+    }
+
+    #[inline]
+    fn read_parent_info(&mut self) -> Result<bool, Error> {
+        let info: u32 = self.read_var()?;
+        Ok(info == 1)
+    }
+
+    #[inline]
+    fn read_type_ref(&mut self) -> Result<u8, Error> {
+        self.read_u8()
+    }
+
+    #[inline]
+    fn read_len(&mut self) -> Result<u32, Error> {
+        self.read_var()
+    }
+
+    #[inline]
+    fn read_any(&mut self) -> Result<Any, Error> {
+        Any::decode(self)
+    }
+
+    fn read_json(&mut self) -> Result<Any, Error> {
+        let src = self.read_string()?;
+        Any::from_json(src)
+    }
+
+    #[inline]
+    fn read_key(&mut self) -> Result<Arc<str>, Error> {
+        let str: Arc<str> = self.read_string()?.into();
+        Ok(str)
+    }
+
+    fn read_to_end(&mut self) -> Result<&[u8], Error> {
+        self.buf.clear();
+        self.reader
+            .read_to_end(&mut self.buf)
+            .map_err(|e| Self::io_error(e, 0))?;
+        Ok(&self.buf)
+    }
+}
+
 /// Version 2 of lib0 decoder.
 pub struct DecoderV2<'a> {
     cursor: Cursor<'a>,
+    limits: Option<DecodeLimits>,
     keys: Vec<Arc<str>>,
     ds_curr_val: u32,
     key_clock_decoder: IntDiffOptRleDecoder<'a>,
@@ -229,6 +416,7 @@ impl<'a> DecoderV2<'a> {
         };
         Ok(DecoderV2 {
             cursor,
+            limits: None,
             ds_curr_val: 0,
             keys: Vec::new(),
             key_clock_decoder: IntDiffOptRleDecoder::new(Cursor::new(key_clock_buf)),
@@ -243,6 +431,14 @@ impl<'a> DecoderV2<'a> {
         })
     }
 
+    /// Creates a decoder working in a strict mode, which validates decoded length prefixes and
+    /// nesting depth against given `limits`.
+    pub fn strict(cursor: Cursor<'a>, limits: DecodeLimits) -> Result<Self, Error> {
+        let mut decoder = Self::new(cursor)?;
+        decoder.limits = Some(limits);
+        Ok(decoder)
+    }
+
     fn read_usize(buf: &[u8], idx: &mut usize) -> Result<usize, Error> {
         if *idx >= buf.len() {
             return Err(Error::InvalidVarInt);
@@ -250,9 +446,9 @@ impl<'a> DecoderV2<'a> {
         let mut num: usize = 0;
         let mut len: usize = 0;
         loop {
-            let r = buf[*idx];
+            let r = *buf.get(*idx).ok_or(Error::InvalidVarInt)?;
             *idx += 1;
-            num |= (r as usize & 127) << len;
+            num |= usize::wrapping_shl(r as usize & 127, len as u32);
             len += 7;
             if r < 128 {
                 return Ok(num);
@@ -266,7 +462,7 @@ impl<'a> DecoderV2<'a> {
     fn read_buf(buf: &'a [u8], idx: &mut usize) -> Result<&'a [u8], Error> {
         let len = Self::read_usize(buf, idx)?;
         let start = *idx;
-        let end = start + len;
+        let end = start.saturating_add(len);
         if end <= buf.len() {
             let slice = &buf[start..end];
             *idx += len as usize;
@@ -292,6 +488,16 @@ impl<'a> Read for DecoderV2<'a> {
     fn read_string(&mut self) -> Result<&str, Error> {
         self.string_decoder.read_str()
     }
+
+    #[inline]
+    fn remaining(&self) -> Option<usize> {
+        self.cursor.remaining()
+    }
+
+    #[inline]
+    fn limits(&self) -> Option<&DecodeLimits> {
+        self.limits.as_ref()
+    }
 }
 
 impl<'a> Decoder for DecoderV2<'a> {
@@ -345,11 +551,11 @@ impl<'a> Decoder for DecoderV2<'a> {
     }
 
     fn read_any(&mut self) -> Result<Any, Error> {
-        Any::decode(&mut self.cursor)
+        Any::decode_bounded(&mut self.cursor, self.limits.as_ref(), 0)
     }
 
     fn read_json(&mut self) -> Result<Any, Error> {
-        Any::decode(&mut self.cursor)
+        Any::decode_bounded(&mut self.cursor, self.limits.as_ref(), 0)
     }
 
     fn read_key(&mut self) -> Result<Arc<str>, Error> {
@@ -477,7 +683,7 @@ impl<'a> StringDecoder<'a> {
         let buf = cursor.buf;
         let mut next = cursor.next;
         let str_bin = DecoderV2::read_buf(buf, &mut next)?;
-        let str = unsafe { std::str::from_utf8_unchecked(str_bin) };
+        let str = std::str::from_utf8(str_bin).map_err(|_| Error::UnexpectedValue)?;
         let len_decoder = UIntOptRleDecoder::new(Cursor { buf, next });
         Ok(StringDecoder {
             pos: 0,
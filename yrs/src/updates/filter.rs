@@ -0,0 +1,475 @@
+//! Filtering of document updates before applying them.
+//!
+//! [Update::filter] strips blocks from an incoming update - i.e. edits of the root types, which
+//! an untrusted peer is not allowed to modify - while keeping the rest of the update applicable
+//! to a document. Removed blocks are replaced with garbage collected ranges, so that clock
+//! sequences of their clients stay continuous, and blocks which used them as their neighbors are
+//! reattached to the closest neighbors of the removed blocks.
+
+use crate::block::{BlockCell, BlockRange, ClientID, Item, ItemContent};
+use crate::id_set::DeleteSet;
+use crate::types::TypePtr;
+use crate::update::{BlockCarrier, Update};
+use crate::updates::inspect::{branch_root, ItemIndex};
+use crate::{ReadTxn, Store, ID};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// A view over a block inserted or deleted by an [Update], passed to a predicate of
+/// [Update::filter].
+pub struct UpdateBlock<'a> {
+    item: &'a Item,
+    root: Result<Arc<str>, Option<ID>>,
+    deletion: bool,
+}
+
+impl<'a> UpdateBlock<'a> {
+    /// Returns an [ID] of the first element stored in this block.
+    pub fn id(&self) -> &ID {
+        &self.item.id
+    }
+
+    /// Returns a number of elements stored in this block.
+    pub fn len(&self) -> u32 {
+        self.item.len
+    }
+
+    /// Returns `true` if this block contains no elements.
+    pub fn is_empty(&self) -> bool {
+        self.item.len == 0
+    }
+
+    /// Returns `true` if this is an existing block of a document, which an update is going to
+    /// delete, rather than a block inserted by an update.
+    pub fn is_deletion(&self) -> bool {
+        self.deletion
+    }
+
+    /// Returns a content stored in this block.
+    pub fn content(&self) -> &ItemContent {
+        &self.item.content
+    }
+
+    /// Returns a key under which this block has been inserted, if its parent is a map-like
+    /// collection. It's only known for map entries inserted under a new key.
+    pub fn parent_sub(&self) -> Option<&Arc<str>> {
+        self.item.parent_sub.as_ref()
+    }
+
+    /// Returns a name of the root type, which this block has been inserted into - either directly
+    /// or through nested collections. Returns `None` if it could not be resolved.
+    pub fn root(&self) -> Option<&Arc<str>> {
+        self.root.as_ref().ok()
+    }
+
+    /// Returns an [ID] of a nested collection containing this block, which is neither a part of
+    /// an update nor of a document used to resolve the root type of this block.
+    pub fn unresolved_parent(&self) -> Option<&ID> {
+        match &self.root {
+            Err(Some(id)) => Some(id),
+            _ => None,
+        }
+    }
+}
+
+impl Update {
+    /// Returns a new update, which contains only the blocks accepted by a given `predicate`.
+    ///
+    /// Blocks rejected by a predicate are replaced with garbage collected ranges and marked as
+    /// deleted. Remaining blocks, which were inserted next to the rejected ones, are reattached to
+    /// the closest neighbors of the rejected blocks. Blocks nested in the rejected collections
+    /// are dropped when such update is being integrated. Blocks which refer to each other in
+    /// a cycle are rejected as well.
+    ///
+    /// Root type of a block is resolved using only the contents of this update. Most of the
+    /// blocks refer to their neighbors rather than their parent, so edits of the collections
+    /// created before this update usually cannot be resolved this way - use [Update::filter_with]
+    /// in such case. Predicates should reject the blocks with unresolved root types.
+    ///
+    /// Deletions of the blocks inserted by this update are kept only if these blocks are accepted.
+    /// Deletions of the blocks existing in a document passed to [Update::filter_with] are passed
+    /// to a predicate (see: [UpdateBlock::is_deletion]). Deletions of any other blocks cannot be
+    /// verified and are removed from the update.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use yrs::{Doc, GetString, Map, ReadTxn, StateVector, Text, Transact, Update};
+    /// use yrs::updates::decoder::Decode;
+    ///
+    /// let remote = Doc::with_client_id(1);
+    /// let config = remote.get_or_insert_map("config");
+    /// let text = remote.get_or_insert_text("text");
+    /// {
+    ///     let mut txn = remote.transact_mut();
+    ///     config.insert(&mut txn, "admin", true);
+    ///     text.push(&mut txn, "hello");
+    /// }
+    /// let update = remote.transact().encode_state_as_update_v1(&StateVector::default());
+    ///
+    /// // reject all changes made to the `config` map
+    /// let update = Update::decode_v1(&update)
+    ///     .unwrap()
+    ///     .filter(|block| matches!(block.root(), Some(name) if &**name != "config"));
+    ///
+    /// let local = Doc::with_client_id(2);
+    /// local.transact_mut().apply_update(update);
+    ///
+    /// let txn = local.transact();
+    /// assert_eq!(txn.get_text("text").unwrap().get_string(&txn), "hello");
+    /// assert!(txn.get_map("config").is_none());
+    /// ```
+    pub fn filter<F>(self, predicate: F) -> Update
+    where
+        F: FnMut(&UpdateBlock) -> bool,
+    {
+        self.filter_in(None, predicate)
+    }
+
+    /// Returns a new update, which contains only the blocks accepted by a given `predicate`.
+    /// Works like [Update::filter], but root types of the blocks, which cannot be resolved using
+    /// the contents of this update, are resolved using a document state visible to a given `txn`.
+    /// This is usually a document the filtered update is going to be applied to.
+    pub fn filter_with<T, F>(self, txn: &T, predicate: F) -> Update
+    where
+        T: ReadTxn,
+        F: FnMut(&UpdateBlock) -> bool,
+    {
+        self.filter_in(Some(txn.store()), predicate)
+    }
+
+    fn filter_in<F>(mut self, store: Option<&Store>, mut predicate: F) -> Update
+    where
+        F: FnMut(&UpdateBlock) -> bool,
+    {
+        let mut rejected: HashMap<ClientID, Vec<Rejected>> = HashMap::new();
+        let mut rejected_count = 0;
+        let delete_set = {
+            let mut index = ItemIndex::new(&self);
+            for block in self.blocks.blocks() {
+                if let BlockCarrier::Item(item) = block {
+                    let view = UpdateBlock {
+                        item,
+                        root: index.resolve_root(item, store),
+                        deletion: false,
+                    };
+                    if !predicate(&view) {
+                        rejected.entry(item.id.client).or_default().push(Rejected {
+                            start: item.id.clock,
+                            end: item.id.clock + item.len,
+                            origin: item.origin,
+                            right_origin: item.right_origin,
+                            parent: item.parent.clone(),
+                            parent_sub: item.parent_sub.clone(),
+                        });
+                        rejected_count += 1;
+                    }
+                }
+            }
+            Self::filter_delete_set(&self.delete_set, &index, store, &mut predicate)
+        };
+        self.delete_set = delete_set;
+        if rejected.is_empty() {
+            return self;
+        }
+
+        let find = |id: &Option<ID>| -> Option<&Rejected> {
+            let id = id.as_ref()?;
+            let ranges = rejected.get(&id.client)?;
+            ranges
+                .iter()
+                .find(|r| r.start <= id.clock && id.clock < r.end)
+        };
+        for block in self.blocks.blocks_mut() {
+            let id = *block.id();
+            if let BlockCarrier::Item(item) = block {
+                if find(&Some(id)).is_some() {
+                    let len = item.len;
+                    *block = BlockCarrier::GC(BlockRange::new(id, len));
+                    self.delete_set.insert(id, len);
+                    continue;
+                }
+                // rejected blocks have been inserted before the ones referring to them, so every
+                // step moves to a different rejected block unless an update is malformed
+                let mut steps = 0;
+                let mut cyclic = false;
+                loop {
+                    if steps > rejected_count {
+                        cyclic = true;
+                        break;
+                    }
+                    steps += 1;
+                    if let Some(r) = find(&item.origin) {
+                        item.origin = r.origin;
+                        if item.origin.is_none() && item.right_origin.is_none() {
+                            item.right_origin = r.right_origin;
+                            item.parent = r.parent.clone();
+                            item.parent_sub = r.parent_sub.clone();
+                        }
+                    } else if let Some(r) = find(&item.right_origin) {
+                        item.right_origin = r.right_origin;
+                        if item.origin.is_none() && item.right_origin.is_none() {
+                            item.origin = r.origin;
+                            item.parent = r.parent.clone();
+                            item.parent_sub = r.parent_sub.clone();
+                        }
+                    } else {
+                        break;
+                    }
+                }
+                if cyclic {
+                    let len = item.len;
+                    *block = BlockCarrier::GC(BlockRange::new(id, len));
+                    self.delete_set.insert(id, len);
+                }
+            }
+        }
+        self.delete_set.squash();
+        self
+    }
+}
+
+impl Update {
+    /// Returns a subset of a `delete_set`, which deletions have been accepted by a `predicate`.
+    fn filter_delete_set<F>(
+        delete_set: &DeleteSet,
+        index: &ItemIndex,
+        store: Option<&Store>,
+        predicate: &mut F,
+    ) -> DeleteSet
+    where
+        F: FnMut(&UpdateBlock) -> bool,
+    {
+        let mut result = DeleteSet::new();
+        for (&client, range) in delete_set.iter() {
+            for r in range.iter() {
+                let mut clock = r.start;
+                while clock < r.end {
+                    let id = ID::new(client, clock);
+                    let (end, accepted) = if let Some(block) = index.get_block(&id) {
+                        // deletions of the rejected blocks are added back once they are replaced
+                        // with garbage collected ranges
+                        (block.id().clock + block.len(), true)
+                    } else {
+                        match store.and_then(|store| store.blocks.get_block(&id)) {
+                            Some(BlockCell::Block(item)) => {
+                                let root = match &item.parent {
+                                    TypePtr::Branch(branch) => branch_root(*branch).ok_or(None),
+                                    _ => Err(None),
+                                };
+                                let view = UpdateBlock {
+                                    item,
+                                    root,
+                                    deletion: true,
+                                };
+                                (item.id.clock + item.len, predicate(&view))
+                            }
+                            Some(BlockCell::GC(gc)) => (gc.end + 1, true),
+                            // blocks unknown to both an update and a document cannot be verified
+                            None => break,
+                        }
+                    };
+                    let end = end.min(r.end);
+                    if accepted {
+                        result.insert(id, end - clock);
+                    }
+                    clock = end;
+                }
+            }
+        }
+        result
+    }
+}
+
+/// Information about a block rejected by [Update::filter], required to reattach its neighbors.
+struct Rejected {
+    start: u32,
+    end: u32,
+    origin: Option<ID>,
+    right_origin: Option<ID>,
+    parent: TypePtr,
+    parent_sub: Option<Arc<str>>,
+}
+
+#[cfg(test)]
+mod test {
+    use crate::updates::decoder::Decode;
+    use crate::{
+        Array, ArrayPrelim, Doc, GetString, Map, ReadTxn, StateVector, Text, Transact, Update,
+    };
+
+    fn reject_root(name: &'static str) -> impl FnMut(&crate::updates::filter::UpdateBlock) -> bool {
+        move |block| matches!(block.root(), Some(root) if &**root != name)
+    }
+
+    #[test]
+    fn filter_by_root() {
+        let remote = Doc::with_client_id(1);
+        let config = remote.get_or_insert_map("config");
+        let text = remote.get_or_insert_text("text");
+        {
+            let mut txn = remote.transact_mut();
+            text.push(&mut txn, "ab");
+            config.insert(&mut txn, "nested", ArrayPrelim::from([1, 2]));
+            text.push(&mut txn, "cd");
+        }
+        let update = remote
+            .transact()
+            .encode_state_as_update_v1(&StateVector::default());
+        let update = Update::decode_v1(&update)
+            .unwrap()
+            .filter(reject_root("config"));
+        assert_eq!(update.state_vector(), remote.transact().state_vector());
+
+        let local = Doc::with_client_id(2);
+        local.transact_mut().apply_update(update);
+        let txn = local.transact();
+        assert_eq!(txn.get_text("text").unwrap().get_string(&txn), "abcd");
+        assert!(txn.get_map("config").is_none());
+        assert!(txn.store().pending_update().is_none());
+    }
+
+    #[test]
+    fn filter_with_document() {
+        let remote = Doc::with_client_id(1);
+        let config = remote.get_or_insert_map("config");
+        let text = remote.get_or_insert_text("text");
+        {
+            let mut txn = remote.transact_mut();
+            config.insert(&mut txn, "list", ArrayPrelim::default());
+            text.push(&mut txn, "hello");
+        }
+        let local = Doc::with_client_id(2);
+        let update = remote
+            .transact()
+            .encode_state_as_update_v1(&StateVector::default());
+        local
+            .transact_mut()
+            .apply_update(Update::decode_v1(&update).unwrap());
+
+        let sv = remote.transact().state_vector();
+        {
+            let mut txn = remote.transact_mut();
+            let list: crate::ArrayRef = config.get(&txn, "list").unwrap().cast().unwrap();
+            list.push_back(&mut txn, 1);
+            text.push(&mut txn, " world");
+        }
+        let diff = remote.transact().encode_state_as_update_v1(&sv);
+
+        // without a document, edits of existing collections cannot be resolved
+        let mut roots = Vec::new();
+        Update::decode_v1(&diff).unwrap().filter(|block| {
+            roots.push(block.root().cloned());
+            true
+        });
+        assert_eq!(roots, vec![None, None]);
+
+        let update = {
+            let txn = local.transact();
+            Update::decode_v1(&diff)
+                .unwrap()
+                .filter_with(&txn, reject_root("config"))
+        };
+        local.transact_mut().apply_update(update);
+
+        let txn = local.transact();
+        assert_eq!(
+            txn.get_text("text").unwrap().get_string(&txn),
+            "hello world"
+        );
+        let list: crate::ArrayRef = txn
+            .get_map("config")
+            .unwrap()
+            .get(&txn, "list")
+            .unwrap()
+            .cast()
+            .unwrap();
+        assert_eq!(list.len(&txn), 0);
+    }
+
+    #[test]
+    fn filter_reattaches_neighbors() {
+        let remote = Doc::with_client_id(1);
+        let text = remote.get_or_insert_text("text");
+        text.push(&mut remote.transact_mut(), "abcd");
+        text.insert(&mut remote.transact_mut(), 2, "XY");
+        // inserted right before rejected "XY"
+        text.insert(&mut remote.transact_mut(), 2, "e");
+        let update = remote
+            .transact()
+            .encode_state_as_update_v1(&StateVector::default());
+
+        let update = Update::decode_v1(&update)
+            .unwrap()
+            .filter(|block| block.id().clock != 4);
+        let local = Doc::with_client_id(2);
+        local.transact_mut().apply_update(update);
+        let txn = local.transact();
+        assert_eq!(txn.get_text("text").unwrap().get_string(&txn), "abecd");
+        assert!(txn.store().pending_update().is_none());
+    }
+
+    #[test]
+    fn filter_deletions() {
+        let remote = Doc::with_client_id(1);
+        let config = remote.get_or_insert_map("config");
+        let text = remote.get_or_insert_text("text");
+        {
+            let mut txn = remote.transact_mut();
+            config.insert(&mut txn, "admin", true);
+            text.push(&mut txn, "hello");
+        }
+        let local = Doc::with_client_id(2);
+        let update = remote
+            .transact()
+            .encode_state_as_update_v1(&StateVector::default());
+        local
+            .transact_mut()
+            .apply_update(Update::decode_v1(&update).unwrap());
+
+        let sv = remote.transact().state_vector();
+        {
+            let mut txn = remote.transact_mut();
+            config.remove(&mut txn, "admin");
+            text.remove_range(&mut txn, 0, 1);
+        }
+        let diff = remote.transact().encode_state_as_update_v1(&sv);
+
+        // deletions of the blocks unknown to the update cannot be verified without a document
+        let update = Update::decode_v1(&diff).unwrap().filter(|_| true);
+        assert!(update.delete_set.is_empty());
+
+        let update = {
+            let txn = local.transact();
+            Update::decode_v1(&diff)
+                .unwrap()
+                .filter_with(&txn, |block| {
+                    assert!(block.is_deletion());
+                    reject_root("config")(block)
+                })
+        };
+        local.transact_mut().apply_update(update);
+        let txn = local.transact();
+        assert_eq!(txn.get_text("text").unwrap().get_string(&txn), "ello");
+        let config = txn.get_map("config").unwrap();
+        assert_eq!(config.get(&txn, "admin"), Some(true.into()));
+    }
+
+    #[test]
+    fn filter_cyclic_blocks() {
+        // a single block of client 1, which is its own parent
+        let update = [1, 1, 1, 0, 4, 0, 1, 0, 1, b'a', 0];
+        let update = Update::decode_v1(&update).unwrap().filter(|block| {
+            assert!(block.root().is_none());
+            false
+        });
+        assert_eq!(update.inspect().gc_len, 1);
+
+        // block inserted after a rejected block, which is its own left neighbor
+        let update = [1, 2, 1, 0, 0x84, 1, 0, 1, b'a', 0x84, 1, 0, 1, b'b', 0];
+        let update = Update::decode_v1(&update)
+            .unwrap()
+            .filter(|block| block.id().clock != 0);
+        assert_eq!(update.inspect().gc_len, 2);
+    }
+}
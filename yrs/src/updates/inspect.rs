@@ -0,0 +1,366 @@
+//! Introspection of document updates without applying them.
+//!
+//! [inspect_v1]/[inspect_v2] decode a binary update and produce a [UpdateSummary] describing
+//! which root types it affects, which blocks it inserts and deletes and which sub-documents it
+//! creates. It can be used by servers to log or authorize incoming updates before applying them
+//! to a document.
+
+use crate::block::{ClientID, Item, ItemContent};
+use crate::branch::BranchPtr;
+use crate::encoding::read::Error;
+use crate::store::BlockRange;
+use crate::types::TypePtr;
+use crate::update::{BlockCarrier, Update};
+use crate::updates::decoder::Decode;
+use crate::{Store, Uuid, ID};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::Arc;
+
+/// Decodes a lib0 v1 encoded `update` and returns a summary of its contents.
+pub fn inspect_v1(update: &[u8]) -> Result<UpdateSummary, Error> {
+    Ok(Update::decode_v1(update)?.inspect())
+}
+
+/// Decodes a lib0 v2 encoded `update` and returns a summary of its contents.
+pub fn inspect_v2(update: &[u8]) -> Result<UpdateSummary, Error> {
+    Ok(Update::decode_v2(update)?.inspect())
+}
+
+/// Summary of the contents of an [Update], produced by [Update::inspect].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UpdateSummary {
+    /// Names of the root types modified by an update - either directly or through nested
+    /// collections created within the same update.
+    pub roots: BTreeSet<Arc<str>>,
+    /// Identifiers of the nested collections modified by an update, which have not been created
+    /// within the same update. A root type they belong to can only be resolved by a document,
+    /// which already contains them.
+    pub nested: BTreeSet<ID>,
+    /// Continuous ranges of blocks inserted by an update, ordered by client and clock.
+    pub inserted: Vec<BlockRange>,
+    /// Continuous ranges of blocks deleted by an update, ordered by client and clock.
+    pub deleted: Vec<BlockRange>,
+    /// Number of inserted blocks. Blocks which are already garbage collected are not included.
+    pub insert_count: usize,
+    /// Number of clock ticks occupied by the inserted blocks.
+    pub inserted_len: u32,
+    /// Number of clock ticks occupied by the garbage collected blocks.
+    pub gc_len: u32,
+    /// Number of clock ticks occupied by the deleted blocks.
+    pub deleted_len: u32,
+    /// Unique identifiers of sub-documents inserted by an update.
+    pub subdocs: Vec<Uuid>,
+}
+
+impl UpdateSummary {
+    /// Returns the range of clocks inserted by a given `client` - from the lowest to the highest
+    /// one. Returns `None` if an update doesn't contain any blocks of that client.
+    pub fn client_range(&self, client: ClientID) -> Option<BlockRange> {
+        let mut ranges = self.inserted.iter().filter(|r| r.client == client);
+        let first = ranges.next()?;
+        let last = ranges.next_back().unwrap_or(first);
+        Some(BlockRange::new(client, first.start, last.end))
+    }
+
+    /// Returns a number of clock ticks inserted by each client.
+    pub fn clients(&self) -> BTreeMap<ClientID, u32> {
+        let mut clients = BTreeMap::new();
+        for range in self.inserted.iter() {
+            *clients.entry(range.client).or_default() += range.len();
+        }
+        clients
+    }
+}
+
+impl Update {
+    /// Returns a summary of the contents of a current update, without integrating it into any
+    /// document.
+    pub fn inspect(&self) -> UpdateSummary {
+        let mut summary = UpdateSummary::default();
+        // nested collections can be resolved up to the root, if they were inserted within this
+        // update as well
+        let mut index = ItemIndex::new(self);
+        for block in self.blocks.blocks() {
+            let (id, len) = (*block.id(), block.len());
+            match block {
+                BlockCarrier::Skip(_) => {}
+                BlockCarrier::GC(_) => summary.gc_len += len,
+                BlockCarrier::Item(item) => {
+                    summary.insert_count += 1;
+                    summary.inserted_len += len;
+                    push_range(&mut summary.inserted, id.client, id.clock, id.clock + len);
+                    if let ItemContent::Doc(_, doc) = &item.content {
+                        summary.subdocs.push(doc.guid().clone());
+                    }
+                    match index.resolve_root(item, None) {
+                        Ok(name) => {
+                            summary.roots.insert(name);
+                        }
+                        Err(Some(id)) => {
+                            summary.nested.insert(id);
+                        }
+                        Err(None) => {}
+                    }
+                }
+            }
+        }
+        for (&client, range) in self.delete_set.iter() {
+            for r in range.iter() {
+                summary.deleted_len += r.end - r.start;
+                push_range(&mut summary.deleted, client, r.start, r.end);
+            }
+        }
+        summary.inserted.sort_by_key(|r| (r.client, r.start));
+        summary.deleted.sort_by_key(|r| (r.client, r.start));
+        summary
+    }
+}
+
+/// Appends a range of clocks, merging it with the last one if they are adjacent.
+fn push_range(ranges: &mut Vec<BlockRange>, client: ClientID, start: u32, end: u32) {
+    if let Some(last) = ranges.last_mut() {
+        if last.client == client && last.end == start {
+            last.end = end;
+            return;
+        }
+    }
+    ranges.push(BlockRange::new(client, start, end));
+}
+
+/// Index of the blocks stored in an [Update], ordered by their clocks. Used to resolve the root
+/// types, which the items of an update have been inserted into.
+pub(crate) struct ItemIndex<'a> {
+    clients: HashMap<ClientID, Vec<&'a BlockCarrier>>,
+    /// Number of items stored in an update.
+    len: usize,
+    /// Root types resolved so far, by the identifiers of the items stored in an update.
+    resolved: HashMap<ID, Result<Arc<str>, Option<ID>>>,
+}
+
+impl<'a> ItemIndex<'a> {
+    pub fn new(update: &'a Update) -> Self {
+        let mut clients: HashMap<ClientID, Vec<&'a BlockCarrier>> = HashMap::new();
+        let mut len = 0;
+        for block in update.blocks.blocks() {
+            match block {
+                BlockCarrier::Skip(_) => continue,
+                BlockCarrier::Item(_) => len += 1,
+                BlockCarrier::GC(_) => {}
+            }
+            clients.entry(block.id().client).or_default().push(block);
+        }
+        ItemIndex {
+            clients,
+            len,
+            resolved: HashMap::new(),
+        }
+    }
+
+    /// Returns a block of an update, which contains a given `id`. Skipped ranges are not included.
+    pub fn get_block(&self, id: &ID) -> Option<&'a BlockCarrier> {
+        let blocks = self.clients.get(&id.client)?;
+        let i = blocks.partition_point(|block| block.id().clock + block.len() <= id.clock);
+        let block = *blocks.get(i)?;
+        if block.id().clock <= id.clock {
+            Some(block)
+        } else {
+            None
+        }
+    }
+
+    /// Returns an item of an update, which contains a given `id`.
+    pub fn get(&self, id: &ID) -> Option<&'a Item> {
+        match self.get_block(id)? {
+            BlockCarrier::Item(item) => Some(item),
+            _ => None,
+        }
+    }
+
+    /// Follows the parents and neighbors of an `item` up to the root type, first within an update
+    /// and then within a `store`. Returns an error with the identifier of the first collection,
+    /// which could not be found, or `None` if a root type could not be resolved otherwise.
+    pub fn resolve_root(
+        &mut self,
+        mut item: &'a Item,
+        store: Option<&Store>,
+    ) -> Result<Arc<str>, Option<ID>> {
+        let mut path = Vec::new();
+        let result = loop {
+            if let Some(result) = self.resolved.get(&item.id) {
+                break result.clone();
+            }
+            // a chain of parents and neighbors within a valid update never visits the same item
+            // twice, so the longer one must be cyclic
+            if path.len() >= self.len {
+                break Err(None);
+            }
+            path.push(item.id);
+            let (id, is_parent) = match &item.parent {
+                TypePtr::Named(name) => break Ok(name.clone()),
+                TypePtr::Branch(branch) => break branch_root(*branch).ok_or(None),
+                TypePtr::ID(id) => match self.get(id) {
+                    Some(parent) => {
+                        item = parent;
+                        continue;
+                    }
+                    None => (*id, true),
+                },
+                // neighbors of an item share its parent
+                TypePtr::Unknown => match item.origin.or(item.right_origin) {
+                    None => break Err(None),
+                    Some(id) => match self.get(&id) {
+                        Some(neighbor) => {
+                            item = neighbor;
+                            continue;
+                        }
+                        None => (id, false),
+                    },
+                },
+            };
+            let resolved = store
+                .and_then(|store| store.blocks.get_item(&id))
+                .and_then(|ptr| {
+                    if is_parent {
+                        match &ptr.content {
+                            ItemContent::Type(branch) => branch_root(BranchPtr::from(branch)),
+                            _ => None,
+                        }
+                    } else {
+                        match &ptr.parent {
+                            TypePtr::Branch(branch) => branch_root(*branch),
+                            _ => None,
+                        }
+                    }
+                });
+            break match resolved {
+                Some(name) => Ok(name),
+                None if is_parent => Err(Some(id)),
+                None => Err(None),
+            };
+        };
+        for id in path {
+            self.resolved.insert(id, result.clone());
+        }
+        result
+    }
+}
+
+/// Returns a name of the root type containing a given `branch`.
+pub(crate) fn branch_root(mut branch: BranchPtr) -> Option<Arc<str>> {
+    while let Some(item) = branch.item {
+        match &item.parent {
+            TypePtr::Branch(parent) => branch = *parent,
+            _ => return None,
+        }
+    }
+    branch.name.clone()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::types::TypePtr;
+    use crate::updates::decoder::Decode;
+    use crate::updates::inspect::{inspect_v1, inspect_v2, ItemIndex};
+    use crate::{
+        ArrayPrelim, BlockRange, Doc, Map, MapPrelim, MapRef, ReadTxn, StateVector, Text, Transact,
+        Update, ID,
+    };
+    use std::collections::BTreeSet;
+    use std::sync::Arc;
+
+    #[test]
+    fn inspect_update() {
+        let doc = Doc::with_client_id(1);
+        let text = doc.get_or_insert_text("text");
+        let map = doc.get_or_insert_map("map");
+        {
+            let mut txn = doc.transact_mut();
+            text.insert(&mut txn, 0, "hello");
+            let nested = map.insert(&mut txn, "nested", MapPrelim::default());
+            nested.insert(&mut txn, "key", "value");
+            map.insert(&mut txn, "subdoc", Doc::new());
+        }
+        let sv = doc.transact().state_vector();
+        {
+            let mut txn = doc.transact_mut();
+            text.remove_range(&mut txn, 0, 2);
+            let nested: MapRef = map.get(&txn, "nested").unwrap().cast().unwrap();
+            nested.insert(&mut txn, "key2", "value2");
+        }
+
+        let full = doc
+            .transact()
+            .encode_state_as_update_v1(&StateVector::default());
+        let summary = inspect_v1(&full).unwrap();
+        let roots: BTreeSet<Arc<str>> = vec!["map".into(), "text".into()].into_iter().collect();
+        assert_eq!(summary.roots, roots);
+        assert!(summary.nested.is_empty());
+        assert_eq!(summary.inserted, vec![BlockRange::new(1, 0, 9)]);
+        assert_eq!(summary.deleted, vec![BlockRange::new(1, 0, 2)]);
+        assert_eq!(summary.deleted_len, 2);
+        assert_eq!(summary.subdocs.len(), 1);
+        assert_eq!(summary.clients().get(&1), Some(&9));
+
+        // nested map has not been created within this update
+        let diff = doc.transact().encode_state_as_update_v2(&sv);
+        let summary = inspect_v2(&diff).unwrap();
+        assert!(summary.roots.is_empty());
+        assert_eq!(
+            summary.nested.iter().collect::<Vec<_>>(),
+            vec![&ID::new(1, 5)]
+        );
+        assert_eq!(summary.insert_count, 1);
+        assert_eq!(summary.client_range(1), Some(BlockRange::new(1, 8, 9)));
+        assert_eq!(summary.deleted, vec![BlockRange::new(1, 0, 2)]);
+        assert!(summary.subdocs.is_empty());
+    }
+
+    #[test]
+    fn inspect_resolves_roots_through_neighbors() {
+        let d1 = Doc::with_client_id(1);
+        let t1 = d1.get_or_insert_text("text");
+        t1.push(&mut d1.transact_mut(), "a");
+        let d2 = Doc::with_client_id(2);
+        let t2 = d2.get_or_insert_text("text");
+        let update = d1
+            .transact()
+            .encode_state_as_update_v1(&StateVector::default());
+        d2.transact_mut()
+            .apply_update(Update::decode_v1(&update).unwrap());
+        t2.push(&mut d2.transact_mut(), "b");
+
+        let update = d2
+            .transact()
+            .encode_state_as_update_v1(&StateVector::default());
+        let update = Update::decode_v1(&update).unwrap();
+        let mut index = ItemIndex::new(&update);
+        let item = index.get(&ID::new(2, 0)).unwrap();
+        assert!(matches!(item.parent, TypePtr::Unknown));
+        assert_eq!(index.resolve_root(item, None), Ok("text".into()));
+    }
+
+    #[test]
+    fn inspect_skips_gc_blocks() {
+        let doc = Doc::with_client_id(1);
+        let map = doc.get_or_insert_map("map");
+        map.insert(&mut doc.transact_mut(), "list", ArrayPrelim::from([1, 2]));
+        map.remove(&mut doc.transact_mut(), "list");
+        let update = doc
+            .transact()
+            .encode_state_as_update_v1(&StateVector::default());
+        let summary = inspect_v1(&update).unwrap();
+        assert_eq!(summary.inserted, vec![BlockRange::new(1, 0, 1)]);
+        assert_eq!(summary.insert_count, 1);
+        assert_eq!(summary.gc_len, 2);
+    }
+
+    #[test]
+    fn inspect_cyclic_parents() {
+        // a single block of client 1, which is its own parent
+        let update = [1, 1, 1, 0, 4, 0, 1, 0, 1, b'a', 0];
+        let summary = inspect_v1(&update).unwrap();
+        assert!(summary.roots.is_empty());
+        assert!(summary.nested.is_empty());
+        assert_eq!(summary.insert_count, 1);
+    }
+}
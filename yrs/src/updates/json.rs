@@ -0,0 +1,503 @@
+//! Human-readable JSON representation of document updates.
+//!
+//! [Update::to_json] produces a JSON document describing every block and deletion of an update,
+//! which can be inspected by hand, diffed or stored next to test fixtures. The representation is
+//! lossless: [Update::from_json] reconstructs an update equal to the original one.
+//!
+//! ```json
+//! {
+//!   "blocks": [
+//!     { "id": [1, 0], "parent": "text", "content": { "type": "string", "value": "hello" } },
+//!     { "id": [1, 5], "origin": [1, 4], "content": { "type": "any", "values": [1.5, 2] } },
+//!     { "id": [2, 0], "gc": 3 }
+//!   ],
+//!   "deleteSet": [ { "client": 1, "ranges": [[0, 2]] } ]
+//! }
+//! ```
+//!
+//! Blocks are ordered by client and clock. Items have optional `origin`, `rightOrigin`, `parent`
+//! (either a root type name or an ID of a nested type's item) and `parentSub` fields, omitted when
+//! absent. Since JSON can't represent all [Any] values, some of them use tagged objects:
+//! `{"$undefined": true}`, `{"$buffer": "<hex>"}`, `{"$number": "NaN"}` and `{"$map": {...}}` for
+//! maps which keys start with `$`. Floating point numbers are always written with a fraction
+//! part, which distinguishes them from integers ([Any::BigInt]). Contents which have no readable
+//! form (moves, sub-documents, weak links) carry their lib0 v1 encoding in an `encoded` field.
+//!
+//! # Example
+//!
+//! ```rust
+//! use yrs::{Doc, ReadTxn, StateVector, Text, Transact, Update};
+//! use yrs::updates::decoder::Decode;
+//!
+//! let doc = Doc::with_client_id(1);
+//! let text = doc.get_or_insert_text("text");
+//! text.push(&mut doc.transact_mut(), "hello");
+//! let bytes = doc.transact().encode_state_as_update_v1(&StateVector::default());
+//!
+//! let update = Update::decode_v1(&bytes).unwrap();
+//! let json = update.to_json();
+//! assert!(json.contains(r#""value": "hello""#));
+//! assert!(Update::from_json(&json).unwrap() == update);
+//! ```
+
+use crate::block::{
+    BlockRange, Item, ItemContent, BLOCK_ITEM_ANY_REF_NUMBER, BLOCK_ITEM_BINARY_REF_NUMBER,
+    BLOCK_ITEM_DELETED_REF_NUMBER, BLOCK_ITEM_DOC_REF_NUMBER, BLOCK_ITEM_EMBED_REF_NUMBER,
+    BLOCK_ITEM_FORMAT_REF_NUMBER, BLOCK_ITEM_JSON_REF_NUMBER, BLOCK_ITEM_MOVE_REF_NUMBER,
+    BLOCK_ITEM_STRING_REF_NUMBER, BLOCK_ITEM_TYPE_REF_NUMBER,
+};
+use crate::branch::{Branch, BranchID};
+use crate::encoding::read::{Cursor, Error};
+use crate::id_set::DeleteSet;
+use crate::types::{TypePtr, TypeRef};
+use crate::update::{BlockCarrier, Update, UpdateBlocks};
+use crate::updates::decoder::DecoderV1;
+use crate::updates::encoder::{Encoder, EncoderV1};
+use crate::{Any, ID};
+use serde_json::{json, Map, Number, Value};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::sync::Arc;
+
+impl Update {
+    /// Returns a lossless, human-readable JSON representation of current update. See
+    /// [module level docs](crate::updates::json) for the description of its format.
+    pub fn to_json(&self) -> String {
+        let mut blocks: Vec<&BlockCarrier> = self.blocks.blocks().collect();
+        blocks.sort_by_key(|block| (block.id().client, block.id().clock));
+        let blocks: Vec<Value> = blocks.into_iter().map(block_to_json).collect();
+
+        let mut delete_set: Vec<_> = self.delete_set.iter().collect();
+        delete_set.sort_by_key(|(client, _)| **client);
+        let delete_set: Vec<Value> = delete_set
+            .into_iter()
+            .map(|(client, range)| {
+                let ranges: Vec<Value> = range.iter().map(|r| json!([r.start, r.end])).collect();
+                json!({ "client": client, "ranges": ranges })
+            })
+            .collect();
+
+        let value = json!({ "blocks": blocks, "deleteSet": delete_set });
+        serde_json::to_string_pretty(&value).unwrap()
+    }
+
+    /// Parses an update from its JSON representation produced by [Update::to_json].
+    pub fn from_json(json: &str) -> Result<Update, Error> {
+        let value: Value = serde_json::from_str(json)?;
+        let mut blocks = UpdateBlocks::default();
+        for block in field(&value, "blocks")?
+            .as_array()
+            .ok_or_else(|| invalid("blocks"))?
+        {
+            blocks.add_block(block_from_json(block)?);
+        }
+        let mut delete_set = DeleteSet::new();
+        let entries = field(&value, "deleteSet")?;
+        for entry in entries.as_array().ok_or_else(|| invalid("deleteSet"))? {
+            let client = as_u64(field(entry, "client")?)?;
+            let ranges = field(entry, "ranges")?;
+            for range in ranges.as_array().ok_or_else(|| invalid("ranges"))? {
+                let (start, end) = match range.as_array().map(Vec::as_slice) {
+                    Some([start, end]) => (as_u32(start)?, as_u32(end)?),
+                    _ => return Err(invalid("delete set range")),
+                };
+                if end <= start {
+                    return Err(invalid("delete set range"));
+                }
+                delete_set.insert(ID::new(client, start), end - start);
+            }
+        }
+        Ok(Update { blocks, delete_set })
+    }
+}
+
+fn invalid(what: &str) -> Error {
+    Error::Custom(format!("invalid update JSON: unexpected {}", what))
+}
+
+fn field<'a>(value: &'a Value, key: &str) -> Result<&'a Value, Error> {
+    value.get(key).ok_or_else(|| invalid(key))
+}
+
+fn as_u64(value: &Value) -> Result<u64, Error> {
+    value.as_u64().ok_or_else(|| invalid("integer"))
+}
+
+fn as_u32(value: &Value) -> Result<u32, Error> {
+    let n = as_u64(value)?;
+    u32::try_from(n).map_err(|_| invalid("integer"))
+}
+
+fn as_str(value: &Value) -> Result<&str, Error> {
+    value.as_str().ok_or_else(|| invalid("string"))
+}
+
+fn id_to_json(id: &ID) -> Value {
+    json!([id.client, id.clock])
+}
+
+fn id_from_json(value: &Value) -> Result<ID, Error> {
+    match value.as_array().map(Vec::as_slice) {
+        Some([client, clock]) => Ok(ID::new(as_u64(client)?, as_u32(clock)?)),
+        _ => Err(invalid("ID")),
+    }
+}
+
+fn block_to_json(block: &BlockCarrier) -> Value {
+    let mut json = Map::new();
+    json.insert("id".into(), id_to_json(block.id()));
+    match block {
+        BlockCarrier::GC(range) => {
+            json.insert("gc".into(), range.len.into());
+        }
+        BlockCarrier::Skip(range) => {
+            json.insert("skip".into(), range.len.into());
+        }
+        BlockCarrier::Item(item) => {
+            if let Some(origin) = &item.origin {
+                json.insert("origin".into(), id_to_json(origin));
+            }
+            if let Some(right_origin) = &item.right_origin {
+                json.insert("rightOrigin".into(), id_to_json(right_origin));
+            }
+            let parent = match &item.parent {
+                TypePtr::Unknown => None,
+                TypePtr::Named(name) => Some(Value::from(name.as_ref())),
+                TypePtr::ID(id) => Some(id_to_json(id)),
+                TypePtr::Branch(branch) => match branch.id() {
+                    BranchID::Root(name) => Some(Value::from(name.as_ref())),
+                    BranchID::Nested(id) => Some(id_to_json(&id)),
+                },
+            };
+            if let Some(parent) = parent {
+                json.insert("parent".into(), parent);
+            }
+            if let Some(parent_sub) = &item.parent_sub {
+                json.insert("parentSub".into(), parent_sub.as_ref().into());
+            }
+            json.insert("content".into(), content_to_json(&item.content));
+        }
+    }
+    Value::Object(json)
+}
+
+fn block_from_json(value: &Value) -> Result<BlockCarrier, Error> {
+    let id = id_from_json(field(value, "id")?)?;
+    if let Some(len) = value.get("gc") {
+        return Ok(BlockCarrier::GC(BlockRange::new(id, as_u32(len)?)));
+    }
+    if let Some(len) = value.get("skip") {
+        return Ok(BlockCarrier::Skip(BlockRange::new(id, as_u32(len)?)));
+    }
+    let origin = value.get("origin").map(id_from_json).transpose()?;
+    let right_origin = value.get("rightOrigin").map(id_from_json).transpose()?;
+    let parent = match value.get("parent") {
+        None => TypePtr::Unknown,
+        Some(Value::String(name)) => TypePtr::Named(name.as_str().into()),
+        Some(id) => TypePtr::ID(id_from_json(id)?),
+    };
+    let parent_sub = match value.get("parentSub") {
+        None => None,
+        Some(key) => Some(Arc::from(as_str(key)?)),
+    };
+    let content = content_from_json(field(value, "content")?)?;
+    let item = Item::new(
+        id,
+        None,
+        origin,
+        None,
+        right_origin,
+        parent,
+        parent_sub,
+        content,
+    )
+    .ok_or_else(|| invalid("empty item content"))?;
+    Ok(BlockCarrier::from(item))
+}
+
+const CONTENT_TYPES: [(&str, u8); 10] = [
+    ("any", BLOCK_ITEM_ANY_REF_NUMBER),
+    ("binary", BLOCK_ITEM_BINARY_REF_NUMBER),
+    ("deleted", BLOCK_ITEM_DELETED_REF_NUMBER),
+    ("doc", BLOCK_ITEM_DOC_REF_NUMBER),
+    ("json", BLOCK_ITEM_JSON_REF_NUMBER),
+    ("embed", BLOCK_ITEM_EMBED_REF_NUMBER),
+    ("format", BLOCK_ITEM_FORMAT_REF_NUMBER),
+    ("string", BLOCK_ITEM_STRING_REF_NUMBER),
+    ("type", BLOCK_ITEM_TYPE_REF_NUMBER),
+    ("move", BLOCK_ITEM_MOVE_REF_NUMBER),
+];
+
+fn content_to_json(content: &ItemContent) -> Value {
+    let ref_num = content.get_ref_number();
+    let (name, _) = CONTENT_TYPES.iter().find(|(_, r)| *r == ref_num).unwrap();
+    match content {
+        ItemContent::Any(values) => {
+            let values: Vec<Value> = values.iter().map(any_to_json).collect();
+            json!({ "type": name, "values": values })
+        }
+        ItemContent::Binary(buf) => json!({ "type": name, "value": to_hex(buf) }),
+        ItemContent::Deleted(len) => json!({ "type": name, "len": len }),
+        ItemContent::JSON(values) => json!({ "type": name, "values": values }),
+        ItemContent::Embed(value) => json!({ "type": name, "value": any_to_json(value) }),
+        ItemContent::Format(key, value) => {
+            json!({ "type": name, "key": key.as_ref(), "value": any_to_json(value) })
+        }
+        ItemContent::String(s) => json!({ "type": name, "value": s.as_str() }),
+        ItemContent::Type(branch) => match type_ref_name(&branch.type_ref) {
+            Some(type_ref) => {
+                let mut json = json!({ "type": name, "typeRef": type_ref });
+                if let TypeRef::XmlElement(tag) = &branch.type_ref {
+                    json["tag"] = tag.as_ref().into();
+                }
+                json
+            }
+            None => json!({ "type": name, "encoded": encode_content(content) }),
+        },
+        ItemContent::Doc(_, doc) => json!({
+            "type": name,
+            "guid": doc.guid().as_ref(),
+            "encoded": encode_content(content),
+        }),
+        ItemContent::Move(_) => json!({ "type": name, "encoded": encode_content(content) }),
+    }
+}
+
+fn content_from_json(value: &Value) -> Result<ItemContent, Error> {
+    let name = as_str(field(value, "type")?)?;
+    let (_, ref_num) = CONTENT_TYPES
+        .iter()
+        .find(|(n, _)| *n == name)
+        .ok_or_else(|| invalid("content type"))?;
+    if let Some(encoded) = value.get("encoded") {
+        let buf = from_hex(as_str(encoded)?)?;
+        let mut decoder = DecoderV1::new(Cursor::new(&buf));
+        return ItemContent::decode(&mut decoder, *ref_num);
+    }
+    let content = match name {
+        "any" => {
+            let values = field(value, "values")?;
+            let values = values.as_array().ok_or_else(|| invalid("values"))?;
+            ItemContent::Any(values.iter().map(any_from_json).collect::<Result<_, _>>()?)
+        }
+        "binary" => ItemContent::Binary(from_hex(as_str(field(value, "value")?)?)?),
+        "deleted" => ItemContent::Deleted(as_u32(field(value, "len")?)?),
+        "json" => {
+            let values = field(value, "values")?;
+            let values = values.as_array().ok_or_else(|| invalid("values"))?;
+            let values: Result<Vec<_>, _> = values
+                .iter()
+                .map(|v| as_str(v).map(str::to_owned))
+                .collect();
+            ItemContent::JSON(values?)
+        }
+        "embed" => ItemContent::Embed(any_from_json(field(value, "value")?)?),
+        "format" => ItemContent::Format(
+            as_str(field(value, "key")?)?.into(),
+            Box::new(any_from_json(field(value, "value")?)?),
+        ),
+        "string" => ItemContent::String(as_str(field(value, "value")?)?.into()),
+        "type" => {
+            let type_ref = match as_str(field(value, "typeRef")?)? {
+                "array" => TypeRef::Array,
+                "map" => TypeRef::Map,
+                "text" => TypeRef::Text,
+                "xmlElement" => TypeRef::XmlElement(as_str(field(value, "tag")?)?.into()),
+                "xmlFragment" => TypeRef::XmlFragment,
+                "xmlHook" => TypeRef::XmlHook,
+                "xmlText" => TypeRef::XmlText,
+                "subDoc" => TypeRef::SubDoc,
+                "counter" => TypeRef::Counter,
+                "gSet" => TypeRef::GSet,
+                "twoPhaseSet" => TypeRef::TwoPhaseSet,
+                "fixed" => TypeRef::Fixed,
+                "undefined" => TypeRef::Undefined,
+                _ => return Err(invalid("typeRef")),
+            };
+            ItemContent::Type(Branch::new(type_ref))
+        }
+        _ => return Err(invalid("content without encoded field")),
+    };
+    Ok(content)
+}
+
+fn type_ref_name(type_ref: &TypeRef) -> Option<&'static str> {
+    match type_ref {
+        TypeRef::Array => Some("array"),
+        TypeRef::Map => Some("map"),
+        TypeRef::Text => Some("text"),
+        TypeRef::XmlElement(_) => Some("xmlElement"),
+        TypeRef::XmlFragment => Some("xmlFragment"),
+        TypeRef::XmlHook => Some("xmlHook"),
+        TypeRef::XmlText => Some("xmlText"),
+        TypeRef::SubDoc => Some("subDoc"),
+        #[cfg(feature = "weak")]
+        TypeRef::WeakLink(_) => None,
+        TypeRef::Counter => Some("counter"),
+        TypeRef::GSet => Some("gSet"),
+        TypeRef::TwoPhaseSet => Some("twoPhaseSet"),
+        TypeRef::Fixed => Some("fixed"),
+        TypeRef::Undefined => Some("undefined"),
+    }
+}
+
+fn encode_content(content: &ItemContent) -> String {
+    let mut encoder = EncoderV1::new();
+    content.encode(&mut encoder);
+    to_hex(&encoder.to_vec())
+}
+
+fn any_to_json(any: &Any) -> Value {
+    match any {
+        Any::Null => Value::Null,
+        Any::Undefined => json!({ "$undefined": true }),
+        Any::Bool(b) => Value::Bool(*b),
+        Any::Number(n) => match Number::from_f64(*n) {
+            Some(n) => Value::Number(n),
+            None => json!({ "$number": n.to_string() }),
+        },
+        Any::BigInt(n) => Value::from(*n),
+        Any::String(s) => Value::from(s.as_ref()),
+        Any::Buffer(buf) => json!({ "$buffer": to_hex(buf) }),
+        Any::Array(values) => Value::Array(values.iter().map(any_to_json).collect()),
+        Any::Map(entries) => {
+            let map: Map<String, Value> = entries
+                .iter()
+                .map(|(k, v)| (k.clone(), any_to_json(v)))
+                .collect();
+            if entries.keys().any(|k| k.starts_with('$')) {
+                json!({ "$map": map })
+            } else {
+                Value::Object(map)
+            }
+        }
+    }
+}
+
+fn any_from_json(value: &Value) -> Result<Any, Error> {
+    let any = match value {
+        Value::Null => Any::Null,
+        Value::Bool(b) => Any::Bool(*b),
+        Value::Number(n) => {
+            if let Some(i) = n.as_i64() {
+                Any::BigInt(i)
+            } else {
+                Any::Number(n.as_f64().ok_or_else(|| invalid("number"))?)
+            }
+        }
+        Value::String(s) => Any::String(s.as_str().into()),
+        Value::Array(values) => Any::Array(
+            values
+                .iter()
+                .map(any_from_json)
+                .collect::<Result<Vec<_>, _>>()?
+                .into(),
+        ),
+        Value::Object(entries) => {
+            let mut tagged = entries.iter().filter(|_| entries.len() == 1);
+            let entries = match tagged.next() {
+                Some((tag, _)) if tag == "$undefined" => return Ok(Any::Undefined),
+                Some((tag, value)) if tag == "$buffer" => {
+                    return Ok(Any::Buffer(from_hex(as_str(value)?)?.into()))
+                }
+                Some((tag, value)) if tag == "$number" => {
+                    let n = as_str(value)?.parse().map_err(|_| invalid("number"))?;
+                    return Ok(Any::Number(n));
+                }
+                Some((tag, value)) if tag == "$map" => {
+                    value.as_object().ok_or_else(|| invalid("$map"))?
+                }
+                _ => entries,
+            };
+            let mut map = HashMap::with_capacity(entries.len());
+            for (key, value) in entries.iter() {
+                map.insert(key.clone(), any_from_json(value)?);
+            }
+            Any::Map(Arc::new(map))
+        }
+    };
+    Ok(any)
+}
+
+fn to_hex(buf: &[u8]) -> String {
+    use std::fmt::Write;
+    let mut hex = String::with_capacity(buf.len() * 2);
+    for b in buf {
+        write!(hex, "{:02x}", b).unwrap();
+    }
+    hex
+}
+
+fn from_hex(hex: &str) -> Result<Vec<u8>, Error> {
+    hex.as_bytes()
+        .chunks(2)
+        .map(|pair| {
+            let pair = std::str::from_utf8(pair).ok().filter(|p| p.len() == 2);
+            pair.and_then(|p| u8::from_str_radix(p, 16).ok())
+                .ok_or_else(|| invalid("hex string"))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::updates::decoder::Decode;
+    use crate::{
+        Any, Array, ArrayPrelim, Doc, Map, MapPrelim, ReadTxn, StateVector, Text, Transact, Update,
+        XmlElementPrelim, XmlFragment,
+    };
+    use std::collections::HashMap;
+
+    #[test]
+    fn json_roundtrip() {
+        let doc = Doc::with_client_id(1);
+        let text = doc.get_or_insert_text("text");
+        let array = doc.get_or_insert_array("array");
+        let map = doc.get_or_insert_map("map");
+        let xml = doc.get_or_insert_xml_fragment("xml");
+        {
+            let mut txn = doc.transact_mut();
+            text.push(&mut txn, "hello zażółć 😀");
+            text.format(
+                &mut txn,
+                0,
+                5,
+                HashMap::from([("bold".into(), true.into())]),
+            );
+            text.remove_range(&mut txn, 1, 2);
+            array.insert_range(&mut txn, 0, [1.5, 2.0]);
+            array.push_back(&mut txn, Any::BigInt(i64::MAX));
+            array.push_back(&mut txn, Any::Undefined);
+            array.push_back(&mut txn, Any::Number(f64::NAN));
+            array.push_back(&mut txn, Any::from(vec![1u8, 2, 255]));
+            array.push_back(&mut txn, ArrayPrelim::default());
+            map.insert(&mut txn, "nested", MapPrelim::from([("$ref", "x")]));
+            map.insert(
+                &mut txn,
+                "key",
+                Any::from(HashMap::from([("$map".to_string(), Any::from(1))])),
+            );
+            map.insert(&mut txn, "key", "overridden");
+            xml.push_back(&mut txn, XmlElementPrelim::empty("div"));
+        }
+        let bytes = doc
+            .transact()
+            .encode_state_as_update_v1(&StateVector::default());
+        let update = Update::decode_v1(&bytes).unwrap();
+        let json = update.to_json();
+        let parsed = Update::from_json(&json).unwrap();
+        assert_eq!(parsed.to_json(), json);
+
+        // NaN is not equal to itself, so compare documents instead of updates
+        let doc2 = Doc::with_client_id(2);
+        doc2.transact_mut().apply_update(parsed);
+        let txn = doc2.transact();
+        assert_eq!(
+            txn.encode_state_as_update_v1(&StateVector::default()),
+            doc.transact()
+                .encode_state_as_update_v1(&StateVector::default())
+        );
+
+        assert!(Update::from_json(r#"{"blocks": [{"id": [1]}], "deleteSet": []}"#).is_err());
+    }
+}
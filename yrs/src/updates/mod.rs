@@ -1,2 +1,8 @@
+pub mod borrowed;
 pub mod decoder;
 pub mod encoder;
+pub mod filter;
+pub mod inspect;
+pub mod json;
+#[cfg(feature = "proto")]
+pub mod proto;
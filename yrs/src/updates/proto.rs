@@ -0,0 +1,1095 @@
+//! Protocol Buffers encoding of updates, state vectors and delete sets.
+//!
+//! [Update::encode_proto], [StateVector::encode_proto] and [DeleteSet::encode_proto] produce
+//! messages described by the schema below, so that the payload can be passed through gRPC
+//! pipelines and inspected with standard protobuf tooling. Blocks, their content and delete sets
+//! are modeled as regular protobuf messages rather than lib0 encoded payloads. Decoding ignores
+//! unknown fields. Any value decoded with `decode_proto` can be re-encoded using lib0 v1 or v2
+//! encoding:
+//!
+//! ```proto
+//! syntax = "proto3";
+//!
+//! package yrs;
+//!
+//! message Id {
+//!   uint64 client = 1;
+//!   uint32 clock = 2;
+//! }
+//!
+//! message StateVector {
+//!   map<uint64, uint32> clocks = 1;
+//! }
+//!
+//! message DeleteSet {
+//!   repeated ClientDeletes clients = 1;
+//! }
+//!
+//! message ClientDeletes {
+//!   uint64 client = 1;
+//!   repeated DeleteRange ranges = 2;
+//! }
+//!
+//! message DeleteRange {
+//!   uint32 clock = 1;
+//!   uint32 len = 2;
+//! }
+//!
+//! message Update {
+//!   repeated ClientBlocks clients = 1;
+//!   DeleteSet delete_set = 2;
+//! }
+//!
+//! message ClientBlocks {
+//!   uint64 client = 1;
+//!   // clock of the first block, following blocks start where the previous one ends
+//!   uint32 clock = 2;
+//!   repeated Block blocks = 3;
+//! }
+//!
+//! message Block {
+//!   oneof block {
+//!     Item item = 1;
+//!     // length of a garbage collected range
+//!     uint32 gc = 2;
+//!     // length of a range missing from the update
+//!     uint32 skip = 3;
+//!   }
+//! }
+//!
+//! message Item {
+//!   Id origin = 1;
+//!   Id right_origin = 2;
+//!   // parent and parent_sub are only present when an item has neither of the origins
+//!   oneof parent {
+//!     string parent_root = 3;
+//!     Id parent_id = 4;
+//!   }
+//!   optional string parent_sub = 5;
+//!   oneof content {
+//!     uint32 deleted = 6;
+//!     JsonContent json = 7;
+//!     bytes binary = 8;
+//!     string string = 9;
+//!     Any embed = 10;
+//!     Format format = 11;
+//!     TypeRef type = 12;
+//!     AnyContent any = 13;
+//!     DocContent doc = 14;
+//!     Move move = 15;
+//!   }
+//! }
+//!
+//! message JsonContent {
+//!   repeated string values = 1;
+//! }
+//!
+//! message AnyContent {
+//!   repeated Any values = 1;
+//! }
+//!
+//! message Format {
+//!   string key = 1;
+//!   Any value = 2;
+//! }
+//!
+//! message DocContent {
+//!   string guid = 1;
+//!   Any options = 2;
+//! }
+//!
+//! message Move {
+//!   Id start = 1;
+//!   Id end = 2;
+//!   bool start_after = 3;
+//!   bool end_after = 4;
+//!   sint32 priority = 5;
+//! }
+//!
+//! message TypeRef {
+//!   // one of the `TYPE_REFS_*` constants
+//!   uint32 kind = 1;
+//!   // tag name of an XML element
+//!   string name = 2;
+//!   // quoted range of a weak link
+//!   Id quote_start = 3;
+//!   Id quote_end = 4;
+//!   bool start_after = 5;
+//!   bool end_after = 6;
+//! }
+//!
+//! message Any {
+//!   oneof value {
+//!     bool null = 1;
+//!     bool undefined = 2;
+//!     bool bool = 3;
+//!     double number = 4;
+//!     sint64 big_int = 5;
+//!     string string = 6;
+//!     bytes buffer = 7;
+//!     AnyArray array = 8;
+//!     AnyMap map = 9;
+//!   }
+//! }
+//!
+//! message AnyArray {
+//!   repeated Any values = 1;
+//! }
+//!
+//! message AnyMap {
+//!   map<string, Any> entries = 1;
+//! }
+//! ```
+//!
+//! # Example
+//!
+//! ```rust
+//! use yrs::updates::decoder::Decode;
+//! use yrs::updates::encoder::Encode;
+//! use yrs::{Doc, ReadTxn, StateVector, Text, Transact, Update};
+//!
+//! let doc = Doc::new();
+//! let text = doc.get_or_insert_text("text");
+//! text.push(&mut doc.transact_mut(), "hello");
+//!
+//! let v1 = doc.transact().encode_state_as_update_v1(&StateVector::default());
+//! let proto = Update::decode_v1(&v1).unwrap().encode_proto();
+//! let update = Update::decode_proto(&proto).unwrap();
+//! assert_eq!(update.encode_v1(), v1);
+//!
+//! let sv = doc.transact().state_vector();
+//! assert_eq!(StateVector::decode_proto(&sv.encode_proto()).unwrap(), sv);
+//! ```
+//!
+//! [Update::encode_proto]: crate::Update::encode_proto
+//! [StateVector::encode_proto]: crate::StateVector::encode_proto
+//! [DeleteSet::encode_proto]: crate::DeleteSet::encode_proto
+
+use crate::block::{BlockRange, ClientID, Item, ItemContent};
+use crate::branch::Branch;
+use crate::doc::Options;
+use crate::encoding::read::{Cursor, DecodeLimits, Error, Read};
+use crate::encoding::write::Write;
+use crate::id_set::{IdRange, IdSet};
+use crate::moving::{Assoc, IndexScope, Move, StickyIndex};
+#[cfg(feature = "weak")]
+use crate::types::{weak::LinkSource, TYPE_REFS_WEAK};
+use crate::types::{
+    TypePtr, TypeRef, TYPE_REFS_ARRAY, TYPE_REFS_COUNTER, TYPE_REFS_DOC, TYPE_REFS_FIXED,
+    TYPE_REFS_GSET, TYPE_REFS_MAP, TYPE_REFS_TEXT, TYPE_REFS_TWO_PHASE_SET, TYPE_REFS_UNDEFINED,
+    TYPE_REFS_XML_ELEMENT, TYPE_REFS_XML_FRAGMENT, TYPE_REFS_XML_HOOK, TYPE_REFS_XML_TEXT,
+};
+use crate::update::{BlockCarrier, UpdateBlocks};
+use crate::{Any, DeleteSet, Doc, StateVector, Update, ID};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::iter::FromIterator;
+use std::ops::Range;
+use std::sync::Arc;
+
+const WIRE_VARINT: u64 = 0;
+const WIRE_FIXED64: u64 = 1;
+const WIRE_LEN: u64 = 2;
+const WIRE_FIXED32: u64 = 5;
+
+const ID_CLIENT: u64 = 1;
+const ID_CLOCK: u64 = 2;
+
+const STATE_VECTOR_CLOCKS: u64 = 1;
+const MAP_ENTRY_KEY: u64 = 1;
+const MAP_ENTRY_VALUE: u64 = 2;
+
+const DELETE_SET_CLIENTS: u64 = 1;
+const CLIENT_DELETES_CLIENT: u64 = 1;
+const CLIENT_DELETES_RANGES: u64 = 2;
+const DELETE_RANGE_CLOCK: u64 = 1;
+const DELETE_RANGE_LEN: u64 = 2;
+
+const UPDATE_CLIENTS: u64 = 1;
+const UPDATE_DELETE_SET: u64 = 2;
+const CLIENT_BLOCKS_CLIENT: u64 = 1;
+const CLIENT_BLOCKS_CLOCK: u64 = 2;
+const CLIENT_BLOCKS_BLOCKS: u64 = 3;
+
+const BLOCK_ITEM: u64 = 1;
+const BLOCK_GC: u64 = 2;
+const BLOCK_SKIP: u64 = 3;
+
+const ITEM_ORIGIN: u64 = 1;
+const ITEM_RIGHT_ORIGIN: u64 = 2;
+const ITEM_PARENT_ROOT: u64 = 3;
+const ITEM_PARENT_ID: u64 = 4;
+const ITEM_PARENT_SUB: u64 = 5;
+const ITEM_DELETED: u64 = 6;
+const ITEM_JSON: u64 = 7;
+const ITEM_BINARY: u64 = 8;
+const ITEM_STRING: u64 = 9;
+const ITEM_EMBED: u64 = 10;
+const ITEM_FORMAT: u64 = 11;
+const ITEM_TYPE: u64 = 12;
+const ITEM_ANY: u64 = 13;
+const ITEM_DOC: u64 = 14;
+const ITEM_MOVE: u64 = 15;
+
+/// Field number of `values` in `JsonContent`, `AnyContent` and `AnyArray` messages.
+const VALUES: u64 = 1;
+
+const FORMAT_KEY: u64 = 1;
+const FORMAT_VALUE: u64 = 2;
+
+const DOC_GUID: u64 = 1;
+const DOC_OPTIONS: u64 = 2;
+
+const MOVE_START: u64 = 1;
+const MOVE_END: u64 = 2;
+const MOVE_START_AFTER: u64 = 3;
+const MOVE_END_AFTER: u64 = 4;
+const MOVE_PRIORITY: u64 = 5;
+
+const TYPE_KIND: u64 = 1;
+const TYPE_NAME: u64 = 2;
+const TYPE_QUOTE_START: u64 = 3;
+const TYPE_QUOTE_END: u64 = 4;
+const TYPE_START_AFTER: u64 = 5;
+const TYPE_END_AFTER: u64 = 6;
+
+const ANY_NULL: u64 = 1;
+const ANY_UNDEFINED: u64 = 2;
+const ANY_BOOL: u64 = 3;
+const ANY_NUMBER: u64 = 4;
+const ANY_BIG_INT: u64 = 5;
+const ANY_STRING: u64 = 6;
+const ANY_BUFFER: u64 = 7;
+const ANY_ARRAY: u64 = 8;
+const ANY_MAP: u64 = 9;
+const ANY_MAP_ENTRIES: u64 = 1;
+
+impl Update {
+    /// Encodes current update as a protobuf `Update` message.
+    /// See [module level docs](crate::updates::proto).
+    pub fn encode_proto(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        let mut clients: Vec<_> = self.blocks.clients.iter().collect();
+        clients.sort_by_key(|(&client, _)| client);
+        for (&client, blocks) in clients {
+            put_message(&mut buf, UPDATE_CLIENTS, |msg| {
+                put_varint(msg, CLIENT_BLOCKS_CLIENT, client);
+                let clock = blocks.front().map(|block| block.id().clock).unwrap_or(0);
+                put_varint(msg, CLIENT_BLOCKS_CLOCK, clock as u64);
+                for block in blocks.iter() {
+                    put_message(msg, CLIENT_BLOCKS_BLOCKS, |msg| match block {
+                        BlockCarrier::Item(item) => {
+                            put_message(msg, BLOCK_ITEM, |msg| encode_item(msg, item))
+                        }
+                        BlockCarrier::GC(range) => put_varint(msg, BLOCK_GC, range.len as u64),
+                        BlockCarrier::Skip(range) => put_varint(msg, BLOCK_SKIP, range.len as u64),
+                    });
+                }
+            });
+        }
+        if !self.delete_set.is_empty() {
+            put_message(&mut buf, UPDATE_DELETE_SET, |msg| {
+                encode_delete_set(msg, &self.delete_set)
+            });
+        }
+        buf
+    }
+
+    /// Decodes an update from a protobuf `Update` message.
+    /// See [module level docs](crate::updates::proto).
+    pub fn decode_proto(buf: &[u8]) -> Result<Self, Error> {
+        let mut blocks = UpdateBlocks::default();
+        let mut delete_set = IdSet::new();
+        for field in Fields::new(buf) {
+            let (field, value) = field?;
+            match field {
+                UPDATE_CLIENTS => decode_client_blocks(value.bytes()?, &mut blocks)?,
+                UPDATE_DELETE_SET => decode_delete_set(value.bytes()?, &mut delete_set)?,
+                _ => { /* unknown field */ }
+            }
+        }
+        Ok(Update {
+            blocks,
+            delete_set: DeleteSet::from(delete_set),
+        })
+    }
+}
+
+impl StateVector {
+    /// Encodes current state vector as a protobuf `StateVector` message.
+    /// See [module level docs](crate::updates::proto).
+    pub fn encode_proto(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        let mut clocks: Vec<_> = self.iter().collect();
+        clocks.sort_by_key(|(&client, _)| client);
+        for (&client, &clock) in clocks {
+            put_message(&mut buf, STATE_VECTOR_CLOCKS, |msg| {
+                put_varint(msg, MAP_ENTRY_KEY, client);
+                put_varint(msg, MAP_ENTRY_VALUE, clock as u64);
+            });
+        }
+        buf
+    }
+
+    /// Decodes a state vector from a protobuf `StateVector` message.
+    /// See [module level docs](crate::updates::proto).
+    pub fn decode_proto(buf: &[u8]) -> Result<Self, Error> {
+        let mut clocks = HashMap::new();
+        for field in Fields::new(buf) {
+            let (field, value) = field?;
+            if field == STATE_VECTOR_CLOCKS {
+                let mut client: ClientID = 0;
+                let mut clock = 0;
+                for field in Fields::new(value.bytes()?) {
+                    let (field, value) = field?;
+                    match field {
+                        MAP_ENTRY_KEY => client = value.varint()?,
+                        MAP_ENTRY_VALUE => clock = value.uint32()?,
+                        _ => { /* unknown field */ }
+                    }
+                }
+                // later entries with the same key override earlier ones
+                clocks.insert(client, clock);
+            }
+        }
+        Ok(StateVector::from_iter(clocks))
+    }
+}
+
+impl DeleteSet {
+    /// Encodes current delete set as a protobuf `DeleteSet` message.
+    /// See [module level docs](crate::updates::proto).
+    pub fn encode_proto(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        encode_delete_set(&mut buf, self);
+        buf
+    }
+
+    /// Decodes a delete set from a protobuf `DeleteSet` message.
+    /// See [module level docs](crate::updates::proto).
+    pub fn decode_proto(buf: &[u8]) -> Result<Self, Error> {
+        let mut id_set = IdSet::new();
+        decode_delete_set(buf, &mut id_set)?;
+        Ok(DeleteSet::from(id_set))
+    }
+}
+
+fn put_tag(buf: &mut Vec<u8>, field: u64, wire_type: u64) {
+    buf.write_var(field << 3 | wire_type);
+}
+
+fn put_varint(buf: &mut Vec<u8>, field: u64, value: u64) {
+    put_tag(buf, field, WIRE_VARINT);
+    buf.write_var(value);
+}
+
+fn put_bool(buf: &mut Vec<u8>, field: u64, value: bool) {
+    put_varint(buf, field, value as u64);
+}
+
+fn put_double(buf: &mut Vec<u8>, field: u64, value: f64) {
+    put_tag(buf, field, WIRE_FIXED64);
+    buf.write_all(&value.to_le_bytes());
+}
+
+fn put_bytes(buf: &mut Vec<u8>, field: u64, value: &[u8]) {
+    put_tag(buf, field, WIRE_LEN);
+    buf.write_var(value.len());
+    buf.write_all(value);
+}
+
+fn put_message<F>(buf: &mut Vec<u8>, field: u64, f: F)
+where
+    F: FnOnce(&mut Vec<u8>),
+{
+    let mut msg = Vec::new();
+    f(&mut msg);
+    put_bytes(buf, field, &msg);
+}
+
+fn put_id(buf: &mut Vec<u8>, field: u64, id: &ID) {
+    put_message(buf, field, |msg| {
+        put_varint(msg, ID_CLIENT, id.client);
+        put_varint(msg, ID_CLOCK, id.clock as u64);
+    });
+}
+
+fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+fn unzigzag(value: u64) -> i64 {
+    ((value >> 1) as i64) ^ -((value & 1) as i64)
+}
+
+fn encode_delete_set(buf: &mut Vec<u8>, ds: &DeleteSet) {
+    let mut clients: Vec<_> = ds.iter().collect();
+    clients.sort_by_key(|(&client, _)| client);
+    for (&client, range) in clients {
+        put_message(buf, DELETE_SET_CLIENTS, |msg| {
+            put_varint(msg, CLIENT_DELETES_CLIENT, client);
+            for r in range.iter() {
+                put_message(msg, CLIENT_DELETES_RANGES, |msg| {
+                    put_varint(msg, DELETE_RANGE_CLOCK, r.start as u64);
+                    put_varint(msg, DELETE_RANGE_LEN, (r.end - r.start) as u64);
+                });
+            }
+        });
+    }
+}
+
+fn encode_item(buf: &mut Vec<u8>, item: &Item) {
+    if let Some(origin) = item.origin.as_ref() {
+        put_id(buf, ITEM_ORIGIN, origin);
+    }
+    if let Some(right_origin) = item.right_origin.as_ref() {
+        put_id(buf, ITEM_RIGHT_ORIGIN, right_origin);
+    }
+    if item.origin.is_none() && item.right_origin.is_none() {
+        match &item.parent {
+            TypePtr::Branch(branch) => {
+                if let Some(block) = branch.item {
+                    put_id(buf, ITEM_PARENT_ID, block.id());
+                } else if let Some(name) = branch.name.as_deref() {
+                    put_bytes(buf, ITEM_PARENT_ROOT, name.as_bytes());
+                }
+            }
+            TypePtr::Named(name) => put_bytes(buf, ITEM_PARENT_ROOT, name.as_bytes()),
+            TypePtr::ID(id) => put_id(buf, ITEM_PARENT_ID, id),
+            TypePtr::Unknown => { /* parent cannot be resolved, decoding will fail */ }
+        }
+        if let Some(parent_sub) = item.parent_sub.as_deref() {
+            put_bytes(buf, ITEM_PARENT_SUB, parent_sub.as_bytes());
+        }
+    }
+    match &item.content {
+        ItemContent::Deleted(len) => put_varint(buf, ITEM_DELETED, *len as u64),
+        ItemContent::JSON(values) => put_message(buf, ITEM_JSON, |msg| {
+            for value in values.iter() {
+                put_bytes(msg, VALUES, value.as_bytes());
+            }
+        }),
+        ItemContent::Binary(value) => put_bytes(buf, ITEM_BINARY, value),
+        ItemContent::String(value) => put_bytes(buf, ITEM_STRING, value.as_str().as_bytes()),
+        ItemContent::Embed(value) => put_message(buf, ITEM_EMBED, |msg| encode_any(msg, value)),
+        ItemContent::Format(key, value) => put_message(buf, ITEM_FORMAT, |msg| {
+            put_bytes(msg, FORMAT_KEY, key.as_bytes());
+            put_message(msg, FORMAT_VALUE, |msg| encode_any(msg, value));
+        }),
+        ItemContent::Type(branch) => {
+            put_message(buf, ITEM_TYPE, |msg| encode_type_ref(msg, &branch.type_ref))
+        }
+        ItemContent::Any(values) => put_message(buf, ITEM_ANY, |msg| {
+            for value in values.iter() {
+                put_message(msg, VALUES, |msg| encode_any(msg, value));
+            }
+        }),
+        ItemContent::Doc(_, doc) => put_message(buf, ITEM_DOC, |msg| {
+            let options = doc.options();
+            put_bytes(msg, DOC_GUID, options.guid.as_bytes());
+            put_message(msg, DOC_OPTIONS, |msg| encode_any(msg, &options.as_any()));
+        }),
+        ItemContent::Move(m) => put_message(buf, ITEM_MOVE, |msg| {
+            if let Some(id) = m.start.id() {
+                put_id(msg, MOVE_START, id);
+            }
+            if let Some(id) = m.end.id() {
+                put_id(msg, MOVE_END, id);
+            }
+            put_bool(msg, MOVE_START_AFTER, m.start.assoc == Assoc::After);
+            put_bool(msg, MOVE_END_AFTER, m.end.assoc == Assoc::After);
+            put_varint(msg, MOVE_PRIORITY, zigzag(m.priority as i64));
+        }),
+    }
+}
+
+fn encode_type_ref(buf: &mut Vec<u8>, type_ref: &TypeRef) {
+    put_varint(buf, TYPE_KIND, type_ref.kind() as u64);
+    match type_ref {
+        TypeRef::XmlElement(name) => put_bytes(buf, TYPE_NAME, name.as_bytes()),
+        #[cfg(feature = "weak")]
+        TypeRef::WeakLink(source) => {
+            if let Some(id) = source.quote_start.id() {
+                put_id(buf, TYPE_QUOTE_START, id);
+            }
+            if let Some(id) = source.quote_end.id() {
+                put_id(buf, TYPE_QUOTE_END, id);
+            }
+            put_bool(
+                buf,
+                TYPE_START_AFTER,
+                source.quote_start.assoc == Assoc::After,
+            );
+            put_bool(buf, TYPE_END_AFTER, source.quote_end.assoc == Assoc::After);
+        }
+        _ => { /* other types carry no data */ }
+    }
+}
+
+fn encode_any(buf: &mut Vec<u8>, any: &Any) {
+    match any {
+        Any::Null => put_bool(buf, ANY_NULL, true),
+        Any::Undefined => put_bool(buf, ANY_UNDEFINED, true),
+        Any::Bool(value) => put_bool(buf, ANY_BOOL, *value),
+        Any::Number(value) => put_double(buf, ANY_NUMBER, *value),
+        Any::BigInt(value) => put_varint(buf, ANY_BIG_INT, zigzag(*value)),
+        Any::String(value) => put_bytes(buf, ANY_STRING, value.as_bytes()),
+        Any::Buffer(value) => put_bytes(buf, ANY_BUFFER, value),
+        Any::Array(values) => put_message(buf, ANY_ARRAY, |msg| {
+            for value in values.iter() {
+                put_message(msg, VALUES, |msg| encode_any(msg, value));
+            }
+        }),
+        Any::Map(entries) => put_message(buf, ANY_MAP, |msg| {
+            let mut entries: Vec<_> = entries.iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(b.0));
+            for (key, value) in entries {
+                put_message(msg, ANY_MAP_ENTRIES, |msg| {
+                    put_bytes(msg, MAP_ENTRY_KEY, key.as_bytes());
+                    put_message(msg, MAP_ENTRY_VALUE, |msg| encode_any(msg, value));
+                });
+            }
+        }),
+    }
+}
+
+/// Value of a single protobuf field.
+enum Value<'a> {
+    Varint(u64),
+    Fixed64([u8; 8]),
+    Bytes(&'a [u8]),
+    Fixed32,
+}
+
+impl<'a> Value<'a> {
+    fn varint(self) -> Result<u64, Error> {
+        match self {
+            Value::Varint(value) => Ok(value),
+            _ => Err(Error::UnexpectedValue),
+        }
+    }
+
+    fn uint32(self) -> Result<u32, Error> {
+        u32::try_from(self.varint()?).map_err(|_| Error::UnexpectedValue)
+    }
+
+    fn bool(self) -> Result<bool, Error> {
+        Ok(self.varint()? != 0)
+    }
+
+    fn double(self) -> Result<f64, Error> {
+        match self {
+            Value::Fixed64(value) => Ok(f64::from_le_bytes(value)),
+            _ => Err(Error::UnexpectedValue),
+        }
+    }
+
+    fn bytes(self) -> Result<&'a [u8], Error> {
+        match self {
+            Value::Bytes(value) => Ok(value),
+            _ => Err(Error::UnexpectedValue),
+        }
+    }
+
+    fn string(self) -> Result<&'a str, Error> {
+        std::str::from_utf8(self.bytes()?).map_err(|_| Error::UnexpectedValue)
+    }
+}
+
+/// Iterator over `(field number, value)` pairs of a protobuf message.
+struct Fields<'a> {
+    cursor: Cursor<'a>,
+}
+
+impl<'a> Fields<'a> {
+    fn new(buf: &'a [u8]) -> Self {
+        Fields {
+            cursor: Cursor::new(buf),
+        }
+    }
+
+    fn read_field(&mut self) -> Result<(u64, Value<'a>), Error> {
+        let tag: u64 = self.cursor.read_var()?;
+        let value = match tag & 0b111 {
+            WIRE_VARINT => Value::Varint(self.cursor.read_var()?),
+            WIRE_FIXED64 => {
+                let mut value = [0u8; 8];
+                value.copy_from_slice(self.cursor.read_exact(8)?);
+                Value::Fixed64(value)
+            }
+            WIRE_LEN => {
+                let len: usize = self.cursor.read_var()?;
+                let start = self.cursor.next;
+                let end = start
+                    .checked_add(len)
+                    .filter(|&end| end <= self.cursor.buf.len())
+                    .ok_or(Error::EndOfBuffer(len))?;
+                self.cursor.next = end;
+                Value::Bytes(&self.cursor.buf[start..end])
+            }
+            WIRE_FIXED32 => {
+                self.cursor.read_exact(4)?;
+                Value::Fixed32
+            }
+            _ => return Err(Error::UnexpectedValue),
+        };
+        Ok((tag >> 3, value))
+    }
+}
+
+impl<'a> Iterator for Fields<'a> {
+    type Item = Result<(u64, Value<'a>), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.cursor.has_content() {
+            let result = self.read_field();
+            if result.is_err() {
+                // don't try to read past malformed field
+                self.cursor.next = self.cursor.buf.len();
+            }
+            Some(result)
+        } else {
+            None
+        }
+    }
+}
+
+fn decode_id(buf: &[u8]) -> Result<ID, Error> {
+    let mut id = ID::new(0, 0);
+    for field in Fields::new(buf) {
+        let (field, value) = field?;
+        match field {
+            ID_CLIENT => id.client = value.varint()?,
+            ID_CLOCK => id.clock = value.uint32()?,
+            _ => { /* unknown field */ }
+        }
+    }
+    Ok(id)
+}
+
+fn decode_delete_set(buf: &[u8], id_set: &mut IdSet) -> Result<(), Error> {
+    for field in Fields::new(buf) {
+        let (field, value) = field?;
+        if field != DELETE_SET_CLIENTS {
+            continue;
+        }
+        let mut client: ClientID = 0;
+        let mut ranges: Vec<Range<u32>> = Vec::new();
+        for field in Fields::new(value.bytes()?) {
+            let (field, value) = field?;
+            match field {
+                CLIENT_DELETES_CLIENT => client = value.varint()?,
+                CLIENT_DELETES_RANGES => {
+                    let mut clock = 0;
+                    let mut len = 0;
+                    for field in Fields::new(value.bytes()?) {
+                        let (field, value) = field?;
+                        match field {
+                            DELETE_RANGE_CLOCK => clock = value.uint32()?,
+                            DELETE_RANGE_LEN => len = value.uint32()?,
+                            _ => { /* unknown field */ }
+                        }
+                    }
+                    let end = clock.checked_add(len).ok_or(Error::UnexpectedValue)?;
+                    ranges.push(clock..end);
+                }
+                _ => { /* unknown field */ }
+            }
+        }
+        let range = if ranges.len() == 1 {
+            IdRange::Continuous(ranges.pop().unwrap())
+        } else {
+            IdRange::Fragmented(ranges)
+        };
+        id_set.insert_range(client, range);
+    }
+    Ok(())
+}
+
+fn decode_client_blocks(buf: &[u8], blocks: &mut UpdateBlocks) -> Result<(), Error> {
+    let mut client: ClientID = 0;
+    let mut clock = 0;
+    let mut encoded = Vec::new();
+    for field in Fields::new(buf) {
+        let (field, value) = field?;
+        match field {
+            CLIENT_BLOCKS_CLIENT => client = value.varint()?,
+            CLIENT_BLOCKS_CLOCK => clock = value.uint32()?,
+            CLIENT_BLOCKS_BLOCKS => encoded.push(value.bytes()?),
+            _ => { /* unknown field */ }
+        }
+    }
+    let blocks = blocks.clients.entry(client).or_default();
+    for buf in encoded {
+        if let Some(block) = decode_block(ID::new(client, clock), buf)? {
+            let len = block.len();
+            clock = clock.checked_add(len).ok_or(Error::UnexpectedValue)?;
+            if len != 0 {
+                // empty GC and skip ranges are malformed: they'd wrap their end clock
+                blocks.push_back(block);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn decode_block(id: ID, buf: &[u8]) -> Result<Option<BlockCarrier>, Error> {
+    let mut block = Err(Error::UnexpectedValue);
+    for field in Fields::new(buf) {
+        let (field, value) = field?;
+        match field {
+            BLOCK_ITEM => block = Ok(decode_item(id, value.bytes()?)?.map(BlockCarrier::from)),
+            BLOCK_GC => block = Ok(Some(BlockCarrier::GC(BlockRange::new(id, value.uint32()?)))),
+            BLOCK_SKIP => {
+                block = Ok(Some(BlockCarrier::Skip(BlockRange::new(
+                    id,
+                    value.uint32()?,
+                ))))
+            }
+            _ => { /* unknown field */ }
+        }
+    }
+    block
+}
+
+fn decode_item(id: ID, buf: &[u8]) -> Result<Option<Box<Item>>, Error> {
+    let mut origin = None;
+    let mut right_origin = None;
+    let mut parent = TypePtr::Unknown;
+    let mut parent_sub: Option<Arc<str>> = None;
+    let mut content = None;
+    for field in Fields::new(buf) {
+        let (field, value) = field?;
+        match field {
+            ITEM_ORIGIN => origin = Some(decode_id(value.bytes()?)?),
+            ITEM_RIGHT_ORIGIN => right_origin = Some(decode_id(value.bytes()?)?),
+            ITEM_PARENT_ROOT => parent = TypePtr::Named(value.string()?.into()),
+            ITEM_PARENT_ID => parent = TypePtr::ID(decode_id(value.bytes()?)?),
+            ITEM_PARENT_SUB => parent_sub = Some(value.string()?.into()),
+            ITEM_DELETED => content = Some(ItemContent::Deleted(value.uint32()?)),
+            ITEM_JSON => {
+                let mut values = Vec::new();
+                for field in Fields::new(value.bytes()?) {
+                    let (field, value) = field?;
+                    if field == VALUES {
+                        values.push(value.string()?.to_owned());
+                    }
+                }
+                content = Some(ItemContent::JSON(values));
+            }
+            ITEM_BINARY => content = Some(ItemContent::Binary(value.bytes()?.to_owned())),
+            ITEM_STRING => content = Some(ItemContent::String(value.string()?.into())),
+            ITEM_EMBED => content = Some(ItemContent::Embed(decode_any(value.bytes()?, 0)?)),
+            ITEM_FORMAT => {
+                let mut key = None;
+                let mut format = Any::Null;
+                for field in Fields::new(value.bytes()?) {
+                    let (field, value) = field?;
+                    match field {
+                        FORMAT_KEY => key = Some(value.string()?),
+                        FORMAT_VALUE => format = decode_any(value.bytes()?, 0)?,
+                        _ => { /* unknown field */ }
+                    }
+                }
+                let key = key.ok_or(Error::UnexpectedValue)?;
+                content = Some(ItemContent::Format(key.into(), Box::new(format)));
+            }
+            ITEM_TYPE => {
+                let type_ref = decode_type_ref(value.bytes()?)?;
+                content = Some(ItemContent::Type(Branch::new(type_ref)));
+            }
+            ITEM_ANY => {
+                let mut values = Vec::new();
+                for field in Fields::new(value.bytes()?) {
+                    let (field, value) = field?;
+                    if field == VALUES {
+                        values.push(decode_any(value.bytes()?, 0)?);
+                    }
+                }
+                content = Some(ItemContent::Any(values));
+            }
+            ITEM_DOC => content = Some(decode_doc(value.bytes()?)?),
+            ITEM_MOVE => content = Some(ItemContent::Move(Box::new(decode_move(value.bytes()?)?))),
+            _ => { /* unknown field */ }
+        }
+    }
+    let content = content.ok_or(Error::UnexpectedValue)?;
+    if origin.is_some() || right_origin.is_some() {
+        // parent of an item with origins is resolved from its neighbors during integration
+        parent = TypePtr::Unknown;
+        parent_sub = None;
+    } else if let TypePtr::Unknown = parent {
+        return Err(Error::UnexpectedValue);
+    }
+    Ok(Item::new(
+        id,
+        None,
+        origin,
+        None,
+        right_origin,
+        parent,
+        parent_sub,
+        content,
+    ))
+}
+
+fn decode_doc(buf: &[u8]) -> Result<ItemContent, Error> {
+    let mut guid = None;
+    let mut opts = Any::Null;
+    for field in Fields::new(buf) {
+        let (field, value) = field?;
+        match field {
+            DOC_GUID => guid = Some(value.string()?),
+            DOC_OPTIONS => opts = decode_any(value.bytes()?, 0)?,
+            _ => { /* unknown field */ }
+        }
+    }
+    let guid = guid.ok_or(Error::UnexpectedValue)?;
+    let mut options = Options::from_any(guid.into(), &opts);
+    options.should_load = options.should_load || options.auto_load;
+    Ok(ItemContent::Doc(None, Doc::with_options(options)))
+}
+
+fn decode_move(buf: &[u8]) -> Result<Move, Error> {
+    let mut start = None;
+    let mut end = None;
+    let mut start_assoc = Assoc::Before;
+    let mut end_assoc = Assoc::Before;
+    let mut priority = 0;
+    for field in Fields::new(buf) {
+        let (field, value) = field?;
+        match field {
+            MOVE_START => start = Some(decode_id(value.bytes()?)?),
+            MOVE_END => end = Some(decode_id(value.bytes()?)?),
+            MOVE_START_AFTER => start_assoc = assoc(value.bool()?),
+            MOVE_END_AFTER => end_assoc = assoc(value.bool()?),
+            MOVE_PRIORITY => {
+                priority =
+                    i32::try_from(unzigzag(value.varint()?)).map_err(|_| Error::UnexpectedValue)?
+            }
+            _ => { /* unknown field */ }
+        }
+    }
+    let start = start.ok_or(Error::UnexpectedValue)?;
+    let end = end.unwrap_or(start);
+    Ok(Move::new(
+        StickyIndex::new(IndexScope::Relative(start), start_assoc),
+        StickyIndex::new(IndexScope::Relative(end), end_assoc),
+        priority,
+    ))
+}
+
+fn decode_type_ref(buf: &[u8]) -> Result<TypeRef, Error> {
+    let mut kind = None;
+    let mut name = None;
+    let mut quote_start = None;
+    let mut quote_end = None;
+    let mut start_after = false;
+    let mut end_after = false;
+    for field in Fields::new(buf) {
+        let (field, value) = field?;
+        match field {
+            TYPE_KIND => kind = Some(value.uint32()?),
+            TYPE_NAME => name = Some(value.string()?),
+            TYPE_QUOTE_START => quote_start = Some(decode_id(value.bytes()?)?),
+            TYPE_QUOTE_END => quote_end = Some(decode_id(value.bytes()?)?),
+            TYPE_START_AFTER => start_after = value.bool()?,
+            TYPE_END_AFTER => end_after = value.bool()?,
+            _ => { /* unknown field */ }
+        }
+    }
+    let kind = u8::try_from(kind.unwrap_or_default()).map_err(|_| Error::UnexpectedValue)?;
+    let type_ref = match kind {
+        TYPE_REFS_ARRAY => TypeRef::Array,
+        TYPE_REFS_MAP => TypeRef::Map,
+        TYPE_REFS_TEXT => TypeRef::Text,
+        TYPE_REFS_XML_ELEMENT => TypeRef::XmlElement(name.ok_or(Error::UnexpectedValue)?.into()),
+        TYPE_REFS_XML_FRAGMENT => TypeRef::XmlFragment,
+        TYPE_REFS_XML_HOOK => TypeRef::XmlHook,
+        TYPE_REFS_XML_TEXT => TypeRef::XmlText,
+        TYPE_REFS_DOC => TypeRef::SubDoc,
+        #[cfg(feature = "weak")]
+        TYPE_REFS_WEAK => {
+            let start = quote_start.ok_or(Error::UnexpectedValue)?;
+            let end = quote_end.unwrap_or(start);
+            TypeRef::WeakLink(Arc::new(LinkSource::new(
+                StickyIndex::from_id(start, assoc(start_after)),
+                StickyIndex::from_id(end, assoc(end_after)),
+            )))
+        }
+        TYPE_REFS_COUNTER => TypeRef::Counter,
+        TYPE_REFS_GSET => TypeRef::GSet,
+        TYPE_REFS_TWO_PHASE_SET => TypeRef::TwoPhaseSet,
+        TYPE_REFS_FIXED => TypeRef::Fixed,
+        TYPE_REFS_UNDEFINED => TypeRef::Undefined,
+        _ => return Err(Error::UnexpectedValue),
+    };
+    #[cfg(not(feature = "weak"))]
+    let _ = (quote_start, quote_end, start_after, end_after);
+    Ok(type_ref)
+}
+
+fn assoc(after: bool) -> Assoc {
+    if after {
+        Assoc::After
+    } else {
+        Assoc::Before
+    }
+}
+
+fn decode_any(buf: &[u8], depth: usize) -> Result<Any, Error> {
+    let max_depth = DecodeLimits::default().max_depth;
+    if depth > max_depth {
+        return Err(Error::DepthLimitExceeded(max_depth));
+    }
+    let mut any = None;
+    for field in Fields::new(buf) {
+        let (field, value) = field?;
+        any = Some(match field {
+            ANY_NULL => Any::Null,
+            ANY_UNDEFINED => Any::Undefined,
+            ANY_BOOL => Any::Bool(value.bool()?),
+            ANY_NUMBER => Any::Number(value.double()?),
+            ANY_BIG_INT => Any::BigInt(unzigzag(value.varint()?)),
+            ANY_STRING => Any::String(value.string()?.into()),
+            ANY_BUFFER => Any::Buffer(value.bytes()?.into()),
+            ANY_ARRAY => {
+                let mut values = Vec::new();
+                for field in Fields::new(value.bytes()?) {
+                    let (field, value) = field?;
+                    if field == VALUES {
+                        values.push(decode_any(value.bytes()?, depth + 1)?);
+                    }
+                }
+                Any::Array(values.into())
+            }
+            ANY_MAP => {
+                let mut entries = HashMap::new();
+                for field in Fields::new(value.bytes()?) {
+                    let (field, value) = field?;
+                    if field != ANY_MAP_ENTRIES {
+                        continue;
+                    }
+                    let mut key = "";
+                    let mut entry = Any::Null;
+                    for field in Fields::new(value.bytes()?) {
+                        let (field, value) = field?;
+                        match field {
+                            MAP_ENTRY_KEY => key = value.string()?,
+                            MAP_ENTRY_VALUE => entry = decode_any(value.bytes()?, depth + 1)?,
+                            _ => { /* unknown field */ }
+                        }
+                    }
+                    entries.insert(key.to_owned(), entry);
+                }
+                Any::Map(Arc::new(entries))
+            }
+            _ => continue,
+        });
+    }
+    any.ok_or(Error::UnexpectedValue)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::encoding::read::Error;
+    use crate::encoding::write::Write;
+    use crate::updates::decoder::Decode;
+    use crate::updates::encoder::Encode;
+    use crate::{
+        Any, Array, DeleteSet, Doc, Map, MapPrelim, ReadTxn, StateVector, Text, Transact, Update,
+        WriteTxn, XmlElementPrelim, XmlFragment, XmlTextPrelim, ID,
+    };
+    use std::collections::HashMap;
+    use std::iter::FromIterator;
+
+    #[test]
+    fn proto_roundtrip() {
+        let doc = Doc::with_client_id(1);
+        let text = doc.get_or_insert_text("text");
+        let array = doc.get_or_insert_array("array");
+        let map = doc.get_or_insert_map("map");
+        {
+            let mut txn = doc.transact_mut();
+            let xml = txn.get_or_insert_xml_fragment("xml");
+            text.push(&mut txn, "hello world");
+            text.format(
+                &mut txn,
+                0,
+                5,
+                HashMap::from([("bold".into(), Any::Bool(true))]),
+            );
+            array.insert_range(&mut txn, 0, [1.5, -2.0]);
+            array.push_back(&mut txn, vec![1u8, 2, 3]);
+            map.insert(&mut txn, "nested", MapPrelim::from([("a", -10)]));
+            map.insert(&mut txn, "doc", Doc::new());
+            let p = xml.push_back(&mut txn, XmlElementPrelim::empty("p"));
+            p.push_back(&mut txn, XmlTextPrelim::new("content"));
+            text.remove_range(&mut txn, 3, 4);
+        }
+        let v1 = doc
+            .transact()
+            .encode_state_as_update_v1(&StateVector::default());
+        let update = Update::decode_v1(&v1).unwrap();
+        let proto = update.encode_proto();
+        let decoded = Update::decode_proto(&proto).unwrap();
+        assert!(decoded == update);
+
+        // top level message is a sequence of `ClientBlocks` followed by a `DeleteSet`
+        assert_eq!(proto[0], 1 << 3 | 2);
+
+        // unknown fields are skipped
+        let mut extended = proto.clone();
+        extended.extend_from_slice(&[15 << 3, 1, 15 << 3 | 2, 2, 0xff, 0xff]);
+        assert!(Update::decode_proto(&extended).unwrap() == decoded);
+
+        assert!(Update::decode_proto(&proto[..proto.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn proto_state_vector_and_delete_set() {
+        let sv = StateVector::from_iter([(1, 10), (2, 3), (u64::MAX, u32::MAX)]);
+        assert_eq!(StateVector::decode_proto(&sv.encode_proto()).unwrap(), sv);
+
+        let mut ds = DeleteSet::new();
+        ds.insert(ID::new(1, 0), 2);
+        ds.insert(ID::new(1, 5), 3);
+        ds.insert(ID::new(2, 1), 1);
+        assert!(DeleteSet::decode_proto(&ds.encode_proto()).unwrap() == ds);
+    }
+
+    fn message(field: u64, body: &[u8]) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.write_var(field << 3 | 2);
+        buf.write_buf(body);
+        buf
+    }
+
+    fn update_with_item(item: &[u8]) -> Vec<u8> {
+        let block = message(1, item);
+        let client = message(3, &block);
+        message(1, &client)
+    }
+
+    #[test]
+    fn proto_malformed() {
+        // string item without origins nor parent
+        let item = message(9, b"a");
+        assert!(Update::decode_proto(&update_with_item(&item)).is_err());
+
+        // the same item with a root parent
+        let item = [message(3, b"text"), item].concat();
+        assert!(Update::decode_proto(&update_with_item(&item)).is_ok());
+
+        // embedded `Any` value nested deeper than the decoding limit
+        let mut any = vec![1 << 3, 1];
+        for _ in 0..200 {
+            any = message(8, &message(1, &any));
+        }
+        let item = [message(3, b"text"), message(10, &any)].concat();
+        assert!(matches!(
+            Update::decode_proto(&update_with_item(&item)),
+            Err(Error::DepthLimitExceeded(_))
+        ));
+    }
+}
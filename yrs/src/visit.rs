@@ -0,0 +1,337 @@
+use crate::branch::Branch;
+use crate::types::{Path, PathSegment};
+use crate::{
+    Any, Array, ArrayRef, Doc, Map, MapRef, Out, ReadTxn, TextRef, XmlElementRef, XmlFragment,
+    XmlFragmentRef, XmlOut, XmlTextRef,
+};
+use std::sync::Arc;
+
+/// Visitor over the structure of a document, driven by [ReadTxn::visit]. It can be used as
+/// a foundation for exporters, validators or search indexers, which need to traverse all shared
+/// collections of a document without writing their own recursion.
+///
+/// Every method receives a `path` to the visited node, starting with a name of the root-level
+/// collection it belongs to. All methods have default no-op implementations, so implementors
+/// only need to override the ones they're interested in. `enter_*` methods return a flag, which
+/// determines if children of a visited collection should be traversed as well - when `false`
+/// is returned, a corresponding `leave_*` method won't be called.
+///
+/// Map entries and root-level collections are visited in the order of their keys.
+///
+/// # Example
+///
+/// ```rust
+/// use yrs::types::Path;
+/// use yrs::visit::DocVisitor;
+/// use yrs::{Any, Array, Doc, Map, MapPrelim, ReadTxn, Transact};
+///
+/// #[derive(Default)]
+/// struct Counter(usize);
+///
+/// impl DocVisitor for Counter {
+///     fn visit_any(&mut self, _path: &Path, _value: &Any) {
+///         self.0 += 1;
+///     }
+/// }
+///
+/// let doc = Doc::new();
+/// let array = doc.get_or_insert_array("array");
+/// let mut txn = doc.transact_mut();
+/// array.insert_range(&mut txn, 0, [1, 2]);
+/// array.push_back(&mut txn, MapPrelim::from([("key", "value")]));
+///
+/// let mut counter = Counter::default();
+/// txn.visit(&mut counter);
+/// assert_eq!(counter.0, 3);
+/// ```
+#[allow(unused_variables)]
+pub trait DocVisitor {
+    /// Called when entering a [MapRef].
+    fn enter_map(&mut self, path: &Path, map: &MapRef) -> bool {
+        true
+    }
+
+    /// Called after all entries of a [MapRef] have been visited.
+    fn leave_map(&mut self, path: &Path, map: &MapRef) {}
+
+    /// Called when entering an [ArrayRef].
+    fn enter_array(&mut self, path: &Path, array: &ArrayRef) -> bool {
+        true
+    }
+
+    /// Called after all elements of an [ArrayRef] have been visited.
+    fn leave_array(&mut self, path: &Path, array: &ArrayRef) {}
+
+    /// Called when visiting a [TextRef].
+    fn visit_text(&mut self, path: &Path, text: &TextRef) {}
+
+    /// Called when entering a [XmlFragmentRef].
+    fn enter_xml_fragment(&mut self, path: &Path, fragment: &XmlFragmentRef) -> bool {
+        true
+    }
+
+    /// Called after all children of a [XmlFragmentRef] have been visited.
+    fn leave_xml_fragment(&mut self, path: &Path, fragment: &XmlFragmentRef) {}
+
+    /// Called when entering a [XmlElementRef].
+    fn enter_xml_element(&mut self, path: &Path, element: &XmlElementRef) -> bool {
+        true
+    }
+
+    /// Called after all children of a [XmlElementRef] have been visited.
+    fn leave_xml_element(&mut self, path: &Path, element: &XmlElementRef) {}
+
+    /// Called when visiting a [XmlTextRef].
+    fn visit_xml_text(&mut self, path: &Path, text: &XmlTextRef) {}
+
+    /// Called when entering a collection which type is not known, i.e. a root-level type that
+    /// has not been defined locally. Its map entries are visited first, then its sequence values.
+    fn enter_undefined(&mut self, path: &Path, branch: &Branch) -> bool {
+        true
+    }
+
+    /// Called after all children of a collection of unknown type have been visited.
+    fn leave_undefined(&mut self, path: &Path, branch: &Branch) {}
+
+    /// Called when visiting a leaf [Any] value.
+    fn visit_any(&mut self, path: &Path, value: &Any) {}
+
+    /// Called when visiting a sub-document.
+    fn visit_doc(&mut self, path: &Path, doc: &Doc) {}
+
+    /// Called when visiting a weak link.
+    #[cfg(feature = "weak")]
+    fn visit_weak_link(&mut self, path: &Path, link: &crate::WeakRef<crate::branch::BranchPtr>) {}
+}
+
+pub(crate) fn visit_doc<T: ReadTxn, V: DocVisitor>(txn: &T, visitor: &mut V) {
+    let mut roots: Vec<_> = txn.root_refs().collect();
+    roots.sort_by(|a, b| a.0.cmp(b.0));
+    let mut path = Path::new();
+    for (name, value) in roots {
+        path.push_back(PathSegment::Key(name.into()));
+        visit_out(txn, &mut path, &value, visitor);
+        path.pop_back();
+    }
+}
+
+pub(crate) fn visit_out<T: ReadTxn, V: DocVisitor>(
+    txn: &T,
+    path: &mut Path,
+    value: &Out,
+    visitor: &mut V,
+) {
+    match value {
+        Out::Any(any) => visitor.visit_any(path, any),
+        Out::YText(text) => visitor.visit_text(path, text),
+        Out::YXmlText(text) => visitor.visit_xml_text(path, text),
+        Out::YDoc(doc) => visitor.visit_doc(path, doc),
+        #[cfg(feature = "weak")]
+        Out::YWeakLink(link) => visitor.visit_weak_link(path, link),
+        Out::YMap(map) => {
+            if visitor.enter_map(path, map) {
+                let mut entries: Vec<_> = map.iter(txn).collect();
+                entries.sort_by(|a, b| a.0.cmp(b.0));
+                visit_entries(txn, path, entries, visitor);
+                visitor.leave_map(path, map);
+            }
+        }
+        Out::YArray(array) => {
+            if visitor.enter_array(path, array) {
+                visit_values(txn, path, array.iter(txn), visitor);
+                visitor.leave_array(path, array);
+            }
+        }
+        Out::YXmlFragment(fragment) => {
+            if visitor.enter_xml_fragment(path, fragment) {
+                visit_xml_children(txn, path, fragment, visitor);
+                visitor.leave_xml_fragment(path, fragment);
+            }
+        }
+        Out::YXmlElement(element) => {
+            if visitor.enter_xml_element(path, element) {
+                visit_xml_children(txn, path, element, visitor);
+                visitor.leave_xml_element(path, element);
+            }
+        }
+        Out::UndefinedRef(branch) => {
+            if visitor.enter_undefined(path, branch) {
+                let mut entries: Vec<_> = branch.entries(txn).collect();
+                entries.sort_by(|a, b| a.0.cmp(b.0));
+                visit_entries(txn, path, entries, visitor);
+                visit_values(txn, path, branch.iter(txn), visitor);
+                visitor.leave_undefined(path, branch);
+            }
+        }
+    }
+}
+
+fn visit_entries<T: ReadTxn, V: DocVisitor>(
+    txn: &T,
+    path: &mut Path,
+    entries: Vec<(&str, Out)>,
+    visitor: &mut V,
+) {
+    for (key, value) in entries {
+        path.push_back(PathSegment::Key(Arc::from(key)));
+        visit_out(txn, path, &value, visitor);
+        path.pop_back();
+    }
+}
+
+fn visit_values<T, I, V>(txn: &T, path: &mut Path, values: I, visitor: &mut V)
+where
+    T: ReadTxn,
+    I: Iterator<Item = Out>,
+    V: DocVisitor,
+{
+    for (index, value) in values.enumerate() {
+        path.push_back(PathSegment::Index(index as u32));
+        visit_out(txn, path, &value, visitor);
+        path.pop_back();
+    }
+}
+
+fn visit_xml_children<T, X, V>(txn: &T, path: &mut Path, node: &X, visitor: &mut V)
+where
+    T: ReadTxn,
+    X: XmlFragment,
+    V: DocVisitor,
+{
+    let children = node.children(txn).map(|child| match child {
+        XmlOut::Element(n) => Out::YXmlElement(n),
+        XmlOut::Fragment(n) => Out::YXmlFragment(n),
+        XmlOut::Text(n) => Out::YXmlText(n),
+    });
+    visit_values(txn, path, children, visitor);
+}
+
+#[cfg(test)]
+mod test {
+    use crate::branch::Branch;
+    use crate::types::{Path, PathSegment};
+    use crate::updates::decoder::Decode;
+    use crate::visit::DocVisitor;
+    use crate::{
+        Any, Array, ArrayPrelim, ArrayRef, Doc, Map, MapPrelim, MapRef, ReadTxn, StateVector,
+        TextPrelim, TextRef, Transact, Update, XmlElementPrelim, XmlElementRef, XmlFragment,
+        XmlFragmentRef, XmlTextPrelim, XmlTextRef,
+    };
+
+    #[derive(Default)]
+    struct Trace(Vec<String>);
+
+    impl Trace {
+        fn push(&mut self, path: &Path, event: &str) {
+            let path: Vec<_> = path
+                .iter()
+                .map(|segment| match segment {
+                    PathSegment::Key(key) => key.to_string(),
+                    PathSegment::Index(index) => index.to_string(),
+                })
+                .collect();
+            self.0.push(format!("{} {}", path.join("/"), event));
+        }
+    }
+
+    impl DocVisitor for Trace {
+        fn enter_map(&mut self, path: &Path, _map: &MapRef) -> bool {
+            self.push(path, "enter map");
+            true
+        }
+        fn leave_map(&mut self, path: &Path, _map: &MapRef) {
+            self.push(path, "leave map");
+        }
+        fn enter_array(&mut self, path: &Path, _array: &ArrayRef) -> bool {
+            self.push(path, "enter array");
+            path.len() < 3 // skip contents of nested arrays
+        }
+        fn leave_array(&mut self, path: &Path, _array: &ArrayRef) {
+            self.push(path, "leave array");
+        }
+        fn visit_text(&mut self, path: &Path, _text: &TextRef) {
+            self.push(path, "text");
+        }
+        fn enter_xml_fragment(&mut self, path: &Path, _fragment: &XmlFragmentRef) -> bool {
+            self.push(path, "enter fragment");
+            true
+        }
+        fn leave_xml_fragment(&mut self, path: &Path, _fragment: &XmlFragmentRef) {
+            self.push(path, "leave fragment");
+        }
+        fn enter_xml_element(&mut self, path: &Path, _element: &XmlElementRef) -> bool {
+            self.push(path, "enter element");
+            true
+        }
+        fn leave_xml_element(&mut self, path: &Path, _element: &XmlElementRef) {
+            self.push(path, "leave element");
+        }
+        fn visit_xml_text(&mut self, path: &Path, _text: &XmlTextRef) {
+            self.push(path, "xml text");
+        }
+        fn enter_undefined(&mut self, path: &Path, _branch: &Branch) -> bool {
+            self.push(path, "enter undefined");
+            true
+        }
+        fn leave_undefined(&mut self, path: &Path, _branch: &Branch) {
+            self.push(path, "leave undefined");
+        }
+        fn visit_any(&mut self, path: &Path, value: &Any) {
+            self.push(path, &value.to_string());
+        }
+    }
+
+    #[test]
+    fn visit_doc_structure() {
+        let doc = Doc::with_client_id(1);
+        let map = doc.get_or_insert_map("map");
+        let xml = doc.get_or_insert_xml_fragment("xml");
+        {
+            let mut txn = doc.transact_mut();
+            map.insert(&mut txn, "b", TextPrelim::new("text"));
+            let array = map.insert(&mut txn, "a", ArrayPrelim::default());
+            array.push_back(&mut txn, 1);
+            array.push_back(&mut txn, ArrayPrelim::from([2, 3]));
+            array.push_back(&mut txn, MapPrelim::from([("c", true)]));
+            let div = xml.push_back(&mut txn, XmlElementPrelim::empty("div"));
+            div.push_back(&mut txn, XmlTextPrelim::new("hello"));
+        }
+
+        let mut trace = Trace::default();
+        doc.transact().visit(&mut trace);
+        assert_eq!(
+            trace.0,
+            vec![
+                "map enter map",
+                "map/a enter array",
+                "map/a/0 1",
+                "map/a/1 enter array",
+                "map/a/2 enter map",
+                "map/a/2/c true",
+                "map/a/2 leave map",
+                "map/a leave array",
+                "map/b text",
+                "map leave map",
+                "xml enter fragment",
+                "xml/0 enter element",
+                "xml/0/0 xml text",
+                "xml/0 leave element",
+                "xml leave fragment",
+            ]
+        );
+
+        // root types which are not defined locally are visited as well
+        let remote = Doc::with_client_id(2);
+        let update = doc
+            .transact()
+            .encode_state_as_update_v1(&StateVector::default());
+        remote
+            .transact_mut()
+            .apply_update(Update::decode_v1(&update).unwrap());
+        let mut trace = Trace::default();
+        remote.transact().visit(&mut trace);
+        assert_eq!(trace.0[0], "map enter undefined");
+        assert_eq!(trace.0[1], "map/a enter array");
+        assert_eq!(trace.0.len(), 15);
+    }
+}
@@ -0,0 +1,150 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use smallvec::SmallVec;
+
+use crate::block::ItemContent;
+use crate::branch::{Branch, BranchPtr};
+use crate::iter::TxnIterator;
+use crate::transaction::TransactionMut;
+use crate::types::TypeRef;
+use crate::{Any, DeleteSet, Out, ID};
+
+/// Index of XML elements by the value of a designated attribute (eg. `id`). It's enabled with
+/// [Doc::set_xml_id_index] and kept up to date as transactions are committed, so that elements
+/// can be found with [XmlFragment::get_by_id] without walking the whole document.
+///
+/// [Doc::set_xml_id_index]: crate::Doc::set_xml_id_index
+/// [XmlFragment::get_by_id]: crate::XmlFragment::get_by_id
+#[derive(Debug)]
+pub(crate) struct XmlIdIndex {
+    attr: Arc<str>,
+    /// Elements grouped by the value of their attribute. The same value can be used by more than
+    /// one element, ie. when it was concurrently assigned by different peers.
+    by_id: HashMap<Arc<str>, SmallVec<[BranchPtr; 1]>>,
+    /// Current attribute value of every indexed element.
+    by_element: HashMap<BranchPtr, Arc<str>>,
+}
+
+impl XmlIdIndex {
+    pub fn new(attr: Arc<str>) -> Self {
+        XmlIdIndex {
+            attr,
+            by_id: HashMap::new(),
+            by_element: HashMap::new(),
+        }
+    }
+
+    pub fn attr(&self) -> &Arc<str> {
+        &self.attr
+    }
+
+    /// Returns all elements which had their indexed attribute set to a given `id`.
+    pub fn get(&self, id: &str) -> &[BranchPtr] {
+        match self.by_id.get(id) {
+            Some(elements) => elements.as_slice(),
+            None => &[],
+        }
+    }
+
+    /// Indexes all XML elements found within a given `branch` and its descendants.
+    pub fn index_tree(&mut self, branch: BranchPtr) {
+        let mut stack = vec![branch];
+        while let Some(branch) = stack.pop() {
+            self.update(branch);
+            let children = branch
+                .map
+                .values()
+                .copied()
+                .chain(std::iter::successors(branch.start, |item| item.right));
+            for item in children {
+                if let ItemContent::Type(inner) = &item.content {
+                    if !item.is_deleted() {
+                        stack.push(BranchPtr::from(inner));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Updates the index with changes made by a given transaction.
+    pub fn apply(&mut self, txn: &TransactionMut) {
+        let mut insertions = DeleteSet::new();
+        for (client, &clock) in txn.after_state().iter() {
+            let before = txn.before_state().get(client);
+            if clock > before {
+                insertions.insert(ID::new(*client, before), clock - before);
+            }
+        }
+        let mut inserted = insertions.deleted_blocks();
+        while let Some(slice) = inserted.next(txn) {
+            if let Some(item) = slice.as_item() {
+                if item.is_deleted() {
+                    continue;
+                }
+                if let ItemContent::Type(inner) = &item.content {
+                    self.update(BranchPtr::from(inner));
+                }
+                if item.parent_sub.as_deref() == Some(&self.attr) {
+                    if let Some(parent) = item.parent.as_branch() {
+                        self.update(*parent);
+                    }
+                }
+            }
+        }
+
+        let mut deleted = txn.delete_set().deleted_blocks();
+        while let Some(slice) = deleted.next(txn) {
+            if let Some(item) = slice.as_item() {
+                if let ItemContent::Type(inner) = &item.content {
+                    self.remove(BranchPtr::from(inner));
+                }
+                if item.parent_sub.as_deref() == Some(&self.attr) {
+                    if let Some(parent) = item.parent.as_branch() {
+                        self.update(*parent);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Reads a current value of an indexed attribute of a given `branch` and updates its entry.
+    fn update(&mut self, branch: BranchPtr) {
+        let id = match Self::attribute_value(&branch, &self.attr) {
+            Some(id) => id,
+            None => return self.remove(branch),
+        };
+        if self.by_element.get(&branch) == Some(&id) {
+            return;
+        }
+        self.remove(branch);
+        self.by_id.entry(id.clone()).or_default().push(branch);
+        self.by_element.insert(branch, id);
+    }
+
+    fn remove(&mut self, branch: BranchPtr) {
+        if let Some(id) = self.by_element.remove(&branch) {
+            if let Some(elements) = self.by_id.get_mut(&id) {
+                elements.retain(|e| *e != branch);
+                if elements.is_empty() {
+                    self.by_id.remove(&id);
+                }
+            }
+        }
+    }
+
+    fn attribute_value(branch: &Branch, attr: &str) -> Option<Arc<str>> {
+        if !matches!(branch.type_ref(), TypeRef::XmlElement(_)) || branch.is_deleted() {
+            return None;
+        }
+        let item = branch.map.get(attr)?;
+        if item.is_deleted() {
+            return None;
+        }
+        match item.content.get_last()? {
+            Out::Any(Any::String(id)) => Some(id),
+            Out::Any(any) => Some(any.to_string().into()),
+            _ => None,
+        }
+    }
+}
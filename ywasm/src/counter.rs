@@ -0,0 +1,45 @@
+use crate::js::Js;
+use wasm_bindgen::prelude::wasm_bindgen;
+use wasm_bindgen::JsValue;
+use yrs::{CounterEvent, TransactionMut};
+
+/// Event generated by `observeDeep` method of a type containing a nested counter. Emitted during
+/// transaction commit phase.
+#[wasm_bindgen]
+pub struct YCounterEvent {
+    inner: &'static CounterEvent,
+    txn: &'static TransactionMut<'static>,
+    origin: JsValue,
+}
+
+#[wasm_bindgen]
+impl YCounterEvent {
+    pub(crate) fn new<'doc>(event: &CounterEvent, txn: &TransactionMut<'doc>) -> Self {
+        let inner: &'static CounterEvent = unsafe { std::mem::transmute(event) };
+        let txn: &'static TransactionMut<'static> = unsafe { std::mem::transmute(txn) };
+        let origin = if let Some(origin) = txn.origin() {
+            Js::from(origin).into()
+        } else {
+            JsValue::UNDEFINED
+        };
+        YCounterEvent { inner, txn, origin }
+    }
+
+    #[wasm_bindgen(getter, js_name = origin)]
+    pub fn origin(&self) -> JsValue {
+        self.origin.clone()
+    }
+
+    /// Returns an array of keys and indexes creating a path from root type down to current instance
+    /// of shared type.
+    #[wasm_bindgen]
+    pub fn path(&self) -> JsValue {
+        crate::js::convert::path_into_js(self.inner.path())
+    }
+
+    /// Returns a total change of a counter value made within bounds of current transaction.
+    #[wasm_bindgen(getter)]
+    pub fn delta(&self) -> f64 {
+        self.inner.delta(self.txn)
+    }
+}
@@ -542,6 +542,7 @@ impl Callback for js_sys::Function {}
 
 pub(crate) mod convert {
     use crate::array::YArrayEvent;
+    use crate::counter::YCounterEvent;
     use crate::js::errors::INVALID_DELTA;
     use crate::js::Js;
     use crate::map::YMapEvent;
@@ -689,7 +690,7 @@ pub(crate) mod convert {
 
     pub fn events_into_js(txn: &TransactionMut, e: &Events) -> JsValue {
         let mut array = js_sys::Array::new();
-        let mapped = e.iter().map(|e| {
+        let mapped = e.iter().filter_map(|e| {
             let js: JsValue = match e {
                 Event::Text(e) => YTextEvent::new(e, txn).into(),
                 Event::Map(e) => YMapEvent::new(e, txn).into(),
@@ -697,8 +698,11 @@ pub(crate) mod convert {
                 Event::Weak(e) => YWeakLinkEvent::new(e, txn).into(),
                 Event::XmlFragment(e) => YXmlEvent::new(e, txn).into(),
                 Event::XmlText(e) => YXmlTextEvent::new(e, txn).into(),
+                Event::Counter(e) => YCounterEvent::new(e, txn).into(),
+                // sets are not exposed to JavaScript
+                Event::Set(_) => return None,
             };
-            js
+            Some(js)
         });
         array.extend(mapped);
         array.into()
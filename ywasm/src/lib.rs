@@ -14,6 +14,7 @@ use yrs::{Assoc, ReadTxn, StickyIndex, Transact, TransactionMut, Update};
 mod array;
 mod awareness;
 mod collection;
+mod counter;
 mod doc;
 mod js;
 mod map;
@@ -29,6 +30,7 @@ type Result<T> = std::result::Result<T, JsValue>;
 
 pub use crate::array::YArray as Array;
 pub use crate::array::YArrayEvent as ArrayEvent;
+pub use crate::counter::YCounterEvent as CounterEvent;
 pub use crate::doc::YDoc as Doc;
 use crate::js::Shared;
 pub use crate::map::YMap as Map;
@@ -217,7 +217,12 @@ impl YTransaction {
                     None => JsValue::UNDEFINED,
                     Some(doc) => YDoc(doc).into(),
                 },
-                TypeRef::XmlHook | TypeRef::Undefined => JsValue::UNDEFINED,
+                TypeRef::XmlHook
+                | TypeRef::Counter
+                | TypeRef::GSet
+                | TypeRef::TwoPhaseSet
+                | TypeRef::Fixed
+                | TypeRef::Undefined => JsValue::UNDEFINED,
             },
         })
     }
@@ -43,6 +43,8 @@ impl YUndoManager {
         let mut o = yrs::undo::Options {
             capture_timeout_millis: 500,
             tracked_origins: HashSet::new(),
+            tracked_clients: HashSet::new(),
+            tracked_actors: HashSet::new(),
             capture_transaction: None,
             timestamp: Arc::new(crate::awareness::JsClock),
         };